@@ -188,6 +188,11 @@ class NymTunnelManager @Inject constructor(
 						title = context.getString(R.string.bandwidth_alert),
 						description = context.getString(R.string.low_bandwidth) + " ${alert.v1}",
 					)
+					is BandwidthEvent.LowBandwidth -> notificationService.showNotification(
+						title = context.getString(R.string.bandwidth_alert),
+						description = context.getString(R.string.low_bandwidth) + " ${alert.remaining}",
+					)
+					is BandwidthEvent.ToppedUp -> Timber.i("Bandwidth topped up, new total: ${alert.newTotal}")
 				}
 			}
 			BackendMessage.None -> Unit
@@ -1,5 +1,6 @@
 use std::env::consts::{ARCH, OS};
 use std::path::PathBuf;
+use std::time::Duration;
 
 use anyhow::{anyhow, Result};
 use itertools::Itertools;
@@ -8,8 +9,9 @@ use nym_vpn_proto::{
     is_account_stored_response::Resp as IsAccountStoredResp, nym_vpnd_client::NymVpndClient,
     ConnectRequest, ConnectionStatus, DisconnectRequest, Dns, Empty, EntryNode, ExitNode,
     FetchRawAccountSummaryRequest, GatewayType, HealthCheckRequest, InfoRequest, InfoResponse,
-    IsAccountStoredRequest, ListCountriesRequest, Location, RemoveAccountRequest,
-    SetNetworkRequest, StatusRequest, StatusResponse, StoreAccountRequest, UserAgent,
+    IsAccountStoredRequest, ListCountriesRequest, Location, RegionAffinity, RemoveAccountRequest,
+    SelectionStrategy, SetNetworkRequest, StatusRequest, StatusResponse, StoreAccountRequest,
+    UserAgent,
 };
 use parity_tokio_ipc::Endpoint as IpcEndpoint;
 use serde::{Deserialize, Serialize};
@@ -17,6 +19,7 @@ use tauri::{AppHandle, Manager, PackageInfo};
 use thiserror::Error;
 use time::OffsetDateTime;
 use tokio::sync::mpsc;
+use tokio::time::sleep;
 use tonic::transport::Endpoint as TonicEndpoint;
 use tonic::{transport::Channel, Request};
 use tracing::{debug, error, info, instrument, warn};
@@ -38,6 +41,8 @@ const DEFAULT_SOCKET_PATH: &str = "/var/run/nym-vpn.sock";
 #[cfg(windows)]
 const DEFAULT_SOCKET_PATH: &str = r"\\.\pipe\nym-vpn";
 const DEFAULT_HTTP_ENDPOINT: &str = "http://[::1]:53181";
+const HEALTH_WATCH_MIN_BACKOFF: Duration = Duration::from_secs(1);
+const HEALTH_WATCH_MAX_BACKOFF: Duration = Duration::from_secs(30);
 
 #[derive(Clone, Debug)]
 enum Transport {
@@ -45,7 +50,7 @@ enum Transport {
     Ipc(PathBuf),
 }
 
-#[derive(Serialize, Deserialize, Default, Clone, Debug, TS)]
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug, TS)]
 pub enum VpndStatus {
     Ok,
     #[default]
@@ -228,13 +233,15 @@ impl GrpcClient {
         if let Some(e) = res.error.as_ref() {
             warn!("vpn status error: {}", e.message);
         }
-        let connection_time = res.details.clone().and_then(|d| {
-            d.since.map(|s| {
+        // prefer the daemon's own record of when the connection was established
+        let connection_time = res
+            .connected_since
+            .or_else(|| res.details.clone().and_then(|d| d.since))
+            .map(|s| {
                 OffsetDateTime::from_unix_timestamp(s.seconds)
                     .inspect_err(|e| error!("failed to parse timestamp: {:?}", e))
                     .unwrap_or(OffsetDateTime::now_utc())
-            })
-        });
+            });
 
         let status = res.status();
         vpn_status::update(
@@ -365,6 +372,28 @@ impl GrpcClient {
             min_mixnode_performance: None,
             min_gateway_mixnet_performance: None,
             min_gateway_vpn_performance: None,
+            mixnet_client_startup_timeout_secs: None,
+            sticky_gateways: false,
+            allow_lan: false,
+            excluded_gateways: vec![],
+            key_namespace: None,
+            detect_dns_leaks: false,
+            disable_reconnect_on_network_change: false,
+            key_rotation_interval_secs: None,
+            max_session_duration_secs: None,
+            selection_seed: None,
+            region_affinity: RegionAffinity::Unspecified as i32,
+            selection_strategy: SelectionStrategy::Random as i32,
+            connect_deadline_secs: None,
+            gateway_dns: false,
+            preferred_wg_ports: vec![],
+            routed_destinations: vec![],
+            latency_sample_interval_secs: None,
+            idle_timeout_secs: None,
+            dns_leak_probe_interval_secs: None,
+            directory_cache_ttl_secs: None,
+            proxy: None,
+            disallow_same_country: false,
         });
         let response = vpnd
             .vpn_connect(request)
@@ -548,11 +577,26 @@ impl GrpcClient {
         Ok(countries)
     }
 
-    /// Watch the connection with the grpc server
+    /// Watch the connection with the grpc server, forever.
+    /// While the daemon is unreachable, the health stream is re-established
+    /// with an exponential backoff and the status is reported as `NotOk`
     #[instrument(skip_all)]
-    pub async fn watch(&self, app: &AppHandle) -> Result<()> {
+    pub async fn watch(&self, app: &AppHandle) {
+        let mut backoff = HEALTH_WATCH_MIN_BACKOFF;
+        loop {
+            self.watch_health(app, &mut backoff).await.ok();
+            set_vpnd_status(app, VpndStatus::NotOk).await;
+
+            debug!("vpnd health watch retry in {}s", backoff.as_secs());
+            sleep(backoff).await;
+            backoff = next_health_watch_backoff(backoff);
+        }
+    }
+
+    /// Follow the health stream until it ends, the backoff is reset
+    /// on every received message
+    async fn watch_health(&self, app: &AppHandle, backoff: &mut Duration) -> Result<()> {
         let mut health = self.health().await?;
-        let app_state = app.state::<SharedAppState>();
 
         let request = Request::new(HealthCheckRequest {
             service: VPND_SERVICE.into(),
@@ -565,33 +609,24 @@ impl GrpcClient {
             })?
             .into_inner();
 
-        let (tx, mut rx) = mpsc::channel(32);
-        tokio::spawn(async move {
-            loop {
-                match stream.message().await {
-                    Ok(Some(res)) => {
-                        tx.send(res.status()).await.unwrap();
-                    }
-                    Ok(None) => {
-                        warn!("watch health stream closed by the server");
-                        tx.send(ServingStatus::NotServing).await.unwrap();
-                        return;
-                    }
-                    Err(e) => {
-                        warn!("watch health stream get a grpc error: {}", e);
-                    }
+        loop {
+            match stream.message().await {
+                Ok(Some(res)) => {
+                    *backoff = HEALTH_WATCH_MIN_BACKOFF;
+                    let status = res.status();
+                    debug!("health check status: {:?}", status);
+                    set_vpnd_status(app, status.into()).await;
+                }
+                Ok(None) => {
+                    warn!("watch health stream closed by the server");
+                    return Ok(());
+                }
+                Err(e) => {
+                    warn!("watch health stream get a grpc error: {}", e);
+                    return Err(e.into());
                 }
             }
-        });
-
-        while let Some(status) = rx.recv().await {
-            debug!("health check status: {:?}", status);
-            app.emit_vpnd_status(status.into());
-            let mut state = app_state.lock().await;
-            state.vpnd_status = status.into();
         }
-
-        Ok(())
     }
 
     /// Set the network environment of the daemon.
@@ -621,6 +656,11 @@ impl GrpcClient {
     }
 }
 
+/// Double the health watch backoff, up to `HEALTH_WATCH_MAX_BACKOFF`
+fn next_health_watch_backoff(backoff: Duration) -> Duration {
+    (backoff * 2).min(HEALTH_WATCH_MAX_BACKOFF)
+}
+
 impl From<ServingStatus> for VpndStatus {
     fn from(status: ServingStatus) -> Self {
         match status {
@@ -630,6 +670,14 @@ impl From<ServingStatus> for VpndStatus {
     }
 }
 
+/// Update the daemon status in the app state and notify the frontend
+async fn set_vpnd_status(app: &AppHandle, status: VpndStatus) {
+    app.emit_vpnd_status(status);
+    let app_state = app.state::<SharedAppState>();
+    let mut state = app_state.lock().await;
+    state.vpnd_status = status;
+}
+
 async fn get_channel(socket_path: PathBuf) -> anyhow::Result<Channel> {
     // NOTE the uri here is ignored
     Ok(TonicEndpoint::from_static(DEFAULT_HTTP_ENDPOINT)
@@ -688,3 +736,32 @@ impl TryFrom<&Location> for Country {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn health_watch_backoff_doubles_up_to_the_max() {
+        let mut backoff = HEALTH_WATCH_MIN_BACKOFF;
+        let mut delays = Vec::new();
+        for _ in 0..7 {
+            delays.push(backoff.as_secs());
+            backoff = next_health_watch_backoff(backoff);
+        }
+
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 30, 30]);
+    }
+
+    #[test]
+    fn health_watch_backoff_stays_at_the_max() {
+        assert_eq!(
+            next_health_watch_backoff(HEALTH_WATCH_MAX_BACKOFF),
+            HEALTH_WATCH_MAX_BACKOFF
+        );
+        assert_eq!(
+            next_health_watch_backoff(Duration::from_secs(20)),
+            HEALTH_WATCH_MAX_BACKOFF
+        );
+    }
+}
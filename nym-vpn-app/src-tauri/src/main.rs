@@ -190,11 +190,7 @@ async fn main() -> Result<()> {
             let c_grpc = grpc.clone();
             tokio::spawn(async move {
                 info!("starting vpnd health spy");
-                loop {
-                    c_grpc.watch(&handle).await.ok();
-                    sleep(VPND_RETRY_INTERVAL).await;
-                    debug!("vpnd health spy retry");
-                }
+                c_grpc.watch(&handle).await;
             });
 
             let handle = app.handle().clone();
@@ -95,6 +95,8 @@ impl From<ConnectionStatus> for ConnectionState {
             // this variant means "Not connected, but with an error"
             // so it should be treated as disconnected
             ConnectionStatus::ConnectionFailed => ConnectionState::Disconnected,
+            // traffic bypasses a paused tunnel so it must not be shown as connected
+            ConnectionStatus::Paused => ConnectionState::Disconnected,
         }
     }
 }
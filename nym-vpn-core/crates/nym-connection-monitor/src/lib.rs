@@ -14,6 +14,7 @@ mod error;
 mod icmp_beacon;
 mod mixnet_beacon;
 mod monitor;
+mod quality;
 mod sync_self_ping;
 
 pub mod packet_helpers;
@@ -22,7 +23,8 @@ pub use icmp_beacon::{
     is_icmp_beacon_reply, is_icmp_v6_beacon_reply, IcmpBeaconReply, Icmpv6BeaconReply,
 };
 pub use monitor::{ConnectionMonitorStatus, ConnectionStatusEvent};
-pub use sync_self_ping::self_ping_and_wait;
+pub use quality::ConnectionQualityConfig;
+pub use sync_self_ping::{self_ping_and_wait, SelfPingConfig};
 
 fn create_icmp_beacon_identifier() -> u16 {
     // TODO: use something that is more unique than just process id
@@ -36,6 +38,7 @@ pub struct ConnectionMonitorTask {
     icmp_beacon_identifier: u16,
     connection_event_tx: mpsc::UnboundedSender<monitor::ConnectionStatusEvent>,
     connection_event_rx: mpsc::UnboundedReceiver<monitor::ConnectionStatusEvent>,
+    quality_config: ConnectionQualityConfig,
 }
 
 impl ConnectionMonitorTask {
@@ -46,9 +49,15 @@ impl ConnectionMonitorTask {
             icmp_beacon_identifier,
             connection_event_tx,
             connection_event_rx,
+            quality_config: ConnectionQualityConfig::default(),
         }
     }
 
+    pub fn with_quality_config(mut self, quality_config: ConnectionQualityConfig) -> Self {
+        self.quality_config = quality_config;
+        self
+    }
+
     pub fn event_sender(&self) -> mpsc::UnboundedSender<monitor::ConnectionStatusEvent> {
         self.connection_event_tx.clone()
     }
@@ -69,6 +78,7 @@ impl ConnectionMonitorTask {
         mixnet_beacon::start_mixnet_connection_beacon(
             mixnet_client_sender.clone(),
             our_nym_address,
+            self.connection_event_tx.clone(),
             task_manager.subscribe_named("mixnet_beacon"),
         );
 
@@ -84,6 +94,7 @@ impl ConnectionMonitorTask {
         info!("Setting up connection monitor");
         monitor::start_connection_monitor(
             self.connection_event_rx,
+            self.quality_config,
             task_manager.subscribe_named("connection_monitor"),
         );
     }
@@ -3,6 +3,7 @@
 
 use std::time::Duration;
 
+use futures::channel::mpsc;
 use nym_sdk::{
     mixnet::{InputMessage, MixnetClientSender, MixnetMessageSender, Recipient},
     TaskClient,
@@ -11,20 +12,29 @@ use nym_task::connections::TransmissionLane;
 use tokio::task::JoinHandle;
 use tracing::{debug, error, trace};
 
-use crate::{error::Result, nym_ip_packet_requests_current::request::IpPacketRequest};
+use crate::{
+    error::Result, monitor::ConnectionStatusEvent,
+    nym_ip_packet_requests_current::request::IpPacketRequest,
+};
 
 const MIXNET_SELF_PING_INTERVAL: Duration = Duration::from_millis(1000);
 
 struct MixnetConnectionBeacon {
     mixnet_client_sender: MixnetClientSender,
     our_address: Recipient,
+    connection_event_tx: mpsc::UnboundedSender<ConnectionStatusEvent>,
 }
 
 impl MixnetConnectionBeacon {
-    fn new(mixnet_client_sender: MixnetClientSender, our_address: Recipient) -> Self {
+    fn new(
+        mixnet_client_sender: MixnetClientSender,
+        our_address: Recipient,
+        connection_event_tx: mpsc::UnboundedSender<ConnectionStatusEvent>,
+    ) -> Self {
         MixnetConnectionBeacon {
             mixnet_client_sender,
             our_address,
+            connection_event_tx,
         }
     }
 
@@ -45,14 +55,20 @@ impl MixnetConnectionBeacon {
                     break;
                 }
                 _ = ping_interval.tick() => {
-                    let _ping_id = match self.send_mixnet_self_ping().await {
+                    let request_id = match self.send_mixnet_self_ping().await {
                         Ok(id) => id,
                         Err(err) => {
                             error!("Failed to send mixnet self ping: {err}");
                             continue;
                         }
                     };
-                    // TODO: store ping_id to be able to monitor or ping timeouts
+                    // Let the monitor time the reply, to estimate the connection quality
+                    if let Err(err) = self
+                        .connection_event_tx
+                        .unbounded_send(ConnectionStatusEvent::MixnetSelfPingSent { request_id })
+                    {
+                        error!("Failed to send connection status event: {err}");
+                    }
                 }
             }
         }
@@ -77,10 +93,12 @@ pub fn create_self_ping(our_address: Recipient) -> (InputMessage, u64) {
 pub fn start_mixnet_connection_beacon(
     mixnet_client_sender: MixnetClientSender,
     our_address: Recipient,
+    connection_event_tx: mpsc::UnboundedSender<ConnectionStatusEvent>,
     shutdown_listener: TaskClient,
 ) -> JoinHandle<Result<()>> {
     debug!("Creating mixnet connection beacon");
-    let beacon = MixnetConnectionBeacon::new(mixnet_client_sender, our_address);
+    let beacon =
+        MixnetConnectionBeacon::new(mixnet_client_sender, our_address, connection_event_tx);
     tokio::spawn(async move {
         beacon.run(shutdown_listener).await.inspect_err(|err| {
             error!("Mixnet connection beacon error: {err}");
@@ -11,7 +11,10 @@ use nym_sdk::TaskClient;
 use tokio::task::JoinHandle;
 use tracing::{debug, error, trace};
 
-use crate::error::Result;
+use crate::{
+    error::Result,
+    quality::{ConnectionQualityConfig, PingTracker, RollingLatency},
+};
 
 const CONNECTION_MONITOR_REPORT_INTERVAL: Duration = Duration::from_secs(5);
 
@@ -21,7 +24,8 @@ const PING_REPLY_EXPIRY: Duration = Duration::from_secs(5);
 // Events that are reported by other tasks to the connection monitor
 #[derive(Debug)]
 pub enum ConnectionStatusEvent {
-    MixnetSelfPing,
+    MixnetSelfPingSent { request_id: u64 },
+    MixnetSelfPing { request_id: u64 },
     Icmpv4IprTunDevicePingReply,
     Icmpv6IprTunDevicePingReply,
     Icmpv4IprExternalPingReply,
@@ -98,6 +102,9 @@ impl ConnectionStats {
 struct ConnectionMonitor {
     connection_event_rx: mpsc::UnboundedReceiver<ConnectionStatusEvent>,
     stats: ConnectionStats,
+    self_pings: PingTracker,
+    quality_config: ConnectionQualityConfig,
+    latency: RollingLatency,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -128,18 +135,32 @@ struct ConnectivityState {
 }
 
 impl ConnectionMonitor {
-    fn new(connection_event_rx: mpsc::UnboundedReceiver<ConnectionStatusEvent>) -> Self {
+    fn new(
+        connection_event_rx: mpsc::UnboundedReceiver<ConnectionStatusEvent>,
+        quality_config: ConnectionQualityConfig,
+    ) -> Self {
         ConnectionMonitor {
             connection_event_rx,
             stats: ConnectionStats::default(),
+            self_pings: PingTracker::new(quality_config.sampling_window, PING_REPLY_EXPIRY),
+            quality_config,
+            latency: RollingLatency::default(),
         }
     }
 
     fn record_event(&mut self, event: &ConnectionStatusEvent) {
         match event {
-            ConnectionStatusEvent::MixnetSelfPing => {
+            ConnectionStatusEvent::MixnetSelfPingSent { request_id } => {
+                trace!("Sent self ping {request_id}");
+                self.self_pings.record_sent(*request_id, Instant::now());
+            }
+            ConnectionStatusEvent::MixnetSelfPing { request_id } => {
                 trace!("Received self ping event");
                 self.stats.latest_self_ping = Some(Instant::now());
+                if let Some(rtt) = self.self_pings.record_reply(*request_id, Instant::now()) {
+                    trace!("Self ping {request_id} round trip: {}ms", rtt.as_millis());
+                    self.latency.record(rtt);
+                }
             }
             ConnectionStatusEvent::Icmpv4IprTunDevicePingReply => {
                 trace!("Received IPR tun device ping reply event");
@@ -166,6 +187,10 @@ impl ConnectionMonitor {
         // Reset so that we don't send a report immediately before we even have a change for any
         // self pings to be sent and received
         report_interval.reset();
+        let mut quality_interval = tokio::time::interval(self.quality_config.report_interval);
+        quality_interval.reset();
+        let mut latency_interval = tokio::time::interval(self.quality_config.latency_interval);
+        latency_interval.reset();
 
         loop {
             tokio::select! {
@@ -178,9 +203,29 @@ impl ConnectionMonitor {
                 }
                 _ = report_interval.tick() => {
                     self.stats.log_status();
-                    let connectivity = self.stats.evaluate_connectivity();
+                    let mut connectivity = self.stats.evaluate_connectivity();
+                    let lost_pings = self.self_pings.consecutive_lost(Instant::now());
+                    if lost_pings >= self.quality_config.max_consecutive_lost_pings {
+                        debug!("Lost the latest {lost_pings} self pings");
+                        connectivity.entry = ConnectivityStatus::Fail;
+                    }
                     report_connectivity(&connectivity, &mut task_client);
                 }
+                _ = quality_interval.tick() => {
+                    if let Some(quality) = self.self_pings.quality(Instant::now()) {
+                        task_client.send_status_msg(Box::new(ConnectionMonitorStatus::Quality {
+                            rtt_ms: quality.rtt_ms,
+                            loss_pct: quality.loss_pct,
+                        }));
+                    }
+                }
+                _ = latency_interval.tick() => {
+                    if let Some(latency) = self.latency.average() {
+                        task_client.send_status_msg(Box::new(ConnectionMonitorStatus::Latency(
+                            latency,
+                        )));
+                    }
+                }
             }
         }
         debug!("ConnectionMonitor: Exiting");
@@ -236,6 +281,13 @@ pub enum ConnectionMonitorStatus {
     ExitGatewayRoutingErrorIpv6,
     ConnectedIpv4,
     ConnectedIpv6,
+    /// Rolling estimate from the mixnet self pings.
+    Quality {
+        rtt_ms: u32,
+        loss_pct: u8,
+    },
+    /// Rolling average of the mixnet self ping round trip time, reported at the latency interval.
+    Latency(Duration),
 }
 
 impl fmt::Display for ConnectionMonitorStatus {
@@ -271,6 +323,12 @@ impl fmt::Display for ConnectionMonitorStatus {
             ConnectionMonitorStatus::ConnectedIpv6 => {
                 write!(f, "connected with ipv6")
             }
+            ConnectionMonitorStatus::Quality { rtt_ms, loss_pct } => {
+                write!(f, "connection quality: {rtt_ms}ms rtt, {loss_pct}% loss")
+            }
+            ConnectionMonitorStatus::Latency(rtt) => {
+                write!(f, "mixnet latency: {}ms", rtt.as_millis())
+            }
         }
     }
 }
@@ -283,10 +341,11 @@ impl nym_task::TaskStatusEvent for ConnectionMonitorStatus {
 
 pub fn start_connection_monitor(
     connection_event_rx: futures::channel::mpsc::UnboundedReceiver<ConnectionStatusEvent>,
+    quality_config: ConnectionQualityConfig,
     shutdown_listener: TaskClient,
 ) -> JoinHandle<Result<()>> {
     debug!("Creating connection monitor");
-    let monitor = ConnectionMonitor::new(connection_event_rx);
+    let monitor = ConnectionMonitor::new(connection_event_rx, quality_config);
     tokio::spawn(async move {
         monitor.run(shutdown_listener).await.inspect_err(|err| {
             error!("Connection monitor error: {err}");
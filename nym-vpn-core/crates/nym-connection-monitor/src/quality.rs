@@ -0,0 +1,274 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Settings for the connection quality estimate computed from the mixnet self pings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionQualityConfig {
+    /// Only the self pings sent within this window are used for the estimate.
+    pub sampling_window: Duration,
+
+    /// How often the estimate is reported.
+    pub report_interval: Duration,
+
+    /// Number of consecutive lost self pings after which the entry gateway is considered down.
+    pub max_consecutive_lost_pings: usize,
+
+    /// How often the rolling average of the self ping round trip time is reported as the mixnet
+    /// latency. It doesn't change how often the self pings are sent.
+    pub latency_interval: Duration,
+}
+
+impl Default for ConnectionQualityConfig {
+    fn default() -> Self {
+        Self {
+            sampling_window: Duration::from_secs(30),
+            report_interval: Duration::from_secs(10),
+            max_consecutive_lost_pings: 3,
+            latency_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ConnectionQuality {
+    pub(crate) rtt_ms: u32,
+    pub(crate) loss_pct: u8,
+}
+
+#[derive(Debug)]
+struct PingSample {
+    request_id: u64,
+    sent_at: Instant,
+    rtt: Option<Duration>,
+}
+
+impl PingSample {
+    fn is_lost(&self, now: Instant, reply_timeout: Duration) -> bool {
+        self.rtt.is_none() && now.duration_since(self.sent_at) >= reply_timeout
+    }
+
+    // A ping still waiting for its reply is neither answered nor lost yet.
+    fn is_settled(&self, now: Instant, reply_timeout: Duration) -> bool {
+        self.rtt.is_some() || self.is_lost(now, reply_timeout)
+    }
+}
+
+// Keeps the self pings sent within the sampling window, oldest first, along with the round trip
+// time of the ones that got a reply.
+#[derive(Debug)]
+pub(crate) struct PingTracker {
+    sampling_window: Duration,
+    reply_timeout: Duration,
+    samples: VecDeque<PingSample>,
+}
+
+impl PingTracker {
+    pub(crate) fn new(sampling_window: Duration, reply_timeout: Duration) -> Self {
+        Self {
+            sampling_window,
+            reply_timeout,
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn record_sent(&mut self, request_id: u64, now: Instant) {
+        self.prune(now);
+        self.samples.push_back(PingSample {
+            request_id,
+            sent_at: now,
+            rtt: None,
+        });
+    }
+
+    /// Returns the round trip time of the ping, unless it is unknown or was already answered.
+    pub(crate) fn record_reply(&mut self, request_id: u64, now: Instant) -> Option<Duration> {
+        let sample = self
+            .samples
+            .iter_mut()
+            .find(|sample| sample.request_id == request_id && sample.rtt.is_none())?;
+        let rtt = now.duration_since(sample.sent_at);
+        sample.rtt = Some(rtt);
+        Some(rtt)
+    }
+
+    /// Number of lost pings since the latest answered one.
+    pub(crate) fn consecutive_lost(&self, now: Instant) -> usize {
+        self.samples
+            .iter()
+            .rev()
+            .filter(|sample| sample.is_settled(now, self.reply_timeout))
+            .take_while(|sample| sample.rtt.is_none())
+            .count()
+    }
+
+    /// Average round trip time and loss over the sampling window. Returns `None` until at least
+    /// one ping has been answered.
+    pub(crate) fn quality(&mut self, now: Instant) -> Option<ConnectionQuality> {
+        self.prune(now);
+
+        let (mut settled, mut total_rtt, mut replies) = (0u32, Duration::ZERO, 0u32);
+        for sample in &self.samples {
+            if !sample.is_settled(now, self.reply_timeout) {
+                continue;
+            }
+            settled += 1;
+            if let Some(rtt) = sample.rtt {
+                total_rtt += rtt;
+                replies += 1;
+            }
+        }
+        if replies == 0 {
+            return None;
+        }
+
+        let lost = settled - replies;
+        Some(ConnectionQuality {
+            rtt_ms: (total_rtt / replies)
+                .as_millis()
+                .try_into()
+                .unwrap_or(u32::MAX),
+            loss_pct: (lost * 100 / settled) as u8,
+        })
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while self
+            .samples
+            .front()
+            .is_some_and(|sample| now.duration_since(sample.sent_at) > self.sampling_window)
+        {
+            self.samples.pop_front();
+        }
+    }
+}
+
+// Exponentially weighted moving average of the mixnet self ping round trip times.
+#[derive(Debug, Default)]
+pub(crate) struct RollingLatency {
+    average: Option<Duration>,
+}
+
+impl RollingLatency {
+    // Weight of the newest sample.
+    const SMOOTHING: f64 = 0.2;
+
+    pub(crate) fn record(&mut self, rtt: Duration) {
+        self.average = Some(match self.average {
+            Some(average) => average.mul_f64(1.0 - Self::SMOOTHING) + rtt.mul_f64(Self::SMOOTHING),
+            None => rtt,
+        });
+    }
+
+    pub(crate) fn average(&self) -> Option<Duration> {
+        self.average
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn tracker() -> PingTracker {
+        PingTracker::new(Duration::from_secs(30), REPLY_TIMEOUT)
+    }
+
+    #[test]
+    fn quality_averages_rtt_and_counts_lost_pings() {
+        let start = Instant::now();
+        let mut tracker = tracker();
+        for id in 0..4 {
+            tracker.record_sent(id, start + Duration::from_secs(id));
+        }
+        tracker.record_reply(0, start + Duration::from_millis(200));
+        tracker.record_reply(1, start + Duration::from_millis(1400));
+        tracker.record_reply(2, start + Duration::from_millis(2300));
+
+        let quality = tracker.quality(start + Duration::from_secs(10)).unwrap();
+        assert_eq!(
+            quality,
+            ConnectionQuality {
+                rtt_ms: 300,
+                loss_pct: 25
+            }
+        );
+    }
+
+    #[test]
+    fn replies_report_the_round_trip_time_once() {
+        let start = Instant::now();
+        let mut tracker = tracker();
+        tracker.record_sent(0, start);
+
+        let reply_at = start + Duration::from_millis(250);
+        assert_eq!(
+            tracker.record_reply(0, reply_at),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(tracker.record_reply(0, reply_at), None);
+        assert_eq!(tracker.record_reply(1, reply_at), None);
+    }
+
+    #[test]
+    fn pending_pings_are_not_counted_as_lost() {
+        let start = Instant::now();
+        let mut tracker = tracker();
+        tracker.record_sent(0, start);
+        tracker.record_reply(0, start + Duration::from_millis(100));
+        tracker.record_sent(1, start + Duration::from_secs(1));
+
+        let now = start + Duration::from_secs(2);
+        assert_eq!(tracker.quality(now).unwrap().loss_pct, 0);
+        assert_eq!(tracker.consecutive_lost(now), 0);
+    }
+
+    #[test]
+    fn consecutive_lost_resets_on_reply() {
+        let start = Instant::now();
+        let mut tracker = tracker();
+        for id in 0..5 {
+            tracker.record_sent(id, start + Duration::from_secs(id));
+        }
+        tracker.record_reply(1, start + Duration::from_millis(1500));
+
+        let now = start + Duration::from_secs(20);
+        assert_eq!(tracker.consecutive_lost(now), 3);
+    }
+
+    #[test]
+    fn samples_outside_the_window_are_dropped() {
+        let start = Instant::now();
+        let mut tracker = tracker();
+        tracker.record_sent(0, start);
+        tracker.record_sent(1, start + Duration::from_secs(40));
+        tracker.record_reply(1, start + Duration::from_millis(40500));
+
+        let quality = tracker.quality(start + Duration::from_secs(41)).unwrap();
+        assert_eq!(quality.loss_pct, 0);
+        assert_eq!(quality.rtt_ms, 500);
+    }
+
+    #[test]
+    fn rolling_latency_smooths_out_spikes() {
+        let mut latency = RollingLatency::default();
+        assert_eq!(latency.average(), None);
+
+        latency.record(Duration::from_millis(100));
+        assert_eq!(latency.average(), Some(Duration::from_millis(100)));
+
+        latency.record(Duration::from_millis(600));
+        assert_eq!(latency.average(), Some(Duration::from_millis(200)));
+
+        for _ in 0..50 {
+            latency.record(Duration::from_millis(300));
+        }
+        let average = latency.average().unwrap();
+        assert!(average > Duration::from_millis(299) && average <= Duration::from_millis(300));
+    }
+}
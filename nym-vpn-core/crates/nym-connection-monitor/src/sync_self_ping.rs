@@ -5,7 +5,7 @@ use std::{sync::Arc, time::Duration};
 
 use futures::StreamExt;
 use nym_sdk::mixnet::{MixnetClient, MixnetMessageSender, Recipient};
-use tracing::{debug, error};
+use tracing::{debug, error, info};
 
 use crate::{
     error::{Error, Result},
@@ -15,13 +15,47 @@ use crate::{
 
 type SharedMixnetClient = Arc<tokio::sync::Mutex<Option<MixnetClient>>>;
 
+/// How long to wait for the mixnet self ping to return, and how many times to try again.
+#[derive(Debug, Clone, Copy)]
+pub struct SelfPingConfig {
+    /// Time to wait for any of the pings of an attempt to return.
+    pub timeout: Duration,
+    /// Number of attempts after the first one, each sending a new round of pings.
+    pub retries: u32,
+}
+
+impl Default for SelfPingConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            retries: 0,
+        }
+    }
+}
+
 // Send mixnet self ping and wait for the response
 pub async fn self_ping_and_wait(
     our_address: Recipient,
     mixnet_client: SharedMixnetClient,
+    config: SelfPingConfig,
 ) -> Result<()> {
-    let request_ids = send_self_pings(our_address, &mixnet_client).await?;
-    wait_for_self_ping_return(&mixnet_client, &request_ids).await
+    // A late reply to an earlier round is as good as any
+    let mut request_ids = Vec::new();
+    for attempt in 0..=config.retries {
+        if attempt > 0 {
+            info!(
+                "Mixnet self ping did not return, retrying (attempt {} of {})",
+                attempt + 1,
+                config.retries + 1
+            );
+        }
+        request_ids.extend(send_self_pings(our_address, &mixnet_client).await?);
+        match wait_for_self_ping_return(&mixnet_client, &request_ids, config.timeout).await {
+            Err(Error::TimeoutWaitingForMixnetSelfPing) if attempt < config.retries => {}
+            result => return result,
+        }
+    }
+    Err(Error::TimeoutWaitingForMixnetSelfPing)
 }
 
 async fn send_self_pings(
@@ -52,8 +86,9 @@ async fn send_self_pings(
 async fn wait_for_self_ping_return(
     mixnet_client: &SharedMixnetClient,
     request_ids: &[u64],
+    timeout: Duration,
 ) -> Result<()> {
-    let timeout = tokio::time::sleep(Duration::from_secs(5));
+    let timeout = tokio::time::sleep(timeout);
     tokio::pin!(timeout);
 
     // Connecting is basically synchronous from the perspective of the mixnet client, so it's safe
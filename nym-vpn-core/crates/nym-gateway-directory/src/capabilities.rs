@@ -0,0 +1,179 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use nym_sdk::mixnet::NodeIdentity;
+use serde::{de::DeserializeOwned, Deserialize};
+use tracing::debug;
+
+use crate::{error::Result, Error, IpPacketRouterAddress};
+
+// Port the nym-node http api listens on unless the operator changed it
+pub(crate) const DEFAULT_NYM_NODE_HTTP_PORT: u16 = 8080;
+
+/// Services a gateway describes itself as running, as reported by the node's own http api rather
+/// than the directory.
+#[derive(Debug, Clone)]
+pub struct GatewayCapabilities {
+    pub has_ipr: bool,
+    pub has_authenticator: bool,
+    pub wg_port: Option<u16>,
+    pub ipr_address: Option<IpPacketRouterAddress>,
+}
+
+impl GatewayCapabilities {
+    /// Whether the gateway can be used as an entry or exit for a WireGuard tunnel.
+    pub fn supports_wireguard(&self) -> bool {
+        self.has_authenticator && self.wg_port.is_some()
+    }
+}
+
+#[derive(Deserialize)]
+struct ClientInterfacesResponse {
+    #[serde(default)]
+    wireguard: Option<WireguardInterface>,
+}
+
+#[derive(Deserialize)]
+struct WireguardInterface {
+    port: u16,
+}
+
+#[derive(Deserialize)]
+struct ServiceAddressResponse {
+    address: String,
+}
+
+pub(crate) async fn fetch_capabilities(
+    http_client: &reqwest::Client,
+    gateway_identity: &NodeIdentity,
+    node_addr: SocketAddr,
+) -> Result<GatewayCapabilities> {
+    let base_url = format!("http://{node_addr}/api/v1");
+    let gateway_identity = gateway_identity.to_base58_string();
+
+    let client_interfaces: ClientInterfacesResponse = get_json(
+        http_client,
+        &gateway_identity,
+        &format!("{base_url}/gateway/client-interfaces"),
+    )
+    .await?
+    .unwrap_or(ClientInterfacesResponse { wireguard: None });
+    let ipr: Option<ServiceAddressResponse> = get_json(
+        http_client,
+        &gateway_identity,
+        &format!("{base_url}/ip-packet-router"),
+    )
+    .await?;
+    let authenticator: Option<ServiceAddressResponse> = get_json(
+        http_client,
+        &gateway_identity,
+        &format!("{base_url}/authenticator"),
+    )
+    .await?;
+
+    let ipr_address = ipr
+        .map(|ipr| IpPacketRouterAddress::try_from_base58_string(&ipr.address))
+        .transpose()?;
+    Ok(GatewayCapabilities {
+        has_ipr: ipr_address.is_some(),
+        has_authenticator: authenticator.is_some(),
+        wg_port: client_interfaces.wireguard.map(|wireguard| wireguard.port),
+        ipr_address,
+    })
+}
+
+// Returns `None` when the node doesn't expose the endpoint, which is how it reports a service it
+// doesn't run. A response that doesn't parse is an error, not a missing service.
+async fn get_json<T: DeserializeOwned>(
+    http_client: &reqwest::Client,
+    gateway_identity: &str,
+    url: &str,
+) -> Result<Option<T>> {
+    let unreachable = |source| Error::GatewayUnreachable {
+        gateway_identity: gateway_identity.to_owned(),
+        source,
+    };
+
+    debug!("Fetching {url}");
+    let response = http_client.get(url).send().await.map_err(unreachable)?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let bytes = response
+        .error_for_status()
+        .map_err(unreachable)?
+        .bytes()
+        .await
+        .map_err(unreachable)?;
+    serde_json::from_slice(&bytes)
+        .map(Some)
+        .map_err(|source| Error::MalformedGatewayResponse {
+            gateway_identity: gateway_identity.to_owned(),
+            url: url.to_owned(),
+            source,
+        })
+}
+
+/// Short lived cache of the capabilities, so that browsing gateways in the UI doesn't query the
+/// same node over and over.
+#[derive(Debug)]
+pub(crate) struct CapabilitiesCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, GatewayCapabilities)>>,
+}
+
+impl CapabilitiesCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn get(&self, gateway_identity: &str) -> Option<GatewayCapabilities> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(gateway_identity)
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < self.ttl)
+            .map(|(_, capabilities)| capabilities.clone())
+    }
+
+    pub(crate) fn insert(&self, gateway_identity: String, capabilities: GatewayCapabilities) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (fetched_at, _)| fetched_at.elapsed() < self.ttl);
+        entries.insert(gateway_identity, (Instant::now(), capabilities));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capabilities() -> GatewayCapabilities {
+        GatewayCapabilities {
+            has_ipr: false,
+            has_authenticator: true,
+            wg_port: Some(51822),
+            ipr_address: None,
+        }
+    }
+
+    #[test]
+    fn cached_capabilities_expire() {
+        let cache = CapabilitiesCache::new(Duration::from_secs(60));
+        cache.insert("gateway".to_owned(), capabilities());
+        assert!(cache.get("gateway").unwrap().supports_wireguard());
+        assert!(cache.get("other").is_none());
+
+        let cache = CapabilitiesCache::new(Duration::ZERO);
+        cache.insert("gateway".to_owned(), capabilities());
+        assert!(cache.get("gateway").is_none());
+    }
+}
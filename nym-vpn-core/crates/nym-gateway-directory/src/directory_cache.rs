@@ -0,0 +1,210 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use nym_vpn_api_client::{response::NymDirectoryGatewaysResponse, types::GatewayMinPerformance};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::entries::gateway::GatewayType;
+
+const CACHE_DIR: &str = "directory_cache";
+
+#[derive(Serialize, Deserialize)]
+struct CachedGateways {
+    fetched_at: SystemTime,
+    // The nym-vpn-api the gateways were fetched from, so that another network isn't served
+    api_url: String,
+    mixnet_min_performance: Option<String>,
+    vpn_min_performance: Option<String>,
+    gateways: NymDirectoryGatewaysResponse,
+}
+
+/// On disk cache of the last successful gateway lookup for each gateway type, so that connecting
+/// doesn't have to wait for the directory when the network is flaky.
+#[derive(Clone, Debug)]
+pub(crate) struct DirectoryCache {
+    dir: PathBuf,
+    ttl: Duration,
+    api_url: String,
+}
+
+impl DirectoryCache {
+    pub(crate) fn new(data_path: &Path, ttl: Duration, api_url: &Url) -> Self {
+        Self {
+            dir: data_path.join(CACHE_DIR),
+            ttl,
+            api_url: api_url.to_string(),
+        }
+    }
+
+    fn path(&self, gw_type: &GatewayType) -> PathBuf {
+        let name = match gw_type {
+            GatewayType::MixnetEntry => "mixnet_entry",
+            GatewayType::MixnetExit => "mixnet_exit",
+            GatewayType::Wg => "wg",
+        };
+        self.dir.join(format!("{name}_gateways.json"))
+    }
+
+    /// Returns the cached gateways along with their age, if they were fetched within the TTL
+    /// from the same nym-vpn-api using the same performance thresholds.
+    pub(crate) fn load(
+        &self,
+        gw_type: &GatewayType,
+        min_performance: &Option<GatewayMinPerformance>,
+    ) -> Option<(NymDirectoryGatewaysResponse, Duration)> {
+        let path = self.path(gw_type);
+        let cached: CachedGateways = match fs::read(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| serde_json::from_slice(&bytes).map_err(|err| err.to_string()))
+        {
+            Ok(cached) => cached,
+            Err(err) => {
+                debug!("No usable directory cache at {}: {err}", path.display());
+                return None;
+            }
+        };
+
+        if cached.api_url != self.api_url {
+            debug!(
+                "Ignoring {gw_type} directory cache fetched from {}",
+                cached.api_url
+            );
+            return None;
+        }
+
+        let (mixnet_min_performance, vpn_min_performance) = performance_key(min_performance);
+        if cached.mixnet_min_performance != mixnet_min_performance
+            || cached.vpn_min_performance != vpn_min_performance
+        {
+            debug!("Ignoring {gw_type} directory cache fetched with other performance thresholds");
+            return None;
+        }
+
+        // A timestamp in the future means the clock moved, treat the entry as just fetched.
+        let age = cached.fetched_at.elapsed().unwrap_or_default();
+        if age > self.ttl {
+            debug!(
+                "Ignoring stale {gw_type} directory cache, age: {}s, ttl: {}s",
+                age.as_secs(),
+                self.ttl.as_secs()
+            );
+            return None;
+        }
+        Some((cached.gateways, age))
+    }
+
+    pub(crate) fn store(
+        &self,
+        gw_type: &GatewayType,
+        min_performance: &Option<GatewayMinPerformance>,
+        gateways: &NymDirectoryGatewaysResponse,
+    ) {
+        let (mixnet_min_performance, vpn_min_performance) = performance_key(min_performance);
+        let cached = CachedGateways {
+            fetched_at: SystemTime::now(),
+            api_url: self.api_url.clone(),
+            mixnet_min_performance,
+            vpn_min_performance,
+            gateways: gateways.clone(),
+        };
+
+        let path = self.path(gw_type);
+        let result = fs::create_dir_all(&self.dir)
+            .map_err(|err| err.to_string())
+            .and_then(|()| serde_json::to_vec(&cached).map_err(|err| err.to_string()))
+            .and_then(|bytes| fs::write(&path, bytes).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            warn!(
+                "Failed to write directory cache to {}: {err}",
+                path.display()
+            );
+        }
+    }
+}
+
+fn performance_key(
+    min_performance: &Option<GatewayMinPerformance>,
+) -> (Option<String>, Option<String>) {
+    let min_performance = min_performance.clone().unwrap_or_default();
+    (
+        min_performance
+            .mixnet_min_performance
+            .map(|threshold| threshold.to_string()),
+        min_performance
+            .vpn_min_performance
+            .map(|threshold| threshold.to_string()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_data_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "nym-gateway-directory-cache-{name}-{}",
+            std::process::id()
+        ));
+        _ = fs::remove_dir_all(&path);
+        path
+    }
+
+    fn empty_response() -> NymDirectoryGatewaysResponse {
+        serde_json::from_str("[]").unwrap()
+    }
+
+    fn mainnet_url() -> Url {
+        "https://nymvpn.com/api".parse().unwrap()
+    }
+
+    #[test]
+    fn stored_gateways_are_served_within_ttl() {
+        let data_path = temp_data_path("fresh");
+        let cache = DirectoryCache::new(&data_path, Duration::from_secs(60), &mainnet_url());
+        cache.store(&GatewayType::Wg, &None, &empty_response());
+
+        let (_, age) = cache.load(&GatewayType::Wg, &None).unwrap();
+        assert!(age < Duration::from_secs(60));
+        assert!(cache.load(&GatewayType::MixnetEntry, &None).is_none());
+
+        _ = fs::remove_dir_all(&data_path);
+    }
+
+    #[test]
+    fn stale_or_mismatched_entries_are_ignored() {
+        let data_path = temp_data_path("stale");
+        let cache = DirectoryCache::new(&data_path, Duration::ZERO, &mainnet_url());
+        cache.store(&GatewayType::Wg, &None, &empty_response());
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(cache.load(&GatewayType::Wg, &None).is_none());
+
+        let cache = DirectoryCache::new(&data_path, Duration::from_secs(60), &mainnet_url());
+        let min_performance = GatewayMinPerformance::from_percentage_values(Some(50), None).ok();
+        assert!(cache.load(&GatewayType::Wg, &min_performance).is_none());
+
+        _ = fs::remove_dir_all(&data_path);
+    }
+
+    #[test]
+    fn entries_from_another_network_are_ignored() {
+        let data_path = temp_data_path("network");
+        let mainnet_cache =
+            DirectoryCache::new(&data_path, Duration::from_secs(60), &mainnet_url());
+        mainnet_cache.store(&GatewayType::Wg, &None, &empty_response());
+
+        let sandbox_url = "https://sandbox-nym-vpn-api.nymte.ch/api".parse().unwrap();
+        let sandbox_cache = DirectoryCache::new(&data_path, Duration::from_secs(60), &sandbox_url);
+        assert!(sandbox_cache.load(&GatewayType::Wg, &None).is_none());
+        assert!(mainnet_cache.load(&GatewayType::Wg, &None).is_some());
+
+        _ = fs::remove_dir_all(&data_path);
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Continent {
+    Africa,
+    Antarctica,
+    Asia,
+    Europe,
+    NorthAmerica,
+    Oceania,
+    SouthAmerica,
+}
+
+impl Continent {
+    /// Continent of the country with the given two letter ISO code. Transcontinental countries
+    /// belong to the continent of their capital, e.g. Russia is in Europe and Turkey in Asia.
+    pub fn from_iso_code(code: &str) -> Option<Self> {
+        let continent = match code.to_ascii_uppercase().as_str() {
+            "AO" | "BF" | "BI" | "BJ" | "BW" | "CD" | "CF" | "CG" | "CI" | "CM" | "CV" | "DJ"
+            | "DZ" | "EG" | "EH" | "ER" | "ET" | "GA" | "GH" | "GM" | "GN" | "GQ" | "GW" | "KE"
+            | "KM" | "LR" | "LS" | "LY" | "MA" | "MG" | "ML" | "MR" | "MU" | "MW" | "MZ" | "NA"
+            | "NE" | "NG" | "RE" | "RW" | "SC" | "SD" | "SH" | "SL" | "SN" | "SO" | "SS" | "ST"
+            | "SZ" | "TD" | "TG" | "TN" | "TZ" | "UG" | "YT" | "ZA" | "ZM" | "ZW" => Self::Africa,
+
+            "AQ" | "BV" | "GS" | "HM" | "TF" => Self::Antarctica,
+
+            "AE" | "AF" | "AM" | "AZ" | "BD" | "BH" | "BN" | "BT" | "CC" | "CN" | "CX" | "CY"
+            | "GE" | "HK" | "ID" | "IL" | "IN" | "IO" | "IQ" | "IR" | "JO" | "JP" | "KG" | "KH"
+            | "KP" | "KR" | "KW" | "KZ" | "LA" | "LB" | "LK" | "MM" | "MN" | "MO" | "MV" | "MY"
+            | "NP" | "OM" | "PH" | "PK" | "PS" | "QA" | "SA" | "SG" | "SY" | "TH" | "TJ" | "TL"
+            | "TM" | "TR" | "TW" | "UZ" | "VN" | "YE" => Self::Asia,
+
+            "AD" | "AL" | "AT" | "AX" | "BA" | "BE" | "BG" | "BY" | "CH" | "CZ" | "DE" | "DK"
+            | "EE" | "ES" | "FI" | "FO" | "FR" | "GB" | "GG" | "GI" | "GR" | "HR" | "HU" | "IE"
+            | "IM" | "IS" | "IT" | "JE" | "LI" | "LT" | "LU" | "LV" | "MC" | "MD" | "ME" | "MK"
+            | "MT" | "NL" | "NO" | "PL" | "PT" | "RO" | "RS" | "RU" | "SE" | "SI" | "SJ" | "SK"
+            | "SM" | "UA" | "VA" | "XK" => Self::Europe,
+
+            "AG" | "AI" | "AW" | "BB" | "BL" | "BM" | "BQ" | "BS" | "BZ" | "CA" | "CR" | "CU"
+            | "CW" | "DM" | "DO" | "GD" | "GL" | "GP" | "GT" | "HN" | "HT" | "JM" | "KN" | "KY"
+            | "LC" | "MF" | "MQ" | "MS" | "MX" | "NI" | "PA" | "PM" | "PR" | "SV" | "SX" | "TC"
+            | "TT" | "UM" | "US" | "VC" | "VG" | "VI" => Self::NorthAmerica,
+
+            "AS" | "AU" | "CK" | "FJ" | "FM" | "GU" | "KI" | "MH" | "MP" | "NC" | "NF" | "NR"
+            | "NU" | "NZ" | "PF" | "PG" | "PN" | "PW" | "SB" | "TK" | "TO" | "TV" | "VU" | "WF"
+            | "WS" => Self::Oceania,
+
+            "AR" | "BO" | "BR" | "CL" | "CO" | "EC" | "FK" | "GF" | "GY" | "PE" | "PY" | "SR"
+            | "UY" | "VE" => Self::SouthAmerica,
+
+            _ => return None,
+        };
+        Some(continent)
+    }
+}
+
+impl fmt::Display for Continent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Africa => write!(f, "Africa"),
+            Self::Antarctica => write!(f, "Antarctica"),
+            Self::Asia => write!(f, "Asia"),
+            Self::Europe => write!(f, "Europe"),
+            Self::NorthAmerica => write!(f, "North America"),
+            Self::Oceania => write!(f, "Oceania"),
+            Self::SouthAmerica => write!(f, "South America"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_countries_to_continents() {
+        assert_eq!(Continent::from_iso_code("CH"), Some(Continent::Europe));
+        assert_eq!(Continent::from_iso_code("de"), Some(Continent::Europe));
+        assert_eq!(
+            Continent::from_iso_code("US"),
+            Some(Continent::NorthAmerica)
+        );
+        assert_eq!(
+            Continent::from_iso_code("BR"),
+            Some(Continent::SouthAmerica)
+        );
+        assert_eq!(Continent::from_iso_code("JP"), Some(Continent::Asia));
+        assert_eq!(Continent::from_iso_code("AU"), Some(Continent::Oceania));
+        assert_eq!(Continent::from_iso_code("ZA"), Some(Continent::Africa));
+        assert_eq!(Continent::from_iso_code("XX"), None);
+    }
+}
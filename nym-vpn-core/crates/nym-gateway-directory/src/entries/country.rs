@@ -1,6 +1,8 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
+use nym_vpn_api_client::response::NymDirectoryGatewayCountriesResponse;
+
 use crate::Location;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -29,3 +31,36 @@ impl From<Location> for Country {
         }
     }
 }
+
+/// The countries returned by a lookup, along with how many malformed entries were left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CountriesResponse {
+    pub locations: Vec<Country>,
+    pub skipped: usize,
+}
+
+impl From<NymDirectoryGatewayCountriesResponse> for CountriesResponse {
+    fn from(response: NymDirectoryGatewayCountriesResponse) -> Self {
+        let skipped = response.skipped();
+        Self {
+            locations: response.into_iter().map(Country::from).collect(),
+            skipped,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_countries_are_skipped() {
+        let response: NymDirectoryGatewayCountriesResponse =
+            serde_json::from_str(r#"["DE", {"country": 42}, "CH"]"#).unwrap();
+        let countries = CountriesResponse::from(response);
+
+        let iso_codes: Vec<_> = countries.locations.iter().map(Country::iso_code).collect();
+        assert_eq!(iso_codes, vec!["DE", "CH"]);
+        assert_eq!(countries.skipped, 1);
+    }
+}
@@ -1,9 +1,13 @@
 // Copyright 2023 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::fmt::{Display, Formatter};
+use std::{
+    fmt::{Display, Formatter},
+    time::Duration,
+};
 
 use nym_sdk::mixnet::NodeIdentity;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
@@ -18,6 +22,8 @@ pub enum EntryPoint {
     Gateway { identity: NodeIdentity },
     // Select a random entry gateway in a specific location.
     Location { location: String },
+    // An explicit entry gateway, by the hostname or IP address it advertises in the directory.
+    Hostname { hostname: String },
     // Select a random entry gateway but increasey probability of selecting a low latency gateway
     // as determined by ping times.
     RandomLowLatency,
@@ -30,6 +36,7 @@ impl Display for EntryPoint {
         match self {
             EntryPoint::Gateway { identity } => write!(f, "Gateway: {}", identity),
             EntryPoint::Location { location } => write!(f, "Location: {}", location),
+            EntryPoint::Hostname { hostname } => write!(f, "Hostname: {}", hostname),
             EntryPoint::RandomLowLatency => write!(f, "Random low latency"),
             EntryPoint::Random => write!(f, "Random"),
         }
@@ -47,12 +54,84 @@ impl EntryPoint {
         Ok(EntryPoint::Gateway { identity })
     }
 
+    /// Parse an entry point from its command line form:
+    ///
+    /// - `gateway:<identity>`: the gateway with the given base58 identity
+    /// - `country:<code>`: a random gateway in the country with the given two letter ISO code
+    /// - `hostname:<host>`: the gateway advertising the given hostname or IP address
+    /// - `low-latency`: a random gateway, favouring the ones with low ping times
+    /// - `random`: a random gateway
+    ///
+    /// This is the inverse of [`EntryPoint::to_cli_string`].
+    pub fn from_cli_str(input: &str) -> Result<Self> {
+        let invalid = |reason: String| Error::InvalidEntryPoint {
+            input: input.to_string(),
+            reason,
+        };
+        match input.trim() {
+            "low-latency" => return Ok(EntryPoint::RandomLowLatency),
+            "random" => return Ok(EntryPoint::Random),
+            _ => {}
+        }
+        let (kind, value) = split_cli_str(input).map_err(invalid)?;
+        match kind {
+            "gateway" => Ok(EntryPoint::Gateway {
+                identity: parse_identity(value).map_err(invalid)?,
+            }),
+            "country" => Ok(EntryPoint::Location {
+                location: parse_country_code(value).map_err(invalid)?,
+            }),
+            "hostname" => Ok(EntryPoint::Hostname {
+                hostname: parse_hostname(value).map_err(invalid)?,
+            }),
+            _ => Err(invalid(format!(
+                "unknown kind {kind:?}, expected gateway, country, hostname, low-latency or random"
+            ))),
+        }
+    }
+
+    /// The command line form of the entry point, see [`EntryPoint::from_cli_str`].
+    pub fn to_cli_string(&self) -> String {
+        match self {
+            EntryPoint::Gateway { identity } => format!("gateway:{}", identity.to_base58_string()),
+            EntryPoint::Location { location } => format!("country:{location}"),
+            EntryPoint::Hostname { hostname } => format!("hostname:{hostname}"),
+            EntryPoint::RandomLowLatency => "low-latency".to_string(),
+            EntryPoint::Random => "random".to_string(),
+        }
+    }
+
     pub fn is_location(&self) -> bool {
         matches!(self, EntryPoint::Location { .. })
     }
 
     pub async fn lookup_gateway(&self, gateways: &GatewayList) -> Result<Gateway> {
-        match &self {
+        self.lookup_gateway_with_rng(gateways, &mut rand::rngs::OsRng)
+            .await
+    }
+
+    /// Like [`EntryPoint::lookup_gateway`], but drawing the random choices from the given rng.
+    ///
+    /// Selecting by low latency depends on the measured round trip times, so it isn't
+    /// reproducible even with a seeded rng.
+    pub async fn lookup_gateway_with_rng<R: Rng + Send + ?Sized>(
+        &self,
+        gateways: &GatewayList,
+        rng: &mut R,
+    ) -> Result<Gateway> {
+        self.lookup_gateway_with_latency(gateways, rng)
+            .await
+            .map(|(gateway, _)| gateway)
+    }
+
+    /// Like [`EntryPoint::lookup_gateway_with_rng`], but also returning the round trip time to
+    /// the gateway when it was selected based on latency, `None` otherwise.
+    pub async fn lookup_gateway_with_latency<R: Rng + Send + ?Sized>(
+        &self,
+        gateways: &GatewayList,
+        rng: &mut R,
+    ) -> Result<(Gateway, Option<Duration>)> {
+        let gateway = match &self {
             EntryPoint::Gateway { identity } => {
                 debug!("Selecting gateway by identity: {}", identity);
                 gateways
@@ -65,22 +144,111 @@ impl EntryPoint {
             EntryPoint::Location { location } => {
                 debug!("Selecting gateway by location: {}", location);
                 gateways
-                    .random_gateway_located_at(location.to_string())
+                    .random_gateway_located_at_with_rng(location.to_string(), rng)
                     .ok_or_else(|| Error::NoMatchingEntryGatewayForLocation {
                         requested_location: location.clone(),
                         available_countries: gateways.all_iso_codes(),
                     })
             }
+            EntryPoint::Hostname { hostname } => {
+                debug!("Selecting gateway by hostname: {}", hostname);
+                gateways.gateway_with_host(hostname).cloned()
+            }
             EntryPoint::RandomLowLatency => {
                 debug!("Selecting a random low latency gateway");
-                gateways.random_low_latency_gateway().await
+                let (gateway, latency) = gateways.random_low_latency_gateway(rng).await?;
+                return Ok((gateway, Some(latency)));
             }
             EntryPoint::Random => {
                 debug!("Selecting a random gateway");
                 gateways
-                    .random_gateway()
+                    .random_gateway_with_rng(rng)
                     .ok_or_else(|| Error::FailedToSelectGatewayRandomly)
             }
+        }?;
+        Ok((gateway, None))
+    }
+}
+
+// Splits `<kind>:<value>`, the form shared by the entry and exit points that take an argument.
+pub(super) fn split_cli_str(input: &str) -> std::result::Result<(&str, &str), String> {
+    input
+        .trim()
+        .split_once(':')
+        .ok_or_else(|| "expected <kind>:<value>".to_string())
+}
+
+pub(super) fn parse_identity(value: &str) -> std::result::Result<NodeIdentity, String> {
+    NodeIdentity::from_base58_string(value)
+        .map_err(|err| format!("invalid gateway identity: {err}"))
+}
+
+// Gateways report upper case codes, so accept lower case ones too but store them upper case.
+pub(super) fn parse_country_code(value: &str) -> std::result::Result<String, String> {
+    if value.len() != 2 || !value.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(format!(
+            "invalid country code {value:?}, expected a two letter ISO code"
+        ));
+    }
+    Ok(value.to_ascii_uppercase())
+}
+
+pub(super) fn parse_hostname(value: &str) -> std::result::Result<String, String> {
+    if value.is_empty() || value.chars().any(char::is_whitespace) {
+        return Err(format!("invalid hostname {value:?}"));
+    }
+    Ok(value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: &str = "4SPdxfBYsuARBw6REQQa5vFiKcvmYiet9sSWqb751i3Z";
+
+    #[test]
+    fn entry_point_cli_str_round_trip() {
+        let entry_points = [
+            EntryPoint::from_base58_string(IDENTITY).unwrap(),
+            EntryPoint::Location {
+                location: "CH".to_string(),
+            },
+            EntryPoint::Hostname {
+                hostname: "gateway.example.com".to_string(),
+            },
+            EntryPoint::RandomLowLatency,
+            EntryPoint::Random,
+        ];
+        for entry_point in entry_points {
+            let cli_string = entry_point.to_cli_string();
+            assert_eq!(EntryPoint::from_cli_str(&cli_string).unwrap(), entry_point);
+        }
+    }
+
+    #[test]
+    fn entry_point_cli_str_is_validated() {
+        assert_eq!(
+            EntryPoint::from_cli_str(" country:de ").unwrap(),
+            EntryPoint::Location {
+                location: "DE".to_string()
+            }
+        );
+        for input in [
+            "",
+            "gateway",
+            "gateway:not-an-identity",
+            "country:DEU",
+            "country:1",
+            "hostname:",
+            "address:foo",
+        ] {
+            assert!(
+                matches!(
+                    EntryPoint::from_cli_str(input),
+                    Err(Error::InvalidEntryPoint { .. })
+                ),
+                "{input}"
+            );
         }
     }
 }
@@ -4,10 +4,14 @@
 use std::fmt::{Display, Formatter};
 
 use nym_sdk::mixnet::{NodeIdentity, Recipient};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info};
 
-use super::gateway::{Gateway, GatewayList};
+use super::{
+    entry_point::{parse_country_code, parse_hostname, parse_identity, split_cli_str},
+    gateway::{Gateway, GatewayList},
+};
 use crate::{error::Result, Error, IpPacketRouterAddress};
 
 // The exit point is a nym-address, but if the exit ip-packet-router is running embedded on a
@@ -24,6 +28,8 @@ pub enum ExitPoint {
     Gateway { identity: NodeIdentity },
     // NOTE: Consider using a crate with strongly typed country codes instead of strings
     Location { location: String },
+    // An explicit exit gateway, by the hostname or IP address it advertises in the directory.
+    Hostname { hostname: String },
     // Select an exit gateway at random.
     Random,
 }
@@ -34,17 +40,76 @@ impl Display for ExitPoint {
             ExitPoint::Address { address } => write!(f, "Address: {}", address),
             ExitPoint::Gateway { identity } => write!(f, "Gateway: {}", identity),
             ExitPoint::Location { location } => write!(f, "Location: {}", location),
+            ExitPoint::Hostname { hostname } => write!(f, "Hostname: {}", hostname),
             ExitPoint::Random => write!(f, "Random"),
         }
     }
 }
 
 impl ExitPoint {
+    /// Parse an exit point from its command line form:
+    ///
+    /// - `address:<nym address>`: the ip packet router with the given nym address
+    /// - `gateway:<identity>`: the gateway with the given base58 identity
+    /// - `country:<code>`: a random gateway in the country with the given two letter ISO code
+    /// - `hostname:<host>`: the gateway advertising the given hostname or IP address
+    /// - `random`: a random gateway
+    ///
+    /// This is the inverse of [`ExitPoint::to_cli_string`].
+    pub fn from_cli_str(input: &str) -> Result<Self> {
+        let invalid = |reason: String| Error::InvalidExitPoint {
+            input: input.to_string(),
+            reason,
+        };
+        if input.trim() == "random" {
+            return Ok(ExitPoint::Random);
+        }
+        let (kind, value) = split_cli_str(input).map_err(invalid)?;
+        match kind {
+            "address" => Ok(ExitPoint::Address {
+                address: Recipient::try_from_base58_string(value)
+                    .map_err(|err| invalid(format!("invalid nym address: {err}")))?,
+            }),
+            "gateway" => Ok(ExitPoint::Gateway {
+                identity: parse_identity(value).map_err(invalid)?,
+            }),
+            "country" => Ok(ExitPoint::Location {
+                location: parse_country_code(value).map_err(invalid)?,
+            }),
+            "hostname" => Ok(ExitPoint::Hostname {
+                hostname: parse_hostname(value).map_err(invalid)?,
+            }),
+            _ => Err(invalid(format!(
+                "unknown kind {kind:?}, expected address, gateway, country, hostname or random"
+            ))),
+        }
+    }
+
+    /// The command line form of the exit point, see [`ExitPoint::from_cli_str`].
+    pub fn to_cli_string(&self) -> String {
+        match self {
+            ExitPoint::Address { address } => format!("address:{address}"),
+            ExitPoint::Gateway { identity } => format!("gateway:{}", identity.to_base58_string()),
+            ExitPoint::Location { location } => format!("country:{location}"),
+            ExitPoint::Hostname { hostname } => format!("hostname:{hostname}"),
+            ExitPoint::Random => "random".to_string(),
+        }
+    }
+
     pub fn is_location(&self) -> bool {
         matches!(self, ExitPoint::Location { .. })
     }
 
     pub fn lookup_gateway(&self, gateways: &GatewayList) -> Result<Gateway> {
+        self.lookup_gateway_with_rng(gateways, &mut rand::thread_rng())
+    }
+
+    /// Like [`ExitPoint::lookup_gateway`], but drawing the random choices from the given rng.
+    pub fn lookup_gateway_with_rng<R: Rng + ?Sized>(
+        &self,
+        gateways: &GatewayList,
+        rng: &mut R,
+    ) -> Result<Gateway> {
         match &self {
             ExitPoint::Address { address } => {
                 debug!("Selecting gateway by address: {}", address);
@@ -75,18 +140,66 @@ impl ExitPoint {
             ExitPoint::Location { location } => {
                 debug!("Selecting gateway by location: {}", location);
                 gateways
-                    .random_gateway_located_at(location.to_string())
+                    .random_gateway_located_at_with_rng(location.to_string(), rng)
                     .ok_or_else(|| Error::NoMatchingExitGatewayForLocation {
                         requested_location: location.clone(),
                         available_countries: gateways.all_iso_codes(),
                     })
             }
+            ExitPoint::Hostname { hostname } => {
+                debug!("Selecting gateway by hostname: {}", hostname);
+                gateways.gateway_with_host(hostname).cloned()
+            }
             ExitPoint::Random => {
                 info!("Selecting a random exit gateway");
                 gateways
-                    .random_gateway()
+                    .random_gateway_with_rng(rng)
                     .ok_or_else(|| Error::FailedToSelectGatewayRandomly)
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: &str = "4SPdxfBYsuARBw6REQQa5vFiKcvmYiet9sSWqb751i3Z";
+
+    #[test]
+    fn exit_point_cli_str_round_trip() {
+        let address = format!("{IDENTITY}.{IDENTITY}@{IDENTITY}");
+        let exit_points = [
+            ExitPoint::Address {
+                address: Recipient::try_from_base58_string(address).unwrap(),
+            },
+            ExitPoint::Gateway {
+                identity: NodeIdentity::from_base58_string(IDENTITY).unwrap(),
+            },
+            ExitPoint::Location {
+                location: "CH".to_string(),
+            },
+            ExitPoint::Hostname {
+                hostname: "1.2.3.4".to_string(),
+            },
+            ExitPoint::Random,
+        ];
+        for exit_point in exit_points {
+            let cli_string = exit_point.to_cli_string();
+            assert_eq!(ExitPoint::from_cli_str(&cli_string).unwrap(), exit_point);
+        }
+    }
+
+    #[test]
+    fn exit_point_cli_str_is_validated() {
+        for input in ["", "address:foo", "country:Swiss", "low-latency", "random:"] {
+            assert!(
+                matches!(
+                    ExitPoint::from_cli_str(input),
+                    Err(Error::InvalidExitPoint { .. })
+                ),
+                "{input}"
+            );
+        }
+    }
+}
@@ -1,15 +1,32 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::{fmt, net::IpAddr, str::FromStr};
-
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use futures::StreamExt;
 use itertools::Itertools;
 use nym_sdk::mixnet::NodeIdentity;
 use nym_vpn_api_client::types::Percent;
-use rand::seq::IteratorRandom;
-use tracing::error;
-
-use crate::{error::Result, AuthAddress, Country, Error, IpPacketRouterAddress};
+use rand::{
+    seq::{IteratorRandom, SliceRandom},
+    Rng,
+};
+use tokio::net::TcpStream;
+use tracing::{debug, error};
+
+use crate::{error::Result, AuthAddress, Continent, Country, Error, IpPacketRouterAddress};
+
+// Number of TCP handshakes timed when estimating the round trip time to a gateway
+const LATENCY_SAMPLES: usize = 3;
+// Deadline for the whole estimate, since it runs on the connect path
+const LATENCY_TIMEOUT: Duration = Duration::from_secs(1);
+// Number of gateways whose round trip time is estimated at the same time when selecting by latency
+const CONCURRENT_LATENCY_MEASUREMENTS: usize = 20;
 
 #[derive(Clone)]
 pub struct Gateway {
@@ -22,6 +39,8 @@ pub struct Gateway {
     pub clients_ws_port: Option<u16>,
     pub clients_wss_port: Option<u16>,
     pub mixnet_performance: Option<Percent>,
+    /// DNS servers the gateway advertises for use through its exit, if any.
+    pub dns_servers: Vec<IpAddr>,
 }
 
 impl fmt::Debug for Gateway {
@@ -36,6 +55,7 @@ impl fmt::Debug for Gateway {
             .field("clients_ws_port", &self.clients_ws_port)
             .field("clients_wss_port", &self.clients_wss_port)
             .field("mixnet_performance", &self.mixnet_performance)
+            .field("dns_servers", &self.dns_servers)
             .finish()
     }
 }
@@ -51,6 +71,11 @@ impl Gateway {
             .map(|l| l.two_letter_iso_country_code.as_str())
     }
 
+    pub fn continent(&self) -> Option<Continent> {
+        self.two_letter_iso_country_code()
+            .and_then(Continent::from_iso_code)
+    }
+
     pub fn is_two_letter_iso_country_code(&self, code: &str) -> bool {
         self.two_letter_iso_country_code()
             .map_or(false, |gw_code| gw_code == code)
@@ -77,6 +102,40 @@ impl Gateway {
         }
     }
 
+    /// Estimate the round trip time to the gateway by timing concurrent TCP handshakes with its
+    /// client port. The whole estimate, host lookup included, is given one second.
+    ///
+    /// Returns `None` if the gateway couldn't be reached in time.
+    pub async fn measure_latency(&self) -> Option<Duration> {
+        let deadline = tokio::time::Instant::now() + LATENCY_TIMEOUT;
+        let port = self.clients_wss_port.or(self.clients_ws_port)?;
+        let Ok(ip) = tokio::time::timeout_at(deadline, self.lookup_ip()).await else {
+            debug!("Timed out looking up the gateway host");
+            return None;
+        };
+        let address = SocketAddr::new(ip?, port);
+
+        let handshakes = futures::future::join_all((0..LATENCY_SAMPLES).map(|_| async move {
+            let start = Instant::now();
+            match tokio::time::timeout_at(deadline, TcpStream::connect(address)).await {
+                Ok(Ok(_)) => Some(start.elapsed()),
+                Ok(Err(err)) => {
+                    debug!("Failed to connect to gateway at {address}: {err}");
+                    None
+                }
+                Err(_) => {
+                    debug!("Timed out connecting to gateway at {address}");
+                    None
+                }
+            }
+        }))
+        .await;
+
+        let samples = handshakes.into_iter().flatten().collect::<Vec<_>>();
+        let count = u32::try_from(samples.len()).ok()?;
+        (count > 0).then(|| samples.iter().sum::<Duration>() / count)
+    }
+
     pub fn clients_address_no_tls(&self) -> Option<String> {
         match (&self.host, &self.clients_ws_port) {
             (Some(host), Some(port)) => Some(format!("ws://{}:{}", host, port)),
@@ -229,6 +288,12 @@ impl TryFrom<nym_vpn_api_client::response::NymDirectoryGateway> for Gateway {
         });
         let host = hostname.or(first_ip_address);
 
+        let dns_servers = gateway
+            .dns_servers
+            .iter()
+            .filter_map(|ip| IpAddr::from_str(ip).ok())
+            .collect();
+
         Ok(Gateway {
             identity,
             location: Some(gateway.location.into()),
@@ -239,6 +304,7 @@ impl TryFrom<nym_vpn_api_client::response::NymDirectoryGateway> for Gateway {
             clients_ws_port: Some(gateway.entry.ws_port),
             clients_wss_port: gateway.entry.wss_port,
             mixnet_performance: Some(gateway.performance),
+            dns_servers,
         })
     }
 }
@@ -302,6 +368,7 @@ impl TryFrom<nym_validator_client::models::NymNodeDescription> for Gateway {
             clients_ws_port,
             clients_wss_port,
             mixnet_performance: None,
+            dns_servers: Vec::new(),
         })
     }
 }
@@ -344,6 +411,33 @@ impl GatewayList {
             .find(|gateway| gateway.identity() == identity)
     }
 
+    /// Find the single gateway advertising the given hostname or IP address.
+    pub fn gateway_with_host(&self, host: &str) -> Result<&Gateway> {
+        let matching: Vec<&Gateway> = self
+            .gateways
+            .iter()
+            .filter(|gateway| {
+                gateway
+                    .host()
+                    .is_some_and(|gateway_host| host_matches(gateway_host, host))
+            })
+            .collect();
+
+        match matching.as_slice() {
+            [gateway] => Ok(*gateway),
+            [] => Err(Error::NoMatchingGatewayForHost {
+                requested_host: host.to_owned(),
+            }),
+            _ => Err(Error::AmbiguousGatewayHost {
+                requested_host: host.to_owned(),
+                identities: matching
+                    .iter()
+                    .map(|gateway| gateway.identity().to_base58_string())
+                    .collect(),
+            }),
+        }
+    }
+
     pub fn gateways_located_at(&self, code: String) -> impl Iterator<Item = &Gateway> {
         self.gateways.iter().filter(move |gateway| {
             gateway
@@ -353,16 +447,25 @@ impl GatewayList {
     }
 
     pub fn random_gateway(&self) -> Option<Gateway> {
-        self.gateways
-            .iter()
-            .choose(&mut rand::thread_rng())
-            .cloned()
+        self.random_gateway_with_rng(&mut rand::thread_rng())
+    }
+
+    /// Like [`GatewayList::random_gateway`], but drawing from the given rng so that a seeded rng
+    /// gives the same gateway for the same list.
+    pub fn random_gateway_with_rng<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Gateway> {
+        self.gateways.iter().choose(rng).cloned()
     }
 
     pub fn random_gateway_located_at(&self, code: String) -> Option<Gateway> {
-        self.gateways_located_at(code)
-            .choose(&mut rand::thread_rng())
-            .cloned()
+        self.random_gateway_located_at_with_rng(code, &mut rand::thread_rng())
+    }
+
+    pub fn random_gateway_located_at_with_rng<R: Rng + ?Sized>(
+        &self,
+        code: String,
+        rng: &mut R,
+    ) -> Option<Gateway> {
+        self.gateways_located_at(code).choose(rng).cloned()
     }
 
     pub fn remove_gateway(&mut self, entry_gateway: &Gateway) {
@@ -370,6 +473,11 @@ impl GatewayList {
             .retain(|gateway| gateway.identity() != entry_gateway.identity());
     }
 
+    pub fn remove_gateways(&mut self, identities: &[NodeIdentity]) {
+        self.gateways
+            .retain(|gateway| !identities.contains(gateway.identity()));
+    }
+
     pub fn len(&self) -> usize {
         self.gateways.len()
     }
@@ -378,6 +486,10 @@ impl GatewayList {
         self.gateways.is_empty()
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &Gateway> {
+        self.gateways.iter()
+    }
+
     pub fn into_exit_gateways(self) -> GatewayList {
         let gw = self
             .gateways
@@ -404,14 +516,49 @@ impl GatewayList {
         self.gateways
     }
 
-    pub(crate) async fn random_low_latency_gateway(&self) -> Result<Gateway> {
-        let mut rng = rand::rngs::OsRng;
-        nym_client_core::init::helpers::choose_gateway_by_latency(&mut rng, &self.gateways, false)
-            .await
-            .map_err(|err| Error::FailedToSelectGatewayBasedOnLowLatency { source: err })
+    /// Pick a random gateway, weighted by the inverse of its round trip time, and return it
+    /// together with that round trip time. Gateways that can't be reached are never picked.
+    pub(crate) async fn random_low_latency_gateway<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Result<(Gateway, Duration)> {
+        let measured = futures::stream::iter(&self.gateways)
+            .map(|gateway| async move { Some((gateway, gateway.measure_latency().await?)) })
+            .buffer_unordered(CONCURRENT_LATENCY_MEASUREMENTS)
+            .filter_map(futures::future::ready)
+            .collect::<Vec<_>>()
+            .await;
+        debug!(
+            "Measured the latency of {} out of {} gateways",
+            measured.len(),
+            self.gateways.len()
+        );
+
+        choose_by_latency(&measured, rng)
+            .map(|(gateway, latency)| ((*gateway).clone(), *latency))
+            .ok_or(Error::FailedToSelectGatewayBasedOnLowLatency)
     }
 }
 
+// Favour the gateways with a lower round trip time, while still spreading the clients over all of
+// the reachable ones.
+fn choose_by_latency<'a, G, R: Rng + ?Sized>(
+    measured: &'a [(G, Duration)],
+    rng: &mut R,
+) -> Option<&'a (G, Duration)> {
+    measured
+        .choose_weighted(rng, |(_, latency)| {
+            1. / latency.as_secs_f64().max(f64::MIN_POSITIVE)
+        })
+        .ok()
+}
+
+// Hostnames are case insensitive, and may be given fully qualified with a trailing dot.
+fn host_matches(gateway_host: &nym_topology::NetworkAddress, host: &str) -> bool {
+    let normalize = |host: &str| host.trim_end_matches('.').to_ascii_lowercase();
+    normalize(&gateway_host.to_string()) == normalize(host)
+}
+
 impl IntoIterator for GatewayList {
     type Item = Gateway;
     type IntoIter = std::vec::IntoIter<Self::Item>;
@@ -475,3 +622,49 @@ impl From<GatewayType> for nym_vpn_api_client::types::GatewayType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_matching_ignores_case_and_trailing_dot() {
+        let hostname = nym_topology::NetworkAddress::Hostname("Gateway.Example.com".to_owned());
+        assert!(host_matches(&hostname, "gateway.example.com"));
+        assert!(host_matches(&hostname, "gateway.example.com."));
+        assert!(!host_matches(&hostname, "example.com"));
+
+        let ip = nym_topology::NetworkAddress::IpAddr("1.2.3.4".parse().unwrap());
+        assert!(host_matches(&ip, "1.2.3.4"));
+        assert!(!host_matches(&ip, "1.2.3.5"));
+    }
+
+    #[test]
+    fn choose_by_latency_favours_the_lower_round_trip_time() {
+        use rand::SeedableRng;
+
+        let measured = [
+            ("far", Duration::from_millis(500)),
+            ("near", Duration::from_millis(5)),
+        ];
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let near_picks = (0..1000)
+            .filter(|_| choose_by_latency(&measured, &mut rng).unwrap().0 == "near")
+            .count();
+        assert!(near_picks > 900, "near picked {near_picks} times");
+    }
+
+    #[test]
+    fn choose_by_latency_returns_the_measured_round_trip_time() {
+        let measured = [("only", Duration::from_millis(42))];
+        assert_eq!(
+            choose_by_latency(&measured, &mut rand::thread_rng()),
+            Some(&("only", Duration::from_millis(42)))
+        );
+        let unreachable: [(&str, Duration); 0] = [];
+        assert_eq!(
+            choose_by_latency(&unreachable, &mut rand::thread_rng()),
+            None
+        );
+    }
+}
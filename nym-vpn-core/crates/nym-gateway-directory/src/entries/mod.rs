@@ -1,4 +1,5 @@
 pub(crate) mod auth_addresses;
+pub(crate) mod continent;
 pub(crate) mod country;
 pub(crate) mod entry_point;
 pub(crate) mod exit_point;
@@ -15,12 +15,21 @@ pub enum Error {
         source: nym_sdk::mixnet::RecipientFormattingError,
     },
 
+    #[error("invalid entry point {input:?}: {reason}")]
+    InvalidEntryPoint { input: String, reason: String },
+
+    #[error("invalid exit point {input:?}: {reason}")]
+    InvalidExitPoint { input: String, reason: String },
+
     #[error(transparent)]
     ValidatorClientError(#[from] nym_validator_client::ValidatorClientError),
 
     #[error(transparent)]
     VpnApiClientError(#[from] nym_vpn_api_client::VpnApiClientError),
 
+    #[error("failed to create nym api client")]
+    CreateNymApiClient(#[source] nym_http_api_client::HttpClientError),
+
     #[error("failed to resolve gateway hostname: {hostname}: {source}")]
     FailedToDnsResolveGateway {
         hostname: String,
@@ -48,6 +57,15 @@ pub enum Error {
     #[error("no matching gateway found: {requested_identity}")]
     NoMatchingGateway { requested_identity: String },
 
+    #[error("no gateway found with host: {requested_host}")]
+    NoMatchingGatewayForHost { requested_host: String },
+
+    #[error("multiple gateways found with host {requested_host}: {identities:?}")]
+    AmbiguousGatewayHost {
+        requested_host: String,
+        identities: Vec<String>,
+    },
+
     #[error("no entry gateway available for location {requested_location}, available countries: {available_countries:?}")]
     NoMatchingEntryGatewayForLocation {
         requested_location: String,
@@ -60,10 +78,8 @@ pub enum Error {
         available_countries: Vec<String>,
     },
 
-    #[error("failed to select gateway based on low latency: {source}")]
-    FailedToSelectGatewayBasedOnLowLatency {
-        source: nym_client_core::error::ClientCoreError,
-    },
+    #[error("failed to select gateway based on low latency, none of them could be reached")]
+    FailedToSelectGatewayBasedOnLowLatency,
 
     #[error("no matching gateway found after selecting low latency: {requested_identity}")]
     NoMatchingGatewayAfterSelectingLowLatency { requested_identity: String },
@@ -76,6 +92,22 @@ pub enum Error {
 
     #[error("failed to lookup gateway ip for gateway {0}")]
     FailedToLookupIp(String),
+
+    #[error("failed to query gateway {gateway_identity}: {source}")]
+    GatewayUnreachable {
+        gateway_identity: String,
+        source: reqwest::Error,
+    },
+
+    #[error("gateway {gateway_identity} sent a malformed response to {url}: {source}")]
+    MalformedGatewayResponse {
+        gateway_identity: String,
+        url: String,
+        source: serde_json::Error,
+    },
+
+    #[error("failed to create http client: {0}")]
+    CreateHttpClient(#[source] reqwest::Error),
 }
 
 // Result type based on our error type
@@ -1,30 +1,58 @@
 // Copyright 2023-2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::{fmt, net::IpAddr};
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::Mutex,
+    time::Duration,
+};
 
-use nym_sdk::UserAgent;
+use nym_sdk::{mixnet::NodeIdentity, UserAgent};
 use nym_validator_client::{models::NymNodeDescription, nym_nodes::SkimmedNode, NymApiClient};
-use nym_vpn_api_client::types::{GatewayMinPerformance, Percent};
+use nym_vpn_api_client::{
+    response::NymDirectoryGatewaysResponse,
+    types::{GatewayMinPerformance, Percent},
+    ProxyConfig,
+};
 use rand::prelude::SliceRandom;
 use rand::thread_rng;
 use tracing::{debug, error, info, warn};
 use url::Url;
 
 use crate::{
+    capabilities::{
+        fetch_capabilities, CapabilitiesCache, GatewayCapabilities, DEFAULT_NYM_NODE_HTTP_PORT,
+    },
+    directory_cache::DirectoryCache,
     entries::{
-        country::Country,
+        country::CountriesResponse,
         gateway::{Gateway, GatewayList, GatewayType},
     },
     error::Result,
     Error,
 };
 
+// How long the capabilities reported by a gateway are reused before asking it again
+const CAPABILITIES_CACHE_TTL: Duration = Duration::from_secs(60);
+const CAPABILITIES_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub api_url: Url,
     pub nym_vpn_api_url: Option<Url>,
     pub min_gateway_performance: Option<GatewayMinPerformance>,
+    /// Proxy for the requests to the nym-api and nym-vpn-api. This only affects the lookups done
+    /// before the tunnel is up.
+    pub proxy: Option<ProxyConfig>,
+    /// Directory where the last successful gateway lookup is cached.
+    pub directory_cache_path: Option<PathBuf>,
+    /// How long a cached gateway lookup is served for. `None` disables the cache.
+    pub directory_cache_ttl: Option<Duration>,
+    /// Port of the http api of the nym-node the gateways run on, which their capabilities are
+    /// asked from. Defaults to the port nym-node listens on out of the box.
+    pub nym_node_http_port: Option<u16>,
 }
 
 impl Default for Config {
@@ -69,6 +97,10 @@ impl Config {
             api_url: default_api_url,
             nym_vpn_api_url: Some(default_nym_vpn_api_url),
             min_gateway_performance: None,
+            proxy: None,
+            directory_cache_path: None,
+            directory_cache_ttl: None,
+            nym_node_http_port: None,
         }
     }
 
@@ -88,6 +120,10 @@ impl Config {
             api_url,
             nym_vpn_api_url,
             min_gateway_performance: None,
+            proxy: None,
+            directory_cache_path: None,
+            directory_cache_ttl: None,
+            nym_node_http_port: None,
         }
     }
 
@@ -116,26 +152,98 @@ impl Config {
         self.min_gateway_performance = Some(min_gateway_performance);
         self
     }
+
+    pub fn with_proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    pub fn with_directory_cache_path(mut self, directory_cache_path: PathBuf) -> Self {
+        self.directory_cache_path = Some(directory_cache_path);
+        self
+    }
+
+    pub fn with_directory_cache_ttl(mut self, directory_cache_ttl: Duration) -> Self {
+        self.directory_cache_ttl = Some(directory_cache_ttl);
+        self
+    }
+
+    pub fn with_nym_node_http_port(mut self, nym_node_http_port: u16) -> Self {
+        self.nym_node_http_port = Some(nym_node_http_port);
+        self
+    }
+}
+
+fn new_nym_api_client_with_proxy(
+    api_url: Url,
+    user_agent: &UserAgent,
+    proxy: &ProxyConfig,
+) -> Result<NymApiClient> {
+    let reqwest_builder = reqwest::ClientBuilder::new().proxy(proxy.to_reqwest_proxy()?);
+    let nym_api = nym_http_api_client::Client::builder(api_url)
+        .and_then(|builder| {
+            builder
+                .with_user_agent(user_agent.clone())
+                .with_reqwest_builder(reqwest_builder)
+                .build()
+        })
+        .map_err(Error::CreateNymApiClient)?;
+    Ok(NymApiClient { nym_api })
 }
 
 pub struct GatewayClient {
     api_client: NymApiClient,
+    api_url: Url,
     nym_vpn_api_client: Option<nym_vpn_api_client::VpnApiClient>,
     min_gateway_performance: Option<GatewayMinPerformance>,
+    directory_cache: Option<DirectoryCache>,
+    http_client: reqwest::Client,
+    nym_node_http_port: u16,
+    capabilities_cache: CapabilitiesCache,
+    nym_api_clock_skew: Mutex<Option<i64>>,
 }
 
 impl GatewayClient {
     pub fn new(config: Config, user_agent: UserAgent) -> Result<Self> {
-        let api_client = NymApiClient::new_with_user_agent(config.api_url, user_agent.clone());
+        let api_url = config.api_url.clone();
+        let api_client = match config.proxy {
+            Some(ref proxy) => new_nym_api_client_with_proxy(config.api_url, &user_agent, proxy)?,
+            None => NymApiClient::new_with_user_agent(config.api_url, user_agent.clone()),
+        };
+        // The cache only holds nym-vpn-api responses, keyed by its URL
+        let directory_cache = config
+            .directory_cache_path
+            .zip(config.directory_cache_ttl)
+            .zip(config.nym_vpn_api_url.as_ref())
+            .map(|((path, ttl), api_url)| DirectoryCache::new(&path, ttl, api_url));
         let nym_vpn_api_client = config
             .nym_vpn_api_url
-            .map(|url| nym_vpn_api_client::VpnApiClient::new(url, user_agent.clone()))
+            .map(|url| {
+                nym_vpn_api_client::VpnApiClient::new_with_proxy(
+                    url,
+                    user_agent.clone(),
+                    config.proxy.as_ref(),
+                )
+            })
             .transpose()?;
+        let mut http_client = reqwest::ClientBuilder::new().timeout(CAPABILITIES_REQUEST_TIMEOUT);
+        if let Some(ref proxy) = config.proxy {
+            http_client = http_client.proxy(proxy.to_reqwest_proxy()?);
+        }
+        let http_client = http_client.build().map_err(Error::CreateHttpClient)?;
 
         Ok(GatewayClient {
             api_client,
+            api_url,
             nym_vpn_api_client,
             min_gateway_performance: config.min_gateway_performance,
+            directory_cache,
+            http_client,
+            nym_node_http_port: config
+                .nym_node_http_port
+                .unwrap_or(DEFAULT_NYM_NODE_HTTP_PORT),
+            capabilities_cache: CapabilitiesCache::new(CAPABILITIES_CACHE_TTL),
+            nym_api_clock_skew: Mutex::new(None),
         })
     }
 
@@ -151,6 +259,29 @@ impl GatewayClient {
             .and_then(|min_performance| min_performance.vpn_min_performance)
     }
 
+    /// Number of seconds the local clock is ahead of the directory clock, or behind if negative,
+    /// as observed in the last response from the nym-vpn-api, or from the nym-api when operating
+    /// without the former.
+    pub fn clock_skew_seconds(&self) -> Option<i64> {
+        self.nym_vpn_api_client
+            .as_ref()
+            .and_then(|client| client.clock_skew_seconds())
+            .or_else(|| *self.nym_api_clock_skew.lock().unwrap())
+    }
+
+    // The nym-api client doesn't expose the headers of its responses, so ask for them separately,
+    // alongside the lookup rather than before it.
+    async fn measure_nym_api_clock_skew(&self) {
+        match self.http_client.head(self.api_url.clone()).send().await {
+            Ok(response) => {
+                if let Some(skew) = clock_skew_of(&response) {
+                    *self.nym_api_clock_skew.lock().unwrap() = Some(skew);
+                }
+            }
+            Err(err) => debug!("Failed to measure the clock skew against the nym-api: {err}"),
+        }
+    }
+
     async fn lookup_described_nodes(&self) -> Result<Vec<NymNodeDescription>> {
         info!("Fetching all described nodes from nym-api...");
         self.api_client
@@ -170,7 +301,10 @@ impl GatewayClient {
     pub async fn lookup_low_latency_entry_gateway(&self) -> Result<Gateway> {
         debug!("Fetching low latency entry gateway...");
         let gateways = self.lookup_gateways(GatewayType::MixnetEntry).await?;
-        gateways.random_low_latency_gateway().await
+        gateways
+            .random_low_latency_gateway(&mut rand::rngs::OsRng)
+            .await
+            .map(|(gateway, _)| gateway)
     }
 
     pub async fn lookup_gateway_ip_from_nym_api(&self, gateway_identity: &str) -> Result<IpAddr> {
@@ -220,6 +354,27 @@ impl GatewayClient {
         }
     }
 
+    /// Asks the gateway itself which services it runs, e.g. to tell ahead of connecting whether it
+    /// can be used for a WireGuard tunnel.
+    pub async fn gateway_capabilities(
+        &self,
+        gateway_identity: &NodeIdentity,
+    ) -> Result<GatewayCapabilities> {
+        let identity = gateway_identity.to_base58_string();
+        if let Some(capabilities) = self.capabilities_cache.get(&identity) {
+            return Ok(capabilities);
+        }
+
+        let ip = self.lookup_gateway_ip(&identity).await?;
+        let node_addr = SocketAddr::new(ip, self.nym_node_http_port);
+        info!("Fetching capabilities of gateway {identity} at {node_addr}...");
+        let capabilities =
+            fetch_capabilities(&self.http_client, gateway_identity, node_addr).await?;
+        self.capabilities_cache
+            .insert(identity, capabilities.clone());
+        Ok(capabilities)
+    }
+
     pub async fn lookup_all_gateways_from_nym_api(&self) -> Result<GatewayList> {
         let mut gateways = self
             .lookup_described_nodes()
@@ -312,10 +467,19 @@ impl GatewayClient {
 
     pub async fn lookup_gateways(&self, gw_type: GatewayType) -> Result<GatewayList> {
         if let Some(nym_vpn_api_client) = &self.nym_vpn_api_client {
-            info!("Fetching gateways from nym-vpn-api...");
-            let gateways: Vec<_> = nym_vpn_api_client
-                .get_gateways_by_type(gw_type.into(), self.min_gateway_performance.clone())
-                .await?
+            let response = match &self.directory_cache {
+                Some(cache) => {
+                    self.lookup_gateways_with_cache(nym_vpn_api_client, cache, gw_type)
+                        .await?
+                }
+                None => {
+                    info!("Fetching gateways from nym-vpn-api...");
+                    nym_vpn_api_client
+                        .get_gateways_by_type(gw_type.into(), self.min_gateway_performance.clone())
+                        .await?
+                }
+            };
+            let gateways: Vec<_> = response
                 .into_iter()
                 .filter_map(|gw| {
                     Gateway::try_from(gw)
@@ -326,28 +490,120 @@ impl GatewayClient {
             Ok(GatewayList::new(gateways))
         } else {
             warn!("OPERATING IN FALLBACK MODE WITHOUT NYM-VPN-API!");
-            self.lookup_gateways_from_nym_api(gw_type).await
+            let (gateways, ()) = futures::join!(
+                self.lookup_gateways_from_nym_api(gw_type),
+                self.measure_nym_api_clock_skew()
+            );
+            gateways
         }
     }
 
-    pub async fn lookup_countries(&self, gw_type: GatewayType) -> Result<Vec<Country>> {
-        if let Some(nym_vpn_api_client) = &self.nym_vpn_api_client {
+    /// Whether the gateway is listed by the nym-vpn-api when ignoring the performance threshold,
+    /// to tell a gateway below the threshold apart from one that isn't listed at all. Without a
+    /// threshold or without the nym-vpn-api this is `false`.
+    pub async fn is_listed_below_performance_threshold(
+        &self,
+        gw_type: GatewayType,
+        identity: &NodeIdentity,
+    ) -> bool {
+        let (Some(nym_vpn_api_client), Some(_)) =
+            (&self.nym_vpn_api_client, &self.min_gateway_performance)
+        else {
+            return false;
+        };
+        match nym_vpn_api_client
+            .get_gateways_by_type(gw_type.into(), None)
+            .await
+        {
+            Ok(gateways) => {
+                let identity = identity.to_base58_string();
+                gateways.into_iter().any(|gw| gw.identity_key == identity)
+            }
+            Err(err) => {
+                warn!("Failed to look up the gateways without performance threshold: {err}");
+                false
+            }
+        }
+    }
+
+    // Serves a fresh enough cache entry right away and refreshes it in the background, otherwise
+    // fetches from the nym-vpn-api and caches the response.
+    async fn lookup_gateways_with_cache(
+        &self,
+        nym_vpn_api_client: &nym_vpn_api_client::VpnApiClient,
+        cache: &DirectoryCache,
+        gw_type: GatewayType,
+    ) -> Result<NymDirectoryGatewaysResponse> {
+        if let Some((gateways, age)) = cache.load(&gw_type, &self.min_gateway_performance) {
+            info!(
+                "Using cached {gw_type} gateways, age: {}s, refreshing in the background",
+                age.as_secs()
+            );
+            let nym_vpn_api_client = nym_vpn_api_client.clone();
+            let cache = cache.clone();
+            let min_gateway_performance = self.min_gateway_performance.clone();
+            tokio::spawn(async move {
+                match nym_vpn_api_client
+                    .get_gateways_by_type(gw_type.clone().into(), min_gateway_performance.clone())
+                    .await
+                {
+                    Ok(gateways) => cache.store(&gw_type, &min_gateway_performance, &gateways),
+                    Err(err) => warn!("Failed to refresh cached {gw_type} gateways: {err}"),
+                }
+            });
+            return Ok(gateways);
+        }
+
+        info!("Fetching gateways from nym-vpn-api...");
+        let gateways = nym_vpn_api_client
+            .get_gateways_by_type(gw_type.clone().into(), self.min_gateway_performance.clone())
+            .await?;
+        cache.store(&gw_type, &self.min_gateway_performance, &gateways);
+        Ok(gateways)
+    }
+
+    pub async fn lookup_countries(&self, gw_type: GatewayType) -> Result<CountriesResponse> {
+        let countries = if let Some(nym_vpn_api_client) = &self.nym_vpn_api_client {
             info!("Fetching entry countries from nym-vpn-api...");
-            Ok(nym_vpn_api_client
+            nym_vpn_api_client
                 .get_gateway_countries_by_type(gw_type.into(), self.min_gateway_performance.clone())
                 .await?
-                .into_iter()
-                .map(Country::from)
-                .collect())
+                .into()
         } else {
             warn!("OPERATING IN FALLBACK MODE WITHOUT NYM-VPN-API!");
-            self.lookup_gateways_from_nym_api(gw_type)
-                .await
-                .map(GatewayList::into_countries)
+            // Gateways that fail to parse are already dropped, and logged, during the lookup
+            CountriesResponse {
+                locations: self
+                    .lookup_gateways_from_nym_api(gw_type)
+                    .await?
+                    .into_countries(),
+                skipped: 0,
+            }
+        };
+        if countries.skipped > 0 {
+            warn!(
+                "Skipped {} malformed countries in the directory response",
+                countries.skipped
+            );
         }
+        Ok(countries)
     }
 }
 
+// Number of seconds the local clock is ahead of the server clock, based on the `Date` header.
+fn clock_skew_of(response: &reqwest::Response) -> Option<i64> {
+    let server_time = response
+        .headers()
+        .get(reqwest::header::DATE)
+        .and_then(|date| date.to_str().ok())
+        .and_then(|date| chrono::DateTime::parse_from_rfc2822(date).ok())?;
+    Some(
+        chrono::Utc::now()
+            .signed_duration_since(server_time)
+            .num_seconds(),
+    )
+}
+
 // Append the performance to the gateways. This is a temporary hack until the nymvpn.com endpoints
 // are updated to also include this field.
 fn append_performance(
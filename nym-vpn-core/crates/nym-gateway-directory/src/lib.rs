@@ -1,18 +1,25 @@
 // Copyright 2023 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
+mod capabilities;
+mod directory_cache;
 mod entries;
 mod error;
 mod gateway_client;
 mod helpers;
 
 pub use nym_sdk::mixnet::{NodeIdentity, Recipient};
-pub use nym_vpn_api_client::types::{GatewayMinPerformance, Percent};
+pub use nym_vpn_api_client::{
+    types::{GatewayMinPerformance, Percent},
+    ProxyConfig, ProxyCredentials,
+};
 
 pub use crate::{
+    capabilities::GatewayCapabilities,
     entries::{
         auth_addresses::{AuthAddress, AuthAddresses},
-        country::Country,
+        continent::Continent,
+        country::{CountriesResponse, Country},
         entry_point::EntryPoint,
         exit_point::ExitPoint,
         gateway::{Entry, Exit, Gateway, GatewayList, GatewayType, Location, Probe, ProbeOutcome},
@@ -10,11 +10,10 @@ use nym_connection_monitor::{
     ConnectionStatusEvent, IcmpBeaconReply, Icmpv6BeaconReply,
 };
 use nym_gateway_directory::IpPacketRouterAddress;
-use nym_ip_packet_client::SharedMixnetClient;
 use nym_ip_packet_requests::{codec::MultiIpPacketCodec, IpPair};
 use nym_sdk::mixnet::{InputMessage, Recipient};
 use nym_task::connections::TransmissionLane;
-use pnet_packet::Packet;
+use pnet_packet::{ipv4::Ipv4Packet, ipv6::Ipv6Packet, Packet};
 
 use crate::Result;
 
@@ -22,13 +21,12 @@ pub fn icmp_identifier() -> u16 {
     8475
 }
 
-pub async fn send_ping_v4(
-    shared_mixnet_client: SharedMixnetClient,
+pub fn create_ping_v4_message(
     our_ips: IpPair,
     sequence_number: u16,
     destination: Ipv4Addr,
     exit_router_address: IpPacketRouterAddress,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<InputMessage> {
     let icmp_identifier = icmp_identifier();
     let icmp_echo_request = create_icmpv4_echo_request(sequence_number, icmp_identifier)?;
     let ipv4_packet = wrap_icmp_in_ipv4(icmp_echo_request, our_ips.ipv4, destination)?;
@@ -38,19 +36,15 @@ pub async fn send_ping_v4(
         MultiIpPacketCodec::bundle_one_packet(ipv4_packet.packet().to_vec().into());
 
     // Wrap into a mixnet input message addressed to the IPR
-    let mixnet_message = create_input_message(exit_router_address.0, bundled_packet)?;
-
-    shared_mixnet_client.send(mixnet_message).await?;
-    Ok(())
+    Ok(create_input_message(exit_router_address.0, bundled_packet)?)
 }
 
-pub async fn send_ping_v6(
-    shared_mixnet_client: SharedMixnetClient,
+pub fn create_ping_v6_message(
     our_ips: IpPair,
     sequence_number: u16,
     destination: Ipv6Addr,
     exit_router_address: IpPacketRouterAddress,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<InputMessage> {
     let icmp_identifier = icmp_identifier();
     let icmp_echo_request = create_icmpv6_echo_request(
         sequence_number,
@@ -65,14 +59,13 @@ pub async fn send_ping_v6(
         MultiIpPacketCodec::bundle_one_packet(ipv6_packet.packet().to_vec().into());
 
     // Wrap into a mixnet input message addressed to the IPR
-    let mixnet_message = create_input_message(exit_router_address.0, bundled_packet)?;
-
-    // Send across the mixnet
-    shared_mixnet_client.send(mixnet_message).await?;
-    Ok(())
+    Ok(create_input_message(exit_router_address.0, bundled_packet)?)
 }
 
-fn create_input_message(recipient: Recipient, bundled_packets: Bytes) -> Result<InputMessage> {
+pub(crate) fn create_input_message(
+    recipient: Recipient,
+    bundled_packets: Bytes,
+) -> Result<InputMessage> {
     let packet =
         nym_ip_packet_requests::request::IpPacketRequest::new_data_request(bundled_packets)
             .to_bytes()?;
@@ -118,3 +111,23 @@ pub fn check_for_icmp_beacon_reply(
 
     None
 }
+
+/// Sequence number of an ICMP or ICMPv6 echo reply, used to match the reply with the echo request
+/// it answers. Expects a packet already identified as a reply by [`check_for_icmp_beacon_reply`].
+pub fn icmp_echo_reply_sequence_number(packet: &Bytes) -> Option<u16> {
+    match packet.first()? >> 4 {
+        4 => {
+            let ipv4_packet = Ipv4Packet::new(packet)?;
+            let echo_reply =
+                pnet_packet::icmp::echo_reply::EchoReplyPacket::new(ipv4_packet.payload())?;
+            Some(echo_reply.get_sequence_number())
+        }
+        6 => {
+            let ipv6_packet = Ipv6Packet::new(packet)?;
+            let echo_reply =
+                pnet_packet::icmpv6::echo_reply::EchoReplyPacket::new(ipv6_packet.payload())?;
+            Some(echo_reply.get_sequence_number())
+        }
+        _ => None,
+    }
+}
@@ -4,14 +4,14 @@
 use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use anyhow::bail;
+use anyhow::{anyhow, bail};
 use base64::{engine::general_purpose, Engine as _};
 use bytes::BytesMut;
 use dns_lookup::lookup_host;
-use futures::StreamExt;
+use futures::{FutureExt as _, StreamExt};
 use netstack::{NetstackCall as _, NetstackCallImpl};
 use nym_authenticator_client::ClientMessage;
 use nym_authenticator_requests::v4::{
@@ -30,25 +30,34 @@ use nym_ip_packet_requests::{
     response::{DataResponse, InfoLevel, IpPacketResponse, IpPacketResponseData},
     IpPair,
 };
-use nym_sdk::mixnet::{MixnetClient, MixnetClientBuilder, ReconstructedMessage};
+use nym_sdk::mixnet::{
+    MixnetClient, MixnetClientBuilder, MixnetMessageSender, ReconstructedMessage,
+};
 use nym_wireguard_types::PeerPublicKey;
-use tokio::sync::Mutex;
+use tokio::{
+    sync::{mpsc, Mutex, Semaphore},
+    task::JoinSet,
+};
 use tokio_util::codec::Decoder;
 use tracing::*;
 use types::WgProbeResults;
 
 use crate::{
-    icmp::{check_for_icmp_beacon_reply, icmp_identifier, send_ping_v4, send_ping_v6},
+    icmp::{
+        check_for_icmp_beacon_reply, create_ping_v4_message, create_ping_v6_message,
+        icmp_echo_reply_sequence_number, icmp_identifier,
+    },
     types::{Entry, Exit},
 };
 
 mod error;
 mod icmp;
+mod mtu;
 mod netstack;
 mod types;
 
 pub use error::{Error, Result};
-pub use types::{IpPingReplies, ProbeOutcome, ProbeResult};
+pub use types::{IpPingReplies, IpPingResults, PingOpts, PingResult, ProbeOutcome, ProbeResult};
 
 pub async fn fetch_gateways() -> anyhow::Result<GatewayList> {
     lookup_gateways().await
@@ -59,12 +68,107 @@ pub async fn fetch_gateways_with_ipr() -> anyhow::Result<GatewayList> {
 }
 
 pub async fn probe(entry_point: EntryPoint) -> anyhow::Result<ProbeResult> {
+    probe_with_opts(entry_point, PingOpts::default()).await
+}
+
+/// Same as [`probe`], but with control over the pings sent through the gateways.
+pub async fn probe_with_opts(
+    entry_point: EntryPoint,
+    ping_opts: PingOpts,
+) -> anyhow::Result<ProbeResult> {
     // Setup the entry gateways
     let gateways = lookup_gateways().await?;
-    let entry_gateway = entry_point.lookup_gateway(&gateways).await?;
+    probe_gateway(entry_point, &gateways, ping_opts).await
+}
+
+/// Probe a list of gateways, running at most `concurrency` probes at the same time.
+///
+/// The gateway directory is only queried once for the whole batch. A failing probe is reported
+/// as an error for that gateway and does not abort the rest of the batch. The results are
+/// returned in the order the probes completed.
+pub async fn probe_many(
+    gateways: Vec<EntryPoint>,
+    concurrency: usize,
+    ping_opts: PingOpts,
+) -> Vec<(EntryPoint, anyhow::Result<ProbeResult>)> {
+    let mut results_rx = probe_many_streaming(gateways, concurrency, ping_opts);
+    let mut results = Vec::new();
+    while let Some(result) = results_rx.recv().await {
+        results.push(result);
+    }
+    results
+}
+
+/// Same as [`probe_many`], but each result is sent on the returned channel as soon as the probe
+/// completes. The channel is closed once all gateways have been probed.
+pub fn probe_many_streaming(
+    gateways: Vec<EntryPoint>,
+    concurrency: usize,
+    ping_opts: PingOpts,
+) -> mpsc::UnboundedReceiver<(EntryPoint, anyhow::Result<ProbeResult>)> {
+    let (results_tx, results_rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let directory = match lookup_gateways().await {
+            Ok(directory) => Arc::new(directory),
+            Err(err) => {
+                error!("Failed to lookup gateways: {err}");
+                for entry_point in gateways {
+                    let result = Err(anyhow!("failed to lookup gateways: {err}"));
+                    _ = results_tx.send((entry_point, result));
+                }
+                return;
+            }
+        };
+
+        let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+        let mut probes = JoinSet::new();
+
+        for entry_point in gateways {
+            let Ok(permit) = semaphore.clone().acquire_owned().await else {
+                break;
+            };
+            let directory = directory.clone();
+            let results_tx = results_tx.clone();
+
+            probes.spawn(async move {
+                // Catch panics so that a single misbehaving gateway is reported like any other
+                // failure rather than silently dropped from the results.
+                let result = std::panic::AssertUnwindSafe(probe_gateway(
+                    entry_point.clone(),
+                    &directory,
+                    ping_opts,
+                ))
+                .catch_unwind()
+                .await
+                .unwrap_or_else(|_| Err(anyhow!("probe panicked")));
+                drop(permit);
+
+                if let Err(err) = &result {
+                    warn!("Failed to probe {entry_point}: {err}");
+                }
+                _ = results_tx.send((entry_point, result));
+            });
+        }
+
+        while probes.join_next().await.is_some() {}
+    });
+
+    results_rx
+}
+
+async fn probe_gateway(
+    entry_point: EntryPoint,
+    gateways: &GatewayList,
+    ping_opts: PingOpts,
+) -> anyhow::Result<ProbeResult> {
+    let entry_gateway = entry_point.lookup_gateway(gateways).await?;
     let exit_router_address = entry_gateway.ipr_address;
     let authenticator = entry_gateway.authenticator_address;
-    let gateway_host = entry_gateway.host.clone().unwrap();
+    let gateway_host = entry_gateway
+        .host
+        .clone()
+        .ok_or(anyhow!("gateway has no host: {}", entry_gateway.identity()))?;
     let entry_gateway_id = entry_gateway.identity();
 
     info!("Probing gateway: {entry_gateway:?}");
@@ -89,6 +193,8 @@ pub async fn probe(entry_point: EntryPoint) -> anyhow::Result<ProbeResult> {
                     as_exit: None,
                     wg: None,
                 },
+                max_mtu: None,
+                ping: None,
             });
         }
     };
@@ -103,7 +209,7 @@ pub async fn probe(entry_point: EntryPoint) -> anyhow::Result<ProbeResult> {
 
     // Now that we have a connected mixnet client, we can start pinging
     let shared_mixnet_client = SharedMixnetClient::from_shared(&shared_client);
-    let outcome = do_ping(shared_mixnet_client.clone(), exit_router_address).await;
+    let outcome = do_ping(shared_mixnet_client.clone(), exit_router_address, ping_opts).await;
 
     let wg_outcome = if let Some(authenticator) = authenticator {
         wg_probe(authenticator, shared_client, &gateway_host)
@@ -117,11 +223,13 @@ pub async fn probe(entry_point: EntryPoint) -> anyhow::Result<ProbeResult> {
     mixnet_client.disconnect().await;
 
     // Disconnect the mixnet client gracefully
-    outcome.map(|mut outcome| {
+    outcome.map(|(mut outcome, ping, max_mtu)| {
         outcome.wg = Some(wg_outcome);
         ProbeResult {
             gateway: entry_gateway.clone(),
             outcome,
+            max_mtu,
+            ping,
         }
     })
 }
@@ -135,7 +243,7 @@ async fn wg_probe(
         nym_authenticator_client::SharedMixnetClient::from_shared(&shared_mixnet_client);
     let mut auth_client = nym_authenticator_client::AuthClient::new(auth_shared_client).await;
 
-    let mut rng = rand::thread_rng();
+    let mut rng = rand::rngs::OsRng;
     let private_key = nym_crypto::asymmetric::encryption::PrivateKey::new(&mut rng);
     let public_key = private_key.public_key();
 
@@ -294,30 +402,40 @@ fn mixnet_debug_config() -> nym_client_core::config::DebugConfig {
 async fn do_ping(
     shared_mixnet_client: SharedMixnetClient,
     exit_router_address: Option<IpPacketRouterAddress>,
-) -> anyhow::Result<ProbeOutcome> {
+    ping_opts: PingOpts,
+) -> anyhow::Result<(ProbeOutcome, Option<IpPingResults>, Option<u16>)> {
     // Step 1: confirm that the entry gateway is routing our mixnet traffic
     info!("Sending mixnet ping to ourselves to verify mixnet connection");
     if self_ping_and_wait(
         shared_mixnet_client.nym_address().await,
         shared_mixnet_client.inner(),
+        ping_opts.self_ping,
     )
     .await
     .is_err()
     {
-        return Ok(ProbeOutcome {
-            as_entry: Entry::fail_to_route(),
-            as_exit: None,
-            wg: None,
-        });
+        return Ok((
+            ProbeOutcome {
+                as_entry: Entry::fail_to_route(),
+                as_exit: None,
+                wg: None,
+            },
+            None,
+            None,
+        ));
     }
     info!("Successfully mixnet pinged ourselves");
 
     let Some(exit_router_address) = exit_router_address else {
-        return Ok(ProbeOutcome {
-            as_entry: Entry::success(),
-            as_exit: None,
-            wg: None,
-        });
+        return Ok((
+            ProbeOutcome {
+                as_entry: Entry::success(),
+                as_exit: None,
+                wg: None,
+            },
+            None,
+            None,
+        ));
     };
 
     // Step 2: connect to the exit gateway
@@ -327,82 +445,129 @@ async fn do_ping(
     );
     let mut ipr_client = IprClientConnect::new(shared_mixnet_client.clone()).await;
     let Ok(our_ips) = ipr_client.connect(exit_router_address.0, None).await else {
-        return Ok(ProbeOutcome {
-            as_entry: Entry::success(),
-            as_exit: Some(Exit::fail_to_connect()),
-            wg: None,
-        });
+        return Ok((
+            ProbeOutcome {
+                as_entry: Entry::success(),
+                as_exit: Some(Exit::fail_to_connect()),
+                wg: None,
+            },
+            None,
+            None,
+        ));
     };
     info!("Successfully connected to exit gateway");
     info!("Using mixnet VPN IP addresses: {our_ips}");
 
     // Step 3: perform ICMP connectivity checks for the exit gateway
-    send_icmp_pings(shared_mixnet_client.clone(), our_ips, exit_router_address).await?;
-    listen_for_icmp_ping_replies(shared_mixnet_client.clone(), our_ips).await
+    let ping_results = ping_exit_gateway(
+        shared_mixnet_client.clone(),
+        our_ips,
+        exit_router_address,
+        ping_opts,
+    )
+    .await?;
+    let outcome = ProbeOutcome {
+        as_entry: Entry::success(),
+        as_exit: Some(Exit::from_ping_results(&ping_results)),
+        wg: None,
+    };
+
+    // Step 4: find the largest packet that makes it through the exit gateway
+    let max_mtu = mtu::probe_mtu(
+        shared_mixnet_client.clone(),
+        our_ips,
+        exit_router_address,
+        ping_opts.max_mtu,
+    )
+    .await
+    .unwrap_or_default();
+
+    Ok((outcome, Some(ping_results), max_mtu))
 }
 
 async fn send_icmp_pings(
-    shared_mixnet_client: SharedMixnetClient,
+    mixnet_client: &MixnetClient,
     our_ips: IpPair,
+    sequence_number: u16,
     exit_router_address: IpPacketRouterAddress,
 ) -> anyhow::Result<()> {
+    // HACK: there is hidden hardcoded assumption about these IPs inside
+    // `check_for_icmp_beacon_reply`
     let ipr_tun_ip_v4 = Ipv4Addr::new(10, 0, 0, 1);
     let ipr_tun_ip_v6 = Ipv6Addr::new(0x2001, 0xdb8, 0xa160, 0, 0, 0, 0, 0x1);
     let external_ip_v4 = Ipv4Addr::new(8, 8, 8, 8);
     let external_ip_v6 = Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888);
-    info!("Sending ICMP echo requests to: {ipr_tun_ip_v4}, {ipr_tun_ip_v6}, {external_ip_v4}, {external_ip_v6}");
-    for ii in 0..10 {
-        // HACK: there is hidden hardcoded assumption about these IPs inside
-        // `check_for_icmp_beacon_reply`
-        send_ping_v4(
-            shared_mixnet_client.clone(),
-            our_ips,
-            ii,
-            ipr_tun_ip_v4,
-            exit_router_address,
-        )
-        .await?;
-        send_ping_v4(
-            shared_mixnet_client.clone(),
-            our_ips,
-            ii,
-            external_ip_v4,
-            exit_router_address,
-        )
-        .await?;
-        send_ping_v6(
-            shared_mixnet_client.clone(),
-            our_ips,
-            ii,
-            ipr_tun_ip_v6,
-            exit_router_address,
-        )
-        .await?;
-        send_ping_v6(
-            shared_mixnet_client.clone(),
-            our_ips,
-            ii,
-            external_ip_v6,
-            exit_router_address,
-        )
-        .await?;
+    for destination in [ipr_tun_ip_v4, external_ip_v4] {
+        let message =
+            create_ping_v4_message(our_ips, sequence_number, destination, exit_router_address)?;
+        mixnet_client.send(message).await?;
+    }
+    for destination in [ipr_tun_ip_v6, external_ip_v6] {
+        let message =
+            create_ping_v6_message(our_ips, sequence_number, destination, exit_router_address)?;
+        mixnet_client.send(message).await?;
     }
     Ok(())
 }
 
-async fn listen_for_icmp_ping_replies(
+async fn ping_exit_gateway(
     shared_mixnet_client: SharedMixnetClient,
     our_ips: IpPair,
-) -> anyhow::Result<ProbeOutcome> {
+    exit_router_address: IpPacketRouterAddress,
+    ping_opts: PingOpts,
+) -> anyhow::Result<IpPingResults> {
     // HACK: take it out of the shared mixnet client
     let mut mixnet_client = shared_mixnet_client.inner().lock().await.take().unwrap();
+
+    let result =
+        send_and_collect_icmp_pings(&mut mixnet_client, our_ips, exit_router_address, ping_opts)
+            .await;
+
+    // HACK: put it back in the shared mixnet client, so it can be properly disconnected
+    shared_mixnet_client
+        .inner()
+        .lock()
+        .await
+        .replace(mixnet_client);
+
+    result
+}
+
+async fn send_and_collect_icmp_pings(
+    mixnet_client: &mut MixnetClient,
+    our_ips: IpPair,
+    exit_router_address: IpPacketRouterAddress,
+    ping_opts: PingOpts,
+) -> anyhow::Result<IpPingResults> {
+    info!(
+        "Sending {} rounds of ICMP echo requests through the exit gateway",
+        ping_opts.count
+    );
     let mut multi_ip_packet_decoder =
         MultiIpPacketCodec::new(nym_ip_packet_requests::codec::BUFFER_TIMEOUT);
-    let mut registered_replies = IpPingReplies::new();
+    let mut registered_replies = IpPingReplies::new(ping_opts.count);
+    let mut sent_at = Vec::with_capacity(ping_opts.count.into());
+
+    // A zero interval would panic, and sending all the pings in one go is close enough anyway
+    let mut send_interval = tokio::time::interval(ping_opts.interval.max(Duration::from_millis(1)));
+    let replies_timeout = tokio::time::sleep(ping_opts.timeout);
+    tokio::pin!(replies_timeout);
 
     loop {
+        let all_sent = sent_at.len() == usize::from(ping_opts.count);
         tokio::select! {
-            _ = tokio::time::sleep(Duration::from_secs(2)) => {
+            _ = send_interval.tick(), if !all_sent => {
+                let sequence_number = sent_at.len() as u16;
+                send_icmp_pings(mixnet_client, our_ips, sequence_number, exit_router_address)
+                    .await?;
+                sent_at.push(Instant::now());
+                if sent_at.len() == usize::from(ping_opts.count) {
+                    replies_timeout
+                        .as_mut()
+                        .reset(tokio::time::Instant::now() + ping_opts.timeout);
+                }
+            }
+            _ = &mut replies_timeout, if all_sent => {
                 info!("Finished waiting for ICMP echo reply from exit gateway");
                 break;
             }
@@ -414,34 +579,25 @@ async fn listen_for_icmp_ping_replies(
                 // IP packets are bundled together in a mixnet message
                 let mut bytes = BytesMut::from(&*data_response.ip_packet);
                 while let Ok(Some(packet)) = multi_ip_packet_decoder.decode(&mut bytes) {
-                    if let Some(event) = check_for_icmp_beacon_reply(&packet, icmp_identifier(), our_ips) {
-                        info!("Received ICMP echo reply from exit gateway");
-                        info!("Connection event: {:?}", event);
-                        registered_replies.register_event(&event);
-                    }
+                    let Some(event) =
+                        check_for_icmp_beacon_reply(&packet, icmp_identifier(), our_ips)
+                    else {
+                        continue;
+                    };
+                    let Some(sequence_number) = icmp_echo_reply_sequence_number(&packet) else {
+                        continue;
+                    };
+                    let Some(sent) = sent_at.get(usize::from(sequence_number)) else {
+                        continue;
+                    };
+                    debug!("Received ICMP echo reply {sequence_number}: {event:?}");
+                    registered_replies.register_event(&event, sequence_number, sent.elapsed());
                 }
             }
         }
     }
 
-    // HACK: put it back in the shared mixnet client, so it can be properly disconnected
-    shared_mixnet_client
-        .inner()
-        .lock()
-        .await
-        .replace(mixnet_client);
-
-    Ok(ProbeOutcome {
-        as_entry: Entry::success(),
-        as_exit: Some(Exit {
-            can_connect: true,
-            can_route_ip_v4: registered_replies.ipr_tun_ip_v4,
-            can_route_ip_external_v4: registered_replies.external_ip_v4,
-            can_route_ip_v6: registered_replies.ipr_tun_ip_v6,
-            can_route_ip_external_v6: registered_replies.external_ip_v6,
-        }),
-        wg: None,
-    })
+    Ok(registered_replies.to_results())
 }
 
 fn unpack_data_response(reconstructed_message: &ReconstructedMessage) -> Option<DataResponse> {
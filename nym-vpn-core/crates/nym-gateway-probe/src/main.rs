@@ -7,15 +7,9 @@ mod run;
 #[cfg(unix)]
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    match run::run().await {
-        Ok(ref result) => {
-            let json = serde_json::to_string_pretty(result)?;
-            println!("{}", json);
-        }
-        Err(err) => {
-            eprintln!("An error occurred: {err}");
-            std::process::exit(1)
-        }
+    if let Err(err) = run::run().await {
+        eprintln!("An error occurred: {err}");
+        std::process::exit(1)
     }
     Ok(())
 }
@@ -0,0 +1,191 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{net::Ipv4Addr, time::Duration};
+
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
+use nym_connection_monitor::packet_helpers::wrap_icmp_in_ipv4;
+use nym_gateway_directory::IpPacketRouterAddress;
+use nym_ip_packet_client::SharedMixnetClient;
+use nym_ip_packet_requests::{codec::MultiIpPacketCodec, IpPair};
+use nym_sdk::mixnet::{MixnetClient, MixnetMessageSender};
+use pnet_packet::{
+    icmp::{
+        echo_reply::EchoReplyPacket, echo_request::MutableEchoRequestPacket, IcmpPacket, IcmpTypes,
+    },
+    ip::IpNextHeaderProtocols,
+    ipv4::Ipv4Packet,
+    Packet,
+};
+use tokio_util::codec::Decoder;
+use tracing::*;
+
+use crate::{icmp::create_input_message, unpack_data_response};
+
+// Smallest datagram every IPv4 host is required to accept.
+const MTU_FLOOR: u16 = 576;
+
+// The address of the tun device on the IPR side, which answers our pings.
+const IPR_TUN_IP_V4: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+
+const IPV4_HEADER_LEN: u16 = 20;
+const ICMP_ECHO_HEADER_LEN: u16 = 8;
+
+// Use a different identifier than the regular pings so the replies don't get mixed up.
+const MTU_PROBE_ICMP_IDENTIFIER: u16 = 8476;
+
+// How long to wait for the reply to a single probe packet before considering it dropped.
+const MTU_PROBE_REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Find the largest IPv4 packet that makes it through the IPR to its tun device and back, by
+/// bisecting between [`MTU_FLOOR`] and `max_mtu`, which is normally the MTU of that tun device.
+///
+/// Returns `None` if not even a packet of `MTU_FLOOR` bytes gets a reply.
+pub(crate) async fn probe_mtu(
+    shared_mixnet_client: SharedMixnetClient,
+    our_ips: IpPair,
+    exit_router_address: IpPacketRouterAddress,
+    max_mtu: u16,
+) -> anyhow::Result<Option<u16>> {
+    let mtu_ceiling = max_mtu.max(MTU_FLOOR);
+    info!("Probing path MTU through the exit gateway, up to {mtu_ceiling} bytes");
+
+    // HACK: take it out of the shared mixnet client, same as when listening for ping replies
+    let mut mixnet_client = shared_mixnet_client.inner().lock().await.take().unwrap();
+
+    let result = bisect_mtu(
+        &mut mixnet_client,
+        our_ips,
+        exit_router_address,
+        mtu_ceiling,
+    )
+    .await;
+
+    // HACK: put it back in the shared mixnet client, so it can be properly disconnected
+    shared_mixnet_client
+        .inner()
+        .lock()
+        .await
+        .replace(mixnet_client);
+
+    match result {
+        Ok(Some(mtu)) => info!("Largest packet making it through the exit gateway: {mtu}"),
+        Ok(None) => warn!("No reply to MTU probes from the exit gateway"),
+        Err(ref err) => warn!("Failed to probe MTU: {err}"),
+    }
+    result
+}
+
+async fn bisect_mtu(
+    mixnet_client: &mut MixnetClient,
+    our_ips: IpPair,
+    exit_router_address: IpPacketRouterAddress,
+    mtu_ceiling: u16,
+) -> anyhow::Result<Option<u16>> {
+    if !send_sized_ping_and_wait(mixnet_client, our_ips, exit_router_address, MTU_FLOOR).await? {
+        return Ok(None);
+    }
+    if send_sized_ping_and_wait(mixnet_client, our_ips, exit_router_address, mtu_ceiling).await? {
+        return Ok(Some(mtu_ceiling));
+    }
+
+    // Invariant: `low` is known to get through and `high` is known to not get through
+    let (mut low, mut high) = (MTU_FLOOR, mtu_ceiling);
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        if send_sized_ping_and_wait(mixnet_client, our_ips, exit_router_address, mid).await? {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(Some(low))
+}
+
+async fn send_sized_ping_and_wait(
+    mixnet_client: &mut MixnetClient,
+    our_ips: IpPair,
+    exit_router_address: IpPacketRouterAddress,
+    packet_size: u16,
+) -> anyhow::Result<bool> {
+    debug!("Sending MTU probe of {packet_size} bytes");
+
+    // The sequence number is the packet size, which lets us match the reply to the probe
+    let icmp_echo_request = create_sized_icmpv4_echo_request(packet_size)?;
+    let ipv4_packet = wrap_icmp_in_ipv4(icmp_echo_request, our_ips.ipv4, IPR_TUN_IP_V4)?;
+    let bundled_packet =
+        MultiIpPacketCodec::bundle_one_packet(ipv4_packet.packet().to_vec().into());
+    let mixnet_message = create_input_message(exit_router_address.0, bundled_packet)?;
+
+    mixnet_client.send(mixnet_message).await?;
+
+    let mut multi_ip_packet_decoder =
+        MultiIpPacketCodec::new(nym_ip_packet_requests::codec::BUFFER_TIMEOUT);
+    let timeout = tokio::time::sleep(MTU_PROBE_REPLY_TIMEOUT);
+    tokio::pin!(timeout);
+
+    loop {
+        tokio::select! {
+            _ = &mut timeout => {
+                debug!("No reply to MTU probe of {packet_size} bytes");
+                return Ok(false);
+            }
+            Some(reconstructed_message) = mixnet_client.next() => {
+                let Some(data_response) = unpack_data_response(&reconstructed_message) else {
+                    continue;
+                };
+
+                let mut bytes = BytesMut::from(&*data_response.ip_packet);
+                while let Ok(Some(packet)) = multi_ip_packet_decoder.decode(&mut bytes) {
+                    if is_mtu_probe_reply(&packet, our_ips.ipv4, packet_size) {
+                        debug!("Received reply to MTU probe of {packet_size} bytes");
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn create_sized_icmpv4_echo_request(
+    packet_size: u16,
+) -> anyhow::Result<pnet_packet::icmp::echo_request::EchoRequestPacket<'static>> {
+    let icmp_len = packet_size.saturating_sub(IPV4_HEADER_LEN);
+    if icmp_len < ICMP_ECHO_HEADER_LEN {
+        anyhow::bail!("MTU probe packet size too small: {packet_size}");
+    }
+
+    let buffer = vec![0; icmp_len as usize];
+    let mut icmp_echo_request = MutableEchoRequestPacket::owned(buffer)
+        .ok_or(anyhow::anyhow!("failed to create ICMP echo request"))?;
+
+    icmp_echo_request.set_identifier(MTU_PROBE_ICMP_IDENTIFIER);
+    icmp_echo_request.set_sequence_number(packet_size);
+    icmp_echo_request.set_icmp_type(IcmpTypes::EchoRequest);
+    icmp_echo_request.set_icmp_code(pnet_packet::icmp::IcmpCode::new(0));
+
+    let icmp_packet = IcmpPacket::new(icmp_echo_request.packet())
+        .ok_or(anyhow::anyhow!("failed to create ICMP packet"))?;
+    let checksum = pnet_packet::icmp::checksum(&icmp_packet);
+    icmp_echo_request.set_checksum(checksum);
+
+    Ok(icmp_echo_request.consume_to_immutable())
+}
+
+fn is_mtu_probe_reply(packet: &Bytes, our_ip: Ipv4Addr, packet_size: u16) -> bool {
+    let Some(ipv4_packet) = Ipv4Packet::new(packet) else {
+        return false;
+    };
+    if ipv4_packet.get_destination() != our_ip
+        || ipv4_packet.get_next_level_protocol() != IpNextHeaderProtocols::Icmp
+    {
+        return false;
+    }
+    let Some(echo_reply) = EchoReplyPacket::new(ipv4_packet.payload()) else {
+        return false;
+    };
+    echo_reply.get_icmp_type() == IcmpTypes::EchoReply
+        && echo_reply.get_identifier() == MTU_PROBE_ICMP_IDENTIFIER
+        && echo_reply.get_sequence_number() == packet_size
+}
@@ -1,13 +1,16 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use anyhow::anyhow;
 use clap::Parser;
 use nym_config::defaults::setup_env;
 use nym_gateway_directory::EntryPoint;
-use nym_gateway_probe::ProbeResult;
+use nym_gateway_probe::PingOpts;
 use tracing::*;
 
 #[derive(Parser)]
@@ -17,9 +20,41 @@ struct CliArgs {
     #[arg(short, long)]
     config_env_file: Option<PathBuf>,
 
-    #[arg(long, short)]
+    #[arg(long, short, conflicts_with_all = ["gateways_file", "all"])]
     gateway: Option<String>,
 
+    /// Path to a file listing the gateway identities to probe, one per line.
+    #[arg(long, conflicts_with = "all")]
+    gateways_file: Option<PathBuf>,
+
+    /// Probe every gateway that has an IPR enabled.
+    #[arg(long)]
+    all: bool,
+
+    /// Maximum number of gateways probed at the same time when probing several gateways.
+    #[arg(long, default_value_t = 4)]
+    concurrency: usize,
+
+    /// Number of ICMP echo requests sent to each destination through the exit gateway.
+    #[arg(long)]
+    count: Option<u16>,
+
+    /// Time between two consecutive ICMP echo requests, in milliseconds.
+    #[arg(long)]
+    interval: Option<u64>,
+
+    /// Largest packet size, in bytes, tried when probing the MTU through the exit gateway.
+    #[arg(long)]
+    max_mtu: Option<u16>,
+
+    /// Time to wait for the mixnet self ping through the entry gateway to return, in seconds.
+    #[arg(long)]
+    self_ping_timeout: Option<u64>,
+
+    /// Number of times to send the mixnet self ping again when it doesn't return.
+    #[arg(long)]
+    self_ping_retries: Option<u32>,
+
     #[arg(long, short)]
     no_log: bool,
 }
@@ -38,7 +73,7 @@ fn setup_logging() {
         .init();
 }
 
-pub(crate) async fn run() -> anyhow::Result<ProbeResult> {
+pub(crate) async fn run() -> anyhow::Result<()> {
     let args = CliArgs::parse();
     if !args.no_log {
         setup_logging();
@@ -46,13 +81,88 @@ pub(crate) async fn run() -> anyhow::Result<ProbeResult> {
     debug!("{:?}", nym_bin_common::bin_info_local_vergen!());
     setup_env(args.config_env_file.as_ref());
 
-    let gateway = if let Some(gateway) = args.gateway {
-        EntryPoint::from_base58_string(&gateway)?
+    let mut ping_opts = PingOpts::default();
+    if let Some(count) = args.count {
+        ping_opts.count = count;
+    }
+    if let Some(interval) = args.interval {
+        ping_opts.interval = Duration::from_millis(interval);
+    }
+    if let Some(max_mtu) = args.max_mtu {
+        ping_opts.max_mtu = max_mtu;
+    }
+    if let Some(timeout) = args.self_ping_timeout {
+        ping_opts.self_ping.timeout = Duration::from_secs(timeout);
+    }
+    if let Some(retries) = args.self_ping_retries {
+        ping_opts.self_ping.retries = retries;
+    }
+
+    let gateways = if let Some(ref gateways_file) = args.gateways_file {
+        read_gateways_file(gateways_file).await?
+    } else if args.all {
+        fetch_all_gateways_with_ipr().await?
     } else {
-        fetch_random_gateway_with_ipr().await?
+        let gateway = if let Some(gateway) = args.gateway {
+            EntryPoint::from_base58_string(&gateway)?
+        } else {
+            fetch_random_gateway_with_ipr().await?
+        };
+        let result = nym_gateway_probe::probe_with_opts(gateway, ping_opts).await?;
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
     };
 
-    nym_gateway_probe::probe(gateway).await
+    info!(
+        "Probing {} gateways with concurrency {}",
+        gateways.len(),
+        args.concurrency
+    );
+    let mut results_rx =
+        nym_gateway_probe::probe_many_streaming(gateways, args.concurrency, ping_opts);
+    while let Some((entry_point, result)) = results_rx.recv().await {
+        let line = match result {
+            Ok(result) => serde_json::json!({
+                "gateway": gateway_identity(&entry_point),
+                "result": result,
+            }),
+            Err(err) => serde_json::json!({
+                "gateway": gateway_identity(&entry_point),
+                "error": err.to_string(),
+            }),
+        };
+        println!("{line}");
+    }
+    Ok(())
+}
+
+fn gateway_identity(entry_point: &EntryPoint) -> String {
+    match entry_point {
+        EntryPoint::Gateway { identity } => identity.to_base58_string(),
+        other => other.to_string(),
+    }
+}
+
+async fn read_gateways_file(path: &Path) -> anyhow::Result<Vec<EntryPoint>> {
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|err| anyhow!("failed to read {}: {err}", path.display()))?;
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| EntryPoint::from_base58_string(line).map_err(anyhow::Error::from))
+        .collect()
+}
+
+async fn fetch_all_gateways_with_ipr() -> anyhow::Result<Vec<EntryPoint>> {
+    let gateways = nym_gateway_probe::fetch_gateways_with_ipr().await?;
+    Ok(gateways
+        .into_iter()
+        .map(|gateway| EntryPoint::Gateway {
+            identity: *gateway.identity(),
+        })
+        .collect())
 }
 
 async fn fetch_random_gateway_with_ipr() -> anyhow::Result<EntryPoint> {
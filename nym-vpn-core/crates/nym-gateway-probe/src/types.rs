@@ -1,10 +1,48 @@
-use nym_connection_monitor::ConnectionStatusEvent;
+use std::time::Duration;
+
+use nym_connection_monitor::{ConnectionStatusEvent, SelfPingConfig};
 use serde::{Deserialize, Serialize};
 
+/// Options for the pings sent through the gateways: the mixnet self ping through the entry
+/// gateway, and the ICMP echo requests and MTU probes through the exit gateway.
+#[derive(Debug, Clone, Copy)]
+pub struct PingOpts {
+    /// Number of echo requests sent to each destination.
+    pub count: u16,
+    /// Time between two consecutive rounds of echo requests.
+    pub interval: Duration,
+    /// How long to keep waiting for replies once the last echo request has been sent.
+    pub timeout: Duration,
+    /// Largest packet size, in bytes, the MTU probe tries. Set it to the MTU of the tun device on
+    /// the IPR side when that isn't the usual 1500. Values below 576 are raised to 576.
+    pub max_mtu: u16,
+    /// How long the mixnet self ping, which checks that the entry gateway routes our traffic,
+    /// waits for a reply and how many times it tries again.
+    pub self_ping: SelfPingConfig,
+}
+
+impl Default for PingOpts {
+    fn default() -> Self {
+        Self {
+            count: 10,
+            interval: Duration::from_millis(100),
+            timeout: Duration::from_secs(2),
+            max_mtu: 1500,
+            self_ping: SelfPingConfig::default(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProbeResult {
     pub gateway: String,
     pub outcome: ProbeOutcome,
+    /// Largest IPv4 packet, in bytes, that made it through the exit gateway and back. `None` if
+    /// the gateway couldn't be used as an exit or never replied.
+    pub max_mtu: Option<u16>,
+    /// Round trip statistics of the ICMP pings sent through the exit gateway. `None` if the
+    /// gateway couldn't be used as an exit.
+    pub ping: Option<IpPingResults>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,37 +117,118 @@ impl Exit {
         }
     }
 
-    pub fn from_ping_replies(replies: &IpPingReplies) -> Self {
+    pub fn from_ping_results(results: &IpPingResults) -> Self {
         Self {
             can_connect: true,
-            can_route_ip_v4: replies.ipr_tun_ip_v4,
-            can_route_ip_external_v4: replies.external_ip_v4,
-            can_route_ip_v6: replies.ipr_tun_ip_v6,
-            can_route_ip_external_v6: replies.external_ip_v6,
+            can_route_ip_v4: results.ipr_tun_ip_v4.received > 0,
+            can_route_ip_external_v4: results.external_ip_v4.received > 0,
+            can_route_ip_v6: results.ipr_tun_ip_v6.received > 0,
+            can_route_ip_external_v6: results.external_ip_v6.received > 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpPingResults {
+    pub ipr_tun_ip_v4: PingResult,
+    pub ipr_tun_ip_v6: PingResult,
+    pub external_ip_v4: PingResult,
+    pub external_ip_v6: PingResult,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PingResult {
+    pub sent: u16,
+    pub received: u16,
+    /// Round trip time, in milliseconds, of each echo request in the order they were sent.
+    /// `None` for the requests that never got a reply.
+    pub rtts_ms: Vec<Option<f64>>,
+    /// Fraction of the echo requests that never got a reply, between 0 and 1.
+    pub packet_loss: f32,
+    /// Mean difference, in milliseconds, between the round trip times of consecutive replies.
+    /// `None` if fewer than two replies came back.
+    pub jitter_ms: Option<f64>,
+}
+
+impl PingResult {
+    pub fn from_rtts(rtts: &[Option<Duration>]) -> Self {
+        let rtts_ms: Vec<_> = rtts
+            .iter()
+            .map(|rtt| rtt.map(|rtt| rtt.as_secs_f64() * 1000.0))
+            .collect();
+        let received_rtts_ms: Vec<_> = rtts_ms.iter().flatten().copied().collect();
+
+        let sent = rtts_ms.len() as u16;
+        let received = received_rtts_ms.len() as u16;
+        let packet_loss = if sent == 0 {
+            0.0
+        } else {
+            f32::from(sent - received) / f32::from(sent)
+        };
+        let jitter_ms = (received_rtts_ms.len() > 1).then(|| {
+            let total: f64 = received_rtts_ms
+                .windows(2)
+                .map(|pair| (pair[1] - pair[0]).abs())
+                .sum();
+            total / (received_rtts_ms.len() - 1) as f64
+        });
+
+        Self {
+            sent,
+            received,
+            rtts_ms,
+            packet_loss,
+            jitter_ms,
         }
     }
 }
 
+/// Round trip times of the echo replies received so far, indexed by sequence number.
 #[derive(Debug, Clone, Default)]
 pub struct IpPingReplies {
-    pub ipr_tun_ip_v4: bool,
-    pub ipr_tun_ip_v6: bool,
-    pub external_ip_v4: bool,
-    pub external_ip_v6: bool,
+    pub ipr_tun_ip_v4: Vec<Option<Duration>>,
+    pub ipr_tun_ip_v6: Vec<Option<Duration>>,
+    pub external_ip_v4: Vec<Option<Duration>>,
+    pub external_ip_v6: Vec<Option<Duration>>,
 }
 
 impl IpPingReplies {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(count: u16) -> Self {
+        let rtts = vec![None; count.into()];
+        Self {
+            ipr_tun_ip_v4: rtts.clone(),
+            ipr_tun_ip_v6: rtts.clone(),
+            external_ip_v4: rtts.clone(),
+            external_ip_v6: rtts,
+        }
+    }
+
+    pub fn register_event(
+        &mut self,
+        event: &ConnectionStatusEvent,
+        sequence_number: u16,
+        rtt: Duration,
+    ) {
+        let rtts = match event {
+            ConnectionStatusEvent::MixnetSelfPingSent { .. }
+            | ConnectionStatusEvent::MixnetSelfPing { .. } => return,
+            ConnectionStatusEvent::Icmpv4IprTunDevicePingReply => &mut self.ipr_tun_ip_v4,
+            ConnectionStatusEvent::Icmpv6IprTunDevicePingReply => &mut self.ipr_tun_ip_v6,
+            ConnectionStatusEvent::Icmpv4IprExternalPingReply => &mut self.external_ip_v4,
+            ConnectionStatusEvent::Icmpv6IprExternalPingReply => &mut self.external_ip_v6,
+        };
+        // Keep the first reply if the same echo request is answered more than once
+        if let Some(slot @ None) = rtts.get_mut(usize::from(sequence_number)) {
+            *slot = Some(rtt);
+        }
     }
 
-    pub fn register_event(&mut self, event: &ConnectionStatusEvent) {
-        match event {
-            ConnectionStatusEvent::MixnetSelfPing => {}
-            ConnectionStatusEvent::Icmpv4IprTunDevicePingReply => self.ipr_tun_ip_v4 = true,
-            ConnectionStatusEvent::Icmpv6IprTunDevicePingReply => self.ipr_tun_ip_v6 = true,
-            ConnectionStatusEvent::Icmpv4IprExternalPingReply => self.external_ip_v4 = true,
-            ConnectionStatusEvent::Icmpv6IprExternalPingReply => self.external_ip_v6 = true,
+    pub fn to_results(&self) -> IpPingResults {
+        IpPingResults {
+            ipr_tun_ip_v4: PingResult::from_rtts(&self.ipr_tun_ip_v4),
+            ipr_tun_ip_v6: PingResult::from_rtts(&self.ipr_tun_ip_v6),
+            external_ip_v4: PingResult::from_rtts(&self.external_ip_v4),
+            external_ip_v6: PingResult::from_rtts(&self.external_ip_v6),
         }
     }
 }
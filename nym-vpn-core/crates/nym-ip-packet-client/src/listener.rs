@@ -17,7 +17,7 @@ use crate::{
 
 pub enum MixnetMessageOutcome {
     IpPackets(Vec<Bytes>),
-    MixnetSelfPing,
+    MixnetSelfPing { request_id: u64 },
 }
 
 pub struct IprListener {
@@ -40,16 +40,17 @@ impl IprListener {
         }
     }
 
-    fn is_mix_self_ping(&self, request: &IpPacketRequest) -> bool {
+    // Returns the request id if this is a ping we sent to ourselves.
+    fn mix_self_ping_id(&self, request: &IpPacketRequest) -> Option<u64> {
         match request.data {
             IpPacketRequestData::Ping(ref ping_request)
                 if ping_request.reply_to == self.our_address =>
             {
-                true
+                request.id()
             }
             ref request => {
                 debug!("Received unexpected request: {request:?}");
-                false
+                None
             }
         }
     }
@@ -104,8 +105,8 @@ impl IprListener {
                 // The exception to when we are not expecting a response, is when we
                 // are sending a ping to ourselves.
                 if let Ok(request) = IpPacketRequest::from_reconstructed_message(&message) {
-                    if self.is_mix_self_ping(&request) {
-                        return Ok(Some(MixnetMessageOutcome::MixnetSelfPing));
+                    if let Some(request_id) = self.mix_self_ping_id(&request) {
+                        return Ok(Some(MixnetMessageOutcome::MixnetSelfPing { request_id }));
                     }
                 } else {
                     warn!("Failed to deserialize reconstructed message: {err}");
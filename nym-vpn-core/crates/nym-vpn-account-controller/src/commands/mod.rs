@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 use nym_vpn_api_client::{
-    response::NymVpnDevice,
+    response::{NymVpnDevice, NymVpnZkNym, NymVpnZkNymResponse},
     types::{Device, VpnApiAccount},
     VpnApiClient,
 };
@@ -23,11 +23,15 @@ pub enum AccountCommand {
     UpdateAccountState,
     RegisterDevice,
     RequestZkNym,
-    GetDeviceZkNym,
-    GetZkNymsAvailableForDownload,
-    GetZkNymById(String),
+    GetDeviceZkNym(oneshot::Sender<Result<NymVpnZkNymResponse, Error>>),
+    GetZkNymsAvailableForDownload(oneshot::Sender<Result<NymVpnZkNymResponse, Error>>),
+    GetZkNymById(String, oneshot::Sender<Result<NymVpnZkNym, Error>>),
     ConfirmZkNymIdDownloaded(String),
     GetAvailableTickets(oneshot::Sender<Result<AvailableTicketbooks, Error>>),
+    /// Request zk-nyms for the ticket types running low right away, and reply with the number of
+    /// tickets imported once all pending requests are done. Joins the refresh already in progress,
+    /// if any.
+    RefreshTickets(oneshot::Sender<Result<u32, Error>>),
 }
 
 impl AccountCommand {
@@ -37,11 +41,14 @@ impl AccountCommand {
             AccountCommand::UpdateAccountState => "update_account_state",
             AccountCommand::RegisterDevice => "register_device",
             AccountCommand::RequestZkNym => "request_zk_nym",
-            AccountCommand::GetDeviceZkNym => "get_device_zk_nym",
-            AccountCommand::GetZkNymsAvailableForDownload => "get_zk_nyms_available_for_download",
-            AccountCommand::GetZkNymById(_) => "get_zk_nym_by_id",
+            AccountCommand::GetDeviceZkNym(_) => "get_device_zk_nym",
+            AccountCommand::GetZkNymsAvailableForDownload(_) => {
+                "get_zk_nyms_available_for_download"
+            }
+            AccountCommand::GetZkNymById(..) => "get_zk_nym_by_id",
             AccountCommand::ConfirmZkNymIdDownloaded(_) => "confirm_zk_nym_id_download",
             AccountCommand::GetAvailableTickets(_) => "get_available_tickets",
+            AccountCommand::RefreshTickets(_) => "refresh_tickets",
         }
     }
 }
@@ -136,11 +143,12 @@ impl CommandHandler {
             AccountCommand::UpdateAccountState => self.update_shared_account_state().await,
             AccountCommand::RegisterDevice => self.register_device().await,
             AccountCommand::RequestZkNym => todo!(),
-            AccountCommand::GetDeviceZkNym => todo!(),
-            AccountCommand::GetZkNymsAvailableForDownload => todo!(),
-            AccountCommand::GetZkNymById(_) => todo!(),
+            AccountCommand::GetDeviceZkNym(_) => todo!(),
+            AccountCommand::GetZkNymsAvailableForDownload(_) => todo!(),
+            AccountCommand::GetZkNymById(..) => todo!(),
             AccountCommand::ConfirmZkNymIdDownloaded(_) => todo!(),
             AccountCommand::GetAvailableTickets(_) => todo!(),
+            AccountCommand::RefreshTickets(_) => todo!(),
         }
         .inspect(|_result| {
             tracing::info!("Command {:?} with id {} completed", self.command, self.id);
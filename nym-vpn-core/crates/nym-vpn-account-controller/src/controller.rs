@@ -13,13 +13,16 @@ use nym_http_api_client::UserAgent;
 use nym_vpn_api_client::{
     response::{
         NymVpnAccountSummaryResponse, NymVpnDevicesResponse, NymVpnZkNym, NymVpnZkNymPost,
-        NymVpnZkNymStatus,
+        NymVpnZkNymResponse, NymVpnZkNymStatus,
     },
     types::{Device, VpnApiAccount},
 };
 use nym_vpn_store::VpnStorage;
 use tokio::{
-    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+    sync::{
+        mpsc::{UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
     task::{JoinError, JoinSet},
 };
 use tokio_util::sync::CancellationToken;
@@ -34,8 +37,8 @@ use crate::{
         AccountCommand, AccountCommandResult, CommandHandler,
     },
     error::Error,
-    shared_state::{MnemonicState, ReadyToRegisterDevice, SharedAccountState},
-    storage::{AccountStorage, VpnCredentialStorage},
+    shared_state::{MnemonicState, ReadyToConnect, ReadyToRegisterDevice, SharedAccountState},
+    storage::{ticketbook_types, AccountStorage, VpnCredentialStorage},
     AvailableTicketbooks,
 };
 
@@ -49,6 +52,15 @@ pub(crate) type DevicesResponse = Arc<tokio::sync::Mutex<Option<NymVpnDevicesRes
 pub(crate) type AccountSummaryResponse =
     Arc<tokio::sync::Mutex<Option<NymVpnAccountSummaryResponse>>>;
 
+// A ticket refresh waiting for its zk-nym requests to be polled and imported
+struct TicketRefresh {
+    // The number of tickets stored locally when the refresh started
+    tickets_before: u32,
+    // The zk-nym requests of this refresh that failed, either when requested or when polled
+    failures: Vec<String>,
+    result_txs: Vec<oneshot::Sender<Result<u32, Error>>>,
+}
+
 pub struct AccountController<S>
 where
     S: VpnStorage,
@@ -91,6 +103,9 @@ where
 
     // List of currently running command tasks and their type
     pending_commands: PendingCommands,
+
+    // The ticket refresh in progress, if any
+    ticket_refresh: Option<TicketRefresh>,
 }
 
 impl<S> AccountController<S>
@@ -132,6 +147,7 @@ where
             cancel_token,
             pending_commands: Default::default(),
             command_tasks: JoinSet::new(),
+            ticket_refresh: None,
         })
     }
 
@@ -285,6 +301,8 @@ where
         self.credential_storage
             .insert_issued_ticketbook(&issued_ticketbook)
             .await?;
+        self.account_state
+            .notify_zk_nym_available(ticketbook_type.to_string());
 
         self.confirm_zk_nym_downloaded(&response.id).await?;
 
@@ -308,9 +326,6 @@ where
     // Check the local credential storage to see if we need to request more zk-nyms, the proceed to
     // request zk-nyms for each ticket type that we need.
     async fn handle_request_zk_nym(&mut self) -> Result<(), Error> {
-        let account = self.account_storage.load_account().await?;
-        let device = self.account_storage.load_device_keys().await?;
-
         // Then we check local storage to see what ticket types we already have stored
         let local_remaining_tickets = self
             .credential_storage
@@ -322,17 +337,33 @@ where
 
         // Get the ticket types that are below the threshold
         // TODO: only count ticketbooks not expired
-        let ticket_types_needed_to_request = local_remaining_tickets
-            .into_iter()
-            .filter(|(_, remaining)| *remaining < TICKET_THRESHOLD)
-            .map(|(ticket_type, _)| ticket_type)
-            .collect::<Vec<_>>();
+        let ticket_types_needed_to_request = ticket_types_below_threshold(local_remaining_tickets);
 
         // For testing: uncomment to only request zk-nyms for a specific ticket type
         //let ticket_types_needed_to_request = vec![TicketType::V1MixnetEntry];
 
+        let (requested, mut failed) = self.request_zk_nyms(ticket_types_needed_to_request).await?;
+
+        // Only fail when nothing could be requested, the other requests are still being polled
+        if failed.len() == requested {
+            if let Some(err) = failed.pop() {
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    // Request zk-nyms for the given ticket types and spawn a polling task for each accepted
+    // request. Returns the number of requests made and the ones that failed.
+    async fn request_zk_nyms(
+        &mut self,
+        ticket_types: Vec<TicketType>,
+    ) -> Result<(usize, Vec<Error>), Error> {
+        let account = self.account_storage.load_account().await?;
+        let device = self.account_storage.load_device_keys().await?;
+
         // Request zk-nyms for each ticket type that we need
-        let responses = futures::stream::iter(ticket_types_needed_to_request)
+        let responses = futures::stream::iter(ticket_types)
             .filter_map(|ticket_type| {
                 let account = account.clone();
                 async move { construct_zk_nym_request_data(&account, ticket_type).ok() }
@@ -348,6 +379,8 @@ where
             .await;
 
         // Spawn polling tasks for each zk-nym request to monitor the outcome
+        let requested = responses.len();
+        let mut failed = Vec::new();
         for (request, response) in responses {
             match response {
                 Ok(response) => {
@@ -356,11 +389,79 @@ where
                 }
                 Err(err) => {
                     tracing::error!("Failed to request zk-nym: {:#?}", err);
+                    failed.push(err);
                 }
             }
         }
+        Ok((requested, failed))
+    }
 
-        Ok(())
+    async fn local_remaining_tickets(&self) -> u32 {
+        self.credential_storage
+            .check_local_remaining_tickets()
+            .await
+            .into_iter()
+            .map(|(_, remaining)| remaining)
+            .sum()
+    }
+
+    async fn handle_refresh_tickets(&mut self, result_tx: oneshot::Sender<Result<u32, Error>>) {
+        if let Some(ticket_refresh) = &mut self.ticket_refresh {
+            tracing::info!("Ticket refresh already in progress, waiting for it to finish");
+            ticket_refresh.result_txs.push(result_tx);
+            return;
+        }
+
+        match self.start_ticket_refresh().await {
+            Ok((tickets_before, failures)) => {
+                self.ticket_refresh = Some(TicketRefresh {
+                    tickets_before,
+                    failures,
+                    result_txs: vec![result_tx],
+                });
+                // Finish right away if there was nothing to request
+                self.finish_ticket_refresh_if_done().await;
+            }
+            Err(err) => {
+                tracing::warn!("Failed to refresh tickets: {err}");
+                result_tx.send(Err(err)).ok();
+            }
+        }
+    }
+
+    // Request every ticket type, regardless of how many tickets are stored locally: the point of a
+    // refresh is to pick up bandwidth bought since the last request.
+    async fn start_ticket_refresh(&mut self) -> Result<(u32, Vec<String>), Error> {
+        let ready_to_connect = self.account_state.is_ready_to_connect().await;
+        if ready_to_connect != ReadyToConnect::Ready {
+            return Err(Error::NotReadyToRefreshTickets(ready_to_connect));
+        }
+
+        tracing::info!("Refreshing tickets");
+        let tickets_before = self.local_remaining_tickets().await;
+        let (_, failed) = self.request_zk_nyms(ticketbook_types().to_vec()).await?;
+        let failures = failed.iter().map(ToString::to_string).collect();
+        Ok((tickets_before, failures))
+    }
+
+    async fn finish_ticket_refresh_if_done(&mut self) {
+        if self.is_pending_zk_nym_tasks().await {
+            return;
+        }
+        let Some(ticket_refresh) = self.ticket_refresh.take() else {
+            return;
+        };
+
+        let acquired = self
+            .local_remaining_tickets()
+            .await
+            .saturating_sub(ticket_refresh.tickets_before);
+        tracing::info!("Ticket refresh finished, acquired {acquired} tickets");
+        for result_tx in ticket_refresh.result_txs {
+            result_tx
+                .send(refresh_outcome(acquired, &ticket_refresh.failures))
+                .ok();
+        }
     }
 
     async fn update_mnemonic_state(&self) -> Option<VpnApiAccount> {
@@ -421,7 +522,7 @@ where
     }
 
     // Get and list zk-nyms for the device
-    async fn handle_get_device_zk_nym(&mut self) -> Result<(), Error> {
+    async fn handle_get_device_zk_nym(&mut self) -> Result<NymVpnZkNymResponse, Error> {
         tracing::info!("Getting device zk-nym from API");
 
         let account = self.account_storage.load_account().await?;
@@ -438,10 +539,12 @@ where
         for zk_nym in &reported_device_zk_nyms.items {
             tracing::info!("{:?}", zk_nym);
         }
-        Ok(())
+        Ok(reported_device_zk_nyms)
     }
 
-    async fn handle_get_zk_nyms_available_for_download(&self) -> Result<(), Error> {
+    async fn handle_get_zk_nyms_available_for_download(
+        &self,
+    ) -> Result<NymVpnZkNymResponse, Error> {
         tracing::info!("Getting zk-nyms available for download from API");
 
         let account = self.account_storage.load_account().await?;
@@ -459,10 +562,10 @@ where
             tracing::info!("{:?}", zk_nym);
         }
 
-        Ok(())
+        Ok(reported_device_zk_nyms)
     }
 
-    async fn handle_get_zk_nym_by_id(&self, id: &str) -> Result<(), Error> {
+    async fn handle_get_zk_nym_by_id(&self, id: &str) -> Result<NymVpnZkNym, Error> {
         tracing::info!("Getting zk-nym by id from API");
 
         let account = self.account_storage.load_account().await?;
@@ -479,7 +582,7 @@ where
             reported_device_zk_nyms
         );
 
-        Ok(())
+        Ok(reported_device_zk_nyms)
     }
 
     async fn handle_get_available_tickets(&self) -> Result<AvailableTicketbooks, Error> {
@@ -494,6 +597,7 @@ where
     async fn handle_polling_result(&mut self, result: Result<PollingResult, JoinError>) {
         let Ok(result) = result else {
             tracing::error!("Polling task failed: {:#?}", result);
+            self.record_refresh_failure("polling task failed".to_string());
             return;
         };
 
@@ -502,28 +606,47 @@ where
                 if response.status == NymVpnZkNymStatus::Active =>
             {
                 tracing::info!("Polling finished succesfully, importing ticketbook");
-                self.import_zk_nym(response, ticketbook_type, *request_info, *request)
+                let id = response.id.clone();
+                if let Err(err) = self
+                    .import_zk_nym(response, ticketbook_type, *request_info, *request)
                     .await
-                    .inspect_err(|err| {
-                        tracing::error!("Failed to import zk-nym: {:#?}", err);
-                    })
-                    .ok();
+                {
+                    tracing::error!("Failed to import zk-nym: {:#?}", err);
+                    self.record_refresh_failure(format!("failed to import zk-nym {id}: {err}"));
+                }
             }
             PollingResult::Finished(response, _, _, _) => {
                 tracing::warn!(
                     "Polling finished with status: {:?}, not importing!",
                     response.status
                 );
+                self.record_refresh_failure(format!(
+                    "zk-nym {} finished with status {:?}",
+                    response.id, response.status
+                ));
             }
             PollingResult::Timeout(response) => {
                 tracing::info!("Polling task timed out: {:#?}", response);
+                self.record_refresh_failure(format!("polling zk-nym {} timed out", response.id));
             }
             PollingResult::Error(error) => {
                 tracing::error!("Polling task failed for {}: {:#?}", error.id, error.error);
+                self.record_refresh_failure(format!(
+                    "failed to poll zk-nym {}: {}",
+                    error.id, error.error
+                ));
             }
         }
     }
 
+    // Keep track of the failed zk-nym requests, so that the ticket refresh in progress can report
+    // them
+    fn record_refresh_failure(&mut self, reason: String) {
+        if let Some(ticket_refresh) = &mut self.ticket_refresh {
+            ticket_refresh.failures.push(reason);
+        }
+    }
+
     async fn is_command_running(&self, command: &AccountCommand) -> Result<bool, Error> {
         self.pending_commands
             .lock()
@@ -553,11 +676,39 @@ where
             AccountCommand::UpdateAccountState => self.handle_update_account_state().await,
             AccountCommand::RegisterDevice => self.handle_register_device().await,
             AccountCommand::RequestZkNym => self.handle_request_zk_nym().await,
-            AccountCommand::GetDeviceZkNym => self.handle_get_device_zk_nym().await,
-            AccountCommand::GetZkNymsAvailableForDownload => {
-                self.handle_get_zk_nyms_available_for_download().await
+            AccountCommand::GetDeviceZkNym(result_tx) => {
+                let result = self.handle_get_device_zk_nym().await;
+                result_tx
+                    .send(result)
+                    .inspect_err(|err| {
+                        tracing::error!("Failed to send device zk-nyms response: {:#?}", err);
+                    })
+                    .ok();
+                Ok(())
+            }
+            AccountCommand::GetZkNymsAvailableForDownload(result_tx) => {
+                let result = self.handle_get_zk_nyms_available_for_download().await;
+                result_tx
+                    .send(result)
+                    .inspect_err(|err| {
+                        tracing::error!(
+                            "Failed to send zk-nyms available for download response: {:#?}",
+                            err
+                        );
+                    })
+                    .ok();
+                Ok(())
+            }
+            AccountCommand::GetZkNymById(id, result_tx) => {
+                let result = self.handle_get_zk_nym_by_id(&id).await;
+                result_tx
+                    .send(result)
+                    .inspect_err(|err| {
+                        tracing::error!("Failed to send zk-nym response: {:#?}", err);
+                    })
+                    .ok();
+                Ok(())
             }
-            AccountCommand::GetZkNymById(id) => self.handle_get_zk_nym_by_id(&id).await,
             AccountCommand::ConfirmZkNymIdDownloaded(id) => {
                 self.confirm_zk_nym_downloaded(&id).await
             }
@@ -571,6 +722,10 @@ where
                     .ok();
                 Ok(())
             }
+            AccountCommand::RefreshTickets(result_tx) => {
+                self.handle_refresh_tickets(result_tx).await;
+                Ok(())
+            }
         }
     }
 
@@ -680,6 +835,7 @@ where
                         self.handle_polling_result(result).await;
                     }
                     self.update_pending_zk_nym_tasks().await;
+                    self.finish_ticket_refresh_if_done().await;
                 }
                 // On a timer we want to refresh the account state
                 _ = update_account_state_timer.tick() => {
@@ -714,3 +870,71 @@ fn create_api_client(user_agent: UserAgent) -> nym_vpn_api_client::VpnApiClient
     // TODO: remove unwrap
     nym_vpn_api_client::VpnApiClient::new(nym_vpn_api_url, user_agent).unwrap()
 }
+
+// The ticket types we are running low on and should request zk-nyms for
+fn ticket_types_below_threshold(
+    local_remaining_tickets: Vec<(TicketType, u32)>,
+) -> Vec<TicketType> {
+    local_remaining_tickets
+        .into_iter()
+        .filter(|(_, remaining)| *remaining < TICKET_THRESHOLD)
+        .map(|(ticket_type, _)| ticket_type)
+        .collect()
+}
+
+// A ticket refresh fails if any of its zk-nym requests did, even when some tickets were acquired
+fn refresh_outcome(acquired: u32, failures: &[String]) -> Result<u32, Error> {
+    if failures.is_empty() {
+        Ok(acquired)
+    } else {
+        Err(Error::RefreshTickets {
+            acquired,
+            reason: failures.join(", "),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_ticket_types_below_threshold_are_requested() {
+        let local_remaining_tickets = vec![
+            (TicketType::V1MixnetEntry, 0),
+            (TicketType::V1MixnetExit, TICKET_THRESHOLD - 1),
+            (TicketType::V1WireguardEntry, TICKET_THRESHOLD),
+            (TicketType::V1WireguardExit, TICKET_THRESHOLD + 100),
+        ];
+
+        assert_eq!(
+            ticket_types_below_threshold(local_remaining_tickets),
+            vec![TicketType::V1MixnetEntry, TicketType::V1MixnetExit]
+        );
+    }
+
+    #[test]
+    fn refresh_without_failures_reports_acquired_tickets() {
+        assert_eq!(refresh_outcome(0, &[]).unwrap(), 0);
+        assert_eq!(refresh_outcome(42, &[]).unwrap(), 42);
+    }
+
+    #[test]
+    fn refresh_with_failures_returns_an_error() {
+        let failures = vec![
+            "polling zk-nym a timed out".to_string(),
+            "failed to poll zk-nym b: error".to_string(),
+        ];
+
+        match refresh_outcome(3, &failures) {
+            Err(Error::RefreshTickets { acquired, reason }) => {
+                assert_eq!(acquired, 3);
+                assert_eq!(
+                    reason,
+                    "polling zk-nym a timed out, failed to poll zk-nym b: error"
+                );
+            }
+            other => panic!("unexpected outcome: {other:?}"),
+        }
+    }
+}
@@ -4,7 +4,7 @@
 use tokio::sync::mpsc::error::SendError;
 use url::Url;
 
-use crate::commands::AccountCommand;
+use crate::{commands::AccountCommand, ReadyToConnect};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -95,6 +95,12 @@ pub enum Error {
 
     #[error("failed to confirm zk-nym downloaded: {0}")]
     ConfirmZkNymDownloaded(#[source] nym_vpn_api_client::VpnApiClientError),
+
+    #[error("not ready to refresh tickets: {0}")]
+    NotReadyToRefreshTickets(ReadyToConnect),
+
+    #[error("failed to refresh tickets, acquired {acquired} tickets: {reason}")]
+    RefreshTickets { acquired: u32, reason: String },
 }
 
 impl Error {
@@ -16,5 +16,7 @@ mod storage;
 pub use commands::AccountCommand;
 pub use controller::AccountController;
 pub use error::Error;
-pub use shared_state::{AccountStateSummary, ReadyToConnect, SharedAccountState};
+pub use shared_state::{
+    AccountStateChange, AccountStateSummary, ReadyToConnect, SharedAccountState,
+};
 pub use storage::{AvailableTicketbook, AvailableTicketbooks};
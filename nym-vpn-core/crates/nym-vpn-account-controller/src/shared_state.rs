@@ -8,11 +8,33 @@ use nym_vpn_api_client::response::{
     NymVpnDeviceStatus, NymVpnSubscriptionStatus,
 };
 use serde::Serialize;
-use tokio::sync::MutexGuard;
+use tokio::sync::{broadcast, MutexGuard};
+
+const ACCOUNT_STATE_CHANGES_CAPACITY: usize = 32;
 
 #[derive(Clone)]
 pub struct SharedAccountState {
     inner: Arc<tokio::sync::Mutex<AccountStateSummary>>,
+    changes_tx: broadcast::Sender<AccountStateChange>,
+}
+
+// A change to the account state, broadcast to everyone subscribed with
+// `SharedAccountState::subscribe`. Only sent when the value actually changes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum AccountStateChange {
+    Mnemonic(MnemonicState),
+    Account(AccountState),
+
+    // Includes the subscription expiring, which shows up as it no longer being active
+    Subscription(SubscriptionState),
+
+    // Includes the device being deregistered, which shows up as it no longer being active
+    Device(DeviceState),
+
+    PendingZkNym(bool),
+
+    // A new zk-nym ticketbook was imported into the local credential store
+    ZkNymAvailable { ticket_type: String },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
@@ -67,11 +89,31 @@ impl fmt::Display for ReadyToConnect {
 
 impl SharedAccountState {
     pub(crate) fn new() -> Self {
+        let (changes_tx, _) = broadcast::channel(ACCOUNT_STATE_CHANGES_CAPACITY);
         SharedAccountState {
             inner: Arc::new(tokio::sync::Mutex::new(AccountStateSummary::default())),
+            changes_tx,
         }
     }
 
+    // Subscribe to account state changes. The current state is returned together with the
+    // receiver, taken under the same lock, so that no change in between is missed.
+    pub async fn subscribe(
+        &self,
+    ) -> (AccountStateSummary, broadcast::Receiver<AccountStateChange>) {
+        let guard = self.inner.lock().await;
+        (guard.clone(), self.changes_tx.subscribe())
+    }
+
+    fn notify(&self, change: AccountStateChange) {
+        // Nobody listening is not an error
+        let _ = self.changes_tx.send(change);
+    }
+
+    pub(crate) fn notify_zk_nym_available(&self, ticket_type: String) {
+        self.notify(AccountStateChange::ZkNymAvailable { ticket_type });
+    }
+
     pub async fn lock(&self) -> MutexGuard<'_, AccountStateSummary> {
         self.inner.lock().await
     }
@@ -133,6 +175,7 @@ impl SharedAccountState {
         let mut guard = self.inner.lock().await;
         if guard.mnemonic.as_ref() != Some(&state) {
             tracing::info!("Setting mnemonic state to {:?}", state);
+            self.notify(AccountStateChange::Mnemonic(state.clone()));
         }
         guard.mnemonic = Some(state);
     }
@@ -141,6 +184,7 @@ impl SharedAccountState {
         let mut guard = self.inner.lock().await;
         if guard.account.as_ref() != Some(&state) {
             tracing::info!("Setting account state to {:?}", state);
+            self.notify(AccountStateChange::Account(state.clone()));
         }
         guard.account = Some(state);
     }
@@ -149,6 +193,7 @@ impl SharedAccountState {
         let mut guard = self.inner.lock().await;
         if guard.subscription.as_ref() != Some(&state) {
             tracing::info!("Setting subscription state to {:?}", state);
+            self.notify(AccountStateChange::Subscription(state.clone()));
         }
         guard.subscription = Some(state);
     }
@@ -157,6 +202,7 @@ impl SharedAccountState {
         let mut guard = self.inner.lock().await;
         if guard.device.as_ref() != Some(&state) {
             tracing::info!("Setting device state to {:?}", state);
+            self.notify(AccountStateChange::Device(state.clone()));
         }
         guard.device = Some(state);
     }
@@ -166,6 +212,7 @@ impl SharedAccountState {
         if guard.pending_zk_nym != pending {
             tracing::debug!("Setting pending zk-nym to {}", pending);
             guard.pending_zk_nym = pending;
+            self.notify(AccountStateChange::PendingZkNym(pending));
         }
     }
 }
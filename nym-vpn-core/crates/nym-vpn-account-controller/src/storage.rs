@@ -219,6 +219,10 @@ impl AvailableTicketbook {
             ticket_size: self.ticket_size,
         }
     }
+
+    pub fn is_expired(&self) -> bool {
+        self.expiration <= nym_ecash_time::ecash_today().date()
+    }
 }
 
 pub struct TicketbookAmount {
@@ -303,8 +307,6 @@ impl TryFrom<BasicTicketbookInformation> for AvailableTicketbook {
 
 impl fmt::Display for AvailableTicketbook {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let ecash_today = nym_ecash_time::ecash_today().date();
-
         let issued = self.issued_tickets;
         let si_issued = si_scale::helpers::bibytes2((issued as u64 * self.ticket_size) as f64);
 
@@ -316,7 +318,7 @@ impl fmt::Display for AvailableTicketbook {
             si_scale::helpers::bibytes2((remaining as u64 * self.ticket_size) as f64);
         let si_size = si_scale::helpers::bibytes2(self.ticket_size as f64);
 
-        let expiration = if self.expiration <= ecash_today {
+        let expiration = if self.is_expired() {
             format!("EXPIRED ON {}", self.expiration)
         } else {
             self.expiration.to_string()
@@ -383,7 +385,7 @@ impl From<Vec<AvailableTicketbook>> for AvailableTicketbooks {
 }
 
 // TODO: add #[derive(EnumIter)] to TicketType so we can iterate over it directly.
-fn ticketbook_types() -> [TicketType; 4] {
+pub(crate) fn ticketbook_types() -> [TicketType; 4] {
     [
         TicketType::V1MixnetEntry,
         TicketType::V1MixnetExit,
@@ -1,7 +1,11 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::{fmt, time::Duration};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use backon::Retryable;
 use nym_credential_proxy_requests::api::v1::ticketbook::models::PartialVerificationKeysResponse;
@@ -11,6 +15,7 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
     error::{Result, VpnApiClientError},
+    proxy::ProxyConfig,
     request::{
         ApplyFreepassRequestBody, CreateSubscriptionKind, CreateSubscriptionRequestBody,
         RegisterDeviceRequestBody, RequestZkNymRequestBody,
@@ -33,18 +38,41 @@ pub(crate) const NYM_VPN_API_TIMEOUT: Duration = Duration::from_secs(60);
 #[derive(Clone)]
 pub struct VpnApiClient {
     inner: nym_http_api_client::Client,
+    proxy: Option<reqwest::Proxy>,
+    // Local time minus server time, as seen in the `Date` header of the last response
+    clock_skew: Arc<Mutex<Option<i64>>>,
 }
 
 impl VpnApiClient {
     pub fn new(base_url: Url, user_agent: UserAgent) -> Result<Self> {
+        Self::new_with_proxy(base_url, user_agent, None)
+    }
+
+    /// Create a client that sends all requests through the given proxy.
+    pub fn new_with_proxy(
+        base_url: Url,
+        user_agent: UserAgent,
+        proxy: Option<&ProxyConfig>,
+    ) -> Result<Self> {
+        let proxy = proxy.map(ProxyConfig::to_reqwest_proxy).transpose()?;
         nym_http_api_client::Client::builder(base_url)
             .map(|builder| {
-                builder
+                let builder = builder
                     .with_user_agent(user_agent)
-                    .with_timeout(NYM_VPN_API_TIMEOUT)
+                    .with_timeout(NYM_VPN_API_TIMEOUT);
+                match proxy.clone() {
+                    Some(proxy) => {
+                        builder.with_reqwest_builder(reqwest::ClientBuilder::new().proxy(proxy))
+                    }
+                    None => builder,
+                }
             })
             .and_then(|builder| builder.build())
-            .map(|c| Self { inner: c })
+            .map(|c| Self {
+                inner: c,
+                proxy,
+                clock_skew: Default::default(),
+            })
             .map_err(VpnApiClientError::FailedToCreateVpnApiClient)
     }
 
@@ -52,6 +80,35 @@ impl VpnApiClient {
         self.inner.current_url()
     }
 
+    /// Number of seconds the local clock is ahead of the server clock, or behind if negative.
+    ///
+    /// This is based on the `Date` header of the last response received, so it's only available
+    /// after a request has been made and has a resolution of one second.
+    pub fn clock_skew_seconds(&self) -> Option<i64> {
+        self.clock_skew
+            .lock()
+            .ok()
+            .and_then(|clock_skew| *clock_skew)
+    }
+
+    fn record_clock_skew(&self, response: &reqwest::Response) {
+        let Some(server_time) = response
+            .headers()
+            .get(reqwest::header::DATE)
+            .and_then(|date| date.to_str().ok())
+            .and_then(|date| chrono::DateTime::parse_from_rfc2822(date).ok())
+        else {
+            return;
+        };
+
+        let skew = chrono::Utc::now()
+            .signed_duration_since(server_time)
+            .num_seconds();
+        if let Ok(mut clock_skew) = self.clock_skew.lock() {
+            *clock_skew = Some(skew);
+        }
+    }
+
     async fn get_authorized<T, E>(
         &self,
         path: PathSegments<'_>,
@@ -76,6 +133,7 @@ impl VpnApiClient {
         };
 
         let response = request.send().await?;
+        self.record_clock_skew(&response);
 
         nym_http_api_client::parse_response(response, false).await
     }
@@ -146,13 +204,17 @@ impl VpnApiClient {
         V: AsRef<str>,
         E: fmt::Display + fmt::Debug + DeserializeOwned,
     {
-        let response = (|| async { self.inner.get_json(path, params).await })
-            .retry(backon::ConstantBuilder::default())
-            .notify(|err: &HttpClientError<E>, dur: Duration| {
-                tracing::warn!("Failed to get JSON: {}", err);
-                tracing::warn!("retrying {:?} after {:?}", err, dur);
-            })
-            .await?;
+        let response = (|| async {
+            let response = self.inner.create_get_request(path, params).send().await?;
+            self.record_clock_skew(&response);
+            nym_http_api_client::parse_response(response, false).await
+        })
+        .retry(backon::ConstantBuilder::default())
+        .notify(|err: &HttpClientError<E>, dur: Duration| {
+            tracing::warn!("Failed to get JSON: {}", err);
+            tracing::warn!("retrying {:?} after {:?}", err, dur);
+        })
+        .await?;
         Ok(response)
     }
 
@@ -197,7 +259,11 @@ impl VpnApiClient {
     ) -> reqwest::RequestBuilder {
         let base_url = self.inner.current_url().clone();
         let url = nym_http_api_client::sanitize_url(&base_url, path, params);
-        let client = reqwest::ClientBuilder::new().build().unwrap();
+        let mut builder = reqwest::ClientBuilder::new();
+        if let Some(proxy) = self.proxy.clone() {
+            builder = builder.proxy(proxy);
+        }
+        let client = builder.build().unwrap();
         client.delete(url)
     }
 
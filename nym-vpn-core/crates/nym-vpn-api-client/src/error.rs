@@ -12,6 +12,12 @@ pub enum VpnApiClientError {
     #[error("failed tp create vpn api client")]
     FailedToCreateVpnApiClient(#[source] HttpClientError<UnexpectedError>),
 
+    #[error("unsupported proxy scheme: {scheme}, expected one of http, https, socks5 or socks5h")]
+    UnsupportedProxyScheme { scheme: String },
+
+    #[error("invalid proxy configuration")]
+    InvalidProxy(#[source] reqwest::Error),
+
     #[error("failed to get account")]
     FailedToGetAccount(#[source] HttpClientError<NymErrorResponse>),
 
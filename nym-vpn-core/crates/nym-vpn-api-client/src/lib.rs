@@ -8,8 +8,10 @@ pub(crate) mod jwt;
 
 mod client;
 mod error;
+mod proxy;
 mod request;
 mod routes;
 
 pub use client::VpnApiClient;
 pub use error::{HttpClientError, VpnApiClientError};
+pub use proxy::{ProxyConfig, ProxyCredentials};
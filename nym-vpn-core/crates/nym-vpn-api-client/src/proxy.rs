@@ -0,0 +1,90 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::fmt;
+
+use reqwest::Url;
+
+use crate::error::{Result, VpnApiClientError};
+
+const SUPPORTED_PROXY_SCHEMES: &[&str] = &["http", "https", "socks5", "socks5h"];
+
+/// Proxy used for the HTTP requests made to the nym-api and nym-vpn-api before the tunnel is up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProxyConfig {
+    /// Proxy url, for example `http://proxy.example.com:8080` or `socks5://127.0.0.1:1080`.
+    pub url: Url,
+    pub credentials: Option<ProxyCredentials>,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct ProxyCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl ProxyConfig {
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            credentials: None,
+        }
+    }
+
+    pub fn with_credentials(mut self, username: String, password: String) -> Self {
+        self.credentials = Some(ProxyCredentials { username, password });
+        self
+    }
+
+    pub fn to_reqwest_proxy(&self) -> Result<reqwest::Proxy> {
+        let scheme = self.url.scheme();
+        if !SUPPORTED_PROXY_SCHEMES.contains(&scheme) {
+            return Err(VpnApiClientError::UnsupportedProxyScheme {
+                scheme: scheme.to_owned(),
+            });
+        }
+
+        let proxy =
+            reqwest::Proxy::all(self.url.as_str()).map_err(VpnApiClientError::InvalidProxy)?;
+        Ok(match self.credentials {
+            Some(ref credentials) => proxy.basic_auth(&credentials.username, &credentials.password),
+            None => proxy,
+        })
+    }
+}
+
+// Don't leak the password into the logs
+impl fmt::Debug for ProxyCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyCredentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supported_schemes_are_accepted() {
+        for url in [
+            "http://127.0.0.1:8080",
+            "https://proxy.example.com",
+            "socks5://127.0.0.1:1080",
+        ] {
+            let config = ProxyConfig::new(url.parse().unwrap());
+            assert!(config.to_reqwest_proxy().is_ok(), "{url}");
+        }
+    }
+
+    #[test]
+    fn unsupported_scheme_is_rejected() {
+        let config = ProxyConfig::new("ftp://127.0.0.1:21".parse().unwrap());
+        assert!(matches!(
+            config.to_reqwest_proxy(),
+            Err(VpnApiClientError::UnsupportedProxyScheme { scheme }) if scheme == "ftp"
+        ));
+    }
+}
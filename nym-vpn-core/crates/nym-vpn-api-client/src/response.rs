@@ -249,6 +249,9 @@ pub struct NymDirectoryGateway {
     pub last_probe: Option<Probe>,
     pub ip_addresses: Vec<String>,
     pub entry: EntryInformation,
+    // DNS resolvers the exit gateway offers to its clients. Not all gateways report them.
+    #[serde(default)]
+    pub dns_servers: Vec<String>,
     // The performance data here originates from the nym-api, and is effectively mixnet performance
     // at the time of writing this
     pub performance: Percent,
@@ -376,21 +379,60 @@ fn is_recently_updated(last_updated_utc: &str) -> bool {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct NymDirectoryGatewayCountriesResponse(Vec<NymDirectoryCountry>);
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct NymDirectoryGatewayCountriesResponse {
+    countries: Vec<NymDirectoryCountry>,
+    #[serde(skip)]
+    skipped: usize,
+}
 
 impl NymDirectoryGatewayCountriesResponse {
     pub fn into_inner(self) -> Vec<NymDirectoryCountry> {
-        self.0
+        self.countries
+    }
+
+    /// Number of malformed entries that were left out of the response.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+}
+
+// A single malformed entry shouldn't make us throw away the whole list, so skip the entries that
+// aren't valid country codes instead of failing to deserialize.
+impl<'de> Deserialize<'de> for NymDirectoryGatewayCountriesResponse {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let entries = Vec::<serde_json::Value>::deserialize(deserializer)?;
+        let mut countries = Vec::with_capacity(entries.len());
+        let mut skipped = 0;
+        for entry in entries {
+            match entry.as_str() {
+                Some(iso_code) if is_valid_iso_code(iso_code) => {
+                    countries.push(NymDirectoryCountry(iso_code.to_owned()))
+                }
+                _ => {
+                    tracing::warn!("Skipping malformed country in directory response: {entry}");
+                    skipped += 1;
+                }
+            }
+        }
+        Ok(Self { countries, skipped })
     }
 }
 
+fn is_valid_iso_code(iso_code: &str) -> bool {
+    iso_code.len() == 2 && iso_code.chars().all(|c| c.is_ascii_alphabetic())
+}
+
 impl IntoIterator for NymDirectoryGatewayCountriesResponse {
     type Item = NymDirectoryCountry;
     type IntoIter = std::vec::IntoIter<Self::Item>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.0.into_iter()
+        self.countries.into_iter()
     }
 }
 
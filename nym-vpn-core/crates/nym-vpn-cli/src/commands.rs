@@ -8,8 +8,8 @@ use std::{
     sync::OnceLock,
 };
 
-use clap::{Args, Parser, Subcommand};
-use ipnetwork::{Ipv4Network, Ipv6Network};
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use ipnetwork::{IpNetwork, Ipv4Network, Ipv6Network};
 
 const TUN_IP4_SUBNET: &str = "10.0.0.0/16";
 const TUN_IP6_SUBNET: &str = "2001:db8:a160::0/112";
@@ -45,6 +45,7 @@ pub(crate) enum Commands {
 }
 
 #[derive(Args)]
+#[command(group(ArgGroup::new("tun_ipv6").args(["nym_ipv6", "nym_ula_ipv6"])))]
 pub(crate) struct RunArgs {
     #[command(flatten)]
     pub(crate) entry: CliEntry,
@@ -60,14 +61,33 @@ pub(crate) struct RunArgs {
     #[arg(long, requires = "wireguard_mode", default_value_t = false)]
     pub(crate) netstack: bool,
 
+    /// Only connect to the entry gateway over this wireguard port. Can be given multiple times.
+    /// Only helps with gateways that listen on several ports.
+    #[arg(
+        long = "preferred-wg-port",
+        value_name = "PORT",
+        requires = "wireguard_mode"
+    )]
+    pub(crate) preferred_wg_ports: Vec<u16>,
+
     /// The IPv4 address of the nym TUN device that wraps IP packets in sphinx packets.
-    #[arg(long, alias = "ipv4", value_parser = validate_ipv4, requires = "nym_ipv6")]
+    #[arg(long, alias = "ipv4", value_parser = validate_ipv4, requires = "tun_ipv6")]
     pub(crate) nym_ipv4: Option<Ipv4Addr>,
 
     /// The IPv6 address of the nym TUN device that wraps IP packets in sphinx packets.
     #[arg(long, alias = "ipv6", value_parser = validate_ipv6, requires = "nym_ipv4")]
     pub(crate) nym_ipv6: Option<Ipv6Addr>,
 
+    /// Request this unique local IPv6 address and network for the nym TUN device from the IPR,
+    /// along with `--nym-ipv4`, e.g. `fd00:1234::2/64`. The network is routed through the device.
+    #[arg(
+        long,
+        value_parser = validate_ula_ipv6,
+        requires = "nym_ipv4",
+        conflicts_with = "wireguard_mode"
+    )]
+    pub(crate) nym_ula_ipv6: Option<Ipv6Network>,
+
     /// The MTU of the nym TUN device that wraps IP packets in sphinx packets.
     #[arg(long, alias = "mtu")]
     pub(crate) nym_mtu: Option<u16>,
@@ -76,12 +96,83 @@ pub(crate) struct RunArgs {
     #[arg(long)]
     pub(crate) dns: Option<IpAddr>,
 
+    /// Use the DNS servers advertised by the exit gateway instead of the default ones, if it
+    /// advertises any.
+    #[arg(long, conflicts_with = "dns")]
+    pub(crate) gateway_dns: bool,
+
+    /// Periodically check for DNS queries answered by other servers than the configured ones.
+    #[arg(long)]
+    pub(crate) detect_dns_leaks: bool,
+
+    /// Check for DNS leaks every this many seconds, 60 by default.
+    #[arg(long, requires = "detect_dns_leaks", value_parser = clap::value_parser!(u64).range(1..))]
+    pub(crate) dns_leak_probe_interval: Option<u64>,
+
+    /// Don't reconnect when the default route changes, e.g. when switching networks.
+    #[arg(long)]
+    pub(crate) disable_reconnect_on_network_change: bool,
+
+    /// Fail instead of picking two distinct gateways when the entry and exit are requested in
+    /// the same country.
+    #[arg(long)]
+    pub(crate) disallow_same_country: bool,
+
+    /// Use throwaway keys for this connection only, nothing is read from or written to the data
+    /// path. Can't be combined with the credentials mode.
+    #[arg(long, conflicts_with = "enable_credentials_mode")]
+    pub(crate) ephemeral_identity: bool,
+
+    /// Give up after this many failed connection attempts instead of retrying forever.
+    #[arg(long)]
+    pub(crate) max_connect_attempts: Option<u32>,
+
+    /// Give up on a connection attempt after this many seconds, 90 by default. Set to 0 to wait
+    /// for as long as it takes.
+    #[arg(long)]
+    pub(crate) connect_deadline: Option<u64>,
+
+    /// Disconnect after having been connected for this many seconds.
+    #[arg(long)]
+    pub(crate) max_session_duration: Option<u64>,
+
+    /// Disconnect once no traffic went through the tunnel for this many seconds.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub(crate) idle_timeout: Option<u64>,
+
+    /// Seed the random gateway selection, so that repeated runs pick the same gateways.
+    #[arg(long)]
+    pub(crate) selection_seed: Option<u64>,
+
+    /// Pick the random entry and exit gateways on the same continent.
+    #[arg(long, conflicts_with = "different_continent")]
+    pub(crate) same_continent: bool,
+
+    /// Pick the random entry and exit gateways on different continents.
+    #[arg(long)]
+    pub(crate) different_continent: bool,
+
+    /// Pick among the gateways with the highest performance instead of any gateway. This makes
+    /// it easier to guess which gateways are used, as there are fewer candidates.
+    #[arg(long)]
+    pub(crate) highest_performance: bool,
+
     /// Disable routing all traffic through the nym TUN device. When the flag is set, the nym TUN
     /// device will be created, but to route traffic through it you will need to do it manually,
     /// e.g. ping -Itun0.
     #[arg(long)]
     pub(crate) disable_routing: bool,
 
+    /// Only route the traffic to this network through the tunnel, e.g. 10.1.0.0/16, and leave
+    /// the rest outside of it. Can be given multiple times. The system DNS is kept unless the DNS
+    /// is set.
+    #[arg(
+        long = "route",
+        value_name = "NETWORK",
+        conflicts_with = "disable_routing"
+    )]
+    pub(crate) routed_destinations: Vec<IpNetwork>,
+
     /// Disable the Poisson process rate limiting of outbound traffic.
     #[arg(long, hide = true)]
     pub(crate) disable_poisson_rate: bool,
@@ -105,6 +196,10 @@ pub(crate) struct RunArgs {
     // Set the minimum performance level for VPN gateways.
     #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
     pub(crate) min_gateway_vpn_performance: Option<u8>,
+
+    /// Cache the gateway directory in the data path and reuse it for this many seconds.
+    #[arg(long)]
+    pub(crate) directory_cache_ttl_secs: Option<u64>,
 }
 
 #[derive(Args)]
@@ -121,6 +216,10 @@ pub(crate) struct CliEntry {
     /// Auto-select entry gateway by latency
     #[arg(long, alias = "entry-fastest")]
     pub(crate) entry_gateway_low_latency: bool,
+
+    /// Hostname or IP address the entry gateway advertises.
+    #[arg(long, alias = "entry-hostname")]
+    pub(crate) entry_gateway_hostname: Option<String>,
 }
 
 #[derive(Args)]
@@ -137,6 +236,10 @@ pub(crate) struct CliExit {
     /// Auto-select exit gateway by country ISO.
     #[arg(long, alias = "exit-country")]
     pub(crate) exit_gateway_country: Option<String>,
+
+    /// Hostname or IP address the exit gateway advertises.
+    #[arg(long, alias = "exit-hostname")]
+    pub(crate) exit_gateway_hostname: Option<String>,
 }
 
 #[derive(Args)]
@@ -170,6 +273,15 @@ fn validate_ipv6(ip: &str) -> Result<Ipv6Addr, String> {
     Ok(ip)
 }
 
+fn validate_ula_ipv6(network: &str) -> Result<Ipv6Network, String> {
+    let network = Ipv6Network::from_str(network).map_err(|err| err.to_string())?;
+    let ula = Ipv6Network::from_str("fc00::/7").unwrap();
+    if !ula.contains(network.ip()) {
+        return Err(format!("IPv6 address must be in the range {}", ula));
+    }
+    Ok(network)
+}
+
 fn check_path(path: &str) -> Result<PathBuf, String> {
     let path = PathBuf::from(path);
     if !path.exists() {
@@ -6,16 +6,20 @@ pub(crate) enum Error {
     #[error(transparent)]
     VpnLib(#[from] nym_vpn_lib::Error),
 
-    #[error("identity not formatted correctly")]
-    NodeIdentityFormatting,
-
-    #[error("recipient is not formatted correctly")]
-    RecipientFormatting,
+    #[error(transparent)]
+    GatewayDirectory(#[from] nym_vpn_lib::gateway_directory::Error),
 
     #[cfg(unix)]
     #[error("sudo/root privileges required, try rerunning with sudo: `sudo -E {binary_name} run`")]
     RootPrivilegesRequired { binary_name: String },
 
+    #[cfg(target_os = "linux")]
+    #[error("missing capabilities {missing}, try rerunning with sudo: `sudo -E {binary_name} run`, or grant them to the binary")]
+    CapabilitiesRequired {
+        binary_name: String,
+        missing: String,
+    },
+
     #[cfg(windows)]
     #[error("administrator privileges required, try rerunning with administrator privileges: `runas /user:Administrator {binary_name} run`")]
     AdminPrivilegesRequired { binary_name: String },
@@ -5,7 +5,7 @@ mod commands;
 mod error;
 mod shutdown_handler;
 
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
 use anyhow::Context;
 use clap::Parser;
@@ -17,11 +17,14 @@ use nym_vpn_lib::{
     gateway_directory::{Config as GatewayConfig, EntryPoint, ExitPoint},
     nym_config::defaults::{setup_env, var_names},
     tunnel_state_machine::{
-        DnsOptions, GatewayPerformanceOptions, MixnetTunnelOptions, NymConfig, TunnelCommand,
-        TunnelEvent, TunnelSettings, TunnelStateMachine, TunnelType, WireguardMultihopMode,
-        WireguardTunnelOptions,
+        ConnectRetryOptions, DnsOptions, GatewayLookupRetryOptions, GatewayPerformanceOptions,
+        MixnetTunnelOptions, NymConfig, RegionAffinity, SelectionStrategy, SystemTunProvider,
+        TunnelCommand, TunnelEvent, TunnelSettings, TunnelStateMachine, TunnelType,
+        WireguardMultihopMode, WireguardTunnelOptions, DEFAULT_CONNECT_DEADLINE,
+        DEFAULT_DNS_LEAK_PROBE_INTERVAL, DEFAULT_IDLE_TIMEOUT_WARNING, DEFAULT_MAX_CLOCK_SKEW,
+        DEFAULT_SESSION_EXPIRY_WARNING,
     },
-    IpPair, MixnetClientConfig, NodeIdentity, Recipient,
+    IpPair, MixnetClientConfig,
 };
 use nym_vpn_store::mnemonic::MnemonicStorage as _;
 
@@ -69,40 +72,33 @@ pub(crate) fn setup_logging(args: &CliArgs) {
 }
 
 fn parse_entry_point(args: &commands::RunArgs) -> Result<EntryPoint> {
-    if let Some(ref entry_gateway_id) = args.entry.entry_gateway_id {
-        Ok(EntryPoint::Gateway {
-            identity: NodeIdentity::from_base58_string(entry_gateway_id.clone())
-                .map_err(|_| Error::NodeIdentityFormatting)?,
-        })
+    let entry = if let Some(ref entry_gateway_id) = args.entry.entry_gateway_id {
+        format!("gateway:{entry_gateway_id}")
     } else if let Some(ref entry_gateway_country) = args.entry.entry_gateway_country {
-        Ok(EntryPoint::Location {
-            location: entry_gateway_country.clone(),
-        })
+        format!("country:{entry_gateway_country}")
     } else if args.entry.entry_gateway_low_latency {
-        Ok(EntryPoint::RandomLowLatency)
+        "low-latency".to_string()
+    } else if let Some(ref entry_gateway_hostname) = args.entry.entry_gateway_hostname {
+        format!("hostname:{entry_gateway_hostname}")
     } else {
-        Ok(EntryPoint::Random)
-    }
+        "random".to_string()
+    };
+    Ok(EntryPoint::from_cli_str(&entry)?)
 }
 
 fn parse_exit_point(args: &commands::RunArgs) -> Result<ExitPoint> {
-    if let Some(ref exit_router_address) = args.exit.exit_router_address {
-        Ok(ExitPoint::Address {
-            address: Recipient::try_from_base58_string(exit_router_address.clone())
-                .map_err(|_| Error::RecipientFormatting)?,
-        })
+    let exit = if let Some(ref exit_router_address) = args.exit.exit_router_address {
+        format!("address:{exit_router_address}")
     } else if let Some(ref exit_router_id) = args.exit.exit_gateway_id {
-        Ok(ExitPoint::Gateway {
-            identity: NodeIdentity::from_base58_string(exit_router_id.clone())
-                .map_err(|_| Error::NodeIdentityFormatting)?,
-        })
+        format!("gateway:{exit_router_id}")
     } else if let Some(ref exit_gateway_country) = args.exit.exit_gateway_country {
-        Ok(ExitPoint::Location {
-            location: exit_gateway_country.clone(),
-        })
+        format!("country:{exit_gateway_country}")
+    } else if let Some(ref exit_gateway_hostname) = args.exit.exit_gateway_hostname {
+        format!("hostname:{exit_gateway_hostname}")
     } else {
-        Ok(ExitPoint::Random)
-    }
+        "random".to_string()
+    };
+    Ok(ExitPoint::from_cli_str(&exit)?)
 }
 
 #[allow(unreachable_code)]
@@ -117,6 +113,13 @@ fn check_root_privileges(args: &commands::CliArgs) -> Result<()> {
         return Ok(());
     }
 
+    // Routing only needs the network capabilities, which is enough for e.g. containers that are
+    // granted them without running as root.
+    #[cfg(target_os = "linux")]
+    if matches!(args.command, Commands::Run(_)) {
+        return linux_has_net_capabilities("nym-vpn-cli");
+    }
+
     #[cfg(unix)]
     return unix_has_root("nym-vpn-cli");
 
@@ -140,6 +143,54 @@ pub(crate) fn unix_has_root(binary_name: &str) -> Result<()> {
     }
 }
 
+/// Capabilities needed to create the tun device and set up the routes.
+#[cfg(target_os = "linux")]
+const REQUIRED_CAPABILITIES: [(&str, u32); 2] = [("CAP_NET_ADMIN", 12), ("CAP_NET_RAW", 13)];
+
+#[cfg(target_os = "linux")]
+pub(crate) fn linux_has_net_capabilities(binary_name: &str) -> Result<()> {
+    let Some(effective) = effective_capabilities() else {
+        tracing::debug!("Failed to query the capabilities, checking for root instead");
+        return unix_has_root(binary_name);
+    };
+
+    let missing = missing_capabilities(effective);
+    if missing.is_empty() {
+        tracing::debug!("Network capabilities acquired");
+        Ok(())
+    } else {
+        Err(Error::CapabilitiesRequired {
+            binary_name: binary_name.to_string(),
+            missing: missing.join(", "),
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn missing_capabilities(effective: u64) -> Vec<&'static str> {
+    REQUIRED_CAPABILITIES
+        .iter()
+        .filter(|(_, bit)| effective & (1u64 << bit) == 0)
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+// Reads the effective capability set of the process, which root has in full unless dropped.
+#[cfg(target_os = "linux")]
+fn effective_capabilities() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    parse_effective_capabilities(&status)
+}
+
+#[cfg(target_os = "linux")]
+fn parse_effective_capabilities(status: &str) -> Option<u64> {
+    let mask = status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))?
+        .trim();
+    u64::from_str_radix(mask, 16).ok()
+}
+
 #[cfg(windows)]
 pub(crate) fn win_has_admin(binary_name: &str) -> Result<()> {
     if is_elevated::is_elevated() {
@@ -174,7 +225,10 @@ async fn run_vpn(args: commands::RunArgs, data_path: Option<PathBuf>) -> anyhow:
 
     let entry_point = parse_entry_point(&args)?;
     let exit_point = parse_exit_point(&args)?;
-    let nym_ips = if let (Some(ipv4), Some(ipv6)) = (args.nym_ipv4, args.nym_ipv6) {
+    let nym_ipv6 = args
+        .nym_ipv6
+        .or(args.nym_ula_ipv6.map(|network| network.ip()));
+    let nym_ips = if let (Some(ipv4), Some(ipv6)) = (args.nym_ipv4, nym_ipv6) {
         Some(IpPair::new(ipv4, ipv6))
     } else {
         None
@@ -184,10 +238,11 @@ async fn run_vpn(args: commands::RunArgs, data_path: Option<PathBuf>) -> anyhow:
     let (event_tx, mut event_rx) = mpsc::unbounded_channel();
     let shutdown_token = CancellationToken::new();
 
-    let dns = args
-        .dns
-        .map(|ip| DnsOptions::Custom(vec![ip]))
-        .unwrap_or_default();
+    let dns = match args.dns {
+        Some(ip) => DnsOptions::Custom(vec![ip]),
+        None if args.gateway_dns => DnsOptions::GatewayProvided,
+        None => DnsOptions::default(),
+    };
 
     let tunnel_type = if args.wireguard_mode {
         TunnelType::Wireguard
@@ -201,11 +256,14 @@ async fn run_vpn(args: commands::RunArgs, data_path: Option<PathBuf>) -> anyhow:
             || args.disable_background_cover_traffic,
         min_mixnode_performance: args.min_mixnode_performance,
         min_gateway_performance: args.min_gateway_mixnet_performance,
+        ..Default::default()
     };
 
     let mixnet_tunnel_options = MixnetTunnelOptions {
         interface_addrs: nym_ips,
+        ula_ipv6: args.nym_ula_ipv6,
         mtu: args.nym_mtu,
+        ..Default::default()
     };
 
     let nym_config = NymConfig {
@@ -219,18 +277,64 @@ async fn run_vpn(args: commands::RunArgs, data_path: Option<PathBuf>) -> anyhow:
         } else {
             WireguardMultihopMode::TunTun
         },
+        preferred_wg_ports: args.preferred_wg_ports.clone(),
+        ..Default::default()
     };
 
     let tunnel_settings = TunnelSettings {
         tunnel_type,
         enable_credentials_mode: args.enable_credentials_mode,
         mixnet_client_config: Some(mixnet_client_config),
+        mixnet_client_startup_timeout: None,
+        shutdown_timeout: None,
+        max_clock_skew: DEFAULT_MAX_CLOCK_SKEW,
+        directory_cache_ttl: args.directory_cache_ttl_secs.map(Duration::from_secs),
+        proxy: None,
         gateway_performance_options: GatewayPerformanceOptions::default(),
+        gateway_lookup_retry_options: GatewayLookupRetryOptions::default(),
+        connect_retry_options: ConnectRetryOptions {
+            max_connect_attempts: args.max_connect_attempts,
+            ..Default::default()
+        },
+        connect_deadline: match args.connect_deadline {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => Some(DEFAULT_CONNECT_DEADLINE),
+        },
         mixnet_tunnel_options,
         wireguard_tunnel_options,
         entry_point: Box::new(entry_point),
         exit_point: Box::new(exit_point),
+        allow_same_country: !args.disallow_same_country,
+        excluded_gateways: Vec::new(),
+        sticky_gateways: false,
+        allow_lan: false,
+        routed_destinations: args.routed_destinations.clone(),
+        reconnect_on_network_change: !args.disable_reconnect_on_network_change,
         dns,
+        detect_dns_leaks: args.detect_dns_leaks,
+        dns_leak_probe_interval: args
+            .dns_leak_probe_interval
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_DNS_LEAK_PROBE_INTERVAL),
+        ephemeral_identity: args.ephemeral_identity,
+        max_session_duration: args.max_session_duration.map(Duration::from_secs),
+        session_expiry_warning: DEFAULT_SESSION_EXPIRY_WARNING,
+        idle_timeout: args.idle_timeout.map(Duration::from_secs),
+        idle_timeout_warning: DEFAULT_IDLE_TIMEOUT_WARNING,
+        selection_seed: args.selection_seed,
+        region_affinity: if args.same_continent {
+            Some(RegionAffinity::SameContinent)
+        } else if args.different_continent {
+            Some(RegionAffinity::DifferentContinent)
+        } else {
+            None
+        },
+        selection_strategy: if args.highest_performance {
+            SelectionStrategy::HighestPerformance
+        } else {
+            SelectionStrategy::Random
+        },
     };
 
     let state_machine_handle = TunnelStateMachine::spawn(
@@ -238,6 +342,7 @@ async fn run_vpn(args: commands::RunArgs, data_path: Option<PathBuf>) -> anyhow:
         event_tx,
         nym_config,
         tunnel_settings,
+        Arc::new(SystemTunProvider),
         shutdown_token.child_token(),
     )
     .await
@@ -303,3 +408,35 @@ fn mixnet_data_path() -> Option<PathBuf> {
         std::env::var(var_names::NETWORK_NAME).expect("NETWORK_NAME env var not set");
     dirs::data_dir().map(|dir| dir.join(CONFIG_DIRECTORY_NAME).join(network_name))
 }
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effective_capabilities_are_parsed_from_proc_status() {
+        let status = "Name:\tnym-vpn-cli\nCapInh:\t0000000000000000\n\
+                      CapPrm:\t0000000000003000\nCapEff:\t0000000000003000\n\
+                      CapBnd:\t000001ffffffffff\n";
+
+        assert_eq!(parse_effective_capabilities(status), Some(0x3000));
+    }
+
+    #[test]
+    fn unreadable_effective_capabilities_are_not_parsed() {
+        assert_eq!(parse_effective_capabilities("Name:\tnym-vpn-cli\n"), None);
+        assert_eq!(parse_effective_capabilities("CapEff:\tnot-hex\n"), None);
+    }
+
+    #[test]
+    fn missing_capabilities_are_listed_by_name() {
+        assert_eq!(
+            missing_capabilities(0),
+            vec!["CAP_NET_ADMIN", "CAP_NET_RAW"]
+        );
+        assert_eq!(missing_capabilities(1 << 12), vec!["CAP_NET_RAW"]);
+        assert_eq!(missing_capabilities(1 << 13), vec!["CAP_NET_ADMIN"]);
+        assert!(missing_capabilities(0x3000).is_empty());
+        assert!(missing_capabilities(0x000001ff_ffffffff).is_empty());
+    }
+}
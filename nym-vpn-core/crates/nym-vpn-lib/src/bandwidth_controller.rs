@@ -1,13 +1,13 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::time::Duration;
+use std::{fmt, net::IpAddr, time::Duration};
 
 use nym_wireguard_types::DEFAULT_PEER_TIMEOUT_CHECK;
+use tokio::sync::{mpsc, oneshot};
 use tokio_stream::{wrappers::IntervalStream, StreamExt};
 
 use nym_credentials_interface::TicketType;
-use nym_gateway_directory::GatewayClient;
 use nym_sdk::{mixnet::CredentialStorage as Storage, NymNetworkDetails, TaskClient};
 use nym_validator_client::{
     nyxd::{Config as NyxdClientConfig, NyxdClient},
@@ -15,18 +15,15 @@ use nym_validator_client::{
 };
 use nym_wg_gateway_client::{ErrorMessage, GatewayData, WgGatewayClient, WgGatewayLightClient};
 
-const DEFAULT_BANDWIDTH_CHECK: Duration = Duration::from_secs(5); // 5 seconds
+use crate::tunnel_state_machine::WireguardTunnelOptions;
+
+pub(crate) const DEFAULT_BANDWIDTH_CHECK: Duration = Duration::from_secs(5); // 5 seconds
+pub(crate) const DEFAULT_TOP_UP_THRESHOLD: u64 = 10 * 1024 * 1024; // 10 MB
+pub(crate) const DEFAULT_LOW_BANDWIDTH_THRESHOLD: u64 = 1024 * 1024; // 1 MB
 const DEFAULT_BANDWIDTH_DEPLETION_RATE: u64 = 1024 * 1024; // 1 MB/s
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
-    #[error("failed to lookup gateway ip: {source}")]
-    LookupGatewayIp {
-        gateway_id: String,
-        #[source]
-        source: nym_gateway_directory::Error,
-    },
-
     #[error("failed to register wireguard with the gateway: {source}")]
     RegisterWireguard {
         gateway_id: String,
@@ -53,6 +50,49 @@ pub enum Error {
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// Status message sent when the bandwidth with a gateway was topped up.
+#[derive(Debug)]
+pub(crate) struct BandwidthToppedUp {
+    pub(crate) new_total: i64,
+}
+
+impl fmt::Display for BandwidthToppedUp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bandwidth topped up, new total: {}", self.new_total)
+    }
+}
+
+impl nym_task::TaskStatusEvent for BandwidthToppedUp {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+/// Status message sent once when the bandwidth with a gateway drops below the low bandwidth
+/// threshold.
+#[derive(Debug)]
+pub(crate) struct BandwidthLow {
+    pub(crate) remaining: i64,
+    pub(crate) threshold: u64,
+}
+
+impl fmt::Display for BandwidthLow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bandwidth running low: {} remaining, below {}",
+            si_scale::helpers::bibytes2(self.remaining as f64),
+            si_scale::helpers::bibytes2(self.threshold as f64)
+        )
+    }
+}
+
+impl nym_task::TaskStatusEvent for BandwidthLow {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum CredentialNyxdClientError {
     #[error("failed to create nyxd client config: {0}")]
@@ -81,6 +121,30 @@ fn get_nyxd_client() -> Result<QueryHttpRpcNyxdClient> {
         .map_err(CredentialNyxdClientError::FailedToConnectUsingNyxdClient)?)
 }
 
+/// Request to rotate the wireguard keys with both gateways. The gateway clients are handed over to
+/// the bandwidth controller for the duration of the rotation, and returned in the reply.
+// Only the desktop tunnels support rotating keys for now
+#[cfg_attr(any(target_os = "ios", target_os = "android"), allow(dead_code))]
+pub(crate) struct KeyRotationRequest {
+    pub(crate) entry_gateway_client: WgGatewayClient,
+    pub(crate) exit_gateway_client: WgGatewayClient,
+    /// The current entry session, which the new keys are registered with.
+    pub(crate) entry_gateway_data: GatewayData,
+    /// The current exit session, which the new keys are registered with.
+    pub(crate) exit_gateway_data: GatewayData,
+    pub(crate) reply_tx: oneshot::Sender<KeyRotationReply>,
+}
+
+pub(crate) struct KeyRotationReply {
+    pub(crate) entry_gateway_client: WgGatewayClient,
+    pub(crate) exit_gateway_client: WgGatewayClient,
+    /// Gateway data registered for the new entry keys. On failure, including when the gateway
+    /// assigned a different endpoint or addresses, the client kept its old keys.
+    pub(crate) entry: Result<GatewayData>,
+    /// Gateway data registered for the new exit keys, see `entry`.
+    pub(crate) exit: Result<GatewayData>,
+}
+
 pub(crate) struct DepletionRate {
     current_depletion_rate: u64,
     available_bandwidth: u64,
@@ -147,6 +211,15 @@ pub(crate) struct BandwidthController<St> {
     timeout_check_interval: IntervalStream,
     entry_depletion_rate: DepletionRate,
     exit_depletion_rate: DepletionRate,
+    enable_credentials_mode: bool,
+    tickets_per_top_up: u32,
+    top_up_threshold: u64,
+    low_bandwidth_threshold: u64,
+    // Whether the bandwidth was below the low bandwidth threshold on the last check, so that we
+    // only notify on the downward crossing.
+    entry_bandwidth_low: bool,
+    exit_bandwidth_low: bool,
+    key_rotation_rx: Option<mpsc::UnboundedReceiver<KeyRotationRequest>>,
     shutdown: TaskClient,
 }
 
@@ -155,12 +228,15 @@ impl<St: Storage> BandwidthController<St> {
         storage: St,
         wg_entry_gateway_client: WgGatewayLightClient,
         wg_exit_gateway_client: WgGatewayLightClient,
+        enable_credentials_mode: bool,
+        wireguard_tunnel_options: &WireguardTunnelOptions,
         shutdown: TaskClient,
     ) -> Result<Self> {
         let client = get_nyxd_client()?;
         let inner = nym_bandwidth_controller::BandwidthController::new(storage, client);
-        let timeout_check_interval =
-            IntervalStream::new(tokio::time::interval(DEFAULT_BANDWIDTH_CHECK));
+        let timeout_check_interval = IntervalStream::new(tokio::time::interval(
+            wireguard_tunnel_options.bandwidth_check_interval,
+        ));
 
         Ok(BandwidthController {
             inner,
@@ -169,15 +245,33 @@ impl<St: Storage> BandwidthController<St> {
             timeout_check_interval,
             entry_depletion_rate: Default::default(),
             exit_depletion_rate: Default::default(),
+            enable_credentials_mode,
+            tickets_per_top_up: wireguard_tunnel_options.tickets_per_top_up,
+            top_up_threshold: wireguard_tunnel_options.top_up_threshold,
+            low_bandwidth_threshold: wireguard_tunnel_options.low_bandwidth_threshold,
+            entry_bandwidth_low: false,
+            exit_bandwidth_low: false,
+            key_rotation_rx: None,
             shutdown,
         })
     }
 
+    /// Serve key rotation requests while running, since registering new keys may need to spend
+    /// tickets from the credential storage.
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    pub(crate) fn with_key_rotation(
+        mut self,
+        key_rotation_rx: mpsc::UnboundedReceiver<KeyRotationRequest>,
+    ) -> Self {
+        self.key_rotation_rx = Some(key_rotation_rx);
+        self
+    }
+
     pub(crate) async fn get_initial_bandwidth(
         &self,
         enable_credentials_mode: bool,
         ticketbook_type: TicketType,
-        gateway_client: &GatewayClient,
+        gateway_host: IpAddr,
         wg_gateway_client: &mut WgGatewayClient,
     ) -> Result<GatewayData>
     where
@@ -187,13 +281,6 @@ impl<St: Storage> BandwidthController<St> {
         tracing::info!("Registering with wireguard gateway");
         let authenticator_address = wg_gateway_client.auth_recipient();
         let gateway_id = *wg_gateway_client.auth_recipient().gateway();
-        let gateway_host = gateway_client
-            .lookup_gateway_ip(&gateway_id.to_base58_string())
-            .await
-            .map_err(|source| Error::LookupGatewayIp {
-                gateway_id: gateway_id.to_base58_string(),
-                source,
-            })?;
         let wg_gateway_data = wg_gateway_client
             .register_wireguard(
                 gateway_host,
@@ -212,6 +299,83 @@ impl<St: Storage> BandwidthController<St> {
         Ok(wg_gateway_data)
     }
 
+    async fn rotate_gateway_keys(
+        &self,
+        ticketbook_type: TicketType,
+        current: &GatewayData,
+        wg_gateway_client: &mut WgGatewayClient,
+    ) -> Result<GatewayData>
+    where
+        <St as Storage>::StorageError: Send + Sync + 'static,
+    {
+        tracing::info!("Rotating keys with wireguard gateway");
+        let authenticator_address = wg_gateway_client.auth_recipient();
+        let gateway_id = *wg_gateway_client.auth_recipient().gateway();
+        wg_gateway_client
+            .rotate_keys(
+                current,
+                &self.inner,
+                self.enable_credentials_mode,
+                ticketbook_type,
+            )
+            .await
+            .map_err(|source| Error::RegisterWireguard {
+                gateway_id: gateway_id.to_base58_string(),
+                authenticator_address: Box::new(authenticator_address),
+                source,
+            })
+    }
+
+    async fn rotate_keys(&mut self, request: KeyRotationRequest)
+    where
+        <St as Storage>::StorageError: Send + Sync + 'static,
+    {
+        let KeyRotationRequest {
+            mut entry_gateway_client,
+            mut exit_gateway_client,
+            entry_gateway_data,
+            exit_gateway_data,
+            reply_tx,
+        } = request;
+
+        let entry = self
+            .rotate_gateway_keys(
+                TicketType::V1WireguardEntry,
+                &entry_gateway_data,
+                &mut entry_gateway_client,
+            )
+            .await;
+        let exit = self
+            .rotate_gateway_keys(
+                TicketType::V1WireguardExit,
+                &exit_gateway_data,
+                &mut exit_gateway_client,
+            )
+            .await;
+
+        // The bandwidth is accounted per key, so start over with the new ones
+        if entry.is_ok() {
+            self.wg_entry_gateway_client = entry_gateway_client.light_client();
+            self.entry_depletion_rate = Default::default();
+            self.entry_bandwidth_low = false;
+        }
+        if exit.is_ok() {
+            self.wg_exit_gateway_client = exit_gateway_client.light_client();
+            self.exit_depletion_rate = Default::default();
+            self.exit_bandwidth_low = false;
+        }
+
+        let reply = KeyRotationReply {
+            entry_gateway_client,
+            exit_gateway_client,
+            entry,
+            exit,
+        };
+        if reply_tx.send(reply).is_err() {
+            tracing::warn!("Key rotation was requested by a tunnel that has gone away");
+        }
+    }
+
     pub(crate) async fn top_up_bandwidth(
         &self,
         ticketbook_type: TicketType,
@@ -222,35 +386,127 @@ impl<St: Storage> BandwidthController<St> {
     {
         let authenticator_address = wg_gateway_client.auth_recipient();
         let gateway_id = *wg_gateway_client.auth_recipient().gateway();
-        let remaining_bandwidth =
-            WgGatewayClient::top_up_wireguard(wg_gateway_client, &self.inner, ticketbook_type)
-                .await
-                .map_err(|source| Error::TopUpWireguard {
-                    gateway_id: gateway_id.to_string(),
-                    ticketbook_type,
-                    authenticator_address: Box::new(authenticator_address),
-                    source,
-                })?;
+        let remaining_bandwidth = WgGatewayClient::top_up_wireguard(
+            wg_gateway_client,
+            &self.inner,
+            ticketbook_type,
+            self.tickets_per_top_up,
+        )
+        .await
+        .map_err(|source| Error::TopUpWireguard {
+            gateway_id: gateway_id.to_string(),
+            ticketbook_type,
+            authenticator_address: Box::new(authenticator_address),
+            source,
+        })?;
         Ok(remaining_bandwidth)
     }
 
+    // Top up the bandwidth with the gateway. If that fails while there's still bandwidth left, we
+    // try again on the next check, and only signal that we're out of bandwidth once it has run out.
+    async fn top_up_or_stop(
+        &mut self,
+        entry: bool,
+        remaining_bandwidth: i64,
+        wg_gateway_client: &mut WgGatewayLightClient,
+    ) where
+        <St as Storage>::StorageError: Send + Sync + 'static,
+    {
+        let ticketbook_type = if entry {
+            TicketType::V1WireguardEntry
+        } else {
+            TicketType::V1WireguardExit
+        };
+        match self
+            .top_up_bandwidth(ticketbook_type, wg_gateway_client)
+            .await
+        {
+            Ok(new_total) => {
+                tracing::info!(
+                    "Topped up bandwidth with gateway {}",
+                    wg_gateway_client.auth_recipient().gateway()
+                );
+                self.shutdown
+                    .send_status_msg(Box::new(BandwidthToppedUp { new_total }));
+            }
+            Err(e) if remaining_bandwidth > 0 => {
+                tracing::warn!(
+                    "Error topping up with more bandwidth, will retry on the next check while {} \
+                     remain: {:?}",
+                    si_scale::helpers::bibytes2(remaining_bandwidth as f64),
+                    e
+                );
+            }
+            Err(e) => {
+                tracing::warn!("Error topping up with more bandwidth {:?}", e);
+                // TODO: try to return this error in the JoinHandle instead
+                self.shutdown
+                    .send_we_stopped(Box::new(ErrorMessage::OutOfBandwidth {
+                        gateway_id: Box::new(*wg_gateway_client.auth_recipient().gateway()),
+                        authenticator_address: Box::new(wg_gateway_client.auth_recipient()),
+                    }));
+            }
+        }
+    }
+
+    // Notify once when the remaining bandwidth crosses below the low bandwidth threshold. Going
+    // back above it, e.g. after a top-up, re-arms the notification.
+    fn notify_low_bandwidth(&mut self, entry: bool, remaining_bandwidth: i64) {
+        let threshold = self.low_bandwidth_threshold;
+        let bandwidth_low = if entry {
+            &mut self.entry_bandwidth_low
+        } else {
+            &mut self.exit_bandwidth_low
+        };
+        let is_low = remaining_bandwidth < threshold as i64;
+        let crossed_below = is_low && !*bandwidth_low;
+        *bandwidth_low = is_low;
+
+        if crossed_below {
+            let msg = BandwidthLow {
+                remaining: remaining_bandwidth,
+                threshold,
+            };
+            tracing::warn!(
+                "Wireguard {msg}. The wireguard mode will get suspended once it runs out, until \
+                 tomorrow, UTC time"
+            );
+            self.shutdown.send_status_msg(Box::new(msg));
+        }
+    }
+
     async fn check_bandwidth(&mut self, entry: bool, current_period: Duration) -> Option<Duration>
     where
         <St as Storage>::StorageError: Send + Sync + 'static,
     {
-        let (mut wg_gateway_client, current_depletion_rate) = if entry {
-            (
-                self.wg_entry_gateway_client.clone(),
-                &mut self.entry_depletion_rate,
-            )
+        let mut wg_gateway_client = if entry {
+            self.wg_entry_gateway_client.clone()
         } else {
-            (
-                self.wg_exit_gateway_client.clone(),
-                &mut self.exit_depletion_rate,
-            )
+            self.wg_exit_gateway_client.clone()
+        };
+        let query_result = wg_gateway_client.query_bandwidth().await;
+        if let Ok(Some(remaining_bandwidth)) = query_result {
+            self.notify_low_bandwidth(entry, remaining_bandwidth);
+        }
+
+        let current_depletion_rate = if entry {
+            &mut self.entry_depletion_rate
+        } else {
+            &mut self.exit_depletion_rate
         };
-        match wg_gateway_client.query_bandwidth().await {
+        match query_result {
             Err(e) => tracing::warn!("Error querying remaining bandwidth {:?}", e),
+            Ok(Some(remaining_bandwidth))
+                if self.enable_credentials_mode
+                    && (remaining_bandwidth as u64) < self.top_up_threshold =>
+            {
+                tracing::info!(
+                    "Remaining bandwidth below {}, topping up",
+                    si_scale::helpers::bibytes2(self.top_up_threshold as f64)
+                );
+                self.top_up_or_stop(entry, remaining_bandwidth, &mut wg_gateway_client)
+                    .await;
+            }
             Ok(Some(remaining_bandwidth)) => {
                 match current_depletion_rate
                     .update_dynamic_check_interval(current_period, remaining_bandwidth as u64)
@@ -260,27 +516,8 @@ impl<St: Storage> BandwidthController<St> {
                         return Some(new_duration);
                     }
                     Ok(None) => {
-                        let ticketbook_type = if entry {
-                            TicketType::V1WireguardEntry
-                        } else {
-                            TicketType::V1WireguardExit
-                        };
-                        if let Err(e) = self
-                            .top_up_bandwidth(ticketbook_type, &mut wg_gateway_client)
-                            .await
-                        {
-                            tracing::warn!("Error topping up with more bandwidth {:?}", e);
-                            // TODO: try to return this error in the JoinHandle instead
-                            self.shutdown
-                                .send_we_stopped(Box::new(ErrorMessage::OutOfBandwidth {
-                                    gateway_id: Box::new(
-                                        *wg_gateway_client.auth_recipient().gateway(),
-                                    ),
-                                    authenticator_address: Box::new(
-                                        wg_gateway_client.auth_recipient(),
-                                    ),
-                                }));
-                        }
+                        self.top_up_or_stop(entry, remaining_bandwidth, &mut wg_gateway_client)
+                            .await;
                     }
                 }
             }
@@ -300,6 +537,9 @@ impl<St: Storage> BandwidthController<St> {
                 _ = self.shutdown.recv() => {
                     tracing::trace!("BandwidthController: Received shutdown");
                 }
+                Some(request) = next_key_rotation_request(&mut self.key_rotation_rx) => {
+                    self.rotate_keys(request).await;
+                }
                 _ = self.timeout_check_interval.next() => {
                     let current_period = self.timeout_check_interval.as_ref().period();
                     let entry_duration = self.check_bandwidth(true, current_period).await;
@@ -325,3 +565,12 @@ impl<St: Storage> BandwidthController<St> {
         }
     }
 }
+
+async fn next_key_rotation_request(
+    key_rotation_rx: &mut Option<mpsc::UnboundedReceiver<KeyRotationRequest>>,
+) -> Option<KeyRotationRequest> {
+    match key_rotation_rx {
+        Some(key_rotation_rx) => key_rotation_rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
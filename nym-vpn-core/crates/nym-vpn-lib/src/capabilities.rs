@@ -0,0 +1,80 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::tunnel_state_machine::TunnelType;
+
+/// Features available in this build of the library, for the platform it was built for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Record)]
+pub struct Capabilities {
+    /// Tunnels through two wireguard gateways.
+    pub wireguard_supported: bool,
+
+    /// Tunnels through the mixnet.
+    pub mixnet_supported: bool,
+
+    /// Blocks the traffic outside of the tunnel while connecting and reconnecting. The firewall is
+    /// not enabled on any platform yet.
+    pub kill_switch_supported: bool,
+
+    /// Sends the traffic of some apps outside of the tunnel.
+    pub split_tunnel_supported: bool,
+
+    /// Reaches IPv4 gateways from IPv6-only networks through DNS64. iOS needs the library to
+    /// re-resolve the gateways, Android does it on its own.
+    pub dns64_supported: bool,
+}
+
+impl Capabilities {
+    /// Whether a tunnel of `tunnel_type` can be set up with this build.
+    pub fn supports_tunnel_type(&self, tunnel_type: TunnelType) -> bool {
+        match tunnel_type {
+            TunnelType::Mixnet => self.mixnet_supported,
+            TunnelType::Wireguard => self.wireguard_supported,
+        }
+    }
+}
+
+/// Returns the features available in this build.
+pub const fn capabilities() -> Capabilities {
+    Capabilities {
+        wireguard_supported: true,
+        mixnet_supported: true,
+        kill_switch_supported: false,
+        split_tunnel_supported: false,
+        dns64_supported: cfg!(any(target_os = "ios", target_os = "android")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tunnel_state_machine::TunnelSettings;
+
+    #[test]
+    fn default_tunnel_type_is_supported() {
+        let tunnel_type = TunnelSettings::default().tunnel_type;
+        assert!(capabilities().supports_tunnel_type(tunnel_type));
+    }
+
+    #[test]
+    fn tunnel_type_support_follows_the_flags() {
+        let capabilities = Capabilities {
+            mixnet_supported: false,
+            ..capabilities()
+        };
+        assert!(capabilities.supports_tunnel_type(TunnelType::Wireguard));
+        assert!(!capabilities.supports_tunnel_type(TunnelType::Mixnet));
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    #[test]
+    fn desktop_has_no_dns64() {
+        assert!(!capabilities().dns64_supported);
+    }
+
+    #[cfg(any(target_os = "ios", target_os = "android"))]
+    #[test]
+    fn mobile_has_dns64() {
+        assert!(capabilities().dns64_supported);
+    }
+}
@@ -29,6 +29,24 @@ pub enum GatewayDirectoryError {
         source: nym_gateway_directory::Error,
     },
 
+    #[error("entry gateway {requested_identity} not found in the {network_name} network")]
+    FailedToSelectEntryGatewayIdNotFound {
+        requested_identity: String,
+        network_name: String,
+    },
+
+    #[error("exit gateway {requested_identity} not found in the {network_name} network")]
+    FailedToSelectExitGatewayIdNotFound {
+        requested_identity: String,
+        network_name: String,
+    },
+
+    #[error("gateway {requested_identity} is below the minimum performance of {min_performance}%")]
+    GatewayBelowPerformanceThreshold {
+        requested_identity: String,
+        min_performance: u8,
+    },
+
     #[error("failed to lookup router address: {source}")]
     FailedToLookupRouterAddress {
         source: nym_gateway_directory::Error,
@@ -36,6 +54,27 @@ pub enum GatewayDirectoryError {
 
     #[error("unable to use same entry and exit gateway for location: {requested_location}")]
     SameEntryAndExitGatewayFromCountry { requested_location: String },
+
+    #[error(
+        "all gateways for location: {} are excluded: {}",
+        requested_location.as_deref().unwrap_or("any"),
+        excluded_gateways.join(", ")
+    )]
+    AllGatewaysExcluded {
+        requested_location: Option<String>,
+        excluded_gateways: Vec<String>,
+    },
+
+    #[error("entry gateway {requested_identity} doesn't run the authenticator wireguard requires")]
+    EntryGatewayWithoutAuthenticator { requested_identity: String },
+
+    #[error("exit gateway {requested_identity} doesn't run the authenticator wireguard requires")]
+    ExitGatewayWithoutAuthenticator { requested_identity: String },
+
+    #[error("no entry and exit gateways available on {region_affinity}")]
+    RegionAffinityNotSatisfied {
+        region_affinity: crate::tunnel_state_machine::RegionAffinity,
+    },
 }
 
 pub use super::tunnel_state_machine::Error;
@@ -7,6 +7,7 @@ pub mod storage;
 pub mod util;
 
 mod bandwidth_controller;
+mod capabilities;
 mod error;
 mod mixnet;
 mod platform;
@@ -16,7 +17,10 @@ pub mod tunnel_state_machine;
 mod uniffi_custom_impls;
 mod wg_config;
 
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
 
 // Re-export some our nym dependencies
 pub use nym_authenticator_client::Error as AuthenticatorClientError;
@@ -37,8 +41,9 @@ pub use nym_wg_gateway_client as wg_gateway_client;
 #[cfg(any(target_os = "ios", target_os = "macos"))]
 pub use crate::platform::swift;
 pub use crate::{
+    capabilities::{capabilities, Capabilities},
     error::{Error, GatewayDirectoryError},
-    mixnet::MixnetError,
+    mixnet::{MixnetError, SharedMixnetClient},
 };
 
 pub const DEFAULT_DNS_SERVERS: [IpAddr; 4] = [
@@ -48,7 +53,7 @@ pub const DEFAULT_DNS_SERVERS: [IpAddr; 4] = [
     IpAddr::V6(Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1001)),
 ];
 
-#[derive(Clone, Default, Debug, Eq, PartialEq)]
+#[derive(Clone, Default, Debug, Eq, PartialEq, uniffi::Record)]
 pub struct MixnetClientConfig {
     /// Disable Poission process rate limiting of outbound traffic.
     pub disable_poisson_rate: bool,
@@ -61,6 +66,14 @@ pub struct MixnetClientConfig {
 
     /// The minimum performance of gateways to use.
     pub min_gateway_performance: Option<u8>,
+
+    /// Number of times to retry connecting to the IPR of the exit gateway when it doesn't
+    /// answer. Set to 0 to only try once.
+    pub ipr_connect_max_retries: u32,
+
+    /// Delay before the first retry of the IPR connect, doubled on each subsequent one. Leave
+    /// `None` to use the default delay.
+    pub ipr_connect_retry_delay: Option<Duration>,
 }
 
 #[derive(Debug, Clone, Copy)]
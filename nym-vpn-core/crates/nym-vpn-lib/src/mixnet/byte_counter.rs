@@ -0,0 +1,78 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Running count of the IP packet bytes read from and written to the tun device by the mixnet
+/// processor and listener. Cover traffic and the connection monitor's own pings never go through
+/// the tun device, so only real traffic is counted.
+#[derive(Debug, Default)]
+pub(super) struct ByteCounter {
+    sent: AtomicU64,
+    received: AtomicU64,
+}
+
+impl ByteCounter {
+    pub(super) fn record_sent(&self, len: usize) {
+        self.sent.fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    pub(super) fn record_received(&self, len: usize) {
+        self.received.fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    pub(super) fn snapshot(&self) -> TunnelBytesStatus {
+        TunnelBytesStatus {
+            bytes_sent: self.sent.load(Ordering::Relaxed),
+            bytes_received: self.received.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Status message carrying the byte counters, sent periodically by the mixnet processor
+/// whenever they changed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TunnelBytesStatus {
+    pub(crate) bytes_sent: u64,
+    pub(crate) bytes_received: u64,
+}
+
+impl fmt::Display for TunnelBytesStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "mixnet tunnel bytes sent: {}, received: {}",
+            self.bytes_sent, self.bytes_received
+        )
+    }
+}
+
+impl nym_task::TaskStatusEvent for TunnelBytesStatus {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_are_counted_per_direction() {
+        let counter = ByteCounter::default();
+        counter.record_sent(1280);
+        counter.record_sent(40);
+        counter.record_received(576);
+
+        assert_eq!(
+            counter.snapshot(),
+            TunnelBytesStatus {
+                bytes_sent: 1320,
+                bytes_received: 576,
+            }
+        );
+    }
+}
@@ -36,6 +36,9 @@ fn apply_mixnet_client_config(
         disable_background_cover_traffic,
         min_mixnode_performance,
         min_gateway_performance,
+        // Used when connecting to the IPR, not by the client itself.
+        ipr_connect_max_retries: _,
+        ipr_connect_retry_delay: _,
     } = mixnet_client_config;
 
     tracing::info!(
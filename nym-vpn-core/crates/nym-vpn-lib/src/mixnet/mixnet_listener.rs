@@ -1,6 +1,8 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
+use std::sync::Arc;
+
 use bytes::Bytes;
 use futures::{channel::mpsc, prelude::stream::SplitSink, SinkExt, StreamExt};
 use nym_connection_monitor::{ConnectionStatusEvent, IcmpBeaconReply, Icmpv6BeaconReply};
@@ -10,9 +12,14 @@ use nym_task::TaskClient;
 use tokio::task::JoinHandle;
 use tokio_util::codec::Framed;
 use tracing::{debug, error, trace};
-use tun::{AsyncDevice, TunPacket, TunPacketCodec};
+use tun::{TunPacket, TunPacketCodec};
 
-use super::SharedMixnetClient;
+use super::{
+    byte_counter::ByteCounter,
+    packet_drops::{Direction, DropReason, PacketDropCounter},
+    SharedMixnetClient, TrafficActivity,
+};
+use crate::tunnel_state_machine::TunDevice;
 
 // The mixnet listener is responsible for listening for incoming mixnet messages from the mixnet
 // client, and if they contain IP packets, forward them to the tun device.
@@ -27,7 +34,7 @@ pub(super) struct MixnetListener {
     task_client: TaskClient,
 
     // Sink for sending packets to the tun device
-    tun_device_sink: SplitSink<Framed<AsyncDevice, TunPacketCodec>, TunPacket>,
+    tun_device_sink: SplitSink<Framed<Box<dyn TunDevice>, TunPacketCodec>, TunPacket>,
 
     // Identifier for ICMP beacon
     icmp_beacon_identifier: u16,
@@ -37,16 +44,29 @@ pub(super) struct MixnetListener {
 
     // Connection event sender
     connection_event_tx: mpsc::UnboundedSender<ConnectionStatusEvent>,
+
+    // Counter for the packets that didn't make it to the tun device
+    packet_drops: Arc<PacketDropCounter>,
+
+    // Counter for the bytes of the real packets written to the tun device
+    byte_counter: Arc<ByteCounter>,
+
+    // Last time a real packet was written to the tun device
+    traffic_activity: TrafficActivity,
 }
 
 impl MixnetListener {
+    #[allow(clippy::too_many_arguments)]
     pub(super) async fn new(
         mixnet_client: SharedMixnetClient,
         task_client: TaskClient,
-        tun_device_sink: SplitSink<Framed<AsyncDevice, TunPacketCodec>, TunPacket>,
+        tun_device_sink: SplitSink<Framed<Box<dyn TunDevice>, TunPacketCodec>, TunPacket>,
         icmp_beacon_identifier: u16,
         our_ips: IpPair,
         connection_event_tx: mpsc::UnboundedSender<ConnectionStatusEvent>,
+        packet_drops: Arc<PacketDropCounter>,
+        byte_counter: Arc<ByteCounter>,
+        traffic_activity: TrafficActivity,
     ) -> Self {
         let our_address = mixnet_client.nym_address().await;
         let ipr_client = IprListener::new(our_address);
@@ -59,6 +79,9 @@ impl MixnetListener {
             icmp_beacon_identifier,
             our_ips,
             connection_event_tx,
+            packet_drops,
+            byte_counter,
+            traffic_activity,
         }
     }
 
@@ -69,15 +92,19 @@ impl MixnetListener {
         }
     }
 
-    fn check_for_icmp_beacon_reply(&self, packet: &Bytes) {
+    // Returns whether the packet is a reply to our beacon.
+    fn check_for_icmp_beacon_reply(&self, packet: &Bytes) -> bool {
         if let Some(connection_event) =
             check_for_icmp_beacon_reply(packet, self.icmp_beacon_identifier, self.our_ips)
         {
             self.send_connection_event(connection_event);
+            true
+        } else {
+            false
         }
     }
 
-    async fn run(mut self) -> SplitSink<Framed<AsyncDevice, TunPacketCodec>, TunPacket> {
+    async fn run(mut self) -> SplitSink<Framed<Box<dyn TunDevice>, TunPacketCodec>, TunPacket> {
         // We are the only one listening for mixnet messages when this is active
         let mut mixnet_client_binding = self.mixnet_client.lock().await;
         let mixnet_client = mixnet_client_binding.as_mut().unwrap();
@@ -93,18 +120,34 @@ impl MixnetListener {
                     match self.ipr_listener.handle_reconstructed_message(reconstructed_message).await {
                         Ok(Some(MixnetMessageOutcome::IpPackets(packets))) => {
                             for packet in packets {
-                                self.check_for_icmp_beacon_reply(&packet);
+                                // The beacon replies aren't traffic of our own, so they don't
+                                // keep the tunnel from being idle.
+                                let is_beacon_reply = self.check_for_icmp_beacon_reply(&packet);
+                                if !is_beacon_reply {
+                                    self.traffic_activity.record();
+                                }
 
                                 // Consider not including packets that are ICMP ping replies to our beacon
                                 // in the responses. We are defensive here just in case we incorrectly
                                 // label real packets as ping replies to our beacon.
-                                if let Err(err) = self.tun_device_sink.send(TunPacket::new(packet.to_vec())).await {
-                                    error!("Failed to send packet to tun device: {err}");
+                                let len = packet.len();
+                                let tun_packet = TunPacket::new(packet.to_vec());
+                                match self.tun_device_sink.send(tun_packet).await {
+                                    Ok(()) if !is_beacon_reply => {
+                                        self.byte_counter.record_received(len);
+                                    }
+                                    Ok(()) => {}
+                                    Err(err) => {
+                                        let reason = DropReason::from_write_error(&err);
+                                        self.packet_drops.record(Direction::Ingress, reason);
+                                    }
                                 }
                             }
                         }
-                        Ok(Some(MixnetMessageOutcome::MixnetSelfPing)) => {
-                            self.send_connection_event(ConnectionStatusEvent::MixnetSelfPing);
+                        Ok(Some(MixnetMessageOutcome::MixnetSelfPing { request_id })) => {
+                            self.send_connection_event(
+                                ConnectionStatusEvent::MixnetSelfPing { request_id },
+                            );
                         }
                         Ok(None) => {}
                         Err(err) => {
@@ -125,7 +168,7 @@ impl MixnetListener {
 
     pub(super) fn start(
         self,
-    ) -> JoinHandle<SplitSink<Framed<AsyncDevice, TunPacketCodec>, TunPacket>> {
+    ) -> JoinHandle<SplitSink<Framed<Box<dyn TunDevice>, TunPacketCodec>, TunPacket>> {
         tokio::spawn(self.run())
     }
 }
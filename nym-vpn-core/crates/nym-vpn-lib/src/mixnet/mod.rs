@@ -1,14 +1,20 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
+mod byte_counter;
 mod connect;
 mod error;
 mod mixnet_listener;
+mod packet_drops;
 mod processor;
 mod shared_mixnet_client;
+mod traffic_activity;
 
+pub(crate) use byte_counter::TunnelBytesStatus;
 pub(crate) use connect::setup_mixnet_client;
+pub(crate) use packet_drops::PacketDropsStatus;
 pub(crate) use processor::{start_processor, Config};
-pub(crate) use shared_mixnet_client::SharedMixnetClient;
+pub(crate) use traffic_activity::TrafficActivity;
 
 pub use error::MixnetError;
+pub use shared_mixnet_client::SharedMixnetClient;
@@ -0,0 +1,158 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{fmt, io, sync::Mutex};
+
+use tracing::warn;
+
+use crate::tunnel_state_machine::{MixnetPacketDrops, PacketDropCounts};
+
+// Log a summary every time this many more packets have been dropped, rather than once per drop.
+const LOG_EVERY_N_DROPS: u64 = 100;
+
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Direction {
+    Ingress,
+    Egress,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) enum DropReason {
+    QueueFull,
+    Oversized,
+    SendError,
+}
+
+impl DropReason {
+    /// Reason for a packet that couldn't be written to the tun device. A full device queue is
+    /// reported as `ENOBUFS` on unix, or as a write that would block.
+    pub(super) fn from_write_error(err: &io::Error) -> Self {
+        #[cfg(unix)]
+        if err.raw_os_error() == Some(nix::errno::Errno::ENOBUFS as i32) {
+            return DropReason::QueueFull;
+        }
+        if err.kind() == io::ErrorKind::WouldBlock {
+            DropReason::QueueFull
+        } else {
+            DropReason::SendError
+        }
+    }
+}
+
+/// Running count of the packets dropped by the mixnet processor and listener.
+#[derive(Debug, Default)]
+pub(super) struct PacketDropCounter {
+    drops: Mutex<MixnetPacketDrops>,
+}
+
+impl PacketDropCounter {
+    pub(super) fn record(&self, direction: Direction, reason: DropReason) {
+        let drops = {
+            let mut drops = self.drops.lock().unwrap();
+            let counts = match direction {
+                Direction::Ingress => &mut drops.ingress,
+                Direction::Egress => &mut drops.egress,
+            };
+            counts.record(reason);
+            *drops
+        };
+
+        if drops.total() % LOG_EVERY_N_DROPS == 1 {
+            warn!(
+                "Mixnet processor dropped {} packets ({drops})",
+                drops.total()
+            );
+        }
+    }
+
+    pub(super) fn snapshot(&self) -> MixnetPacketDrops {
+        *self.drops.lock().unwrap()
+    }
+}
+
+impl PacketDropCounts {
+    fn record(&mut self, reason: DropReason) {
+        let count = match reason {
+            DropReason::QueueFull => &mut self.queue_full,
+            DropReason::Oversized => &mut self.oversized,
+            DropReason::SendError => &mut self.send_error,
+        };
+        *count += 1;
+    }
+}
+
+/// Status message carrying the packet drop counters, sent periodically by the mixnet processor
+/// whenever they changed.
+#[derive(Debug)]
+pub(crate) struct PacketDropsStatus {
+    pub(crate) drops: MixnetPacketDrops,
+}
+
+impl fmt::Display for PacketDropsStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mixnet packet drops: {}", self.drops)
+    }
+}
+
+impl nym_task::TaskStatusEvent for PacketDropsStatus {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_are_counted_per_direction_and_reason() {
+        let counter = PacketDropCounter::default();
+        counter.record(Direction::Ingress, DropReason::QueueFull);
+        counter.record(Direction::Ingress, DropReason::QueueFull);
+        counter.record(Direction::Ingress, DropReason::Oversized);
+        counter.record(Direction::Egress, DropReason::SendError);
+
+        let drops = counter.snapshot();
+        assert_eq!(
+            drops.ingress,
+            PacketDropCounts {
+                queue_full: 2,
+                oversized: 1,
+                send_error: 0,
+            }
+        );
+        assert_eq!(
+            drops.egress,
+            PacketDropCounts {
+                queue_full: 0,
+                oversized: 0,
+                send_error: 1,
+            }
+        );
+        assert_eq!(drops.total(), 4);
+    }
+
+    #[test]
+    fn full_device_queue_is_told_apart_from_other_write_errors() {
+        let would_block = io::Error::from(io::ErrorKind::WouldBlock);
+        assert!(matches!(
+            DropReason::from_write_error(&would_block),
+            DropReason::QueueFull
+        ));
+
+        #[cfg(unix)]
+        {
+            let no_buffers = io::Error::from_raw_os_error(nix::errno::Errno::ENOBUFS as i32);
+            assert!(matches!(
+                DropReason::from_write_error(&no_buffers),
+                DropReason::QueueFull
+            ));
+        }
+
+        let broken_pipe = io::Error::from(io::ErrorKind::BrokenPipe);
+        assert!(matches!(
+            DropReason::from_write_error(&broken_pipe),
+            DropReason::SendError
+        ));
+    }
+}
@@ -1,7 +1,7 @@
 // Copyright 2023 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::result::Result;
+use std::{result::Result, sync::Arc, time::Duration};
 
 use bytes::Bytes;
 use futures::{channel::mpsc, StreamExt};
@@ -9,11 +9,18 @@ use nym_connection_monitor::{ConnectionMonitorTask, ConnectionStatusEvent};
 use nym_ip_packet_requests::{codec::MultiIpPacketCodec, request::IpPacketRequest};
 use nym_sdk::mixnet::{InputMessage, MixnetMessageSender, Recipient};
 use nym_task::{connections::TransmissionLane, TaskClient, TaskManager};
-use tokio::task::JoinHandle;
-use tracing::{debug, error, info, trace};
-use tun::{AsyncDevice, Device};
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{debug, error, info, trace, warn};
 
-use super::{MixnetError, SharedMixnetClient};
+use super::{
+    byte_counter::ByteCounter,
+    packet_drops::{Direction, DropReason, PacketDropCounter, PacketDropsStatus},
+    MixnetError, SharedMixnetClient, TrafficActivity,
+};
+use crate::tunnel_state_machine::TunDevice;
+
+// How often the packet drop and byte counters are reported, if they changed.
+const COUNTERS_REPORT_INTERVAL: Duration = Duration::from_secs(2);
 
 #[derive(Debug)]
 pub(crate) struct Config {
@@ -48,21 +55,26 @@ impl MessageCreator {
 }
 
 struct MixnetProcessor {
-    device: AsyncDevice,
+    device: Box<dyn TunDevice>,
     mixnet_client: SharedMixnetClient,
     connection_event_tx: mpsc::UnboundedSender<ConnectionStatusEvent>,
     ip_packet_router_address: Recipient,
     our_ips: nym_ip_packet_requests::IpPair,
     icmp_beacon_identifier: u16,
+    /// Set while the tunnel is paused, the tun device isn't read from in the meantime.
+    paused_rx: watch::Receiver<bool>,
+    traffic_activity: TrafficActivity,
 }
 
 impl MixnetProcessor {
     fn new(
-        device: AsyncDevice,
+        device: Box<dyn TunDevice>,
         mixnet_client: SharedMixnetClient,
         connection_monitor: &ConnectionMonitorTask,
         ip_packet_router_address: Recipient,
         our_ips: nym_ip_packet_requests::IpPair,
+        paused_rx: watch::Receiver<bool>,
+        traffic_activity: TrafficActivity,
     ) -> Self {
         MixnetProcessor {
             device,
@@ -71,6 +83,8 @@ impl MixnetProcessor {
             ip_packet_router_address,
             our_ips,
             icmp_beacon_identifier: connection_monitor.icmp_beacon_identifier(),
+            paused_rx,
+            traffic_activity,
         }
     }
 
@@ -78,14 +92,29 @@ impl MixnetProcessor {
         self,
         mut task_client_mix_processor: TaskClient,
         task_client_mix_listener: TaskClient,
-    ) -> Result<AsyncDevice, MixnetError> {
-        info!(
-            "Opened mixnet processor on tun device {}",
-            self.device.get_ref().name().unwrap(),
-        );
+    ) -> Result<Box<dyn TunDevice>, MixnetError> {
+        match self.device.name() {
+            Ok(name) => info!("Opened mixnet processor on tun device {name}"),
+            Err(err) => warn!("Opened mixnet processor on a tun device without a name: {err}"),
+        }
+
+        let mtu = match self.device.mtu() {
+            Ok(mtu) => usize::try_from(mtu).ok(),
+            Err(err) => {
+                warn!("Failed to get tun device mtu, not checking packet sizes: {err}");
+                None
+            }
+        };
+
+        let mut paused_rx = self.paused_rx;
 
         debug!("Splitting tun device into sink and stream");
         let (tun_device_sink, mut tun_device_stream) = self.device.into_framed().split();
+        let packet_drops = Arc::new(PacketDropCounter::default());
+        let mut reported_packet_drops = packet_drops.snapshot();
+        let byte_counter = Arc::new(ByteCounter::default());
+        let mut reported_bytes = byte_counter.snapshot();
+        let mut counters_report_interval = tokio::time::interval(COUNTERS_REPORT_INTERVAL);
 
         debug!("Split mixnet sender");
         let sender = self.mixnet_client.split_sender().await;
@@ -108,6 +137,9 @@ impl MixnetProcessor {
             self.icmp_beacon_identifier,
             self.our_ips,
             self.connection_event_tx.clone(),
+            packet_drops.clone(),
+            byte_counter.clone(),
+            self.traffic_activity.clone(),
         )
         .await;
         let mixnet_listener_handle = mixnet_listener.start();
@@ -128,15 +160,29 @@ impl MixnetProcessor {
                         Ok(input_message) => {
                             let ret = sender.send(input_message).await;
                             if ret.is_err() && !task_client_mix_processor.is_shutdown_poll() {
-                                error!("Could not forward IP packet to the mixnet. The packet will be dropped.");
+                                packet_drops.record(Direction::Egress, DropReason::SendError);
                             }
                         }
-                        Err(err) => {
-                            error!("Failed to create input message: {err}");
+                        Err(_) => {
+                            packet_drops.record(Direction::Egress, DropReason::SendError);
                         }
                     };
                 }
-                Some(Ok(packet)) = tun_device_stream.next() => {
+                Ok(()) = paused_rx.changed() => {
+                    if *paused_rx.borrow_and_update() {
+                        info!("Mixnet processor is paused");
+                    } else {
+                        info!("Mixnet processor is resumed");
+                    }
+                }
+                Some(Ok(packet)) = tun_device_stream.next(), if !*paused_rx.borrow() => {
+                    if mtu.is_some_and(|mtu| packet.get_bytes().len() > mtu) {
+                        packet_drops.record(Direction::Egress, DropReason::Oversized);
+                        continue;
+                    }
+                    self.traffic_activity.record();
+                    byte_counter.record_sent(packet.get_bytes().len());
+
                     // Bundle up IP packets into a single mixnet message
                     if let Some(input_message) = multi_ip_packet_encoder
                         .append_packet(packet.into_bytes())
@@ -145,15 +191,28 @@ impl MixnetProcessor {
                             Ok(input_message) => {
                                 let ret = sender.send(input_message).await;
                                 if ret.is_err() && !task_client_mix_processor.is_shutdown_poll() {
-                                    error!("Could not forward IP packet to the mixnet. The packet(s) will be dropped.");
+                                    packet_drops.record(Direction::Egress, DropReason::SendError);
                                 }
                             }
-                            Err(err) => {
-                                error!("Failed to create input message, the packet(s) will be dropped: {err}");
+                            Err(_) => {
+                                packet_drops.record(Direction::Egress, DropReason::SendError);
                             }
                         }
                     }
                 }
+                _ = counters_report_interval.tick() => {
+                    let drops = packet_drops.snapshot();
+                    if drops != reported_packet_drops {
+                        reported_packet_drops = drops;
+                        task_client_mix_processor
+                            .send_status_msg(Box::new(PacketDropsStatus { drops }));
+                    }
+                    let bytes = byte_counter.snapshot();
+                    if bytes != reported_bytes {
+                        reported_bytes = bytes;
+                        task_client_mix_processor.send_status_msg(Box::new(bytes));
+                    }
+                }
                 else => {
                     error!("Mixnet processor: tun device stream ended");
                     break;
@@ -172,14 +231,17 @@ impl MixnetProcessor {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn start_processor(
     config: Config,
-    dev: AsyncDevice,
+    dev: Box<dyn TunDevice>,
     mixnet_client: SharedMixnetClient,
     task_manager: &TaskManager,
     our_ips: nym_ip_packet_requests::IpPair,
     connection_monitor: &ConnectionMonitorTask,
-) -> JoinHandle<Result<AsyncDevice, MixnetError>> {
+    paused_rx: watch::Receiver<bool>,
+    traffic_activity: TrafficActivity,
+) -> JoinHandle<Result<Box<dyn TunDevice>, MixnetError>> {
     info!("Creating mixnet processor");
     let processor = MixnetProcessor::new(
         dev,
@@ -187,6 +249,8 @@ pub(crate) async fn start_processor(
         connection_monitor,
         config.ip_packet_router_address,
         our_ips,
+        paused_rx,
+        traffic_activity,
     );
 
     // This is an unfortunate limitation of the TaskManager/TaskClient. Would be better if we could
@@ -5,6 +5,8 @@ use std::sync::Arc;
 
 use nym_sdk::mixnet::{MixnetClient, MixnetClientSender, Recipient};
 
+/// Mixnet client shared between the tasks using it. The client is taken out on shutdown, after
+/// which the accessors panic.
 #[derive(Clone)]
 pub struct SharedMixnetClient(Arc<tokio::sync::Mutex<Option<MixnetClient>>>);
 
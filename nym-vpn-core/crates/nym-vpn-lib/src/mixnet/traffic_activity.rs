@@ -0,0 +1,39 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use tokio::time::{Duration, Instant};
+
+/// Time of the last packet read from or written to the tun device, shared between the mixnet
+/// processor and listener. Cover traffic and the connection monitor's own pings never go through
+/// the tun device, so only real traffic counts as activity.
+#[derive(Debug, Clone)]
+pub(crate) struct TrafficActivity {
+    started_at: Instant,
+    // Milliseconds since `started_at`.
+    last_activity_ms: Arc<AtomicU64>,
+}
+
+impl TrafficActivity {
+    pub(crate) fn new() -> Self {
+        Self {
+            started_at: Instant::now(),
+            last_activity_ms: Arc::default(),
+        }
+    }
+
+    pub(crate) fn record(&self) {
+        let elapsed_ms = u64::try_from(self.started_at.elapsed().as_millis()).unwrap_or(u64::MAX);
+        self.last_activity_ms
+            .fetch_max(elapsed_ms, Ordering::Relaxed);
+    }
+
+    /// Returns when the last packet went through, or when tracking started if none did yet.
+    pub(crate) fn last_activity(&self) -> Instant {
+        self.started_at + Duration::from_millis(self.last_activity_ms.load(Ordering::Relaxed))
+    }
+}
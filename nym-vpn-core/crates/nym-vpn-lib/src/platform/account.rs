@@ -3,13 +3,19 @@
 
 use std::{path::PathBuf, str::FromStr, sync::Arc, time::Duration};
 
-use nym_vpn_account_controller::{AccountCommand, ReadyToConnect, SharedAccountState};
+use nym_sdk::mixnet::CredentialStorage;
+use nym_vpn_account_controller::{
+    AccountCommand, AvailableTicketbook, ReadyToConnect, SharedAccountState,
+};
 use nym_vpn_api_client::types::VpnApiAccount;
 use nym_vpn_store::{keys::KeyStore, mnemonic::MnemonicStorage};
-use tokio::{sync::mpsc::UnboundedSender, task::JoinHandle};
+use tokio::{
+    sync::{mpsc::UnboundedSender, oneshot},
+    task::JoinHandle,
+};
 use tokio_util::sync::CancellationToken;
 
-use crate::uniffi_custom_impls::AccountStateSummary;
+use crate::uniffi_custom_impls::{AccountStateSummary, TicketbookInfo};
 
 use super::{error::VpnError, ACCOUNT_CONTROLLER_HANDLE};
 
@@ -150,17 +156,23 @@ fn setup_account_storage(path: &str) -> Result<crate::storage::VpnClientOnDiskSt
     Ok(crate::storage::VpnClientOnDiskStorage::new(path))
 }
 
+fn parse_mnemonic(mnemonic: &str) -> Result<nym_vpn_store::mnemonic::Mnemonic, VpnError> {
+    nym_vpn_store::mnemonic::Mnemonic::parse(mnemonic).map_err(VpnError::from)
+}
+
+/// Parses the mnemonic and returns the id of its account, without touching the storage.
+pub(super) fn validate_account_mnemonic(mnemonic: &str) -> Result<String, VpnError> {
+    parse_mnemonic(mnemonic)
+        .map(VpnApiAccount::from)
+        .map(|account| account.id())
+}
+
 pub(super) async fn store_account_mnemonic(mnemonic: &str, path: &str) -> Result<(), VpnError> {
     // TODO: store the mnemonic by sending a command to the account controller instead of directly
     // interacting with the storage.
 
     let storage = setup_account_storage(path)?;
-
-    let mnemonic = nym_vpn_store::mnemonic::Mnemonic::parse(mnemonic).map_err(|err| {
-        VpnError::InternalError {
-            details: err.to_string(),
-        }
-    })?;
+    let mnemonic = parse_mnemonic(mnemonic)?;
 
     storage
         .store_mnemonic(mnemonic)
@@ -172,6 +184,70 @@ pub(super) async fn store_account_mnemonic(mnemonic: &str, path: &str) -> Result
     Ok(())
 }
 
+/// Like [`store_account_mnemonic`], but gives up when cancelled or once the timeout expires.
+///
+/// The write itself runs in its own task so that giving up can't interrupt it halfway through.
+/// If the caller gave up while it was in progress, the mnemonic is removed again once the write
+/// completes, so the store is left as it was found. Only the entry written by this call is
+/// removed, other mnemonics stored in the same file are kept.
+pub(super) async fn store_account_mnemonic_cancellable(
+    mnemonic: &str,
+    path: &str,
+    timeout: Duration,
+    cancel_token: &CancellationToken,
+) -> Result<(), VpnError> {
+    let storage = setup_account_storage(path)?;
+    let mnemonic = parse_mnemonic(mnemonic)?;
+    if cancel_token.is_cancelled() {
+        return Err(VpnError::Cancelled);
+    }
+
+    let written = mnemonic.clone();
+    let mut write = tokio::spawn(async move {
+        let result = storage.store_mnemonic(mnemonic).await;
+        (storage, result)
+    });
+    let reason = tokio::select! {
+        result = &mut write => return store_result(result.map(|(_, result)| result)),
+        _ = cancel_token.cancelled() => VpnError::Cancelled,
+        _ = tokio::time::sleep(timeout) => timed_out(timeout),
+    };
+
+    if let Ok((storage, Ok(()))) = write.await {
+        tracing::info!("Removing the mnemonic stored after the caller gave up: {reason}");
+        roll_back_stored_mnemonic(&storage, &written).await;
+    }
+    Err(reason)
+}
+
+// Removes the default mnemonic, but only if it's still the one that was just written.
+async fn roll_back_stored_mnemonic(
+    storage: &crate::storage::VpnClientOnDiskStorage,
+    written: &nym_vpn_store::mnemonic::Mnemonic,
+) {
+    match storage.load_mnemonic().await {
+        Ok(stored) if stored == *written => {
+            if let Err(err) = storage.remove_mnemonic().await {
+                tracing::error!("Failed to remove mnemonic: {err}");
+            }
+        }
+        Ok(_) => tracing::warn!("Not removing the mnemonic, it was replaced in the meantime"),
+        Err(err) => tracing::error!("Failed to load the mnemonic to remove: {err}"),
+    }
+}
+
+fn store_result<E: std::fmt::Display>(
+    result: Result<Result<(), E>, tokio::task::JoinError>,
+) -> Result<(), VpnError> {
+    result
+        .map_err(|err| VpnError::InternalError {
+            details: err.to_string(),
+        })?
+        .map_err(|err| VpnError::InternalError {
+            details: err.to_string(),
+        })
+}
+
 pub(super) async fn is_account_mnemonic_stored(path: &str) -> Result<bool, VpnError> {
     // TODO: query the mnemonic by sending a command to the account controller instead of directly
     // interacting with the storage.
@@ -185,6 +261,30 @@ pub(super) async fn is_account_mnemonic_stored(path: &str) -> Result<bool, VpnEr
         })
 }
 
+/// Like [`is_account_mnemonic_stored`], but gives up when cancelled or once the timeout expires.
+/// The check only reads from the store, so it's simply left to finish in the background.
+pub(super) async fn is_account_mnemonic_stored_cancellable(
+    path: &str,
+    timeout: Duration,
+    cancel_token: &CancellationToken,
+) -> Result<bool, VpnError> {
+    let path = path.to_owned();
+    let check = tokio::spawn(async move { is_account_mnemonic_stored(&path).await });
+    tokio::select! {
+        result = check => result.map_err(|err| VpnError::InternalError {
+            details: err.to_string(),
+        })?,
+        _ = cancel_token.cancelled() => Err(VpnError::Cancelled),
+        _ = tokio::time::sleep(timeout) => Err(timed_out(timeout)),
+    }
+}
+
+fn timed_out(timeout: Duration) -> VpnError {
+    VpnError::InternalError {
+        details: format!("operation timed out after {}s", timeout.as_secs()),
+    }
+}
+
 pub(super) async fn get_account_id(path: &str) -> Result<String, VpnError> {
     let storage = setup_account_storage(path)?;
     storage
@@ -228,8 +328,97 @@ pub(super) async fn update_account_state() -> Result<(), VpnError> {
     send_account_command(AccountCommand::UpdateAccountState).await
 }
 
+pub(super) async fn refresh_tickets() -> Result<u32, VpnError> {
+    let (result_tx, result_rx) = oneshot::channel();
+    send_account_command(AccountCommand::RefreshTickets(result_tx)).await?;
+    let result = result_rx.await.map_err(|err| VpnError::InternalError {
+        details: err.to_string(),
+    })?;
+    result.map_err(|err| match err {
+        nym_vpn_account_controller::Error::NotReadyToRefreshTickets(ready_to_connect) => {
+            VpnError::from(ready_to_connect)
+        }
+        err => VpnError::InternalError {
+            details: err.to_string(),
+        },
+    })
+}
+
 pub(super) async fn get_account_state() -> Result<AccountStateSummary, VpnError> {
     let shared_account_state = get_shared_account_state().await?;
     let account_state_summary = shared_account_state.lock().await.clone();
     Ok(AccountStateSummary::from(account_state_summary))
 }
+
+// Reads the ticketbooks directly from the credential storage in the data directory, so this works
+// without the account controller running.
+pub(super) async fn get_stored_ticketbooks(path: &str) -> Result<Vec<TicketbookInfo>, VpnError> {
+    let data_dir = PathBuf::from_str(path).map_err(|err| VpnError::InternalError {
+        details: err.to_string(),
+    })?;
+    let storage_paths = nym_sdk::mixnet::StoragePaths::new_from_dir(data_dir).map_err(|err| {
+        VpnError::InternalError {
+            details: err.to_string(),
+        }
+    })?;
+
+    // Don't create an empty credential storage just to list it
+    if !storage_paths.credential_database_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let storage = storage_paths
+        .persistent_credential_storage()
+        .await
+        .map_err(|err| VpnError::InternalError {
+            details: err.to_string(),
+        })?;
+    let ticketbooks_info =
+        storage
+            .get_ticketbooks_info()
+            .await
+            .map_err(|err| VpnError::InternalError {
+                details: err.to_string(),
+            })?;
+
+    Ok(ticketbooks_info
+        .into_iter()
+        .filter_map(|ticketbook| {
+            AvailableTicketbook::try_from(ticketbook)
+                .inspect_err(|err| tracing::error!("Failed to parse ticketbook: {}", err))
+                .ok()
+        })
+        .map(TicketbookInfo::from)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon \
+                            abandon abandon abandon about";
+
+    #[test]
+    fn validating_a_mnemonic_returns_its_account_id() {
+        let account_id = validate_account_mnemonic(MNEMONIC).unwrap();
+        let mnemonic = nym_vpn_store::mnemonic::Mnemonic::parse(MNEMONIC).unwrap();
+        assert_eq!(account_id, VpnApiAccount::from(mnemonic).id());
+    }
+
+    #[test]
+    fn invalid_mnemonics_are_told_apart() {
+        assert_eq!(
+            validate_account_mnemonic(&["abandon"; 11].join(" ")),
+            Err(VpnError::InvalidMnemonicWordCount { word_count: 11 })
+        );
+        assert_eq!(
+            validate_account_mnemonic(&["abandon"; 12].join(" ")),
+            Err(VpnError::InvalidMnemonicChecksum)
+        );
+        assert_eq!(
+            validate_account_mnemonic(&MNEMONIC.replace("about", "abuot")),
+            Err(VpnError::InvalidMnemonicWord { index: 11 })
+        );
+    }
+}
@@ -3,15 +3,27 @@
 
 use log::LevelFilter;
 
-pub(crate) fn init_logs(level: String) {
+use super::log_filter::LogFilter;
+
+pub(crate) fn init_logs(directives: String) {
     use android_logger::{Config, FilterBuilder};
-    let levels = level + ",tungstenite=warn,mio=warn,tokio_tungstenite=warn";
+    let (filter, parse_error) = LogFilter::parse_or_default(&directives);
+
+    let mut filter_builder = FilterBuilder::new();
+    filter_builder.filter_level(filter.default_level);
+    for (target, level) in &filter.targets {
+        filter_builder.filter_module(target, *level);
+    }
 
     android_logger::init_once(
         Config::default()
             .with_max_level(LevelFilter::Trace)
             .with_tag("libnymvpn")
-            .with_filter(FilterBuilder::new().parse(levels.as_str()).build()),
+            .with_filter(filter_builder.build()),
     );
     log::debug!("Logger initialized");
+
+    if let Some(err) = parse_error {
+        log::warn!("Invalid log filter \"{directives}\", falling back to info: {err}");
+    }
 }
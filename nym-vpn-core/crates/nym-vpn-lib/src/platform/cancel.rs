@@ -0,0 +1,56 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+use tokio_util::sync::CancellationToken;
+
+use super::error::VpnError;
+
+/// Handle that lets the app abort a pending call, for example a country fetch when the user
+/// navigates away from the screen that requested it.
+#[derive(Debug, Default, uniffi::Object)]
+pub struct CancelHandle {
+    token: CancellationToken,
+}
+
+#[uniffi::export]
+impl CancelHandle {
+    #[uniffi::constructor]
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+}
+
+impl CancelHandle {
+    pub(super) fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+// The lookup future is dropped on timeout or cancellation, which aborts the in-flight request
+// rather than leaving it running in the background.
+pub(super) async fn run_with_timeout<T>(
+    lookup: impl Future<Output = Result<T, VpnError>>,
+    timeout: Duration,
+    cancel_token: &CancellationToken,
+) -> Result<T, VpnError> {
+    match cancel_token
+        .run_until_cancelled(tokio::time::timeout(timeout, lookup))
+        .await
+    {
+        Some(Ok(result)) => result,
+        Some(Err(_)) => Err(VpnError::NetworkConnectionError {
+            details: format!("request timed out after {}s", timeout.as_secs()),
+        }),
+        None => Err(VpnError::Cancelled),
+    }
+}
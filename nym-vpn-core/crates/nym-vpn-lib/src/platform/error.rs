@@ -41,6 +41,38 @@ pub enum VpnError {
 
     #[error("account status unknown")]
     AccountStatusUnknown,
+
+    #[error("operation was cancelled")]
+    Cancelled,
+
+    #[error("invalid mnemonic: {word_count} words, expected 12, 15, 18, 21 or 24")]
+    InvalidMnemonicWordCount { word_count: u32 },
+
+    #[error("invalid mnemonic: word {index} is not in the word list")]
+    InvalidMnemonicWord { index: u32 },
+
+    #[error("invalid mnemonic: the checksum doesn't match, a word may be mistyped")]
+    InvalidMnemonicChecksum,
+
+    #[error("invalid shutdown timeout: {secs}s, expected between 1 and 60 seconds")]
+    InvalidShutdownTimeout { secs: u64 },
+}
+
+impl From<bip39::Error> for VpnError {
+    fn from(value: bip39::Error) -> Self {
+        match value {
+            bip39::Error::BadWordCount(word_count) => Self::InvalidMnemonicWordCount {
+                word_count: u32::try_from(word_count).unwrap_or(u32::MAX),
+            },
+            bip39::Error::UnknownWord(index) => Self::InvalidMnemonicWord {
+                index: u32::try_from(index).unwrap_or(u32::MAX),
+            },
+            bip39::Error::InvalidChecksum => Self::InvalidMnemonicChecksum,
+            err => Self::InternalError {
+                details: err.to_string(),
+            },
+        }
+    }
 }
 
 impl From<nym_vpn_account_controller::ReadyToConnect> for VpnError {
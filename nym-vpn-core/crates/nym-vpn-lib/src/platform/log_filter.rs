@@ -0,0 +1,124 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Translation of tracing env-filter directives, as used in `RUST_LOG`, into the per-target level
+//! filters understood by the platform loggers on iOS and Android.
+
+use std::str::FromStr;
+
+use log::LevelFilter;
+use tracing_subscriber::EnvFilter;
+
+// Targets that are too chatty at the default level, kept at warn unless the directives say
+// otherwise.
+const NOISY_TARGETS: &[&str] = &[
+    "hyper",
+    "tokio_reactor",
+    "reqwest",
+    "mio",
+    "want",
+    "tungstenite",
+    "tokio_tungstenite",
+    "handlebars",
+    "sled",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LogFilter {
+    pub(crate) default_level: LevelFilter,
+    pub(crate) targets: Vec<(String, LevelFilter)>,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self {
+            default_level: LevelFilter::Info,
+            targets: NOISY_TARGETS
+                .iter()
+                .map(|target| (target.to_string(), LevelFilter::Warn))
+                .collect(),
+        }
+    }
+}
+
+impl LogFilter {
+    /// Parses a comma separated list of `target=level` or `level` directives.
+    ///
+    /// The string is validated as a tracing env-filter. Span and field filters are accepted but
+    /// ignored, since the platform loggers only filter on the target.
+    pub(crate) fn parse(directives: &str) -> Result<Self, String> {
+        EnvFilter::builder()
+            .parse(directives)
+            .map_err(|err| err.to_string())?;
+
+        let mut filter = Self::default();
+        let mut targets = Vec::new();
+        for directive in directives.split(',').map(str::trim) {
+            if directive.is_empty() || directive.contains('[') {
+                continue;
+            }
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    targets.push((target.to_owned(), parse_level(level)?));
+                }
+                // A bare level sets the default, a bare target enables everything for it
+                None => match parse_level(directive) {
+                    Ok(level) => filter.default_level = level,
+                    Err(_) => targets.push((directive.to_owned(), LevelFilter::Trace)),
+                },
+            }
+        }
+
+        filter
+            .targets
+            .retain(|(noisy, _)| !targets.iter().any(|(target, _)| target == noisy));
+        filter.targets.extend(targets);
+        Ok(filter)
+    }
+
+    /// Like [`LogFilter::parse`], but falls back to the default filter on error. The error is
+    /// returned alongside so that it can be logged once the logger is up.
+    pub(crate) fn parse_or_default(directives: &str) -> (Self, Option<String>) {
+        match Self::parse(directives) {
+            Ok(filter) => (filter, None),
+            Err(err) => (Self::default(), Some(err)),
+        }
+    }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    LevelFilter::from_str(level).map_err(|_| format!("invalid log level: {level}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_default_level_and_targets() {
+        let filter = LogFilter::parse("warn,nym_gateway_directory=debug,hyper=info").unwrap();
+
+        assert_eq!(filter.default_level, LevelFilter::Warn);
+        assert!(filter
+            .targets
+            .contains(&("nym_gateway_directory".to_owned(), LevelFilter::Debug)));
+        assert!(filter
+            .targets
+            .contains(&("hyper".to_owned(), LevelFilter::Info)));
+        assert!(!filter
+            .targets
+            .contains(&("hyper".to_owned(), LevelFilter::Warn)));
+        assert!(filter
+            .targets
+            .contains(&("reqwest".to_owned(), LevelFilter::Warn)));
+    }
+
+    #[test]
+    fn invalid_directives_fall_back_to_default() {
+        let (filter, err) = LogFilter::parse_or_default("nym_gateway_directory=loud");
+        assert_eq!(filter, LogFilter::default());
+        assert!(err.is_some());
+
+        assert_eq!(LogFilter::parse("").unwrap(), LogFilter::default());
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Forwarding of tracing events to a listener implemented by the app, so that it can show logs
+//! in-app or attach them to bug reports.
+//!
+//! Events are handed over through a bounded channel to a dedicated thread that calls into the
+//! listener. If the listener can't keep up, events are dropped rather than holding up the code
+//! that emitted them.
+
+use std::{
+    fmt::{self, Write},
+    sync::{
+        mpsc::{self, SyncSender},
+        Arc, Once, RwLock,
+    },
+    time::SystemTime,
+};
+
+use tracing::{field::Field, Event, Level, Subscriber};
+use tracing_subscriber::{
+    layer::{Context, SubscriberExt},
+    EnvFilter, Layer,
+};
+
+// Number of log records that can be queued up for the listener before we start dropping them.
+const LOG_BUFFER_SIZE: usize = 1024;
+
+static INSTALL_LAYER: Once = Once::new();
+static LOG_SENDER: RwLock<Option<SyncSender<LogRecord>>> = RwLock::new(None);
+
+#[derive(uniffi::Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl From<&Level> for LogLevel {
+    fn from(level: &Level) -> Self {
+        match *level {
+            Level::TRACE => LogLevel::Trace,
+            Level::DEBUG => LogLevel::Debug,
+            Level::INFO => LogLevel::Info,
+            Level::WARN => LogLevel::Warn,
+            Level::ERROR => LogLevel::Error,
+        }
+    }
+}
+
+#[uniffi::export(with_foreign)]
+pub trait LogListener: Send + Sync {
+    fn on_log(&self, level: LogLevel, target: String, message: String, timestamp: SystemTime);
+}
+
+struct LogRecord {
+    level: LogLevel,
+    target: String,
+    message: String,
+    timestamp: SystemTime,
+}
+
+/// Start forwarding log events to the given listener, replacing any previously set listener.
+///
+/// The events are filtered by the given level filter, using the same syntax as `RUST_LOG`. Note
+/// that the filter is applied the first time a listener is set, and kept for later listeners.
+pub(crate) fn set_log_listener(listener: Arc<dyn LogListener>, log_level: &str) {
+    install_layer(log_level);
+
+    let (sender, receiver) = mpsc::sync_channel::<LogRecord>(LOG_BUFFER_SIZE);
+    let spawn_result = std::thread::Builder::new()
+        .name("log-listener".to_owned())
+        .spawn(move || {
+            // Exits when the sender is dropped, which happens when the listener is replaced
+            for record in receiver {
+                listener.on_log(
+                    record.level,
+                    record.target,
+                    record.message,
+                    record.timestamp,
+                );
+            }
+        });
+
+    if let Err(err) = spawn_result {
+        tracing::error!("Failed to spawn log listener thread: {}", err);
+        return;
+    }
+
+    match LOG_SENDER.write() {
+        Ok(mut guard) => *guard = Some(sender),
+        Err(err) => tracing::error!("Failed to set log listener: {}", err),
+    }
+}
+
+fn install_layer(log_level: &str) {
+    INSTALL_LAYER.call_once(|| {
+        let filter = EnvFilter::builder().parse_lossy(log_level);
+        let subscriber = tracing_subscriber::registry().with(LogListenerLayer.with_filter(filter));
+        // Not `try_init`, which also installs a `LogTracer` as the global `log` logger. That slot
+        // is taken by the platform logger from `init_logs`, which gets the tracing events through
+        // the `log-always` feature instead.
+        let result = tracing::subscriber::set_global_default(subscriber);
+
+        if let Err(err) = result {
+            log::error!("Failed to install log listener layer: {}", err);
+        }
+    });
+}
+
+struct LogListenerLayer;
+
+impl<S: Subscriber> Layer<S> for LogListenerLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let Ok(guard) = LOG_SENDER.read() else {
+            return;
+        };
+        let Some(sender) = guard.as_ref() else {
+            return;
+        };
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let metadata = event.metadata();
+        let record = LogRecord {
+            level: LogLevel::from(metadata.level()),
+            target: metadata.target().to_owned(),
+            message: visitor.finish(),
+            timestamp: SystemTime::now(),
+        };
+
+        // Never block here, this is called from whatever code emitted the event. If the listener
+        // is lagging behind the record is dropped.
+        let _ = sender.try_send(record);
+    }
+}
+
+// Formats the message of the event, followed by any other fields as `key=value`.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl MessageVisitor {
+    fn finish(mut self) -> String {
+        if !self.fields.is_empty() {
+            if !self.message.is_empty() {
+                self.message.push(' ');
+            }
+            self.message.push_str(&self.fields);
+        }
+        self.message
+    }
+}
+
+impl tracing::field::Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            if !self.fields.is_empty() {
+                self.fields.push(' ');
+            }
+            let _ = write!(self.fields, "{}={:?}", field.name(), value);
+        }
+    }
+}
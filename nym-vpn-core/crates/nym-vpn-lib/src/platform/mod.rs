@@ -5,45 +5,63 @@
 #[cfg(target_os = "android")]
 pub mod android;
 pub(crate) mod error;
+#[cfg(any(target_os = "android", target_os = "ios", target_os = "macos", test))]
+mod log_filter;
+pub mod log_listener;
 #[cfg(any(target_os = "ios", target_os = "macos"))]
 pub mod swift;
 
 mod account;
+mod cancel;
+mod preflight;
+mod status_listeners;
 
-use std::{env, path::PathBuf, sync::Arc, time::Duration};
+use std::{env, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
 
 use account::AccountControllerHandle;
 use lazy_static::lazy_static;
 use log::*;
+use time::OffsetDateTime;
 use tokio::{
     runtime::Runtime,
-    sync::{mpsc, Mutex},
+    sync::{mpsc, oneshot, Mutex},
     task::JoinHandle,
 };
 use tokio_util::sync::CancellationToken;
 use url::Url;
 
-use nym_gateway_directory::Config as GatewayDirectoryConfig;
+use nym_gateway_directory::{Config as GatewayDirectoryConfig, ProxyConfig};
 
-use self::error::VpnError;
+use self::{
+    cancel::CancelHandle, error::VpnError, log_listener::LogListener, preflight::PreflightReport,
+};
 #[cfg(target_os = "android")]
 use crate::tunnel_provider::android::AndroidTunProvider;
 #[cfg(target_os = "ios")]
 use crate::tunnel_provider::ios::OSTunProvider;
 use crate::{
+    capabilities::Capabilities,
     gateway_directory::GatewayClient,
     tunnel_state_machine::{
-        BandwidthEvent, ConnectionEvent, DnsOptions, GatewayPerformanceOptions,
-        MixnetTunnelOptions, NymConfig, TunnelCommand, TunnelEvent, TunnelSettings, TunnelState,
-        TunnelStateMachine, TunnelType, WireguardTunnelOptions,
+        BandwidthEvent, ConnectRetryOptions, ConnectionEvent, DisconnectReason, DnsOptions,
+        ExitPublicIp, GatewayLookupRetryOptions, GatewayPerformanceOptions, MixnetTunnelOptions,
+        NymConfig, OverheadEstimate, SelectionStrategy, TunnelCommand, TunnelEvent, TunnelSettings,
+        TunnelState, TunnelStateMachine, TunnelType, WireguardTunnelOptions,
+        DEFAULT_CONNECT_DEADLINE, DEFAULT_DNS_LEAK_PROBE_INTERVAL, DEFAULT_IDLE_TIMEOUT_WARNING,
+        DEFAULT_MAX_CLOCK_SKEW, DEFAULT_SESSION_EXPIRY_WARNING, SHUTDOWN_TIMEOUT_RANGE,
     },
     uniffi_custom_impls::{
-        AccountLinks, AccountStateSummary, BandwidthStatus, ConnectionStatus, EntryPoint,
-        ExitPoint, GatewayMinPerformance, GatewayType, Location, NetworkEnvironment, SystemMessage,
-        TunStatus, UserAgent,
+        AccountLinks, AccountStateSummary, AvailableNetwork, BandwidthStatus, ConnectionStatus,
+        EntryPoint, ExitPoint, GatewayMinPerformance, GatewayType, Location, NetworkEnvironment,
+        SystemMessage, TicketbookInfo, TunStatus, UserAgent,
     },
 };
 
+/// Timeout for the gateway directory lookups when the caller doesn't pass one.
+const DEFAULT_DIRECTORY_LOOKUP_TIMEOUT: Duration = Duration::from_secs(15);
+/// Timeout for the account storage operations when the caller doesn't pass one.
+const DEFAULT_ACCOUNT_STORAGE_TIMEOUT: Duration = Duration::from_secs(10);
+
 lazy_static! {
     static ref RUNTIME: Runtime = Runtime::new().unwrap();
     static ref STATE_MACHINE_HANDLE: Mutex<Option<StateMachineHandle>> = Mutex::new(None);
@@ -80,12 +98,48 @@ async fn start_vpn_inner(config: VPNConfig) -> Result<(), VpnError> {
     }
 }
 
+/// Check that the gateway directory is reachable, that the account is ready to connect and, for
+/// wireguard with an explicit entry gateway, that the gateway can be reached. Meant to be called
+/// before `startVPN` so the UI can tell what is wrong instead of waiting for the connect to time
+/// out.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn preflightCheck(entry_gateway: EntryPoint, enable_two_hop: bool) -> PreflightReport {
+    RUNTIME.block_on(preflight::preflight_check(
+        entry_gateway.into(),
+        enable_two_hop,
+    ))
+}
+
 #[allow(non_snake_case)]
 #[uniffi::export]
 pub fn stopVPN() -> Result<(), VpnError> {
     RUNTIME.block_on(stop_vpn_inner())
 }
 
+/// Like `stopVPN`, but doesn't wait for the tunnel to shut down gracefully. Meant for when the
+/// OS is about to kill the app. Routing and DNS are reset before returning, but the final flush of
+/// the mixnet client, e.g. the remaining cover traffic, may be left incomplete.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn forceStopVPN() -> Result<(), VpnError> {
+    RUNTIME.block_on(force_stop_vpn_inner())
+}
+
+async fn force_stop_vpn_inner() -> Result<(), VpnError> {
+    let mut guard = STATE_MACHINE_HANDLE.lock().await;
+
+    match guard.take() {
+        Some(state_machine_handle) => {
+            state_machine_handle.force_shutdown_and_wait().await;
+            Ok(())
+        }
+        None => Err(VpnError::InvalidStateError {
+            details: "State machine is not running.".to_owned(),
+        }),
+    }
+}
+
 async fn stop_vpn_inner() -> Result<(), VpnError> {
     let mut guard = STATE_MACHINE_HANDLE.lock().await;
 
@@ -101,6 +155,135 @@ async fn stop_vpn_inner() -> Result<(), VpnError> {
     }
 }
 
+/// Switch the running tunnel between mixnet and wireguard without stopping the VPN.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn setTunnelType(tunnel_type: TunnelType) -> Result<(), VpnError> {
+    RUNTIME.block_on(set_tunnel_type_inner(tunnel_type))
+}
+
+async fn set_tunnel_type_inner(tunnel_type: TunnelType) -> Result<(), VpnError> {
+    let guard = STATE_MACHINE_HANDLE.lock().await;
+
+    match guard.as_ref() {
+        Some(state_machine_handle) => {
+            state_machine_handle.send_command(TunnelCommand::SetTunnelType(tunnel_type));
+            Ok(())
+        }
+        None => Err(VpnError::InvalidStateError {
+            details: "State machine is not running.".to_owned(),
+        }),
+    }
+}
+
+/// Stop passing traffic through the connected mixnet tunnel while keeping the mixnet client
+/// connected, so that `resumeVPN` doesn't have to reconnect. The tunnel is reconnected from
+/// scratch if it stays paused for too long.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn pauseVPN() -> Result<(), VpnError> {
+    RUNTIME.block_on(send_tunnel_command(TunnelCommand::Pause))
+}
+
+/// Resume the tunnel paused by `pauseVPN`.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn resumeVPN() -> Result<(), VpnError> {
+    RUNTIME.block_on(send_tunnel_command(TunnelCommand::Resume))
+}
+
+/// Keep the tunnel up after an `IdleTimeoutWarning`, counting it as idle from now on.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn resetIdleTimer() -> Result<(), VpnError> {
+    RUNTIME.block_on(send_tunnel_command(TunnelCommand::ResetIdleTimer))
+}
+
+async fn send_tunnel_command(command: TunnelCommand) -> Result<(), VpnError> {
+    let guard = STATE_MACHINE_HANDLE.lock().await;
+
+    match guard.as_ref() {
+        Some(state_machine_handle) => {
+            state_machine_handle.send_command(command);
+            Ok(())
+        }
+        None => Err(VpnError::InvalidStateError {
+            details: "State machine is not running.".to_owned(),
+        }),
+    }
+}
+
+/// Public address of the connected tunnel as seen by an IP echo service queried through the tunnel
+/// interface, along with the country it's located in. This is the address the exit gateway makes
+/// requests from.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn getExitPublicIp() -> Result<ExitPublicIp, VpnError> {
+    RUNTIME.block_on(get_exit_public_ip_inner())
+}
+
+async fn get_exit_public_ip_inner() -> Result<ExitPublicIp, VpnError> {
+    let guard = STATE_MACHINE_HANDLE.lock().await;
+
+    match guard.as_ref() {
+        Some(state_machine_handle) => state_machine_handle.exit_public_ip().await,
+        None => Err(VpnError::InvalidStateError {
+            details: "State machine is not running.".to_owned(),
+        }),
+    }
+}
+
+/// Current tunnel state, so that a listener attached after the latest transition can still render
+/// the right state. Returns `Disconnected` when the VPN isn't started.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn getTunnelState() -> Result<TunnelState, VpnError> {
+    RUNTIME.block_on(get_tunnel_state_inner())
+}
+
+/// Current tunnel state along with the sequence number it is up to date with, for a sequenced
+/// listener added after the VPN started to tell which of its envelopes are already reflected in
+/// the state.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn currentState() -> Result<TunnelStateSnapshot, VpnError> {
+    RUNTIME.block_on(async {
+        // Read before the state, any event broadcast in between has a higher sequence number and
+        // is applied again by the listener.
+        let sequence = status_listeners::last_sequence();
+        let state = get_tunnel_state_inner().await?;
+        Ok(TunnelStateSnapshot { sequence, state })
+    })
+}
+
+async fn get_tunnel_state_inner() -> Result<TunnelState, VpnError> {
+    let guard = STATE_MACHINE_HANDLE.lock().await;
+
+    match guard.as_ref() {
+        Some(state_machine_handle) => state_machine_handle.current_state().await,
+        None => Ok(TunnelState::Disconnected {
+            reason: DisconnectReason::UserRequested,
+            session_summary: None,
+        }),
+    }
+}
+
+/// Expected bandwidth overhead of the given tunnel type with the settings used by `startVPN`, for
+/// informing users on metered connections before they connect.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn overheadEstimate(tunnel_type: TunnelType) -> OverheadEstimate {
+    TunnelSettings::default().overhead_estimate(tunnel_type)
+}
+
+/// Features available in this build of the library, so that the app can hide what the platform
+/// doesn't support.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn getCapabilities() -> Capabilities {
+    crate::capabilities()
+}
+
 #[allow(non_snake_case)]
 #[uniffi::export]
 pub fn configureLib(data_dir: String) -> Result<(), VpnError> {
@@ -120,13 +303,20 @@ fn start_account_controller(data_dir: String) -> Result<(), VpnError> {
     )))
 }
 
+fn log_level() -> String {
+    env::var("RUST_LOG").unwrap_or("info".to_string())
+}
+
 pub fn init_logger() {
-    let log_level = env::var("RUST_LOG").unwrap_or("info".to_string());
-    info!("Setting log level: {}", log_level);
+    init_logger_with_filter(log_level());
+}
+
+pub fn init_logger_with_filter(directives: String) {
+    info!("Setting log level: {}", directives);
     #[cfg(target_os = "ios")]
-    swift::init_logs(log_level);
+    swift::init_logs(directives);
     #[cfg(target_os = "android")]
-    android::init_logs(log_level);
+    android::init_logs(directives);
 }
 
 #[allow(non_snake_case)]
@@ -135,6 +325,22 @@ pub fn initLogger() {
     init_logger();
 }
 
+/// Initializes the platform logger with the given tracing env-filter directives, for example
+/// `info,nym_gateway_directory=debug`. Invalid directives fall back to info with a warning.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn initLoggerWithFilter(directives: String) {
+    init_logger_with_filter(directives);
+}
+
+/// Forward log events to the given listener, in addition to the platform log. Replaces any
+/// previously set listener.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn setLogListener(listener: Arc<dyn LogListener>) {
+    log_listener::set_log_listener(listener, &log_level());
+}
+
 /// Fetches the network environment details from the network name and initializes the environment,
 /// including exporting to the environment
 #[allow(non_snake_case)]
@@ -190,6 +396,17 @@ async fn fetch_environment(network_name: &str) -> Result<NetworkEnvironment, Vpn
         })
 }
 
+/// Lists the networks that can be passed to `initEnvironment`, falling back to the built-in ones
+/// when the network registry can't be reached.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn listAvailableNetworks() -> Vec<AvailableNetwork> {
+    nym_vpn_network_config::fetch_networks()
+        .into_iter()
+        .map(AvailableNetwork::from)
+        .collect()
+}
+
 #[allow(non_snake_case)]
 #[uniffi::export]
 pub fn fetchSystemMessages(network_name: &str) -> Result<Vec<SystemMessage>, VpnError> {
@@ -243,18 +460,66 @@ async fn fetch_account_links(
         })
 }
 
+/// Checks the mnemonic and returns the id of its account, without storing it nor accessing the
+/// network, e.g. to validate it as it's typed.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn validateMnemonic(mnemonic: String) -> Result<String, VpnError> {
+    account::validate_account_mnemonic(&mnemonic)
+}
+
 #[allow(non_snake_case)]
 #[uniffi::export]
 pub fn storeAccountMnemonic(mnemonic: String, path: String) -> Result<(), VpnError> {
     RUNTIME.block_on(account::store_account_mnemonic(&mnemonic, &path))
 }
 
+/// Same as `storeAccountMnemonic`, but can be aborted through the cancel handle and takes an
+/// optional timeout in seconds. Giving up never leaves a partially stored mnemonic behind.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn storeAccountMnemonicCancellable(
+    mnemonic: String,
+    path: String,
+    timeout_secs: Option<u64>,
+    cancel_handle: Arc<CancelHandle>,
+) -> Result<(), VpnError> {
+    RUNTIME.block_on(account::store_account_mnemonic_cancellable(
+        &mnemonic,
+        &path,
+        account_storage_timeout(timeout_secs),
+        cancel_handle.token(),
+    ))
+}
+
 #[allow(non_snake_case)]
 #[uniffi::export]
 pub fn isAccountMnemonicStored(path: String) -> Result<bool, VpnError> {
     RUNTIME.block_on(account::is_account_mnemonic_stored(&path))
 }
 
+/// Same as `isAccountMnemonicStored`, but can be aborted through the cancel handle and takes an
+/// optional timeout in seconds.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn isAccountMnemonicStoredCancellable(
+    path: String,
+    timeout_secs: Option<u64>,
+    cancel_handle: Arc<CancelHandle>,
+) -> Result<bool, VpnError> {
+    RUNTIME.block_on(account::is_account_mnemonic_stored_cancellable(
+        &path,
+        account_storage_timeout(timeout_secs),
+        cancel_handle.token(),
+    ))
+}
+
+fn account_storage_timeout(timeout_secs: Option<u64>) -> Duration {
+    timeout_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_ACCOUNT_STORAGE_TIMEOUT)
+}
+
 #[allow(non_snake_case)]
 #[uniffi::export]
 pub fn removeAccountMnemonic(path: String) -> Result<bool, VpnError> {
@@ -273,12 +538,26 @@ pub fn updateAccountState() -> Result<(), VpnError> {
     RUNTIME.block_on(account::update_account_state())
 }
 
+/// Request tickets right away instead of waiting for the next check, e.g. after buying more
+/// bandwidth. Blocks until they are imported and returns how many new tickets were acquired.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn refreshTickets() -> Result<u32, VpnError> {
+    RUNTIME.block_on(account::refresh_tickets())
+}
+
 #[allow(non_snake_case)]
 #[uniffi::export]
 pub fn getAccountState() -> Result<AccountStateSummary, VpnError> {
     RUNTIME.block_on(account::get_account_state())
 }
 
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn getStoredTicketbooks(path: String) -> Result<Vec<TicketbookInfo>, VpnError> {
+    RUNTIME.block_on(account::get_stored_ticketbooks(&path))
+}
+
 #[allow(non_snake_case)]
 #[uniffi::export]
 pub fn getGatewayCountries(
@@ -288,12 +567,42 @@ pub fn getGatewayCountries(
 ) -> Result<Vec<Location>, VpnError> {
     let (api_url, nym_vpn_api_url) = get_nym_urls()?;
 
-    RUNTIME.block_on(get_gateway_countries(
-        api_url,
-        nym_vpn_api_url,
-        gw_type,
-        user_agent,
-        min_gateway_performance,
+    RUNTIME.block_on(cancel::run_with_timeout(
+        get_gateway_countries(
+            api_url,
+            nym_vpn_api_url,
+            gw_type,
+            user_agent,
+            min_gateway_performance,
+        ),
+        DEFAULT_DIRECTORY_LOOKUP_TIMEOUT,
+        &CancellationToken::new(),
+    ))
+}
+
+/// Same as `getGatewayCountries`, but can be aborted through the cancel handle and takes an
+/// optional timeout in seconds.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn getGatewayCountriesCancellable(
+    gw_type: GatewayType,
+    user_agent: Option<UserAgent>,
+    min_gateway_performance: Option<GatewayMinPerformance>,
+    timeout_secs: Option<u64>,
+    cancel_handle: Arc<CancelHandle>,
+) -> Result<Vec<Location>, VpnError> {
+    let (api_url, nym_vpn_api_url) = get_nym_urls()?;
+
+    RUNTIME.block_on(cancel::run_with_timeout(
+        get_gateway_countries(
+            api_url,
+            nym_vpn_api_url,
+            gw_type,
+            user_agent,
+            min_gateway_performance,
+        ),
+        directory_lookup_timeout(timeout_secs),
+        cancel_handle.token(),
     ))
 }
 
@@ -312,11 +621,21 @@ async fn get_gateway_countries(
         api_url,
         nym_vpn_api_url: Some(nym_vpn_api_url),
         min_gateway_performance,
+        proxy: None,
+        directory_cache_path: None,
+        directory_cache_ttl: None,
+        nym_node_http_port: None,
     };
     GatewayClient::new(directory_config, user_agent)?
         .lookup_countries(gw_type.into())
         .await
-        .map(|countries| countries.into_iter().map(Location::from).collect())
+        .map(|countries| {
+            countries
+                .locations
+                .into_iter()
+                .map(Location::from)
+                .collect()
+        })
         .map_err(VpnError::from)
 }
 
@@ -325,13 +644,37 @@ async fn get_gateway_countries(
 pub fn getLowLatencyEntryCountry(user_agent: UserAgent) -> Result<Location, VpnError> {
     let (api_url, vpn_api_url) = get_nym_urls()?;
 
-    RUNTIME.block_on(get_low_latency_entry_country(
-        api_url,
-        vpn_api_url,
-        user_agent,
+    RUNTIME.block_on(cancel::run_with_timeout(
+        get_low_latency_entry_country(api_url, vpn_api_url, user_agent),
+        DEFAULT_DIRECTORY_LOOKUP_TIMEOUT,
+        &CancellationToken::new(),
     ))
 }
 
+/// Same as `getLowLatencyEntryCountry`, but can be aborted through the cancel handle and takes
+/// an optional timeout in seconds.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn getLowLatencyEntryCountryCancellable(
+    user_agent: UserAgent,
+    timeout_secs: Option<u64>,
+    cancel_handle: Arc<CancelHandle>,
+) -> Result<Location, VpnError> {
+    let (api_url, vpn_api_url) = get_nym_urls()?;
+
+    RUNTIME.block_on(cancel::run_with_timeout(
+        get_low_latency_entry_country(api_url, vpn_api_url, user_agent),
+        directory_lookup_timeout(timeout_secs),
+        cancel_handle.token(),
+    ))
+}
+
+fn directory_lookup_timeout(timeout_secs: Option<u64>) -> Duration {
+    timeout_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_DIRECTORY_LOOKUP_TIMEOUT)
+}
+
 async fn get_low_latency_entry_country(
     api_url: Url,
     vpn_api_url: Url,
@@ -341,6 +684,12 @@ async fn get_low_latency_entry_country(
         api_url,
         nym_vpn_api_url: Some(vpn_api_url),
         min_gateway_performance: None,
+        proxy: None,
+        // Without a data path there is nowhere to cache the directory, connecting with
+        // `VPNConfig::directory_cache_ttl` uses the cache
+        directory_cache_path: None,
+        directory_cache_ttl: None,
+        nym_node_http_port: None,
     };
     GatewayClient::new(config, user_agent.into())?
         .lookup_low_latency_entry_gateway()
@@ -365,6 +714,57 @@ pub struct VPNConfig {
     pub tun_provider: Arc<dyn OSTunProvider>,
     pub credential_data_path: Option<PathBuf>,
     pub tun_status_listener: Option<Arc<dyn TunnelStatusListener>>,
+    #[uniffi(default = None)]
+    pub mixnet_client_startup_timeout: Option<Duration>,
+    #[uniffi(default = None)]
+    pub shutdown_timeout: Option<Duration>,
+    #[uniffi(default = false)]
+    pub sticky_gateways: bool,
+    /// Pick two distinct gateways when the entry and exit are requested in the same country,
+    /// instead of failing with `SameEntryAndExitGatewayFromCountry`.
+    #[uniffi(default = true)]
+    pub allow_same_country: bool,
+    /// Connect to the entry gateway at this address instead of the advertised one, keeping the
+    /// negotiated port when the port is 0.
+    #[uniffi(default = None)]
+    pub wg_endpoint_override: Option<SocketAddr>,
+    /// Disconnect once no traffic went through the tunnel for this long, warning with
+    /// `IdleTimeoutWarning` beforehand.
+    #[uniffi(default = None)]
+    pub idle_timeout: Option<Duration>,
+    /// Cache the gateway directory in the credential data path and serve it for this long, so
+    /// that connecting doesn't wait for the directory on a flaky network. Disabled when unset.
+    #[uniffi(default = None)]
+    pub directory_cache_ttl: Option<Duration>,
+    /// Fail with `SystemClockSkew` when the system clock is off by more than this from the
+    /// directory. Defaults to a minute when unset.
+    #[uniffi(default = None)]
+    pub max_clock_skew: Option<Duration>,
+    /// Send the gateway directory and nym-vpn-api requests made before the tunnel is up through
+    /// this proxy.
+    #[uniffi(default = None)]
+    pub proxy: Option<VpnProxy>,
+}
+
+/// HTTP or SOCKS5 proxy, the url scheme is one of `http`, `https`, `socks5` or `socks5h`.
+#[derive(uniffi::Record)]
+pub struct VpnProxy {
+    pub url: Url,
+    /// Authenticate with the proxy when set, with an empty password unless one is given.
+    #[uniffi(default = None)]
+    pub username: Option<String>,
+    #[uniffi(default = None)]
+    pub password: Option<String>,
+}
+
+impl From<VpnProxy> for ProxyConfig {
+    fn from(proxy: VpnProxy) -> Self {
+        let config = ProxyConfig::new(proxy.url);
+        match proxy.username {
+            Some(username) => config.with_credentials(username, proxy.password.unwrap_or_default()),
+            None => config,
+        }
+    }
 }
 
 #[uniffi::export(with_foreign)]
@@ -372,6 +772,56 @@ pub trait TunnelStatusListener: Send + Sync {
     fn on_event(&self, event: TunnelEvent);
 }
 
+/// A tunnel event along with its position in the stream of events.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct TunnelEventEnvelope {
+    /// Increases by one with every event, starting at 1. Every event produced by the tunnel is
+    /// delivered, so there are no gaps: events go through an unbounded channel to a single task
+    /// that is drained before the VPN is stopped.
+    pub sequence: u64,
+    pub timestamp: OffsetDateTime,
+    pub event: TunnelEvent,
+}
+
+/// Like `TunnelStatusListener`, but the events come in an envelope so that they can be ordered
+/// against the state returned by `currentState`.
+#[uniffi::export(with_foreign)]
+pub trait SequencedTunnelStatusListener: Send + Sync {
+    fn on_event(&self, envelope: TunnelEventEnvelope);
+}
+
+/// The tunnel state along with the sequence number of the last event broadcast when it was read.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct TunnelStateSnapshot {
+    /// Envelopes up to this sequence number are already reflected in the state.
+    pub sequence: u64,
+    pub state: TunnelState,
+}
+
+/// Register a listener for the tunnel events, in addition to the one passed in `VPNConfig`.
+/// Listeners stay registered across VPN restarts. Returns the id to remove the listener with.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn addTunnelStatusListener(listener: Arc<dyn TunnelStatusListener>) -> u64 {
+    status_listeners::add(listener)
+}
+
+/// Register a listener for the tunnel events wrapped in envelopes with sequence numbers. Listeners
+/// stay registered across VPN restarts. Returns the id to remove the listener with.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn addSequencedTunnelStatusListener(listener: Arc<dyn SequencedTunnelStatusListener>) -> u64 {
+    status_listeners::add_sequenced(listener)
+}
+
+/// Remove a listener added with `addTunnelStatusListener` or `addSequencedTunnelStatusListener`.
+/// Unknown ids are ignored.
+#[allow(non_snake_case)]
+#[uniffi::export]
+pub fn removeTunnelStatusListener(id: u64) {
+    status_listeners::remove(id)
+}
+
 struct StateMachineHandle {
     state_machine_handle: JoinHandle<()>,
     event_broadcaster_handler: JoinHandle<()>,
@@ -386,6 +836,30 @@ impl StateMachineHandle {
         }
     }
 
+    async fn current_state(&self) -> Result<TunnelState, VpnError> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.send_command(TunnelCommand::QueryState(reply_tx));
+        reply_rx.await.map_err(|_| VpnError::InvalidStateError {
+            details: "State machine is not running.".to_owned(),
+        })
+    }
+
+    async fn exit_public_ip(&self) -> Result<ExitPublicIp, VpnError> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.send_command(TunnelCommand::GetExitPublicIp(reply_tx));
+        match reply_rx.await {
+            Ok(Some(result)) => result.map_err(|e| VpnError::NetworkConnectionError {
+                details: e.to_string(),
+            }),
+            Ok(None) => Err(VpnError::InvalidStateError {
+                details: "The tunnel is not connected.".to_owned(),
+            }),
+            Err(_) => Err(VpnError::InvalidStateError {
+                details: "State machine is not running.".to_owned(),
+            }),
+        }
+    }
+
     async fn shutdown_and_wait(self) {
         self.shutdown_token.cancel();
 
@@ -397,6 +871,17 @@ impl StateMachineHandle {
             tracing::error!("Failed to join on event broadcaster handle: {}", e);
         }
     }
+
+    async fn force_shutdown_and_wait(self) {
+        self.send_command(TunnelCommand::ForceDisconnect);
+
+        // Commands are handled in order, so the reply only comes once the tunnel is disconnected.
+        if let Err(e) = self.current_state().await {
+            tracing::error!("Failed to force disconnect: {}", e);
+        }
+
+        self.shutdown_and_wait().await;
+    }
 }
 
 fn get_api_url() -> Option<Url> {
@@ -423,6 +908,15 @@ fn get_nym_urls() -> Result<(Url, Url), VpnError> {
 }
 
 async fn start_state_machine(config: VPNConfig) -> Result<StateMachineHandle, VpnError> {
+    if let Some(shutdown_timeout) = config
+        .shutdown_timeout
+        .filter(|timeout| !SHUTDOWN_TIMEOUT_RANGE.contains(timeout))
+    {
+        return Err(VpnError::InvalidShutdownTimeout {
+            secs: shutdown_timeout.as_secs(),
+        });
+    }
+
     let tunnel_type = if config.enable_two_hop {
         TunnelType::Wireguard
     } else {
@@ -449,12 +943,39 @@ async fn start_state_machine(config: VPNConfig) -> Result<StateMachineHandle, Vp
         tunnel_type,
         enable_credentials_mode: false,
         mixnet_tunnel_options: MixnetTunnelOptions::default(),
-        wireguard_tunnel_options: WireguardTunnelOptions::default(),
+        wireguard_tunnel_options: WireguardTunnelOptions {
+            wg_endpoint_override: config.wg_endpoint_override,
+            ..Default::default()
+        },
         gateway_performance_options: GatewayPerformanceOptions::default(),
+        gateway_lookup_retry_options: GatewayLookupRetryOptions::default(),
+        connect_retry_options: ConnectRetryOptions::default(),
+        connect_deadline: Some(DEFAULT_CONNECT_DEADLINE),
         mixnet_client_config: None,
+        mixnet_client_startup_timeout: config.mixnet_client_startup_timeout,
+        shutdown_timeout: config.shutdown_timeout,
+        max_clock_skew: config.max_clock_skew.unwrap_or(DEFAULT_MAX_CLOCK_SKEW),
+        directory_cache_ttl: config.directory_cache_ttl,
+        proxy: config.proxy.map(ProxyConfig::from),
         entry_point: Box::new(entry_point),
         exit_point: Box::new(exit_point),
+        allow_same_country: config.allow_same_country,
+        excluded_gateways: Vec::new(),
+        sticky_gateways: config.sticky_gateways,
+        allow_lan: false,
+        routed_destinations: Vec::new(),
+        reconnect_on_network_change: true,
         dns: DnsOptions::default(),
+        detect_dns_leaks: false,
+        dns_leak_probe_interval: DEFAULT_DNS_LEAK_PROBE_INTERVAL,
+        ephemeral_identity: false,
+        max_session_duration: None,
+        session_expiry_warning: DEFAULT_SESSION_EXPIRY_WARNING,
+        idle_timeout: config.idle_timeout,
+        idle_timeout_warning: DEFAULT_IDLE_TIMEOUT_WARNING,
+        selection_seed: None,
+        region_affinity: None,
+        selection_strategy: SelectionStrategy::Random,
     };
 
     let (command_sender, command_receiver) = mpsc::unbounded_channel();
@@ -463,6 +984,7 @@ async fn start_state_machine(config: VPNConfig) -> Result<StateMachineHandle, Vp
     let state_listener = config.tun_status_listener;
     let event_broadcaster_handler = tokio::spawn(async move {
         while let Some(event) = event_receiver.recv().await {
+            status_listeners::broadcast(&event);
             if let Some(ref state_listener) = state_listener {
                 (*state_listener).on_event(event);
             }
@@ -475,6 +997,8 @@ async fn start_state_machine(config: VPNConfig) -> Result<StateMachineHandle, Vp
         event_sender,
         nym_config,
         tunnel_settings,
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+        Arc::new(crate::tunnel_state_machine::SystemTunProvider),
         #[cfg(any(target_os = "ios", target_os = "android"))]
         config.tun_provider,
         shutdown_token.child_token(),
@@ -495,8 +1019,9 @@ impl From<&TunnelState> for TunStatus {
         match value {
             TunnelState::Connecting { .. } => Self::EstablishingConnection,
             TunnelState::Connected { .. } => Self::Up,
+            TunnelState::Paused { .. } => Self::Down,
             TunnelState::Disconnecting { .. } => Self::Disconnecting,
-            TunnelState::Disconnected => Self::Down,
+            TunnelState::Disconnected { .. } => Self::Down,
             TunnelState::Error(_) => Self::Down,
         }
     }
@@ -507,6 +1032,14 @@ impl From<BandwidthEvent> for BandwidthStatus {
         match value {
             BandwidthEvent::NoBandwidth => Self::NoBandwidth,
             BandwidthEvent::RemainingBandwidth(bandwidth) => Self::RemainingBandwidth { bandwidth },
+            BandwidthEvent::ToppedUp { new_total } => Self::ToppedUp { new_total },
+            BandwidthEvent::LowBandwidth {
+                remaining,
+                threshold,
+            } => Self::LowBandwidth {
+                remaining,
+                threshold,
+            },
         }
     }
 }
@@ -521,6 +1054,110 @@ impl From<ConnectionEvent> for ConnectionStatus {
             ConnectionEvent::ExitGatewayDownIpv6 => Self::ExitGatewayDownIpv6,
             ConnectionEvent::ExitGatewayRoutingErrorIpv4 => Self::ExitGatewayRoutingErrorIpv4,
             ConnectionEvent::ExitGatewayRoutingErrorIpv6 => Self::ExitGatewayRoutingErrorIpv6,
+            ConnectionEvent::RetryingGatewayLookup {
+                attempt,
+                max_retries,
+            } => Self::RetryingGatewayLookup {
+                attempt,
+                max_retries,
+            },
+            ConnectionEvent::RetryingIprConnect {
+                attempt,
+                max_retries,
+            } => Self::RetryingIprConnect {
+                attempt,
+                max_retries,
+            },
+            ConnectionEvent::DnsLeakDetected { server } => Self::DnsLeakDetected { server },
+            ConnectionEvent::Quality { rtt_ms, loss_pct } => Self::Quality { rtt_ms, loss_pct },
+            ConnectionEvent::Latency(rtt) => Self::Latency { rtt },
+            ConnectionEvent::SessionExpiring { remaining_secs } => {
+                Self::SessionExpiring { remaining_secs }
+            }
+            ConnectionEvent::IdleTimeoutWarning { remaining_secs } => {
+                Self::IdleTimeoutWarning { remaining_secs }
+            }
+            ConnectionEvent::MtuConfigured { mtu } => Self::MtuConfigured { mtu },
+            ConnectionEvent::TunFdEstablished { fd } => Self::TunFdEstablished { fd },
+            ConnectionEvent::TunFdReleased { fd } => Self::TunFdReleased { fd },
+            ConnectionEvent::CoverTrafficDisabledForWireguard => {
+                Self::CoverTrafficDisabledForWireguard
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tunnel_state_machine::ErrorStateReason;
+
+    fn handle_with_state_machine(
+        mut handle_command: impl FnMut(TunnelCommand) + Send + 'static,
+    ) -> StateMachineHandle {
+        let (command_sender, mut command_receiver) = mpsc::unbounded_channel();
+        let state_machine_handle = tokio::spawn(async move {
+            while let Some(command) = command_receiver.recv().await {
+                handle_command(command);
+            }
+        });
+
+        StateMachineHandle {
+            state_machine_handle,
+            event_broadcaster_handler: tokio::spawn(async {}),
+            command_sender,
+            shutdown_token: CancellationToken::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn current_state_is_queried_from_the_state_machine() {
+        let handle = handle_with_state_machine(|command| {
+            if let TunnelCommand::QueryState(reply_tx) = command {
+                let _ = reply_tx.send(TunnelState::Error(ErrorStateReason::Dns));
+            }
+        });
+
+        assert_eq!(
+            handle.current_state().await,
+            Ok(TunnelState::Error(ErrorStateReason::Dns))
+        );
+    }
+
+    #[tokio::test]
+    async fn current_state_fails_when_the_query_is_dropped() {
+        let handle = handle_with_state_machine(drop);
+
+        assert!(matches!(
+            handle.current_state().await,
+            Err(VpnError::InvalidStateError { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn current_state_fails_when_the_state_machine_stopped() {
+        let (command_sender, _) = mpsc::unbounded_channel();
+        let handle = StateMachineHandle {
+            state_machine_handle: tokio::spawn(async {}),
+            event_broadcaster_handler: tokio::spawn(async {}),
+            command_sender,
+            shutdown_token: CancellationToken::new(),
+        };
+
+        assert!(matches!(
+            handle.current_state().await,
+            Err(VpnError::InvalidStateError { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn tunnel_state_is_disconnected_when_vpn_is_not_started() {
+        assert_eq!(
+            get_tunnel_state_inner().await,
+            Ok(TunnelState::Disconnected {
+                reason: DisconnectReason::UserRequested,
+                session_summary: None,
+            })
+        );
+    }
+}
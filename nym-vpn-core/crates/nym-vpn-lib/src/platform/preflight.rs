@@ -0,0 +1,139 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{net::SocketAddr, time::Duration};
+
+use nym_gateway_directory::{EntryPoint, GatewayClient, GatewayList, GatewayType, NodeIdentity};
+use tokio::net::TcpStream;
+
+use super::account;
+
+// Kept short so that an offline device fails the checks quickly instead of running into the
+// connect timeout.
+const PREFLIGHT_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Enum)]
+pub enum PreflightCheckResult {
+    Passed,
+    Failed { reason: String },
+    Skipped { reason: String },
+}
+
+/// Outcome of each of the checks run before connecting, so that the UI can show them as a
+/// checklist.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct PreflightReport {
+    /// The gateway directory returned the gateways for the tunnel type.
+    pub directory_reachable: PreflightCheckResult,
+
+    /// An account mnemonic is stored and the account is ready to connect.
+    pub account_ready: PreflightCheckResult,
+
+    /// The explicitly chosen wireguard entry gateway accepts connections. The wireguard port is
+    /// only handed out when registering with the gateway, so this checks the client port that
+    /// the connect goes through first.
+    pub gateway_reachable: PreflightCheckResult,
+}
+
+pub(super) async fn preflight_check(
+    entry_point: EntryPoint,
+    enable_two_hop: bool,
+) -> PreflightReport {
+    let gateway_type = if enable_two_hop {
+        GatewayType::Wg
+    } else {
+        GatewayType::MixnetEntry
+    };
+    let (gateways, account_ready) = tokio::join!(lookup_gateways(gateway_type), check_account());
+
+    let directory_reachable = match gateways {
+        Ok(_) => PreflightCheckResult::Passed,
+        Err(ref reason) => PreflightCheckResult::Failed {
+            reason: reason.clone(),
+        },
+    };
+
+    let gateway_reachable = match (enable_two_hop, entry_point, gateways) {
+        (false, _, _) => PreflightCheckResult::Skipped {
+            reason: "only checked for wireguard".to_owned(),
+        },
+        (true, EntryPoint::Gateway { identity }, Ok(gateways)) => {
+            check_gateway(&gateways, &identity).await
+        }
+        (true, EntryPoint::Gateway { .. }, Err(_)) => PreflightCheckResult::Skipped {
+            reason: "gateway directory is not reachable".to_owned(),
+        },
+        (true, _, _) => PreflightCheckResult::Skipped {
+            reason: "entry gateway is selected when connecting".to_owned(),
+        },
+    };
+
+    PreflightReport {
+        directory_reachable,
+        account_ready,
+        gateway_reachable,
+    }
+}
+
+async fn lookup_gateways(gateway_type: GatewayType) -> Result<GatewayList, String> {
+    let (api_url, nym_vpn_api_url) = super::get_nym_urls().map_err(|err| err.to_string())?;
+    let config = nym_gateway_directory::Config {
+        api_url,
+        nym_vpn_api_url: Some(nym_vpn_api_url),
+        min_gateway_performance: None,
+        proxy: None,
+        // Always ask the directory, a cached lookup would hide that it isn't reachable
+        directory_cache_path: None,
+        directory_cache_ttl: None,
+        nym_node_http_port: None,
+    };
+    let gateway_client = GatewayClient::new(config, crate::util::construct_user_agent())
+        .map_err(|err| err.to_string())?;
+
+    tokio::time::timeout(
+        PREFLIGHT_CHECK_TIMEOUT,
+        gateway_client.lookup_gateways(gateway_type),
+    )
+    .await
+    .map_err(|_| "timed out looking up gateways".to_owned())?
+    .map_err(|err| err.to_string())
+}
+
+async fn check_account() -> PreflightCheckResult {
+    match account::assert_account_ready_to_connect(PREFLIGHT_CHECK_TIMEOUT).await {
+        Ok(()) => PreflightCheckResult::Passed,
+        Err(err) => PreflightCheckResult::Failed {
+            reason: err.to_string(),
+        },
+    }
+}
+
+async fn check_gateway(gateways: &GatewayList, identity: &NodeIdentity) -> PreflightCheckResult {
+    let Some(gateway) = gateways.gateway_with_identity(identity) else {
+        return PreflightCheckResult::Failed {
+            reason: format!("gateway {identity} is not in the directory"),
+        };
+    };
+    let Some(port) = gateway.clients_wss_port.or(gateway.clients_ws_port) else {
+        return PreflightCheckResult::Failed {
+            reason: format!("gateway {identity} has no client port"),
+        };
+    };
+
+    let connect = async {
+        let ip = gateway
+            .lookup_ip()
+            .await
+            .ok_or_else(|| format!("failed to resolve the host of gateway {identity}"))?;
+        TcpStream::connect(SocketAddr::new(ip, port))
+            .await
+            .map_err(|err| format!("failed to connect to gateway {identity}: {err}"))
+    };
+    match tokio::time::timeout(PREFLIGHT_CHECK_TIMEOUT, connect).await {
+        Ok(Ok(_)) => PreflightCheckResult::Passed,
+        Ok(Err(reason)) => PreflightCheckResult::Failed { reason },
+        Err(_) => PreflightCheckResult::Failed {
+            reason: format!("timed out connecting to gateway {identity}"),
+        },
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Registry of the tunnel status listeners added by the app, so that several components (main
+//! screen, widget, notification service) can observe the tunnel at the same time.
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, RwLock,
+};
+
+use time::OffsetDateTime;
+
+use super::{SequencedTunnelStatusListener, TunnelEventEnvelope, TunnelStatusListener};
+use crate::tunnel_state_machine::TunnelEvent;
+
+static NEXT_LISTENER_ID: AtomicU64 = AtomicU64::new(1);
+static LISTENERS: RwLock<Vec<(u64, Listener)>> = RwLock::new(Vec::new());
+
+/// Sequence number of the last broadcast event, 0 before the first one.
+static LAST_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone)]
+enum Listener {
+    Plain(Arc<dyn TunnelStatusListener>),
+    Sequenced(Arc<dyn SequencedTunnelStatusListener>),
+}
+
+pub(super) fn add(listener: Arc<dyn TunnelStatusListener>) -> u64 {
+    add_listener(Listener::Plain(listener))
+}
+
+pub(super) fn add_sequenced(listener: Arc<dyn SequencedTunnelStatusListener>) -> u64 {
+    add_listener(Listener::Sequenced(listener))
+}
+
+fn add_listener(listener: Listener) -> u64 {
+    let id = NEXT_LISTENER_ID.fetch_add(1, Ordering::Relaxed);
+    LISTENERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((id, listener));
+    id
+}
+
+pub(super) fn remove(id: u64) {
+    LISTENERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|(listener_id, _)| *listener_id != id);
+}
+
+pub(super) fn last_sequence() -> u64 {
+    LAST_SEQUENCE.load(Ordering::Relaxed)
+}
+
+// Events are broadcast from a single task, so the sequence numbers are handed out in order. That
+// task receives every event the state machine sends over an unbounded channel and is drained on
+// shutdown, so numbering at delivery can't leave gaps that numbering at production would show.
+fn next_envelope(event: &TunnelEvent) -> TunnelEventEnvelope {
+    TunnelEventEnvelope {
+        sequence: LAST_SEQUENCE.fetch_add(1, Ordering::Relaxed) + 1,
+        timestamp: OffsetDateTime::now_utc(),
+        event: event.clone(),
+    }
+}
+
+/// Hand the event to every registered listener, wrapped in an envelope with the next sequence
+/// number for the sequenced listeners.
+///
+/// The listeners are called on a snapshot taken outside of the lock, so that a listener can add or
+/// remove listeners from within its callback.
+pub(super) fn broadcast(event: &TunnelEvent) {
+    let envelope = next_envelope(event);
+    let listeners: Vec<_> = LISTENERS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(_, listener)| listener.clone())
+        .collect();
+
+    for listener in listeners {
+        match listener {
+            Listener::Plain(listener) => listener.on_event(event.clone()),
+            Listener::Sequenced(listener) => listener.on_event(envelope.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::tunnel_state_machine::{DisconnectReason, TunnelState};
+
+    #[derive(Default)]
+    struct CountingListener {
+        events: AtomicUsize,
+    }
+
+    impl TunnelStatusListener for CountingListener {
+        fn on_event(&self, _event: TunnelEvent) {
+            self.events.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn events_are_fanned_out_to_registered_listeners() {
+        let first = Arc::new(CountingListener::default());
+        let second = Arc::new(CountingListener::default());
+        let first_id = add(first.clone());
+        let second_id = add(second.clone());
+
+        let event = TunnelEvent::NewState(TunnelState::Disconnected {
+            reason: DisconnectReason::UserRequested,
+            session_summary: None,
+        });
+        broadcast(&event);
+
+        remove(first_id);
+        remove(u64::MAX);
+        broadcast(&event);
+        remove(second_id);
+
+        assert_eq!(first.events.load(Ordering::Relaxed), 1);
+        assert_eq!(second.events.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn envelopes_have_increasing_sequence_numbers() {
+        let event = TunnelEvent::NewState(TunnelState::Disconnected {
+            reason: DisconnectReason::UserRequested,
+            session_summary: None,
+        });
+        let first = next_envelope(&event);
+        let second = next_envelope(&event);
+
+        assert!(first.sequence > 0);
+        assert!(second.sequence > first.sequence);
+        assert!(last_sequence() >= second.sequence);
+        assert!(second.timestamp >= first.timestamp);
+    }
+}
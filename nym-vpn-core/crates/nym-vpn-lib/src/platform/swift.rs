@@ -1,26 +1,21 @@
 // Copyright 2023 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::str::FromStr;
-
-use log::LevelFilter;
 use oslog::OsLogger;
 
-pub fn init_logs(level: String) {
-    let result = OsLogger::new("net.nymtech.vpn.agent")
-        .level_filter(LevelFilter::from_str(&level).unwrap_or(LevelFilter::Info))
-        .category_level_filter("hyper", LevelFilter::Warn)
-        .category_level_filter("tokio_reactor", LevelFilter::Warn)
-        .category_level_filter("reqwest", LevelFilter::Warn)
-        .category_level_filter("mio", LevelFilter::Warn)
-        .category_level_filter("want", LevelFilter::Warn)
-        .category_level_filter("tungstenite", LevelFilter::Warn)
-        .category_level_filter("tokio_tungstenite", LevelFilter::Warn)
-        .category_level_filter("handlebars", LevelFilter::Warn)
-        .category_level_filter("sled", LevelFilter::Warn)
-        .init();
+use super::log_filter::LogFilter;
+
+/// Initializes os_log with the given tracing env-filter directives, falling back to info if they
+/// can't be parsed.
+pub fn init_logs(directives: String) {
+    let (filter, parse_error) = LogFilter::parse_or_default(&directives);
 
-    match result {
+    let logger = filter.targets.iter().fold(
+        OsLogger::new("net.nymtech.vpn.agent").level_filter(filter.default_level),
+        |logger, (target, level)| logger.category_level_filter(target, *level),
+    );
+
+    match logger.init() {
         Ok(_) => {
             tracing::debug!("Logger initialized");
         }
@@ -28,4 +23,8 @@ pub fn init_logs(level: String) {
             tracing::error!("Failed to initialize os_log: {}", e);
         }
     };
+
+    if let Some(err) = parse_error {
+        tracing::warn!("Invalid log filter \"{directives}\", falling back to info: {err}");
+    }
 }
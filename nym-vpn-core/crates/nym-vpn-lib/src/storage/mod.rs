@@ -9,6 +9,7 @@ use nym_vpn_store::{
         DeviceKeys, KeyStore,
     },
     mnemonic::{on_disk::OnDiskMnemonicStorageError, Mnemonic, MnemonicStorage},
+    permissions::StoragePermissions,
 };
 
 mod helpers;
@@ -34,6 +35,14 @@ impl VpnClientOnDiskStorage {
             mnemonic_storage,
         }
     }
+
+    /// Sets the permissions of the mnemonic and key files, private by default.
+    pub fn with_permissions(self, permissions: StoragePermissions) -> Self {
+        VpnClientOnDiskStorage {
+            key_store: self.key_store.with_permissions(permissions),
+            mnemonic_storage: self.mnemonic_storage.with_permissions(permissions),
+        }
+    }
 }
 
 impl nym_vpn_store::VpnStorage for VpnClientOnDiskStorage {}
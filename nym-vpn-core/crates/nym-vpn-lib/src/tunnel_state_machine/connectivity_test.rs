@@ -0,0 +1,257 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use hickory_resolver::{
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    TokioAsyncResolver,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::Instant,
+};
+
+/// Host resolved and fetched through the tunnel.
+const CONNECTIVITY_TEST_HOST: &str = "nymvpn.com";
+
+const HTTP_PORT: u16 = 80;
+
+/// Time allowed for each step, so that a dead tunnel doesn't hold up the test.
+const STEP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of connections opened to measure the round trip time. The shortest one is reported.
+const RTT_SAMPLES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectivityTestStepKind {
+    /// Resolve the canary host through the DNS servers of the tunnel.
+    Dns,
+
+    /// Fetch a small page from the canary host.
+    Http,
+
+    /// Measure the round trip time to the canary host.
+    Rtt,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectivityTestStep {
+    pub kind: ConnectivityTestStepKind,
+
+    /// How long the step took, up to the timeout.
+    pub duration: Duration,
+
+    /// Why the step failed, `None` if it passed.
+    pub error: Option<String>,
+}
+
+/// Outcome of a connectivity test run through the connected tunnel.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectivityTestReport {
+    /// The steps in the order they ran. Each step depends on the previous one, so the test stops
+    /// at the first one that fails.
+    pub steps: Vec<ConnectivityTestStep>,
+
+    /// Round trip time to the canary host, set once the last step passed.
+    pub rtt: Option<Duration>,
+}
+
+impl ConnectivityTestReport {
+    pub fn passed(&self) -> bool {
+        self.failed_step().is_none() && self.rtt.is_some()
+    }
+
+    /// Returns the step that failed, if any.
+    pub fn failed_step(&self) -> Option<ConnectivityTestStepKind> {
+        self.steps
+            .iter()
+            .find(|step| step.error.is_some())
+            .map(|step| step.kind)
+    }
+
+    // Records the outcome of a step, returning its output if it passed.
+    fn record<T>(
+        &mut self,
+        kind: ConnectivityTestStepKind,
+        started_at: Instant,
+        result: Result<T, String>,
+    ) -> Option<T> {
+        let (output, error) = match result {
+            Ok(output) => (Some(output), None),
+            Err(error) => {
+                tracing::warn!("Connectivity test step {:?} failed: {}", kind, error);
+                (None, Some(error))
+            }
+        };
+        self.steps.push(ConnectivityTestStep {
+            kind,
+            duration: started_at.elapsed(),
+            error,
+        });
+        output
+    }
+}
+
+/// Resolves the canary host through the DNS servers of the tunnel, fetches a page from it and
+/// measures the round trip time. The traffic goes through the tunnel since the routes of the
+/// connected tunnel are in place.
+pub async fn run(dns_servers: &[IpAddr]) -> ConnectivityTestReport {
+    tracing::info!("Running connectivity test");
+    let mut report = ConnectivityTestReport::default();
+
+    let started_at = Instant::now();
+    let result = time_boxed(resolve(dns_servers, CONNECTIVITY_TEST_HOST)).await;
+    let Some(addr) = report.record(ConnectivityTestStepKind::Dns, started_at, result) else {
+        return report;
+    };
+    let addr = SocketAddr::new(addr, HTTP_PORT);
+
+    let started_at = Instant::now();
+    let result = time_boxed(fetch(addr)).await;
+    if report
+        .record(ConnectivityTestStepKind::Http, started_at, result)
+        .is_none()
+    {
+        return report;
+    }
+
+    let started_at = Instant::now();
+    let result = time_boxed(measure_rtt(addr)).await;
+    report.rtt = report.record(ConnectivityTestStepKind::Rtt, started_at, result);
+    if let Some(rtt) = report.rtt {
+        tracing::info!("Connectivity test passed, rtt: {:?}", rtt);
+    }
+    report
+}
+
+pub(super) async fn time_boxed<T>(
+    step: impl Future<Output = Result<T, String>>,
+) -> Result<T, String> {
+    tokio::time::timeout(STEP_TIMEOUT, step)
+        .await
+        .map_err(|_| format!("timed out after {}s", STEP_TIMEOUT.as_secs()))?
+}
+
+/// Resolves the host through the given DNS servers, returning its first address.
+pub(super) async fn resolve(dns_servers: &[IpAddr], host: &str) -> Result<IpAddr, String> {
+    if dns_servers.is_empty() {
+        return Err("no DNS servers configured for the tunnel".to_owned());
+    }
+
+    let config = ResolverConfig::from_parts(
+        None,
+        vec![],
+        NameServerConfigGroup::from_ips_clear(dns_servers, 53, true),
+    );
+    let mut opts = ResolverOpts::default();
+    opts.timeout = STEP_TIMEOUT;
+    opts.attempts = 1;
+    opts.cache_size = 0;
+
+    TokioAsyncResolver::tokio(config, opts)
+        .lookup_ip(format!("{host}."))
+        .await
+        .map_err(|e| format!("failed to resolve {host}: {e}"))?
+        .iter()
+        .next()
+        .ok_or_else(|| format!("no address found for {host}"))
+}
+
+// Any response proves that the host can be reached, redirects included.
+async fn fetch(addr: SocketAddr) -> Result<u16, String> {
+    let mut stream = TcpStream::connect(addr)
+        .await
+        .map_err(|e| format!("failed to connect to {addr}: {e}"))?;
+
+    let request =
+        format!("HEAD / HTTP/1.1\r\nHost: {CONNECTIVITY_TEST_HOST}\r\nConnection: close\r\n\r\n");
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| format!("failed to send the request: {e}"))?;
+
+    let mut response = [0; 64];
+    let len = stream
+        .read(&mut response)
+        .await
+        .map_err(|e| format!("failed to read the response: {e}"))?;
+
+    parse_status_code(&response[..len]).ok_or_else(|| "invalid HTTP response".to_owned())
+}
+
+pub(super) fn parse_status_code(response: &[u8]) -> Option<u16> {
+    let status_line = std::str::from_utf8(response).ok()?.lines().next()?;
+    let mut parts = status_line.split_whitespace();
+    if !parts.next()?.starts_with("HTTP/") {
+        return None;
+    }
+    parts.next()?.parse().ok()
+}
+
+async fn measure_rtt(addr: SocketAddr) -> Result<Duration, String> {
+    let mut rtt = Duration::MAX;
+    for _ in 0..RTT_SAMPLES {
+        let started_at = Instant::now();
+        TcpStream::connect(addr)
+            .await
+            .map_err(|e| format!("failed to connect to {addr}: {e}"))?;
+        rtt = rtt.min(started_at.elapsed());
+    }
+    Ok(rtt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(kind: ConnectivityTestStepKind, error: Option<&str>) -> ConnectivityTestStep {
+        ConnectivityTestStep {
+            kind,
+            duration: Duration::from_millis(10),
+            error: error.map(ToOwned::to_owned),
+        }
+    }
+
+    #[test]
+    fn reports_the_failed_step() {
+        let report = ConnectivityTestReport {
+            steps: vec![
+                step(ConnectivityTestStepKind::Dns, None),
+                step(ConnectivityTestStepKind::Http, Some("timed out after 5s")),
+            ],
+            rtt: None,
+        };
+        assert!(!report.passed());
+        assert_eq!(report.failed_step(), Some(ConnectivityTestStepKind::Http));
+    }
+
+    #[test]
+    fn passes_once_the_rtt_is_measured() {
+        let report = ConnectivityTestReport {
+            steps: vec![
+                step(ConnectivityTestStepKind::Dns, None),
+                step(ConnectivityTestStepKind::Http, None),
+                step(ConnectivityTestStepKind::Rtt, None),
+            ],
+            rtt: Some(Duration::from_millis(42)),
+        };
+        assert!(report.passed());
+        assert_eq!(report.failed_step(), None);
+    }
+
+    #[test]
+    fn parses_the_status_code() {
+        assert_eq!(
+            parse_status_code(b"HTTP/1.1 301 Moved Permanently\r\nLocation: https://"),
+            Some(301)
+        );
+        assert_eq!(parse_status_code(b"SSH-2.0-OpenSSH\r\n"), None);
+        assert_eq!(parse_status_code(b""), None);
+    }
+}
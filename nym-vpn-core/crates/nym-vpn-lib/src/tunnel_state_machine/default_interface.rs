@@ -1,6 +1,7 @@
 // Copyright 2023 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
+#[cfg(any(target_os = "linux", target_os = "windows"))]
 use std::net::IpAddr;
 
 use super::{Error, Result};
@@ -21,6 +22,21 @@ impl DefaultInterface {
         &self.inner.name
     }
 
+    #[cfg(target_os = "macos")]
+    pub fn index(&self) -> u32 {
+        self.inner.index
+    }
+
+    /// The first address of the interface in the same family as `destination`.
+    #[cfg(target_os = "windows")]
+    pub fn address_for(&self, destination: IpAddr) -> Option<IpAddr> {
+        match destination {
+            IpAddr::V4(_) => self.inner.ipv4.first().map(|net| IpAddr::V4(net.addr())),
+            IpAddr::V6(_) => self.inner.ipv6.first().map(|net| IpAddr::V6(net.addr())),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
     pub fn gateway_ip(&self) -> Option<IpAddr> {
         self.inner
             .gateway
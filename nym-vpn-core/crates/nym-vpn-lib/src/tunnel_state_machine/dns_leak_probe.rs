@@ -0,0 +1,381 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    collections::HashSet,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use hickory_resolver::system_conf;
+use tokio::{net::UdpSocket, sync::mpsc, time::MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+
+use super::{default_interface::DefaultInterface, ConnectionEvent, MixnetEvent};
+
+/// Name queried from the system name servers to find out which of them answer.
+const DNS_LEAK_CANARY: &str = "nymvpn.com.";
+
+const DNS_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const DNS_PORT: u16 = 53;
+
+/// Where systemd-resolved lists the servers its local stub resolver forwards to.
+#[cfg(target_os = "linux")]
+const SYSTEMD_RESOLVED_UPSTREAM_CONF: &str = "/run/systemd/resolve/resolv.conf";
+
+/// Periodically checks the name servers used by the system resolver, and emits
+/// [`ConnectionEvent::DnsLeakDetected`] when one that isn't configured for the tunnel answers a
+/// query sent outside the tunnel.
+///
+/// The queries go out over the physical interface, which is the path the system resolver takes
+/// when it bypasses the tunnel. A local stub resolver, e.g. systemd-resolved on 127.0.0.53, is
+/// followed to the servers it forwards to.
+pub struct DnsLeakProbe {
+    dns_servers: Vec<IpAddr>,
+    interval: Duration,
+    mixnet_event_sender: mpsc::UnboundedSender<MixnetEvent>,
+    reported_servers: HashSet<IpAddr>,
+}
+
+impl DnsLeakProbe {
+    /// Start probing every `interval`, which must not be zero.
+    pub fn spawn(
+        dns_servers: Vec<IpAddr>,
+        interval: Duration,
+        mixnet_event_sender: mpsc::UnboundedSender<MixnetEvent>,
+        cancel_token: CancellationToken,
+    ) {
+        let probe = Self {
+            dns_servers,
+            interval,
+            mixnet_event_sender,
+            reported_servers: HashSet::new(),
+        };
+        tokio::spawn(probe.run(cancel_token));
+    }
+
+    async fn run(mut self, cancel_token: CancellationToken) {
+        tracing::debug!("Starting DNS leak probe");
+        let mut interval = tokio::time::interval(self.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        cancel_token
+            .run_until_cancelled(async {
+                loop {
+                    interval.tick().await;
+                    self.probe().await;
+                }
+            })
+            .await;
+        tracing::debug!("DNS leak probe stopped");
+    }
+
+    async fn probe(&mut self) {
+        let (system_config, _) = match system_conf::read_system_conf() {
+            Ok(system_conf) => system_conf,
+            Err(e) => {
+                tracing::warn!("Failed to read the system DNS config: {}", e);
+                return;
+            }
+        };
+        let name_servers = system_config
+            .name_servers()
+            .iter()
+            .map(|name_server| name_server.socket_addr.ip())
+            .collect::<Vec<_>>();
+        let servers = servers_to_probe(
+            &name_servers,
+            stub_upstream_servers,
+            &self.dns_servers,
+            &self.reported_servers,
+        );
+        if servers.is_empty() {
+            return;
+        }
+
+        // The tunnel routes don't change the default interface, so this is the physical one.
+        let physical_interface = match DefaultInterface::current() {
+            Ok(physical_interface) => physical_interface,
+            Err(e) => {
+                tracing::warn!("Failed to find the physical interface: {}", e);
+                return;
+            }
+        };
+        for server in servers {
+            if answers_outside_tunnel(server, &physical_interface).await {
+                tracing::warn!(
+                    "DNS leak detected, {} answered a query outside the tunnel",
+                    server
+                );
+                self.reported_servers.insert(server);
+                self.send_event(ConnectionEvent::DnsLeakDetected { server });
+            }
+        }
+    }
+
+    fn send_event(&self, event: ConnectionEvent) {
+        if let Err(e) = self
+            .mixnet_event_sender
+            .send(MixnetEvent::Connection(event))
+        {
+            tracing::error!("Failed to send event: {}", e);
+        }
+    }
+}
+
+// The system name servers that aren't configured for the tunnel and haven't been reported yet.
+// Local stub resolvers are replaced with the servers they forward to, when those can be found.
+fn servers_to_probe(
+    name_servers: &[IpAddr],
+    stub_upstream_servers: impl FnOnce() -> Option<Vec<IpAddr>>,
+    dns_servers: &[IpAddr],
+    reported_servers: &HashSet<IpAddr>,
+) -> Vec<IpAddr> {
+    let mut servers = name_servers.to_vec();
+    if servers.iter().any(IpAddr::is_loopback) {
+        match stub_upstream_servers() {
+            Some(upstream_servers) => servers.extend(upstream_servers),
+            None => tracing::debug!("Can't find the servers behind the local stub resolver"),
+        }
+    }
+
+    let mut seen = HashSet::new();
+    servers.retain(|server| {
+        !server.is_loopback()
+            && !dns_servers.contains(server)
+            && !reported_servers.contains(server)
+            && seen.insert(*server)
+    });
+    servers
+}
+
+#[cfg(target_os = "linux")]
+fn stub_upstream_servers() -> Option<Vec<IpAddr>> {
+    let contents = std::fs::read_to_string(SYSTEMD_RESOLVED_UPSTREAM_CONF).ok()?;
+    Some(parse_name_servers(&contents))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn stub_upstream_servers() -> Option<Vec<IpAddr>> {
+    None
+}
+
+// Parse the `nameserver` lines of a resolv.conf, dropping the scope of link-local addresses.
+#[cfg(any(target_os = "linux", test))]
+fn parse_name_servers(resolv_conf: &str) -> Vec<IpAddr> {
+    resolv_conf
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            if fields.next()? != "nameserver" {
+                return None;
+            }
+            fields.next()?.split('%').next()?.parse().ok()
+        })
+        .collect()
+}
+
+async fn answers_outside_tunnel(server: IpAddr, physical_interface: &DefaultInterface) -> bool {
+    let query = query_outside_tunnel(server, physical_interface);
+    match tokio::time::timeout(DNS_QUERY_TIMEOUT, query).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            tracing::debug!("Failed to query {} outside the tunnel: {}", server, e);
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+// Any answer counts, even an error, since the query reached the server either way.
+async fn query_outside_tunnel(
+    server: IpAddr,
+    physical_interface: &DefaultInterface,
+) -> io::Result<()> {
+    let socket = bind_outside_tunnel(server, physical_interface).await?;
+    socket.connect(SocketAddr::new(server, DNS_PORT)).await?;
+
+    let id = rand::random();
+    socket.send(&build_query(id, DNS_LEAK_CANARY)).await?;
+    let mut response = [0; 512];
+    loop {
+        let len = socket.recv(&mut response).await?;
+        if is_response_to(&response[..len], id) {
+            return Ok(());
+        }
+    }
+}
+
+async fn bind_outside_tunnel(
+    server: IpAddr,
+    physical_interface: &DefaultInterface,
+) -> io::Result<UdpSocket> {
+    #[cfg(not(target_os = "windows"))]
+    let local_addr = match server {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    // Windows sends from the interface that owns the source address.
+    #[cfg(target_os = "windows")]
+    let local_addr = physical_interface.address_for(server).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            format!(
+                "{} has no address to reach {}",
+                physical_interface.interface_name(),
+                server
+            ),
+        )
+    })?;
+    let socket = UdpSocket::bind(SocketAddr::new(local_addr, 0)).await?;
+
+    #[cfg(target_os = "linux")]
+    socket.bind_device(Some(physical_interface.interface_name().as_bytes()))?;
+    #[cfg(target_os = "macos")]
+    bind_to_interface_index(&socket, server, physical_interface.index())?;
+
+    Ok(socket)
+}
+
+#[cfg(target_os = "macos")]
+fn bind_to_interface_index(socket: &UdpSocket, server: IpAddr, index: u32) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let (level, name) = match server {
+        IpAddr::V4(_) => (libc::IPPROTO_IP, libc::IP_BOUND_IF),
+        IpAddr::V6(_) => (libc::IPPROTO_IPV6, libc::IPV6_BOUND_IF),
+    };
+    let index = index as libc::c_int;
+    // SAFETY: the socket stays open for the duration of the call, and the value points to a
+    // c_int of the given size.
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &index as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+// A recursive query for the A records of `name`.
+fn build_query(id: u16, name: &str) -> Vec<u8> {
+    let mut query = Vec::with_capacity(32);
+    query.extend_from_slice(&id.to_be_bytes());
+    // Standard query with recursion desired, a single question and no other records
+    query.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    for label in name.trim_end_matches('.').split('.') {
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    // Type A, class IN
+    query.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+    query
+}
+
+fn is_response_to(message: &[u8], id: u16) -> bool {
+    message.len() >= 12 && message[..2] == id.to_be_bytes() && message[2] & 0x80 != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STUB: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 53));
+    const TUNNEL_DNS: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 64, 0, 1));
+    const ISP_DNS: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53));
+    const ROUTER_DNS: IpAddr = IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1));
+
+    fn no_stub() -> Option<Vec<IpAddr>> {
+        panic!("the stub resolver shouldn't be looked up without a loopback name server")
+    }
+
+    #[test]
+    fn follow_stub_resolver_to_upstream_servers() {
+        let servers = servers_to_probe(
+            &[STUB],
+            || Some(vec![ISP_DNS, ROUTER_DNS]),
+            &[TUNNEL_DNS],
+            &HashSet::new(),
+        );
+        assert_eq!(servers, vec![ISP_DNS, ROUTER_DNS]);
+    }
+
+    #[test]
+    fn unknown_stub_resolver_is_not_probed() {
+        let servers = servers_to_probe(&[STUB], || None, &[TUNNEL_DNS], &HashSet::new());
+        assert!(servers.is_empty());
+    }
+
+    #[test]
+    fn skip_tunnel_and_reported_servers() {
+        let servers = servers_to_probe(
+            &[TUNNEL_DNS, ISP_DNS, ROUTER_DNS, ISP_DNS],
+            no_stub,
+            &[TUNNEL_DNS],
+            &HashSet::from([ROUTER_DNS]),
+        );
+        assert_eq!(servers, vec![ISP_DNS]);
+    }
+
+    #[test]
+    fn stub_upstream_pointing_at_tunnel_is_not_probed() {
+        let servers = servers_to_probe(
+            &[STUB],
+            || Some(vec![TUNNEL_DNS]),
+            &[TUNNEL_DNS],
+            &HashSet::new(),
+        );
+        assert!(servers.is_empty());
+    }
+
+    #[test]
+    fn parse_systemd_resolved_upstream_conf() {
+        let resolv_conf = "\
+# This is /run/systemd/resolve/resolv.conf managed by man:systemd-resolved(8).
+nameserver 192.0.2.53
+nameserver fe80::1%wlan0
+nameserver not-an-address
+search lan
+options edns0
+";
+        assert_eq!(parse_name_servers(resolv_conf), vec![ISP_DNS, ROUTER_DNS]);
+    }
+
+    #[test]
+    fn build_recursive_a_query() {
+        let query = build_query(0x1234, "nymvpn.com.");
+        assert_eq!(
+            query,
+            [
+                &[0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00][..],
+                b"\x06nymvpn\x03com\x00",
+                &[0x00, 0x01, 0x00, 0x01],
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn only_responses_with_matching_id_count() {
+        let query = build_query(0x1234, DNS_LEAK_CANARY);
+        let mut response = query.clone();
+        // Response flag with the REFUSED code, which still shows the server is reachable
+        response[2] |= 0x80;
+        response[3] |= 0x05;
+
+        assert!(is_response_to(&response, 0x1234));
+        assert!(!is_response_to(&response, 0x4321));
+        assert!(!is_response_to(&query, 0x1234));
+        assert!(!is_response_to(&response[..11], 0x1234));
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpSocket, TcpStream},
+};
+use tokio_rustls::{
+    rustls::{pki_types::ServerName, ClientConfig, RootCertStore},
+    TlsConnector,
+};
+
+use super::{connectivity_test, ConnectionData, TunnelConnectionData};
+
+/// IP echo service run by Nym, answering with the address the request came from and where it's
+/// located. It's queried over TLS so that the exit gateway, or anyone past it, can't forge the
+/// answer.
+const ECHO_HOST: &str = "nymvpn.com";
+
+const ECHO_PATH: &str = "/api/public/v1/ip";
+
+const HTTPS_PORT: u16 = 443;
+
+/// The response is a few hundred bytes, anything past this isn't from the echo service.
+const MAX_RESPONSE_LEN: u64 = 16 * 1024;
+
+/// Public address of the connected tunnel as seen from the internet, i.e. the one of the exit
+/// gateway.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct ExitPublicIp {
+    pub ip: IpAddr,
+
+    /// ISO 3166-1 alpha-2 code of the country the address is located in, if known.
+    pub country: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ExitIpLookupError {
+    #[error("the IP echo service is unreachable through the tunnel: {0}")]
+    EchoServiceUnreachable(String),
+
+    #[error("the IP echo service answered with HTTP status {0}")]
+    EchoServiceStatus(u16),
+
+    #[error("invalid response from the IP echo service: {0}")]
+    InvalidResponse(String),
+}
+
+#[derive(Deserialize)]
+struct EchoResponse {
+    ip: IpAddr,
+    #[serde(default)]
+    country: Option<String>,
+}
+
+/// Asks the echo service for the public address of the tunnel, resolving it through the DNS
+/// servers of the tunnel. The connection is bound to the tunnel interface, so that it doesn't
+/// leave through the default one when only some destinations are routed through the tunnel. Each
+/// step is time-boxed.
+pub async fn lookup(connection_data: &ConnectionData) -> Result<ExitPublicIp, ExitIpLookupError> {
+    let addr = connectivity_test::time_boxed(connectivity_test::resolve(
+        &connection_data.dns_servers,
+        ECHO_HOST,
+    ))
+    .await
+    .map_err(ExitIpLookupError::EchoServiceUnreachable)?;
+
+    let response =
+        connectivity_test::time_boxed(fetch(SocketAddr::new(addr, HTTPS_PORT), connection_data))
+            .await
+            .map_err(ExitIpLookupError::EchoServiceUnreachable)?;
+
+    let exit_ip = parse_response(&response)?;
+    tracing::info!(
+        "Exit public IP: {} ({})",
+        exit_ip.ip,
+        exit_ip.country.as_deref().unwrap_or("unknown country")
+    );
+    Ok(exit_ip)
+}
+
+async fn fetch(addr: SocketAddr, connection_data: &ConnectionData) -> Result<Vec<u8>, String> {
+    let stream = connect_through_tunnel(addr, connection_data).await?;
+    let mut stream = tls_connector()
+        .connect(ServerName::try_from(ECHO_HOST).unwrap(), stream)
+        .await
+        .map_err(|e| format!("TLS handshake with {ECHO_HOST} failed: {e}"))?;
+
+    // HTTP/1.0 so that the body isn't chunked.
+    let request = format!(
+        "GET {ECHO_PATH} HTTP/1.0\r\nHost: {ECHO_HOST}\r\nAccept: application/json\r\n\
+         Connection: close\r\n\r\n"
+    );
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| format!("failed to send the request: {e}"))?;
+
+    let mut response = Vec::new();
+    stream
+        .take(MAX_RESPONSE_LEN)
+        .read_to_end(&mut response)
+        .await
+        .map_err(|e| format!("failed to read the response: {e}"))?;
+    Ok(response)
+}
+
+fn tls_connector() -> TlsConnector {
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    TlsConnector::from(Arc::new(config))
+}
+
+async fn connect_through_tunnel(
+    addr: SocketAddr,
+    connection_data: &ConnectionData,
+) -> Result<TcpStream, String> {
+    let (socket, tunnel_ip) = match (addr, &connection_data.tunnel) {
+        (SocketAddr::V4(_), TunnelConnectionData::Mixnet(data)) => {
+            (TcpSocket::new_v4(), IpAddr::V4(data.ipv4))
+        }
+        (SocketAddr::V6(_), TunnelConnectionData::Mixnet(data)) => {
+            (TcpSocket::new_v6(), IpAddr::V6(data.ipv6))
+        }
+        // The traffic goes through the exit tunnel, carried by the entry tunnel.
+        (SocketAddr::V4(_), TunnelConnectionData::Wireguard(data)) => {
+            (TcpSocket::new_v4(), IpAddr::V4(data.exit.private_ipv4))
+        }
+        (SocketAddr::V6(_), TunnelConnectionData::Wireguard(data)) => {
+            (TcpSocket::new_v6(), IpAddr::V6(data.exit.private_ipv6))
+        }
+    };
+    let socket = socket.map_err(|e| format!("failed to create a socket: {e}"))?;
+
+    // Binding to the address alone doesn't pick the outgoing interface on linux.
+    #[cfg(target_os = "linux")]
+    if let Some(tun_name) = connection_data.tun_name.as_deref() {
+        socket
+            .bind_device(Some(tun_name.as_bytes()))
+            .map_err(|e| format!("failed to bind to {tun_name}: {e}"))?;
+    }
+    socket
+        .bind(SocketAddr::new(tunnel_ip, 0))
+        .map_err(|e| format!("failed to bind to {tunnel_ip}: {e}"))?;
+
+    socket
+        .connect(addr)
+        .await
+        .map_err(|e| format!("failed to connect to {addr}: {e}"))
+}
+
+fn parse_response(response: &[u8]) -> Result<ExitPublicIp, ExitIpLookupError> {
+    match connectivity_test::parse_status_code(response) {
+        Some(200) => {}
+        Some(status) => return Err(ExitIpLookupError::EchoServiceStatus(status)),
+        None => {
+            return Err(ExitIpLookupError::InvalidResponse(
+                "not an HTTP response".to_owned(),
+            ))
+        }
+    }
+
+    let body = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|headers_len| &response[headers_len + 4..])
+        .ok_or_else(|| ExitIpLookupError::InvalidResponse("missing body".to_owned()))?;
+
+    let echo: EchoResponse = serde_json::from_slice(body)
+        .map_err(|e| ExitIpLookupError::InvalidResponse(e.to_string()))?;
+    Ok(ExitPublicIp {
+        ip: echo.ip,
+        country: echo.country.filter(|country| !country.is_empty()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn parses_the_echo_response() {
+        let response = b"HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n\
+                         {\"ip\": \"203.0.113.7\", \"city\": \"Zurich\", \"country\": \"CH\"}";
+        assert_eq!(
+            parse_response(response),
+            Ok(ExitPublicIp {
+                ip: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)),
+                country: Some("CH".to_owned()),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_error_responses() {
+        let response = b"HTTP/1.0 429 Too Many Requests\r\n\r\n{\"error\": \"rate limited\"}";
+        assert_eq!(
+            parse_response(response),
+            Err(ExitIpLookupError::EchoServiceStatus(429))
+        );
+        assert!(parse_response(b"HTTP/1.0 200 OK\r\n\r\nnot json").is_err());
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::time::Duration;
+
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+
+/// How often the tunnel activity is checked. Wireguard tunnels tell traffic apart from keepalives
+/// by how much went through in between two checks, so this must stay short.
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum IdleTimerEvent {
+    /// The tunnel is disconnected in `remaining` unless some traffic goes through or the timer is
+    /// reset in the meantime.
+    Warning { remaining: Duration },
+
+    /// The tunnel was idle for the whole timeout.
+    Expired,
+}
+
+/// Returns the interval at which [`IdleTimer::check`] is meant to be called.
+pub fn check_interval() -> Interval {
+    let mut interval =
+        tokio::time::interval_at(Instant::now() + IDLE_CHECK_INTERVAL, IDLE_CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    interval
+}
+
+/// Tells when to disconnect the tunnel once no traffic went through it for a while.
+pub struct IdleTimer {
+    timeout: Duration,
+    warning: Duration,
+    reset_at: Instant,
+    warned: bool,
+}
+
+impl IdleTimer {
+    pub fn new(timeout: Duration, warning: Duration) -> Self {
+        Self {
+            timeout,
+            warning,
+            reset_at: Instant::now(),
+            warned: false,
+        }
+    }
+
+    /// Counts the tunnel as idle from now on, e.g. once the user dismissed the warning.
+    pub fn reset(&mut self) {
+        self.reset_at = Instant::now();
+        self.warned = false;
+    }
+
+    /// Returns the event due given when the last traffic went through. The warning is only
+    /// returned once per idle period.
+    pub fn check(&mut self, now: Instant, last_activity: Instant) -> Option<IdleTimerEvent> {
+        let idle_since = last_activity.max(self.reset_at);
+        let remaining = self
+            .timeout
+            .saturating_sub(now.saturating_duration_since(idle_since));
+
+        if remaining.is_zero() {
+            Some(IdleTimerEvent::Expired)
+        } else if remaining > self.warning {
+            self.warned = false;
+            None
+        } else if self.warned {
+            None
+        } else {
+            self.warned = true;
+            Some(IdleTimerEvent::Warning { remaining })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(300);
+    const WARNING: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn warns_once_before_expiring() {
+        let mut idle_timer = IdleTimer::new(TIMEOUT, WARNING);
+        let idle_since = idle_timer.reset_at;
+
+        assert_eq!(
+            idle_timer.check(idle_since + Duration::from_secs(200), idle_since),
+            None
+        );
+        assert_eq!(
+            idle_timer.check(idle_since + Duration::from_secs(280), idle_since),
+            Some(IdleTimerEvent::Warning {
+                remaining: Duration::from_secs(20)
+            })
+        );
+        assert_eq!(
+            idle_timer.check(idle_since + Duration::from_secs(290), idle_since),
+            None
+        );
+        assert_eq!(
+            idle_timer.check(idle_since + TIMEOUT, idle_since),
+            Some(IdleTimerEvent::Expired)
+        );
+    }
+
+    #[test]
+    fn traffic_restarts_the_timer() {
+        let mut idle_timer = IdleTimer::new(TIMEOUT, WARNING);
+        let idle_since = idle_timer.reset_at;
+
+        assert!(idle_timer
+            .check(idle_since + Duration::from_secs(280), idle_since)
+            .is_some());
+
+        let last_activity = idle_since + Duration::from_secs(285);
+        assert_eq!(idle_timer.check(idle_since + TIMEOUT, last_activity), None);
+        assert_eq!(
+            idle_timer.check(last_activity + Duration::from_secs(290), last_activity),
+            Some(IdleTimerEvent::Warning {
+                remaining: Duration::from_secs(10)
+            })
+        );
+    }
+}
@@ -1,34 +1,55 @@
 // Copyright 2023 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-#[cfg(target_os = "linux")]
+mod connectivity_test;
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
 mod default_interface;
 #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
 mod dns_handler;
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+mod dns_leak_probe;
+mod exit_ip;
 //mod firewall_handler;
+mod idle_timer;
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+mod network_monitor;
+mod overhead;
 #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
 mod route_handler;
+mod session_summary;
 mod states;
+mod tun_device;
+#[cfg(any(target_os = "ios", target_os = "android", all(test, unix)))]
+mod tun_fd;
 #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
 mod tun_ipv6;
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+mod tun_provider;
 pub mod tunnel;
 mod tunnel_monitor;
 
-#[cfg(any(target_os = "ios", target_os = "android"))]
-use std::sync::Arc;
 use std::{
-    fmt,
+    cmp, fmt,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    ops::RangeInclusive,
     path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
+use serde::{Deserialize, Serialize};
 use si_scale::helpers::bibytes2;
 use time::OffsetDateTime;
-use tokio::{sync::mpsc, task::JoinHandle};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
 use tokio_util::sync::CancellationToken;
 
+use ipnetwork::{IpNetwork, Ipv6Network};
+use nym_connection_monitor::ConnectionQualityConfig;
 use nym_gateway_directory::{
-    Config as GatewayDirectoryConfig, EntryPoint, ExitPoint, NodeIdentity, Recipient,
+    Config as GatewayDirectoryConfig, EntryPoint, ExitPoint, NodeIdentity, ProxyConfig, Recipient,
 };
 use nym_ip_packet_requests::IpPair;
 use nym_wg_gateway_client::{Error as WgGatewayClientError, GatewayData};
@@ -47,8 +68,42 @@ use crate::{
 };
 #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
 use route_handler::RouteHandler;
+use session_summary::SessionRecorder;
 use states::DisconnectedState;
 
+pub use connectivity_test::{
+    ConnectivityTestReport, ConnectivityTestStep, ConnectivityTestStepKind,
+};
+pub use exit_ip::{ExitIpLookupError, ExitPublicIp};
+pub use overhead::OverheadEstimate;
+pub use session_summary::SessionSummary;
+pub use tun_device::TunDevice;
+#[cfg(any(target_os = "ios", target_os = "android"))]
+pub use tun_fd::open_tun_fds;
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+pub use tun_provider::{SystemTunProvider, TunProvider};
+
+/// Default maximum difference between the system clock and the directory clock.
+pub const DEFAULT_MAX_CLOCK_SKEW: Duration = Duration::from_secs(60);
+
+/// Allowed range of [`TunnelSettings::shutdown_timeout`].
+pub const SHUTDOWN_TIMEOUT_RANGE: RangeInclusive<Duration> =
+    Duration::from_secs(1)..=Duration::from_secs(60);
+
+/// Default interval between two DNS leak checks.
+pub const DEFAULT_DNS_LEAK_PROBE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default time allowed for a connection attempt altogether.
+pub const DEFAULT_CONNECT_DEADLINE: Duration = Duration::from_secs(90);
+
+/// Default time before the end of the session at which [`ConnectionEvent::SessionExpiring`] is
+/// emitted.
+pub const DEFAULT_SESSION_EXPIRY_WARNING: Duration = Duration::from_secs(60);
+
+/// Default time before the idle timeout at which [`ConnectionEvent::IdleTimeoutWarning`] is
+/// emitted.
+pub const DEFAULT_IDLE_TIMEOUT_WARNING: Duration = Duration::from_secs(30);
+
 #[async_trait::async_trait]
 trait TunnelStateHandler: Send {
     async fn handle_event(
@@ -65,12 +120,46 @@ enum NextTunnelState {
     Finished,
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, uniffi::Enum)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, uniffi::Enum)]
 pub enum TunnelType {
     Mixnet,
     Wireguard,
 }
 
+/// Where the randomly selected entry and exit gateways are located relative to each other.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RegionAffinity {
+    /// Both gateways on the same continent, for lower latency.
+    SameContinent,
+
+    /// The gateways on different continents, to make traffic analysis harder.
+    DifferentContinent,
+}
+
+impl fmt::Display for RegionAffinity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SameContinent => write!(f, "the same continent"),
+            Self::DifferentContinent => write!(f, "different continents"),
+        }
+    }
+}
+
+/// How to pick among the gateways that pass the performance thresholds.
+///
+/// Any strategy other than [`SelectionStrategy::Random`] concentrates the clients on fewer
+/// gateways, making it easier to guess which gateways a client is using. The top candidates are
+/// still picked from at random to spread the clients that connect at the same time.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum SelectionStrategy {
+    /// Any of the gateways, the default.
+    #[default]
+    Random,
+
+    /// One of the gateways with the highest performance reported by the directory.
+    HighestPerformance,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct TunnelSettings {
     /// Type of tunnel.
@@ -79,6 +168,15 @@ pub struct TunnelSettings {
     /// Enable the credentials mode between the client and the gateways.
     pub enable_credentials_mode: bool,
 
+    /// Generate the mixnet client and WireGuard keys in memory for this connection only, instead
+    /// of loading them from or writing them to the data path. They are dropped on disconnect.
+    /// The sticky gateways and the directory cache are skipped too, so the connection neither
+    /// reads from nor writes to the data path.
+    ///
+    /// Credentials are kept in an in-memory store as well, so this can't be combined with the
+    /// credentials mode, and no bandwidth is tracked across connections.
+    pub ephemeral_identity: bool,
+
     /// Mixnet tunnel options.
     pub mixnet_tunnel_options: MixnetTunnelOptions,
 
@@ -88,18 +186,114 @@ pub struct TunnelSettings {
     /// Overrides gateway config.
     pub gateway_performance_options: GatewayPerformanceOptions,
 
+    /// How to retry gateway IP lookups in the directory when connecting.
+    pub gateway_lookup_retry_options: GatewayLookupRetryOptions,
+
+    /// How to retry when connecting fails.
+    pub connect_retry_options: ConnectRetryOptions,
+
+    /// Give up with [`ErrorStateReason::ConnectDeadlineExceeded`] when a connection attempt takes
+    /// longer than this altogether, as a ceiling above the timeouts of the individual steps. The
+    /// delay before a retry doesn't count. Leave `None` to wait for as long as the steps take.
+    ///
+    /// A longer `mixnet_client_startup_timeout` raises the deadline, so that the other steps keep
+    /// as much time beyond the startup as they have with the default timeouts.
+    pub connect_deadline: Option<Duration>,
+
     /// Overrides mixnet client config when provided.
     /// Leave `None` to use sane defaults.
     pub mixnet_client_config: Option<MixnetClientConfig>,
 
+    /// Overrides the time allowed for the mixnet client to start up.
+    /// Leave `None` to use the default of 30 seconds.
+    pub mixnet_client_startup_timeout: Option<Duration>,
+
+    /// Overrides the time allowed for the mixnet client to shut down gracefully, between 1 and
+    /// 60 seconds. Leave `None` to use the default of 10 seconds.
+    ///
+    /// Note that a too short timeout may cut off the final flush of cover traffic.
+    pub shutdown_timeout: Option<Duration>,
+
+    /// Maximum difference between the system clock and the directory clock before giving up on
+    /// connecting, since credentials can't be verified with a clock that far off.
+    pub max_clock_skew: Duration,
+
+    /// Cache the gateway directory in the data path and serve it for this long. Overrides the
+    /// TTL of the gateway config when set. The cache is disabled without a TTL and with an
+    /// ephemeral identity.
+    pub directory_cache_ttl: Option<Duration>,
+
+    /// Send the gateway directory and nym-vpn-api requests made before the tunnel is up through
+    /// this proxy. Overrides the proxy of the gateway config when set.
+    pub proxy: Option<ProxyConfig>,
+
     /// Entry node.
     pub entry_point: Box<EntryPoint>,
 
     /// Exit node.
     pub exit_point: Box<ExitPoint>,
 
+    /// Allow entry and exit gateways in the same country when both are requested by the same
+    /// location.
+    pub allow_same_country: bool,
+
+    /// Gateways that must never be selected as entry or exit.
+    pub excluded_gateways: Vec<NodeIdentity>,
+
+    /// Seed for the random gateway selection, so that the same seed and directory listing yield
+    /// the same entry and exit gateways. Leave `None` to pick at random.
+    pub selection_seed: Option<u64>,
+
+    /// Constrain the continents of the entry and exit gateways. Only applies when neither
+    /// gateway is requested by location or identity.
+    pub region_affinity: Option<RegionAffinity>,
+
+    /// How to pick among the gateways selected at random or by location. Ignored when a region
+    /// affinity is set.
+    pub selection_strategy: SelectionStrategy,
+
+    /// Reuse the gateways from the last successful connection when they're still available and
+    /// were selected for the same entry and exit point. Gateways are only remembered once the
+    /// tunnel is up.
+    pub sticky_gateways: bool,
+
+    /// Keep local network traffic outside of the tunnel.
+    pub allow_lan: bool,
+
+    /// Only route the traffic to these networks through the tunnel, and leave the rest outside of
+    /// it. Empty to route all traffic through the tunnel. The system DNS is left alone unless DNS
+    /// servers are configured explicitly, in which case they are routed through the tunnel as
+    /// well. Only used on desktop.
+    pub routed_destinations: Vec<IpNetwork>,
+
+    /// Reconnect when the non-tunnel default route changes while connected, e.g. when switching
+    /// from wifi to ethernet. Only used on desktop.
+    pub reconnect_on_network_change: bool,
+
     /// DNS configuration.
     pub dns: DnsOptions,
+
+    /// Periodically check while connected that the name servers of the system resolver, other
+    /// than the configured DNS servers, don't answer queries sent outside the tunnel, and emit
+    /// [`ConnectionEvent::DnsLeakDetected`] otherwise. Only supported on desktop.
+    pub detect_dns_leaks: bool,
+
+    /// Interval between two DNS leak checks. Must be more than zero.
+    pub dns_leak_probe_interval: Duration,
+
+    /// Disconnect once the tunnel has been connected for this long, e.g. on shared devices.
+    /// The timer restarts whenever the tunnel reconnects. Leave `None` to stay connected.
+    pub max_session_duration: Option<Duration>,
+
+    /// How long before the end of the session to emit [`ConnectionEvent::SessionExpiring`].
+    pub session_expiry_warning: Duration,
+
+    /// Disconnect once no traffic went through the tun device for this long, to save battery.
+    /// Cover traffic doesn't count. Leave `None` to stay connected.
+    pub idle_timeout: Option<Duration>,
+
+    /// How long before the idle timeout to emit [`ConnectionEvent::IdleTimeoutWarning`].
+    pub idle_timeout_warning: Duration,
 }
 
 #[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
@@ -108,13 +302,85 @@ pub struct GatewayPerformanceOptions {
     pub vpn_min_performance: Option<u8>,
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct GatewayLookupRetryOptions {
+    /// Number of times to retry a failed lookup. Set to 0 to only try once.
+    pub max_retries: u32,
+
+    /// Delay before the first retry, doubled on each subsequent one.
+    pub base_delay: Duration,
+}
+
+impl Default for GatewayLookupRetryOptions {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ConnectRetryOptions {
+    /// Number of attempts after which connecting is given up with
+    /// [`ErrorStateReason::EstablishMixnetConnection`]. At least one attempt is always made.
+    ///
+    /// Leave `None` to retry forever, which also means that a configuration that can never
+    /// connect keeps retrying until the user disconnects.
+    pub max_connect_attempts: Option<u32>,
+
+    /// Delay before retry `n` is `base_delay * n * delay_multiplier`, capped at `max_delay`.
+    pub base_delay: Duration,
+
+    pub delay_multiplier: u32,
+
+    pub max_delay: Duration,
+}
+
+impl ConnectRetryOptions {
+    /// Returns the delay to wait for before the given retry attempt.
+    pub fn wait_delay(&self, retry_attempt: u32) -> Duration {
+        let multiplier = retry_attempt.saturating_mul(self.delay_multiplier);
+        let delay = self.base_delay.saturating_mul(multiplier);
+        cmp::min(delay, self.max_delay)
+    }
+
+    /// Returns true when no further attempt should be made after the given, failed, retry
+    /// attempt.
+    pub fn is_exhausted(&self, retry_attempt: u32) -> bool {
+        self.max_connect_attempts
+            .is_some_and(|max_attempts| retry_attempt.saturating_add(1) >= max_attempts)
+    }
+}
+
+impl Default for ConnectRetryOptions {
+    fn default() -> Self {
+        Self {
+            max_connect_attempts: None,
+            base_delay: Duration::from_secs(2),
+            delay_multiplier: 2,
+            max_delay: Duration::from_secs(15),
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, Eq, PartialEq)]
 pub struct MixnetTunnelOptions {
     /// Overrides tunnel interface addresses.
     pub interface_addrs: Option<IpPair>,
 
+    /// Requests this unique local address from the IPR as the IPv6 address of the tunnel
+    /// interface, e.g. for a closed network. The network is routed on-link through the tunnel
+    /// interface. Must be within `fc00::/7`, and requires `interface_addrs` with a private IPv4
+    /// address, whose IPv6 address it replaces. Connecting fails if the IPR assigns another
+    /// address.
+    pub ula_ipv6: Option<Ipv6Network>,
+
     /// Overrides tunnel interface MTU.
     pub mtu: Option<u16>,
+
+    /// Sampling window and cadence of the connection quality reports.
+    pub connection_quality: ConnectionQualityConfig,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -141,25 +407,152 @@ impl Default for WireguardMultihopMode {
     }
 }
 
-#[derive(Debug, Default, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct WireguardTunnelOptions {
     pub multihop_mode: WireguardMultihopMode,
+
+    /// Number of tickets to spend each time the bandwidth is topped up.
+    pub tickets_per_top_up: u32,
+
+    /// Top up the bandwidth ahead of time once the remaining bandwidth with a gateway drops below
+    /// this many bytes. Only used when credentials mode is enabled.
+    pub top_up_threshold: u64,
+
+    /// How often to check the remaining bandwidth with the gateways.
+    pub bandwidth_check_interval: Duration,
+
+    /// Interval in seconds between keepalive packets sent to the gateways, between 1 and 65535.
+    /// Leave `None` to disable persistent keepalive, or use e.g. 25 seconds to keep the tunnel
+    /// alive behind NATs that drop idle mappings.
+    pub persistent_keepalive: Option<u16>,
+
+    /// Emit [`BandwidthEvent::LowBandwidth`] once the remaining bandwidth with a gateway drops
+    /// below this many bytes.
+    pub low_bandwidth_threshold: u64,
+
+    /// Store the wireguard keys in this subdirectory of the data path, so that several profiles
+    /// sharing a data path don't use the same keys. Must be a single directory name.
+    pub key_namespace: Option<String>,
+
+    /// Periodically rotate the wireguard keys with the gateways while connected, at least an hour
+    /// apart. Leave `None` to keep the keys for the whole session.
+    pub key_rotation_interval: Option<Duration>,
+
+    /// Only connect to the entry gateway over one of these ports, e.g. on networks that block
+    /// most UDP ports. The exit gateway is reached through the entry tunnel, so its port doesn't
+    /// matter. Leave empty to allow any port.
+    ///
+    /// Gateways currently listen on a single port, so this can only reject a gateway whose port
+    /// is not allowed, which is checked before registering with it. Picking an allowed port among
+    /// several depends on the gateways advertising more than one.
+    pub preferred_wg_ports: Vec<u16>,
+
+    /// Connect to the entry gateway at this address instead of the one it advertises, e.g. when
+    /// the advertised address isn't reachable from behind a carrier-grade NAT. The keys
+    /// negotiated with the gateway are kept, and so is the port when this one is 0. A port given
+    /// here must be one of the preferred ports.
+    pub wg_endpoint_override: Option<SocketAddr>,
+
+    /// How the exit tunnel is brought back up once its handshakes stop, only used by the
+    /// netstack tunnel on iOS.
+    pub exit_tunnel_recovery: ExitTunnelRecoveryOptions,
+}
+
+impl Default for WireguardTunnelOptions {
+    fn default() -> Self {
+        Self {
+            multihop_mode: WireguardMultihopMode::default(),
+            tickets_per_top_up: nym_wg_gateway_client::TICKETS_TO_SPEND,
+            top_up_threshold: crate::bandwidth_controller::DEFAULT_TOP_UP_THRESHOLD,
+            bandwidth_check_interval: crate::bandwidth_controller::DEFAULT_BANDWIDTH_CHECK,
+            low_bandwidth_threshold: crate::bandwidth_controller::DEFAULT_LOW_BANDWIDTH_THRESHOLD,
+            persistent_keepalive: None,
+            key_namespace: None,
+            key_rotation_interval: None,
+            preferred_wg_ports: Vec::new(),
+            wg_endpoint_override: None,
+            exit_tunnel_recovery: ExitTunnelRecoveryOptions::default(),
+        }
+    }
+}
+
+/// Restarting the exit tunnel nested in the entry tunnel, e.g. when its session was lost while
+/// the device was asleep.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ExitTunnelRecoveryOptions {
+    /// How often to check the age of the last handshake with the exit gateway.
+    pub check_interval: Duration,
+
+    /// Consider the exit tunnel dead once the last handshake is older than this and packets sent
+    /// since the previous check got no reply. An idle tunnel doesn't handshake, so it's never
+    /// considered dead. Handshakes are renewed every 2 minutes while traffic flows and retried for
+    /// another 90 seconds, so this should stay above 3.5 minutes to tolerate a slow handshake.
+    pub max_handshake_age: Duration,
+
+    /// Number of times to restart the exit tunnel before giving up with
+    /// [`ErrorStateReason::ExitTunnelDown`]. Set to 0 to give up right away.
+    pub max_retries: u32,
+
+    /// How long to wait for a new handshake after probing the exit tunnel with keepalives, both
+    /// before the first restart in case the tunnel recovers on its own and after each restart.
+    pub handshake_timeout: Duration,
+
+    /// Delay between a failed restart and the next one.
+    pub retry_delay: Duration,
+}
+
+impl Default for ExitTunnelRecoveryOptions {
+    fn default() -> Self {
+        Self {
+            check_interval: Duration::from_secs(30),
+            max_handshake_age: Duration::from_secs(240),
+            max_retries: 3,
+            handshake_timeout: Duration::from_secs(10),
+            retry_delay: Duration::from_secs(2),
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, Eq, PartialEq)]
 pub enum DnsOptions {
     #[default]
     Default,
+    /// Use the DNS servers advertised by the exit gateway, so that lookups don't reveal to a
+    /// third party resolver that we are using the VPN. Falls back to the default servers when the
+    /// gateway advertises none.
+    GatewayProvided,
     Custom(Vec<IpAddr>),
 }
 
 impl DnsOptions {
     fn ip_addresses(&self) -> &[IpAddr] {
         match self {
-            Self::Default => &crate::DEFAULT_DNS_SERVERS,
+            // Only used before the exit gateway is known, see `DnsOptions::for_exit_gateway`
+            Self::Default | Self::GatewayProvided => &crate::DEFAULT_DNS_SERVERS,
             Self::Custom(addrs) => addrs,
         }
     }
+
+    /// Resolve the gateway provided servers once the exit gateway is selected.
+    fn for_exit_gateway(self, exit_gateway: &nym_gateway_directory::Gateway) -> Self {
+        match self {
+            Self::GatewayProvided if exit_gateway.dns_servers.is_empty() => {
+                tracing::warn!(
+                    "Exit gateway {} doesn't advertise any DNS servers, using the default ones",
+                    exit_gateway.identity()
+                );
+                Self::Default
+            }
+            Self::GatewayProvided => {
+                tracing::info!(
+                    "Using the DNS servers of the exit gateway: {:?}",
+                    exit_gateway.dns_servers
+                );
+                Self::Custom(exit_gateway.dns_servers.clone())
+            }
+            other => other,
+        }
+    }
 }
 
 impl Default for TunnelSettings {
@@ -169,16 +562,53 @@ impl Default for TunnelSettings {
             enable_credentials_mode: false,
             mixnet_tunnel_options: MixnetTunnelOptions::default(),
             mixnet_client_config: None,
+            mixnet_client_startup_timeout: None,
+            shutdown_timeout: None,
+            max_clock_skew: DEFAULT_MAX_CLOCK_SKEW,
+            directory_cache_ttl: None,
+            proxy: None,
             wireguard_tunnel_options: WireguardTunnelOptions::default(),
             gateway_performance_options: GatewayPerformanceOptions::default(),
+            gateway_lookup_retry_options: GatewayLookupRetryOptions::default(),
+            connect_retry_options: ConnectRetryOptions::default(),
+            connect_deadline: Some(DEFAULT_CONNECT_DEADLINE),
             entry_point: Box::new(EntryPoint::Random),
             exit_point: Box::new(ExitPoint::Random),
+            allow_same_country: true,
+            excluded_gateways: Vec::new(),
+            sticky_gateways: false,
+            allow_lan: false,
+            routed_destinations: Vec::new(),
             dns: DnsOptions::default(),
+            reconnect_on_network_change: true,
+            detect_dns_leaks: false,
+            dns_leak_probe_interval: DEFAULT_DNS_LEAK_PROBE_INTERVAL,
+            ephemeral_identity: false,
+            max_session_duration: None,
+            session_expiry_warning: DEFAULT_SESSION_EXPIRY_WARNING,
+            idle_timeout: None,
+            idle_timeout_warning: DEFAULT_IDLE_TIMEOUT_WARNING,
+            selection_seed: None,
+            region_affinity: None,
+            selection_strategy: SelectionStrategy::default(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+impl TunnelSettings {
+    /// The connect deadline, raised to fit a mixnet client startup timeout above the default.
+    pub fn effective_connect_deadline(&self) -> Option<Duration> {
+        let startup_timeout = self
+            .mixnet_client_startup_timeout
+            .unwrap_or(tunnel::DEFAULT_MIXNET_CLIENT_STARTUP_TIMEOUT);
+        let extra_startup_time =
+            startup_timeout.saturating_sub(tunnel::DEFAULT_MIXNET_CLIENT_STARTUP_TIMEOUT);
+        self.connect_deadline
+            .map(|connect_deadline| connect_deadline + extra_startup_time)
+    }
+}
+
+#[derive(Debug)]
 pub enum TunnelCommand {
     /// Connect the tunnel.
     Connect,
@@ -186,8 +616,57 @@ pub enum TunnelCommand {
     /// Disconnect the tunnel.
     Disconnect,
 
+    /// Disconnect the tunnel without waiting for it to shut down gracefully, for when the app is
+    /// about to be killed. Routing and DNS are still reset before entering the disconnected state,
+    /// but the tunnel keeps shutting down in the background, so the final flush of the mixnet
+    /// client may not complete.
+    ForceDisconnect,
+
     /// Set new tunnel settings.
     SetTunnelSettings(TunnelSettings),
+
+    /// Switch between the mixnet and wireguard tunnels, keeping the rest of the tunnel settings.
+    /// An active or connecting tunnel is reconnected with the new type. No-op if the tunnel type
+    /// doesn't change.
+    SetTunnelType(TunnelType),
+
+    /// Rotate the wireguard keys of a connected tunnel without reconnecting. Ignored in other
+    /// states.
+    RotateWireguardKeys,
+
+    /// Reply with the config of the connected wireguard tunnel in the wg-quick format, for
+    /// debugging. The private keys are redacted unless `include_private_keys` is set. Replies with
+    /// `None` in other states or when connected over the mixnet.
+    ExportWireguardConfig {
+        include_private_keys: bool,
+        reply_tx: oneshot::Sender<Option<String>>,
+    },
+
+    /// Reply with the outcome of a connectivity test run through the connected tunnel, using its
+    /// DNS servers. The test runs in the background and each of its steps is time-boxed. Replies
+    /// with `None` in other states.
+    RunConnectivityTest(oneshot::Sender<Option<ConnectivityTestReport>>),
+
+    /// Reply with the public address of the connected tunnel, as reported by an IP echo service
+    /// queried through the tunnel interface. The query runs in the background and is time-boxed.
+    /// Replies with `None` in other states.
+    GetExitPublicIp(oneshot::Sender<Option<Result<ExitPublicIp, ExitIpLookupError>>>),
+
+    /// Stop passing traffic through the connected mixnet tunnel while keeping the mixnet client
+    /// connected, so that the tunnel can be resumed without reconnecting. The routes and DNS are
+    /// reset on desktop. Ignored in other states or when connected over wireguard.
+    Pause,
+
+    /// Resume the paused tunnel. Ignored in other states.
+    Resume,
+
+    /// Count the connected tunnel as idle from now on, e.g. to keep it up after
+    /// [`ConnectionEvent::IdleTimeoutWarning`]. Ignored in other states.
+    ResetIdleTimer,
+
+    /// Reply with the last state the state machine entered. A transition in progress is only
+    /// reflected once the new state is entered.
+    QueryState(oneshot::Sender<TunnelState>),
 }
 
 #[derive(Clone, Eq, PartialEq, uniffi::Record)]
@@ -198,25 +677,106 @@ pub struct ConnectionData {
     /// Mixnet exit gateway
     pub exit_gateway: Box<NodeIdentity>,
 
+    /// Details about the selected entry gateway.
+    pub entry_gateway_diagnostics: GatewayDiagnostics,
+
+    /// Details about the selected exit gateway.
+    pub exit_gateway_diagnostics: GatewayDiagnostics,
+
     /// When the tunnel was last established.
     /// Set once the tunnel is connected.
     pub connected_at: Option<OffsetDateTime>,
 
+    /// Name of the tun interface carrying our traffic, i.e. the exit tunnel for wireguard.
+    /// Set as soon as the device is created, `None` if the platform doesn't expose it.
+    pub tun_name: Option<String>,
+
+    /// MTU of the tun interface carrying our traffic, after any clamping by us or the OS.
+    pub mtu: u16,
+
+    /// DNS servers used inside the tunnel, once the gateway provided servers are resolved.
+    pub dns_servers: Vec<IpAddr>,
+
+    /// Whether ecash tickets were spent for this session. Credentials mode may be requested but
+    /// not used, e.g. when the wireguard gateways already know our keys.
+    pub credentials_mode_active: bool,
+
+    /// Tickets spent to register with the gateways. Tickets the mixnet client spends with its
+    /// gateway are not included.
+    pub tickets_spent: u32,
+
+    /// Config the mixnet client was started with. Cover traffic is always disabled for
+    /// wireguard, regardless of what was requested.
+    pub mixnet_client_config: MixnetClientConfig,
+
     /// Tunnel connection data.
     pub tunnel: TunnelConnectionData,
 }
 
+/// Details about a selected gateway, to help explain the quality of the connection.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, uniffi::Record)]
+pub struct GatewayDiagnostics {
+    /// Round trip time to the gateway. Only measured when the gateway was selected based on
+    /// latency.
+    pub latency: Option<Duration>,
+
+    /// Mixnet performance score of the gateway according to the directory, in percent.
+    pub performance: Option<u8>,
+}
+
+impl GatewayDiagnostics {
+    fn new(gateway: &nym_gateway_directory::Gateway, latency: Option<Duration>) -> Self {
+        Self {
+            latency,
+            performance: gateway.mixnet_performance.map(|p| p.round_to_integer()),
+        }
+    }
+}
+
 impl fmt::Debug for ConnectionData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("ConnectionData")
             .field("entry_gateway", &self.entry_gateway.to_base58_string())
             .field("exit_gateway", &self.exit_gateway.to_base58_string())
+            .field("entry_gateway_diagnostics", &self.entry_gateway_diagnostics)
+            .field("exit_gateway_diagnostics", &self.exit_gateway_diagnostics)
             .field("connected_at", &self.connected_at)
+            .field("tun_name", &self.tun_name)
+            .field("mtu", &self.mtu)
+            .field("dns_servers", &self.dns_servers)
+            .field("credentials_mode_active", &self.credentials_mode_active)
+            .field("tickets_spent", &self.tickets_spent)
+            .field("mixnet_client_config", &self.mixnet_client_config)
             .field("tunnel", &self.tunnel)
             .finish()
     }
 }
 
+impl ConnectionData {
+    /// Type of the tunnel this connection data describes.
+    pub fn tunnel_type(&self) -> TunnelType {
+        match self.tunnel {
+            TunnelConnectionData::Mixnet(_) => TunnelType::Mixnet,
+            TunnelConnectionData::Wireguard(_) => TunnelType::Wireguard,
+        }
+    }
+
+    /// IP addresses assigned to us inside the tunnel.
+    pub fn assigned_ips(&self) -> IpPair {
+        match self.tunnel {
+            TunnelConnectionData::Mixnet(ref data) => IpPair {
+                ipv4: data.ipv4,
+                ipv6: data.ipv6,
+            },
+            // The exit tunnel is the one carrying our traffic.
+            TunnelConnectionData::Wireguard(ref data) => IpPair {
+                ipv4: data.exit.private_ipv4,
+                ipv6: data.exit.private_ipv6,
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, uniffi::Enum)]
 pub enum TunnelConnectionData {
     Mixnet(MixnetConnectionData),
@@ -259,13 +819,23 @@ pub struct WireguardConnectionData {
 /// Public enum describing the tunnel state
 #[derive(Debug, Clone, Eq, PartialEq, uniffi::Enum)]
 pub enum TunnelState {
-    Disconnected,
+    Disconnected {
+        reason: DisconnectReason,
+        /// Totals of the session that just ended, `None` if the tunnel never came up.
+        session_summary: Option<SessionSummary>,
+    },
     Connecting {
+        /// Number of the current retry, 0 for the first attempt.
+        retry_attempt: u32,
         connection_data: Option<ConnectionData>,
     },
     Connected {
         connection_data: ConnectionData,
     },
+    /// The tunnel is up but doesn't pass traffic until it's resumed.
+    Paused {
+        connection_data: ConnectionData,
+    },
     Disconnecting {
         after_disconnect: ActionAfterDisconnect,
     },
@@ -275,13 +845,21 @@ pub enum TunnelState {
 impl From<PrivateTunnelState> for TunnelState {
     fn from(value: PrivateTunnelState) -> Self {
         match value {
-            PrivateTunnelState::Disconnected => Self::Disconnected,
+            PrivateTunnelState::Disconnected { reason } => Self::Disconnected {
+                reason,
+                session_summary: None,
+            },
             PrivateTunnelState::Connected { connection_data } => {
                 Self::Connected { connection_data }
             }
-            PrivateTunnelState::Connecting { connection_data } => {
-                Self::Connecting { connection_data }
-            }
+            PrivateTunnelState::Paused { connection_data } => Self::Paused { connection_data },
+            PrivateTunnelState::Connecting {
+                retry_attempt,
+                connection_data,
+            } => Self::Connecting {
+                retry_attempt,
+                connection_data,
+            },
             PrivateTunnelState::Disconnecting { after_disconnect } => Self::Disconnecting {
                 after_disconnect: ActionAfterDisconnect::from(after_disconnect),
             },
@@ -293,13 +871,19 @@ impl From<PrivateTunnelState> for TunnelState {
 /// Private enum describing the tunnel state
 #[derive(Debug, Clone)]
 enum PrivateTunnelState {
-    Disconnected,
+    Disconnected {
+        reason: DisconnectReason,
+    },
     Connecting {
+        retry_attempt: u32,
         connection_data: Option<ConnectionData>,
     },
     Connected {
         connection_data: ConnectionData,
     },
+    Paused {
+        connection_data: ConnectionData,
+    },
     Disconnecting {
         after_disconnect: PrivateActionAfterDisconnect,
     },
@@ -323,7 +907,7 @@ impl From<PrivateActionAfterDisconnect> for ActionAfterDisconnect {
     fn from(value: PrivateActionAfterDisconnect) -> Self {
         match value {
             PrivateActionAfterDisconnect::Error(_) => Self::Error,
-            PrivateActionAfterDisconnect::Nothing => Self::Nothing,
+            PrivateActionAfterDisconnect::Nothing { .. } => Self::Nothing,
             PrivateActionAfterDisconnect::Reconnect { .. } => Self::Reconnect,
         }
     }
@@ -332,8 +916,8 @@ impl From<PrivateActionAfterDisconnect> for ActionAfterDisconnect {
 /// Private enum describing action to perform after disconnect
 #[derive(Debug, Clone)]
 enum PrivateActionAfterDisconnect {
-    /// Do nothing after disconnect
-    Nothing,
+    /// Do nothing after disconnect, providing the reason reported by the disconnected state
+    Nothing { reason: DisconnectReason },
 
     /// Reconnect after disconnect, providing the retry attempt counter
     Reconnect { retry_attempt: u32 },
@@ -342,6 +926,28 @@ enum PrivateActionAfterDisconnect {
     Error(ErrorStateReason),
 }
 
+/// Public enum describing why the tunnel ended up disconnected
+#[derive(Debug, Clone, Copy, Eq, PartialEq, uniffi::Enum)]
+pub enum DisconnectReason {
+    /// Disconnect was requested, or the state machine has just started.
+    UserRequested,
+
+    /// The error state was left by disconnecting.
+    Error(ErrorStateReason),
+
+    /// The gateway bandwidth ran out and could not be topped up.
+    BandwidthExhausted,
+
+    /// The network changed and reconnecting the tunnel failed.
+    NetworkChange,
+
+    /// The tunnel was connected for the maximum session duration.
+    SessionExpired,
+
+    /// No traffic went through the tunnel for the idle timeout.
+    IdleTimeout,
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, uniffi::Enum)]
 pub enum ErrorStateReason {
     /// Issues related to firewall configuration.
@@ -368,6 +974,12 @@ pub enum ErrorStateReason {
     /// Invalid country set for exit gateway
     InvalidExitGatewayCountry,
 
+    /// Entry gateway identity is not listed in the active network
+    InvalidEntryGatewayId,
+
+    /// Exit gateway identity is not listed in the active network
+    InvalidExitGatewayId,
+
     /// Gateway is not responding or responding badly to a bandwidth
     /// increase request, causing credential waste
     BadBandwidthIncrease,
@@ -375,11 +987,72 @@ pub enum ErrorStateReason {
     /// Failure to duplicate tunnel file descriptor.
     DuplicateTunFd,
 
+    /// System clock is too far off for credentials to be verified.
+    SystemClockSkew {
+        /// Number of seconds the system clock is ahead of the directory, or behind if negative.
+        skew_seconds: i64,
+    },
+
+    /// All the gateways matching the requested location are excluded.
+    AllGatewaysExcluded,
+
+    /// A gateway requested by identity is listed, but below the minimum performance.
+    GatewayBelowPerformanceThreshold {
+        /// The minimum performance in percent.
+        min_performance: u8,
+    },
+
+    /// No pair of entry and exit gateways satisfies the requested region affinity.
+    RegionAffinityNotSatisfied,
+
+    /// A gateway only listens for wireguard on a port outside of the preferred ports.
+    WireguardPortNotAllowed,
+
+    /// A gateway requested by identity doesn't run the authenticator wireguard requires.
+    GatewayWithoutAuthenticator,
+
+    /// Gave up connecting after the maximum number of attempts.
+    EstablishMixnetConnection,
+
+    /// The exit tunnel stopped completing handshakes and restarting it didn't help.
+    ExitTunnelDown,
+
+    /// The overridden tunnel addresses are invalid or weren't assigned by the IPR.
+    InvalidTunAddressOverride,
+
+    /// A connection attempt didn't complete within the connect deadline.
+    ConnectDeadlineExceeded {
+        /// The step the attempt was stuck at.
+        pending_step: ConnectStep,
+    },
+
     /// Program errors that must not happen.
     Internal,
 }
 
-#[derive(Debug, uniffi::Enum)]
+/// Steps of a connection attempt.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, uniffi::Enum)]
+pub enum ConnectStep {
+    Starting,
+    SelectingGateways,
+    ConnectingTunnel,
+    EstablishingTunnel,
+}
+
+impl fmt::Display for ConnectStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Starting => write!(f, "starting"),
+            Self::SelectingGateways => write!(f, "selecting gateways"),
+            Self::ConnectingTunnel => {
+                write!(f, "connecting to the mixnet and setting up the tunnel")
+            }
+            Self::EstablishingTunnel => write!(f, "establishing the tunnel"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, uniffi::Enum)]
 pub enum TunnelEvent {
     NewState(TunnelState),
     MixnetState(MixnetEvent),
@@ -399,12 +1072,15 @@ pub enum MixnetEvent {
     Bandwidth(BandwidthEvent),
     Connection(ConnectionEvent),
     ConnectionStatistics(ConnectionStatisticsEvent),
+    WireguardTraffic(WireguardTrafficEvent),
 }
 
 #[derive(Debug, Copy, Clone, uniffi::Enum)]
 pub enum BandwidthEvent {
     NoBandwidth,
     RemainingBandwidth(i64),
+    ToppedUp { new_total: i64 },
+    LowBandwidth { remaining: i64, threshold: u64 },
 }
 
 #[derive(Debug, Copy, Clone, uniffi::Enum)]
@@ -416,14 +1092,95 @@ pub enum ConnectionEvent {
     ExitGatewayRoutingErrorIpv6,
     ConnectedIpv4,
     ConnectedIpv6,
+    RetryingGatewayLookup { attempt: u32, max_retries: u32 },
+    RetryingIprConnect { attempt: u32, max_retries: u32 },
+    DnsLeakDetected { server: IpAddr },
+    Quality { rtt_ms: u32, loss_pct: u8 },
+    Latency(Duration),
+    SessionExpiring { remaining_secs: u64 },
+    IdleTimeoutWarning { remaining_secs: u64 },
+    MtuConfigured { mtu: u16 },
+    TunFdEstablished { fd: i32 },
+    TunFdReleased { fd: i32 },
+    CoverTrafficDisabledForWireguard,
 }
 
 #[derive(Debug, Copy, Clone, uniffi::Record)]
 pub struct ConnectionStatisticsEvent {
     pub rates: SphinxPacketRates,
+    /// Bytes of the IP packets sent and received through the tunnel since it came up.
+    pub real_bytes: u64,
+    /// Bytes of the IP packets read from the tun device since the tunnel came up.
+    pub real_bytes_sent: u64,
+    /// Bytes of the IP packets written to the tun device since the tunnel came up.
+    pub real_bytes_received: u64,
+    /// Cover traffic bytes sent and received since the mixnet client started, estimated from the
+    /// packet rates.
+    pub cover_bytes: u64,
+    /// Packets dropped by the mixnet processor since the tunnel came up.
+    pub packet_drops: MixnetPacketDrops,
+    /// Rolling average of the mixnet self ping round trip time, if any ping was answered yet.
+    pub mixnet_latency: Option<Duration>,
 }
 
-#[derive(Debug, Copy, Clone, uniffi::Record)]
+/// Bytes that went through the wireguard exit tunnel since it came up, counted by wireguard so
+/// including its handshakes and keepalives.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, uniffi::Record)]
+pub struct WireguardTrafficEvent {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, uniffi::Record)]
+pub struct MixnetPacketDrops {
+    /// Packets received from the mixnet that didn't make it to the tun device.
+    pub ingress: PacketDropCounts,
+    /// Packets read from the tun device that didn't make it to the mixnet. A bundle of packets
+    /// dropped as a whole counts once.
+    pub egress: PacketDropCounts,
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, uniffi::Record)]
+pub struct PacketDropCounts {
+    /// The tun device queue was full. The mixnet sender applies backpressure instead, so this
+    /// only happens on ingress.
+    pub queue_full: u64,
+    /// The packet read from the tun device was larger than its MTU, so this only happens on
+    /// egress.
+    pub oversized: u64,
+    /// Sending failed for any other reason.
+    pub send_error: u64,
+}
+
+impl MixnetPacketDrops {
+    pub fn total(&self) -> u64 {
+        self.ingress.total() + self.egress.total()
+    }
+}
+
+impl PacketDropCounts {
+    pub fn total(&self) -> u64 {
+        self.queue_full + self.oversized + self.send_error
+    }
+}
+
+impl fmt::Display for MixnetPacketDrops {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ingress: {}, egress: {}", self.ingress, self.egress)
+    }
+}
+
+impl fmt::Display for PacketDropCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "queue full: {}, oversized: {}, send error: {}",
+            self.queue_full, self.oversized, self.send_error
+        )
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, uniffi::Record)]
 pub struct SphinxPacketRates {
     pub real_packets_sent: f64,
     pub real_packets_sent_size: f64,
@@ -450,7 +1207,11 @@ pub struct SphinxPacketRates {
 
 impl fmt::Display for ConnectionStatisticsEvent {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.rates)
+        write!(f, "{}", self.rates)?;
+        if let Some(latency) = self.mixnet_latency {
+            write!(f, ", mixnet latency: {}ms", latency.as_millis())?;
+        }
+        Ok(())
     }
 }
 
@@ -497,13 +1258,27 @@ pub struct SharedState {
     dns_handler: DnsHandlerHandle,
     nym_config: NymConfig,
     tunnel_settings: TunnelSettings,
+    current_state: TunnelState,
     status_listener_handle: Option<JoinHandle<()>>,
+    /// Set while reconnecting because the network changed, until the tunnel is up again or the
+    /// reconnect is abandoned.
+    reconnecting_after_network_change: bool,
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    tun_provider: Arc<dyn TunProvider>,
     #[cfg(target_os = "ios")]
     tun_provider: Arc<dyn OSTunProvider>,
     #[cfg(target_os = "android")]
     tun_provider: Arc<dyn AndroidTunProvider>,
 }
 
+impl SharedState {
+    fn reply_with_current_state(&self, reply_tx: oneshot::Sender<TunnelState>) {
+        if reply_tx.send(self.current_state.clone()).is_err() {
+            tracing::warn!("Failed to reply to tunnel state query, receiver dropped");
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct NymConfig {
     pub data_path: Option<PathBuf>,
@@ -527,11 +1302,13 @@ impl TunnelStateMachine {
         event_sender: mpsc::UnboundedSender<TunnelEvent>,
         nym_config: NymConfig,
         tunnel_settings: TunnelSettings,
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+        tun_provider: Arc<dyn TunProvider>,
         #[cfg(target_os = "ios")] tun_provider: Arc<dyn OSTunProvider>,
         #[cfg(target_os = "android")] tun_provider: Arc<dyn AndroidTunProvider>,
         shutdown_token: CancellationToken,
     ) -> Result<JoinHandle<()>> {
-        let (current_state_handler, _) = DisconnectedState::enter();
+        let (current_state_handler, _) = DisconnectedState::enter(DisconnectReason::UserRequested);
 
         #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
         let route_handler = RouteHandler::new()
@@ -557,8 +1334,12 @@ impl TunnelStateMachine {
             dns_handler,
             nym_config,
             tunnel_settings,
+            current_state: TunnelState::Disconnected {
+                reason: DisconnectReason::UserRequested,
+                session_summary: None,
+            },
             status_listener_handle: None,
-            #[cfg(any(target_os = "ios", target_os = "android"))]
+            reconnecting_after_network_change: false,
             tun_provider,
         };
 
@@ -579,8 +1360,14 @@ impl TunnelStateMachine {
     async fn run(mut self) {
         let mut mixnet_event_receiver = self.mixnet_event_receiver;
         let cloned_event_sender = self.event_sender.clone();
+        let session_recorder = Arc::new(Mutex::new(SessionRecorder::default()));
+        let cloned_session_recorder = session_recorder.clone();
         tokio::spawn(async move {
             while let Some(event) = mixnet_event_receiver.recv().await {
+                cloned_session_recorder
+                    .lock()
+                    .unwrap()
+                    .handle_mixnet_event(&event);
                 if let Err(e) = cloned_event_sender.send(TunnelEvent::MixnetState(event)) {
                     tracing::error!("Failed to send tunnel event: {}", e);
                 }
@@ -601,8 +1388,18 @@ impl TunnelStateMachine {
                 NextTunnelState::NewState((new_state_handler, new_state)) => {
                     self.current_state_handler = new_state_handler;
 
-                    let state = TunnelState::from(new_state);
+                    let mut state = TunnelState::from(new_state);
+                    let tickets_per_top_up = self
+                        .shared_state
+                        .tunnel_settings
+                        .wireguard_tunnel_options
+                        .tickets_per_top_up;
+                    session_recorder
+                        .lock()
+                        .unwrap()
+                        .handle_new_state(&mut state, tickets_per_top_up);
                     tracing::debug!("New tunnel state: {}", state);
+                    self.shared_state.current_state = state.clone();
                     let _ = self.event_sender.send(TunnelEvent::NewState(state));
                 }
                 NextTunnelState::SameState(same_state) => {
@@ -624,6 +1421,54 @@ impl TunnelStateMachine {
     }
 }
 
+/// Connect the tunnel state machine and wait until the tunnel is up.
+///
+/// Returns the connection data once connected, or an error if the state machine enters the
+/// error state. Cancelling `cancel_token` while connecting disconnects the tunnel, waits for
+/// routing and DNS to be restored, and returns [`Error::Cancelled`].
+///
+/// Events received from `event_receiver` while connecting are consumed by this function.
+pub async fn connect_with_cancel(
+    command_sender: &mpsc::UnboundedSender<TunnelCommand>,
+    event_receiver: &mut mpsc::UnboundedReceiver<TunnelEvent>,
+    cancel_token: CancellationToken,
+) -> Result<ConnectionData> {
+    command_sender
+        .send(TunnelCommand::Connect)
+        .map_err(|_| Error::StateMachineStopped)?;
+
+    loop {
+        let event = tokio::select! {
+            _ = cancel_token.cancelled() => break,
+            event = event_receiver.recv() => event.ok_or(Error::StateMachineStopped)?,
+        };
+
+        match event {
+            TunnelEvent::NewState(TunnelState::Connected { connection_data }) => {
+                return Ok(connection_data);
+            }
+            TunnelEvent::NewState(TunnelState::Error(reason)) => {
+                return Err(Error::ConnectFailed(reason));
+            }
+            _ => {}
+        }
+    }
+
+    tracing::debug!("Connect cancelled, disconnecting");
+    command_sender
+        .send(TunnelCommand::Disconnect)
+        .map_err(|_| Error::StateMachineStopped)?;
+
+    while let Some(event) = event_receiver.recv().await {
+        if let TunnelEvent::NewState(TunnelState::Disconnected { .. } | TunnelState::Error(_)) =
+            event
+        {
+            return Err(Error::Cancelled);
+        }
+    }
+    Err(Error::StateMachineStopped)
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
@@ -651,7 +1496,7 @@ pub enum Error {
     #[error("failed to obtain route handle: {}", _0)]
     GetRouteHandle(#[source] route_handler::Error),
 
-    #[cfg(target_os = "linux")]
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
     #[error("failed to obtain default interface: {}", _0)]
     GetDefaultInterface(String),
 
@@ -667,12 +1512,28 @@ pub enum Error {
     #[error("failed to add routes: {}", _0)]
     AddRoutes(#[source] route_handler::Error),
 
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    #[error("can't route only {} through the tunnel", _0)]
+    InvalidRoutedDestination(IpNetwork),
+
+    #[error("DNS leak probe interval must be more than zero")]
+    InvalidDnsLeakProbeInterval,
+
     #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
     #[error("failed to set dns: {}", _0)]
     SetDns(#[source] dns_handler::Error),
 
     #[error("tunnel error: {}", _0)]
     Tunnel(#[from] tunnel::Error),
+
+    #[error("failed to connect: {:?}", _0)]
+    ConnectFailed(ErrorStateReason),
+
+    #[error("tunnel state machine has stopped")]
+    StateMachineStopped,
+
+    #[error("connection cancelled")]
+    Cancelled,
 }
 
 impl Error {
@@ -699,10 +1560,18 @@ impl Error {
             Self::LocateTunDevice(_) => ErrorStateReason::TunDevice,
 
             #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
-            Self::GetRouteHandle(_) => ErrorStateReason::Internal,
+            Self::GetRouteHandle(_) | Self::InvalidRoutedDestination(_) => {
+                ErrorStateReason::Internal
+            }
 
-            #[cfg(target_os = "linux")]
+            #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
             Self::GetDefaultInterface(_) => ErrorStateReason::Internal,
+
+            Self::InvalidDnsLeakProbeInterval => ErrorStateReason::Internal,
+
+            Self::ConnectFailed(reason) => *reason,
+
+            Self::StateMachineStopped | Self::Cancelled => return None,
         })
     }
 }
@@ -723,6 +1592,33 @@ impl tunnel::Error {
                     source: nym_gateway_directory::Error::NoMatchingExitGatewayForLocation { .. },
                 } => Some(ErrorStateReason::InvalidExitGatewayCountry),
 
+                GatewayDirectoryError::FailedToSelectEntryGatewayIdNotFound { .. } => {
+                    Some(ErrorStateReason::InvalidEntryGatewayId)
+                }
+
+                GatewayDirectoryError::FailedToSelectExitGatewayIdNotFound { .. } => {
+                    Some(ErrorStateReason::InvalidExitGatewayId)
+                }
+
+                GatewayDirectoryError::GatewayBelowPerformanceThreshold {
+                    min_performance, ..
+                } => Some(ErrorStateReason::GatewayBelowPerformanceThreshold {
+                    min_performance: *min_performance,
+                }),
+
+                GatewayDirectoryError::AllGatewaysExcluded { .. } => {
+                    Some(ErrorStateReason::AllGatewaysExcluded)
+                }
+
+                GatewayDirectoryError::RegionAffinityNotSatisfied { .. } => {
+                    Some(ErrorStateReason::RegionAffinityNotSatisfied)
+                }
+
+                GatewayDirectoryError::EntryGatewayWithoutAuthenticator { .. }
+                | GatewayDirectoryError::ExitGatewayWithoutAuthenticator { .. } => {
+                    Some(ErrorStateReason::GatewayWithoutAuthenticator)
+                }
+
                 _ => None,
             },
             Self::BandwidthController(BandwidthControllerError::RegisterWireguard {
@@ -734,6 +1630,22 @@ impl tunnel::Error {
                 ..
             }) => Some(ErrorStateReason::BadBandwidthIncrease),
             Self::DupFd(_) => Some(ErrorStateReason::DuplicateTunFd),
+            Self::SystemClockSkew { skew_seconds } => Some(ErrorStateReason::SystemClockSkew {
+                skew_seconds: *skew_seconds,
+            }),
+            Self::WireguardPortNotAllowed { .. } => Some(ErrorStateReason::WireguardPortNotAllowed),
+            Self::NonPrivateTunAddress(_)
+            | Self::UlaWithoutTunIpv4
+            | Self::UlaNotAssigned { .. } => Some(ErrorStateReason::InvalidTunAddressOverride),
+            #[cfg(target_os = "ios")]
+            Self::ExitTunnelDown { .. } => Some(ErrorStateReason::ExitTunnelDown),
+            // Retrying won't help with an invalid setting
+            Self::InvalidShutdownTimeout(_)
+            | Self::EphemeralIdentityWithCredentials
+            | Self::InvalidPersistentKeepalive
+            | Self::InvalidKeyNamespace(_)
+            | Self::InvalidWgEndpointOverride(_)
+            | Self::InvalidKeyRotationInterval(_) => Some(ErrorStateReason::Internal),
             _ => None,
         }
     }
@@ -744,8 +1656,27 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 impl fmt::Display for TunnelState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Disconnected => f.write_str("Disconnected"),
-            Self::Connecting { connection_data } => match connection_data {
+            Self::Disconnected { reason, .. } => match reason {
+                DisconnectReason::UserRequested => f.write_str("Disconnected"),
+                DisconnectReason::Error(reason) => {
+                    write!(f, "Disconnected after error: {:?}", reason)
+                }
+                DisconnectReason::BandwidthExhausted => {
+                    f.write_str("Disconnected because the bandwidth ran out")
+                }
+                DisconnectReason::NetworkChange => {
+                    f.write_str("Disconnected because the network changed")
+                }
+                DisconnectReason::SessionExpired => {
+                    f.write_str("Disconnected because the session expired")
+                }
+                DisconnectReason::IdleTimeout => {
+                    f.write_str("Disconnected because the tunnel was idle")
+                }
+            },
+            Self::Connecting {
+                connection_data, ..
+            } => match connection_data {
                 Some(data) => match data.tunnel {
                     TunnelConnectionData::Mixnet(ref data) => {
                         write!(
@@ -782,6 +1713,23 @@ impl fmt::Display for TunnelState {
                     )
                 }
             },
+            Self::Paused { connection_data } => match connection_data.tunnel {
+                TunnelConnectionData::Mixnet(ref data) => {
+                    write!(
+                        f,
+                        "Paused Mixnet tunnel with entry {} and exit {}",
+                        data.nym_address.gateway().to_base58_string(),
+                        data.exit_ipr.gateway().to_base58_string(),
+                    )
+                }
+                TunnelConnectionData::Wireguard(ref data) => {
+                    write!(
+                        f,
+                        "Paused WireGuard tunnel with entry {} and exit {}",
+                        data.entry.endpoint, data.exit.endpoint
+                    )
+                }
+            },
             Self::Disconnecting { after_disconnect } => match after_disconnect {
                 ActionAfterDisconnect::Nothing => f.write_str("Disconnecting"),
                 ActionAfterDisconnect::Reconnect => f.write_str("Disconnecting to reconnect"),
@@ -800,14 +1748,72 @@ impl fmt::Display for MixnetEvent {
             Self::Bandwidth(event) => write!(f, "{}", event),
             Self::Connection(event) => write!(f, "{}", event),
             Self::ConnectionStatistics(event) => write!(f, "{}", event),
+            Self::WireguardTraffic(event) => write!(f, "{}", event),
         }
     }
 }
 
+impl fmt::Display for WireguardTrafficEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Wireguard traffic: {} bytes sent, {} bytes received",
+            self.bytes_sent, self.bytes_received
+        )
+    }
+}
+
 impl fmt::Display for ConnectionEvent {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s = match self {
+            Self::RetryingGatewayLookup {
+                attempt,
+                max_retries,
+            } => {
+                return write!(
+                    f,
+                    "Retrying gateway lookup (attempt {}/{})",
+                    attempt, max_retries
+                );
+            }
+            Self::RetryingIprConnect {
+                attempt,
+                max_retries,
+            } => {
+                return write!(
+                    f,
+                    "Retrying to connect to the IPR (attempt {}/{})",
+                    attempt, max_retries
+                );
+            }
+            Self::DnsLeakDetected { server } => {
+                return write!(f, "DNS leak detected, query answered by {}", server);
+            }
+            Self::Quality { rtt_ms, loss_pct } => {
+                return write!(f, "Connection quality: {}ms rtt, {}% loss", rtt_ms, loss_pct);
+            }
+            Self::Latency(rtt) => {
+                return write!(f, "Mixnet latency: {}ms", rtt.as_millis());
+            }
+            Self::SessionExpiring { remaining_secs } => {
+                return write!(f, "Session expires in {}s", remaining_secs);
+            }
+            Self::IdleTimeoutWarning { remaining_secs } => {
+                return write!(f, "Disconnecting in {}s as the tunnel is idle", remaining_secs);
+            }
+            Self::MtuConfigured { mtu } => {
+                return write!(f, "Tunnel MTU is {}", mtu);
+            }
+            Self::TunFdEstablished { fd } => {
+                return write!(f, "Tunnel device fd {} established", fd);
+            }
+            Self::TunFdReleased { fd } => {
+                return write!(f, "Tunnel device fd {} released", fd);
+            }
             Self::ConnectedIpv4 => "Connected with IPv4",
+            Self::CoverTrafficDisabledForWireguard => {
+                "Cover traffic is disabled, wireguard tunnels don't support it"
+            }
             Self::ConnectedIpv6 => "Connected with IPv6",
             Self::EntryGatewayDown => {
                 "Entry gateway appears down - it's not routing our mixnet traffic"
@@ -826,6 +1832,13 @@ impl fmt::Display for BandwidthEvent {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::NoBandwidth => f.write_str("No bandwidth"),
+            Self::ToppedUp { new_total } => {
+                write!(
+                    f,
+                    "Bandwidth topped up, new total: {}",
+                    si_scale::helpers::bibytes2(*new_total as f64)
+                )
+            }
             Self::RemainingBandwidth(value) => {
                 write!(
                     f,
@@ -833,6 +1846,272 @@ impl fmt::Display for BandwidthEvent {
                     si_scale::helpers::bibytes2(*value as f64)
                 )
             }
+            Self::LowBandwidth {
+                remaining,
+                threshold,
+            } => {
+                write!(
+                    f,
+                    "Bandwidth running low: {}, below {}",
+                    si_scale::helpers::bibytes2(*remaining as f64),
+                    si_scale::helpers::bibytes2(*threshold as f64)
+                )
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use nym_gateway_directory::Gateway;
+
+    use super::*;
+
+    fn exit_gateway_with_dns(dns_servers: Vec<IpAddr>) -> Gateway {
+        let mut rng = rand::rngs::OsRng;
+        Gateway {
+            identity: *nym_crypto::asymmetric::ed25519::KeyPair::new(&mut rng).public_key(),
+            location: None,
+            ipr_address: None,
+            authenticator_address: None,
+            last_probe: None,
+            host: None,
+            clients_ws_port: None,
+            clients_wss_port: None,
+            mixnet_performance: None,
+            dns_servers,
+        }
+    }
+
+    #[test]
+    fn retry_delay_grows_with_the_attempt_up_to_the_cap() {
+        let options = ConnectRetryOptions::default();
+        let delays = (0..6)
+            .map(|retry_attempt| options.wait_delay(retry_attempt).as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, vec![0, 4, 8, 12, 15, 15]);
+        assert_eq!(options.wait_delay(u32::MAX), options.max_delay);
+    }
+
+    #[test]
+    fn retry_delay_follows_the_configured_backoff() {
+        let options = ConnectRetryOptions {
+            base_delay: Duration::from_millis(500),
+            delay_multiplier: 3,
+            max_delay: Duration::from_secs(60),
+            ..Default::default()
+        };
+        assert_eq!(options.wait_delay(1), Duration::from_millis(1500));
+        assert_eq!(options.wait_delay(4), Duration::from_secs(6));
+    }
+
+    #[test]
+    fn retries_are_unlimited_by_default() {
+        let options = ConnectRetryOptions::default();
+        assert!(!options.is_exhausted(0));
+        assert!(!options.is_exhausted(u32::MAX));
+    }
+
+    #[test]
+    fn retries_stop_after_the_maximum_attempts() {
+        let options = ConnectRetryOptions {
+            max_connect_attempts: Some(3),
+            ..Default::default()
+        };
+        // The retry attempt counts from 0, so the third attempt is retry attempt 2
+        assert!(!options.is_exhausted(0));
+        assert!(!options.is_exhausted(1));
+        assert!(options.is_exhausted(2));
+
+        // The first attempt is always made, even when no attempts are allowed
+        let options = ConnectRetryOptions {
+            max_connect_attempts: Some(0),
+            ..Default::default()
+        };
+        assert!(options.is_exhausted(0));
+    }
+
+    #[test]
+    fn connect_deadline_fits_a_longer_mixnet_client_startup() {
+        let default_settings = TunnelSettings::default();
+        assert_eq!(
+            default_settings.effective_connect_deadline(),
+            Some(DEFAULT_CONNECT_DEADLINE)
+        );
+
+        let shorter_startup = TunnelSettings {
+            mixnet_client_startup_timeout: Some(Duration::from_secs(10)),
+            ..TunnelSettings::default()
+        };
+        assert_eq!(
+            shorter_startup.effective_connect_deadline(),
+            Some(DEFAULT_CONNECT_DEADLINE)
+        );
+
+        let longer_startup = TunnelSettings {
+            mixnet_client_startup_timeout: Some(Duration::from_secs(120)),
+            ..TunnelSettings::default()
+        };
+        assert_eq!(
+            longer_startup.effective_connect_deadline(),
+            Some(DEFAULT_CONNECT_DEADLINE + Duration::from_secs(90))
+        );
+
+        let no_deadline = TunnelSettings {
+            connect_deadline: None,
+            ..longer_startup
+        };
+        assert_eq!(no_deadline.effective_connect_deadline(), None);
+    }
+
+    #[test]
+    fn gateway_provided_dns_uses_exit_gateway_servers() {
+        let servers = vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))];
+        let exit_gateway = exit_gateway_with_dns(servers.clone());
+
+        let dns = DnsOptions::GatewayProvided.for_exit_gateway(&exit_gateway);
+
+        assert_eq!(dns, DnsOptions::Custom(servers.clone()));
+        assert_eq!(dns.ip_addresses(), servers.as_slice());
+    }
+
+    #[test]
+    fn gateway_provided_dns_falls_back_to_default_servers() {
+        let exit_gateway = exit_gateway_with_dns(Vec::new());
+
+        let dns = DnsOptions::GatewayProvided.for_exit_gateway(&exit_gateway);
+
+        assert_eq!(dns, DnsOptions::Default);
+        assert_eq!(dns.ip_addresses(), crate::DEFAULT_DNS_SERVERS.as_slice());
+    }
+
+    #[test]
+    fn other_dns_options_ignore_exit_gateway_servers() {
+        let exit_gateway = exit_gateway_with_dns(vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))]);
+        let custom = DnsOptions::Custom(vec![IpAddr::V4(Ipv4Addr::new(9, 9, 9, 9))]);
+
+        assert_eq!(
+            DnsOptions::Default.for_exit_gateway(&exit_gateway),
+            DnsOptions::Default
+        );
+        assert_eq!(custom.clone().for_exit_gateway(&exit_gateway), custom);
+    }
+
+    fn new_state(state: TunnelState) -> TunnelEvent {
+        TunnelEvent::NewState(state)
+    }
+
+    #[tokio::test]
+    async fn connect_with_cancel_fails_when_entering_error_state() {
+        let (command_sender, mut command_receiver) = mpsc::unbounded_channel();
+        let (event_sender, mut event_receiver) = mpsc::unbounded_channel();
+        event_sender
+            .send(new_state(TunnelState::Connecting {
+                retry_attempt: 0,
+                connection_data: None,
+            }))
+            .unwrap();
+        event_sender
+            .send(new_state(TunnelState::Error(ErrorStateReason::Routing)))
+            .unwrap();
+
+        let result = connect_with_cancel(
+            &command_sender,
+            &mut event_receiver,
+            CancellationToken::new(),
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(Error::ConnectFailed(ErrorStateReason::Routing))
+        ));
+        assert!(matches!(
+            command_receiver.try_recv(),
+            Ok(TunnelCommand::Connect)
+        ));
+        assert!(command_receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn connect_with_cancel_disconnects_and_waits_when_cancelled() {
+        let (command_sender, mut command_receiver) = mpsc::unbounded_channel();
+        let (event_sender, mut event_receiver) = mpsc::unbounded_channel();
+        let cancel_token = CancellationToken::new();
+
+        let state_machine = async {
+            assert!(matches!(
+                command_receiver.recv().await,
+                Some(TunnelCommand::Connect)
+            ));
+            event_sender
+                .send(new_state(TunnelState::Connecting {
+                    retry_attempt: 0,
+                    connection_data: None,
+                }))
+                .unwrap();
+            cancel_token.cancel();
+
+            assert!(matches!(
+                command_receiver.recv().await,
+                Some(TunnelCommand::Disconnect)
+            ));
+            event_sender
+                .send(new_state(TunnelState::Disconnecting {
+                    after_disconnect: ActionAfterDisconnect::Nothing,
+                }))
+                .unwrap();
+            event_sender
+                .send(new_state(TunnelState::Disconnected {
+                    reason: DisconnectReason::UserRequested,
+                    session_summary: None,
+                }))
+                .unwrap();
+        };
+
+        let (result, ()) = tokio::join!(
+            connect_with_cancel(&command_sender, &mut event_receiver, cancel_token.clone()),
+            state_machine
+        );
+
+        assert!(matches!(result, Err(Error::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn connect_with_cancel_fails_when_state_machine_is_gone() {
+        let (command_sender, command_receiver) = mpsc::unbounded_channel();
+        let (_event_sender, mut event_receiver) = mpsc::unbounded_channel();
+        drop(command_receiver);
+
+        let result = connect_with_cancel(
+            &command_sender,
+            &mut event_receiver,
+            CancellationToken::new(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(Error::StateMachineStopped)));
+    }
+
+    #[tokio::test]
+    async fn connect_with_cancel_fails_when_events_stop() {
+        let (command_sender, _command_receiver) = mpsc::unbounded_channel();
+        let (event_sender, mut event_receiver) = mpsc::unbounded_channel();
+        event_sender
+            .send(new_state(TunnelState::Connecting {
+                retry_attempt: 0,
+                connection_data: None,
+            }))
+            .unwrap();
+        drop(event_sender);
+
+        let result = connect_with_cancel(
+            &command_sender,
+            &mut event_receiver,
+            CancellationToken::new(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(Error::StateMachineStopped)));
+    }
+}
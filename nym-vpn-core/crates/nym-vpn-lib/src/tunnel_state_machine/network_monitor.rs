@@ -0,0 +1,56 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::route_handler::RouteHandler;
+
+/// Further default route changes within this period are merged into the previous one, so that a
+/// flapping interface only triggers a single reconnect once it settles.
+const HOLD_DOWN_PERIOD: Duration = Duration::from_secs(3);
+
+/// Watch the non-tunnel default route and send a single notification once it has changed and
+/// settled.
+pub fn spawn(
+    route_handler: RouteHandler,
+    cancel_token: CancellationToken,
+) -> mpsc::UnboundedReceiver<()> {
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let changed = cancel_token
+            .run_until_cancelled(wait_for_default_route_change(route_handler))
+            .await;
+        if changed == Some(true) {
+            _ = tx.send(());
+        }
+    });
+
+    rx
+}
+
+async fn wait_for_default_route_change(route_handler: RouteHandler) -> bool {
+    let mut changes = match route_handler.default_route_changes().await {
+        Ok(changes) => changes,
+        Err(e) => {
+            tracing::error!("Failed to listen for default route changes: {}", e);
+            return false;
+        }
+    };
+
+    if changes.recv().await.is_none() {
+        return false;
+    }
+    tracing::info!("Default route changed, waiting for it to settle");
+
+    loop {
+        match tokio::time::timeout(HOLD_DOWN_PERIOD, changes.recv()).await {
+            Ok(Some(())) => continue,
+            Ok(None) => return false,
+            Err(_) => return true,
+        }
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::time::Duration;
+
+use super::{
+    tunnel::wireguard::two_hop_config::{EXIT_MTU, WG_TUNNEL_OVERHEAD},
+    TunnelSettings, TunnelType,
+};
+
+/// Expected bandwidth cost of a tunnel on top of the traffic it carries.
+#[derive(Debug, Clone, Copy, PartialEq, uniffi::Record)]
+pub struct OverheadEstimate {
+    /// Bytes sent on the wire for every byte of payload, e.g. 1.1 for 10% overhead.
+    pub bytes_per_payload_byte: f64,
+
+    /// Bytes sent per second even when no payload is sent, from cover traffic and keepalives.
+    pub cover_traffic_bytes_per_sec: f64,
+}
+
+impl TunnelSettings {
+    /// Estimates the overhead of a tunnel of the given type with the current settings, without
+    /// connecting.
+    ///
+    /// For the mixnet this is a lower bound: it accounts for the sphinx packet size and the cover
+    /// traffic, but not for acknowledgements or retransmissions. For wireguard it assumes full
+    /// size packets through both hops.
+    pub fn overhead_estimate(&self, tunnel_type: TunnelType) -> OverheadEstimate {
+        match tunnel_type {
+            TunnelType::Mixnet => self.mixnet_overhead_estimate(),
+            TunnelType::Wireguard => self.wireguard_overhead_estimate(),
+        }
+    }
+
+    fn mixnet_overhead_estimate(&self) -> OverheadEstimate {
+        let mixnet_client_config = self.mixnet_client_config.clone().unwrap_or_default();
+        let debug_config = nym_client_core::config::DebugConfig::default();
+        let packet_size = debug_config.traffic.primary_packet_size;
+        let packet_bytes = packet_size.size() as f64;
+
+        // With the poisson process enabled, the main stream sends a packet on every tick whether
+        // or not there is payload to fill it with.
+        let mut cover_traffic_bytes_per_sec = 0.0;
+        if !mixnet_client_config.disable_poisson_rate {
+            cover_traffic_bytes_per_sec += packets_per_sec(
+                packet_bytes,
+                debug_config.traffic.message_sending_average_delay,
+            );
+        }
+        if !mixnet_client_config.disable_background_cover_traffic {
+            cover_traffic_bytes_per_sec += packets_per_sec(
+                packet_bytes,
+                debug_config.cover_traffic.loop_cover_traffic_average_delay,
+            );
+        }
+
+        OverheadEstimate {
+            bytes_per_payload_byte: packet_bytes / packet_size.plaintext_size() as f64,
+            cover_traffic_bytes_per_sec,
+        }
+    }
+
+    // The exit tunnel is nested in the entry tunnel, so every packet carries the overhead twice.
+    // The mixnet client is only used to talk to the authenticators and has its cover traffic
+    // disabled.
+    fn wireguard_overhead_estimate(&self) -> OverheadEstimate {
+        let payload_bytes = f64::from(EXIT_MTU);
+        let overhead_bytes = f64::from(WG_TUNNEL_OVERHEAD) * 2.0;
+
+        // A keepalive is an empty data packet. Both tunnels send them, and the ones of the nested
+        // exit tunnel carry the overhead twice.
+        let cover_traffic_bytes_per_sec = self
+            .wireguard_tunnel_options
+            .persistent_keepalive
+            .map(|interval| f64::from(WG_TUNNEL_OVERHEAD) * 3.0 / f64::from(interval.max(1)))
+            .unwrap_or(0.0);
+
+        OverheadEstimate {
+            bytes_per_payload_byte: (payload_bytes + overhead_bytes) / payload_bytes,
+            cover_traffic_bytes_per_sec,
+        }
+    }
+}
+
+fn packets_per_sec(packet_bytes: f64, average_delay: Duration) -> f64 {
+    if average_delay.is_zero() {
+        return 0.0;
+    }
+    packet_bytes / average_delay.as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MixnetClientConfig;
+
+    #[test]
+    fn overhead_follows_the_settings() {
+        let mut tunnel_settings = TunnelSettings::default();
+
+        let wireguard = tunnel_settings.overhead_estimate(TunnelType::Wireguard);
+        assert!(wireguard.bytes_per_payload_byte > 1.0);
+        assert_eq!(wireguard.cover_traffic_bytes_per_sec, 0.0);
+
+        tunnel_settings
+            .wireguard_tunnel_options
+            .persistent_keepalive = Some(25);
+        let wireguard = tunnel_settings.overhead_estimate(TunnelType::Wireguard);
+        assert!(wireguard.cover_traffic_bytes_per_sec > 0.0);
+
+        let mixnet = tunnel_settings.overhead_estimate(TunnelType::Mixnet);
+        assert!(mixnet.bytes_per_payload_byte > wireguard.bytes_per_payload_byte);
+        assert!(mixnet.cover_traffic_bytes_per_sec > 0.0);
+
+        tunnel_settings.mixnet_client_config = Some(MixnetClientConfig {
+            disable_poisson_rate: true,
+            disable_background_cover_traffic: true,
+            ..Default::default()
+        });
+        let mixnet = tunnel_settings.overhead_estimate(TunnelType::Mixnet);
+        assert_eq!(mixnet.cover_traffic_bytes_per_sec, 0.0);
+    }
+}
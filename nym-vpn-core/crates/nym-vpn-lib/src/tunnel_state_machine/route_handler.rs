@@ -3,10 +3,15 @@
 
 use std::{collections::HashSet, fmt, net::IpAddr};
 
-use ipnetwork::IpNetwork;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use futures::StreamExt;
+use ipnetwork::{IpNetwork, Ipv6Network};
+#[cfg(target_os = "windows")]
+use nym_routing::EventType;
 #[cfg(not(target_os = "linux"))]
 use nym_routing::NetNode;
 use nym_routing::{Node, RequiredRoute, RouteManagerHandle};
+use tokio::sync::mpsc;
 
 #[cfg(target_os = "linux")]
 use super::default_interface::DefaultInterface;
@@ -16,10 +21,28 @@ pub const TUNNEL_TABLE_ID: u32 = 0x14d;
 #[cfg(target_os = "linux")]
 pub const TUNNEL_FWMARK: u32 = 0x14d;
 
+/// Local networks kept outside of the tunnel when LAN access is allowed.
+const LAN_NETWORKS: [&str; 8] = [
+    // RFC1918 private networks
+    "10.0.0.0/8",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    // Link-local
+    "169.254.0.0/16",
+    "fe80::/10",
+    // Unique local
+    "fc00::/7",
+    // Multicast
+    "224.0.0.0/4",
+    "ff00::/8",
+];
+
 pub enum RoutingConfig {
     Mixnet {
         tun_name: String,
         entry_gateway_address: IpAddr,
+        /// Unique local network of the tun device, routed on-link.
+        ula_ipv6: Option<Ipv6Network>,
         #[cfg(target_os = "linux")]
         physical_interface: DefaultInterface,
     },
@@ -56,8 +79,15 @@ impl RouteHandler {
         Ok(Self { route_manager })
     }
 
-    pub async fn add_routes(&mut self, routing_config: RoutingConfig) -> Result<()> {
-        let routes = Self::get_routes(routing_config);
+    /// Routes all traffic through the tunnel, or only the traffic to `routed_destinations` when
+    /// not empty.
+    pub async fn add_routes(
+        &mut self,
+        routing_config: RoutingConfig,
+        allow_lan: bool,
+        routed_destinations: &[IpNetwork],
+    ) -> Result<()> {
+        let routes = Self::get_routes(routing_config, allow_lan, routed_destinations);
 
         #[cfg(target_os = "linux")]
         self.route_manager.create_routing_rules().await?;
@@ -84,18 +114,105 @@ impl RouteHandler {
         _ = tokio::task::spawn_blocking(|| drop(self.route_manager)).await;
     }
 
+    /// Returns a channel that is notified whenever the non-tunnel default route changed. The
+    /// subscription ends once the receiver is dropped.
+    pub async fn default_route_changes(&self) -> Result<mpsc::UnboundedReceiver<()>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        #[cfg(target_os = "linux")]
+        {
+            let mut changes = Box::pin(self.route_manager.change_listener().await?);
+            let mut default_route = Self::current_default_route();
+            tokio::spawn(async move {
+                loop {
+                    tokio::select! {
+                        _ = tx.closed() => break,
+                        Some(_) = changes.next() => {
+                            // Changes are reported for all the routing tables, including the
+                            // tunnel one, so only notify when the default route is a new one.
+                            let new_default_route = Self::current_default_route();
+                            if new_default_route != default_route {
+                                default_route = new_default_route;
+                                if tx.send(()).is_err() {
+                                    break;
+                                }
+                            }
+                        }
+                        else => break,
+                    }
+                }
+            });
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            let mut changes = Box::pin(self.route_manager.default_route_listener().await?);
+            tokio::spawn(async move {
+                loop {
+                    tokio::select! {
+                        _ = tx.closed() => break,
+                        Some(_) = changes.next() => {
+                            if tx.send(()).is_err() {
+                                break;
+                            }
+                        }
+                        else => break,
+                    }
+                }
+            });
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+            let callback_tx = tx.clone();
+            let callback_handle = self
+                .route_manager
+                .add_default_route_change_callback(Box::new(
+                    move |event: EventType<'_>, _family| {
+                        // Only the details of the same default route changed
+                        if !matches!(event, EventType::UpdatedDetails(_)) {
+                            _ = callback_tx.send(());
+                        }
+                    },
+                ))
+                .await?;
+            // The callback stays registered for as long as the handle is alive.
+            tokio::spawn(async move {
+                tx.closed().await;
+                drop(callback_handle);
+            });
+        }
+
+        Ok(rx)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn current_default_route() -> Option<(String, Option<IpAddr>)> {
+        DefaultInterface::current().ok().map(|interface| {
+            (
+                interface.interface_name().to_owned(),
+                interface.gateway_ip(),
+            )
+        })
+    }
+
     #[cfg(target_os = "linux")]
     pub(super) fn inner_handle(&self) -> nym_routing::RouteManagerHandle {
         self.route_manager.clone()
     }
 
-    fn get_routes(routing_config: RoutingConfig) -> HashSet<RequiredRoute> {
+    fn get_routes(
+        routing_config: RoutingConfig,
+        allow_lan: bool,
+        routed_destinations: &[IpNetwork],
+    ) -> HashSet<RequiredRoute> {
         let mut routes = HashSet::new();
 
         match routing_config {
             RoutingConfig::Mixnet {
                 tun_name,
                 entry_gateway_address,
+                ula_ipv6,
                 #[cfg(target_os = "linux")]
                 physical_interface,
             } => {
@@ -111,15 +228,26 @@ impl RouteHandler {
                     physical_interface.as_node(),
                 ));
 
-                routes.insert(RequiredRoute::new(
-                    "0.0.0.0/0".parse().unwrap(),
-                    Node::device(tun_name.to_owned()),
-                ));
-
-                routes.insert(RequiredRoute::new(
-                    "::0/0".parse().unwrap(),
-                    Node::device(tun_name.to_owned()),
-                ));
+                if allow_lan {
+                    Self::add_lan_routes(
+                        &mut routes,
+                        #[cfg(target_os = "linux")]
+                        &physical_interface,
+                    );
+                }
+
+                Self::add_tunnel_routes(&mut routes, &tun_name, routed_destinations);
+
+                // More specific than the unique local range kept outside of the tunnel when LAN
+                // access is allowed, so it takes precedence.
+                if let Some(ula_ipv6) = ula_ipv6 {
+                    let network = Ipv6Network::new(ula_ipv6.network(), ula_ipv6.prefix())
+                        .expect("network of a valid ipv6 network");
+                    routes.insert(RequiredRoute::new(
+                        IpNetwork::V6(network),
+                        Node::device(tun_name.to_owned()),
+                    ));
+                }
             }
             RoutingConfig::Wireguard {
                 entry_tun_name,
@@ -141,20 +269,20 @@ impl RouteHandler {
                     physical_interface.as_node(),
                 ));
 
+                if allow_lan {
+                    Self::add_lan_routes(
+                        &mut routes,
+                        #[cfg(target_os = "linux")]
+                        &physical_interface,
+                    );
+                }
+
                 routes.insert(RequiredRoute::new(
                     IpNetwork::from(exit_gateway_address),
                     Node::device(entry_tun_name.to_owned()),
                 ));
 
-                routes.insert(RequiredRoute::new(
-                    "0.0.0.0/0".parse().unwrap(),
-                    Node::device(exit_tun_name.to_owned()),
-                ));
-
-                routes.insert(RequiredRoute::new(
-                    "::0/0".parse().unwrap(),
-                    Node::device(exit_tun_name.to_owned()),
-                ));
+                Self::add_tunnel_routes(&mut routes, &exit_tun_name, routed_destinations);
             }
             RoutingConfig::WireguardNetstack {
                 exit_tun_name,
@@ -174,15 +302,15 @@ impl RouteHandler {
                     physical_interface.as_node(),
                 ));
 
-                routes.insert(RequiredRoute::new(
-                    "0.0.0.0/0".parse().unwrap(),
-                    Node::device(exit_tun_name.to_owned()),
-                ));
+                if allow_lan {
+                    Self::add_lan_routes(
+                        &mut routes,
+                        #[cfg(target_os = "linux")]
+                        &physical_interface,
+                    );
+                }
 
-                routes.insert(RequiredRoute::new(
-                    "::0/0".parse().unwrap(),
-                    Node::device(exit_tun_name.to_owned()),
-                ));
+                Self::add_tunnel_routes(&mut routes, &exit_tun_name, routed_destinations);
             }
         }
 
@@ -196,6 +324,45 @@ impl RouteHandler {
 
         routes
     }
+
+    fn add_tunnel_routes(
+        routes: &mut HashSet<RequiredRoute>,
+        tun_name: &str,
+        routed_destinations: &[IpNetwork],
+    ) {
+        if routed_destinations.is_empty() {
+            routes.insert(RequiredRoute::new(
+                "0.0.0.0/0".parse().unwrap(),
+                Node::device(tun_name.to_owned()),
+            ));
+
+            routes.insert(RequiredRoute::new(
+                "::0/0".parse().unwrap(),
+                Node::device(tun_name.to_owned()),
+            ));
+        } else {
+            for network in routed_destinations {
+                routes.insert(RequiredRoute::new(
+                    *network,
+                    Node::device(tun_name.to_owned()),
+                ));
+            }
+        }
+    }
+
+    fn add_lan_routes(
+        routes: &mut HashSet<RequiredRoute>,
+        #[cfg(target_os = "linux")] physical_interface: &DefaultInterface,
+    ) {
+        for network in LAN_NETWORKS {
+            let network: IpNetwork = network.parse().unwrap();
+
+            #[cfg(not(target_os = "linux"))]
+            routes.insert(RequiredRoute::new(network, NetNode::DefaultNode));
+            #[cfg(target_os = "linux")]
+            routes.insert(RequiredRoute::new(network, physical_interface.as_node()));
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -226,6 +393,15 @@ impl fmt::Display for Error {
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// Returns the first of the destinations that can't be routed through the tunnel on its own: the
+/// default routes, which are what the full tunnel is for, networks with host bits set, and
+/// loopback networks.
+pub fn invalid_routed_destination(routed_destinations: &[IpNetwork]) -> Option<IpNetwork> {
+    routed_destinations.iter().copied().find(|network| {
+        network.prefix() == 0 || network.ip() != network.network() || network.ip().is_loopback()
+    })
+}
+
 #[cfg(target_os = "linux")]
 impl DefaultInterface {
     fn as_node(&self) -> Node {
@@ -238,3 +414,22 @@ impl DefaultInterface {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routed_destinations_must_be_networks() {
+        let valid = ["10.1.0.0/16", "203.0.113.7/32", "2001:db8::/32"]
+            .map(|network| network.parse().unwrap());
+        assert_eq!(invalid_routed_destination(&valid), None);
+        assert_eq!(invalid_routed_destination(&[]), None);
+
+        for invalid in ["0.0.0.0/0", "::/0", "10.1.2.3/16", "127.0.0.0/8", "::1/128"] {
+            let invalid: IpNetwork = invalid.parse().unwrap();
+            let destinations = [valid[0], invalid];
+            assert_eq!(invalid_routed_destination(&destinations), Some(invalid));
+        }
+    }
+}
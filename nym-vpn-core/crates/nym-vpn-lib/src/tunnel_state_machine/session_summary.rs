@@ -0,0 +1,352 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::time::{Duration, Instant};
+
+use nym_gateway_directory::NodeIdentity;
+use time::OffsetDateTime;
+
+use super::{BandwidthEvent, ConnectionData, MixnetEvent, TunnelState, TunnelType};
+
+/// Totals of a session, from the moment the tunnel first came up until it was disconnected.
+/// Reconnects in between, e.g. after a network change, are part of the same session.
+#[derive(Debug, Clone, Eq, PartialEq, uniffi::Record)]
+pub struct SessionSummary {
+    /// Entry gateway of the last tunnel of the session.
+    pub entry_gateway: Box<NodeIdentity>,
+
+    /// Exit gateway of the last tunnel of the session.
+    pub exit_gateway: Box<NodeIdentity>,
+
+    /// Type of the last tunnel of the session.
+    pub tunnel_type: TunnelType,
+
+    /// When the tunnel first came up.
+    pub started_at: OffsetDateTime,
+
+    /// Time spent connected, not counting the time spent reconnecting.
+    pub duration: Duration,
+
+    /// Bytes sent. Mixnet tunnels count the payload only, wireguard tunnels count what went
+    /// through the exit tunnel including the wireguard handshakes and keepalives.
+    pub bytes_sent: u64,
+
+    /// Bytes received, counted the same way as `bytes_sent`.
+    pub bytes_received: u64,
+
+    /// Payload bytes sent and received per second connected.
+    pub average_throughput: u64,
+
+    /// Tickets spent to register with the gateways and top up the bandwidth. Tickets the mixnet
+    /// client spends with its gateway are not included.
+    pub tickets_spent: u32,
+}
+
+/// Accumulates the totals of the current session from the state transitions and mixnet events.
+#[derive(Debug, Default)]
+pub(super) struct SessionRecorder {
+    session: Option<Session>,
+}
+
+#[derive(Debug)]
+struct Session {
+    summary: SessionSummary,
+    connected_since: Option<Instant>,
+    tickets_per_top_up: u32,
+    // Traffic of the current tunnel, the counters restart with every tunnel.
+    tunnel_bytes_sent: u64,
+    tunnel_bytes_received: u64,
+    // Set while the tunnel is paused, resuming it carries on with the same tunnel.
+    tunnel_paused: bool,
+}
+
+impl SessionRecorder {
+    /// Updates the session with a state transition, and fills in the summary when the session
+    /// ends with a disconnect.
+    pub(super) fn handle_new_state(&mut self, state: &mut TunnelState, tickets_per_top_up: u32) {
+        match state {
+            TunnelState::Connected { connection_data } => {
+                self.connected(connection_data, tickets_per_top_up)
+            }
+            TunnelState::Paused { .. } => {
+                self.pause();
+                if let Some(session) = self.session.as_mut() {
+                    session.tunnel_paused = true;
+                }
+            }
+            TunnelState::Disconnected {
+                session_summary, ..
+            } => {
+                self.pause();
+                *session_summary = self.finish();
+            }
+            _ => {
+                self.pause();
+                if let Some(session) = self.session.as_mut() {
+                    session.tunnel_paused = false;
+                }
+            }
+        }
+    }
+
+    pub(super) fn handle_mixnet_event(&mut self, event: &MixnetEvent) {
+        let Some(session) = self.session.as_mut() else {
+            return;
+        };
+        match event {
+            // Statistics from before the tunnel is up, or from a tunnel going away, would be
+            // attributed to the wrong tunnel.
+            MixnetEvent::ConnectionStatistics(statistics)
+                if session.connected_since.is_some()
+                    && session.summary.tunnel_type == TunnelType::Mixnet =>
+            {
+                session.tunnel_bytes_sent = statistics.real_bytes_sent;
+                session.tunnel_bytes_received = statistics.real_bytes_received;
+            }
+            MixnetEvent::WireguardTraffic(traffic)
+                if session.connected_since.is_some()
+                    && session.summary.tunnel_type == TunnelType::Wireguard =>
+            {
+                session.tunnel_bytes_sent = traffic.bytes_sent;
+                session.tunnel_bytes_received = traffic.bytes_received;
+            }
+            MixnetEvent::Bandwidth(BandwidthEvent::ToppedUp { .. }) => {
+                session.summary.tickets_spent += session.tickets_per_top_up;
+            }
+            _ => {}
+        }
+    }
+
+    fn connected(&mut self, connection_data: &ConnectionData, tickets_per_top_up: u32) {
+        let session = self.session.get_or_insert_with(|| Session {
+            summary: SessionSummary {
+                entry_gateway: connection_data.entry_gateway.clone(),
+                exit_gateway: connection_data.exit_gateway.clone(),
+                tunnel_type: connection_data.tunnel_type(),
+                started_at: connection_data
+                    .connected_at
+                    .unwrap_or_else(OffsetDateTime::now_utc),
+                duration: Duration::ZERO,
+                bytes_sent: 0,
+                bytes_received: 0,
+                average_throughput: 0,
+                tickets_spent: 0,
+            },
+            connected_since: None,
+            tickets_per_top_up,
+            tunnel_bytes_sent: 0,
+            tunnel_bytes_received: 0,
+            tunnel_paused: false,
+        });
+
+        if std::mem::take(&mut session.tunnel_paused) {
+            session.connected_since.get_or_insert_with(Instant::now);
+            return;
+        }
+
+        session.fold_tunnel_traffic();
+        session.summary.entry_gateway = connection_data.entry_gateway.clone();
+        session.summary.exit_gateway = connection_data.exit_gateway.clone();
+        session.summary.tunnel_type = connection_data.tunnel_type();
+        session.summary.tickets_spent += connection_data.tickets_spent;
+        session.tickets_per_top_up = tickets_per_top_up;
+        session.connected_since.get_or_insert_with(Instant::now);
+    }
+
+    fn pause(&mut self) {
+        if let Some(session) = self.session.as_mut() {
+            if let Some(connected_since) = session.connected_since.take() {
+                session.summary.duration += connected_since.elapsed();
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Option<SessionSummary> {
+        let mut session = self.session.take()?;
+        session.fold_tunnel_traffic();
+
+        let mut summary = session.summary;
+        let secs = summary.duration.as_secs_f64();
+        if secs > 0.0 {
+            summary.average_throughput =
+                ((summary.bytes_sent + summary.bytes_received) as f64 / secs) as u64;
+        }
+        Some(summary)
+    }
+}
+
+impl Session {
+    fn fold_tunnel_traffic(&mut self) {
+        self.summary.bytes_sent += std::mem::take(&mut self.tunnel_bytes_sent);
+        self.summary.bytes_received += std::mem::take(&mut self.tunnel_bytes_received);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    use nym_gateway_directory::Recipient;
+    use nym_wg_go::PublicKey;
+
+    use super::*;
+    use crate::tunnel_state_machine::{
+        ConnectionStatisticsEvent, DisconnectReason, GatewayDiagnostics, MixnetConnectionData,
+        MixnetPacketDrops, SphinxPacketRates, TunnelConnectionData, WireguardConnectionData,
+        WireguardNode, WireguardTrafficEvent,
+    };
+
+    const IDENTITY: &str = "4SPdxfBYsuARBw6REQQa5vFiKcvmYiet9sSWqb751i3Z";
+
+    fn connected(tickets_spent: u32) -> TunnelState {
+        let identity = Box::new(NodeIdentity::from_base58_string(IDENTITY).unwrap());
+        let address = Box::new(
+            Recipient::try_from_base58_string(format!("{IDENTITY}.{IDENTITY}@{IDENTITY}")).unwrap(),
+        );
+        TunnelState::Connected {
+            connection_data: ConnectionData {
+                entry_gateway: identity.clone(),
+                exit_gateway: identity,
+                entry_gateway_diagnostics: GatewayDiagnostics::default(),
+                exit_gateway_diagnostics: GatewayDiagnostics::default(),
+                connected_at: None,
+                tun_name: None,
+                mtu: 1280,
+                dns_servers: Vec::new(),
+                credentials_mode_active: tickets_spent > 0,
+                tickets_spent,
+                mixnet_client_config: Default::default(),
+                tunnel: TunnelConnectionData::Mixnet(MixnetConnectionData {
+                    nym_address: address.clone(),
+                    exit_ipr: address,
+                    ipv4: Ipv4Addr::new(10, 0, 0, 2),
+                    ipv6: Ipv6Addr::LOCALHOST,
+                }),
+            },
+        }
+    }
+
+    fn connected_wireguard() -> TunnelState {
+        let TunnelState::Connected {
+            mut connection_data,
+        } = connected(0)
+        else {
+            unreachable!()
+        };
+        let node = WireguardNode {
+            endpoint: SocketAddr::from((Ipv4Addr::new(192, 0, 2, 1), 51822)),
+            public_key: Box::new(PublicKey::from([1; 32])),
+            private_ipv4: Ipv4Addr::new(10, 1, 0, 2),
+            private_ipv6: Ipv6Addr::LOCALHOST,
+        };
+        connection_data.tunnel = TunnelConnectionData::Wireguard(WireguardConnectionData {
+            entry: node.clone(),
+            exit: node,
+        });
+        TunnelState::Connected { connection_data }
+    }
+
+    fn wireguard_traffic(bytes_sent: u64, bytes_received: u64) -> MixnetEvent {
+        MixnetEvent::WireguardTraffic(WireguardTrafficEvent {
+            bytes_sent,
+            bytes_received,
+        })
+    }
+
+    fn statistics(real_bytes_sent: u64, real_bytes_received: u64) -> MixnetEvent {
+        MixnetEvent::ConnectionStatistics(ConnectionStatisticsEvent {
+            rates: SphinxPacketRates::default(),
+            real_bytes: real_bytes_sent + real_bytes_received,
+            real_bytes_sent,
+            real_bytes_received,
+            cover_bytes: 0,
+            packet_drops: MixnetPacketDrops::default(),
+            mixnet_latency: None,
+        })
+    }
+
+    fn disconnect(recorder: &mut SessionRecorder) -> Option<SessionSummary> {
+        let mut state = TunnelState::Disconnected {
+            reason: DisconnectReason::UserRequested,
+            session_summary: None,
+        };
+        recorder.handle_new_state(&mut state, 1);
+        match state {
+            TunnelState::Disconnected {
+                session_summary, ..
+            } => session_summary,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn summary_covers_reconnects() {
+        let mut recorder = SessionRecorder::default();
+        assert_eq!(disconnect(&mut recorder), None);
+
+        recorder.handle_new_state(&mut connected(2), 1);
+        recorder.handle_mixnet_event(&statistics(1000, 2000));
+        recorder.handle_mixnet_event(&MixnetEvent::Bandwidth(BandwidthEvent::ToppedUp {
+            new_total: 1024,
+        }));
+
+        recorder.handle_new_state(
+            &mut TunnelState::Connecting {
+                retry_attempt: 0,
+                connection_data: None,
+            },
+            1,
+        );
+        // Late statistics of the old tunnel are ignored
+        recorder.handle_mixnet_event(&statistics(5000, 5000));
+
+        recorder.handle_new_state(&mut connected(2), 1);
+        recorder.handle_mixnet_event(&statistics(100, 200));
+
+        let summary = disconnect(&mut recorder).unwrap();
+        assert_eq!(summary.bytes_sent, 1100);
+        assert_eq!(summary.bytes_received, 2200);
+        assert_eq!(summary.tickets_spent, 5);
+        assert_eq!(summary.tunnel_type, TunnelType::Mixnet);
+
+        assert_eq!(disconnect(&mut recorder), None);
+    }
+
+    #[test]
+    fn summary_covers_pauses() {
+        let mut recorder = SessionRecorder::default();
+
+        recorder.handle_new_state(&mut connected(2), 1);
+        recorder.handle_mixnet_event(&statistics(1000, 2000));
+
+        let TunnelState::Connected { connection_data } = connected(2) else {
+            unreachable!()
+        };
+        recorder.handle_new_state(&mut TunnelState::Paused { connection_data }, 1);
+
+        // Resuming carries on with the same tunnel, whose statistics keep adding up
+        recorder.handle_new_state(&mut connected(2), 1);
+        recorder.handle_mixnet_event(&statistics(1100, 2200));
+
+        let summary = disconnect(&mut recorder).unwrap();
+        assert_eq!(summary.bytes_sent, 1100);
+        assert_eq!(summary.bytes_received, 2200);
+        assert_eq!(summary.tickets_spent, 2);
+    }
+
+    #[test]
+    fn summary_counts_wireguard_traffic() {
+        let mut recorder = SessionRecorder::default();
+
+        recorder.handle_new_state(&mut connected_wireguard(), 1);
+        recorder.handle_mixnet_event(&wireguard_traffic(1000, 2000));
+        // Mixnet statistics only count for mixnet tunnels
+        recorder.handle_mixnet_event(&statistics(5000, 5000));
+        recorder.handle_mixnet_event(&wireguard_traffic(1500, 3000));
+
+        let summary = disconnect(&mut recorder).unwrap();
+        assert_eq!(summary.bytes_sent, 1500);
+        assert_eq!(summary.bytes_received, 3000);
+        assert_eq!(summary.tunnel_type, TunnelType::Wireguard);
+    }
+}
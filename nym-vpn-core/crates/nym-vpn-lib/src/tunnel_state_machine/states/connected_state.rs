@@ -1,19 +1,38 @@
 // Copyright 2023 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use tokio::sync::mpsc;
+use tokio::{sync::mpsc, time::Instant};
 use tokio_util::sync::CancellationToken;
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+use tokio_util::sync::DropGuard;
 
 use crate::tunnel_state_machine::{
-    states::DisconnectingState,
+    connectivity_test, exit_ip,
+    states::{wait_until, DisconnectingState, PausedState},
     tunnel_monitor::{TunnelMonitorEvent, TunnelMonitorEventReceiver, TunnelMonitorHandle},
-    ConnectionData, NextTunnelState, PrivateActionAfterDisconnect, PrivateTunnelState, SharedState,
-    TunnelCommand, TunnelStateHandler,
+    ConnectionData, ConnectionEvent, DisconnectReason, MixnetEvent, NextTunnelState,
+    PrivateActionAfterDisconnect, PrivateTunnelState, SharedState, TunnelCommand,
+    TunnelConnectionData, TunnelStateHandler,
 };
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+use crate::tunnel_state_machine::{dns_leak_probe::DnsLeakProbe, network_monitor};
 
 pub struct ConnectedState {
-    monitor_handle: TunnelMonitorHandle,
-    monitor_event_receiver: TunnelMonitorEventReceiver,
+    pub(super) monitor_handle: TunnelMonitorHandle,
+    pub(super) monitor_event_receiver: TunnelMonitorEventReceiver,
+    pub(super) connection_data: ConnectionData,
+    /// Stops the DNS leak probe when leaving the connected state or pausing.
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    _dns_leak_probe_guard: Option<DropGuard>,
+    /// Notified once the default route has changed.
+    network_change_rx: mpsc::UnboundedReceiver<()>,
+    /// Stops the network monitor when leaving the connected state or pausing.
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    _network_monitor_guard: Option<DropGuard>,
+    /// When the maximum session duration is reached, if there is one.
+    pub(super) session_expires_at: Option<Instant>,
+    /// When to warn that the session is about to expire. Cleared once the warning was sent.
+    session_warning_at: Option<Instant>,
 }
 
 impl ConnectedState {
@@ -21,16 +40,111 @@ impl ConnectedState {
         connection_data: ConnectionData,
         monitor_handle: TunnelMonitorHandle,
         monitor_event_receiver: TunnelMonitorEventReceiver,
-        _shared_state: &mut SharedState,
+        shared_state: &mut SharedState,
     ) -> (Box<dyn TunnelStateHandler>, PrivateTunnelState) {
+        shared_state.reconnecting_after_network_change = false;
+
+        // Entering the connected state again after a reconnect starts a new session
+        let now = Instant::now();
+        let session_expires_at = shared_state
+            .tunnel_settings
+            .max_session_duration
+            .map(|max_session_duration| now + max_session_duration);
+        let session_warning_at = session_expires_at.map(|expires_at| {
+            expires_at
+                .checked_sub(shared_state.tunnel_settings.session_expiry_warning)
+                .unwrap_or(now)
+        });
+
+        let mut connected_state = Box::new(Self {
+            monitor_handle,
+            monitor_event_receiver,
+            connection_data: connection_data.clone(),
+            #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+            _dns_leak_probe_guard: None,
+            network_change_rx: mpsc::unbounded_channel().1,
+            #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+            _network_monitor_guard: None,
+            session_expires_at,
+            session_warning_at,
+        });
+        connected_state.start_monitors(shared_state);
+
         (
-            Box::new(Self {
-                monitor_handle,
-                monitor_event_receiver,
-            }),
+            connected_state,
             PrivateTunnelState::Connected { connection_data },
         )
     }
+
+    /// Resumes the paused tunnel, keeping the session going.
+    pub(super) fn resume(
+        mut self: Box<Self>,
+        shared_state: &mut SharedState,
+    ) -> (Box<dyn TunnelStateHandler>, PrivateTunnelState) {
+        self.monitor_handle.resume();
+        self.start_monitors(shared_state);
+
+        let connection_data = self.connection_data.clone();
+        (self, PrivateTunnelState::Connected { connection_data })
+    }
+
+    fn pause(mut self: Box<Self>) -> (Box<dyn TunnelStateHandler>, PrivateTunnelState) {
+        self.monitor_handle.pause();
+        self.stop_monitors();
+        PausedState::enter(self)
+    }
+
+    // The DNS leak probe and the network monitor expect the traffic to go through the tunnel, so
+    // they only run while it does.
+    fn start_monitors(&mut self, shared_state: &SharedState) {
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+        {
+            self._dns_leak_probe_guard = shared_state.tunnel_settings.detect_dns_leaks.then(|| {
+                let cancel_token = CancellationToken::new();
+                DnsLeakProbe::spawn(
+                    self.connection_data.dns_servers.clone(),
+                    shared_state.tunnel_settings.dns_leak_probe_interval,
+                    shared_state.mixnet_event_sender.clone(),
+                    cancel_token.clone(),
+                );
+                cancel_token.drop_guard()
+            });
+
+            // Mobile platforms handle network changes within the tunnel
+            if shared_state.tunnel_settings.reconnect_on_network_change {
+                let cancel_token = CancellationToken::new();
+                self.network_change_rx = network_monitor::spawn(
+                    shared_state.route_handler.clone(),
+                    cancel_token.clone(),
+                );
+                self._network_monitor_guard = Some(cancel_token.drop_guard());
+            }
+        }
+
+        #[cfg(any(target_os = "ios", target_os = "android"))]
+        if shared_state.tunnel_settings.detect_dns_leaks {
+            tracing::warn!("DNS leak detection is not supported on this platform");
+        }
+    }
+
+    fn stop_monitors(&mut self) {
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+        {
+            self._dns_leak_probe_guard = None;
+            self._network_monitor_guard = None;
+        }
+        self.network_change_rx = mpsc::unbounded_channel().1;
+    }
+
+    // Tears the tunnel down and brings it back up with the current tunnel settings.
+    fn reconnect(self: Box<Self>, shared_state: &mut SharedState) -> NextTunnelState {
+        let after_disconnect = PrivateActionAfterDisconnect::Reconnect { retry_attempt: 0 };
+        NextTunnelState::NewState(DisconnectingState::enter(
+            after_disconnect,
+            self.monitor_handle,
+            shared_state,
+        ))
+    }
 }
 
 #[async_trait::async_trait]
@@ -43,24 +157,97 @@ impl TunnelStateHandler for ConnectedState {
     ) -> NextTunnelState {
         tokio::select! {
             _ = shutdown_token.cancelled() => {
-                NextTunnelState::NewState(DisconnectingState::enter(PrivateActionAfterDisconnect::Nothing, self.monitor_handle, shared_state))
+                NextTunnelState::NewState(DisconnectingState::enter(PrivateActionAfterDisconnect::Nothing { reason: DisconnectReason::UserRequested }, self.monitor_handle, shared_state))
             }
             Some(command) = command_rx.recv() => {
                 match command {
                     TunnelCommand::Connect => NextTunnelState::SameState(self),
                     TunnelCommand::Disconnect => {
-                        NextTunnelState::NewState(DisconnectingState::enter(PrivateActionAfterDisconnect::Nothing, self.monitor_handle , shared_state))
+                        NextTunnelState::NewState(DisconnectingState::enter(PrivateActionAfterDisconnect::Nothing { reason: DisconnectReason::UserRequested }, self.monitor_handle, shared_state))
                     },
+                    TunnelCommand::ForceDisconnect => {
+                        NextTunnelState::NewState(DisconnectingState::enter_forced(self.monitor_handle, shared_state))
+                    }
                     TunnelCommand::SetTunnelSettings(tunnel_settings) => {
                         if shared_state.tunnel_settings == tunnel_settings {
                             NextTunnelState::SameState(self)
                         } else {
                             shared_state.tunnel_settings = tunnel_settings;
-                            NextTunnelState::NewState(DisconnectingState::enter(PrivateActionAfterDisconnect::Reconnect { retry_attempt: 0 }, self.monitor_handle, shared_state))
+                            self.reconnect(shared_state)
                         }
                     }
+                    TunnelCommand::SetTunnelType(tunnel_type) => {
+                        if shared_state.tunnel_settings.tunnel_type == tunnel_type {
+                            NextTunnelState::SameState(self)
+                        } else {
+                            tracing::info!("Switching tunnel type to {:?}", tunnel_type);
+                            shared_state.tunnel_settings.tunnel_type = tunnel_type;
+                            self.reconnect(shared_state)
+                        }
+                    }
+                    TunnelCommand::RotateWireguardKeys => {
+                        self.monitor_handle.rotate_keys();
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::ExportWireguardConfig { include_private_keys, reply_tx } => {
+                        self.monitor_handle.export_wireguard_config(include_private_keys, reply_tx);
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::RunConnectivityTest(reply_tx) => {
+                        let dns_servers = self.connection_data.dns_servers.clone();
+                        tokio::spawn(async move {
+                            let report = connectivity_test::run(&dns_servers).await;
+                            let _ = reply_tx.send(Some(report));
+                        });
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::GetExitPublicIp(reply_tx) => {
+                        let connection_data = self.connection_data.clone();
+                        tokio::spawn(async move {
+                            let result = exit_ip::lookup(&connection_data).await;
+                            let _ = reply_tx.send(Some(result));
+                        });
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::Pause => {
+                        if matches!(self.connection_data.tunnel, TunnelConnectionData::Mixnet(_)) {
+                            NextTunnelState::NewState(self.pause())
+                        } else {
+                            tracing::warn!("Pausing is only supported by the mixnet tunnel");
+                            NextTunnelState::SameState(self)
+                        }
+                    }
+                    TunnelCommand::Resume => NextTunnelState::SameState(self),
+                    TunnelCommand::ResetIdleTimer => {
+                        self.monitor_handle.reset_idle_timer();
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::QueryState(reply_tx) => {
+                        shared_state.reply_with_current_state(reply_tx);
+                        NextTunnelState::SameState(self)
+                    }
                 }
             }
+            Some(()) = self.network_change_rx.recv() => {
+                tracing::info!("Reconnecting after the default route changed");
+                shared_state.reconnecting_after_network_change = true;
+                self.reconnect(shared_state)
+            }
+            Some(()) = wait_until(self.session_warning_at) => {
+                self.session_warning_at = None;
+                let remaining_secs = self.session_expires_at
+                    .map(|expires_at| expires_at.saturating_duration_since(Instant::now()).as_secs())
+                    .unwrap_or_default();
+                let event = MixnetEvent::Connection(ConnectionEvent::SessionExpiring { remaining_secs });
+                if let Err(e) = shared_state.mixnet_event_sender.send(event) {
+                    tracing::error!("Failed to send event: {}", e);
+                }
+                NextTunnelState::SameState(self)
+            }
+            Some(()) = wait_until(self.session_expires_at) => {
+                tracing::info!("Disconnecting because the maximum session duration was reached");
+                NextTunnelState::NewState(DisconnectingState::enter(PrivateActionAfterDisconnect::Nothing { reason: DisconnectReason::SessionExpired }, self.monitor_handle, shared_state))
+            }
             Some(monitor_event) = self.monitor_event_receiver.recv() => {
                 match monitor_event {
                     TunnelMonitorEvent::Down(reason) => {
@@ -68,6 +255,14 @@ impl TunnelStateHandler for ConnectedState {
 
                         NextTunnelState::NewState(DisconnectingState::enter(after_disconnect, self.monitor_handle, shared_state))
                     }
+                    TunnelMonitorEvent::BandwidthExhausted => {
+                        tracing::info!("Disconnecting because the bandwidth ran out");
+                        NextTunnelState::NewState(DisconnectingState::enter(PrivateActionAfterDisconnect::Nothing { reason: DisconnectReason::BandwidthExhausted }, self.monitor_handle, shared_state))
+                    }
+                    TunnelMonitorEvent::IdleTimeout => {
+                        tracing::info!("Disconnecting because the tunnel was idle");
+                        NextTunnelState::NewState(DisconnectingState::enter(PrivateActionAfterDisconnect::Nothing { reason: DisconnectReason::IdleTimeout }, self.monitor_handle, shared_state))
+                    }
                     _ => {
                         NextTunnelState::SameState(self)
                     }
@@ -1,17 +1,17 @@
 // Copyright 2023 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use tokio::sync::mpsc;
+use tokio::{sync::mpsc, time::Instant};
 use tokio_util::sync::CancellationToken;
 
 use crate::tunnel_state_machine::{
-    states::{ConnectedState, DisconnectingState},
+    states::{wait_until, ConnectedState, DisconnectingState},
     tunnel::SelectedGateways,
     tunnel_monitor::{
         TunnelMonitor, TunnelMonitorEvent, TunnelMonitorEventReceiver, TunnelMonitorHandle,
     },
-    NextTunnelState, PrivateActionAfterDisconnect, PrivateTunnelState, SharedState, TunnelCommand,
-    TunnelStateHandler,
+    ConnectStep, DisconnectReason, ErrorStateReason, NextTunnelState, PrivateActionAfterDisconnect,
+    PrivateTunnelState, SharedState, TunnelCommand, TunnelStateHandler,
 };
 
 pub struct ConnectingState {
@@ -19,6 +19,10 @@ pub struct ConnectingState {
     monitor_event_receiver: TunnelMonitorEventReceiver,
     retry_attempt: u32,
     selected_gateways: Option<SelectedGateways>,
+    /// When to give up on this attempt, if there is a connect deadline.
+    deadline: Option<Instant>,
+    /// What the tunnel monitor is busy with, to tell where an attempt stalled.
+    pending_step: ConnectStep,
 }
 
 impl ConnectingState {
@@ -27,6 +31,16 @@ impl ConnectingState {
         selected_gateways: Option<SelectedGateways>,
         shared_state: &mut SharedState,
     ) -> (Box<dyn TunnelStateHandler>, PrivateTunnelState) {
+        // The tunnel monitor waits before retrying, which doesn't count towards the deadline.
+        let retry_delay = shared_state
+            .tunnel_settings
+            .connect_retry_options
+            .wait_delay(retry_attempt);
+        let deadline = shared_state
+            .tunnel_settings
+            .effective_connect_deadline()
+            .map(|connect_deadline| Instant::now() + retry_delay + connect_deadline);
+
         let (monitor_event_sender, monitor_event_receiver) = mpsc::unbounded_channel();
         let monitor_handle = TunnelMonitor::start(
             retry_attempt,
@@ -37,7 +51,6 @@ impl ConnectingState {
             shared_state.route_handler.clone(),
             #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
             shared_state.dns_handler.clone(),
-            #[cfg(any(target_os = "ios", target_os = "android"))]
             shared_state.tun_provider.clone(),
             shared_state.nym_config.clone(),
             shared_state.tunnel_settings.clone(),
@@ -49,8 +62,11 @@ impl ConnectingState {
                 monitor_event_receiver,
                 retry_attempt,
                 selected_gateways,
+                deadline,
+                pending_step: ConnectStep::Starting,
             }),
             PrivateTunnelState::Connecting {
+                retry_attempt,
                 connection_data: None,
             },
         )
@@ -68,7 +84,7 @@ impl TunnelStateHandler for ConnectingState {
         tokio::select! {
             _ = shutdown_token.cancelled() => {
                 NextTunnelState::NewState(DisconnectingState::enter(
-                    PrivateActionAfterDisconnect::Nothing,
+                    PrivateActionAfterDisconnect::Nothing { reason: DisconnectReason::UserRequested },
                     self.monitor_handle,
                     shared_state,
                 ))
@@ -76,13 +92,17 @@ impl TunnelStateHandler for ConnectingState {
            Some(monitor_event) = self.monitor_event_receiver.recv() => {
             match monitor_event {
                 TunnelMonitorEvent::InitializingClient => {
+                    self.pending_step = ConnectStep::SelectingGateways;
                     NextTunnelState::SameState(self)
                 }
                 TunnelMonitorEvent::EstablishingTunnel(conn_data) => {
-                    NextTunnelState::NewState((self, PrivateTunnelState::Connecting { connection_data: Some(*conn_data) }))
+                    self.pending_step = ConnectStep::EstablishingTunnel;
+                    let retry_attempt = self.retry_attempt;
+                    NextTunnelState::NewState((self, PrivateTunnelState::Connecting { retry_attempt, connection_data: Some(*conn_data) }))
                 }
                 TunnelMonitorEvent::SelectedGateways(new_gateways) => {
                     self.selected_gateways = Some(*new_gateways);
+                    self.pending_step = ConnectStep::ConnectingTunnel;
                     NextTunnelState::SameState(self)
                 }
                 TunnelMonitorEvent::Up(conn_data) => {
@@ -91,6 +111,22 @@ impl TunnelStateHandler for ConnectingState {
                 TunnelMonitorEvent::Down(reason) => {
                     if let Some(reason) = reason {
                         NextTunnelState::NewState(DisconnectingState::enter(PrivateActionAfterDisconnect::Error(reason), self.monitor_handle, shared_state))
+                    } else if shared_state.tunnel_settings.connect_retry_options.is_exhausted(self.retry_attempt) {
+                        tracing::error!("Giving up after {} connection attempts", self.retry_attempt.saturating_add(1));
+                        let after_disconnect = if shared_state.reconnecting_after_network_change {
+                            PrivateActionAfterDisconnect::Nothing {
+                                reason: DisconnectReason::NetworkChange,
+                            }
+                        } else {
+                            PrivateActionAfterDisconnect::Error(
+                                ErrorStateReason::EstablishMixnetConnection,
+                            )
+                        };
+                        NextTunnelState::NewState(DisconnectingState::enter(
+                            after_disconnect,
+                            self.monitor_handle,
+                            shared_state,
+                        ))
                     } else {
                         #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
                         shared_state.route_handler.remove_routes().await;
@@ -98,18 +134,50 @@ impl TunnelStateHandler for ConnectingState {
                         NextTunnelState::NewState(ConnectingState::enter( self.retry_attempt.saturating_add(1), self.selected_gateways, shared_state))
                     }
                 }
+                TunnelMonitorEvent::BandwidthExhausted => {
+                    NextTunnelState::NewState(DisconnectingState::enter(
+                        PrivateActionAfterDisconnect::Nothing { reason: DisconnectReason::BandwidthExhausted },
+                        self.monitor_handle,
+                        shared_state,
+                    ))
+                }
+                TunnelMonitorEvent::IdleTimeout => {
+                    // The idle timer only starts once the tunnel is up.
+                    tracing::warn!("Disconnecting because the tunnel was idle before it was up");
+                    NextTunnelState::NewState(DisconnectingState::enter(
+                        PrivateActionAfterDisconnect::Nothing { reason: DisconnectReason::IdleTimeout },
+                        self.monitor_handle,
+                        shared_state,
+                    ))
+                }
             }
            }
+            Some(()) = wait_until(self.deadline) => {
+                tracing::error!(
+                    "Giving up connecting, the connect deadline passed while {}",
+                    self.pending_step
+                );
+                NextTunnelState::NewState(DisconnectingState::enter(
+                    PrivateActionAfterDisconnect::Error(ErrorStateReason::ConnectDeadlineExceeded {
+                        pending_step: self.pending_step,
+                    }),
+                    self.monitor_handle,
+                    shared_state,
+                ))
+            }
             Some(command) = command_rx.recv() => {
                 match command {
                     TunnelCommand::Connect => NextTunnelState::SameState(self),
                     TunnelCommand::Disconnect => {
                         NextTunnelState::NewState(DisconnectingState::enter(
-                            PrivateActionAfterDisconnect::Nothing,
+                            PrivateActionAfterDisconnect::Nothing { reason: DisconnectReason::UserRequested },
                             self.monitor_handle,
                             shared_state,
                         ))
                     },
+                    TunnelCommand::ForceDisconnect => {
+                        NextTunnelState::NewState(DisconnectingState::enter_forced(self.monitor_handle, shared_state))
+                    }
                     TunnelCommand::SetTunnelSettings(tunnel_settings) => {
                         if shared_state.tunnel_settings == tunnel_settings {
                             NextTunnelState::SameState(self)
@@ -122,6 +190,37 @@ impl TunnelStateHandler for ConnectingState {
                             ))
                         }
                     }
+                    TunnelCommand::SetTunnelType(tunnel_type) => {
+                        if shared_state.tunnel_settings.tunnel_type == tunnel_type {
+                            NextTunnelState::SameState(self)
+                        } else {
+                            tracing::info!("Switching tunnel type to {:?}", tunnel_type);
+                            shared_state.tunnel_settings.tunnel_type = tunnel_type;
+                            NextTunnelState::NewState(DisconnectingState::enter(
+                                PrivateActionAfterDisconnect::Reconnect { retry_attempt: 0 },
+                                self.monitor_handle,
+                                shared_state,
+                            ))
+                        }
+                    }
+                    TunnelCommand::RotateWireguardKeys => NextTunnelState::SameState(self),
+                    TunnelCommand::ExportWireguardConfig { reply_tx, .. } => {
+                        let _ = reply_tx.send(None);
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::RunConnectivityTest(reply_tx) => {
+                        let _ = reply_tx.send(None);
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::GetExitPublicIp(reply_tx) => {
+                        let _ = reply_tx.send(None);
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::Pause | TunnelCommand::Resume | TunnelCommand::ResetIdleTimer => NextTunnelState::SameState(self),
+                    TunnelCommand::QueryState(reply_tx) => {
+                        shared_state.reply_with_current_state(reply_tx);
+                        NextTunnelState::SameState(self)
+                    }
                 }
             }
             else => NextTunnelState::Finished
@@ -5,15 +5,15 @@ use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 
 use crate::tunnel_state_machine::{
-    states::ConnectingState, NextTunnelState, PrivateTunnelState, SharedState, TunnelCommand,
-    TunnelStateHandler,
+    states::ConnectingState, DisconnectReason, NextTunnelState, PrivateTunnelState, SharedState,
+    TunnelCommand, TunnelStateHandler,
 };
 
 pub struct DisconnectedState;
 
 impl DisconnectedState {
-    pub fn enter() -> (Box<dyn TunnelStateHandler>, PrivateTunnelState) {
-        (Box::new(Self), PrivateTunnelState::Disconnected)
+    pub fn enter(reason: DisconnectReason) -> (Box<dyn TunnelStateHandler>, PrivateTunnelState) {
+        (Box::new(Self), PrivateTunnelState::Disconnected { reason })
     }
 }
 
@@ -34,11 +34,33 @@ impl TunnelStateHandler for DisconnectedState {
                     TunnelCommand::Connect => {
                         NextTunnelState::NewState(ConnectingState::enter(0, None, shared_state))
                     },
-                    TunnelCommand::Disconnect => NextTunnelState::SameState(self),
+                    TunnelCommand::Disconnect | TunnelCommand::ForceDisconnect => NextTunnelState::SameState(self),
                     TunnelCommand::SetTunnelSettings(tunnel_settings) => {
                         shared_state.tunnel_settings = tunnel_settings;
                         NextTunnelState::SameState(self)
                     }
+                    TunnelCommand::SetTunnelType(tunnel_type) => {
+                        shared_state.tunnel_settings.tunnel_type = tunnel_type;
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::RotateWireguardKeys => NextTunnelState::SameState(self),
+                    TunnelCommand::ExportWireguardConfig { reply_tx, .. } => {
+                        let _ = reply_tx.send(None);
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::RunConnectivityTest(reply_tx) => {
+                        let _ = reply_tx.send(None);
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::GetExitPublicIp(reply_tx) => {
+                        let _ = reply_tx.send(None);
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::Pause | TunnelCommand::Resume | TunnelCommand::ResetIdleTimer => NextTunnelState::SameState(self),
+                    TunnelCommand::QueryState(reply_tx) => {
+                        shared_state.reply_with_current_state(reply_tx);
+                        NextTunnelState::SameState(self)
+                    }
                 }
             }
             else => NextTunnelState::Finished
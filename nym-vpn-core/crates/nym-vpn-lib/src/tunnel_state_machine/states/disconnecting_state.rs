@@ -4,21 +4,22 @@
 use futures::future::{BoxFuture, Fuse, FutureExt};
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
-use tun::AsyncDevice;
 
 use crate::tunnel_state_machine::{
     states::{ConnectingState, DisconnectedState, ErrorState},
     tunnel_monitor::TunnelMonitorHandle,
-    NextTunnelState, PrivateActionAfterDisconnect, PrivateTunnelState, SharedState, TunnelCommand,
-    TunnelStateHandler,
+    DisconnectReason, NextTunnelState, PrivateActionAfterDisconnect, PrivateTunnelState,
+    SharedState, TunDevice, TunnelCommand, TunnelStateHandler,
 };
 
-type WaitHandle = BoxFuture<'static, Vec<AsyncDevice>>;
+type WaitHandle = BoxFuture<'static, Vec<Box<dyn TunDevice>>>;
 
 pub struct DisconnectingState {
     after_disconnect: PrivateActionAfterDisconnect,
     retry_attempt: u32,
     wait_handle: Fuse<WaitHandle>,
+    /// Leave without waiting for the tunnel to shut down.
+    force: bool,
 }
 
 impl DisconnectingState {
@@ -26,6 +27,32 @@ impl DisconnectingState {
         after_disconnect: PrivateActionAfterDisconnect,
         monitor_handle: TunnelMonitorHandle,
         shared_state: &mut SharedState,
+    ) -> (Box<dyn TunnelStateHandler>, PrivateTunnelState) {
+        Self::enter_inner(after_disconnect, false, monitor_handle, shared_state)
+    }
+
+    /// Enter the disconnecting state and move on to the disconnected state right away, once
+    /// routing and DNS are reset. The tunnel is left shutting down in the background. See
+    /// [`TunnelCommand::ForceDisconnect`].
+    pub fn enter_forced(
+        monitor_handle: TunnelMonitorHandle,
+        shared_state: &mut SharedState,
+    ) -> (Box<dyn TunnelStateHandler>, PrivateTunnelState) {
+        Self::enter_inner(
+            PrivateActionAfterDisconnect::Nothing {
+                reason: DisconnectReason::UserRequested,
+            },
+            true,
+            monitor_handle,
+            shared_state,
+        )
+    }
+
+    fn enter_inner(
+        after_disconnect: PrivateActionAfterDisconnect,
+        force: bool,
+        monitor_handle: TunnelMonitorHandle,
+        shared_state: &mut SharedState,
     ) -> (Box<dyn TunnelStateHandler>, PrivateTunnelState) {
         // It's safe to abort status listener as it's stateless.
         if let Some(status_listener_handle) = shared_state.status_listener_handle.take() {
@@ -37,6 +64,8 @@ impl DisconnectingState {
             if let PrivateActionAfterDisconnect::Reconnect { retry_attempt } = &after_disconnect {
                 *retry_attempt
             } else {
+                // Any other disconnect ends the reconnect after a network change
+                shared_state.reconnecting_after_network_change = false;
                 0
             };
 
@@ -45,12 +74,23 @@ impl DisconnectingState {
                 after_disconnect: after_disconnect.clone(),
                 retry_attempt,
                 wait_handle: monitor_handle.wait().boxed().fuse(),
+                force,
             }),
             PrivateTunnelState::Disconnecting { after_disconnect },
         )
     }
 
-    async fn on_tunnel_exit(mut tun_devices: Vec<AsyncDevice>, _shared_state: &mut SharedState) {
+    async fn on_tunnel_exit(
+        mut tun_devices: Vec<Box<dyn TunDevice>>,
+        shared_state: &mut SharedState,
+    ) {
+        Self::reset_network(shared_state).await;
+
+        tracing::info!("Closing {} tunnel device(s).", tun_devices.len());
+        tun_devices.clear();
+    }
+
+    async fn reset_network(_shared_state: &mut SharedState) {
         #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
         _shared_state.route_handler.remove_routes().await;
 
@@ -66,11 +106,27 @@ impl DisconnectingState {
         #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
         _shared_state.route_handler.remove_routes().await;
 
-        tracing::info!("Closing {} tunnel device(s).", tun_devices.len());
-        tun_devices.clear();
-
         // todo: reset firewall
     }
+
+    // The final flush of the mixnet client, e.g. the remaining cover traffic, may not complete if
+    // the app is killed before the tunnel is done shutting down.
+    async fn force_exit(self: Box<Self>, shared_state: &mut SharedState) -> NextTunnelState {
+        tracing::info!("Forcing disconnect without waiting for the tunnel to shut down");
+        Self::reset_network(shared_state).await;
+
+        // Keep waiting in the background, since it's unsafe to drop the task manager.
+        let wait_handle = self.wait_handle;
+        tokio::spawn(async move {
+            let tun_devices = wait_handle.await;
+            tracing::info!(
+                "Tunnel exited after forced disconnect, closing {} tunnel device(s).",
+                tun_devices.len()
+            );
+        });
+
+        NextTunnelState::NewState(DisconnectedState::enter(DisconnectReason::UserRequested))
+    }
 }
 
 #[async_trait::async_trait]
@@ -81,19 +137,23 @@ impl TunnelStateHandler for DisconnectingState {
         command_rx: &'async_trait mut mpsc::UnboundedReceiver<TunnelCommand>,
         shared_state: &'async_trait mut SharedState,
     ) -> NextTunnelState {
+        if self.force {
+            return self.force_exit(shared_state).await;
+        }
+
         tokio::select! {
             _ = shutdown_token.cancelled() => {
                 // Wait for tunnel to exit anyway because it's unsafe to drop the task manager.
                 let result = self.wait_handle.await;
                 Self::on_tunnel_exit(result, shared_state).await;
 
-                NextTunnelState::NewState(DisconnectedState::enter())
+                NextTunnelState::NewState(DisconnectedState::enter(DisconnectReason::UserRequested))
             }
             result = (&mut self.wait_handle) => {
                 Self::on_tunnel_exit(result, shared_state).await;
 
                 match self.after_disconnect {
-                    PrivateActionAfterDisconnect::Nothing => NextTunnelState::NewState(DisconnectedState::enter()),
+                    PrivateActionAfterDisconnect::Nothing { reason } => NextTunnelState::NewState(DisconnectedState::enter(reason)),
                     PrivateActionAfterDisconnect::Error(reason) => {
                         NextTunnelState::NewState(ErrorState::enter(reason))
                     },
@@ -108,11 +168,31 @@ impl TunnelStateHandler for DisconnectingState {
                         self.after_disconnect = PrivateActionAfterDisconnect::Reconnect { retry_attempt: self.retry_attempt };
                     },
                     TunnelCommand::Disconnect => {
-                        self.after_disconnect = PrivateActionAfterDisconnect::Nothing;
+                        self.after_disconnect = PrivateActionAfterDisconnect::Nothing { reason: DisconnectReason::UserRequested };
+                    }
+                    TunnelCommand::ForceDisconnect => {
+                        self.force = true;
                     }
                     TunnelCommand::SetTunnelSettings(tunnel_settings) => {
                         shared_state.tunnel_settings = tunnel_settings;
                     }
+                    TunnelCommand::SetTunnelType(tunnel_type) => {
+                        shared_state.tunnel_settings.tunnel_type = tunnel_type;
+                    }
+                    TunnelCommand::RotateWireguardKeys => {}
+                    TunnelCommand::ExportWireguardConfig { reply_tx, .. } => {
+                        let _ = reply_tx.send(None);
+                    }
+                    TunnelCommand::RunConnectivityTest(reply_tx) => {
+                        let _ = reply_tx.send(None);
+                    }
+                    TunnelCommand::GetExitPublicIp(reply_tx) => {
+                        let _ = reply_tx.send(None);
+                    }
+                    TunnelCommand::Pause | TunnelCommand::Resume | TunnelCommand::ResetIdleTimer => {}
+                    TunnelCommand::QueryState(reply_tx) => {
+                        shared_state.reply_with_current_state(reply_tx);
+                    }
                 }
                 NextTunnelState::SameState(self)
             }
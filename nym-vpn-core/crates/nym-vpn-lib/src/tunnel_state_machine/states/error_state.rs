@@ -6,15 +6,17 @@ use tokio_util::sync::CancellationToken;
 
 use crate::tunnel_state_machine::{
     states::{ConnectingState, DisconnectedState},
-    ErrorStateReason, NextTunnelState, PrivateTunnelState, SharedState, TunnelCommand,
-    TunnelStateHandler,
+    DisconnectReason, ErrorStateReason, NextTunnelState, PrivateTunnelState, SharedState,
+    TunnelCommand, TunnelStateHandler,
 };
 
-pub struct ErrorState;
+pub struct ErrorState {
+    reason: ErrorStateReason,
+}
 
 impl ErrorState {
     pub fn enter(reason: ErrorStateReason) -> (Box<dyn TunnelStateHandler>, PrivateTunnelState) {
-        (Box::new(Self), PrivateTunnelState::Error(reason))
+        (Box::new(Self { reason }), PrivateTunnelState::Error(reason))
     }
 }
 
@@ -35,11 +37,34 @@ impl TunnelStateHandler for ErrorState {
                     TunnelCommand::Connect => {
                         NextTunnelState::NewState(ConnectingState::enter(0, None, shared_state))
                     },
-                    TunnelCommand::Disconnect => NextTunnelState::NewState(DisconnectedState::enter()),
+                    // Keep reporting the error that brought the tunnel down.
+                    TunnelCommand::Disconnect | TunnelCommand::ForceDisconnect => NextTunnelState::NewState(DisconnectedState::enter(DisconnectReason::Error(self.reason))),
                     TunnelCommand::SetTunnelSettings(tunnel_settings) => {
                         shared_state.tunnel_settings = tunnel_settings;
                         NextTunnelState::SameState(self)
                     }
+                    TunnelCommand::SetTunnelType(tunnel_type) => {
+                        shared_state.tunnel_settings.tunnel_type = tunnel_type;
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::RotateWireguardKeys => NextTunnelState::SameState(self),
+                    TunnelCommand::ExportWireguardConfig { reply_tx, .. } => {
+                        let _ = reply_tx.send(None);
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::RunConnectivityTest(reply_tx) => {
+                        let _ = reply_tx.send(None);
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::GetExitPublicIp(reply_tx) => {
+                        let _ = reply_tx.send(None);
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::Pause | TunnelCommand::Resume | TunnelCommand::ResetIdleTimer => NextTunnelState::SameState(self),
+                    TunnelCommand::QueryState(reply_tx) => {
+                        shared_state.reply_with_current_state(reply_tx);
+                        NextTunnelState::SameState(self)
+                    }
                 }
             }
             else => NextTunnelState::Finished
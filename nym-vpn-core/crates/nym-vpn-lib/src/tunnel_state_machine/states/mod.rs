@@ -6,9 +6,20 @@ mod connecting_state;
 mod disconnected_state;
 mod disconnecting_state;
 mod error_state;
+mod paused_state;
 
 pub use connected_state::ConnectedState;
 pub use connecting_state::ConnectingState;
 pub use disconnected_state::DisconnectedState;
 pub use disconnecting_state::DisconnectingState;
 pub use error_state::ErrorState;
+pub use paused_state::PausedState;
+
+use tokio::time::Instant;
+
+// Resolves at the deadline, or returns `None` right away without one so that the select branch is
+// disabled.
+async fn wait_until(deadline: Option<Instant>) -> Option<()> {
+    tokio::time::sleep_until(deadline?).await;
+    Some(())
+}
@@ -0,0 +1,216 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::time::Duration;
+
+use tokio::{sync::mpsc, time::Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::tunnel_state_machine::{
+    states::{ConnectedState, DisconnectingState},
+    tunnel_monitor::TunnelMonitorEvent,
+    DisconnectReason, NextTunnelState, PrivateActionAfterDisconnect, PrivateTunnelState,
+    SharedState, TunnelCommand, TunnelStateHandler,
+};
+
+/// How long the tunnel can stay paused. Past that the mixnet client is considered stale and the
+/// tunnel is reconnected from scratch.
+const MAX_PAUSE_DURATION: Duration = Duration::from_secs(5 * 60);
+
+pub struct PausedState {
+    connected_state: Box<ConnectedState>,
+    deadline: PauseDeadline,
+}
+
+impl PausedState {
+    pub fn enter(
+        connected_state: Box<ConnectedState>,
+    ) -> (Box<dyn TunnelStateHandler>, PrivateTunnelState) {
+        let connection_data = connected_state.connection_data.clone();
+        let deadline = PauseDeadline::new(
+            Instant::now() + MAX_PAUSE_DURATION,
+            connected_state.session_expires_at,
+        );
+        (
+            Box::new(Self {
+                connected_state,
+                deadline,
+            }),
+            PrivateTunnelState::Paused { connection_data },
+        )
+    }
+}
+
+/// What ends the pause, unless a command or the tunnel going down comes first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PauseDeadline {
+    /// The maximum session duration is reached, which disconnects the tunnel like it would when
+    /// connected.
+    SessionExpired(Instant),
+
+    /// The tunnel was paused for too long and is reconnected.
+    Stale(Instant),
+}
+
+impl PauseDeadline {
+    fn new(stale_at: Instant, session_expires_at: Option<Instant>) -> Self {
+        match session_expires_at {
+            Some(expires_at) if expires_at <= stale_at => Self::SessionExpired(expires_at),
+            _ => Self::Stale(stale_at),
+        }
+    }
+
+    fn at(&self) -> Instant {
+        match self {
+            Self::SessionExpired(at) | Self::Stale(at) => *at,
+        }
+    }
+
+    fn after_disconnect(&self) -> PrivateActionAfterDisconnect {
+        match self {
+            Self::SessionExpired(_) => {
+                tracing::info!("Disconnecting because the maximum session duration was reached");
+                PrivateActionAfterDisconnect::Nothing {
+                    reason: DisconnectReason::SessionExpired,
+                }
+            }
+            Self::Stale(_) => {
+                tracing::info!(
+                    "Reconnecting after being paused for {}s",
+                    MAX_PAUSE_DURATION.as_secs()
+                );
+                PrivateActionAfterDisconnect::Reconnect { retry_attempt: 0 }
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl TunnelStateHandler for PausedState {
+    async fn handle_event(
+        mut self: Box<Self>,
+        shutdown_token: &CancellationToken,
+        command_rx: &'async_trait mut mpsc::UnboundedReceiver<TunnelCommand>,
+        shared_state: &'async_trait mut SharedState,
+    ) -> NextTunnelState {
+        tokio::select! {
+            _ = shutdown_token.cancelled() => {
+                NextTunnelState::NewState(DisconnectingState::enter(PrivateActionAfterDisconnect::Nothing { reason: DisconnectReason::UserRequested }, self.connected_state.monitor_handle, shared_state))
+            }
+            Some(command) = command_rx.recv() => {
+                match command {
+                    // The idle timer restarts once resumed.
+                    TunnelCommand::Connect | TunnelCommand::Pause | TunnelCommand::ResetIdleTimer => NextTunnelState::SameState(self),
+                    TunnelCommand::Resume => NextTunnelState::NewState(self.connected_state.resume(shared_state)),
+                    TunnelCommand::Disconnect => {
+                        NextTunnelState::NewState(DisconnectingState::enter(PrivateActionAfterDisconnect::Nothing { reason: DisconnectReason::UserRequested }, self.connected_state.monitor_handle, shared_state))
+                    },
+                    TunnelCommand::ForceDisconnect => {
+                        NextTunnelState::NewState(DisconnectingState::enter_forced(self.connected_state.monitor_handle, shared_state))
+                    }
+                    TunnelCommand::SetTunnelSettings(tunnel_settings) => {
+                        if shared_state.tunnel_settings == tunnel_settings {
+                            NextTunnelState::SameState(self)
+                        } else {
+                            shared_state.tunnel_settings = tunnel_settings;
+                            NextTunnelState::NewState(DisconnectingState::enter(PrivateActionAfterDisconnect::Reconnect { retry_attempt: 0 }, self.connected_state.monitor_handle, shared_state))
+                        }
+                    }
+                    TunnelCommand::SetTunnelType(tunnel_type) => {
+                        if shared_state.tunnel_settings.tunnel_type == tunnel_type {
+                            NextTunnelState::SameState(self)
+                        } else {
+                            tracing::info!("Switching tunnel type to {:?}", tunnel_type);
+                            shared_state.tunnel_settings.tunnel_type = tunnel_type;
+                            NextTunnelState::NewState(DisconnectingState::enter(PrivateActionAfterDisconnect::Reconnect { retry_attempt: 0 }, self.connected_state.monitor_handle, shared_state))
+                        }
+                    }
+                    // Only mixnet tunnels are paused.
+                    TunnelCommand::RotateWireguardKeys => NextTunnelState::SameState(self),
+                    TunnelCommand::ExportWireguardConfig { reply_tx, .. } => {
+                        let _ = reply_tx.send(None);
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::RunConnectivityTest(reply_tx) => {
+                        let _ = reply_tx.send(None);
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::GetExitPublicIp(reply_tx) => {
+                        let _ = reply_tx.send(None);
+                        NextTunnelState::SameState(self)
+                    }
+                    TunnelCommand::QueryState(reply_tx) => {
+                        shared_state.reply_with_current_state(reply_tx);
+                        NextTunnelState::SameState(self)
+                    }
+                }
+            }
+            _ = tokio::time::sleep_until(self.deadline.at()) => {
+                let after_disconnect = self.deadline.after_disconnect();
+                let monitor_handle = self.connected_state.monitor_handle;
+                NextTunnelState::NewState(
+                    DisconnectingState::enter(after_disconnect, monitor_handle, shared_state)
+                )
+            }
+            Some(monitor_event) = self.connected_state.monitor_event_receiver.recv() => {
+                match monitor_event {
+                    TunnelMonitorEvent::Down(reason) => {
+                        let after_disconnect = reason.map(PrivateActionAfterDisconnect::Error).unwrap_or(PrivateActionAfterDisconnect::Reconnect { retry_attempt: 0 });
+
+                        NextTunnelState::NewState(DisconnectingState::enter(after_disconnect, self.connected_state.monitor_handle, shared_state))
+                    }
+                    TunnelMonitorEvent::BandwidthExhausted => {
+                        tracing::info!("Disconnecting because the bandwidth ran out");
+                        NextTunnelState::NewState(DisconnectingState::enter(PrivateActionAfterDisconnect::Nothing { reason: DisconnectReason::BandwidthExhausted }, self.connected_state.monitor_handle, shared_state))
+                    }
+                    _ => {
+                        NextTunnelState::SameState(self)
+                    }
+                }
+            }
+            else => NextTunnelState::Finished
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pause_ends_when_it_goes_stale_without_a_session_limit() {
+        let stale_at = Instant::now() + MAX_PAUSE_DURATION;
+        assert_eq!(
+            PauseDeadline::new(stale_at, None),
+            PauseDeadline::Stale(stale_at)
+        );
+    }
+
+    #[test]
+    fn session_expiring_during_the_pause_ends_it_first() {
+        let stale_at = Instant::now() + MAX_PAUSE_DURATION;
+        let expires_at = stale_at - Duration::from_secs(60);
+        let deadline = PauseDeadline::new(stale_at, Some(expires_at));
+        assert_eq!(deadline, PauseDeadline::SessionExpired(expires_at));
+        assert_eq!(deadline.at(), expires_at);
+    }
+
+    #[test]
+    fn session_expiring_after_the_pause_goes_stale_is_left_for_later() {
+        let stale_at = Instant::now() + MAX_PAUSE_DURATION;
+        let expires_at = stale_at + Duration::from_secs(60);
+        assert_eq!(
+            PauseDeadline::new(stale_at, Some(expires_at)),
+            PauseDeadline::Stale(stale_at)
+        );
+    }
+
+    #[test]
+    fn session_already_expired_ends_the_pause_right_away() {
+        let now = Instant::now();
+        let expires_at = now - Duration::from_secs(1);
+        let deadline = PauseDeadline::new(now + MAX_PAUSE_DURATION, Some(expires_at));
+        assert_eq!(deadline, PauseDeadline::SessionExpired(expires_at));
+        assert!(deadline.at() <= now);
+    }
+}
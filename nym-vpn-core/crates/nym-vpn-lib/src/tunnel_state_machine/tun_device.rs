@@ -0,0 +1,149 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::Framed;
+use tun::{AsyncDevice, Device, TunPacketCodec};
+
+/// The device the mixnet tunnel reads packets from and writes them to.
+///
+/// The system tun device is the default. Anything that carries whole IP packets over a byte
+/// stream can stand in for it, e.g. to capture the packets in tests without creating a device.
+pub trait TunDevice: AsyncRead + AsyncWrite + Send + Unpin {
+    /// Name of the device, which the routes and DNS are set up against.
+    fn name(&self) -> tun::Result<String>;
+
+    /// MTU of the device, packets larger than this are dropped.
+    fn mtu(&self) -> tun::Result<i32>;
+
+    /// Frames the device into a stream and sink of packets.
+    fn into_framed(self: Box<Self>) -> Framed<Box<dyn TunDevice>, TunPacketCodec>;
+}
+
+impl TunDevice for AsyncDevice {
+    fn name(&self) -> tun::Result<String> {
+        self.get_ref().name()
+    }
+
+    fn mtu(&self) -> tun::Result<i32> {
+        self.get_ref().mtu()
+    }
+
+    fn into_framed(self: Box<Self>) -> Framed<Box<dyn TunDevice>, TunPacketCodec> {
+        // The codec knows whether the platform prefixes packets with their information header.
+        let parts = AsyncDevice::into_framed(*self).into_parts();
+        Framed::new(Box::new(parts.io) as Box<dyn TunDevice>, parts.codec)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::{
+        io,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use tokio::io::{DuplexStream, ReadBuf};
+
+    use super::*;
+
+    /// One end of an in-memory pipe posing as a tun device, the other end sees the raw packets.
+    pub(crate) struct MockTunDevice {
+        io: DuplexStream,
+        // `None` for a device that can't report its MTU
+        mtu: Option<i32>,
+    }
+
+    impl MockTunDevice {
+        pub(crate) fn new(mtu: Option<i32>) -> (Self, DuplexStream) {
+            let (io, peer) = tokio::io::duplex(64 * 1024);
+            (Self { io, mtu }, peer)
+        }
+    }
+
+    impl TunDevice for MockTunDevice {
+        fn name(&self) -> tun::Result<String> {
+            Ok("mock0".to_string())
+        }
+
+        fn mtu(&self) -> tun::Result<i32> {
+            self.mtu.ok_or(tun::Error::NotImplemented)
+        }
+
+        fn into_framed(self: Box<Self>) -> Framed<Box<dyn TunDevice>, TunPacketCodec> {
+            let mtu = self.mtu.unwrap_or(1500);
+            Framed::new(self as Box<dyn TunDevice>, TunPacketCodec::new(false, mtu))
+        }
+    }
+
+    impl AsyncRead for MockTunDevice {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.io).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for MockTunDevice {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.io).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.io).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.io).poll_shutdown(cx)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tun::TunPacket;
+
+    use super::{mock::MockTunDevice, *};
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn framed_device_carries_whole_packets() {
+        block_on(async {
+            let (device, mut peer) = MockTunDevice::new(Some(1280));
+            let device: Box<dyn TunDevice> = Box::new(device);
+            assert_eq!(device.name().unwrap(), "mock0");
+            let mut framed = device.into_framed();
+
+            framed
+                .send(TunPacket::new(vec![0x45, 1, 2, 3]))
+                .await
+                .unwrap();
+            let mut buf = [0; 16];
+            let len = peer.read(&mut buf).await.unwrap();
+            assert_eq!(&buf[..len], &[0x45, 1, 2, 3]);
+
+            peer.write_all(&[0x60, 4, 5]).await.unwrap();
+            let packet = framed.next().await.unwrap().unwrap();
+            assert_eq!(packet.get_bytes(), &[0x60, 4, 5]);
+
+            // The device is handed back once the tunnel is done with it
+            let device = framed.into_inner();
+            assert_eq!(device.mtu().unwrap(), 1280);
+        });
+    }
+}
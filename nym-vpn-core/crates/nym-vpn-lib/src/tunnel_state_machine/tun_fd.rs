@@ -0,0 +1,130 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use tokio::sync::mpsc;
+
+use super::{ConnectionEvent, MixnetEvent};
+
+static OPEN_TUN_FDS: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of tun file descriptors handed over by the OS that are still open. It goes
+/// back to where it was once a tunnel is torn down, so it can be checked around connect and
+/// disconnect cycles to catch leaks.
+pub fn open_tun_fds() -> usize {
+    OPEN_TUN_FDS.load(Ordering::SeqCst)
+}
+
+/// The file descriptor of the tun device handed over by the OS, held until the tunnel is torn
+/// down and closed when dropped.
+///
+/// The tun device is given its own duplicate with [`TunFd::dup`], which it closes when dropped,
+/// so the original is never closed twice nor left open when creating the device fails.
+#[derive(Debug)]
+pub struct TunFd {
+    fd: OwnedFd,
+    event_sender: mpsc::UnboundedSender<MixnetEvent>,
+}
+
+impl TunFd {
+    /// Takes ownership of the fd and sends [`ConnectionEvent::TunFdEstablished`].
+    pub fn new(fd: OwnedFd, event_sender: mpsc::UnboundedSender<MixnetEvent>) -> Self {
+        OPEN_TUN_FDS.fetch_add(1, Ordering::SeqCst);
+        let tun_fd = Self { fd, event_sender };
+        tun_fd.send_event(ConnectionEvent::TunFdEstablished {
+            fd: tun_fd.as_raw_fd(),
+        });
+        tun_fd
+    }
+
+    /// Takes ownership of a raw fd, e.g. one returned by the Android VPN service.
+    ///
+    /// # Safety
+    ///
+    /// The fd must be open and must not be closed by anyone else.
+    pub unsafe fn from_raw_fd(
+        raw_fd: RawFd,
+        event_sender: mpsc::UnboundedSender<MixnetEvent>,
+    ) -> Self {
+        Self::new(OwnedFd::from_raw_fd(raw_fd), event_sender)
+    }
+
+    /// Duplicates the fd for the tun device. On error the original is left untouched, and closed
+    /// as usual once dropped.
+    pub fn dup(&self) -> io::Result<OwnedFd> {
+        self.fd.try_clone()
+    }
+
+    fn send_event(&self, event: ConnectionEvent) {
+        if let Err(e) = self.event_sender.send(MixnetEvent::Connection(event)) {
+            tracing::error!("Failed to send tun fd event: {}", e);
+        }
+    }
+}
+
+impl AsRawFd for TunFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl Drop for TunFd {
+    fn drop(&mut self) {
+        OPEN_TUN_FDS.fetch_sub(1, Ordering::SeqCst);
+        tracing::debug!("Releasing tun fd {}", self.as_raw_fd());
+        self.send_event(ConnectionEvent::TunFdReleased {
+            fd: self.as_raw_fd(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, os::fd::IntoRawFd};
+
+    use nix::fcntl::{fcntl, FcntlArg};
+
+    use super::*;
+    use crate::tunnel_state_machine::ConnectionEvent::{TunFdEstablished, TunFdReleased};
+
+    fn is_open(raw_fd: RawFd) -> bool {
+        fcntl(raw_fd, FcntlArg::F_GETFD).is_ok()
+    }
+
+    #[test]
+    fn closes_the_fd_once_released() {
+        let (event_sender, mut event_rx) = mpsc::unbounded_channel();
+        let open_before = open_tun_fds();
+
+        for _ in 0..3 {
+            let raw_fd = File::open("/dev/null").unwrap().into_raw_fd();
+            let tun_fd = unsafe { TunFd::from_raw_fd(raw_fd, event_sender.clone()) };
+            assert_eq!(open_tun_fds(), open_before + 1);
+
+            let device_fd = tun_fd.dup().unwrap();
+            let device_raw_fd = device_fd.as_raw_fd();
+            assert_ne!(device_raw_fd, raw_fd);
+
+            // The device closing its duplicate leaves the original open
+            drop(device_fd);
+            assert!(is_open(raw_fd));
+
+            drop(tun_fd);
+            assert_eq!(open_tun_fds(), open_before);
+
+            let events = [event_rx.try_recv(), event_rx.try_recv()];
+            assert!(matches!(
+                events,
+                [
+                    Ok(MixnetEvent::Connection(TunFdEstablished { fd: established })),
+                    Ok(MixnetEvent::Connection(TunFdReleased { fd: released })),
+                ] if established == raw_fd && released == raw_fd
+            ));
+        }
+    }
+}
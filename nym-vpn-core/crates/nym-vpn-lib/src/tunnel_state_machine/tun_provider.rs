@@ -0,0 +1,116 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::fmt::Debug;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::net::Ipv4Addr;
+
+use nym_ip_packet_requests::IpPair;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use tun::AsyncDevice;
+use tun::Device;
+
+use super::{tun_ipv6, Error, Result, TunDevice};
+
+/// Creates the tun devices the tunnels read from and write to on desktop platforms.
+///
+/// Implement this to hand the mixnet tunnel something other than a system device, e.g. an
+/// in-memory [`TunDevice`] to capture the packets in tests. The wireguard tunnels hand their
+/// devices over to wireguard-go, so they need real ones.
+pub trait TunProvider: Send + Sync + Debug {
+    /// Creates the device of the mixnet tunnel.
+    fn create_mixnet_device(
+        &self,
+        interface_addresses: IpPair,
+        mtu: u16,
+    ) -> Result<Box<dyn TunDevice>>;
+
+    /// Whether the routes and DNS are set up against the mixnet device, which needs root. Devices
+    /// that only capture the packets don't need them.
+    fn configure_routing(&self) -> bool {
+        true
+    }
+
+    /// Creates the device of a wireguard tunnel. The exit tunnel is given the private address of
+    /// the entry tunnel as `destination`, since it is nested in it.
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    fn create_wireguard_device(
+        &self,
+        interface_addresses: IpPair,
+        destination: Option<Ipv4Addr>,
+        mtu: u16,
+    ) -> Result<AsyncDevice>;
+}
+
+/// Creates real tun devices in the system, the default.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemTunProvider;
+
+impl TunProvider for SystemTunProvider {
+    fn create_mixnet_device(
+        &self,
+        interface_addresses: IpPair,
+        mtu: u16,
+    ) -> Result<Box<dyn TunDevice>> {
+        let mut tun_config = tun::Configuration::default();
+
+        tun_config
+            .address(interface_addresses.ipv4)
+            .mtu(i32::from(mtu))
+            .up();
+
+        #[cfg(target_os = "linux")]
+        tun_config.platform(|platform_config| {
+            platform_config.packet_information(false);
+        });
+
+        let tun_device = tun::create_as_async(&tun_config).map_err(Error::CreateTunDevice)?;
+
+        let tun_name = tun_device
+            .get_ref()
+            .name()
+            .map_err(Error::GetTunDeviceName)?;
+
+        tun_ipv6::set_ipv6_addr(&tun_name, interface_addresses.ipv6)
+            .map_err(Error::SetTunDeviceIpv6Addr)?;
+
+        Ok(Box::new(tun_device))
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    fn create_wireguard_device(
+        &self,
+        interface_addresses: IpPair,
+        destination: Option<Ipv4Addr>,
+        mtu: u16,
+    ) -> Result<AsyncDevice> {
+        let mut tun_config = tun::Configuration::default();
+
+        tun_config
+            .address(interface_addresses.ipv4)
+            .netmask(Ipv4Addr::BROADCAST)
+            .mtu(i32::from(mtu))
+            .up();
+
+        if let Some(destination) = destination {
+            tun_config.destination(destination);
+        }
+
+        #[cfg(target_os = "linux")]
+        tun_config.platform(|platform_config| {
+            platform_config.packet_information(false);
+        });
+
+        let tun_device = tun::create_as_async(&tun_config).map_err(Error::CreateTunDevice)?;
+
+        let tun_name = tun_device
+            .get_ref()
+            .name()
+            .map_err(Error::GetTunDeviceName)?;
+
+        tun_ipv6::set_ipv6_addr(&tun_name, interface_addresses.ipv6)
+            .map_err(Error::SetTunDeviceIpv6Addr)?;
+
+        Ok(tun_device)
+    }
+}
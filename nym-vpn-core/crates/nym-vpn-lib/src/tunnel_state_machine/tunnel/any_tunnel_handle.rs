@@ -3,12 +3,14 @@
 
 use super::{Error, Result};
 
-use tun::AsyncDevice;
+use nym_wg_go::uapi::TransferStats;
+use tokio::time::Instant;
 
 use super::{
     mixnet::connected_tunnel::TunnelHandle as MixnetTunnelHandle,
     wireguard::connected_tunnel::TunnelHandle as WireguardTunnelHandle,
 };
+use crate::tunnel_state_machine::TunDevice;
 
 pub enum AnyTunnelHandle {
     Mixnet(MixnetTunnelHandle),
@@ -39,6 +41,58 @@ impl AnyTunnelHandle {
         }
     }
 
+    /// Rotate the wireguard keys, if the tunnel supports it. See
+    /// [`WireguardTunnelHandle::rotate_keys`].
+    pub async fn rotate_keys(&mut self) -> Result<()> {
+        match self {
+            #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+            Self::Wireguard(handle) => handle.rotate_keys().await,
+            _ => {
+                tracing::warn!("Key rotation is only supported by desktop wireguard tunnels");
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the wireguard config in the wg-quick format, if the tunnel supports it. See
+    /// [`WireguardTunnelHandle::wireguard_config`].
+    pub fn wireguard_config(&self, include_private_keys: bool) -> Option<String> {
+        match self {
+            #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+            Self::Wireguard(handle) => Some(handle.wireguard_config(include_private_keys)),
+            _ => None,
+        }
+    }
+
+    /// Pause or resume the traffic, if the tunnel supports it. See
+    /// [`MixnetTunnelHandle::set_paused`].
+    pub fn set_paused(&self, paused: bool) {
+        match self {
+            Self::Mixnet(handle) => handle.set_paused(paused),
+            Self::Wireguard(_) => {
+                tracing::warn!("Pausing is only supported by the mixnet tunnel");
+            }
+        }
+    }
+
+    /// Returns when real traffic last went through the tunnel.
+    /// See [`MixnetTunnelHandle::last_activity`] and [`WireguardTunnelHandle::last_activity`].
+    pub fn last_activity(&mut self) -> Instant {
+        match self {
+            Self::Mixnet(handle) => handle.last_activity(),
+            Self::Wireguard(handle) => handle.last_activity(),
+        }
+    }
+
+    /// Returns the bytes sent and received through the tunnel, only wireguard tunnels count them
+    /// here. See [`WireguardTunnelHandle::transfer_stats`].
+    pub fn transfer_stats(&self) -> Option<TransferStats> {
+        match self {
+            Self::Mixnet(_) => None,
+            Self::Wireguard(handle) => handle.transfer_stats(),
+        }
+    }
+
     pub async fn recv_error(
         &mut self,
     ) -> Option<Box<dyn std::error::Error + 'static + Send + Sync>> {
@@ -48,7 +102,7 @@ impl AnyTunnelHandle {
         }
     }
 
-    pub async fn wait(self) -> Result<Vec<AsyncDevice>> {
+    pub async fn wait(self) -> Result<Vec<Box<dyn TunDevice>>> {
         match self {
             Self::Mixnet(handle) => match handle.wait().await {
                 Ok(Ok(device)) => Ok(vec![device]),
@@ -58,7 +112,12 @@ impl AnyTunnelHandle {
                     Ok(vec![])
                 }
             },
-            Self::Wireguard(handle) => Ok(handle.wait().await),
+            Self::Wireguard(handle) => Ok(handle
+                .wait()
+                .await
+                .into_iter()
+                .map(|device| Box::new(device) as Box<dyn TunDevice>)
+                .collect()),
         }
     }
 }
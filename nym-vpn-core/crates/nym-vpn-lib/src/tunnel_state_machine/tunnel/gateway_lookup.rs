@@ -0,0 +1,112 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{net::IpAddr, time::Duration};
+
+use nym_gateway_directory::GatewayClient;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::{Error, Result};
+use crate::tunnel_state_machine::{ConnectionEvent, GatewayLookupRetryOptions, MixnetEvent};
+
+/// Upper bound for the delay between two lookup attempts.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Looks up gateway IPs in the directory, retrying with exponential backoff since a transient DNS
+/// or API hiccup would otherwise abort the whole connect.
+#[derive(Clone)]
+pub struct GatewayIpLookup {
+    retry_options: GatewayLookupRetryOptions,
+    event_sender: mpsc::UnboundedSender<MixnetEvent>,
+    cancel_token: CancellationToken,
+}
+
+impl GatewayIpLookup {
+    pub fn new(
+        retry_options: GatewayLookupRetryOptions,
+        event_sender: mpsc::UnboundedSender<MixnetEvent>,
+        cancel_token: CancellationToken,
+    ) -> Self {
+        Self {
+            retry_options,
+            event_sender,
+            cancel_token,
+        }
+    }
+
+    /// Look up the IP of the given gateway. With no retries configured this is a single lookup.
+    pub async fn lookup(
+        &self,
+        gateway_directory_client: &GatewayClient,
+        gateway_id: &str,
+    ) -> Result<IpAddr> {
+        let mut attempt = 0;
+        loop {
+            let result = self
+                .cancel_token
+                .run_until_cancelled(gateway_directory_client.lookup_gateway_ip(gateway_id))
+                .await
+                .ok_or(Error::Cancelled)?;
+
+            match result {
+                Ok(ip) => return Ok(ip),
+                Err(e) if attempt < self.retry_options.max_retries => {
+                    attempt += 1;
+                    let delay = retry_delay(self.retry_options.base_delay, attempt);
+                    tracing::warn!(
+                        "Failed to lookup ip for gateway {} (attempt {}/{}), retrying in {}ms: {}",
+                        gateway_id,
+                        attempt,
+                        self.retry_options.max_retries,
+                        delay.as_millis(),
+                        e
+                    );
+                    self.send_event(ConnectionEvent::RetryingGatewayLookup {
+                        attempt,
+                        max_retries: self.retry_options.max_retries,
+                    });
+
+                    self.cancel_token
+                        .run_until_cancelled(tokio::time::sleep(delay))
+                        .await
+                        .ok_or(Error::Cancelled)?;
+                }
+                Err(source) => {
+                    return Err(Error::LookupGatewayIp {
+                        gateway_id: gateway_id.to_owned(),
+                        source,
+                    })
+                }
+            }
+        }
+    }
+
+    fn send_event(&self, event: ConnectionEvent) {
+        if let Err(e) = self.event_sender.send(MixnetEvent::Connection(event)) {
+            tracing::error!("Failed to send event: {}", e);
+        }
+    }
+}
+
+// Delay before the given retry attempt, starting at 1: the base delay doubled for each previous
+// attempt.
+pub(super) fn retry_delay(base_delay: Duration, attempt: u32) -> Duration {
+    let multiplier = 2u32.saturating_pow(attempt.saturating_sub(1));
+    base_delay.saturating_mul(multiplier).min(MAX_RETRY_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_max() {
+        let base_delay = Duration::from_millis(500);
+        assert_eq!(retry_delay(base_delay, 1), Duration::from_millis(500));
+        assert_eq!(retry_delay(base_delay, 2), Duration::from_secs(1));
+        assert_eq!(retry_delay(base_delay, 3), Duration::from_secs(2));
+        assert_eq!(retry_delay(base_delay, 10), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(base_delay, u32::MAX), MAX_RETRY_DELAY);
+    }
+}
@@ -1,26 +1,51 @@
 // Copyright 2023 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use nym_gateway_directory::{EntryPoint, ExitPoint, Gateway, GatewayClient, GatewayType};
+use std::time::Duration;
 
-use crate::{tunnel_state_machine::TunnelType, GatewayDirectoryError};
+use nym_config::defaults::NymNetworkDetails;
+use nym_gateway_directory::{
+    EntryPoint, ExitPoint, Gateway, GatewayClient, GatewayList, GatewayType, NodeIdentity,
+};
+use rand::{
+    rngs::StdRng,
+    seq::{IteratorRandom, SliceRandom},
+    Rng, SeedableRng,
+};
+
+use crate::{
+    tunnel_state_machine::{RegionAffinity, SelectionStrategy, TunnelType},
+    GatewayDirectoryError,
+};
 
 #[derive(Debug, Clone)]
 pub struct SelectedGateways {
     pub entry: Gateway,
     pub exit: Gateway,
+
+    /// Round trip time to the entry gateway that the low latency selection was based on, `None`
+    /// when the entry gateway wasn't selected based on latency.
+    pub entry_latency: Option<Duration>,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn select_gateways(
     gateway_directory_client: &GatewayClient,
     tunnel_type: TunnelType,
     entry_point: Box<EntryPoint>,
     exit_point: Box<ExitPoint>,
+    allow_same_country: bool,
+    excluded_gateways: &[NodeIdentity],
+    selection_seed: Option<u64>,
+    region_affinity: Option<RegionAffinity>,
+    selection_strategy: SelectionStrategy,
 ) -> Result<SelectedGateways, GatewayDirectoryError> {
+    let mut rng = selection_rng(selection_seed);
+
     // The set of exit gateways is smaller than the set of entry gateways, so we start by selecting
     // the exit gateway and then filter out the exit gateway from the set of entry gateways.
 
-    let (mut entry_gateways, exit_gateways) = match tunnel_type {
+    let (mut entry_gateways, mut exit_gateways) = match tunnel_type {
         TunnelType::Wireguard => {
             let all_gateways = gateway_directory_client
                 .lookup_gateways(GatewayType::Wg)
@@ -43,29 +68,108 @@ pub async fn select_gateways(
         }
     };
 
-    let exit_gateway = exit_point
-        .lookup_gateway(&exit_gateways)
-        .map_err(|source| GatewayDirectoryError::FailedToSelectExitGateway { source })?;
+    if let Err(err) = check_requested_gateways_exist(
+        &entry_point,
+        &exit_point,
+        &entry_gateways,
+        &exit_gateways,
+        &NymNetworkDetails::new_from_env().network_name,
+    ) {
+        check_missing_gateways_performance(
+            gateway_directory_client,
+            tunnel_type,
+            &entry_point,
+            &exit_point,
+            &entry_gateways,
+            &exit_gateways,
+        )
+        .await?;
+        return Err(err);
+    }
 
-    // Exclude the exit gateway from the list of entry gateways for privacy reasons
-    entry_gateways.remove_gateway(&exit_gateway);
+    // Both wireguard hops register with the authenticator of their own gateway.
+    if tunnel_type == TunnelType::Wireguard {
+        check_requested_gateways_have_authenticator(
+            &entry_point,
+            &exit_point,
+            &entry_gateways,
+            &exit_gateways,
+        )?;
+        remove_gateways_without_authenticator(&mut entry_gateways);
+        remove_gateways_without_authenticator(&mut exit_gateways);
+    }
 
-    let entry_gateway = entry_point
-        .lookup_gateway(&entry_gateways)
-        .await
-        .map_err(|source| match source {
-            nym_gateway_directory::Error::NoMatchingEntryGatewayForLocation {
-                requested_location,
-                available_countries: _,
-            } if Some(requested_location.as_str())
-                == exit_gateway.two_letter_iso_country_code() =>
-            {
-                GatewayDirectoryError::SameEntryAndExitGatewayFromCountry {
-                    requested_location: requested_location.to_string(),
-                }
-            }
-            _ => GatewayDirectoryError::FailedToSelectEntryGateway { source },
-        })?;
+    exclude_gateways(
+        &mut entry_gateways,
+        excluded_gateways,
+        requested_entry_location(&entry_point),
+    )?;
+    exclude_gateways(
+        &mut exit_gateways,
+        excluded_gateways,
+        requested_exit_location(&exit_point),
+    )?;
+
+    let region_affinity = region_affinity.filter(|region_affinity| {
+        let unpinned = is_unpinned(&entry_point, &exit_point);
+        if !unpinned {
+            tracing::info!(
+                "Ignoring the region affinity {:?} for explicitly requested gateways",
+                region_affinity
+            );
+        }
+        unpinned
+    });
+
+    if region_affinity.is_some() && selection_strategy != SelectionStrategy::Random {
+        tracing::info!(
+            "Ignoring the selection strategy {:?} in favor of the region affinity",
+            selection_strategy
+        );
+    } else {
+        apply_selection_strategy(
+            &mut entry_gateways,
+            selection_strategy,
+            strategy_entry_candidates(&entry_point),
+            &mut rng,
+        );
+        apply_selection_strategy(
+            &mut exit_gateways,
+            selection_strategy,
+            strategy_exit_candidates(&exit_point),
+            &mut rng,
+        );
+    }
+
+    let (entry_gateway, exit_gateway, entry_latency) =
+        if let Some(location) = same_requested_country(&entry_point, &exit_point) {
+            let (entry_gateway, exit_gateway) = select_gateways_in_same_country(
+                &entry_gateways,
+                &exit_gateways,
+                location,
+                allow_same_country,
+                &mut rng,
+            )?;
+            (entry_gateway, exit_gateway, None)
+        } else if let Some(region_affinity) = region_affinity {
+            select_gateways_with_region_affinity(
+                &entry_gateways,
+                &exit_gateways,
+                &entry_point,
+                region_affinity,
+                &mut rng,
+            )
+            .await?
+        } else {
+            select_entry_and_exit_gateway(
+                &mut entry_gateways,
+                &exit_gateways,
+                &entry_point,
+                &exit_point,
+                &mut rng,
+            )
+            .await?
+        };
 
     tracing::info!("Found {} entry gateways", entry_gateways.len());
     tracing::info!("Found {} exit gateways", exit_gateways.len());
@@ -79,6 +183,9 @@ pub async fn select_gateways(
             .mixnet_performance
             .map_or_else(|| "unknown".to_string(), |perf| perf.to_string()),
     );
+    if let Some(entry_latency) = entry_latency {
+        tracing::info!("Entry gateway latency: {:?}", entry_latency);
+    }
     tracing::info!(
         "Using exit gateway: {}, location: {}, performance: {}",
         *exit_gateway.identity(),
@@ -99,5 +206,962 @@ pub async fn select_gateways(
     Ok(SelectedGateways {
         entry: entry_gateway,
         exit: exit_gateway,
+        entry_latency,
+    })
+}
+
+/// Select the entry gateway of a single-hop mixnet connection, which doesn't use an exit gateway.
+pub async fn select_entry_gateway(
+    gateway_directory_client: &GatewayClient,
+    entry_point: &EntryPoint,
+    excluded_gateways: &[NodeIdentity],
+    selection_seed: Option<u64>,
+    selection_strategy: SelectionStrategy,
+) -> Result<Gateway, GatewayDirectoryError> {
+    let mut rng = selection_rng(selection_seed);
+
+    let mut entry_gateways = gateway_directory_client
+        .lookup_gateways(GatewayType::MixnetEntry)
+        .await
+        .map_err(|source| GatewayDirectoryError::FailedToLookupGateways { source })?;
+
+    exclude_gateways(
+        &mut entry_gateways,
+        excluded_gateways,
+        requested_entry_location(entry_point),
+    )?;
+    apply_selection_strategy(
+        &mut entry_gateways,
+        selection_strategy,
+        strategy_entry_candidates(entry_point),
+        &mut rng,
+    );
+
+    let entry_gateway = entry_point
+        .lookup_gateway_with_rng(&entry_gateways, &mut rng)
+        .await
+        .map_err(|source| GatewayDirectoryError::FailedToSelectEntryGateway { source })?;
+
+    tracing::info!("Found {} entry gateways", entry_gateways.len());
+    tracing::info!(
+        "Using entry gateway: {}, location: {}, performance: {}",
+        *entry_gateway.identity(),
+        entry_gateway
+            .two_letter_iso_country_code()
+            .map_or_else(|| "unknown".to_string(), |code| code.to_string()),
+        entry_gateway
+            .mixnet_performance
+            .map_or_else(|| "unknown".to_string(), |perf| perf.to_string()),
+    );
+
+    Ok(entry_gateway)
+}
+
+// With a seed, the same directory listing always yields the same choice of gateways.
+fn selection_rng(selection_seed: Option<u64>) -> StdRng {
+    match selection_seed {
+        Some(seed) => {
+            tracing::debug!("Selecting gateways with seed {}", seed);
+            StdRng::seed_from_u64(seed)
+        }
+        None => StdRng::from_entropy(),
+    }
+}
+
+async fn select_entry_and_exit_gateway(
+    entry_gateways: &mut GatewayList,
+    exit_gateways: &GatewayList,
+    entry_point: &EntryPoint,
+    exit_point: &ExitPoint,
+    rng: &mut StdRng,
+) -> Result<(Gateway, Gateway, Option<Duration>), GatewayDirectoryError> {
+    if let EntryPoint::Gateway { identity } = entry_point {
+        let (entry_gateway, exit_gateway) =
+            select_exit_for_pinned_entry(entry_gateways, exit_gateways, identity, exit_point, rng)?;
+        return Ok((entry_gateway, exit_gateway, None));
+    }
+
+    let exit_gateway = exit_point
+        .lookup_gateway_with_rng(exit_gateways, rng)
+        .map_err(|source| GatewayDirectoryError::FailedToSelectExitGateway { source })?;
+
+    // Exclude the exit gateway from the list of entry gateways for privacy reasons
+    entry_gateways.remove_gateway(&exit_gateway);
+
+    let (entry_gateway, entry_latency) = entry_point
+        .lookup_gateway_with_latency(entry_gateways, rng)
+        .await
+        .map_err(|source| match source {
+            nym_gateway_directory::Error::NoMatchingEntryGatewayForLocation {
+                requested_location,
+                available_countries: _,
+            } if Some(requested_location.as_str())
+                == exit_gateway.two_letter_iso_country_code() =>
+            {
+                GatewayDirectoryError::SameEntryAndExitGatewayFromCountry {
+                    requested_location: requested_location.to_string(),
+                }
+            }
+            _ => GatewayDirectoryError::FailedToSelectEntryGateway { source },
+        })?;
+
+    Ok((entry_gateway, exit_gateway, entry_latency))
+}
+
+// Picking the exit first could take the pinned entry gateway away from the entry, e.g. when the
+// exit is requested by location and the pinned entry is in that location. Instead, look up the
+// pinned entry first and pick the exit among the other gateways.
+fn select_exit_for_pinned_entry(
+    entry_gateways: &GatewayList,
+    exit_gateways: &GatewayList,
+    entry_identity: &NodeIdentity,
+    exit_point: &ExitPoint,
+    rng: &mut impl Rng,
+) -> Result<(Gateway, Gateway), GatewayDirectoryError> {
+    let entry_gateway = entry_gateways
+        .gateway_with_identity(entry_identity)
+        .cloned()
+        .ok_or_else(|| GatewayDirectoryError::FailedToSelectEntryGateway {
+            source: nym_gateway_directory::Error::NoMatchingGateway {
+                requested_identity: entry_identity.to_string(),
+            },
+        })?;
+
+    // Exclude the entry gateway from the list of exit gateways for privacy reasons
+    let mut exit_gateways = exit_gateways.clone();
+    exit_gateways.remove_gateway(&entry_gateway);
+
+    let exit_gateway = exit_point
+        .lookup_gateway_with_rng(&exit_gateways, rng)
+        .map_err(|source| GatewayDirectoryError::FailedToSelectExitGateway { source })?;
+
+    Ok((entry_gateway, exit_gateway))
+}
+
+fn has_authenticator(gateway: &Gateway) -> bool {
+    gateway
+        .authenticator_address
+        .as_ref()
+        .is_some_and(|address| address.0.is_some())
+}
+
+// A gateway requested by identity that doesn't run an authenticator would otherwise only fail once
+// registering with it, and be retried over and over.
+fn check_requested_gateways_have_authenticator(
+    entry_point: &EntryPoint,
+    exit_point: &ExitPoint,
+    entry_gateways: &GatewayList,
+    exit_gateways: &GatewayList,
+) -> Result<(), GatewayDirectoryError> {
+    if let EntryPoint::Gateway { identity } = entry_point {
+        if let Some(gateway) = entry_gateways.gateway_with_identity(identity) {
+            if !has_authenticator(gateway) {
+                return Err(GatewayDirectoryError::EntryGatewayWithoutAuthenticator {
+                    requested_identity: identity.to_base58_string(),
+                });
+            }
+        }
+    }
+    if let ExitPoint::Gateway { identity } = exit_point {
+        if let Some(gateway) = exit_gateways.gateway_with_identity(identity) {
+            if !has_authenticator(gateway) {
+                return Err(GatewayDirectoryError::ExitGatewayWithoutAuthenticator {
+                    requested_identity: identity.to_base58_string(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn remove_gateways_without_authenticator(gateways: &mut GatewayList) {
+    let without_authenticator = gateways
+        .iter()
+        .filter(|gateway| !has_authenticator(gateway))
+        .map(|gateway| *gateway.identity())
+        .collect::<Vec<_>>();
+    gateways.remove_gateways(&without_authenticator);
+}
+
+// Gateways requested by identity may belong to a different network than the active one, e.g. after
+// switching environments. Catch that before anything else so that the error names the network
+// being searched, instead of surfacing as a generic lookup failure further down.
+fn check_requested_gateways_exist(
+    entry_point: &EntryPoint,
+    exit_point: &ExitPoint,
+    entry_gateways: &GatewayList,
+    exit_gateways: &GatewayList,
+    network_name: &str,
+) -> Result<(), GatewayDirectoryError> {
+    if let EntryPoint::Gateway { identity } = entry_point {
+        if entry_gateways.gateway_with_identity(identity).is_none() {
+            return Err(
+                GatewayDirectoryError::FailedToSelectEntryGatewayIdNotFound {
+                    requested_identity: identity.to_base58_string(),
+                    network_name: network_name.to_string(),
+                },
+            );
+        }
+    }
+    if let ExitPoint::Gateway { identity } = exit_point {
+        if exit_gateways.gateway_with_identity(identity).is_none() {
+            return Err(GatewayDirectoryError::FailedToSelectExitGatewayIdNotFound {
+                requested_identity: identity.to_base58_string(),
+                network_name: network_name.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// The directory leaves out the gateways below the performance threshold, so a requested gateway can
+// be missing because of the threshold rather than the network. Only look that up once a gateway
+// is missing, so that connecting doesn't fetch the directory twice.
+async fn check_missing_gateways_performance(
+    gateway_directory_client: &GatewayClient,
+    tunnel_type: TunnelType,
+    entry_point: &EntryPoint,
+    exit_point: &ExitPoint,
+    entry_gateways: &GatewayList,
+    exit_gateways: &GatewayList,
+) -> Result<(), GatewayDirectoryError> {
+    let (min_performance, entry_type, exit_type) = match tunnel_type {
+        TunnelType::Wireguard => (
+            gateway_directory_client.vpn_min_performance(),
+            GatewayType::Wg,
+            GatewayType::Wg,
+        ),
+        TunnelType::Mixnet => (
+            gateway_directory_client.mixnet_min_performance(),
+            GatewayType::MixnetEntry,
+            GatewayType::MixnetExit,
+        ),
+    };
+    let Some(min_performance) = min_performance else {
+        return Ok(());
+    };
+
+    let missing_entry = match entry_point {
+        EntryPoint::Gateway { identity } => entry_gateways
+            .gateway_with_identity(identity)
+            .is_none()
+            .then_some((entry_type, identity)),
+        _ => None,
+    };
+    let missing_exit = match exit_point {
+        ExitPoint::Gateway { identity } => exit_gateways
+            .gateway_with_identity(identity)
+            .is_none()
+            .then_some((exit_type, identity)),
+        _ => None,
+    };
+    for (gw_type, identity) in missing_entry.into_iter().chain(missing_exit) {
+        if gateway_directory_client
+            .is_listed_below_performance_threshold(gw_type, identity)
+            .await
+        {
+            return Err(GatewayDirectoryError::GatewayBelowPerformanceThreshold {
+                requested_identity: identity.to_base58_string(),
+                min_performance: min_performance.round_to_integer(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Removes the excluded gateways before any selection takes place. If that leaves nothing to pick
+// from for the requested location, fail with an error naming the excluded set rather than a
+// generic "no matching gateway" error, since the location itself is valid.
+fn exclude_gateways(
+    gateways: &mut GatewayList,
+    excluded_gateways: &[NodeIdentity],
+    requested_location: Option<&str>,
+) -> Result<(), GatewayDirectoryError> {
+    if excluded_gateways.is_empty() {
+        return Ok(());
+    }
+
+    let had_candidates = has_candidates(gateways, requested_location);
+    gateways.remove_gateways(excluded_gateways);
+    if had_candidates && !has_candidates(gateways, requested_location) {
+        return Err(GatewayDirectoryError::AllGatewaysExcluded {
+            requested_location: requested_location.map(ToString::to_string),
+            excluded_gateways: excluded_gateways
+                .iter()
+                .map(|identity| identity.to_base58_string())
+                .collect(),
+        });
+    }
+    Ok(())
+}
+
+fn has_candidates(gateways: &GatewayList, location: Option<&str>) -> bool {
+    match location {
+        Some(location) => gateways
+            .gateways_located_at(location.to_string())
+            .next()
+            .is_some(),
+        None => !gateways.is_empty(),
+    }
+}
+
+// The gateways a selection strategy narrows down, `None` when the gateway is not picked among
+// candidates, e.g. when requested by identity or by latency.
+fn strategy_entry_candidates(entry_point: &EntryPoint) -> Option<StrategyCandidates<'_>> {
+    match entry_point {
+        EntryPoint::Random => Some(StrategyCandidates::All),
+        EntryPoint::Location { location } => Some(StrategyCandidates::LocatedAt(location)),
+        _ => None,
+    }
+}
+
+fn strategy_exit_candidates(exit_point: &ExitPoint) -> Option<StrategyCandidates<'_>> {
+    match exit_point {
+        ExitPoint::Random => Some(StrategyCandidates::All),
+        ExitPoint::Location { location } => Some(StrategyCandidates::LocatedAt(location)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum StrategyCandidates<'a> {
+    All,
+    LocatedAt(&'a str),
+}
+
+// Keep at least this many of the best candidates, and more when there are many candidates, so
+// that the clients connecting at the same time don't all pick the same gateway.
+const MIN_TOP_CANDIDATES: usize = 3;
+const TOP_CANDIDATES_DIVISOR: usize = 10;
+
+// Narrows the candidates down to the best ones according to the strategy, leaving the actual
+// pick to the usual random selection. Gateways outside of the candidates, e.g. in other countries
+// than the requested one, are left alone so that errors still list all available countries.
+fn apply_selection_strategy(
+    gateways: &mut GatewayList,
+    selection_strategy: SelectionStrategy,
+    candidates: Option<StrategyCandidates<'_>>,
+    rng: &mut impl Rng,
+) {
+    let Some(candidates) = candidates else {
+        return;
+    };
+    if selection_strategy == SelectionStrategy::Random {
+        return;
+    }
+
+    let mut ranked = match candidates {
+        StrategyCandidates::All => gateways.iter().collect::<Vec<_>>(),
+        StrategyCandidates::LocatedAt(location) => gateways
+            .gateways_located_at(location.to_string())
+            .collect::<Vec<_>>(),
+    };
+    // Shuffle first so that gateways with the same rank are kept in random order
+    ranked.shuffle(rng);
+    ranked.sort_by_key(|gateway| std::cmp::Reverse(strategy_rank(selection_strategy, gateway)));
+
+    let keep = MIN_TOP_CANDIDATES.max(ranked.len() / TOP_CANDIDATES_DIVISOR);
+    let dropped = ranked
+        .iter()
+        .skip(keep)
+        .map(|gateway| *gateway.identity())
+        .collect::<Vec<_>>();
+    gateways.remove_gateways(&dropped);
+}
+
+// Higher is better. Gateways without a reported performance rank last.
+fn strategy_rank(selection_strategy: SelectionStrategy, gateway: &Gateway) -> Option<u8> {
+    match selection_strategy {
+        SelectionStrategy::Random => None,
+        SelectionStrategy::HighestPerformance => gateway
+            .mixnet_performance
+            .map(|performance| performance.round_to_integer()),
+    }
+}
+
+fn requested_entry_location(entry_point: &EntryPoint) -> Option<&str> {
+    match entry_point {
+        EntryPoint::Location { location } => Some(location),
+        _ => None,
+    }
+}
+
+fn requested_exit_location(exit_point: &ExitPoint) -> Option<&str> {
+    match exit_point {
+        ExitPoint::Location { location } => Some(location),
+        _ => None,
+    }
+}
+
+// Returns the requested country if both the entry and the exit are requested by the same
+// location.
+fn same_requested_country<'a>(
+    entry_point: &'a EntryPoint,
+    exit_point: &ExitPoint,
+) -> Option<&'a str> {
+    match (entry_point, exit_point) {
+        (
+            EntryPoint::Location {
+                location: entry_location,
+            },
+            ExitPoint::Location {
+                location: exit_location,
+            },
+        ) if entry_location == exit_location => Some(entry_location),
+        _ => None,
+    }
+}
+
+// Whether both the entry and the exit are left for us to pick at random.
+fn is_unpinned(entry_point: &EntryPoint, exit_point: &ExitPoint) -> bool {
+    matches!(
+        entry_point,
+        EntryPoint::Random | EntryPoint::RandomLowLatency
+    ) && matches!(exit_point, ExitPoint::Random)
+}
+
+fn satisfies_region_affinity(
+    region_affinity: RegionAffinity,
+    entry_gateway: &Gateway,
+    exit_gateway: &Gateway,
+) -> bool {
+    let (Some(entry_continent), Some(exit_continent)) =
+        (entry_gateway.continent(), exit_gateway.continent())
+    else {
+        return false;
+    };
+    match region_affinity {
+        RegionAffinity::SameContinent => entry_continent == exit_continent,
+        RegionAffinity::DifferentContinent => entry_continent != exit_continent,
+    }
+}
+
+async fn select_gateways_with_region_affinity(
+    entry_gateways: &GatewayList,
+    exit_gateways: &GatewayList,
+    entry_point: &EntryPoint,
+    region_affinity: RegionAffinity,
+    rng: &mut StdRng,
+) -> Result<(Gateway, Gateway, Option<Duration>), GatewayDirectoryError> {
+    let (exit_gateway, entry_candidates) =
+        region_affinity_candidates(entry_gateways, exit_gateways, region_affinity, rng)
+            .ok_or(GatewayDirectoryError::RegionAffinityNotSatisfied { region_affinity })?;
+
+    let (entry_gateway, entry_latency) = entry_point
+        .lookup_gateway_with_latency(&entry_candidates, rng)
+        .await
+        .map_err(|source| GatewayDirectoryError::FailedToSelectEntryGateway { source })?;
+
+    Ok((entry_gateway, exit_gateway, entry_latency))
+}
+
+// Like for gateways in the same country, go through the exit candidates in random order and pick
+// the first one that leaves entry gateways satisfying the affinity. The entry is then picked among
+// these according to the entry point. Gateways without a known continent are never picked.
+fn region_affinity_candidates(
+    entry_gateways: &GatewayList,
+    exit_gateways: &GatewayList,
+    region_affinity: RegionAffinity,
+    rng: &mut impl Rng,
+) -> Option<(Gateway, GatewayList)> {
+    let mut exit_candidates = exit_gateways
+        .iter()
+        .filter(|gateway| gateway.continent().is_some())
+        .collect::<Vec<_>>();
+    exit_candidates.shuffle(rng);
+
+    exit_candidates.into_iter().find_map(|exit_gateway| {
+        let entry_candidates = entry_gateways
+            .iter()
+            .filter(|gateway| gateway.identity() != exit_gateway.identity())
+            .filter(|gateway| satisfies_region_affinity(region_affinity, gateway, exit_gateway))
+            .cloned()
+            .collect::<Vec<_>>();
+        (!entry_candidates.is_empty())
+            .then(|| (exit_gateway.clone(), GatewayList::new(entry_candidates)))
     })
 }
+
+// Only select the entry and exit in the same requested country when that is allowed.
+fn select_gateways_in_same_country(
+    entry_gateways: &GatewayList,
+    exit_gateways: &GatewayList,
+    location: &str,
+    allow_same_country: bool,
+    rng: &mut impl Rng,
+) -> Result<(Gateway, Gateway), GatewayDirectoryError> {
+    if !allow_same_country {
+        return Err(GatewayDirectoryError::SameEntryAndExitGatewayFromCountry {
+            requested_location: location.to_string(),
+        });
+    }
+    select_distinct_gateways_in_country(entry_gateways, exit_gateways, location, rng)
+}
+
+// When the entry and exit are requested in the same country, picking the exit first and then
+// looking for an entry among what's left can fail even though a valid pair exists, e.g. when the
+// randomly picked exit is the only entry gateway in that country. Instead, go through the exit
+// candidates in random order and pick the first one that leaves a distinct entry gateway.
+fn select_distinct_gateways_in_country(
+    entry_gateways: &GatewayList,
+    exit_gateways: &GatewayList,
+    location: &str,
+    rng: &mut impl Rng,
+) -> Result<(Gateway, Gateway), GatewayDirectoryError> {
+    let mut exit_candidates = exit_gateways
+        .gateways_located_at(location.to_string())
+        .collect::<Vec<_>>();
+    if exit_candidates.is_empty() {
+        return Err(GatewayDirectoryError::FailedToSelectExitGateway {
+            source: nym_gateway_directory::Error::NoMatchingExitGatewayForLocation {
+                requested_location: location.to_string(),
+                available_countries: exit_gateways.all_iso_codes(),
+            },
+        });
+    }
+    if entry_gateways
+        .gateways_located_at(location.to_string())
+        .next()
+        .is_none()
+    {
+        return Err(GatewayDirectoryError::FailedToSelectEntryGateway {
+            source: nym_gateway_directory::Error::NoMatchingEntryGatewayForLocation {
+                requested_location: location.to_string(),
+                available_countries: entry_gateways.all_iso_codes(),
+            },
+        });
+    }
+
+    exit_candidates.shuffle(rng);
+    for exit_gateway in exit_candidates {
+        let entry_gateway = entry_gateways
+            .gateways_located_at(location.to_string())
+            .filter(|gateway| gateway.identity() != exit_gateway.identity())
+            .choose(rng);
+        if let Some(entry_gateway) = entry_gateway {
+            return Ok((entry_gateway.clone(), exit_gateway.clone()));
+        }
+    }
+
+    Err(GatewayDirectoryError::SameEntryAndExitGatewayFromCountry {
+        requested_location: location.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use nym_gateway_directory::{AuthAddress, Location, Recipient};
+
+    use super::*;
+
+    fn new_identity() -> NodeIdentity {
+        let mut rng = rand::rngs::OsRng;
+        *nym_crypto::asymmetric::ed25519::KeyPair::new(&mut rng).public_key()
+    }
+
+    fn gateway_located_at(code: &str) -> Gateway {
+        Gateway {
+            identity: new_identity(),
+            location: Some(Location {
+                two_letter_iso_country_code: code.to_string(),
+                ..Default::default()
+            }),
+            ipr_address: None,
+            authenticator_address: None,
+            last_probe: None,
+            host: None,
+            clients_ws_port: None,
+            clients_wss_port: None,
+            mixnet_performance: None,
+            dns_servers: Vec::new(),
+        }
+    }
+
+    fn wg_gateway_located_at(code: &str) -> Gateway {
+        let gateway = gateway_located_at(code);
+        let identity = gateway.identity().to_base58_string();
+        let authenticator =
+            Recipient::try_from_base58_string(format!("{identity}.{identity}@{identity}")).unwrap();
+        Gateway {
+            authenticator_address: Some(AuthAddress(Some(authenticator))),
+            ..gateway
+        }
+    }
+
+    #[test]
+    fn pinned_wg_entry_is_kept_with_exit_in_same_location() {
+        let pinned = wg_gateway_located_at("CH");
+        let other = wg_gateway_located_at("CH");
+        let gateways = GatewayList::new(vec![pinned.clone(), other.clone()]);
+        let exit_point = ExitPoint::Location {
+            location: "CH".to_string(),
+        };
+
+        for _ in 0..100 {
+            let (entry, exit) = select_exit_for_pinned_entry(
+                &gateways,
+                &gateways,
+                pinned.identity(),
+                &exit_point,
+                &mut rand::thread_rng(),
+            )
+            .unwrap();
+            assert_eq!(entry.identity(), pinned.identity());
+            assert_eq!(exit.identity(), other.identity());
+            assert!(has_authenticator(&entry) && has_authenticator(&exit));
+        }
+
+        let gateways = GatewayList::new(vec![pinned.clone(), wg_gateway_located_at("DE")]);
+        let result = select_exit_for_pinned_entry(
+            &gateways,
+            &gateways,
+            pinned.identity(),
+            &exit_point,
+            &mut rand::thread_rng(),
+        );
+        assert!(matches!(
+            result,
+            Err(GatewayDirectoryError::FailedToSelectExitGateway {
+                source: nym_gateway_directory::Error::NoMatchingExitGatewayForLocation { .. },
+            })
+        ));
+    }
+
+    #[test]
+    fn pinned_wg_entry_without_authenticator_is_rejected() {
+        let without_authenticator = gateway_located_at("CH");
+        let with_authenticator = wg_gateway_located_at("DE");
+        let mut gateways = GatewayList::new(vec![
+            without_authenticator.clone(),
+            with_authenticator.clone(),
+        ]);
+        let exit_point = ExitPoint::Location {
+            location: "DE".to_string(),
+        };
+
+        let result = check_requested_gateways_have_authenticator(
+            &EntryPoint::Gateway {
+                identity: *without_authenticator.identity(),
+            },
+            &exit_point,
+            &gateways,
+            &gateways,
+        );
+        assert!(matches!(
+            result,
+            Err(GatewayDirectoryError::EntryGatewayWithoutAuthenticator { requested_identity })
+                if requested_identity == without_authenticator.identity().to_base58_string()
+        ));
+
+        check_requested_gateways_have_authenticator(
+            &EntryPoint::Random,
+            &exit_point,
+            &gateways,
+            &gateways,
+        )
+        .unwrap();
+        remove_gateways_without_authenticator(&mut gateways);
+        assert_eq!(gateways.len(), 1);
+        assert!(gateways
+            .gateway_with_identity(with_authenticator.identity())
+            .is_some());
+    }
+
+    #[test]
+    fn selects_distinct_gateways_in_same_country() {
+        let first = gateway_located_at("CH");
+        let second = gateway_located_at("CH");
+        let gateways = GatewayList::new(vec![first, second]);
+
+        for _ in 0..100 {
+            let (entry, exit) = select_distinct_gateways_in_country(
+                &gateways,
+                &gateways,
+                "CH",
+                &mut rand::thread_rng(),
+            )
+            .unwrap();
+            assert_ne!(entry.identity(), exit.identity());
+        }
+    }
+
+    #[test]
+    fn leaves_the_only_entry_gateway_for_entry() {
+        let entry_only = gateway_located_at("CH");
+        let exit_only = gateway_located_at("CH");
+        let entry_gateways = GatewayList::new(vec![entry_only.clone()]);
+        let exit_gateways = GatewayList::new(vec![entry_only.clone(), exit_only.clone()]);
+
+        for _ in 0..100 {
+            let (entry, exit) = select_distinct_gateways_in_country(
+                &entry_gateways,
+                &exit_gateways,
+                "CH",
+                &mut rand::thread_rng(),
+            )
+            .unwrap();
+            assert_eq!(entry.identity(), entry_only.identity());
+            assert_eq!(exit.identity(), exit_only.identity());
+        }
+    }
+
+    #[test]
+    fn fails_with_a_single_gateway_in_country() {
+        let gateways = GatewayList::new(vec![gateway_located_at("CH")]);
+
+        let result = select_distinct_gateways_in_country(
+            &gateways,
+            &gateways,
+            "CH",
+            &mut rand::thread_rng(),
+        );
+        assert!(matches!(
+            result,
+            Err(GatewayDirectoryError::SameEntryAndExitGatewayFromCountry { .. })
+        ));
+    }
+
+    #[test]
+    fn same_country_is_rejected_unless_allowed() {
+        let gateways = GatewayList::new(vec![gateway_located_at("CH"), gateway_located_at("CH")]);
+
+        let result = select_gateways_in_same_country(
+            &gateways,
+            &gateways,
+            "CH",
+            false,
+            &mut rand::thread_rng(),
+        );
+        assert!(matches!(
+            result,
+            Err(GatewayDirectoryError::SameEntryAndExitGatewayFromCountry { requested_location })
+                if requested_location == "CH"
+        ));
+
+        let (entry, exit) = select_gateways_in_same_country(
+            &gateways,
+            &gateways,
+            "CH",
+            true,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+        assert_ne!(entry.identity(), exit.identity());
+    }
+
+    #[test]
+    fn same_seed_gives_same_selection() {
+        let gateways = GatewayList::new((0..10).map(|_| gateway_located_at("CH")).collect());
+        let select = |seed| {
+            let (entry, exit) = select_distinct_gateways_in_country(
+                &gateways,
+                &gateways,
+                "CH",
+                &mut StdRng::seed_from_u64(seed),
+            )
+            .unwrap();
+            (*entry.identity(), *exit.identity())
+        };
+
+        let selection = select(42);
+        for _ in 0..10 {
+            assert_eq!(select(42), selection);
+        }
+
+        let exit_point = ExitPoint::Location {
+            location: "CH".to_string(),
+        };
+        let select_exit = |seed| {
+            *exit_point
+                .lookup_gateway_with_rng(&gateways, &mut StdRng::seed_from_u64(seed))
+                .unwrap()
+                .identity()
+        };
+        let exit = select_exit(7);
+        for _ in 0..10 {
+            assert_eq!(select_exit(7), exit);
+        }
+    }
+
+    #[test]
+    fn region_affinity_constrains_continents() {
+        let swiss = gateway_located_at("CH");
+        let german = gateway_located_at("DE");
+        let american = gateway_located_at("US");
+        let gateways = GatewayList::new(vec![swiss.clone(), german.clone(), american.clone()]);
+
+        for _ in 0..100 {
+            let (exit, entry_candidates) = region_affinity_candidates(
+                &gateways,
+                &gateways,
+                RegionAffinity::SameContinent,
+                &mut rand::thread_rng(),
+            )
+            .unwrap();
+            assert_ne!(exit.identity(), american.identity());
+            assert!(entry_candidates
+                .iter()
+                .all(|entry| entry.continent() == exit.continent()
+                    && entry.identity() != exit.identity()));
+
+            let (exit, entry_candidates) = region_affinity_candidates(
+                &gateways,
+                &gateways,
+                RegionAffinity::DifferentContinent,
+                &mut rand::thread_rng(),
+            )
+            .unwrap();
+            assert!(entry_candidates
+                .iter()
+                .all(|entry| entry.continent() != exit.continent()));
+        }
+
+        let gateways = GatewayList::new(vec![swiss, american]);
+        assert!(region_affinity_candidates(
+            &gateways,
+            &gateways,
+            RegionAffinity::SameContinent,
+            &mut rand::thread_rng(),
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn excluded_gateway_is_never_selected() {
+        let excluded = gateway_located_at("CH");
+        let other = gateway_located_at("DE");
+        let excluded_gateways = [*excluded.identity()];
+
+        let mut gateways = GatewayList::new(vec![excluded.clone(), other.clone()]);
+        let result = exclude_gateways(&mut gateways, &excluded_gateways, Some("CH"));
+        assert!(matches!(
+            result,
+            Err(GatewayDirectoryError::AllGatewaysExcluded {
+                requested_location: Some(location),
+                excluded_gateways,
+            }) if location == "CH" && excluded_gateways == [excluded.identity().to_base58_string()]
+        ));
+        assert_eq!(gateways.gateways_located_at("CH".to_string()).count(), 0);
+
+        let mut gateways = GatewayList::new(vec![excluded.clone(), other.clone()]);
+        exclude_gateways(&mut gateways, &excluded_gateways, None).unwrap();
+        assert_eq!(gateways.len(), 1);
+        assert_eq!(
+            gateways.random_gateway().unwrap().identity(),
+            other.identity()
+        );
+    }
+
+    #[test]
+    fn fails_early_for_gateway_missing_from_active_network() {
+        let listed = gateway_located_at("CH");
+        let gateways = GatewayList::new(vec![listed.clone()]);
+        let missing = new_identity();
+
+        let result = check_requested_gateways_exist(
+            &EntryPoint::Gateway { identity: missing },
+            &ExitPoint::Random,
+            &gateways,
+            &gateways,
+            "sandbox",
+        );
+        let Err(err) = result else {
+            panic!("expected the missing entry gateway to be rejected");
+        };
+        assert!(matches!(
+            &err,
+            GatewayDirectoryError::FailedToSelectEntryGatewayIdNotFound {
+                requested_identity,
+                network_name,
+            } if *requested_identity == missing.to_base58_string() && network_name == "sandbox"
+        ));
+        assert!(err.to_string().contains("sandbox"));
+
+        check_requested_gateways_exist(
+            &EntryPoint::Gateway {
+                identity: *listed.identity(),
+            },
+            &ExitPoint::Gateway {
+                identity: *listed.identity(),
+            },
+            &gateways,
+            &gateways,
+            "sandbox",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn same_requested_country_only_matches_locations() {
+        let entry_point = EntryPoint::Location {
+            location: "CH".to_string(),
+        };
+        let exit_point = ExitPoint::Location {
+            location: "CH".to_string(),
+        };
+        assert_eq!(
+            same_requested_country(&entry_point, &exit_point),
+            Some("CH")
+        );
+
+        let exit_point = ExitPoint::Location {
+            location: "DE".to_string(),
+        };
+        assert_eq!(same_requested_country(&entry_point, &exit_point), None);
+        assert_eq!(
+            same_requested_country(&EntryPoint::Random, &ExitPoint::Random),
+            None
+        );
+    }
+
+    #[test]
+    fn highest_performance_keeps_the_top_candidates() {
+        let with_performance = |code, performance| Gateway {
+            mixnet_performance: Some(
+                nym_gateway_directory::Percent::from_percentage_value(performance).unwrap(),
+            ),
+            ..gateway_located_at(code)
+        };
+        let swiss = (0..20)
+            .map(|performance| with_performance("CH", performance))
+            .collect::<Vec<_>>();
+        let german = with_performance("DE", 0);
+        let all = swiss
+            .iter()
+            .cloned()
+            .chain([german.clone()])
+            .collect::<Vec<_>>();
+
+        let mut gateways = GatewayList::new(all.clone());
+        apply_selection_strategy(
+            &mut gateways,
+            SelectionStrategy::Random,
+            Some(StrategyCandidates::All),
+            &mut rand::thread_rng(),
+        );
+        assert_eq!(gateways.len(), all.len());
+
+        let mut gateways = GatewayList::new(all.clone());
+        apply_selection_strategy(
+            &mut gateways,
+            SelectionStrategy::HighestPerformance,
+            Some(StrategyCandidates::LocatedAt("CH")),
+            &mut rand::thread_rng(),
+        );
+        assert_eq!(gateways.len(), MIN_TOP_CANDIDATES + 1);
+        assert!(gateways.gateway_with_identity(german.identity()).is_some());
+        for best in &swiss[swiss.len() - MIN_TOP_CANDIDATES..] {
+            assert!(gateways.gateway_with_identity(best.identity()).is_some());
+        }
+
+        let mut gateways = GatewayList::new(all);
+        apply_selection_strategy(
+            &mut gateways,
+            SelectionStrategy::HighestPerformance,
+            Some(StrategyCandidates::All),
+            &mut rand::thread_rng(),
+        );
+        assert_eq!(gateways.len(), MIN_TOP_CANDIDATES);
+        assert!(gateways.gateway_with_identity(german.identity()).is_none());
+    }
+}
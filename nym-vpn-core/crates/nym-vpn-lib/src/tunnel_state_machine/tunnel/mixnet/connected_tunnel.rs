@@ -3,14 +3,20 @@
 
 use std::error::Error as StdError;
 
-use nym_connection_monitor::ConnectionMonitorTask;
-use tokio::task::{JoinError, JoinHandle};
-use tun::AsyncDevice;
+use nym_connection_monitor::{ConnectionMonitorTask, ConnectionQualityConfig};
+use tokio::{
+    sync::watch,
+    task::{JoinError, JoinHandle},
+    time::Instant,
+};
 
 use nym_task::TaskManager;
 
 use super::connector::AssignedAddresses;
-use crate::mixnet::{MixnetError, SharedMixnetClient};
+use crate::{
+    mixnet::{MixnetError, SharedMixnetClient, TrafficActivity},
+    tunnel_state_machine::TunDevice,
+};
 
 /// Type representing a connected mixnet tunnel.
 pub struct ConnectedTunnel {
@@ -36,9 +42,15 @@ impl ConnectedTunnel {
         &self.assigned_addresses
     }
 
-    pub async fn run(self, tun_device: AsyncDevice) -> TunnelHandle {
-        let connection_monitor = ConnectionMonitorTask::setup();
+    pub async fn run(
+        self,
+        tun_device: Box<dyn TunDevice>,
+        quality_config: ConnectionQualityConfig,
+    ) -> TunnelHandle {
+        let connection_monitor = ConnectionMonitorTask::setup().with_quality_config(quality_config);
 
+        let (paused_tx, paused_rx) = watch::channel(false);
+        let traffic_activity = TrafficActivity::new();
         let processor_config =
             crate::mixnet::Config::new(self.assigned_addresses.exit_mix_addresses.0);
         let processor_handle = crate::mixnet::start_processor(
@@ -48,6 +60,8 @@ impl ConnectedTunnel {
             &self.task_manager,
             self.assigned_addresses.interface_addresses,
             &connection_monitor,
+            paused_rx,
+            traffic_activity.clone(),
         )
         .await;
 
@@ -63,16 +77,20 @@ impl ConnectedTunnel {
         TunnelHandle {
             task_manager: self.task_manager,
             processor_handle,
+            paused_tx,
+            traffic_activity,
         }
     }
 }
 
-pub type ProcessorHandle = JoinHandle<Result<AsyncDevice, MixnetError>>;
+pub type ProcessorHandle = JoinHandle<Result<Box<dyn TunDevice>, MixnetError>>;
 
 /// Type providing a back channel for tunnel errors and a way to wait for tunnel to finish execution.
 pub struct TunnelHandle {
     task_manager: TaskManager,
     processor_handle: ProcessorHandle,
+    paused_tx: watch::Sender<bool>,
+    traffic_activity: TrafficActivity,
 }
 
 impl TunnelHandle {
@@ -83,6 +101,17 @@ impl TunnelHandle {
         }
     }
 
+    /// Stop or resume forwarding the packets read from the tun device. The mixnet client stays
+    /// connected in the meantime.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused_tx.send_replace(paused);
+    }
+
+    /// Returns when the last packet went through the tun device, cover traffic excluded.
+    pub fn last_activity(&self) -> Instant {
+        self.traffic_activity.last_activity()
+    }
+
     /// Wait for the next error.
     ///
     /// This method is cancel safe.
@@ -92,7 +121,7 @@ impl TunnelHandle {
     }
 
     /// Wait until the tunnel finished execution.
-    pub async fn wait(self) -> Result<Result<AsyncDevice, MixnetError>, JoinError> {
+    pub async fn wait(self) -> Result<Result<Box<dyn TunDevice>, MixnetError>, JoinError> {
         self.processor_handle.await
     }
 }
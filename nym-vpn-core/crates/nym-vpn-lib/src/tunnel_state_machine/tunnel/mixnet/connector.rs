@@ -1,25 +1,76 @@
 // Copyright 2023 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::net::IpAddr;
+use std::{
+    net::{IpAddr, Ipv6Addr},
+    time::Duration,
+};
 
+use ipnetwork::Ipv6Network;
 use nym_gateway_directory::{GatewayClient, IpPacketRouterAddress, Recipient};
 use nym_ip_packet_client::IprClientConnect;
 use nym_ip_packet_requests::IpPair;
 use nym_task::TaskManager;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
 use super::connected_tunnel::ConnectedTunnel;
 use crate::{
     mixnet::SharedMixnetClient,
-    tunnel_state_machine::tunnel::{gateway_selector::SelectedGateways, Error, Result},
+    tunnel_state_machine::{
+        tunnel::{
+            gateway_lookup::retry_delay, gateway_selector::SelectedGateways, Error,
+            GatewayIpLookup, Result,
+        },
+        ConnectionEvent, MixnetEvent,
+    },
+    MixnetClientConfig,
 };
 
+/// Delay before the first retry of the IPR connect when the config doesn't set one.
+const DEFAULT_IPR_CONNECT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
 /// Struct holding addresses assigned by mixnet upon connect.
 pub struct AssignedAddresses {
     pub entry_mixnet_gateway_ip: IpAddr,
     pub mixnet_client_address: Recipient,
     pub exit_mix_addresses: IpPacketRouterAddress,
     pub interface_addresses: IpPair,
+    /// Network of the IPv6 interface address when it is overridden with a unique local address,
+    /// to be routed on-link.
+    pub ula_ipv6: Option<Ipv6Network>,
+}
+
+/// How to retry connecting to the IPR, since a hiccup of the exit gateway would otherwise abort
+/// the whole connect.
+pub struct IprConnectRetry {
+    max_retries: u32,
+    base_delay: Duration,
+    event_sender: mpsc::UnboundedSender<MixnetEvent>,
+    cancel_token: CancellationToken,
+}
+
+impl IprConnectRetry {
+    pub fn new(
+        mixnet_client_config: &MixnetClientConfig,
+        event_sender: mpsc::UnboundedSender<MixnetEvent>,
+        cancel_token: CancellationToken,
+    ) -> Self {
+        Self {
+            max_retries: mixnet_client_config.ipr_connect_max_retries,
+            base_delay: mixnet_client_config
+                .ipr_connect_retry_delay
+                .unwrap_or(DEFAULT_IPR_CONNECT_RETRY_DELAY),
+            event_sender,
+            cancel_token,
+        }
+    }
+
+    fn send_event(&self, event: ConnectionEvent) {
+        if let Err(e) = self.event_sender.send(MixnetEvent::Connection(event)) {
+            tracing::error!("Failed to send event: {}", e);
+        }
+    }
 }
 
 /// Type responsible for connecting the mixnet tunnel.
@@ -46,31 +97,70 @@ impl Connector {
         self,
         selected_gateways: SelectedGateways,
         nym_ips: Option<IpPair>,
+        ula_ipv6: Option<Ipv6Network>,
+        ipr_connect_retry: &IprConnectRetry,
+        gateway_ip_lookup: &GatewayIpLookup,
     ) -> Result<ConnectedTunnel> {
+        // The IPR only routes the addresses it assigned, so request the unique local address
+        // from it rather than only configuring it locally.
+        let requested_ips = match ula_ipv6 {
+            Some(ula_ipv6) => Some(ula_request(nym_ips, ula_ipv6)?),
+            None => nym_ips,
+        };
+
         let mixnet_client_address = self.mixnet_client.nym_address().await;
         let gateway_used = mixnet_client_address.gateway().to_base58_string();
-        let entry_mixnet_gateway_ip: IpAddr = self
-            .gateway_directory_client
-            .lookup_gateway_ip(&gateway_used)
-            .await
-            .map_err(|source| Error::LookupGatewayIp {
-                gateway_id: gateway_used,
-                source,
-            })?;
+        let entry_mixnet_gateway_ip: IpAddr = gateway_ip_lookup
+            .lookup(&self.gateway_directory_client, &gateway_used)
+            .await?;
 
         let exit_mix_addresses = selected_gateways.exit.ipr_address.unwrap();
 
         let mut ipr_client = IprClientConnect::new_from_inner(self.mixnet_client.inner()).await;
-        let interface_addresses = ipr_client
-            .connect(exit_mix_addresses.0, nym_ips)
-            .await
-            .map_err(Error::ConnectToIpPacketRouter)?;
+        let mut attempt = 0;
+        let interface_addresses = loop {
+            let result = ipr_connect_retry
+                .cancel_token
+                .run_until_cancelled(ipr_client.connect(exit_mix_addresses.0, requested_ips))
+                .await
+                .ok_or(Error::Cancelled)?;
+
+            match result {
+                Ok(interface_addresses) => break interface_addresses,
+                Err(e) if attempt < ipr_connect_retry.max_retries => {
+                    attempt += 1;
+                    let delay = retry_delay(ipr_connect_retry.base_delay, attempt);
+                    tracing::warn!(
+                        "Failed to connect to the IPR (attempt {}/{}), retrying in {}ms: {}",
+                        attempt,
+                        ipr_connect_retry.max_retries,
+                        delay.as_millis(),
+                        e
+                    );
+                    ipr_connect_retry.send_event(ConnectionEvent::RetryingIprConnect {
+                        attempt,
+                        max_retries: ipr_connect_retry.max_retries,
+                    });
+
+                    ipr_connect_retry
+                        .cancel_token
+                        .run_until_cancelled(tokio::time::sleep(delay))
+                        .await
+                        .ok_or(Error::Cancelled)?;
+                }
+                Err(e) => return Err(Error::ConnectToIpPacketRouter(e)),
+            }
+        };
+        if let Some(ula_ipv6) = ula_ipv6 {
+            check_ula_assigned(ula_ipv6, interface_addresses)?;
+        }
 
         let assigned_addresses = AssignedAddresses {
             entry_mixnet_gateway_ip,
             mixnet_client_address,
             exit_mix_addresses,
             interface_addresses,
+            ula_ipv6,
         };
 
         Ok(ConnectedTunnel::new(
@@ -80,3 +170,82 @@ impl Connector {
         ))
     }
 }
+
+// The addresses to request from the IPR: the overridden IPv4 along with the unique local IPv6.
+// Overridden addresses must not clash with routable ones, so only the local ranges are allowed.
+fn ula_request(nym_ips: Option<IpPair>, ula_ipv6: Ipv6Network) -> Result<IpPair> {
+    if !is_unique_local(ula_ipv6.ip()) {
+        return Err(Error::NonPrivateTunAddress(IpAddr::V6(ula_ipv6.ip())));
+    }
+    let nym_ips = nym_ips.ok_or(Error::UlaWithoutTunIpv4)?;
+    if !nym_ips.ipv4.is_private() {
+        return Err(Error::NonPrivateTunAddress(IpAddr::V4(nym_ips.ipv4)));
+    }
+    Ok(IpPair::new(nym_ips.ipv4, ula_ipv6.ip()))
+}
+
+fn check_ula_assigned(ula_ipv6: Ipv6Network, interface_addresses: IpPair) -> Result<()> {
+    if interface_addresses.ipv6 != ula_ipv6.ip() {
+        return Err(Error::UlaNotAssigned {
+            requested: ula_ipv6.ip(),
+            assigned: interface_addresses.ipv6,
+        });
+    }
+    Ok(())
+}
+
+fn is_unique_local(addr: Ipv6Addr) -> bool {
+    addr.segments()[0] & 0xfe00 == 0xfc00
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    fn tun_ips(ipv4: Ipv4Addr) -> Option<IpPair> {
+        Some(IpPair::new(ipv4, Ipv6Addr::LOCALHOST))
+    }
+
+    #[test]
+    fn ula_override_is_requested_from_the_ipr() {
+        let ula_ipv6: Ipv6Network = "fd00:1234::2/64".parse().unwrap();
+        let requested = ula_request(tun_ips(Ipv4Addr::new(10, 0, 0, 2)), ula_ipv6).unwrap();
+        assert_eq!(requested.ipv4, Ipv4Addr::new(10, 0, 0, 2));
+        assert_eq!(requested.ipv6, ula_ipv6.ip());
+    }
+
+    #[test]
+    fn ula_override_must_be_local() {
+        let ula_ipv6 = "fd00:1234::2/64".parse().unwrap();
+        assert!(matches!(
+            ula_request(tun_ips(Ipv4Addr::new(8, 8, 8, 8)), ula_ipv6),
+            Err(Error::NonPrivateTunAddress(_))
+        ));
+        assert!(matches!(
+            ula_request(
+                tun_ips(Ipv4Addr::new(10, 0, 0, 2)),
+                "2001:db8::2/64".parse().unwrap()
+            ),
+            Err(Error::NonPrivateTunAddress(_))
+        ));
+        assert!(matches!(
+            ula_request(None, ula_ipv6),
+            Err(Error::UlaWithoutTunIpv4)
+        ));
+    }
+
+    #[test]
+    fn ula_override_must_be_assigned() {
+        let ula_ipv6: Ipv6Network = "fd00:1234::2/64".parse().unwrap();
+        let assigned = IpPair::new(Ipv4Addr::new(10, 0, 0, 2), ula_ipv6.ip());
+        assert!(check_ula_assigned(ula_ipv6, assigned).is_ok());
+
+        let other = IpPair::new(Ipv4Addr::new(10, 0, 0, 2), "fc00::2".parse().unwrap());
+        assert!(matches!(
+            check_ula_assigned(ula_ipv6, other),
+            Err(Error::UlaNotAssigned { .. })
+        ));
+    }
+}
@@ -2,27 +2,38 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 pub mod any_tunnel_handle;
+mod gateway_lookup;
 mod gateway_selector;
 pub mod mixnet;
 mod status_listener;
+mod sticky_gateways;
 pub mod wireguard;
 
-use std::{path::PathBuf, time::Duration};
+use std::{
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
+pub use gateway_lookup::GatewayIpLookup;
 pub use gateway_selector::SelectedGateways;
-use nym_gateway_directory::{EntryPoint, ExitPoint, GatewayClient};
+use ipnetwork::Ipv6Network;
+use nym_gateway_directory::{EntryPoint, ExitPoint, Gateway, GatewayClient, NodeIdentity};
 use nym_ip_packet_requests::IpPair;
 use nym_sdk::UserAgent;
 use nym_task::{TaskManager, TaskStatus};
 use tokio::{sync::mpsc, task::JoinHandle};
 use tokio_util::sync::CancellationToken;
 
-use super::{MixnetEvent, TunnelType};
+use super::{
+    MixnetEvent, RegionAffinity, SelectionStrategy, TunnelType, WireguardTunnelOptions,
+    SHUTDOWN_TIMEOUT_RANGE,
+};
 use crate::{mixnet::SharedMixnetClient, GatewayDirectoryError, MixnetClientConfig, MixnetError};
 use status_listener::StatusListener;
 
-const MIXNET_CLIENT_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
-const TASK_MANAGER_SHUTDOWN_TIMER_SECS: u64 = 10;
+pub(crate) const DEFAULT_MIXNET_CLIENT_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub struct ConnectedMixnet {
     task_manager: TaskManager,
@@ -30,6 +41,8 @@ pub struct ConnectedMixnet {
     selected_gateways: SelectedGateways,
     data_path: Option<PathBuf>,
     mixnet_client: SharedMixnetClient,
+    mixnet_client_config: MixnetClientConfig,
+    cover_traffic_overridden: bool,
 }
 
 impl ConnectedMixnet {
@@ -37,6 +50,18 @@ impl ConnectedMixnet {
         &self.selected_gateways
     }
 
+    /// Returns the config the mixnet client was started with, after the overrides applied for
+    /// the tunnel type.
+    pub fn mixnet_client_config(&self) -> &MixnetClientConfig {
+        &self.mixnet_client_config
+    }
+
+    /// Whether cover traffic was enabled in the mixnet client config passed in, but disabled since
+    /// wireguard tunnels don't support it.
+    pub fn cover_traffic_overridden(&self) -> bool {
+        self.cover_traffic_overridden
+    }
+
     pub async fn start_event_listener(
         &mut self,
         event_sender: mpsc::UnboundedSender<MixnetEvent>,
@@ -54,6 +79,9 @@ impl ConnectedMixnet {
     pub async fn connect_mixnet_tunnel(
         self,
         interface_addresses: Option<IpPair>, // known as config.nym_ips
+        ula_ipv6: Option<Ipv6Network>,
+        ipr_connect_retry: &mixnet::connector::IprConnectRetry,
+        gateway_ip_lookup: &GatewayIpLookup,
     ) -> Result<mixnet::connected_tunnel::ConnectedTunnel> {
         let connector = mixnet::connector::Connector::new(
             self.task_manager,
@@ -61,7 +89,13 @@ impl ConnectedMixnet {
             self.gateway_directory_client,
         );
         connector
-            .connect(self.selected_gateways, interface_addresses)
+            .connect(
+                self.selected_gateways,
+                interface_addresses,
+                ula_ipv6,
+                ipr_connect_retry,
+                gateway_ip_lookup,
+            )
             .await
     }
 
@@ -69,6 +103,8 @@ impl ConnectedMixnet {
     pub async fn connect_wireguard_tunnel(
         self,
         enable_credentials_mode: bool,
+        wireguard_tunnel_options: &WireguardTunnelOptions,
+        gateway_ip_lookup: &GatewayIpLookup,
     ) -> Result<wireguard::connected_tunnel::ConnectedTunnel> {
         let connector = wireguard::connector::Connector::new(
             self.task_manager,
@@ -78,8 +114,10 @@ impl ConnectedMixnet {
         connector
             .connect(
                 enable_credentials_mode,
+                wireguard_tunnel_options,
                 self.selected_gateways,
                 self.data_path,
+                gateway_ip_lookup,
             )
             .await
     }
@@ -90,6 +128,32 @@ impl ConnectedMixnet {
     }
 }
 
+/// Mixnet client connected to an entry gateway only, to use the mixnet directly instead of
+/// tunneling IP traffic, e.g. to reach Nym-native services.
+///
+/// No exit gateway or IPR is involved, and nothing is routed through the mixnet by the system: no
+/// tun device, routes or DNS are set up. The client stays up until [`Self::dispose`] is called.
+pub struct EntryOnlyMixnet {
+    task_manager: TaskManager,
+    entry_gateway: Gateway,
+    mixnet_client: SharedMixnetClient,
+}
+
+impl EntryOnlyMixnet {
+    pub fn entry_gateway(&self) -> &Gateway {
+        &self.entry_gateway
+    }
+
+    pub fn mixnet_client(&self) -> SharedMixnetClient {
+        self.mixnet_client.clone()
+    }
+
+    /// Gracefully shutdown the mixnet client and consume the struct.
+    pub async fn dispose(self) {
+        shutdown_task_manager(self.task_manager).await;
+    }
+}
+
 pub struct MixnetConnectOptions {
     pub data_path: Option<PathBuf>,
     pub gateway_config: nym_gateway_directory::Config,
@@ -98,13 +162,39 @@ pub struct MixnetConnectOptions {
     pub enable_credentials_mode: bool,
     pub selected_gateways: SelectedGateways,
     pub user_agent: Option<UserAgent>,
+    /// Overrides the mixnet client startup timeout.
+    /// Leave `None` to use the default timeout.
+    pub mixnet_client_startup_timeout: Option<Duration>,
+    /// Overrides the time allowed for the mixnet client tasks to shut down gracefully.
+    /// Leave `None` to use the default timeout.
+    pub shutdown_timeout: Option<Duration>,
 }
 
+pub struct EntryOnlyConnectOptions {
+    pub data_path: Option<PathBuf>,
+    pub mixnet_client_config: Option<MixnetClientConfig>,
+    pub enable_credentials_mode: bool,
+    pub entry_gateway: Gateway,
+    /// Overrides the mixnet client startup timeout.
+    /// Leave `None` to use the default timeout.
+    pub mixnet_client_startup_timeout: Option<Duration>,
+    /// Overrides the time allowed for the mixnet client tasks to shut down gracefully.
+    /// Leave `None` to use the default timeout.
+    pub shutdown_timeout: Option<Duration>,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn select_gateways(
     gateway_config: nym_gateway_directory::Config,
     tunnel_type: TunnelType,
     entry_point: Box<EntryPoint>,
     exit_point: Box<ExitPoint>,
+    allow_same_country: bool,
+    excluded_gateways: &[NodeIdentity],
+    selection_seed: Option<u64>,
+    region_affinity: Option<RegionAffinity>,
+    selection_strategy: SelectionStrategy,
+    max_clock_skew: Duration,
     user_agent: Option<UserAgent>,
     cancel_token: CancellationToken,
 ) -> Result<SelectedGateways> {
@@ -118,12 +208,150 @@ pub async fn select_gateways(
         tunnel_type,
         entry_point,
         exit_point,
+        allow_same_country,
+        excluded_gateways,
+        selection_seed,
+        region_affinity,
+        selection_strategy,
     );
-    cancel_token
+    let result = cancel_token
         .run_until_cancelled(select_gateways_fut)
         .await
-        .ok_or(Error::Cancelled)?
-        .map_err(Error::SelectGateways)
+        .ok_or(Error::Cancelled)?;
+
+    // A skewed clock makes the credentials fail to verify later on, so check it here where we
+    // already have a fresh response from the directory.
+    check_clock_skew(&gateway_directory_client, max_clock_skew)?;
+
+    result.map_err(Error::SelectGateways)
+}
+
+fn check_clock_skew(
+    gateway_directory_client: &GatewayClient,
+    max_clock_skew: Duration,
+) -> Result<()> {
+    match gateway_directory_client.clock_skew_seconds() {
+        Some(skew_seconds) if skew_seconds.unsigned_abs() > max_clock_skew.as_secs() => {
+            tracing::error!(
+                "System clock is off by {}s from the directory",
+                skew_seconds
+            );
+            Err(Error::SystemClockSkew { skew_seconds })
+        }
+        Some(skew_seconds) => {
+            tracing::debug!("System clock skew: {}s", skew_seconds);
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Select the entry gateway for [`connect_entry_only_mixnet`].
+#[allow(clippy::too_many_arguments)]
+pub async fn select_entry_gateway(
+    gateway_config: nym_gateway_directory::Config,
+    entry_point: &EntryPoint,
+    excluded_gateways: &[NodeIdentity],
+    selection_seed: Option<u64>,
+    selection_strategy: SelectionStrategy,
+    max_clock_skew: Duration,
+    user_agent: Option<UserAgent>,
+    cancel_token: CancellationToken,
+) -> Result<Gateway> {
+    let user_agent =
+        user_agent.unwrap_or(UserAgent::from(nym_bin_common::bin_info_local_vergen!()));
+    let gateway_directory_client =
+        GatewayClient::new(gateway_config, user_agent).map_err(Error::CreateGatewayClient)?;
+
+    let select_gateway_fut = gateway_selector::select_entry_gateway(
+        &gateway_directory_client,
+        entry_point,
+        excluded_gateways,
+        selection_seed,
+        selection_strategy,
+    );
+    let result = cancel_token
+        .run_until_cancelled(select_gateway_fut)
+        .await
+        .ok_or(Error::Cancelled)?;
+
+    check_clock_skew(&gateway_directory_client, max_clock_skew)?;
+
+    result.map_err(Error::SelectGateways)
+}
+
+/// Select the gateways we last successfully connected through, as long as they were selected for
+/// the same `entry_point` and `exit_point`, are still in the directory and satisfy the
+/// performance requirements of `gateway_config`.
+///
+/// Returns `None` if there are no such gateways, in which case the caller should fall back to
+/// [`select_gateways`].
+#[allow(clippy::too_many_arguments)]
+pub async fn select_sticky_gateways(
+    gateway_config: nym_gateway_directory::Config,
+    tunnel_type: TunnelType,
+    data_path: &Path,
+    entry_point: &EntryPoint,
+    exit_point: &ExitPoint,
+    excluded_gateways: &[NodeIdentity],
+    max_clock_skew: Duration,
+    user_agent: Option<UserAgent>,
+    cancel_token: CancellationToken,
+) -> Result<Option<SelectedGateways>> {
+    let Some(sticky_gateways) = sticky_gateways::load(data_path, entry_point, exit_point).await
+    else {
+        tracing::debug!("No sticky gateways found");
+        return Ok(None);
+    };
+    let (Some(entry_identity), Some(exit_identity)) = (
+        sticky_gateways.entry_identity(),
+        sticky_gateways.exit_identity(),
+    ) else {
+        tracing::warn!("Ignoring invalid sticky gateways: {:?}", sticky_gateways);
+        return Ok(None);
+    };
+
+    let result = select_gateways(
+        gateway_config,
+        tunnel_type,
+        Box::new(EntryPoint::Gateway {
+            identity: entry_identity,
+        }),
+        Box::new(ExitPoint::Gateway {
+            identity: exit_identity,
+        }),
+        true,
+        excluded_gateways,
+        None,
+        None,
+        max_clock_skew,
+        user_agent,
+        cancel_token,
+    )
+    .await;
+
+    match result {
+        Ok(selected_gateways) => {
+            tracing::info!("Reusing sticky gateways");
+            Ok(Some(selected_gateways))
+        }
+        Err(Error::Cancelled) => Err(Error::Cancelled),
+        Err(e) => {
+            tracing::info!("Sticky gateways are not usable anymore: {}", e);
+            Ok(None)
+        }
+    }
+}
+
+/// Remember the gateways of an established tunnel, so that they can be reused by
+/// [`select_sticky_gateways`] on the next connect to the same `entry_point` and `exit_point`.
+pub async fn store_sticky_gateways(
+    data_path: &Path,
+    selected_gateways: &SelectedGateways,
+    entry_point: &EntryPoint,
+    exit_point: &ExitPoint,
+) {
+    sticky_gateways::store(data_path, selected_gateways, entry_point, exit_point).await;
 }
 
 pub async fn connect_mixnet(
@@ -136,26 +364,113 @@ pub async fn connect_mixnet(
     let gateway_directory_client = GatewayClient::new(options.gateway_config, user_agent)
         .map_err(Error::CreateGatewayClient)?;
 
-    let mut mixnet_client_config = options.mixnet_client_config.unwrap_or_default();
-    match options.tunnel_type {
+    let (mixnet_client_config, cover_traffic_overridden) =
+        mixnet_client_config_for(options.tunnel_type, options.mixnet_client_config);
+
+    let (task_manager, mixnet_client) = start_mixnet_client(
+        &options.selected_gateways.entry,
+        &options.data_path,
+        mixnet_client_config.clone(),
+        options.enable_credentials_mode,
+        options.mixnet_client_startup_timeout,
+        options.shutdown_timeout,
+        cancel_token,
+    )
+    .await?;
+
+    Ok(ConnectedMixnet {
+        task_manager,
+        selected_gateways: options.selected_gateways,
+        data_path: options.data_path,
+        gateway_directory_client,
+        mixnet_client,
+        mixnet_client_config,
+        cover_traffic_overridden,
+    })
+}
+
+/// Connects the mixnet client to the entry gateway only, see [`EntryOnlyMixnet`].
+pub async fn connect_entry_only_mixnet(
+    options: EntryOnlyConnectOptions,
+    cancel_token: CancellationToken,
+) -> Result<EntryOnlyMixnet> {
+    let (task_manager, mixnet_client) = start_mixnet_client(
+        &options.entry_gateway,
+        &options.data_path,
+        options.mixnet_client_config.unwrap_or_default(),
+        options.enable_credentials_mode,
+        options.mixnet_client_startup_timeout,
+        options.shutdown_timeout,
+        cancel_token,
+    )
+    .await?;
+
+    Ok(EntryOnlyMixnet {
+        task_manager,
+        entry_gateway: options.entry_gateway,
+        mixnet_client,
+    })
+}
+
+// Applies the overrides of the tunnel type to the mixnet client config, and returns whether cover
+// traffic the caller asked for had to be disabled. The default config has cover traffic enabled,
+// which only counts as asking for it when the caller passed the config in.
+fn mixnet_client_config_for(
+    tunnel_type: TunnelType,
+    mixnet_client_config: Option<MixnetClientConfig>,
+) -> (MixnetClientConfig, bool) {
+    let cover_traffic_requested = mixnet_client_config.as_ref().is_some_and(|config| {
+        !config.disable_poisson_rate || !config.disable_background_cover_traffic
+    });
+    let mut mixnet_client_config = mixnet_client_config.unwrap_or_default();
+    let mut cover_traffic_overridden = false;
+    match tunnel_type {
         TunnelType::Mixnet => {}
         TunnelType::Wireguard => {
+            if cover_traffic_requested {
+                tracing::warn!("Cover traffic is not supported with wireguard, disabling it");
+                cover_traffic_overridden = true;
+            }
             // Always disable poisson process for outbound traffic in wireguard.
             mixnet_client_config.disable_poisson_rate = true;
             // Always disable background cover traffic in wireguard.
             mixnet_client_config.disable_background_cover_traffic = true;
         }
     };
+    (mixnet_client_config, cover_traffic_overridden)
+}
 
-    let task_manager = TaskManager::new(TASK_MANAGER_SHUTDOWN_TIMER_SECS);
+async fn start_mixnet_client(
+    entry_gateway: &Gateway,
+    data_path: &Option<PathBuf>,
+    mixnet_client_config: MixnetClientConfig,
+    enable_credentials_mode: bool,
+    startup_timeout: Option<Duration>,
+    shutdown_timeout: Option<Duration>,
+    cancel_token: CancellationToken,
+) -> Result<(TaskManager, SharedMixnetClient)> {
+    let startup_timeout = startup_timeout.unwrap_or(DEFAULT_MIXNET_CLIENT_STARTUP_TIMEOUT);
+    tracing::debug!(
+        "Using mixnet client startup timeout: {}s",
+        startup_timeout.as_secs()
+    );
+
+    let shutdown_timeout = shutdown_timeout.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT);
+    // Also checked where the setting is accepted, this catches settings set directly
+    if !SHUTDOWN_TIMEOUT_RANGE.contains(&shutdown_timeout) {
+        return Err(Error::InvalidShutdownTimeout(shutdown_timeout));
+    }
+    tracing::debug!("Using shutdown timeout: {}s", shutdown_timeout.as_secs());
+
+    let task_manager = TaskManager::new(shutdown_timeout.as_secs());
     let connect_fut = tokio::time::timeout(
-        MIXNET_CLIENT_STARTUP_TIMEOUT,
+        startup_timeout,
         crate::mixnet::setup_mixnet_client(
-            options.selected_gateways.entry.identity(),
-            &options.data_path,
+            entry_gateway.identity(),
+            data_path,
             task_manager.subscribe_named("mixnet_client_main"),
             mixnet_client_config,
-            options.enable_credentials_mode,
+            enable_credentials_mode,
         ),
     );
 
@@ -169,13 +484,7 @@ pub async fn connect_mixnet(
         });
 
     match res {
-        Ok(mixnet_client) => Ok(ConnectedMixnet {
-            task_manager,
-            selected_gateways: options.selected_gateways,
-            data_path: options.data_path,
-            gateway_directory_client,
-            mixnet_client,
-        }),
+        Ok(mixnet_client) => Ok((task_manager, mixnet_client)),
         Err(e) => {
             shutdown_task_manager(task_manager).await;
             Err(e)
@@ -204,6 +513,52 @@ pub enum Error {
     #[error("start mixnet client timeout")]
     StartMixnetClientTimeout,
 
+    #[error("system clock is off by {skew_seconds}s, please correct the date and time")]
+    SystemClockSkew { skew_seconds: i64 },
+
+    #[error("shutdown timeout must be between 1 and 60 seconds, got {}s", _0.as_secs_f32())]
+    InvalidShutdownTimeout(Duration),
+
+    #[error("an ephemeral identity can't be used with the credentials mode enabled")]
+    EphemeralIdentityWithCredentials,
+
+    #[error("persistent keepalive interval must be between 1 and 65535 seconds")]
+    InvalidPersistentKeepalive,
+
+    #[error("overridden tunnel address {0} is not in a unique local or private range")]
+    NonPrivateTunAddress(IpAddr),
+
+    #[error("a unique local tunnel IPv6 address also requires overriding the tunnel IPv4 address")]
+    UlaWithoutTunIpv4,
+
+    #[error("the IPR assigned the tunnel IPv6 address {assigned} instead of {requested}")]
+    UlaNotAssigned {
+        requested: Ipv6Addr,
+        assigned: Ipv6Addr,
+    },
+
+    #[error("wireguard endpoint override {0} is not a routable address")]
+    InvalidWgEndpointOverride(SocketAddr),
+
+    #[error("key namespace must be a single directory name, got {0:?}")]
+    InvalidKeyNamespace(String),
+
+    #[error("key rotation interval must be at least an hour, got {}s", _0.as_secs_f32())]
+    InvalidKeyRotationInterval(Duration),
+
+    #[error(
+        "gateway {gateway_id} only accepts wireguard on port {port}, which is not one of the \
+         preferred ports {preferred_ports:?}, try another network or allow more ports"
+    )]
+    WireguardPortNotAllowed {
+        gateway_id: String,
+        port: u16,
+        preferred_ports: Vec<u16>,
+    },
+
+    #[error("wireguard key rotation is not available")]
+    KeyRotationUnavailable,
+
     #[error("mixnet tunnel has failed: {}", _0)]
     MixnetClient(#[from] MixnetError),
 
@@ -243,8 +598,60 @@ pub enum Error {
     #[error("failed to set default path observer: {0}")]
     SetDefaultPathObserver(String),
 
+    #[cfg(target_os = "ios")]
+    #[error("exit tunnel is down, restarting it failed {retries} times")]
+    ExitTunnelDown { retries: u32 },
+
     #[error("connection cancelled")]
     Cancelled,
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn without_cover_traffic() -> MixnetClientConfig {
+        MixnetClientConfig {
+            disable_poisson_rate: true,
+            disable_background_cover_traffic: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn wireguard_always_disables_cover_traffic() {
+        for config in [None, Some(MixnetClientConfig::default())] {
+            let (config, _) = mixnet_client_config_for(TunnelType::Wireguard, config);
+            assert!(config.disable_poisson_rate);
+            assert!(config.disable_background_cover_traffic);
+        }
+    }
+
+    #[test]
+    fn wireguard_only_reports_override_of_requested_cover_traffic() {
+        let (_, overridden) = mixnet_client_config_for(TunnelType::Wireguard, None);
+        assert!(!overridden);
+
+        let (_, overridden) =
+            mixnet_client_config_for(TunnelType::Wireguard, Some(without_cover_traffic()));
+        assert!(!overridden);
+
+        let poisson_rate = MixnetClientConfig {
+            disable_poisson_rate: false,
+            ..without_cover_traffic()
+        };
+        let (_, overridden) = mixnet_client_config_for(TunnelType::Wireguard, Some(poisson_rate));
+        assert!(overridden);
+    }
+
+    #[test]
+    fn mixnet_keeps_cover_traffic() {
+        let (config, overridden) =
+            mixnet_client_config_for(TunnelType::Mixnet, Some(MixnetClientConfig::default()));
+        assert!(!config.disable_poisson_rate);
+        assert!(!config.disable_background_cover_traffic);
+        assert!(!overridden);
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::{Duration, Instant};
+
 use futures::stream::StreamExt;
 use nym_client_core::client::packet_statistics_control::{
     MixnetBandwidthStatisticsEvent, PacketRates,
@@ -8,19 +10,35 @@ use nym_bandwidth_controller::BandwidthStatusMessage;
 use nym_connection_monitor::ConnectionMonitorStatus;
 use nym_task::{StatusReceiver, TaskStatus};
 
-use crate::tunnel_state_machine::{
-    BandwidthEvent, ConnectionEvent, ConnectionStatisticsEvent, MixnetEvent, SphinxPacketRates,
+use crate::{
+    bandwidth_controller::{BandwidthLow, BandwidthToppedUp},
+    mixnet::{PacketDropsStatus, TunnelBytesStatus},
+    tunnel_state_machine::{
+        BandwidthEvent, ConnectionEvent, ConnectionStatisticsEvent, MixnetEvent, MixnetPacketDrops,
+        SphinxPacketRates,
+    },
 };
 
 pub struct StatusListener {
     rx: StatusReceiver,
     tx: mpsc::UnboundedSender<MixnetEvent>,
+    traffic_counters: TrafficCounters,
+    last_statistics_update: Instant,
+    packet_drops: MixnetPacketDrops,
+    mixnet_latency: Option<Duration>,
 }
 
 impl StatusListener {
     pub fn spawn(rx: StatusReceiver, tx: mpsc::UnboundedSender<MixnetEvent>) -> JoinHandle<()> {
         tokio::spawn(async move {
-            let status_listener = Self { rx, tx };
+            let status_listener = Self {
+                rx,
+                tx,
+                traffic_counters: TrafficCounters::default(),
+                last_statistics_update: Instant::now(),
+                packet_drops: MixnetPacketDrops::default(),
+                mixnet_latency: None,
+            };
             status_listener.run().await;
         })
     }
@@ -31,19 +49,41 @@ impl StatusListener {
         while let Some(msg) = self.rx.next().await {
             if let Some(msg) = msg.as_any().downcast_ref::<TaskStatus>() {
                 tracing::info!("Received ignored TaskStatus message: {msg}");
+            } else if let Some(ConnectionMonitorStatus::Latency(rtt)) =
+                msg.as_any().downcast_ref::<ConnectionMonitorStatus>()
+            {
+                tracing::info!("Mixnet latency: {}ms", rtt.as_millis());
+                self.mixnet_latency = Some(*rtt);
+                self.send_event(MixnetEvent::Connection(ConnectionEvent::Latency(*rtt)));
             } else if let Some(msg) = msg.as_any().downcast_ref::<ConnectionMonitorStatus>() {
                 tracing::info!("VPN connection monitor status: {msg}");
                 self.send_event(MixnetEvent::Connection(ConnectionEvent::from(msg)));
             } else if let Some(msg) = msg.as_any().downcast_ref::<BandwidthStatusMessage>() {
                 self.send_event(MixnetEvent::Bandwidth(BandwidthEvent::from(msg)));
+            } else if let Some(msg) = msg.as_any().downcast_ref::<BandwidthToppedUp>() {
+                tracing::info!("Wireguard bandwidth: {msg}");
+                self.send_event(MixnetEvent::Bandwidth(BandwidthEvent::ToppedUp {
+                    new_total: msg.new_total,
+                }));
+            } else if let Some(msg) = msg.as_any().downcast_ref::<BandwidthLow>() {
+                tracing::info!("Wireguard bandwidth: {msg}");
+                self.send_event(MixnetEvent::Bandwidth(BandwidthEvent::LowBandwidth {
+                    remaining: msg.remaining,
+                    threshold: msg.threshold,
+                }));
             } else if let Some(msg) = msg
                 .as_any()
                 .downcast_ref::<MixnetBandwidthStatisticsEvent>()
             {
                 tracing::info!("Mixnet bandwidth: {msg}");
-                self.send_event(MixnetEvent::ConnectionStatistics(
-                    ConnectionStatisticsEvent::from(msg),
-                ));
+                let event = self.connection_statistics_event(msg);
+                self.send_event(MixnetEvent::ConnectionStatistics(event));
+            } else if let Some(msg) = msg.as_any().downcast_ref::<PacketDropsStatus>() {
+                tracing::info!("{msg}");
+                self.packet_drops = msg.drops;
+            } else if let Some(msg) = msg.as_any().downcast_ref::<TunnelBytesStatus>() {
+                tracing::debug!("{msg}");
+                self.traffic_counters.record_real(msg);
             } else {
                 tracing::warn!("VPN status: unknown: {msg}");
                 tracing::debug!("Unknown status message received: {msg}");
@@ -53,6 +93,27 @@ impl StatusListener {
         tracing::debug!("Exiting status listener loop");
     }
 
+    fn connection_statistics_event(
+        &mut self,
+        msg: &MixnetBandwidthStatisticsEvent,
+    ) -> ConnectionStatisticsEvent {
+        let now = Instant::now();
+        let rates = SphinxPacketRates::from(msg.rates.clone());
+        self.traffic_counters
+            .record_cover(&rates, now.duration_since(self.last_statistics_update));
+        self.last_statistics_update = now;
+
+        ConnectionStatisticsEvent {
+            rates,
+            real_bytes: self.traffic_counters.real_bytes(),
+            real_bytes_sent: self.traffic_counters.real_bytes_sent(),
+            real_bytes_received: self.traffic_counters.real_bytes_received(),
+            cover_bytes: self.traffic_counters.cover_bytes(),
+            packet_drops: self.packet_drops,
+            mixnet_latency: self.mixnet_latency,
+        }
+    }
+
     fn send_event(&self, event: MixnetEvent) {
         if let Err(e) = self.tx.send(event) {
             tracing::error!("Failed to send event: {}", e);
@@ -83,14 +144,11 @@ impl From<&ConnectionMonitorStatus> for ConnectionEvent {
             ConnectionMonitorStatus::ExitGatewayRoutingErrorIpv6 => {
                 Self::ExitGatewayRoutingErrorIpv6
             }
-        }
-    }
-}
-
-impl From<&MixnetBandwidthStatisticsEvent> for ConnectionStatisticsEvent {
-    fn from(value: &MixnetBandwidthStatisticsEvent) -> Self {
-        Self {
-            rates: SphinxPacketRates::from(value.rates.clone()),
+            ConnectionMonitorStatus::Quality { rtt_ms, loss_pct } => Self::Quality {
+                rtt_ms: *rtt_ms,
+                loss_pct: *loss_pct,
+            },
+            ConnectionMonitorStatus::Latency(rtt) => Self::Latency(*rtt),
         }
     }
 }
@@ -119,3 +177,104 @@ impl From<PacketRates> for SphinxPacketRates {
         }
     }
 }
+
+// The real bytes are counted by the mixnet processor as the packets go through the tun device.
+// Cover traffic never does, and the mixnet client only reports its packet rates, so the cover
+// total is integrated from the rates over the time between two reports.
+#[derive(Debug, Default)]
+struct TrafficCounters {
+    real_bytes: TunnelBytesStatus,
+    cover_bytes: f64,
+}
+
+impl TrafficCounters {
+    fn record_real(&mut self, bytes: &TunnelBytesStatus) {
+        self.real_bytes = *bytes;
+    }
+
+    fn record_cover(&mut self, rates: &SphinxPacketRates, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        self.cover_bytes +=
+            (rates.cover_packets_sent_size + rates.cover_packets_received_size) * secs;
+    }
+
+    fn real_bytes(&self) -> u64 {
+        self.real_bytes_sent() + self.real_bytes_received()
+    }
+
+    fn real_bytes_sent(&self) -> u64 {
+        self.real_bytes.bytes_sent
+    }
+
+    fn real_bytes_received(&self) -> u64 {
+        self.real_bytes.bytes_received
+    }
+
+    fn cover_bytes(&self) -> u64 {
+        self.cover_bytes as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn idle_rates_with_cover_traffic() -> SphinxPacketRates {
+        SphinxPacketRates {
+            real_packets_sent: 0.0,
+            real_packets_sent_size: 0.0,
+            cover_packets_sent: 50.0,
+            cover_packets_sent_size: 50.0 * 2048.0,
+            real_packets_received: 0.0,
+            real_packets_received_size: 0.0,
+            cover_packets_received: 50.0,
+            cover_packets_received_size: 50.0 * 2048.0,
+            total_acks_received: 0.0,
+            total_acks_received_size: 0.0,
+            real_acks_received: 0.0,
+            real_acks_received_size: 0.0,
+            cover_acks_received: 0.0,
+            cover_acks_received_size: 0.0,
+            real_packets_queued: 0.0,
+            retransmissions_queued: 0.0,
+            reply_surbs_queued: 0.0,
+            additional_reply_surbs_queued: 0.0,
+        }
+    }
+
+    #[test]
+    fn cover_bytes_increase_while_real_traffic_is_idle() {
+        let rates = idle_rates_with_cover_traffic();
+        let mut counters = TrafficCounters::default();
+
+        let mut previous_cover_bytes = 0;
+        for _ in 0..5 {
+            counters.record_cover(&rates, Duration::from_secs(1));
+            assert!(counters.cover_bytes() > previous_cover_bytes);
+            previous_cover_bytes = counters.cover_bytes();
+        }
+
+        assert_eq!(counters.cover_bytes(), 5 * 2 * 50 * 2048);
+        assert_eq!(counters.real_bytes(), 0);
+    }
+
+    #[test]
+    fn real_bytes_come_from_the_tunnel_counters_not_the_rates() {
+        let rates = SphinxPacketRates {
+            real_packets_sent_size: 1000.0,
+            real_packets_received_size: 3000.0,
+            ..idle_rates_with_cover_traffic()
+        };
+        let mut counters = TrafficCounters::default();
+        counters.record_cover(&rates, Duration::from_secs(2));
+        assert_eq!(counters.real_bytes(), 0);
+
+        counters.record_real(&TunnelBytesStatus {
+            bytes_sent: 1500,
+            bytes_received: 4200,
+        });
+        assert_eq!(counters.real_bytes_sent(), 1500);
+        assert_eq!(counters.real_bytes_received(), 4200);
+        assert_eq!(counters.real_bytes(), 5700);
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Cache of the last gateway pair that we successfully connected through, so that reconnecting
+//! can keep using the same entry and exit gateway.
+
+use std::{collections::HashMap, path::Path};
+
+use nym_config::defaults::NymNetworkDetails;
+use nym_gateway_directory::{EntryPoint, ExitPoint, NodeIdentity};
+use serde::{Deserialize, Serialize};
+
+use super::SelectedGateways;
+
+const STICKY_GATEWAYS_FILE: &str = "sticky_gateways.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StickyGatewayPair {
+    pub entry: String,
+    pub exit: String,
+    /// The entry point that was requested when the pair was selected.
+    pub entry_point: EntryPoint,
+    /// The exit point that was requested when the pair was selected.
+    pub exit_point: ExitPoint,
+}
+
+impl StickyGatewayPair {
+    fn new(
+        selected_gateways: &SelectedGateways,
+        entry_point: &EntryPoint,
+        exit_point: &ExitPoint,
+    ) -> Self {
+        Self {
+            entry: selected_gateways.entry.identity().to_base58_string(),
+            exit: selected_gateways.exit.identity().to_base58_string(),
+            entry_point: entry_point.clone(),
+            exit_point: exit_point.clone(),
+        }
+    }
+
+    /// Whether the pair was selected for the same entry and exit point as requested now.
+    pub fn matches(&self, entry_point: &EntryPoint, exit_point: &ExitPoint) -> bool {
+        self.entry_point == *entry_point && self.exit_point == *exit_point
+    }
+
+    pub fn entry_identity(&self) -> Option<NodeIdentity> {
+        NodeIdentity::from_base58_string(&self.entry).ok()
+    }
+
+    pub fn exit_identity(&self) -> Option<NodeIdentity> {
+        NodeIdentity::from_base58_string(&self.exit).ok()
+    }
+}
+
+// Keyed by network name, so that switching between e.g. mainnet and a testnet doesn't pick up
+// gateways from the wrong network.
+type StickyGatewaysCache = HashMap<String, StickyGatewayPair>;
+
+fn current_network_name() -> String {
+    NymNetworkDetails::new_from_env().network_name
+}
+
+async fn read_cache(data_path: &Path) -> Option<StickyGatewaysCache> {
+    let path = data_path.join(STICKY_GATEWAYS_FILE);
+    let contents = tokio::fs::read_to_string(&path).await.ok()?;
+    serde_json::from_str(&contents)
+        .inspect_err(|e| {
+            tracing::warn!(
+                "Failed to parse sticky gateways cache {}: {}",
+                path.display(),
+                e
+            );
+        })
+        .ok()
+}
+
+fn take_matching(
+    cache: &mut StickyGatewaysCache,
+    network_name: &str,
+    entry_point: &EntryPoint,
+    exit_point: &ExitPoint,
+) -> Option<StickyGatewayPair> {
+    let pair = cache.remove(network_name)?;
+    if pair.matches(entry_point, exit_point) {
+        Some(pair)
+    } else {
+        tracing::debug!("Ignoring sticky gateways selected for a different entry or exit point");
+        None
+    }
+}
+
+/// Load the last gateway pair stored for the current network, if it was selected for the given
+/// entry and exit point.
+pub async fn load(
+    data_path: &Path,
+    entry_point: &EntryPoint,
+    exit_point: &ExitPoint,
+) -> Option<StickyGatewayPair> {
+    let mut cache = read_cache(data_path).await?;
+    take_matching(&mut cache, &current_network_name(), entry_point, exit_point)
+}
+
+/// Store the given gateway pair, together with the entry and exit point it was selected for, for
+/// the current network.
+///
+/// This is best effort, failures are logged and otherwise ignored.
+pub async fn store(
+    data_path: &Path,
+    selected_gateways: &SelectedGateways,
+    entry_point: &EntryPoint,
+    exit_point: &ExitPoint,
+) {
+    let mut cache = read_cache(data_path).await.unwrap_or_default();
+    cache.insert(
+        current_network_name(),
+        StickyGatewayPair::new(selected_gateways, entry_point, exit_point),
+    );
+
+    let path = data_path.join(STICKY_GATEWAYS_FILE);
+    let result = match serde_json::to_string_pretty(&cache) {
+        Ok(contents) => tokio::fs::write(&path, contents)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+
+    if let Err(e) = result {
+        tracing::warn!(
+            "Failed to store sticky gateways to {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRY: &str = "4SPdxfBYsuARBw6REQQa5vFiKcvmYiet9sSWqb751i3Z";
+    const EXIT: &str = "FJDUECYAeosXhNGjxf8w5MJM7N2DfDwQznvWwTxJz6ft";
+    const NETWORK: &str = "mainnet";
+
+    fn germany() -> EntryPoint {
+        EntryPoint::Location {
+            location: "DE".to_string(),
+        }
+    }
+
+    fn pair(entry_point: EntryPoint, exit_point: ExitPoint) -> StickyGatewayPair {
+        StickyGatewayPair {
+            entry: ENTRY.to_string(),
+            exit: EXIT.to_string(),
+            entry_point,
+            exit_point,
+        }
+    }
+
+    fn cache_with(pair: StickyGatewayPair) -> StickyGatewaysCache {
+        HashMap::from([(NETWORK.to_string(), pair)])
+    }
+
+    #[test]
+    fn reuse_pair_for_same_points() {
+        let stored = pair(germany(), ExitPoint::Random);
+        let mut cache = cache_with(stored.clone());
+
+        let loaded = take_matching(&mut cache, NETWORK, &germany(), &ExitPoint::Random);
+        assert_eq!(loaded, Some(stored));
+    }
+
+    #[test]
+    fn ignore_pair_for_different_entry_point() {
+        let mut cache = cache_with(pair(germany(), ExitPoint::Random));
+
+        let loaded = take_matching(&mut cache, NETWORK, &EntryPoint::Random, &ExitPoint::Random);
+        assert_eq!(loaded, None);
+    }
+
+    #[test]
+    fn ignore_pair_for_different_exit_point() {
+        let mut cache = cache_with(pair(germany(), ExitPoint::Random));
+        let exit_point = ExitPoint::Location {
+            location: "CH".to_string(),
+        };
+
+        let loaded = take_matching(&mut cache, NETWORK, &germany(), &exit_point);
+        assert_eq!(loaded, None);
+    }
+
+    #[test]
+    fn ignore_pair_for_other_network() {
+        let mut cache = cache_with(pair(germany(), ExitPoint::Random));
+
+        let loaded = take_matching(&mut cache, "sandbox", &germany(), &ExitPoint::Random);
+        assert_eq!(loaded, None);
+    }
+
+    #[test]
+    fn stored_points_survive_roundtrip() {
+        let cache = cache_with(pair(germany(), ExitPoint::Random));
+
+        let contents = serde_json::to_string_pretty(&cache).unwrap();
+        let mut parsed: StickyGatewaysCache = serde_json::from_str(&contents).unwrap();
+
+        let loaded = take_matching(&mut parsed, NETWORK, &germany(), &ExitPoint::Random).unwrap();
+        assert_eq!(loaded.entry_identity().unwrap().to_base58_string(), ENTRY);
+        assert_eq!(loaded.exit_identity().unwrap().to_base58_string(), EXIT);
+    }
+}
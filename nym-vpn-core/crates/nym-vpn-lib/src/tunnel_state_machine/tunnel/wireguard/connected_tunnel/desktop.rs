@@ -3,16 +3,21 @@
 
 use std::{error::Error as StdError, net::IpAddr};
 
-use tokio::task::JoinHandle;
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+    time::Instant,
+};
 use tun::AsyncDevice;
 
 use nym_task::TaskManager;
 use nym_wg_gateway_client::WgGatewayClient;
-use nym_wg_go::{netstack, wireguard_go};
+use nym_wg_go::{netstack, uapi::TransferStats, wireguard_go, PrivateKey};
 
 #[cfg(unix)]
 use crate::tunnel_state_machine::tunnel::wireguard::fd::DupFd;
 use crate::{
+    bandwidth_controller::{KeyRotationReply, KeyRotationRequest},
     tunnel_state_machine::tunnel::{
         wireguard::{connector::ConnectionData, two_hop_config::TwoHopConfig},
         Error, Result,
@@ -26,6 +31,8 @@ pub struct ConnectedTunnel {
     exit_gateway_client: WgGatewayClient,
     connection_data: ConnectionData,
     bandwidth_controller_handle: JoinHandle<()>,
+    key_rotation_tx: mpsc::UnboundedSender<KeyRotationRequest>,
+    persistent_keepalive: Option<u16>,
 }
 
 impl ConnectedTunnel {
@@ -35,6 +42,8 @@ impl ConnectedTunnel {
         exit_gateway_client: WgGatewayClient,
         connection_data: ConnectionData,
         bandwidth_controller_handle: JoinHandle<()>,
+        key_rotation_tx: mpsc::UnboundedSender<KeyRotationRequest>,
+        persistent_keepalive: Option<u16>,
     ) -> Self {
         Self {
             task_manager,
@@ -42,6 +51,8 @@ impl ConnectedTunnel {
             exit_gateway_client,
             connection_data,
             bandwidth_controller_handle,
+            key_rotation_tx,
+            persistent_keepalive,
         }
     }
 
@@ -72,6 +83,7 @@ impl ConnectedTunnel {
             self.entry_gateway_client.keypair().private_key(),
             options.dns.clone(),
             self.entry_mtu(),
+            self.persistent_keepalive,
         );
 
         let wg_exit_config = WgNodeConfig::with_gateway_data(
@@ -79,8 +91,11 @@ impl ConnectedTunnel {
             self.exit_gateway_client.keypair().private_key(),
             options.dns,
             self.exit_mtu(),
+            self.persistent_keepalive,
         );
 
+        let wg_configs = (wg_entry_config.clone(), wg_exit_config.clone());
+
         let entry_tunnel = wireguard_go::Tunnel::start(
             wg_entry_config.into_wireguard_config(),
             #[cfg(unix)]
@@ -110,6 +125,11 @@ impl ConnectedTunnel {
                 exit_wg_tunnel: Some(exit_tunnel),
             },
             bandwidth_controller_handle: self.bandwidth_controller_handle,
+            gateway_clients: Some((self.entry_gateway_client, self.exit_gateway_client)),
+            connection_data: self.connection_data,
+            wg_configs,
+            key_rotation_tx: self.key_rotation_tx,
+            exit_traffic: ExitTraffic::new(),
         })
     }
 
@@ -119,6 +139,7 @@ impl ConnectedTunnel {
             self.entry_gateway_client.keypair().private_key(),
             options.dns.clone(),
             self.entry_mtu(),
+            self.persistent_keepalive,
         );
 
         let wg_exit_config = WgNodeConfig::with_gateway_data(
@@ -126,8 +147,10 @@ impl ConnectedTunnel {
             self.exit_gateway_client.keypair().private_key(),
             options.dns,
             self.exit_mtu(),
+            self.persistent_keepalive,
         );
 
+        let wg_configs = (wg_entry_config.clone(), wg_exit_config.clone());
         let two_hop_config = TwoHopConfig::new(wg_entry_config, wg_exit_config);
 
         let mut entry_tunnel =
@@ -159,6 +182,11 @@ impl ConnectedTunnel {
                 exit_connection: Some(exit_connection),
             },
             bandwidth_controller_handle: self.bandwidth_controller_handle,
+            gateway_clients: Some((self.entry_gateway_client, self.exit_gateway_client)),
+            connection_data: self.connection_data,
+            wg_configs,
+            key_rotation_tx: self.key_rotation_tx,
+            exit_traffic: ExitTraffic::new(),
         })
     }
 }
@@ -229,6 +257,28 @@ pub struct TunnelHandle {
     task_manager: TaskManager,
     internal_handle: InternalTunnelHandle,
     bandwidth_controller_handle: JoinHandle<()>,
+    /// Entry and exit gateway clients, handed over to the bandwidth controller while rotating keys.
+    gateway_clients: Option<(WgGatewayClient, WgGatewayClient)>,
+    connection_data: ConnectionData,
+    /// Entry and exit configs with the addresses and endpoints negotiated with the gateways.
+    wg_configs: (WgNodeConfig, WgNodeConfig),
+    key_rotation_tx: mpsc::UnboundedSender<KeyRotationRequest>,
+    exit_traffic: ExitTraffic,
+}
+
+/// Bytes that went through the exit tunnel as of the last poll.
+struct ExitTraffic {
+    transferred_bytes: u64,
+    last_activity: Instant,
+}
+
+impl ExitTraffic {
+    fn new() -> Self {
+        Self {
+            transferred_bytes: 0,
+            last_activity: Instant::now(),
+        }
+    }
 }
 
 impl TunnelHandle {
@@ -271,6 +321,162 @@ impl TunnelHandle {
         }
     }
 
+    /// Rotate the wireguard keys with both gateways and switch the running tunnels over to them.
+    /// Traffic only stalls until the handshakes with the new keys complete.
+    ///
+    /// A gateway that fails to register the new keys, or that assigns a different endpoint or
+    /// addresses to them, keeps the old ones, so the tunnel never has to be re-established for a
+    /// rotation. An error is only returned when the tunnel can no longer be used.
+    pub async fn rotate_keys(&mut self) -> Result<()> {
+        let (entry_gateway_client, exit_gateway_client) = self
+            .gateway_clients
+            .take()
+            .ok_or(Error::KeyRotationUnavailable)?;
+
+        tracing::info!("Rotating wireguard keys");
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.key_rotation_tx
+            .send(KeyRotationRequest {
+                entry_gateway_client,
+                exit_gateway_client,
+                entry_gateway_data: self.connection_data.entry.clone(),
+                exit_gateway_data: self.connection_data.exit.clone(),
+                reply_tx,
+            })
+            .map_err(|_| Error::KeyRotationUnavailable)?;
+        let KeyRotationReply {
+            entry_gateway_client,
+            exit_gateway_client,
+            entry,
+            exit,
+        } = reply_rx.await.map_err(|_| Error::KeyRotationUnavailable)?;
+
+        let entry_private_key =
+            PrivateKey::from(entry_gateway_client.keypair().private_key().to_bytes());
+        let exit_private_key =
+            PrivateKey::from(exit_gateway_client.keypair().private_key().to_bytes());
+        self.gateway_clients = Some((entry_gateway_client, exit_gateway_client));
+
+        match entry {
+            Ok(_) => {
+                self.set_entry_private_key(&entry_private_key)?;
+                self.wg_configs.0.interface.private_key = entry_private_key;
+            }
+            Err(e) => tracing::warn!("Keeping the old entry keys: {}", e),
+        }
+        match exit {
+            Ok(_) => {
+                self.set_exit_private_key(&exit_private_key)?;
+                self.wg_configs.1.interface.private_key = exit_private_key;
+            }
+            Err(e) => tracing::warn!("Keeping the old exit keys: {}", e),
+        }
+
+        Ok(())
+    }
+
+    fn set_entry_private_key(&mut self, private_key: &PrivateKey) -> Result<()> {
+        match self.internal_handle {
+            InternalTunnelHandle::TunTun {
+                ref mut entry_wg_tunnel,
+                ..
+            } => entry_wg_tunnel
+                .as_mut()
+                .ok_or(Error::KeyRotationUnavailable)?
+                .set_private_key(private_key)?,
+            InternalTunnelHandle::Netstack {
+                ref mut entry_wg_tunnel,
+                ..
+            } => entry_wg_tunnel
+                .as_mut()
+                .ok_or(Error::KeyRotationUnavailable)?
+                .set_private_key(private_key)?,
+        }
+        Ok(())
+    }
+
+    fn set_exit_private_key(&mut self, private_key: &PrivateKey) -> Result<()> {
+        let exit_wg_tunnel = match self.internal_handle {
+            InternalTunnelHandle::TunTun {
+                ref mut exit_wg_tunnel,
+                ..
+            }
+            | InternalTunnelHandle::Netstack {
+                ref mut exit_wg_tunnel,
+                ..
+            } => exit_wg_tunnel,
+        };
+        exit_wg_tunnel
+            .as_mut()
+            .ok_or(Error::KeyRotationUnavailable)?
+            .set_private_key(private_key)?;
+        Ok(())
+    }
+
+    /// Returns when traffic last went through the exit tunnel. Meant to be polled every few
+    /// seconds, as the keepalives and handshakes in between two polls don't count as traffic.
+    pub fn last_activity(&mut self) -> Instant {
+        let exit_wg_tunnel = match self.internal_handle {
+            InternalTunnelHandle::TunTun {
+                ref exit_wg_tunnel, ..
+            }
+            | InternalTunnelHandle::Netstack {
+                ref exit_wg_tunnel, ..
+            } => exit_wg_tunnel,
+        };
+
+        match exit_wg_tunnel
+            .as_ref()
+            .map(wireguard_go::Tunnel::transferred_bytes)
+        {
+            Some(Ok(transferred_bytes)) => {
+                let exit_traffic = &mut self.exit_traffic;
+                if super::is_traffic(exit_traffic.transferred_bytes, transferred_bytes) {
+                    exit_traffic.last_activity = Instant::now();
+                }
+                exit_traffic.transferred_bytes = transferred_bytes;
+            }
+            Some(Err(e)) => {
+                tracing::error!("Failed to get the exit tunnel transferred bytes: {}", e);
+            }
+            None => {}
+        }
+        self.exit_traffic.last_activity
+    }
+
+    /// Returns the bytes sent and received through the exit tunnel since it was started,
+    /// including the wireguard handshakes and keepalives.
+    pub fn transfer_stats(&self) -> Option<TransferStats> {
+        let exit_wg_tunnel = match self.internal_handle {
+            InternalTunnelHandle::TunTun {
+                ref exit_wg_tunnel, ..
+            }
+            | InternalTunnelHandle::Netstack {
+                ref exit_wg_tunnel, ..
+            } => exit_wg_tunnel,
+        };
+
+        exit_wg_tunnel
+            .as_ref()?
+            .transfer_stats()
+            .inspect_err(|e| {
+                tracing::error!("Failed to get the exit tunnel transfer stats: {}", e);
+            })
+            .ok()
+    }
+
+    /// Renders the entry and exit configs in the wg-quick format, for debugging.
+    pub fn wireguard_config(&self, include_private_keys: bool) -> String {
+        let (entry, exit) = &self.wg_configs;
+        format!(
+            "# Addresses, allowed IPs and endpoints are as negotiated with the gateways.\n\
+             # The exit tunnel is carried inside the entry tunnel.\n\
+             \n# Entry gateway\n{}\n# Exit gateway\n{}",
+            entry.to_wg_quick(include_private_keys),
+            exit.to_wg_quick(include_private_keys),
+        )
+    }
+
     /// Wait for the next mixnet error.
     ///
     /// This method is cancel safe.
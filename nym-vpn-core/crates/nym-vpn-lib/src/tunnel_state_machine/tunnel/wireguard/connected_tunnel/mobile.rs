@@ -1,36 +1,57 @@
 // Copyright 2023 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::{error::Error as StdError, net::IpAddr, sync::Arc};
+use std::{
+    error::Error as StdError,
+    net::IpAddr,
+    os::fd::OwnedFd,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[cfg(target_os = "ios")]
+use std::time::SystemTime;
 
 #[cfg(target_os = "ios")]
 use tokio::sync::mpsc;
-use tokio::task::JoinHandle;
+use tokio::{
+    task::JoinHandle,
+    time::{Instant, MissedTickBehavior},
+};
 use tokio_util::sync::CancellationToken;
 use tun::AsyncDevice;
 
 use nym_task::TaskManager;
 use nym_wg_gateway_client::WgGatewayClient;
-use nym_wg_go::{netstack, wireguard_go};
+use nym_wg_go::{netstack, uapi::TransferStats, wireguard_go};
 
 #[cfg(target_os = "android")]
 use crate::tunnel_provider::android::AndroidTunProvider;
-#[cfg(target_os = "ios")]
-use crate::{
-    tunnel_provider::ios::{default_path_observer::DefaultPathObserver, OSTunProvider},
-    tunnel_state_machine::tunnel::wireguard::dns64::Dns64Resolution,
-};
 use crate::{
+    mixnet::TrafficActivity,
     tunnel_state_machine::tunnel::{
         wireguard::{
             connector::ConnectionData,
             fd::DupFd,
-            two_hop_config::{TwoHopConfig, ENTRY_MTU, EXIT_MTU},
+            two_hop_config::{TwoHopConfig, WgForwarderConfig, ENTRY_MTU, EXIT_MTU},
         },
         Error, Result,
     },
     wg_config::WgNodeConfig,
 };
+#[cfg(target_os = "ios")]
+use crate::{
+    tunnel_provider::ios::{default_path_observer::DefaultPathObserver, OSTunProvider},
+    tunnel_state_machine::{tunnel::wireguard::dns64::Dns64Resolution, ExitTunnelRecoveryOptions},
+};
+
+/// Delay before reacting to a default path change, so that a burst of changes (e.g. while
+/// switching from Wi-Fi to cellular) only updates the entry peer endpoint once.
+#[cfg(target_os = "ios")]
+const DEFAULT_PATH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// How often the exit tunnel counters are polled to tell when traffic last went through it.
+const ACTIVITY_POLL_INTERVAL: Duration = Duration::from_secs(5);
 
 pub struct ConnectedTunnel {
     task_manager: TaskManager,
@@ -38,6 +59,9 @@ pub struct ConnectedTunnel {
     exit_gateway_client: WgGatewayClient,
     connection_data: ConnectionData,
     bandwidth_controller_handle: JoinHandle<()>,
+    persistent_keepalive: Option<u16>,
+    #[cfg(target_os = "ios")]
+    exit_tunnel_recovery: ExitTunnelRecoveryOptions,
 }
 
 impl ConnectedTunnel {
@@ -47,6 +71,8 @@ impl ConnectedTunnel {
         exit_gateway_client: WgGatewayClient,
         connection_data: ConnectionData,
         bandwidth_controller_handle: JoinHandle<()>,
+        persistent_keepalive: Option<u16>,
+        #[cfg(target_os = "ios")] exit_tunnel_recovery: ExitTunnelRecoveryOptions,
     ) -> Self {
         Self {
             task_manager,
@@ -54,6 +80,9 @@ impl ConnectedTunnel {
             exit_gateway_client,
             connection_data,
             bandwidth_controller_handle,
+            persistent_keepalive,
+            #[cfg(target_os = "ios")]
+            exit_tunnel_recovery,
         }
     }
 
@@ -82,6 +111,7 @@ impl ConnectedTunnel {
             self.entry_gateway_client.keypair().private_key(),
             dns.clone(),
             self.entry_mtu(),
+            self.persistent_keepalive,
         );
 
         let wg_exit_config = WgNodeConfig::with_gateway_data(
@@ -89,6 +119,7 @@ impl ConnectedTunnel {
             self.exit_gateway_client.keypair().private_key(),
             dns,
             self.exit_mtu(),
+            self.persistent_keepalive,
         );
 
         // Save entry peer so that we can re-resolve it and update wg config on network changes.
@@ -118,22 +149,39 @@ impl ConnectedTunnel {
             }
         }
 
-        // Open connection to the exit node via entry node.
-        let exit_connection = entry_tunnel.open_connection(
-            two_hop_config.forwarder.listen_endpoint.port(),
-            two_hop_config.forwarder.client_port,
-            two_hop_config.forwarder.exit_endpoint,
-        )?;
+        // Keep what's needed to restart the exit tunnel should its handshakes stop.
+        #[cfg(target_os = "ios")]
+        let mut exit_tunnel_supervisor = ExitTunnelSupervisor {
+            config: two_hop_config.exit.clone(),
+            forwarder: two_hop_config.forwarder.clone(),
+            tun_fd: tun_device.get_ref().dup_fd().map_err(Error::DupFd)?,
+            options: self.exit_tunnel_recovery,
+            last_transfer_stats: None,
+        };
 
         #[allow(unused_mut)]
-        let mut exit_tunnel = wireguard_go::Tunnel::start(
-            two_hop_config.exit.into_wireguard_config(),
+        let mut exit_tunnel = Some(ExitTunnel::start(
+            &mut entry_tunnel,
+            &two_hop_config.forwarder,
+            two_hop_config.exit,
             tun_device.get_ref().dup_fd().map_err(Error::DupFd)?,
-        )?;
+        )?);
 
         let shutdown_token = CancellationToken::new();
         let cloned_shutdown_token = shutdown_token.child_token();
 
+        let traffic_activity = TrafficActivity::new();
+        let transfer_stats = Arc::new(Mutex::new(TransferStats::default()));
+        let mut exit_activity = ExitActivity {
+            last_stats: TransferStats::default(),
+            restarted_stats: TransferStats::default(),
+            traffic_activity: traffic_activity.clone(),
+            transfer_stats: transfer_stats.clone(),
+        };
+
+        #[cfg(target_os = "ios")]
+        let (exit_tunnel_error_tx, exit_tunnel_error_rx) = mpsc::channel(1);
+
         #[cfg(target_os = "ios")]
         let mut default_path_rx = {
             let (default_path_tx, default_path_rx) = mpsc::unbounded_channel();
@@ -148,57 +196,129 @@ impl ConnectedTunnel {
         };
 
         let event_loop_handle = tokio::spawn(async move {
+            let mut activity_poll = tokio::time::interval(ACTIVITY_POLL_INTERVAL);
+            activity_poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
             #[cfg(target_os = "ios")]
-            loop {
-                tokio::select! {
-                    _ = cloned_shutdown_token.cancelled() => {
-                        tracing::debug!("Received tunnel shutdown event. Exiting event loop.");
-                        break;
-                    }
-                    Some(new_path) = default_path_rx.recv() => {
-                        tracing::debug!("New default path: {:?}", new_path);
-
-                        // Depending on the network device is connected to, we may need to re-resolve the IP addresses.
-                        // For instance when device connects to IPv4-only server from IPv6-only network,
-                        // it needs to use an IPv4-mapped address, which can be received by re-resolving
-                        // the original peer IP.
-                        match orig_entry_peer.resolved() {
-                            Ok(resolved_peer) => {
-                                let peer_update = resolved_peer.into_peer_endpoint_update();
-
-                                // Update wireguard-go configuration with re-resolved peer endpoints.
-                                if let Err(e) = entry_tunnel.update_peers(&[peer_update]) {
-                                   tracing::error!("Failed to update peers on network change: {}", e);
+            {
+                let debounce_timer = tokio::time::sleep(Duration::ZERO);
+                tokio::pin!(debounce_timer);
+                let mut pending_path_update = false;
+
+                // Recovery is driven from here rather than awaited, so that default path updates
+                // keep being handled while waiting for the exit tunnel to come back.
+                let recovery_timer = tokio::time::sleep(Duration::ZERO);
+                tokio::pin!(recovery_timer);
+                let mut recovery = None;
+
+                let check_interval = exit_tunnel_supervisor.options.check_interval;
+                let mut handshake_check =
+                    tokio::time::interval_at(Instant::now() + check_interval, check_interval);
+                handshake_check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+                loop {
+                    tokio::select! {
+                        _ = cloned_shutdown_token.cancelled() => {
+                            tracing::debug!("Received tunnel shutdown event. Exiting event loop.");
+                            break;
+                        }
+                        _ = activity_poll.tick() => {
+                            exit_activity.poll(exit_tunnel.as_ref());
+                        }
+                        new_path = default_path_rx.recv() => {
+                            let Some(new_path) = new_path else {
+                                tracing::error!("Default path observer has been dropped. Exiting event loop.");
+                                break;
+                            };
+                            tracing::debug!("New default path: {:?}", new_path);
+
+                            // Restart the timer on every change to only handle the last one.
+                            debounce_timer.as_mut().reset(Instant::now() + DEFAULT_PATH_DEBOUNCE);
+                            pending_path_update = true;
+                        }
+                        _ = &mut debounce_timer, if pending_path_update => {
+                            pending_path_update = false;
+
+                            // Depending on the network device is connected to, we may need to re-resolve the IP addresses.
+                            // For instance when device connects to IPv4-only server from IPv6-only network,
+                            // it needs to use an IPv4-mapped address, which can be received by re-resolving
+                            // the original peer IP.
+                            match orig_entry_peer.resolved() {
+                                Ok(resolved_peer) => {
+                                    let peer_update = resolved_peer.into_peer_endpoint_update();
+
+                                    // Update wireguard-go configuration with re-resolved peer endpoints.
+                                    if let Err(e) = entry_tunnel.update_peers(&[peer_update]) {
+                                       tracing::error!("Failed to update peers on network change: {}", e);
+                                    }
+                                }
+                                Err(e) => {
+                                    tracing::error!("Failed to re-resolve peer on default path update: {}", e);
                                 }
                             }
-                            Err(e) => {
-                                tracing::error!("Failed to re-resolve peer on default path update: {}", e);
+
+                            // Rebind wireguard-go on tun device.
+                            if let Some(exit_tunnel) = exit_tunnel.as_mut() {
+                                exit_tunnel.tunnel.bump_sockets();
                             }
+                            entry_tunnel.bump_sockets();
                         }
+                        _ = handshake_check.tick(), if recovery.is_none() => {
+                            if exit_tunnel_supervisor.is_alive(exit_tunnel.as_ref()) {
+                                continue;
+                            }
 
-                        // Rebind wireguard-go on tun device.
-                        exit_tunnel.bump_sockets();
-                        entry_tunnel.bump_sockets();
-                    }
-                    else => {
-                        tracing::error!("Default path observer has been dropped. Exiting event loop.");
-                        break;
+                            let (next_step, delay) =
+                                exit_tunnel_supervisor.start_recovery(&mut exit_tunnel);
+                            recovery = Some(next_step);
+                            recovery_timer.as_mut().reset(Instant::now() + delay);
+                        }
+                        _ = &mut recovery_timer, if recovery.is_some() => {
+                            let Some(step) = recovery.take() else {
+                                continue;
+                            };
+                            let next = exit_tunnel_supervisor.advance_recovery(
+                                step,
+                                &mut entry_tunnel,
+                                &mut exit_tunnel,
+                            );
+                            match next {
+                                Ok(Some((next_step, delay))) => {
+                                    recovery = Some(next_step);
+                                    recovery_timer.as_mut().reset(Instant::now() + delay);
+                                }
+                                Ok(None) => {}
+                                Err(e) => {
+                                    tracing::error!("Failed to recover the exit tunnel: {}", e);
+                                    let _ = exit_tunnel_error_tx.try_send(e);
+                                    break;
+                                }
+                            }
+                        }
                     }
                 }
             }
 
             #[cfg(target_os = "android")]
-            {
-                cloned_shutdown_token.cancelled().await;
-                tracing::debug!("Received tunnel shutdown event. Exiting event loop.");
+            loop {
+                tokio::select! {
+                    _ = cloned_shutdown_token.cancelled() => {
+                        tracing::debug!("Received tunnel shutdown event. Exiting event loop.");
+                        break;
+                    }
+                    _ = activity_poll.tick() => {
+                        exit_activity.poll(exit_tunnel.as_ref());
+                    }
+                }
             }
 
             // Reset default path observer before exiting the event loop.
             #[cfg(target_os = "ios")]
             let _ = tun_provider.set_default_path_observer(None).await;
 
-            exit_tunnel.stop();
-            exit_connection.close();
+            if let Some(exit_tunnel) = exit_tunnel {
+                exit_tunnel.stop();
+            }
             entry_tunnel.stop();
         });
 
@@ -208,16 +328,270 @@ impl ConnectedTunnel {
             shutdown_token,
             event_loop_handle,
             bandwidth_controller_handle: self.bandwidth_controller_handle,
+            traffic_activity,
+            transfer_stats,
+            #[cfg(target_os = "ios")]
+            exit_tunnel_error_rx,
         })
     }
 }
 
+/// Tells when traffic last went through the exit tunnel from the growth of its counters, and
+/// keeps their running total for the handle. Polled from the event loop, which owns the exit
+/// tunnel.
+struct ExitActivity {
+    last_stats: TransferStats,
+    // Totals of the exit tunnels replaced on iOS, whose counters started over.
+    restarted_stats: TransferStats,
+    traffic_activity: TrafficActivity,
+    transfer_stats: Arc<Mutex<TransferStats>>,
+}
+
+impl ExitActivity {
+    fn poll(&mut self, exit_tunnel: Option<&ExitTunnel>) {
+        let Some(exit_tunnel) = exit_tunnel else {
+            return;
+        };
+        match exit_tunnel.tunnel.transfer_stats() {
+            Ok(stats) => {
+                if super::is_traffic(self.last_stats.total(), stats.total()) {
+                    self.traffic_activity.record();
+                }
+                if stats.total() < self.last_stats.total() {
+                    self.restarted_stats = add_stats(self.restarted_stats, self.last_stats);
+                }
+                self.last_stats = stats;
+                *self.transfer_stats.lock().unwrap() = add_stats(self.restarted_stats, stats);
+            }
+            Err(e) => {
+                tracing::error!("Failed to get the exit tunnel transferred bytes: {}", e);
+            }
+        }
+    }
+}
+
+fn add_stats(a: TransferStats, b: TransferStats) -> TransferStats {
+    TransferStats {
+        rx_bytes: a.rx_bytes.saturating_add(b.rx_bytes),
+        tx_bytes: a.tx_bytes.saturating_add(b.tx_bytes),
+    }
+}
+
+/// The exit tunnel and the connection through the entry tunnel carrying it.
+struct ExitTunnel {
+    tunnel: wireguard_go::Tunnel,
+    connection: netstack::TunnelConnection,
+}
+
+impl ExitTunnel {
+    fn start(
+        entry_tunnel: &mut netstack::Tunnel,
+        forwarder: &WgForwarderConfig,
+        config: WgNodeConfig,
+        tun_fd: OwnedFd,
+    ) -> Result<Self> {
+        // Open connection to the exit node via entry node.
+        let connection = entry_tunnel.open_connection(
+            forwarder.listen_endpoint.port(),
+            forwarder.client_port,
+            forwarder.exit_endpoint,
+        )?;
+
+        let tunnel = wireguard_go::Tunnel::start(config.into_wireguard_config(), tun_fd)?;
+
+        Ok(Self { tunnel, connection })
+    }
+
+    fn stop(self) {
+        self.tunnel.stop();
+        self.connection.close();
+    }
+}
+
+/// Keepalive interval used to probe the exit tunnel while recovering it. Without keepalives the
+/// exit tunnel only handshakes when there is traffic, which there may be none of.
+#[cfg(target_os = "ios")]
+const PROBE_KEEPALIVE_INTERVAL: u16 = 5;
+
+/// Restarts the exit tunnel once it stopped completing handshakes, e.g. after the session was
+/// lost while the device was asleep.
+#[cfg(target_os = "ios")]
+struct ExitTunnelSupervisor {
+    config: WgNodeConfig,
+    forwarder: WgForwarderConfig,
+    tun_fd: OwnedFd,
+    options: ExitTunnelRecoveryOptions,
+    /// Counters of the exit tunnel at the last check, `None` before the first one.
+    last_transfer_stats: Option<TransferStats>,
+}
+
+/// Next step of an ongoing exit tunnel recovery.
+#[cfg(target_os = "ios")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecoveryStep {
+    /// Check for a handshake completed since the probe was sent, after the given number of
+    /// restarts.
+    CheckHandshake { restarts: u32, since: SystemTime },
+    /// Restart the exit tunnel, the attempt counting from 1.
+    Restart { attempt: u32 },
+}
+
+#[cfg(target_os = "ios")]
+impl ExitTunnelSupervisor {
+    /// A stale handshake alone doesn't mean that the exit tunnel is down: without keepalives,
+    /// handshakes only happen while there is traffic. It's only considered down when it sent
+    /// packets since the last check without receiving any.
+    fn is_alive(&mut self, exit_tunnel: Option<&ExitTunnel>) -> bool {
+        let Some(exit_tunnel) = exit_tunnel else {
+            return false;
+        };
+        let stats = match exit_tunnel.tunnel.transfer_stats() {
+            Ok(stats) => stats,
+            Err(e) => {
+                tracing::error!("Failed to get the exit tunnel transfer stats: {}", e);
+                return false;
+            }
+        };
+        let last_stats = self.last_transfer_stats.replace(stats);
+
+        let handshake_is_fresh = self.has_handshake_since(
+            Some(exit_tunnel),
+            SystemTime::now() - self.options.max_handshake_age,
+        );
+        handshake_is_fresh || !last_stats.is_some_and(|last| is_unanswered(last, stats))
+    }
+
+    fn has_handshake_since(&self, exit_tunnel: Option<&ExitTunnel>, since: SystemTime) -> bool {
+        match exit_tunnel.map(|exit_tunnel| exit_tunnel.tunnel.last_handshake()) {
+            Some(Ok(last_handshake)) => last_handshake.is_some_and(|handshake| handshake >= since),
+            Some(Err(e)) => {
+                tracing::error!("Failed to get the last exit tunnel handshake: {}", e);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Probes the exit tunnel, it may recover on its own as soon as packets flow again, e.g. once
+    /// the device woke up. Returns the first step of the recovery and when to run it.
+    fn start_recovery(&mut self, exit_tunnel: &mut Option<ExitTunnel>) -> (RecoveryStep, Duration) {
+        tracing::warn!("Exit tunnel handshake is stale and traffic is unanswered, probing it");
+        let step = RecoveryStep::CheckHandshake {
+            restarts: 0,
+            since: SystemTime::now(),
+        };
+        self.set_probe(exit_tunnel, true);
+        (step, self.options.handshake_timeout)
+    }
+
+    /// Runs a step of the recovery. Returns the next step and when to run it, `None` once the
+    /// exit tunnel is back up, or an error once all the restarts failed.
+    fn advance_recovery(
+        &mut self,
+        step: RecoveryStep,
+        entry_tunnel: &mut netstack::Tunnel,
+        exit_tunnel: &mut Option<ExitTunnel>,
+    ) -> Result<Option<(RecoveryStep, Duration)>> {
+        match step {
+            RecoveryStep::CheckHandshake { restarts, since } => {
+                if self.has_handshake_since(exit_tunnel.as_ref(), since) {
+                    if restarts == 0 {
+                        tracing::info!("Exit tunnel recovered on its own");
+                    } else {
+                        tracing::info!("Exit tunnel is back up");
+                    }
+                    self.set_probe(exit_tunnel, false);
+                    self.last_transfer_stats = None;
+                    return Ok(None);
+                }
+                self.next_restart(restarts)
+            }
+            RecoveryStep::Restart { attempt } => {
+                tracing::info!(
+                    "Restarting the exit tunnel (attempt {}/{})",
+                    attempt,
+                    self.options.max_retries
+                );
+
+                if let Some(exit_tunnel) = exit_tunnel.take() {
+                    exit_tunnel.stop();
+                }
+
+                let tun_fd = self.tun_fd.try_clone().map_err(Error::DupFd)?;
+                match ExitTunnel::start(entry_tunnel, &self.forwarder, self.config.clone(), tun_fd)
+                {
+                    Ok(restarted) => *exit_tunnel = Some(restarted),
+                    Err(e) => {
+                        tracing::error!("Failed to restart the exit tunnel: {}", e);
+                        return self.next_restart(attempt);
+                    }
+                }
+
+                let step = RecoveryStep::CheckHandshake {
+                    restarts: attempt,
+                    since: SystemTime::now(),
+                };
+                self.set_probe(exit_tunnel, true);
+                Ok(Some((step, self.options.handshake_timeout)))
+            }
+        }
+    }
+
+    // Schedules the restart following the given number of restarts, if any is left.
+    fn next_restart(&self, restarts: u32) -> Result<Option<(RecoveryStep, Duration)>> {
+        if restarts >= self.options.max_retries {
+            return Err(Error::ExitTunnelDown {
+                retries: self.options.max_retries,
+            });
+        }
+        let delay = if restarts == 0 {
+            Duration::ZERO
+        } else {
+            self.options.retry_delay
+        };
+        Ok(Some((
+            RecoveryStep::Restart {
+                attempt: restarts + 1,
+            },
+            delay,
+        )))
+    }
+
+    // Turns the probing keepalives on, or back to the configured interval.
+    fn set_probe(&self, exit_tunnel: &mut Option<ExitTunnel>, enabled: bool) {
+        let Some(exit_tunnel) = exit_tunnel.as_mut() else {
+            return;
+        };
+        let interval = if enabled {
+            Some(PROBE_KEEPALIVE_INTERVAL)
+        } else {
+            self.config.peer.persistent_keepalive
+        };
+        if let Err(e) = exit_tunnel
+            .tunnel
+            .set_persistent_keepalive(&self.config.peer.public_key, interval)
+        {
+            tracing::error!("Failed to set the exit tunnel keepalive: {}", e);
+        }
+    }
+}
+
+/// Whether packets were sent between the two readings of the counters without any received.
+#[cfg(target_os = "ios")]
+fn is_unanswered(last: TransferStats, current: TransferStats) -> bool {
+    current.tx_bytes > last.tx_bytes && current.rx_bytes <= last.rx_bytes
+}
+
 pub struct TunnelHandle {
     task_manager: TaskManager,
     tun_device: AsyncDevice,
     shutdown_token: CancellationToken,
     event_loop_handle: JoinHandle<()>,
     bandwidth_controller_handle: JoinHandle<()>,
+    traffic_activity: TrafficActivity,
+    transfer_stats: Arc<Mutex<TransferStats>>,
+    #[cfg(target_os = "ios")]
+    exit_tunnel_error_rx: mpsc::Receiver<Error>,
 }
 
 impl TunnelHandle {
@@ -230,12 +604,33 @@ impl TunnelHandle {
         }
     }
 
-    /// Wait for the next mixnet error.
+    /// Returns when traffic last went through the exit tunnel, as of the last poll of its
+    /// counters by the event loop.
+    pub fn last_activity(&self) -> Instant {
+        self.traffic_activity.last_activity()
+    }
+
+    /// Returns the bytes sent and received through the exit tunnel as of the last poll of its
+    /// counters, including the wireguard handshakes and keepalives.
+    pub fn transfer_stats(&self) -> Option<TransferStats> {
+        Some(*self.transfer_stats.lock().unwrap())
+    }
+
+    /// Wait for the next mixnet error, or on iOS for the exit tunnel to fail to recover.
     ///
     /// This method is cancel safe.
     /// Returns `None` if the underlying channel has been closed.
     pub async fn recv_error(&mut self) -> Option<Box<dyn StdError + 'static + Send + Sync>> {
-        self.task_manager.wait_for_error().await
+        #[cfg(target_os = "ios")]
+        let error = tokio::select! {
+            error = self.task_manager.wait_for_error() => error,
+            Some(error) = self.exit_tunnel_error_rx.recv() => Some(error.into()),
+        };
+
+        #[cfg(target_os = "android")]
+        let error = self.task_manager.wait_for_error().await;
+
+        error
     }
 
     /// Wait until the tunnel finished execution.
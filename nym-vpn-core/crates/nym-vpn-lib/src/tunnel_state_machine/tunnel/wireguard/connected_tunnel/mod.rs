@@ -14,3 +14,36 @@ pub use desktop::{
 
 #[cfg(any(target_os = "ios", target_os = "android"))]
 pub use mobile::{ConnectedTunnel, TunnelHandle};
+
+/// Growth of the exit tunnel counters between two polls below which it's put down to keepalives
+/// and handshakes rather than traffic.
+const IDLE_TRANSFER_THRESHOLD: u64 = 1024;
+
+/// Whether the exit tunnel counters grew by more than keepalives and handshakes since the last
+/// poll. The counters start over when the exit tunnel is restarted.
+fn is_traffic(last_transferred_bytes: u64, transferred_bytes: u64) -> bool {
+    transferred_bytes.saturating_sub(last_transferred_bytes) > IDLE_TRANSFER_THRESHOLD
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keepalives_are_not_traffic() {
+        assert!(!is_traffic(0, 0));
+        assert!(!is_traffic(100, 100 + IDLE_TRANSFER_THRESHOLD));
+    }
+
+    #[test]
+    fn counters_growth_is_traffic() {
+        assert!(is_traffic(0, IDLE_TRANSFER_THRESHOLD + 1));
+        assert!(is_traffic(100, 100 + 64 * 1024));
+    }
+
+    #[test]
+    fn restarted_counters_are_not_traffic() {
+        assert!(!is_traffic(64 * 1024, 0));
+        assert!(!is_traffic(64 * 1024, 512));
+    }
+}
@@ -1,7 +1,11 @@
 // Copyright 2023 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::path::PathBuf;
+use std::{
+    net::IpAddr,
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
 
 use nym_authenticator_client::AuthClient;
 use nym_credentials_interface::TicketType;
@@ -9,19 +13,44 @@ use nym_gateway_directory::{AuthAddresses, Gateway, GatewayClient};
 use nym_sdk::mixnet::{EphemeralCredentialStorage, StoragePaths};
 use nym_task::TaskManager;
 use nym_wg_gateway_client::{GatewayData, WgGatewayClient};
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+use tokio::sync::mpsc;
 
 use super::connected_tunnel::ConnectedTunnel;
 use crate::{
     bandwidth_controller::BandwidthController,
     mixnet::SharedMixnetClient,
-    tunnel_state_machine::tunnel::{gateway_selector::SelectedGateways, Error, Result},
+    tunnel_state_machine::{
+        tunnel::{gateway_selector::SelectedGateways, Error, GatewayIpLookup, Result},
+        WireguardTunnelOptions,
+    },
 };
 
+/// Rotating keys registers them with the gateways, which spends a ticket with each of them in
+/// credentials mode, so don't let it happen too often.
+const MIN_KEY_ROTATION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 pub struct ConnectionData {
     pub entry: GatewayData,
     pub exit: GatewayData,
 }
 
+impl ConnectionData {
+    /// Whether tickets were spent with either gateway to register this session.
+    pub fn credentials_spent(&self) -> bool {
+        self.entry.credentials_spent || self.exit.credentials_spent
+    }
+
+    /// Number of tickets spent with the gateways to register this session.
+    pub fn tickets_spent(&self) -> u32 {
+        [&self.entry, &self.exit]
+            .into_iter()
+            .filter(|gateway| gateway.credentials_spent)
+            .count() as u32
+            * nym_wg_gateway_client::TICKETS_TO_SPEND
+    }
+}
+
 pub struct Connector {
     task_manager: TaskManager,
     mixnet_client: SharedMixnetClient,
@@ -44,9 +73,46 @@ impl Connector {
     pub async fn connect(
         self,
         enable_credentials_mode: bool,
+        wireguard_tunnel_options: &WireguardTunnelOptions,
         selected_gateways: SelectedGateways,
         data_path: Option<PathBuf>,
+        gateway_ip_lookup: &GatewayIpLookup,
     ) -> Result<ConnectedTunnel> {
+        if wireguard_tunnel_options.persistent_keepalive == Some(0) {
+            return Err(Error::InvalidPersistentKeepalive);
+        }
+        if let Some(key_rotation_interval) = wireguard_tunnel_options.key_rotation_interval {
+            validate_key_rotation_interval(key_rotation_interval)?;
+        }
+        if let Some(endpoint_override) = wireguard_tunnel_options.wg_endpoint_override {
+            if !is_routable(endpoint_override.ip()) {
+                return Err(Error::InvalidWgEndpointOverride(endpoint_override));
+            }
+        }
+        let key_namespace = wireguard_tunnel_options.key_namespace.as_deref();
+        if let Some(key_namespace) = key_namespace {
+            Self::validate_key_namespace(key_namespace)?;
+        }
+
+        // Only the entry gateway is reached over the local network, the exit gateway is reached
+        // through the entry tunnel. Check its port before registering, which spends tickets.
+        let preferred_ports = &wireguard_tunnel_options.preferred_wg_ports;
+        let override_port = wireguard_tunnel_options
+            .wg_endpoint_override
+            .map(|endpoint_override| endpoint_override.port())
+            .filter(|port| *port != 0);
+        let entry_port_checked = match override_port {
+            // The overridden port is the one connected to, not the one the gateway advertises
+            Some(port) => {
+                check_wg_port(&selected_gateways.entry, port, preferred_ports)?;
+                true
+            }
+            None => {
+                self.check_advertised_wg_port(&selected_gateways.entry, preferred_ports)
+                    .await?
+            }
+        };
+
         let auth_addresses =
             Self::setup_auth_addresses(&selected_gateways.entry, &selected_gateways.exit)?;
         let (Some(entry_auth_recipient), Some(exit_auth_recipient)) =
@@ -56,13 +122,36 @@ impl Connector {
         };
         let auth_client = AuthClient::new_from_inner(self.mixnet_client.inner()).await;
 
-        let mut wg_entry_gateway_client =
-            WgGatewayClient::new_entry(&data_path, auth_client.clone(), entry_auth_recipient);
-        let mut wg_exit_gateway_client =
-            WgGatewayClient::new_exit(&data_path, auth_client.clone(), exit_auth_recipient);
+        let mut wg_entry_gateway_client = WgGatewayClient::new_entry(
+            &data_path,
+            key_namespace,
+            auth_client.clone(),
+            entry_auth_recipient,
+        );
+        let mut wg_exit_gateway_client = WgGatewayClient::new_exit(
+            &data_path,
+            key_namespace,
+            auth_client.clone(),
+            exit_auth_recipient,
+        );
+
+        let entry_gateway_host = gateway_ip_lookup
+            .lookup(
+                &self.gateway_directory_client,
+                &entry_auth_recipient.gateway().to_base58_string(),
+            )
+            .await?;
+        let exit_gateway_host = gateway_ip_lookup
+            .lookup(
+                &self.gateway_directory_client,
+                &exit_auth_recipient.gateway().to_base58_string(),
+            )
+            .await?;
 
         let shutdown = self.task_manager.subscribe_named("bandwidth controller");
-        let (connection_data, bandwidth_controller_handle) = if let Some(data_path) =
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+        let (key_rotation_tx, key_rotation_rx) = mpsc::unbounded_channel();
+        let (mut connection_data, bandwidth_controller_handle) = if let Some(data_path) =
             data_path.as_ref()
         {
             let paths = StoragePaths::new_from_dir(data_path).map_err(Error::SetupStoragePaths)?;
@@ -74,13 +163,17 @@ impl Connector {
                 storage,
                 wg_entry_gateway_client.light_client(),
                 wg_exit_gateway_client.light_client(),
+                enable_credentials_mode,
+                wireguard_tunnel_options,
                 shutdown,
             )?;
+            #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+            let bw = bw.with_key_rotation(key_rotation_rx);
             let entry = bw
                 .get_initial_bandwidth(
                     enable_credentials_mode,
                     TicketType::V1WireguardEntry,
-                    &self.gateway_directory_client,
+                    entry_gateway_host,
                     &mut wg_entry_gateway_client,
                 )
                 .await?;
@@ -88,7 +181,7 @@ impl Connector {
                 .get_initial_bandwidth(
                     enable_credentials_mode,
                     TicketType::V1WireguardExit,
-                    &self.gateway_directory_client,
+                    exit_gateway_host,
                     &mut wg_exit_gateway_client,
                 )
                 .await?;
@@ -102,13 +195,17 @@ impl Connector {
                 storage,
                 wg_entry_gateway_client.light_client(),
                 wg_exit_gateway_client.light_client(),
+                enable_credentials_mode,
+                wireguard_tunnel_options,
                 shutdown,
             )?;
+            #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+            let bw = bw.with_key_rotation(key_rotation_rx);
             let entry = bw
                 .get_initial_bandwidth(
                     enable_credentials_mode,
                     TicketType::V1WireguardEntry,
-                    &self.gateway_directory_client,
+                    entry_gateway_host,
                     &mut wg_entry_gateway_client,
                 )
                 .await?;
@@ -116,7 +213,7 @@ impl Connector {
                 .get_initial_bandwidth(
                     enable_credentials_mode,
                     TicketType::V1WireguardExit,
-                    &self.gateway_directory_client,
+                    exit_gateway_host,
                     &mut wg_exit_gateway_client,
                 )
                 .await?;
@@ -126,15 +223,92 @@ impl Connector {
             (ConnectionData { entry, exit }, bandwidth_controller_handle)
         };
 
+        // Fall back to the port the entry gateway registered us on when it didn't advertise one.
+        if !entry_port_checked {
+            let port = connection_data.entry.endpoint.port();
+            if let Err(e) = check_wg_port(&selected_gateways.entry, port, preferred_ports) {
+                bandwidth_controller_handle.abort();
+                return Err(e);
+            }
+        }
+
+        // Only the entry gateway is reached directly, the exit one is reached through it.
+        if let Some(endpoint_override) = wireguard_tunnel_options.wg_endpoint_override {
+            tracing::info!(
+                "Connecting to the entry gateway at {} instead of {}",
+                endpoint_override,
+                connection_data.entry.endpoint
+            );
+            connection_data
+                .entry
+                .endpoint
+                .set_ip(endpoint_override.ip());
+            if endpoint_override.port() != 0 {
+                connection_data
+                    .entry
+                    .endpoint
+                    .set_port(endpoint_override.port());
+            }
+        }
+
         Ok(ConnectedTunnel::new(
             self.task_manager,
             wg_entry_gateway_client,
             wg_exit_gateway_client,
             connection_data,
             bandwidth_controller_handle,
+            #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+            key_rotation_tx,
+            wireguard_tunnel_options.persistent_keepalive,
+            #[cfg(target_os = "ios")]
+            wireguard_tunnel_options.exit_tunnel_recovery,
         ))
     }
 
+    /// Checks the WireGuard port advertised by the entry gateway against the preferred ports.
+    /// Returns whether the port could be checked, which isn't the case when the gateway can't be
+    /// asked or doesn't advertise it.
+    async fn check_advertised_wg_port(
+        &self,
+        entry: &Gateway,
+        preferred_ports: &[u16],
+    ) -> Result<bool> {
+        if preferred_ports.is_empty() {
+            return Ok(true);
+        }
+
+        let port = match self
+            .gateway_directory_client
+            .gateway_capabilities(entry.identity())
+            .await
+        {
+            Ok(capabilities) => capabilities.wg_port,
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to fetch the capabilities of the entry gateway: {}",
+                    e
+                );
+                None
+            }
+        };
+        let Some(port) = port else {
+            tracing::warn!(
+                "Entry gateway doesn't advertise its wireguard port, checking it once registered"
+            );
+            return Ok(false);
+        };
+        check_wg_port(entry, port, preferred_ports).map(|()| true)
+    }
+
+    // The namespace is joined onto the data path, so it must not be able to point outside of it.
+    fn validate_key_namespace(key_namespace: &str) -> Result<()> {
+        let mut components = Path::new(key_namespace).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) => Ok(()),
+            _ => Err(Error::InvalidKeyNamespace(key_namespace.to_owned())),
+        }
+    }
+
     fn setup_auth_addresses(entry: &Gateway, exit: &Gateway) -> Result<AuthAddresses> {
         let entry_authenticator_address = entry
             .authenticator_address
@@ -148,3 +322,127 @@ impl Connector {
         ))
     }
 }
+
+/// Any port is allowed when there are no preferred ports.
+fn validate_key_rotation_interval(key_rotation_interval: Duration) -> Result<()> {
+    if key_rotation_interval < MIN_KEY_ROTATION_INTERVAL {
+        return Err(Error::InvalidKeyRotationInterval(key_rotation_interval));
+    }
+    Ok(())
+}
+
+fn is_wg_port_allowed(port: u16, preferred: &[u16]) -> bool {
+    preferred.is_empty() || preferred.contains(&port)
+}
+
+fn check_wg_port(gateway: &Gateway, port: u16, preferred: &[u16]) -> Result<()> {
+    if is_wg_port_allowed(port, preferred) {
+        Ok(())
+    } else {
+        Err(Error::WireguardPortNotAllowed {
+            gateway_id: gateway.identity().to_base58_string(),
+            port,
+            preferred_ports: preferred.to_vec(),
+        })
+    }
+}
+
+fn is_routable(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(addr) => {
+            !(addr.is_unspecified()
+                || addr.is_loopback()
+                || addr.is_link_local()
+                || addr.is_multicast()
+                || addr.is_broadcast())
+        }
+        IpAddr::V6(addr) => {
+            !(addr.is_unspecified()
+                || addr.is_loopback()
+                || addr.is_multicast()
+                || addr.segments()[0] & 0xffc0 == 0xfe80)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    use nym_wg_go::PublicKey;
+
+    use super::*;
+
+    fn gateway_data(credentials_spent: bool) -> GatewayData {
+        GatewayData {
+            public_key: PublicKey::from([0; 32]),
+            endpoint: SocketAddr::from((Ipv4Addr::LOCALHOST, 51822)),
+            private_ipv4: Ipv4Addr::new(10, 1, 0, 2),
+            private_ipv6: Ipv6Addr::LOCALHOST,
+            credentials_spent,
+        }
+    }
+
+    #[test]
+    fn credentials_spent_with_either_gateway() {
+        let connection_data = |entry, exit| ConnectionData {
+            entry: gateway_data(entry),
+            exit: gateway_data(exit),
+        };
+        assert!(!connection_data(false, false).credentials_spent());
+        assert!(connection_data(true, false).credentials_spent());
+        assert!(connection_data(false, true).credentials_spent());
+        assert!(connection_data(true, true).credentials_spent());
+        assert_eq!(connection_data(false, false).tickets_spent(), 0);
+        assert_eq!(connection_data(true, false).tickets_spent(), 1);
+        assert_eq!(connection_data(true, true).tickets_spent(), 2);
+    }
+
+    #[test]
+    fn key_rotation_interval_must_be_at_least_an_hour() {
+        assert!(validate_key_rotation_interval(Duration::from_secs(3600)).is_ok());
+        assert!(validate_key_rotation_interval(Duration::from_secs(86400)).is_ok());
+        for secs in [0, 60, 3599] {
+            assert!(
+                validate_key_rotation_interval(Duration::from_secs(secs)).is_err(),
+                "{secs}"
+            );
+        }
+    }
+
+    #[test]
+    fn wg_port_must_be_preferred() {
+        assert!(is_wg_port_allowed(51822, &[]));
+        assert!(is_wg_port_allowed(51822, &[443, 51822]));
+        assert!(!is_wg_port_allowed(51822, &[443]));
+    }
+
+    #[test]
+    fn endpoint_override_must_be_routable() {
+        assert!(is_routable("203.0.113.7".parse().unwrap()));
+        assert!(is_routable("10.1.2.3".parse().unwrap()));
+        assert!(is_routable("2001:db8::7".parse().unwrap()));
+        for addr in [
+            "0.0.0.0",
+            "127.0.0.1",
+            "169.254.1.1",
+            "224.0.0.1",
+            "::",
+            "::1",
+            "fe80::1",
+        ] {
+            assert!(!is_routable(addr.parse().unwrap()), "{addr}");
+        }
+    }
+
+    #[test]
+    fn key_namespace_must_stay_inside_data_path() {
+        assert!(Connector::validate_key_namespace("profile-1").is_ok());
+        for key_namespace in ["", "..", "/tmp", "profile/keys", "../profile"] {
+            assert!(
+                Connector::validate_key_namespace(key_namespace).is_err(),
+                "{key_namespace}"
+            );
+        }
+    }
+}
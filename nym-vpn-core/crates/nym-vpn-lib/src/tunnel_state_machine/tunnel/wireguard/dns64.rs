@@ -49,6 +49,7 @@ impl Dns64Resolution for WgPeer {
         Ok(WgPeer {
             endpoint: reresolve_endpoint(self.endpoint)?,
             public_key: self.public_key,
+            persistent_keepalive: self.persistent_keepalive,
         })
     }
 }
@@ -24,6 +24,8 @@ fn dup_fd(raw_fd: RawFd) -> io::Result<OwnedFd> {
         return Err(io::Error::last_os_error());
     }
 
+    // Own the duplicate right away, so that it's closed should setting the flags fail. The
+    // original is never closed here.
     let owned_fd = unsafe { OwnedFd::from_raw_fd(dup_fd) };
 
     let flags = OFlag::from_bits_retain(fcntl::fcntl(owned_fd.as_raw_fd(), FcntlArg::F_GETFL)?);
@@ -103,7 +103,7 @@ impl TwoHopConfig {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WgForwarderConfig {
     /// Local endpoint for collecting exit wg traffic.
     pub listen_endpoint: SocketAddr,
@@ -1,5 +1,5 @@
-#[cfg(any(target_os = "linux", target_os = "macos"))]
-use std::net::Ipv4Addr;
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+use std::net::IpAddr;
 #[cfg(any(
     target_os = "linux",
     target_os = "macos",
@@ -8,40 +8,52 @@ use std::net::Ipv4Addr;
 ))]
 #[cfg(any(target_os = "android", target_os = "ios"))]
 use std::os::fd::{AsRawFd, IntoRawFd};
-#[cfg(target_os = "android")]
-use std::os::fd::{FromRawFd, OwnedFd};
-#[cfg(any(target_os = "android", target_os = "ios"))]
-use std::sync::Arc;
-use std::{cmp, time::Duration};
+use std::{path::Path, sync::Arc, time::Duration};
 
 #[cfg(any(target_os = "ios", target_os = "android"))]
-use ipnetwork::{IpNetwork, Ipv4Network, Ipv6Network};
+use ipnetwork::Ipv4Network;
+use ipnetwork::{IpNetwork, Ipv6Network};
 use nym_gateway_directory::GatewayMinPerformance;
+use nym_wg_gateway_client::ErrorMessage;
 use time::OffsetDateTime;
-use tokio::{sync::mpsc, task::JoinHandle};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+    time::{Instant, Interval, MissedTickBehavior},
+};
 use tokio_util::sync::CancellationToken;
+#[cfg(any(target_os = "ios", target_os = "android"))]
 use tun::AsyncDevice;
 #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
 use tun::Device;
 
-#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+#[cfg(any(target_os = "linux", target_os = "macos"))]
 use nym_ip_packet_requests::IpPair;
 
 #[cfg(target_os = "linux")]
 use super::default_interface::DefaultInterface;
 #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
 use super::{dns_handler::DnsHandlerHandle, route_handler::RouteHandler};
-#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
-use super::{route_handler::RoutingConfig, tun_ipv6};
 use super::{
+    idle_timer::{self, IdleTimer, IdleTimerEvent},
     tunnel::{
-        self, any_tunnel_handle::AnyTunnelHandle, ConnectedMixnet, MixnetConnectOptions,
-        SelectedGateways,
+        self, any_tunnel_handle::AnyTunnelHandle, mixnet::connector::IprConnectRetry,
+        wireguard::two_hop_config::MIN_IPV6_MTU, ConnectedMixnet, GatewayIpLookup,
+        MixnetConnectOptions, SelectedGateways,
     },
-    ConnectionData, Error, ErrorStateReason, MixnetConnectionData, MixnetEvent, NymConfig, Result,
-    TunnelConnectionData, TunnelSettings, TunnelType, WireguardConnectionData, WireguardNode,
+    ConnectionData, ConnectionEvent, Error, ErrorStateReason, GatewayDiagnostics,
+    MixnetConnectionData, MixnetEvent, NymConfig, Result, TunDevice, TunnelConnectionData,
+    TunnelSettings, TunnelType, WireguardConnectionData, WireguardNode, WireguardTrafficEvent,
+};
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+use super::{
+    route_handler::{self, RoutingConfig},
+    tun_provider::TunProvider,
+    DnsOptions,
 };
 
+#[cfg(any(target_os = "ios", target_os = "android"))]
+use super::tun_fd::TunFd;
 #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
 use super::tunnel::wireguard::connected_tunnel::{
     NetstackTunnelOptions, TunTunTunnelOptions, TunnelOptions,
@@ -54,6 +66,10 @@ use crate::tunnel_provider::android::AndroidTunProvider;
 use crate::tunnel_provider::ios::OSTunProvider;
 use crate::tunnel_state_machine::WireguardMultihopMode;
 
+/// How often the traffic of wireguard tunnels is reported, mixnet tunnels report theirs with the
+/// connection statistics.
+const WIREGUARD_TRAFFIC_INTERVAL: Duration = Duration::from_secs(5);
+
 /// Default MTU for mixnet tun device.
 const DEFAULT_TUN_MTU: u16 = if cfg!(any(target_os = "ios", target_os = "android")) {
     1280
@@ -63,15 +79,6 @@ const DEFAULT_TUN_MTU: u16 = if cfg!(any(target_os = "ios", target_os = "android
 
 pub type TunnelMonitorEventReceiver = mpsc::UnboundedReceiver<TunnelMonitorEvent>;
 
-/// Initial delay between retry attempts.
-const INITIAL_WAIT_DELAY: Duration = Duration::from_secs(2);
-
-/// Wait delay multiplier used for each subsequent retry attempt.
-const DELAY_MULTIPLIER: u32 = 2;
-
-/// Max wait delay between retry attempts.
-const MAX_WAIT_DELAY: Duration = Duration::from_secs(15);
-
 #[derive(Debug, Clone)]
 pub enum TunnelMonitorEvent {
     /// Initializing mixnet client
@@ -88,11 +95,48 @@ pub enum TunnelMonitorEvent {
 
     /// Tunnel went down
     Down(Option<ErrorStateReason>),
+
+    /// Tunnel went down because the gateway bandwidth ran out
+    BandwidthExhausted,
+
+    /// Tunnel went down because no traffic went through it for the idle timeout
+    IdleTimeout,
+}
+
+struct ExportWireguardConfigRequest {
+    include_private_keys: bool,
+    reply_tx: oneshot::Sender<Option<String>>,
+}
+
+/// What's needed to route the traffic into the mixnet tunnel again when it's resumed.
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+#[derive(Debug, Clone)]
+struct MixnetRoutes {
+    tun_name: String,
+    entry_gateway_address: IpAddr,
+    ula_ipv6: Option<Ipv6Network>,
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+impl MixnetRoutes {
+    fn routing_config(&self) -> Result<RoutingConfig> {
+        Ok(RoutingConfig::Mixnet {
+            tun_name: self.tun_name.clone(),
+            entry_gateway_address: self.entry_gateway_address,
+            ula_ipv6: self.ula_ipv6,
+            #[cfg(target_os = "linux")]
+            physical_interface: DefaultInterface::current()?,
+        })
+    }
 }
 
 pub struct TunnelMonitorHandle {
     cancel_token: CancellationToken,
-    join_handle: JoinHandle<Vec<AsyncDevice>>,
+    key_rotation_tx: mpsc::UnboundedSender<()>,
+    export_wg_config_tx: mpsc::UnboundedSender<ExportWireguardConfigRequest>,
+    pause_tx: mpsc::UnboundedSender<bool>,
+    reset_idle_timer_tx: mpsc::UnboundedSender<()>,
+    join_handle: JoinHandle<Vec<Box<dyn TunDevice>>>,
 }
 
 impl TunnelMonitorHandle {
@@ -100,7 +144,53 @@ impl TunnelMonitorHandle {
         self.cancel_token.cancel();
     }
 
-    pub async fn wait(self) -> Vec<AsyncDevice> {
+    /// Rotate the wireguard keys once the tunnel is up.
+    pub fn rotate_keys(&self) {
+        if self.key_rotation_tx.send(()).is_err() {
+            tracing::warn!("Tunnel monitor has exited, not rotating keys");
+        }
+    }
+
+    /// Reply with the wireguard config once the tunnel is up, or `None` when it isn't a
+    /// wireguard tunnel.
+    pub fn export_wireguard_config(
+        &self,
+        include_private_keys: bool,
+        reply_tx: oneshot::Sender<Option<String>>,
+    ) {
+        let request = ExportWireguardConfigRequest {
+            include_private_keys,
+            reply_tx,
+        };
+        if let Err(mpsc::error::SendError(request)) = self.export_wg_config_tx.send(request) {
+            tracing::warn!("Tunnel monitor has exited, not exporting the wireguard config");
+            let _ = request.reply_tx.send(None);
+        }
+    }
+
+    /// Stop passing traffic once the tunnel is up, keeping the mixnet client connected. The
+    /// routes and DNS are reset on desktop.
+    pub fn pause(&self) {
+        if self.pause_tx.send(true).is_err() {
+            tracing::warn!("Tunnel monitor has exited, not pausing");
+        }
+    }
+
+    /// Pass traffic again after a pause, restoring the routes and DNS on desktop.
+    pub fn resume(&self) {
+        if self.pause_tx.send(false).is_err() {
+            tracing::warn!("Tunnel monitor has exited, not resuming");
+        }
+    }
+
+    /// Count the tunnel as idle from now on, if an idle timeout is set.
+    pub fn reset_idle_timer(&self) {
+        if self.reset_idle_timer_tx.send(()).is_err() {
+            tracing::warn!("Tunnel monitor has exited, not resetting the idle timer");
+        }
+    }
+
+    pub async fn wait(self) -> Vec<Box<dyn TunDevice>> {
         self.join_handle
             .await
             .inspect_err(|e| {
@@ -110,6 +200,16 @@ impl TunnelMonitorHandle {
     }
 }
 
+/// What the tunnel monitor needs from a tunnel that was just started.
+struct StartedTunnel {
+    tunnel_conn_data: TunnelConnectionData,
+    tun_name: Option<String>,
+    mtu: u16,
+    credentials_mode_active: bool,
+    tickets_spent: u32,
+    tunnel_handle: AnyTunnelHandle,
+}
+
 pub struct TunnelMonitor {
     monitor_event_sender: mpsc::UnboundedSender<TunnelMonitorEvent>,
     mixnet_event_sender: mpsc::UnboundedSender<MixnetEvent>,
@@ -117,12 +217,24 @@ pub struct TunnelMonitor {
     route_handler: RouteHandler,
     #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
     dns_handler: DnsHandlerHandle,
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    tun_provider: Arc<dyn TunProvider>,
     #[cfg(target_os = "ios")]
     tun_provider: Arc<dyn OSTunProvider>,
     #[cfg(target_os = "android")]
     tun_provider: Arc<dyn AndroidTunProvider>,
+    /// The tun fd handed over by the OS, held until the tunnel is torn down.
+    #[cfg(any(target_os = "ios", target_os = "android"))]
+    tun_fd: Option<TunFd>,
     nym_config: NymConfig,
     tunnel_settings: TunnelSettings,
+    key_rotation_rx: mpsc::UnboundedReceiver<()>,
+    export_wg_config_rx: mpsc::UnboundedReceiver<ExportWireguardConfigRequest>,
+    pause_rx: mpsc::UnboundedReceiver<bool>,
+    reset_idle_timer_rx: mpsc::UnboundedReceiver<()>,
+    /// Set once the routes of the mixnet tunnel are in place.
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    mixnet_routes: Option<MixnetRoutes>,
     cancel_token: CancellationToken,
 }
 
@@ -138,12 +250,18 @@ impl TunnelMonitor {
         route_handler: RouteHandler,
         #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
         dns_handler: DnsHandlerHandle,
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+        tun_provider: Arc<dyn TunProvider>,
         #[cfg(target_os = "ios")] tun_provider: Arc<dyn OSTunProvider>,
         #[cfg(target_os = "android")] tun_provider: Arc<dyn AndroidTunProvider>,
         nym_config: NymConfig,
         tunnel_settings: TunnelSettings,
     ) -> TunnelMonitorHandle {
         let cancel_token = CancellationToken::new();
+        let (key_rotation_tx, key_rotation_rx) = mpsc::unbounded_channel();
+        let (export_wg_config_tx, export_wg_config_rx) = mpsc::unbounded_channel();
+        let (pause_tx, pause_rx) = mpsc::unbounded_channel();
+        let (reset_idle_timer_tx, reset_idle_timer_rx) = mpsc::unbounded_channel();
         let tunnel_monitor = Self {
             monitor_event_sender,
             mixnet_event_sender,
@@ -151,16 +269,27 @@ impl TunnelMonitor {
             route_handler,
             #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
             dns_handler,
-            #[cfg(any(target_os = "ios", target_os = "android"))]
             tun_provider,
+            #[cfg(any(target_os = "ios", target_os = "android"))]
+            tun_fd: None,
             nym_config,
             tunnel_settings,
+            key_rotation_rx,
+            export_wg_config_rx,
+            pause_rx,
+            reset_idle_timer_rx,
+            #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+            mixnet_routes: None,
             cancel_token: cancel_token.clone(),
         };
         let join_handle = tokio::spawn(tunnel_monitor.run(retry_attempt, selected_gateways));
 
         TunnelMonitorHandle {
             cancel_token,
+            key_rotation_tx,
+            export_wg_config_tx,
+            pause_tx,
+            reset_idle_timer_tx,
             join_handle,
         }
     }
@@ -169,27 +298,35 @@ impl TunnelMonitor {
         mut self,
         retry_attempt: u32,
         selected_gateways: Option<SelectedGateways>,
-    ) -> Vec<AsyncDevice> {
-        let (devices, reason) = match self.run_inner(retry_attempt, selected_gateways).await {
-            Ok(devices) => (devices, None),
+    ) -> Vec<Box<dyn TunDevice>> {
+        let (devices, event) = match self.run_inner(retry_attempt, selected_gateways).await {
+            Ok(result) => result,
             Err(e) => {
                 tracing::error!("Tunnel monitor exited with error: {}", e);
-                (vec![], e.error_state_reason())
+                (vec![], TunnelMonitorEvent::Down(e.error_state_reason()))
             }
         };
 
-        self.send_event(TunnelMonitorEvent::Down(reason));
+        // The devices hold their own duplicates, closed once they're dropped after the shutdown.
+        #[cfg(any(target_os = "ios", target_os = "android"))]
+        drop(self.tun_fd.take());
+
+        self.send_event(event);
 
         devices
     }
 
+    /// Returns the tunnel devices and the event reporting why the tunnel went down.
     async fn run_inner(
         &mut self,
         retry_attempt: u32,
         selected_gateways: Option<SelectedGateways>,
-    ) -> Result<Vec<AsyncDevice>> {
+    ) -> Result<(Vec<Box<dyn TunDevice>>, TunnelMonitorEvent)> {
         if retry_attempt > 0 {
-            let delay = wait_delay(retry_attempt);
+            let delay = self
+                .tunnel_settings
+                .connect_retry_options
+                .wait_delay(retry_attempt);
             tracing::debug!("Waiting for {}s before connecting.", delay.as_secs());
 
             self.cancel_token
@@ -198,6 +335,26 @@ impl TunnelMonitor {
                 .ok_or(Error::Tunnel(tunnel::Error::Cancelled))?;
         }
 
+        // There are no ticketbooks to spend without the persistent credential storage
+        if self.tunnel_settings.ephemeral_identity && self.tunnel_settings.enable_credentials_mode {
+            return Err(Error::Tunnel(
+                tunnel::Error::EphemeralIdentityWithCredentials,
+            ));
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+        if let Some(network) =
+            route_handler::invalid_routed_destination(&self.tunnel_settings.routed_destinations)
+        {
+            return Err(Error::InvalidRoutedDestination(network));
+        }
+
+        if self.tunnel_settings.detect_dns_leaks
+            && self.tunnel_settings.dns_leak_probe_interval.is_zero()
+        {
+            return Err(Error::InvalidDnsLeakProbeInterval);
+        }
+
         self.send_event(TunnelMonitorEvent::InitializingClient);
 
         let gateway_performance_options = self.tunnel_settings.gateway_performance_options;
@@ -211,6 +368,14 @@ impl TunnelMonitor {
         );
 
         let mut gateway_config = self.nym_config.gateway_config.clone();
+        if let Some(ttl) = self.tunnel_settings.directory_cache_ttl {
+            gateway_config = gateway_config.with_directory_cache_ttl(ttl);
+        }
+        if let Some(proxy) = self.tunnel_settings.proxy.clone() {
+            gateway_config = gateway_config.with_proxy(proxy);
+        }
+        // Cached next to the keys, so an ephemeral identity leaves no directory behind either
+        gateway_config.directory_cache_path = self.persistent_data_path().map(Path::to_path_buf);
         match gateway_min_performance {
             Ok(gateway_min_performance) => {
                 gateway_config =
@@ -226,15 +391,43 @@ impl TunnelMonitor {
         let selected_gateways = if let Some(selected_gateways) = selected_gateways {
             selected_gateways
         } else {
-            let new_gateways = tunnel::select_gateways(
-                gateway_config.clone(),
-                self.tunnel_settings.tunnel_type,
-                self.tunnel_settings.entry_point.clone(),
-                self.tunnel_settings.exit_point.clone(),
-                None, // todo: provider user agent
-                self.cancel_token.child_token(),
-            )
-            .await?;
+            let sticky_gateways = match self.persistent_data_path() {
+                Some(data_path) if self.tunnel_settings.sticky_gateways => {
+                    tunnel::select_sticky_gateways(
+                        gateway_config.clone(),
+                        self.tunnel_settings.tunnel_type,
+                        data_path,
+                        &self.tunnel_settings.entry_point,
+                        &self.tunnel_settings.exit_point,
+                        &self.tunnel_settings.excluded_gateways,
+                        self.tunnel_settings.max_clock_skew,
+                        None, // todo: provider user agent
+                        self.cancel_token.child_token(),
+                    )
+                    .await?
+                }
+                _ => None,
+            };
+
+            let new_gateways = if let Some(sticky_gateways) = sticky_gateways {
+                sticky_gateways
+            } else {
+                tunnel::select_gateways(
+                    gateway_config.clone(),
+                    self.tunnel_settings.tunnel_type,
+                    self.tunnel_settings.entry_point.clone(),
+                    self.tunnel_settings.exit_point.clone(),
+                    self.tunnel_settings.allow_same_country,
+                    &self.tunnel_settings.excluded_gateways,
+                    self.tunnel_settings.selection_seed,
+                    self.tunnel_settings.region_affinity,
+                    self.tunnel_settings.selection_strategy,
+                    self.tunnel_settings.max_clock_skew,
+                    None, // todo: provider user agent
+                    self.cancel_token.child_token(),
+                )
+                .await?
+            };
 
             self.send_event(TunnelMonitorEvent::SelectedGateways(Box::new(
                 new_gateways.clone(),
@@ -243,14 +436,22 @@ impl TunnelMonitor {
             new_gateways
         };
 
+        // Without a data path all keys are generated in memory and never written to disk
+        if self.tunnel_settings.ephemeral_identity {
+            tracing::info!("Using an ephemeral identity for this connection");
+        }
+        let key_data_path = self.persistent_data_path().map(Path::to_path_buf);
+
         let connect_options = MixnetConnectOptions {
-            data_path: self.nym_config.data_path.clone(),
+            data_path: key_data_path.clone(),
             gateway_config,
             mixnet_client_config: self.tunnel_settings.mixnet_client_config.clone(),
             tunnel_type: self.tunnel_settings.tunnel_type,
             enable_credentials_mode: self.tunnel_settings.enable_credentials_mode,
             selected_gateways: selected_gateways.clone(),
             user_agent: None, // todo: provide user-agent
+            mixnet_client_startup_timeout: self.tunnel_settings.mixnet_client_startup_timeout,
+            shutdown_timeout: self.tunnel_settings.shutdown_timeout,
         };
 
         let mut connected_mixnet =
@@ -260,8 +461,24 @@ impl TunnelMonitor {
             .start_event_listener(self.mixnet_event_sender.clone())
             .await;
 
+        if connected_mixnet.cover_traffic_overridden() {
+            self.send_mixnet_event(MixnetEvent::Connection(
+                ConnectionEvent::CoverTrafficDisabledForWireguard,
+            ));
+        }
+        let mixnet_client_config = connected_mixnet.mixnet_client_config().clone();
+
         let selected_gateways = connected_mixnet.selected_gateways().clone();
-        let (tunnel_conn_data, mut tunnel_handle) = match self.tunnel_settings.tunnel_type {
+        self.tunnel_settings.dns =
+            std::mem::take(&mut self.tunnel_settings.dns).for_exit_gateway(&selected_gateways.exit);
+        let StartedTunnel {
+            tunnel_conn_data,
+            tun_name,
+            mtu,
+            credentials_mode_active,
+            tickets_spent,
+            mut tunnel_handle,
+        } = match self.tunnel_settings.tunnel_type {
             TunnelType::Mixnet => self.start_mixnet_tunnel(connected_mixnet).await?,
             TunnelType::Wireguard => {
                 match self.tunnel_settings.wireguard_tunnel_options.multihop_mode {
@@ -280,7 +497,18 @@ impl TunnelMonitor {
         let conn_data = ConnectionData {
             entry_gateway: Box::new(*selected_gateways.entry.identity()),
             exit_gateway: Box::new(*selected_gateways.exit.identity()),
+            entry_gateway_diagnostics: GatewayDiagnostics::new(
+                &selected_gateways.entry,
+                selected_gateways.entry_latency,
+            ),
+            exit_gateway_diagnostics: GatewayDiagnostics::new(&selected_gateways.exit, None),
             connected_at: None,
+            tun_name,
+            mtu,
+            dns_servers: self.tunnel_settings.dns.ip_addresses().to_vec(),
+            credentials_mode_active,
+            tickets_spent,
+            mixnet_client_config,
             tunnel: tunnel_conn_data,
         };
         self.send_event(TunnelMonitorEvent::EstablishingTunnel(Box::new(
@@ -289,21 +517,135 @@ impl TunnelMonitor {
 
         // todo: do initial ping
 
+        self.send_mixnet_event(MixnetEvent::Connection(ConnectionEvent::MtuConfigured {
+            mtu,
+        }));
+
         let conn_data = ConnectionData {
             connected_at: Some(OffsetDateTime::now_utc()),
             ..conn_data
         };
         self.send_event(TunnelMonitorEvent::Up(conn_data));
 
-        let task_error = self
-            .cancel_token
-            .run_until_cancelled(tunnel_handle.recv_error())
-            .await;
-
-        if let Some(Some(task_error)) = task_error {
-            tracing::error!("Task manager quit with error: {}", task_error);
+        // Only remember gateways that we managed to bring the tunnel up with
+        if self.tunnel_settings.sticky_gateways {
+            if let Some(data_path) = key_data_path.as_deref() {
+                tunnel::store_sticky_gateways(
+                    data_path,
+                    &selected_gateways,
+                    &self.tunnel_settings.entry_point,
+                    &self.tunnel_settings.exit_point,
+                )
+                .await;
+            }
         }
 
+        let mut key_rotation_interval = self.key_rotation_interval();
+        let mut idle_timer = self.idle_timer(&mut tunnel_handle);
+        let mut idle_check_interval = idle_timer.as_ref().map(|_| idle_timer::check_interval());
+        let mut traffic_interval = self.wireguard_traffic_interval();
+        let mut tunnel_paused = false;
+        let mut idle_timed_out = false;
+        let task_error = loop {
+            tokio::select! {
+                _ = self.cancel_token.cancelled() => break None,
+                task_error = tunnel_handle.recv_error() => break task_error,
+                Some(()) = self.key_rotation_rx.recv() => {}
+                _ = next_tick(&mut key_rotation_interval) => {}
+                Some(request) = self.export_wg_config_rx.recv() => {
+                    let config = tunnel_handle.wireguard_config(request.include_private_keys);
+                    let _ = request.reply_tx.send(config);
+                    continue;
+                }
+                Some(paused) = self.pause_rx.recv() => {
+                    if let Err(e) = self.set_paused(&tunnel_handle, paused).await {
+                        tracing::error!("Failed to resume the tunnel, reconnecting: {}", e);
+                        break None;
+                    }
+                    // No traffic goes through while paused, so the idle time starts over.
+                    tunnel_paused = paused;
+                    if let Some(idle_timer) = idle_timer.as_mut() {
+                        idle_timer.reset();
+                    }
+                    continue;
+                }
+                Some(()) = self.reset_idle_timer_rx.recv() => {
+                    if let Some(idle_timer) = idle_timer.as_mut() {
+                        tracing::info!("Resetting the idle timer");
+                        idle_timer.reset();
+                    }
+                    continue;
+                }
+                _ = next_tick(&mut idle_check_interval), if !tunnel_paused => {
+                    let Some(idle_timer) = idle_timer.as_mut() else {
+                        continue;
+                    };
+                    match idle_timer.check(Instant::now(), tunnel_handle.last_activity()) {
+                        Some(IdleTimerEvent::Warning { remaining }) => {
+                            tracing::info!(
+                                "Tunnel is idle, disconnecting in {}s",
+                                remaining.as_secs()
+                            );
+                            self.send_mixnet_event(MixnetEvent::Connection(
+                                ConnectionEvent::IdleTimeoutWarning {
+                                    remaining_secs: remaining.as_secs(),
+                                },
+                            ));
+                        }
+                        Some(IdleTimerEvent::Expired) => {
+                            idle_timed_out = true;
+                            break None;
+                        }
+                        None => {}
+                    }
+                    continue;
+                }
+                _ = next_tick(&mut traffic_interval) => {
+                    if let Some(stats) = tunnel_handle.transfer_stats() {
+                        self.send_mixnet_event(MixnetEvent::WireguardTraffic(
+                            WireguardTrafficEvent {
+                                bytes_sent: stats.tx_bytes,
+                                bytes_received: stats.rx_bytes,
+                            },
+                        ));
+                    }
+                    continue;
+                }
+            }
+
+            match self
+                .cancel_token
+                .run_until_cancelled(tunnel_handle.rotate_keys())
+                .await
+            {
+                Some(Ok(())) => {}
+                Some(Err(e)) => {
+                    tracing::error!("Failed to rotate keys, reconnecting: {}", e);
+                    break None;
+                }
+                None => break None,
+            }
+        };
+
+        let event = match task_error {
+            None if idle_timed_out => TunnelMonitorEvent::IdleTimeout,
+            Some(task_error) => {
+                tracing::error!("Task manager quit with error: {}", task_error);
+                if task_error.is::<ErrorMessage>() {
+                    TunnelMonitorEvent::BandwidthExhausted
+                } else {
+                    // Only the tunnel giving up on its own, e.g. the iOS exit tunnel failing to
+                    // restart, is worth an error state rather than a reconnect.
+                    TunnelMonitorEvent::Down(
+                        task_error
+                            .downcast_ref::<tunnel::Error>()
+                            .and_then(|e| e.error_state_reason()),
+                    )
+                }
+            }
+            None => TunnelMonitorEvent::Down(None),
+        };
+
         tracing::debug!("Wait for tunnel to exit");
         tunnel_handle.cancel();
 
@@ -320,7 +662,84 @@ impl TunnelMonitor {
             tracing::error!("Failed to join on status listener: {}", e);
         }
 
-        Ok(tun_devices)
+        Ok((tun_devices, event))
+    }
+
+    async fn set_paused(&mut self, tunnel_handle: &AnyTunnelHandle, paused: bool) -> Result<()> {
+        if paused {
+            tracing::info!("Pausing the tunnel");
+            tunnel_handle.set_paused(true);
+            #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+            if self.mixnet_routes.is_some() {
+                if let Err(e) = self.dns_handler.reset().await {
+                    tracing::error!("Failed to reset dns: {}", e);
+                }
+                self.route_handler.remove_routes().await;
+            }
+        } else {
+            tracing::info!("Resuming the tunnel");
+            #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+            if let Some(mixnet_routes) = self.mixnet_routes.clone() {
+                self.set_routes(mixnet_routes.routing_config()?).await?;
+                self.set_dns(&mixnet_routes.tun_name).await?;
+            }
+            tunnel_handle.set_paused(false);
+        }
+        Ok(())
+    }
+
+    /// Data path the connection reads its state from and writes it to: the keys, the sticky
+    /// gateways and the directory cache. An ephemeral identity keeps all of it in memory, so it
+    /// has none.
+    fn persistent_data_path(&self) -> Option<&Path> {
+        if self.tunnel_settings.ephemeral_identity {
+            None
+        } else {
+            self.nym_config.data_path.as_deref()
+        }
+    }
+
+    fn key_rotation_interval(&self) -> Option<Interval> {
+        if self.tunnel_settings.tunnel_type != TunnelType::Wireguard {
+            return None;
+        }
+        let period = self
+            .tunnel_settings
+            .wireguard_tunnel_options
+            .key_rotation_interval?;
+        let mut interval = tokio::time::interval_at(Instant::now() + period, period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Some(interval)
+    }
+
+    fn wireguard_traffic_interval(&self) -> Option<Interval> {
+        if self.tunnel_settings.tunnel_type != TunnelType::Wireguard {
+            return None;
+        }
+        let mut interval = tokio::time::interval_at(
+            Instant::now() + WIREGUARD_TRAFFIC_INTERVAL,
+            WIREGUARD_TRAFFIC_INTERVAL,
+        );
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Some(interval)
+    }
+
+    fn idle_timer(&self, tunnel_handle: &mut AnyTunnelHandle) -> Option<IdleTimer> {
+        let timeout = self.tunnel_settings.idle_timeout?;
+        // Primes the wireguard traffic counters the activity is measured against.
+        tunnel_handle.last_activity();
+        Some(IdleTimer::new(
+            timeout,
+            self.tunnel_settings.idle_timeout_warning,
+        ))
+    }
+
+    fn gateway_ip_lookup(&self) -> GatewayIpLookup {
+        GatewayIpLookup::new(
+            self.tunnel_settings.gateway_lookup_retry_options,
+            self.mixnet_event_sender.clone(),
+            self.cancel_token.child_token(),
+        )
     }
 
     fn send_event(&mut self, event: TunnelMonitorEvent) {
@@ -329,25 +748,96 @@ impl TunnelMonitor {
         }
     }
 
+    fn send_mixnet_event(&self, event: MixnetEvent) {
+        if let Err(e) = self.mixnet_event_sender.send(event) {
+            tracing::error!("Failed to send mixnet event: {}", e);
+        }
+    }
+
+    /// Returns the MTU to configure on the mixnet tun device, clamping the requested one to what
+    /// IPv6 requires.
+    fn mixnet_tun_mtu(requested_mtu: Option<u16>) -> u16 {
+        match requested_mtu {
+            Some(mtu) if mtu < MIN_IPV6_MTU => {
+                tracing::warn!(
+                    "Requested MTU {} is below the IPv6 minimum, using {} instead",
+                    mtu,
+                    MIN_IPV6_MTU
+                );
+                MIN_IPV6_MTU
+            }
+            Some(mtu) => mtu,
+            None => DEFAULT_TUN_MTU,
+        }
+    }
+
+    /// Returns the MTU the tun device ended up with, which the OS may have adjusted.
+    fn effective_tun_mtu(tun_device: &dyn TunDevice, configured_mtu: u16) -> u16 {
+        match tun_device.mtu().map(u16::try_from) {
+            Ok(Ok(mtu)) => {
+                if mtu != configured_mtu {
+                    tracing::warn!(
+                        "Tun device MTU is {} instead of the configured {}",
+                        mtu,
+                        configured_mtu
+                    );
+                }
+                mtu
+            }
+            Ok(Err(_)) => configured_mtu,
+            Err(e) => {
+                tracing::warn!("Failed to get tun device MTU: {}", e);
+                configured_mtu
+            }
+        }
+    }
+
+    // The mixnet MTU override only applies to the mixnet tun device, wireguard tunnels are sized
+    // to fit into each other.
+    fn wireguard_tun_mtu(&self, exit_mtu: u16) -> u16 {
+        if let Some(requested_mtu) = self.tunnel_settings.mixnet_tunnel_options.mtu {
+            tracing::info!(
+                "Ignoring requested MTU {} for the wireguard tunnel, using {}",
+                requested_mtu,
+                exit_mtu
+            );
+        }
+        exit_mtu
+    }
+
     async fn start_mixnet_tunnel(
         &mut self,
         connected_mixnet: ConnectedMixnet,
-    ) -> Result<(TunnelConnectionData, AnyTunnelHandle)> {
+    ) -> Result<StartedTunnel> {
         let interface_addrs = self.tunnel_settings.mixnet_tunnel_options.interface_addrs;
+        let ula_ipv6 = self.tunnel_settings.mixnet_tunnel_options.ula_ipv6;
+
+        let ipr_connect_retry = IprConnectRetry::new(
+            &self
+                .tunnel_settings
+                .mixnet_client_config
+                .clone()
+                .unwrap_or_default(),
+            self.mixnet_event_sender.clone(),
+            self.cancel_token.child_token(),
+        );
 
         let connected_tunnel = connected_mixnet
-            .connect_mixnet_tunnel(interface_addrs)
+            .connect_mixnet_tunnel(
+                interface_addrs,
+                ula_ipv6,
+                &ipr_connect_retry,
+                &self.gateway_ip_lookup(),
+            )
             .await?;
         let assigned_addresses = connected_tunnel.assigned_addresses();
 
-        let mtu: u16 = self
-            .tunnel_settings
-            .mixnet_tunnel_options
-            .mtu
-            .unwrap_or(DEFAULT_TUN_MTU);
+        let mtu = Self::mixnet_tun_mtu(self.tunnel_settings.mixnet_tunnel_options.mtu);
 
         #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
-        let tun_device = Self::create_mixnet_device(assigned_addresses.interface_addresses, mtu)?;
+        let tun_device = self
+            .tun_provider
+            .create_mixnet_device(assigned_addresses.interface_addresses, mtu)?;
 
         #[cfg(any(target_os = "ios", target_os = "android"))]
         let tun_device = {
@@ -359,8 +849,14 @@ impl TunnelMonitor {
                             .expect("ipv4/32 to ipnetwork"),
                     ),
                     IpNetwork::V6(
-                        Ipv6Network::new(assigned_addresses.interface_addresses.ipv6, 128)
-                            .expect("ipv6/128 addr to ipnetwork"),
+                        Ipv6Network::new(
+                            assigned_addresses.interface_addresses.ipv6,
+                            assigned_addresses
+                                .ula_ipv6
+                                .map(|network| network.prefix())
+                                .unwrap_or(128),
+                        )
+                        .expect("ipv6 addr to ipnetwork"),
                     ),
                 ],
                 remote_addresses: vec![assigned_addresses.entry_mixnet_gateway_ip],
@@ -369,28 +865,38 @@ impl TunnelMonitor {
 
             let tun_device = self.create_tun_device(packet_tunnel_settings).await?;
             tracing::debug!("Created tun device");
-            tun_device
+            Box::new(tun_device) as Box<dyn TunDevice>
         };
+        let mtu = Self::effective_tun_mtu(tun_device.as_ref(), mtu);
 
         #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
-        {
-            let tun_name = tun_device
-                .get_ref()
-                .name()
-                .map_err(Error::GetTunDeviceName)?;
+        let tun_name = {
+            let tun_name = tun_device.name().map_err(Error::GetTunDeviceName)?;
 
             tracing::debug!("Created tun device: {}", tun_name);
 
-            let routing_config = RoutingConfig::Mixnet {
+            let mixnet_routes = MixnetRoutes {
                 tun_name: tun_name.clone(),
                 entry_gateway_address: assigned_addresses.entry_mixnet_gateway_ip,
-                #[cfg(target_os = "linux")]
-                physical_interface: DefaultInterface::current()?,
+                ula_ipv6: assigned_addresses.ula_ipv6,
             };
 
-            self.set_routes(routing_config).await?;
-            self.set_dns(&tun_name).await?;
-        }
+            if self.tun_provider.configure_routing() {
+                self.set_routes(mixnet_routes.routing_config()?).await?;
+                self.set_dns(&tun_name).await?;
+                self.mixnet_routes = Some(mixnet_routes);
+            } else {
+                tracing::info!(
+                    "Not setting up the routes and DNS for tun device {}",
+                    tun_name
+                );
+            }
+
+            Some(tun_name)
+        };
+
+        #[cfg(any(target_os = "ios", target_os = "android"))]
+        let tun_name = Self::mobile_tun_name(tun_device.as_ref());
 
         let tunnel_conn_data = TunnelConnectionData::Mixnet(MixnetConnectionData {
             nym_address: Box::new(assigned_addresses.mixnet_client_address),
@@ -399,23 +905,47 @@ impl TunnelMonitor {
             ipv6: assigned_addresses.interface_addresses.ipv6,
         });
 
-        let tunnel_handle = AnyTunnelHandle::from(connected_tunnel.run(tun_device).await);
-
-        Ok((tunnel_conn_data, tunnel_handle))
+        let quality_config = self
+            .tunnel_settings
+            .mixnet_tunnel_options
+            .connection_quality;
+        let tunnel_handle =
+            AnyTunnelHandle::from(connected_tunnel.run(tun_device, quality_config).await);
+
+        // The mixnet client fails to connect rather than going without tickets.
+        let credentials_mode_active = self.tunnel_settings.enable_credentials_mode;
+        // The mixnet client pays its gateway on its own, out of our sight.
+        let tickets_spent = 0;
+
+        Ok(StartedTunnel {
+            tunnel_conn_data,
+            tun_name,
+            mtu,
+            credentials_mode_active,
+            tickets_spent,
+            tunnel_handle,
+        })
     }
 
     #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
     async fn start_wireguard_netstack_tunnel(
         &mut self,
         connected_mixnet: ConnectedMixnet,
-    ) -> Result<(TunnelConnectionData, AnyTunnelHandle)> {
+    ) -> Result<StartedTunnel> {
         let connected_tunnel = connected_mixnet
-            .connect_wireguard_tunnel(self.tunnel_settings.enable_credentials_mode)
+            .connect_wireguard_tunnel(
+                self.tunnel_settings.enable_credentials_mode,
+                &self.tunnel_settings.wireguard_tunnel_options,
+                &self.gateway_ip_lookup(),
+            )
             .await?;
         let conn_data = connected_tunnel.connection_data();
+        let mtu = self.wireguard_tun_mtu(connected_tunnel.exit_mtu());
+        let credentials_mode_active = conn_data.credentials_spent();
+        let tickets_spent = conn_data.tickets_spent();
 
         #[cfg(unix)]
-        let exit_tun = Self::create_wireguard_device(
+        let exit_tun = self.tun_provider.create_wireguard_device(
             IpPair {
                 ipv4: conn_data.exit.private_ipv4,
                 ipv6: conn_data.exit.private_ipv6,
@@ -445,6 +975,7 @@ impl TunnelMonitor {
             entry: WireguardNode::from(conn_data.entry.clone()),
             exit: WireguardNode::from(conn_data.exit.clone()),
         });
+        let tun_name = Some(exit_tun_name.clone());
 
         let tunnel_options = TunnelOptions::Netstack(NetstackTunnelOptions {
             #[cfg(unix)]
@@ -458,21 +989,35 @@ impl TunnelMonitor {
 
         let any_tunnel_handle = AnyTunnelHandle::from(tunnel_handle);
 
-        Ok((tunnel_conn_data, any_tunnel_handle))
+        Ok(StartedTunnel {
+            tunnel_conn_data,
+            tun_name,
+            mtu,
+            credentials_mode_active,
+            tickets_spent,
+            tunnel_handle: any_tunnel_handle,
+        })
     }
 
     #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
     async fn start_wireguard_tunnel(
         &mut self,
         connected_mixnet: ConnectedMixnet,
-    ) -> Result<(TunnelConnectionData, AnyTunnelHandle)> {
+    ) -> Result<StartedTunnel> {
         let connected_tunnel = connected_mixnet
-            .connect_wireguard_tunnel(self.tunnel_settings.enable_credentials_mode)
+            .connect_wireguard_tunnel(
+                self.tunnel_settings.enable_credentials_mode,
+                &self.tunnel_settings.wireguard_tunnel_options,
+                &self.gateway_ip_lookup(),
+            )
             .await?;
         let conn_data = connected_tunnel.connection_data();
+        let mtu = self.wireguard_tun_mtu(connected_tunnel.exit_mtu());
+        let credentials_mode_active = conn_data.credentials_spent();
+        let tickets_spent = conn_data.tickets_spent();
 
         #[cfg(unix)]
-        let entry_tun = Self::create_wireguard_device(
+        let entry_tun = self.tun_provider.create_wireguard_device(
             IpPair {
                 ipv4: conn_data.entry.private_ipv4,
                 ipv6: conn_data.entry.private_ipv6,
@@ -489,7 +1034,7 @@ impl TunnelMonitor {
         tracing::info!("Created entry tun device: {}", entry_tun_name);
 
         #[cfg(unix)]
-        let exit_tun = Self::create_wireguard_device(
+        let exit_tun = self.tun_provider.create_wireguard_device(
             IpPair {
                 ipv4: conn_data.exit.private_ipv4,
                 ipv6: conn_data.exit.private_ipv6,
@@ -529,6 +1074,7 @@ impl TunnelMonitor {
             entry: WireguardNode::from(conn_data.entry.clone()),
             exit: WireguardNode::from(conn_data.exit.clone()),
         });
+        let tun_name = Some(exit_tun_name.clone());
 
         let tunnel_options = TunnelOptions::TunTun(TunTunTunnelOptions {
             #[cfg(unix)]
@@ -546,19 +1092,33 @@ impl TunnelMonitor {
 
         let any_tunnel_handle = AnyTunnelHandle::from(tunnel_handle);
 
-        Ok((tunnel_conn_data, any_tunnel_handle))
+        Ok(StartedTunnel {
+            tunnel_conn_data,
+            tun_name,
+            mtu,
+            credentials_mode_active,
+            tickets_spent,
+            tunnel_handle: any_tunnel_handle,
+        })
     }
 
     #[cfg(any(target_os = "ios", target_os = "android"))]
     async fn start_wireguard_netstack_tunnel(
-        &self,
+        &mut self,
         connected_mixnet: ConnectedMixnet,
-    ) -> Result<(TunnelConnectionData, AnyTunnelHandle)> {
+    ) -> Result<StartedTunnel> {
         let connected_tunnel = connected_mixnet
-            .connect_wireguard_tunnel(self.tunnel_settings.enable_credentials_mode)
+            .connect_wireguard_tunnel(
+                self.tunnel_settings.enable_credentials_mode,
+                &self.tunnel_settings.wireguard_tunnel_options,
+                &self.gateway_ip_lookup(),
+            )
             .await?;
 
         let conn_data = connected_tunnel.connection_data();
+        let mtu = self.wireguard_tun_mtu(connected_tunnel.exit_mtu());
+        let credentials_mode_active = conn_data.credentials_spent();
+        let tickets_spent = conn_data.tickets_spent();
 
         let packet_tunnel_settings = tunnel_provider::tunnel_settings::TunnelSettings {
             dns_servers: self.tunnel_settings.dns.ip_addresses().to_vec(),
@@ -577,6 +1137,7 @@ impl TunnelMonitor {
         };
 
         let tun_device = self.create_tun_device(packet_tunnel_settings).await?;
+        let tun_name = Self::mobile_tun_name(&tun_device);
 
         tracing::info!("Created tun device");
 
@@ -599,11 +1160,25 @@ impl TunnelMonitor {
 
         let any_tunnel_handle = AnyTunnelHandle::from(tunnel_handle);
 
-        Ok((tunnel_conn_data, any_tunnel_handle))
+        Ok(StartedTunnel {
+            tunnel_conn_data,
+            tun_name,
+            mtu,
+            credentials_mode_active,
+            tickets_spent,
+            tunnel_handle: any_tunnel_handle,
+        })
     }
 
     #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
     async fn set_dns(&mut self, tun_name: &str) -> Result<()> {
+        // Only the traffic to some networks goes through the tunnel, so leave the system DNS alone
+        // unless asked otherwise.
+        if self.keeps_system_dns() {
+            tracing::debug!("Keeping the system DNS for the partial tunnel");
+            return Ok(());
+        }
+
         let dns_servers = self.tunnel_settings.dns.ip_addresses().to_vec();
 
         self.dns_handler
@@ -614,8 +1189,24 @@ impl TunnelMonitor {
 
     #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
     async fn set_routes(&mut self, routing_config: RoutingConfig) -> Result<()> {
+        let mut routed_destinations = self.tunnel_settings.routed_destinations.clone();
+        // The configured DNS servers are used for all lookups, so keep them in the tunnel.
+        if !routed_destinations.is_empty() && !self.keeps_system_dns() {
+            routed_destinations.extend(
+                self.tunnel_settings
+                    .dns
+                    .ip_addresses()
+                    .iter()
+                    .map(|addr| IpNetwork::from(*addr)),
+            );
+        }
+
         self.route_handler
-            .add_routes(routing_config)
+            .add_routes(
+                routing_config,
+                self.tunnel_settings.allow_lan,
+                &routed_destinations,
+            )
             .await
             .map_err(Error::AddRoutes)?;
 
@@ -623,86 +1214,41 @@ impl TunnelMonitor {
     }
 
     #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
-    fn create_mixnet_device(interface_addresses: IpPair, mtu: u16) -> Result<AsyncDevice> {
-        let mut tun_config = tun::Configuration::default();
-
-        tun_config
-            .address(interface_addresses.ipv4)
-            .mtu(i32::from(mtu))
-            .up();
-
-        #[cfg(target_os = "linux")]
-        tun_config.platform(|platform_config| {
-            platform_config.packet_information(false);
-        });
-
-        let tun_device = tun::create_as_async(&tun_config).map_err(Error::CreateTunDevice)?;
-
-        let tun_name = tun_device
-            .get_ref()
-            .name()
-            .map_err(Error::GetTunDeviceName)?;
-
-        tun_ipv6::set_ipv6_addr(&tun_name, interface_addresses.ipv6)
-            .map_err(Error::SetTunDeviceIpv6Addr)?;
-
-        Ok(tun_device)
-    }
-
-    #[cfg(any(target_os = "linux", target_os = "macos"))]
-    fn create_wireguard_device(
-        interface_addresses: IpPair,
-        destination: Option<Ipv4Addr>,
-        mtu: u16,
-    ) -> Result<AsyncDevice> {
-        let mut tun_config = tun::Configuration::default();
-
-        tun_config
-            .address(interface_addresses.ipv4)
-            .netmask(Ipv4Addr::BROADCAST)
-            .mtu(i32::from(mtu))
-            .up();
-
-        if let Some(destination) = destination {
-            tun_config.destination(destination);
-        }
-
-        #[cfg(target_os = "linux")]
-        tun_config.platform(|platform_config| {
-            platform_config.packet_information(false);
-        });
-
-        let tun_device = tun::create_as_async(&tun_config).map_err(Error::CreateTunDevice)?;
-
-        let tun_name = tun_device
-            .get_ref()
-            .name()
-            .map_err(Error::GetTunDeviceName)?;
-
-        tun_ipv6::set_ipv6_addr(&tun_name, interface_addresses.ipv6)
-            .map_err(Error::SetTunDeviceIpv6Addr)?;
-
-        Ok(tun_device)
+    fn keeps_system_dns(&self) -> bool {
+        !self.tunnel_settings.routed_destinations.is_empty()
+            && self.tunnel_settings.dns == DnsOptions::Default
     }
 
     #[cfg(any(target_os = "ios", target_os = "android"))]
     async fn create_tun_device(
-        &self,
+        &mut self,
         packet_tunnel_settings: tunnel_provider::tunnel_settings::TunnelSettings,
     ) -> Result<AsyncDevice> {
+        // Release the fd of a previous device, if any, before asking for a new one.
+        drop(self.tun_fd.take());
+
         #[cfg(target_os = "ios")]
-        let owned_tun_fd =
-            tunnel_provider::ios::interface::get_tun_fd().map_err(Error::LocateTunDevice)?;
+        let tun_fd = TunFd::new(
+            tunnel_provider::ios::interface::get_tun_fd().map_err(Error::LocateTunDevice)?,
+            self.mixnet_event_sender.clone(),
+        );
 
         #[cfg(target_os = "android")]
-        let owned_tun_fd = {
+        let tun_fd = {
             let raw_tun_fd = self
                 .tun_provider
                 .configure_tunnel(packet_tunnel_settings.into_tunnel_network_settings())
                 .map_err(|e| Error::ConfigureTunnelProvider(e.to_string()))?;
-            unsafe { OwnedFd::from_raw_fd(raw_tun_fd) }
+            // SAFETY: the VPN service hands over the ownership of the fd.
+            unsafe { TunFd::from_raw_fd(raw_tun_fd, self.mixnet_event_sender.clone()) }
         };
 
+        // Duplicate the fd once for the device. Should any of this fail, both the duplicate and
+        // the original are closed on return.
+        let owned_tun_fd = tun_fd
+            .dup()
+            .map_err(|e| Error::Tunnel(tunnel::Error::DupFd(e)))?;
+
         let mut tun_config = tun::Configuration::default();
         tun_config.raw_fd(owned_tun_fd.as_raw_fd());
 
@@ -718,13 +1264,46 @@ impl TunnelMonitor {
 
         // Consume the owned fd, since the device is now responsible for closing the underlying raw fd.
         let _ = owned_tun_fd.into_raw_fd();
+        self.tun_fd = Some(tun_fd);
 
         Ok(device)
     }
+
+    // The tun device is handed to us by the OS, so its name may not be available.
+    #[cfg(any(target_os = "ios", target_os = "android"))]
+    fn mobile_tun_name(tun_device: &dyn TunDevice) -> Option<String> {
+        tun_device
+            .name()
+            .inspect_err(|e| tracing::debug!("Failed to get tun device name: {}", e))
+            .ok()
+            .filter(|name| !name.is_empty())
+    }
 }
 
-fn wait_delay(retry_attempt: u32) -> Duration {
-    let multiplier = retry_attempt.saturating_mul(DELAY_MULTIPLIER);
-    let delay = INITIAL_WAIT_DELAY.saturating_mul(multiplier);
-    cmp::min(delay, MAX_WAIT_DELAY)
+async fn next_tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tunnel_state_machine::tun_device::mock::MockTunDevice;
+
+    #[test]
+    fn effective_tun_mtu_is_the_device_one() {
+        let (device, _peer) = MockTunDevice::new(Some(1400));
+        assert_eq!(TunnelMonitor::effective_tun_mtu(&device, 1500), 1400);
+
+        let (device, _peer) = MockTunDevice::new(None);
+        assert_eq!(TunnelMonitor::effective_tun_mtu(&device, 1500), 1500);
+
+        // Not something a tun device can be configured with
+        let (device, _peer) = MockTunDevice::new(Some(-1));
+        assert_eq!(TunnelMonitor::effective_tun_mtu(&device, 1280), 1280);
+    }
 }
@@ -6,6 +6,7 @@ use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     path::PathBuf,
     str::FromStr,
+    time::Duration,
 };
 
 use ipnetwork::{IpNetwork, Ipv4Network, Ipv6Network};
@@ -290,6 +291,27 @@ impl From<nym_vpn_network_config::Network> for NetworkEnvironment {
     }
 }
 
+/// A network that can be passed to `initEnvironment`. The URLs are unset when the discovery of
+/// the network couldn't be fetched.
+#[derive(uniffi::Record)]
+pub struct AvailableNetwork {
+    pub name: String,
+    pub display_name: String,
+    pub nym_api_url: Option<String>,
+    pub nym_vpn_api_url: Option<String>,
+}
+
+impl From<nym_vpn_network_config::AvailableNetwork> for AvailableNetwork {
+    fn from(network: nym_vpn_network_config::AvailableNetwork) -> Self {
+        AvailableNetwork {
+            name: network.name,
+            display_name: network.display_name,
+            nym_api_url: network.nym_api_url.map(|url| url.to_string()),
+            nym_vpn_api_url: network.nym_vpn_api_url.map(|url| url.to_string()),
+        }
+    }
+}
+
 #[derive(uniffi::Record)]
 pub struct NymNetworkDetails {
     pub network_name: String,
@@ -558,6 +580,7 @@ pub enum EntryPoint {
     Location { location: String },
     RandomLowLatency,
     Random,
+    Hostname { hostname: String },
 }
 
 impl From<EntryPoint> for GwEntryPoint {
@@ -567,6 +590,7 @@ impl From<EntryPoint> for GwEntryPoint {
             EntryPoint::Location { location } => GwEntryPoint::Location { location },
             EntryPoint::RandomLowLatency => GwEntryPoint::RandomLowLatency,
             EntryPoint::Random => GwEntryPoint::Random,
+            EntryPoint::Hostname { hostname } => GwEntryPoint::Hostname { hostname },
         }
     }
 }
@@ -577,6 +601,7 @@ pub enum ExitPoint {
     Address { address: Recipient },
     Gateway { identity: NodeIdentity },
     Location { location: String },
+    Hostname { hostname: String },
 }
 
 impl From<ExitPoint> for GwExitPoint {
@@ -585,6 +610,7 @@ impl From<ExitPoint> for GwExitPoint {
             ExitPoint::Address { address } => GwExitPoint::Address { address },
             ExitPoint::Gateway { identity } => GwExitPoint::Gateway { identity },
             ExitPoint::Location { location } => GwExitPoint::Location { location },
+            ExitPoint::Hostname { hostname } => GwExitPoint::Hostname { hostname },
         }
     }
 }
@@ -621,6 +647,8 @@ pub enum NymVpnStatus {
 pub enum BandwidthStatus {
     NoBandwidth,
     RemainingBandwidth { bandwidth: i64 },
+    ToppedUp { new_total: i64 },
+    LowBandwidth { remaining: i64, threshold: u64 },
 }
 
 impl From<&BandwidthStatusMessage> for BandwidthStatus {
@@ -645,6 +673,17 @@ pub enum ConnectionStatus {
     ExitGatewayRoutingErrorIpv6,
     ConnectedIpv4,
     ConnectedIpv6,
+    RetryingGatewayLookup { attempt: u32, max_retries: u32 },
+    RetryingIprConnect { attempt: u32, max_retries: u32 },
+    DnsLeakDetected { server: IpAddr },
+    Quality { rtt_ms: u32, loss_pct: u8 },
+    Latency { rtt: Duration },
+    SessionExpiring { remaining_secs: u64 },
+    IdleTimeoutWarning { remaining_secs: u64 },
+    MtuConfigured { mtu: u16 },
+    TunFdEstablished { fd: i32 },
+    TunFdReleased { fd: i32 },
+    CoverTrafficDisabledForWireguard,
 }
 
 impl From<ConnectionMonitorStatus> for ConnectionStatus {
@@ -661,6 +700,10 @@ impl From<ConnectionMonitorStatus> for ConnectionStatus {
             }
             ConnectionMonitorStatus::ConnectedIpv4 => ConnectionStatus::ConnectedIpv4,
             ConnectionMonitorStatus::ConnectedIpv6 => ConnectionStatus::ConnectedIpv6,
+            ConnectionMonitorStatus::Quality { rtt_ms, loss_pct } => {
+                ConnectionStatus::Quality { rtt_ms, loss_pct }
+            }
+            ConnectionMonitorStatus::Latency(rtt) => ConnectionStatus::Latency { rtt },
         }
     }
 }
@@ -698,6 +741,54 @@ impl From<nym_vpn_account_controller::AccountStateSummary> for AccountStateSumma
     }
 }
 
+#[derive(uniffi::Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TicketType {
+    V1MixnetEntry,
+    V1MixnetExit,
+    V1WireguardEntry,
+    V1WireguardExit,
+}
+
+impl From<nym_credentials_interface::TicketType> for TicketType {
+    fn from(value: nym_credentials_interface::TicketType) -> Self {
+        match value {
+            nym_credentials_interface::TicketType::V1MixnetEntry => TicketType::V1MixnetEntry,
+            nym_credentials_interface::TicketType::V1MixnetExit => TicketType::V1MixnetExit,
+            nym_credentials_interface::TicketType::V1WireguardEntry => TicketType::V1WireguardEntry,
+            nym_credentials_interface::TicketType::V1WireguardExit => TicketType::V1WireguardExit,
+        }
+    }
+}
+
+#[derive(uniffi::Record, Debug, Clone, PartialEq)]
+pub struct TicketbookInfo {
+    pub id: i64,
+    pub ticket_type: TicketType,
+    pub expiration: OffsetDateTime,
+    pub is_expired: bool,
+    pub issued_tickets: u32,
+    pub claimed_tickets: u32,
+    pub remaining_tickets: u32,
+    /// Remaining bandwidth in bytes.
+    pub remaining_bandwidth: u64,
+}
+
+impl From<nym_vpn_account_controller::AvailableTicketbook> for TicketbookInfo {
+    fn from(value: nym_vpn_account_controller::AvailableTicketbook) -> Self {
+        let remaining = value.remaining();
+        TicketbookInfo {
+            id: value.id,
+            ticket_type: value.typ.into(),
+            expiration: value.expiration.midnight().assume_utc(),
+            is_expired: value.is_expired(),
+            issued_tickets: value.issued_tickets,
+            claimed_tickets: value.claimed_tickets,
+            remaining_tickets: remaining.remaining,
+            remaining_bandwidth: remaining.remaining_size(),
+        }
+    }
+}
+
 #[derive(uniffi::Enum, Debug, Clone, PartialEq)]
 pub enum MnemonicState {
     NotStored,
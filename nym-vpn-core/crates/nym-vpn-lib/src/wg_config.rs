@@ -1,5 +1,5 @@
 use std::{
-    fmt,
+    fmt::{self, Write},
     net::{IpAddr, SocketAddr},
 };
 
@@ -11,7 +11,7 @@ use nym_wg_go::{wireguard_go, PeerConfig, PrivateKey, PublicKey};
 
 use nym_wg_go::netstack;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WgNodeConfig {
     /// Interface configuration
     pub interface: WgInterface,
@@ -20,6 +20,7 @@ pub struct WgNodeConfig {
     pub peer: WgPeer,
 }
 
+#[derive(Clone)]
 pub struct WgInterface {
     /// WG client port.
     pub listen_port: Option<u16>,
@@ -62,6 +63,9 @@ pub struct WgPeer {
 
     /// Gateway endpoint
     pub endpoint: SocketAddr,
+
+    /// Persistent keepalive interval in seconds, `None` to disable.
+    pub persistent_keepalive: Option<u16>,
 }
 
 impl WgPeer {
@@ -95,6 +99,7 @@ impl WgNodeConfig {
                 endpoint: self.peer.endpoint,
                 // todo: limit to loopback?
                 allowed_ips,
+                persistent_keepalive: self.peer.persistent_keepalive,
             }],
         }
     }
@@ -114,10 +119,65 @@ impl WgNodeConfig {
                 preshared_key: None,
                 endpoint: self.peer.endpoint,
                 allowed_ips,
+                persistent_keepalive: self.peer.persistent_keepalive,
             }],
         }
     }
 
+    /// Renders the config in the wg-quick format. The private key is redacted unless
+    /// `include_private_key` is set.
+    pub fn to_wg_quick(&self, include_private_key: bool) -> String {
+        let join = |items: Vec<String>| items.join(", ");
+        let mut out = String::new();
+
+        // Writing to a string can't fail
+        let _ = writeln!(out, "[Interface]");
+        if include_private_key {
+            let _ = writeln!(
+                out,
+                "PrivateKey = {}",
+                self.interface.private_key.to_base64()
+            );
+        } else {
+            let _ = writeln!(out, "PrivateKey = (redacted)");
+        }
+        let _ = writeln!(
+            out,
+            "Address = {}",
+            join(
+                self.interface
+                    .addresses
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect()
+            )
+        );
+        if !self.interface.dns.is_empty() {
+            let _ = writeln!(
+                out,
+                "DNS = {}",
+                join(self.interface.dns.iter().map(|x| x.to_string()).collect())
+            );
+        }
+        let _ = writeln!(out, "MTU = {}", self.interface.mtu);
+        if let Some(listen_port) = self.interface.listen_port {
+            let _ = writeln!(out, "ListenPort = {listen_port}");
+        }
+
+        let _ = writeln!(out, "\n[Peer]");
+        let _ = writeln!(out, "PublicKey = {}", self.peer.public_key.to_base64());
+        let _ = writeln!(out, "Endpoint = {}", self.peer.endpoint);
+        let _ = writeln!(
+            out,
+            "AllowedIPs = {}",
+            join(self.allowed_ips().iter().map(|x| x.to_string()).collect())
+        );
+        if let Some(persistent_keepalive) = self.peer.persistent_keepalive {
+            let _ = writeln!(out, "PersistentKeepalive = {persistent_keepalive}");
+        }
+        out
+    }
+
     fn allowed_ips(&self) -> Vec<IpNetwork> {
         let mut allowed_ips = vec![];
         if self.interface.addresses.iter().any(|x| x.ip().is_ipv4()) {
@@ -136,6 +196,7 @@ impl WgNodeConfig {
         private_key: &nym_crypto::asymmetric::encryption::PrivateKey,
         dns: Vec<IpAddr>,
         mtu: u16,
+        persistent_keepalive: Option<u16>,
     ) -> Self {
         Self {
             interface: WgInterface {
@@ -159,7 +220,52 @@ impl WgNodeConfig {
             peer: WgPeer {
                 public_key: PublicKey::from(*gateway_data.public_key.as_bytes()),
                 endpoint: gateway_data.endpoint,
+                persistent_keepalive,
             },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_config() -> WgNodeConfig {
+        WgNodeConfig {
+            interface: WgInterface {
+                listen_port: None,
+                private_key: PrivateKey::from([1u8; 32]),
+                addresses: vec![
+                    "10.1.0.2/32".parse().unwrap(),
+                    "fc01::2/128".parse().unwrap(),
+                ],
+                dns: vec!["1.1.1.1".parse().unwrap()],
+                mtu: 1420,
+                #[cfg(target_os = "linux")]
+                fwmark: None,
+            },
+            peer: WgPeer {
+                public_key: PublicKey::from([2u8; 32]),
+                endpoint: "1.2.3.4:51822".parse().unwrap(),
+                persistent_keepalive: Some(25),
+            },
+        }
+    }
+
+    #[test]
+    fn wg_quick_config_redacts_private_key_by_default() {
+        let config = node_config();
+        let private_key = config.interface.private_key.to_base64();
+
+        let redacted = config.to_wg_quick(false);
+        assert!(redacted.contains("PrivateKey = (redacted)"));
+        assert!(!redacted.contains(&private_key));
+        assert!(redacted.contains("Address = 10.1.0.2/32, fc01::2/128"));
+        assert!(redacted.contains("Endpoint = 1.2.3.4:51822"));
+        assert!(redacted.contains("AllowedIPs = 0.0.0.0/0, ::/0"));
+        assert!(redacted.contains("PersistentKeepalive = 25"));
+
+        let full = config.to_wg_quick(true);
+        assert!(full.contains(&format!("PrivateKey = {private_key}")));
+    }
+}
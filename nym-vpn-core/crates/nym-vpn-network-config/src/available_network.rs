@@ -0,0 +1,119 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::path::Path;
+
+use url::Url;
+
+use super::{discovery::Discovery, envs::RegisteredNetworks};
+
+const MAINNET: &str = "mainnet";
+
+/// A network that can be selected by its name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AvailableNetwork {
+    pub name: String,
+    pub display_name: String,
+
+    /// Unset when the discovery of the network couldn't be fetched.
+    pub nym_api_url: Option<Url>,
+    pub nym_vpn_api_url: Option<Url>,
+}
+
+impl AvailableNetwork {
+    fn new(name: String, discovery: Option<Discovery>) -> Self {
+        let (nym_api_url, nym_vpn_api_url) = discovery
+            .map(|discovery| (discovery.nym_api_url, discovery.nym_vpn_api_url))
+            .unzip();
+        Self {
+            display_name: display_name(&name),
+            name,
+            nym_api_url,
+            nym_vpn_api_url,
+        }
+    }
+}
+
+fn display_name(network_name: &str) -> String {
+    match network_name {
+        "qa" => "QA".to_owned(),
+        _ => {
+            let mut chars = network_name.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        }
+    }
+}
+
+// Mainnet first, the rest by name.
+fn sorted_names(networks: RegisteredNetworks) -> Vec<String> {
+    let mut names = networks.into_names();
+    names.sort_by(|a, b| (a != MAINNET, a).cmp(&(b != MAINNET, b)));
+    names
+}
+
+/// Lists the registered networks, using the files cached in the config directory. Falls back to
+/// the built-in networks when the registry can't be reached and nothing is cached.
+pub(super) fn list_networks(config_dir: &Path) -> Vec<AvailableNetwork> {
+    let networks = RegisteredNetworks::ensure_exists(config_dir)
+        .inspect_err(|err| tracing::warn!("Using the built-in networks: {err}"))
+        .unwrap_or_default();
+
+    sorted_names(networks)
+        .into_iter()
+        .map(|name| {
+            let discovery = Discovery::ensure_exists(config_dir, &name)
+                .inspect_err(|err| tracing::warn!("No discovery for network {name}: {err}"))
+                .ok();
+            AvailableNetwork::new(name, discovery)
+        })
+        .collect()
+}
+
+/// Lists the registered networks directly from the registry without persisting them. Falls back
+/// to the built-in networks when the registry can't be reached.
+pub(super) fn fetch_networks() -> Vec<AvailableNetwork> {
+    let networks = RegisteredNetworks::fetch()
+        .inspect_err(|err| tracing::warn!("Using the built-in networks: {err}"))
+        .unwrap_or_default();
+
+    sorted_names(networks)
+        .into_iter()
+        .map(|name| {
+            let discovery = Discovery::fetch(&name)
+                .inspect_err(|err| tracing::warn!("No discovery for network {name}: {err}"))
+                .ok()
+                .or_else(|| (name == MAINNET).then(Discovery::default));
+            AvailableNetwork::new(name, discovery)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_name() {
+        assert_eq!(display_name("mainnet"), "Mainnet");
+        assert_eq!(display_name("qa"), "QA");
+        assert_eq!(display_name(""), "");
+    }
+
+    #[test]
+    fn test_mainnet_is_listed_first() {
+        let names = sorted_names(RegisteredNetworks::default());
+        assert_eq!(names.first().map(String::as_str), Some(MAINNET));
+        assert!(names[1..].windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_built_in_mainnet_has_urls() {
+        let network = AvailableNetwork::new(MAINNET.to_owned(), Some(Discovery::default()));
+        assert_eq!(network.display_name, "Mainnet");
+        assert!(network.nym_api_url.is_some());
+        assert!(network.nym_vpn_api_url.is_some());
+    }
+}
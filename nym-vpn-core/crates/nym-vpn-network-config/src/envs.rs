@@ -67,7 +67,7 @@ impl RegisteredNetworks {
         ENVS_WELLKNOWN.parse().map_err(Into::into)
     }
 
-    fn fetch() -> anyhow::Result<Self> {
+    pub(super) fn fetch() -> anyhow::Result<Self> {
         let url = Self::endpoint()?;
         tracing::debug!("Fetching registered networks from: {}", url);
 
@@ -87,6 +87,10 @@ impl RegisteredNetworks {
         serde_json::from_str(&text_response).with_context(|| "Failed to parse envs response")
     }
 
+    pub(super) fn into_names(self) -> Vec<String> {
+        self.inner.into_iter().collect()
+    }
+
     fn read_from_file(config_dir: &Path) -> anyhow::Result<Self> {
         let path = Self::path(config_dir);
         tracing::debug!(
@@ -7,6 +7,7 @@ pub mod system_messages;
 pub(crate) mod response;
 
 mod account_management;
+mod available_network;
 mod discovery;
 mod envs;
 mod nym_network;
@@ -15,6 +16,7 @@ mod refresh;
 mod util;
 
 pub use account_management::{AccountManagement, ParsedAccountLinks};
+pub use available_network::AvailableNetwork;
 pub use feature_flags::FeatureFlags;
 use futures_util::FutureExt;
 pub use nym_network::NymNetwork;
@@ -110,6 +112,18 @@ pub fn discover_networks(config_path: &Path) -> anyhow::Result<RegisteredNetwork
     RegisteredNetworks::ensure_exists(config_path)
 }
 
+/// Lists the networks that can be passed to [`discover_env`], with their API URLs when their
+/// discovery could be fetched. Falls back to the built-in networks when the network registry is
+/// unreachable.
+pub fn list_networks(config_path: &Path) -> Vec<AvailableNetwork> {
+    available_network::list_networks(config_path)
+}
+
+/// Same as [`list_networks`] but without persisting anything to disk, like [`Network::fetch`].
+pub fn fetch_networks() -> Vec<AvailableNetwork> {
+    available_network::fetch_networks()
+}
+
 pub fn discover_env(config_path: &Path, network_name: &str) -> anyhow::Result<Network> {
     tracing::trace!(
         "Discovering network details: config_path={:?}, network_name={}",
@@ -7,7 +7,10 @@ use nym_crypto::asymmetric::ed25519;
 use nym_pemstore::{traits::PemStorableKeyPair, KeyPairPath};
 use rand::SeedableRng as _;
 
-use crate::keys::{DeviceKeys, KeyStore};
+use crate::{
+    keys::{DeviceKeys, KeyStore},
+    permissions::StoragePermissions,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum OnDiskKeysError {
@@ -28,6 +31,7 @@ pub enum OnDiskKeysError {
 
 pub struct OnDiskKeys {
     paths: DeviceKeysPaths,
+    permissions: StoragePermissions,
 }
 
 pub struct DeviceKeysPaths {
@@ -66,7 +70,16 @@ impl DeviceKeysPaths {
 
 impl OnDiskKeys {
     pub fn new(paths: DeviceKeysPaths) -> Self {
-        OnDiskKeys { paths }
+        OnDiskKeys {
+            paths,
+            permissions: StoragePermissions::default(),
+        }
+    }
+
+    /// Sets the permissions of the key files and their directory, private by default.
+    pub fn with_permissions(mut self, permissions: StoragePermissions) -> Self {
+        self.permissions = permissions;
+        self
     }
 
     fn load_device_keypair(&self) -> Result<ed25519::KeyPair, OnDiskKeysError> {
@@ -92,13 +105,24 @@ impl OnDiskKeys {
         paths: KeyPairPath,
         name: impl Into<String>,
     ) -> Result<(), OnDiskKeysError> {
-        nym_pemstore::store_keypair(keypair, &paths).map_err(|error| {
-            OnDiskKeysError::UnableToStoreKeys {
+        nym_pemstore::store_keypair(keypair, &paths)
+            .and_then(|_| self.restrict_permissions(&paths))
+            .map_err(|error| OnDiskKeysError::UnableToStoreKeys {
                 paths,
                 name: name.into(),
                 error,
-            }
-        })
+            })
+    }
+
+    fn restrict_permissions(&self, paths: &KeyPairPath) -> std::io::Result<()> {
+        let files = [&paths.private_key_path, &paths.public_key_path];
+        for file in files {
+            self.permissions.apply_to_file(file)?;
+        }
+        for dir in files.into_iter().filter_map(|file| file.parent()) {
+            self.permissions.apply_to_dir(dir)?;
+        }
+        Ok(())
     }
 
     fn load_keys(&self) -> Result<DeviceKeys, OnDiskKeysError> {
@@ -3,5 +3,6 @@
 
 pub mod keys;
 pub mod mnemonic;
+pub mod permissions;
 
 pub trait VpnStorage: mnemonic::MnemonicStorage + keys::KeyStore {}
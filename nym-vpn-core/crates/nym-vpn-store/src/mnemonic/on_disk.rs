@@ -1,14 +1,24 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-#[cfg(unix)]
-use std::os::unix::fs::PermissionsExt;
 use std::{
+    collections::BTreeMap,
     fs::{self, File},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
 };
 
+use serde::{Deserialize, Serialize};
+
 use super::{MnemonicStorage, MnemonicStorageError, StoredMnemonic};
+use crate::permissions::StoragePermissions;
+
+const DEFAULT_MNEMONIC_NAME: &str = "default";
+
+// All mnemonics share one file, and storage instances are created for the same path all over the
+// place. Changes to the file are read-modify-write, so they are serialized across the process to
+// not lose each other's entries.
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
 
 #[derive(Debug, thiserror::Error)]
 pub enum OnDiskMnemonicStorageError {
@@ -32,6 +42,33 @@ pub enum OnDiskMnemonicStorageError {
 
     #[error("failed to remove mnemonic file")]
     RemoveError(#[source] std::io::Error),
+
+    #[error("mnemonic already stored under name: {name}")]
+    NamedMnemonicAlreadyStored { name: String },
+
+    #[error("no mnemonic stored under name: {name}")]
+    NamedMnemonicNotFound { name: String },
+}
+
+// The file either contains a single mnemonic, which is how the default mnemonic has always been
+// stored, or a map of named mnemonics. A file with a single mnemonic is migrated to a map the
+// first time a named mnemonic is stored.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum StoredMnemonics {
+    Single(StoredMnemonic),
+    Named(BTreeMap<String, StoredMnemonic>),
+}
+
+impl StoredMnemonics {
+    fn into_named(self) -> BTreeMap<String, StoredMnemonic> {
+        match self {
+            StoredMnemonics::Single(stored_mnemonic) => {
+                BTreeMap::from([(stored_mnemonic.name.clone(), stored_mnemonic)])
+            }
+            StoredMnemonics::Named(stored_mnemonics) => stored_mnemonics,
+        }
+    }
 }
 
 impl MnemonicStorageError for OnDiskMnemonicStorageError {
@@ -39,52 +76,96 @@ impl MnemonicStorageError for OnDiskMnemonicStorageError {
         matches!(
             self,
             OnDiskMnemonicStorageError::MnemonicAlreadyStored { .. }
+                | OnDiskMnemonicStorageError::NamedMnemonicAlreadyStored { .. }
         )
     }
 }
 
 pub struct OnDiskMnemonicStorage {
     path: PathBuf,
+    permissions: StoragePermissions,
 }
 
 impl OnDiskMnemonicStorage {
     pub fn new(path: PathBuf) -> Self {
-        Self { path }
+        Self {
+            path,
+            permissions: StoragePermissions::default(),
+        }
     }
-}
 
-impl MnemonicStorage for OnDiskMnemonicStorage {
-    type StorageError = OnDiskMnemonicStorageError;
+    /// Sets the permissions of the mnemonic file and its directory, private by default.
+    pub fn with_permissions(mut self, permissions: StoragePermissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
 
-    async fn store_mnemonic(
+    /// Store a mnemonic under the given name, alongside any other mnemonics already stored in
+    /// the same file. Fails if a mnemonic is already stored under that name.
+    pub async fn store_named_mnemonic(
         &self,
+        name: &str,
         mnemonic: bip39::Mnemonic,
     ) -> Result<(), OnDiskMnemonicStorageError> {
-        let name = "default".to_string();
-        let nonce = 0;
-        let stored_mnemonic = StoredMnemonic {
-            name,
-            mnemonic,
-            nonce,
-        };
-
-        // Error if the file already exists
-        if self.path.exists() {
-            return Err(OnDiskMnemonicStorageError::MnemonicAlreadyStored {
-                path: self.path.clone(),
+        let _guard = lock_for_write();
+        let mut stored_mnemonics = self.read_stored_mnemonics()?;
+        if stored_mnemonics.contains_key(name) {
+            return Err(OnDiskMnemonicStorageError::NamedMnemonicAlreadyStored {
+                name: name.to_string(),
             });
         }
 
-        // Another layer of defense, only create the file if it doesn't already exist
-        let file = std::fs::OpenOptions::new()
-            .create_new(true)
-            .write(true)
-            .open(&self.path)
-            .map_err(|err| OnDiskMnemonicStorageError::FileCreateError {
-                path: self.path.clone(),
-                source: err,
-            })?;
+        stored_mnemonics.insert(
+            name.to_string(),
+            StoredMnemonic {
+                name: name.to_string(),
+                mnemonic,
+                nonce: 0,
+            },
+        );
+        self.write_stored_mnemonics(&StoredMnemonics::Named(stored_mnemonics))
+    }
+
+    pub async fn load_named_mnemonic(
+        &self,
+        name: &str,
+    ) -> Result<bip39::Mnemonic, OnDiskMnemonicStorageError> {
+        self.read_stored_mnemonics()?
+            .get(name)
+            .map(|s| s.mnemonic.clone())
+            .ok_or_else(|| OnDiskMnemonicStorageError::NamedMnemonicNotFound {
+                name: name.to_string(),
+            })
+    }
 
+    /// List the names of all stored mnemonics.
+    pub async fn list_mnemonics(&self) -> Result<Vec<String>, OnDiskMnemonicStorageError> {
+        Ok(self.read_stored_mnemonics()?.into_keys().collect())
+    }
+
+    // Returns an empty map if there is no file yet.
+    fn read_stored_mnemonics(
+        &self,
+    ) -> Result<BTreeMap<String, StoredMnemonic>, OnDiskMnemonicStorageError> {
+        if !self.path.exists() {
+            return Ok(BTreeMap::new());
+        }
+
+        // Make sure that the file has the requested permissions
+        self.permissions
+            .apply_to_file(&self.path)
+            .map_err(OnDiskMnemonicStorageError::FileOpenError)?;
+
+        let file = File::open(&self.path).map_err(OnDiskMnemonicStorageError::FileOpenError)?;
+        serde_json::from_reader(file)
+            .map_err(OnDiskMnemonicStorageError::ReadError)
+            .map(StoredMnemonics::into_named)
+    }
+
+    fn write_stored_mnemonics(
+        &self,
+        stored_mnemonics: &StoredMnemonics,
+    ) -> Result<(), OnDiskMnemonicStorageError> {
         // Create parent directories
         if let Some(parent) = self.path.parent() {
             fs::create_dir_all(parent).map_err(|err| {
@@ -94,60 +175,148 @@ impl MnemonicStorage for OnDiskMnemonicStorage {
                 }
             })?;
 
-            #[cfg(unix)]
-            {
-                // Set directory permissions to 700 (rwx------)
-                let permissions = fs::Permissions::from_mode(0o700);
-                fs::set_permissions(parent, permissions).map_err(|source| {
-                    OnDiskMnemonicStorageError::FileCreateError {
-                        path: parent.to_path_buf(),
-                        source,
-                    }
-                })?;
+            self.permissions.apply_to_dir(parent).map_err(|source| {
+                OnDiskMnemonicStorageError::FileCreateError {
+                    path: parent.to_path_buf(),
+                    source,
+                }
+            })?;
+        }
+
+        // Write the mnemonics to a file next to the stored one, and only then move it into place,
+        // so that a failed write never costs us the mnemonics already stored
+        let staged_path = staged_path(&self.path);
+        self.write_staged_mnemonics(&staged_path, stored_mnemonics)
+            .inspect_err(|_| {
+                fs::remove_file(&staged_path).ok();
+            })?;
+
+        fs::rename(&staged_path, &self.path).map_err(|source| {
+            fs::remove_file(&staged_path).ok();
+            OnDiskMnemonicStorageError::FileCreateError {
+                path: self.path.clone(),
+                source,
             }
+        })
+    }
 
-            // TODO: same for windows
-        }
+    fn write_staged_mnemonics(
+        &self,
+        staged_path: &Path,
+        stored_mnemonics: &StoredMnemonics,
+    ) -> Result<(), OnDiskMnemonicStorageError> {
+        let create_error = |source| OnDiskMnemonicStorageError::FileCreateError {
+            path: staged_path.to_path_buf(),
+            source,
+        };
+
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(staged_path)
+            .map_err(create_error)?;
 
-        serde_json::to_writer(file, &stored_mnemonic)
+        // Restrict permissions before writing anything to the file
+        self.permissions
+            .apply_to_file(staged_path)
+            .map_err(create_error)?;
+
+        serde_json::to_writer(&file, stored_mnemonics)
             .map_err(OnDiskMnemonicStorageError::WriteError)?;
+        file.sync_all().map_err(create_error)
+    }
+}
 
-        #[cfg(unix)]
-        {
-            // Set directory permissions to 600 (rw------)
-            let permissions = fs::Permissions::from_mode(0o600);
-            fs::set_permissions(self.path.clone(), permissions).map_err(|source| {
-                OnDiskMnemonicStorageError::FileCreateError {
-                    path: self.path.clone(),
-                    source,
-                }
-            })?;
+fn lock_for_write() -> MutexGuard<'static, ()> {
+    // The lock guards no data, so a panic while holding it leaves nothing inconsistent behind
+    WRITE_LOCK.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn staged_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".new");
+    path.with_file_name(file_name)
+}
+
+impl MnemonicStorage for OnDiskMnemonicStorage {
+    type StorageError = OnDiskMnemonicStorageError;
+
+    async fn store_mnemonic(
+        &self,
+        mnemonic: bip39::Mnemonic,
+    ) -> Result<(), OnDiskMnemonicStorageError> {
+        let _guard = lock_for_write();
+
+        // Named mnemonics may already be stored in the file, only the default entry must be absent
+        let mut stored_mnemonics = self.read_stored_mnemonics()?;
+        if stored_mnemonics.contains_key(DEFAULT_MNEMONIC_NAME) {
+            return Err(OnDiskMnemonicStorageError::MnemonicAlreadyStored {
+                path: self.path.clone(),
+            });
         }
 
-        // TODO: same for windows
+        let name = DEFAULT_MNEMONIC_NAME.to_string();
+        let nonce = 0;
+        let stored_mnemonic = StoredMnemonic {
+            name,
+            mnemonic,
+            nonce,
+        };
+
+        // Keep the single mnemonic format when there is nothing else to store, so that the file
+        // stays readable by older versions
+        if stored_mnemonics.is_empty() {
+            return self.write_stored_mnemonics(&StoredMnemonics::Single(stored_mnemonic));
+        }
 
-        Ok(())
+        stored_mnemonics.insert(DEFAULT_MNEMONIC_NAME.to_string(), stored_mnemonic);
+        self.write_stored_mnemonics(&StoredMnemonics::Named(stored_mnemonics))
     }
 
     async fn load_mnemonic(&self) -> Result<bip39::Mnemonic, OnDiskMnemonicStorageError> {
         tracing::debug!("Opening: {}", self.path.display());
 
-        // Make sure that the file has permissions set to 600 (rw------)
-        #[cfg(unix)]
-        {
-            let permissions = fs::Permissions::from_mode(0o600);
-            fs::set_permissions(&self.path, permissions)
-                .map_err(OnDiskMnemonicStorageError::FileOpenError)?;
-        }
+        // Make sure that the file has the requested permissions
+        self.permissions
+            .apply_to_file(&self.path)
+            .map_err(OnDiskMnemonicStorageError::FileOpenError)?;
 
         let file = File::open(&self.path).map_err(OnDiskMnemonicStorageError::FileOpenError)?;
-        serde_json::from_reader(file)
-            .map_err(OnDiskMnemonicStorageError::ReadError)
-            .map(|s: StoredMnemonic| s.mnemonic.clone())
+        let stored_mnemonics: StoredMnemonics =
+            serde_json::from_reader(file).map_err(OnDiskMnemonicStorageError::ReadError)?;
+        match stored_mnemonics {
+            StoredMnemonics::Single(s) => Ok(s.mnemonic.clone()),
+            StoredMnemonics::Named(stored_mnemonics) => stored_mnemonics
+                .get(DEFAULT_MNEMONIC_NAME)
+                .map(|s| s.mnemonic.clone())
+                .ok_or_else(|| OnDiskMnemonicStorageError::NamedMnemonicNotFound {
+                    name: DEFAULT_MNEMONIC_NAME.to_string(),
+                }),
+        }
     }
 
     async fn remove_mnemonic(&self) -> Result<(), OnDiskMnemonicStorageError> {
-        std::fs::remove_file(&self.path).map_err(OnDiskMnemonicStorageError::RemoveError)
+        let _guard = lock_for_write();
+        if !self.path.exists() {
+            return Err(OnDiskMnemonicStorageError::RemoveError(
+                std::io::ErrorKind::NotFound.into(),
+            ));
+        }
+
+        // Only remove the default entry, the named mnemonics stored next to it are kept
+        let mut stored_mnemonics = self.read_stored_mnemonics()?;
+        if stored_mnemonics.remove(DEFAULT_MNEMONIC_NAME).is_none() {
+            return Err(OnDiskMnemonicStorageError::NamedMnemonicNotFound {
+                name: DEFAULT_MNEMONIC_NAME.to_string(),
+            });
+        }
+
+        if stored_mnemonics.is_empty() {
+            std::fs::remove_file(&self.path).map_err(OnDiskMnemonicStorageError::RemoveError)
+        } else {
+            self.write_stored_mnemonics(&StoredMnemonics::Named(stored_mnemonics))
+        }
     }
 }
 
@@ -224,4 +393,226 @@ mod tests {
             Err(OnDiskMnemonicStorageError::ReadError(_))
         ));
     }
+
+    #[tokio::test]
+    async fn store_and_list_named_mnemonics() {
+        let first = bip39::Mnemonic::generate_in(bip39::Language::English, 12).unwrap();
+        let second = bip39::Mnemonic::generate_in(bip39::Language::English, 12).unwrap();
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.txt");
+        let mnemonic_storage = OnDiskMnemonicStorage::new(path.clone());
+        mnemonic_storage
+            .store_named_mnemonic("first", first.clone())
+            .await
+            .unwrap();
+        mnemonic_storage
+            .store_named_mnemonic("second", second.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            mnemonic_storage.list_mnemonics().await.unwrap(),
+            vec!["first".to_string(), "second".to_string()]
+        );
+        assert_eq!(
+            mnemonic_storage.load_named_mnemonic("first").await.unwrap(),
+            first
+        );
+        assert_eq!(
+            mnemonic_storage
+                .load_named_mnemonic("second")
+                .await
+                .unwrap(),
+            second
+        );
+    }
+
+    #[tokio::test]
+    async fn store_named_twice_fails() {
+        let mnemonic = bip39::Mnemonic::generate_in(bip39::Language::English, 12).unwrap();
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.txt");
+        let mnemonic_storage = OnDiskMnemonicStorage::new(path.clone());
+        mnemonic_storage
+            .store_named_mnemonic("first", mnemonic.clone())
+            .await
+            .unwrap();
+
+        let result = mnemonic_storage
+            .store_named_mnemonic("first", mnemonic)
+            .await;
+        assert!(matches!(
+            result,
+            Err(OnDiskMnemonicStorageError::NamedMnemonicAlreadyStored { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn store_named_migrates_default_mnemonic() {
+        let default = bip39::Mnemonic::generate_in(bip39::Language::English, 12).unwrap();
+        let named = bip39::Mnemonic::generate_in(bip39::Language::English, 12).unwrap();
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.txt");
+        let mnemonic_storage = OnDiskMnemonicStorage::new(path.clone());
+        mnemonic_storage
+            .store_mnemonic(default.clone())
+            .await
+            .unwrap();
+        mnemonic_storage
+            .store_named_mnemonic("named", named.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            mnemonic_storage.list_mnemonics().await.unwrap(),
+            vec!["default".to_string(), "named".to_string()]
+        );
+        assert_eq!(mnemonic_storage.load_mnemonic().await.unwrap(), default);
+        assert_eq!(
+            mnemonic_storage.load_named_mnemonic("named").await.unwrap(),
+            named
+        );
+    }
+
+    #[tokio::test]
+    async fn remove_default_keeps_named_mnemonics() {
+        let default = bip39::Mnemonic::generate_in(bip39::Language::English, 12).unwrap();
+        let named = bip39::Mnemonic::generate_in(bip39::Language::English, 12).unwrap();
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.txt");
+        let mnemonic_storage = OnDiskMnemonicStorage::new(path.clone());
+        mnemonic_storage.store_mnemonic(default).await.unwrap();
+        mnemonic_storage
+            .store_named_mnemonic("named", named.clone())
+            .await
+            .unwrap();
+
+        mnemonic_storage.remove_mnemonic().await.unwrap();
+
+        assert!(matches!(
+            mnemonic_storage.load_mnemonic().await,
+            Err(OnDiskMnemonicStorageError::NamedMnemonicNotFound { .. })
+        ));
+        assert_eq!(
+            mnemonic_storage.list_mnemonics().await.unwrap(),
+            vec!["named".to_string()]
+        );
+        assert_eq!(
+            mnemonic_storage.load_named_mnemonic("named").await.unwrap(),
+            named
+        );
+    }
+
+    #[tokio::test]
+    async fn remove_last_mnemonic_removes_file() {
+        let mnemonic = bip39::Mnemonic::generate_in(bip39::Language::English, 12).unwrap();
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.txt");
+        let mnemonic_storage = OnDiskMnemonicStorage::new(path.clone());
+        mnemonic_storage.store_mnemonic(mnemonic).await.unwrap();
+
+        mnemonic_storage.remove_mnemonic().await.unwrap();
+
+        assert!(!path.exists());
+        assert!(matches!(
+            mnemonic_storage.remove_mnemonic().await,
+            Err(OnDiskMnemonicStorageError::RemoveError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn store_default_next_to_named_mnemonics() {
+        let default = bip39::Mnemonic::generate_in(bip39::Language::English, 12).unwrap();
+        let named = bip39::Mnemonic::generate_in(bip39::Language::English, 12).unwrap();
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.txt");
+        let mnemonic_storage = OnDiskMnemonicStorage::new(path.clone());
+        mnemonic_storage
+            .store_named_mnemonic("named", named.clone())
+            .await
+            .unwrap();
+
+        mnemonic_storage
+            .store_mnemonic(default.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(mnemonic_storage.load_mnemonic().await.unwrap(), default);
+        assert_eq!(
+            mnemonic_storage.load_named_mnemonic("named").await.unwrap(),
+            named
+        );
+        assert!(matches!(
+            mnemonic_storage.store_mnemonic(default).await,
+            Err(OnDiskMnemonicStorageError::MnemonicAlreadyStored { .. })
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_stores_keep_every_mnemonic() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.txt");
+
+        let stores = (0..16).map(|i| {
+            let path = path.clone();
+            tokio::spawn(async move {
+                let mnemonic = bip39::Mnemonic::generate_in(bip39::Language::English, 12).unwrap();
+                OnDiskMnemonicStorage::new(path)
+                    .store_named_mnemonic(&format!("account-{i:02}"), mnemonic)
+                    .await
+            })
+        });
+        for store in stores.collect::<Vec<_>>() {
+            store.await.unwrap().unwrap();
+        }
+
+        let names = OnDiskMnemonicStorage::new(path.clone())
+            .list_mnemonics()
+            .await
+            .unwrap();
+        assert_eq!(names.len(), 16);
+        assert!(!staged_path(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn store_replaces_staged_file_left_by_interrupted_write() {
+        let default = bip39::Mnemonic::generate_in(bip39::Language::English, 12).unwrap();
+        let named = bip39::Mnemonic::generate_in(bip39::Language::English, 12).unwrap();
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.txt");
+        let mnemonic_storage = OnDiskMnemonicStorage::new(path.clone());
+        mnemonic_storage
+            .store_mnemonic(default.clone())
+            .await
+            .unwrap();
+        fs::write(staged_path(&path), "{\"truncated").unwrap();
+
+        mnemonic_storage
+            .store_named_mnemonic("named", named.clone())
+            .await
+            .unwrap();
+
+        assert!(!staged_path(&path).exists());
+        assert_eq!(mnemonic_storage.load_mnemonic().await.unwrap(), default);
+        assert_eq!(
+            mnemonic_storage.load_named_mnemonic("named").await.unwrap(),
+            named
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn store_mnemonic_with_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mnemonic = bip39::Mnemonic::generate_in(bip39::Language::English, 12).unwrap();
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.txt");
+        let mnemonic_storage = OnDiskMnemonicStorage::new(path.clone())
+            .with_permissions(StoragePermissions::from_mode(0o640).unwrap());
+        mnemonic_storage.store_mnemonic(mnemonic).await.unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o640);
+    }
 }
@@ -0,0 +1,238 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{io, path::Path};
+
+const PRIVATE_FILE_MODE: u32 = 0o600;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoragePermissionsError {
+    #[error("storage mode {mode:#o} is not a file mode")]
+    InvalidMode { mode: u32 },
+
+    #[error("storage mode {mode:#o} doesn't let the owner read and write the files")]
+    NotOwnerReadWrite { mode: u32 },
+
+    #[error("storage mode {mode:#o} would let anyone write to the storage")]
+    WorldWritable { mode: u32 },
+}
+
+/// Permissions of the files and directories written by the on-disk storage, i.e. the mnemonic
+/// and the keys. Other files in the data directory only get them when their writer applies them.
+///
+/// By default only the owner can access them, i.e. `0600` for files and `0700` for directories
+/// on unix. On Windows the group and other bits are mapped onto the Administrators and Users
+/// groups, SYSTEM and the owner always have full access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoragePermissions {
+    file_mode: u32,
+}
+
+impl Default for StoragePermissions {
+    fn default() -> Self {
+        Self::private()
+    }
+}
+
+impl StoragePermissions {
+    /// Only the owner can access the storage.
+    pub const fn private() -> Self {
+        Self {
+            file_mode: PRIVATE_FILE_MODE,
+        }
+    }
+
+    /// Uses an explicit mode for the files, e.g. `0o640` to let the group read them. Directories
+    /// also get the execute bit wherever the read bit is set.
+    ///
+    /// The owner must be able to read and write the files, and the mode must not be world
+    /// writable.
+    pub fn from_mode(mode: u32) -> Result<Self, StoragePermissionsError> {
+        if mode & !0o777 != 0 {
+            return Err(StoragePermissionsError::InvalidMode { mode });
+        }
+        if mode & 0o600 != 0o600 {
+            return Err(StoragePermissionsError::NotOwnerReadWrite { mode });
+        }
+        if mode & 0o002 != 0 {
+            return Err(StoragePermissionsError::WorldWritable { mode });
+        }
+        Ok(Self { file_mode: mode })
+    }
+
+    pub fn file_mode(&self) -> u32 {
+        self.file_mode
+    }
+
+    pub fn dir_mode(&self) -> u32 {
+        self.file_mode | ((self.file_mode & 0o444) >> 2)
+    }
+
+    /// Restricts the permissions of a file of the storage.
+    pub fn apply_to_file(&self, path: &Path) -> io::Result<()> {
+        apply(self, path, false)
+    }
+
+    /// Restricts the permissions of a directory of the storage.
+    pub fn apply_to_dir(&self, path: &Path) -> io::Result<()> {
+        apply(self, path, true)
+    }
+
+    // A protected DACL, so that nothing is inherited from the parent directory.
+    #[cfg(any(windows, test))]
+    fn sddl(&self, is_dir: bool) -> String {
+        // Directories pass their entries on to the files created in them and need to be
+        // traversed.
+        let (inherit, read) = if is_dir { ("OICI", "FRFX") } else { ("", "FR") };
+        let ace = |rights: &str, sid: &str| format!("(A;{inherit};{rights};;;{sid})");
+
+        let mut sddl = format!("D:P{}{}", ace("FA", "SY"), ace("FA", "OW"));
+        let group_rights = match (self.file_mode & 0o040 != 0, self.file_mode & 0o020 != 0) {
+            (true, true) => Some(format!("{read}FW")),
+            (true, false) => Some(read.to_owned()),
+            (false, true) => Some("FW".to_owned()),
+            (false, false) => None,
+        };
+        if let Some(group_rights) = group_rights {
+            sddl.push_str(&ace(&group_rights, "BA"));
+        }
+        if self.file_mode & 0o004 != 0 {
+            sddl.push_str(&ace(read, "BU"));
+        }
+        sddl
+    }
+}
+
+#[cfg(unix)]
+fn apply(permissions: &StoragePermissions, path: &Path, is_dir: bool) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = if is_dir {
+        permissions.dir_mode()
+    } else {
+        permissions.file_mode()
+    };
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+}
+
+#[cfg(windows)]
+fn apply(permissions: &StoragePermissions, path: &Path, is_dir: bool) -> io::Result<()> {
+    windows::set_dacl(path, &permissions.sddl(is_dir))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn apply(_permissions: &StoragePermissions, _path: &Path, _is_dir: bool) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::{ffi::OsStr, io, os::windows::ffi::OsStrExt, path::Path, ptr};
+
+    use windows_sys::Win32::{
+        Foundation::LocalFree,
+        Security::{
+            Authorization::{
+                ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+            },
+            SetFileSecurityW, DACL_SECURITY_INFORMATION, PROTECTED_DACL_SECURITY_INFORMATION,
+            PSECURITY_DESCRIPTOR,
+        },
+    };
+
+    /// Replaces the access control list of the file or directory.
+    pub(super) fn set_dacl(path: &Path, sddl: &str) -> io::Result<()> {
+        let path = to_wide(path.as_os_str());
+        let sddl = to_wide(OsStr::new(sddl));
+
+        let mut security_descriptor: PSECURITY_DESCRIPTOR = ptr::null_mut();
+        let converted = unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                sddl.as_ptr(),
+                SDDL_REVISION_1,
+                &mut security_descriptor,
+                ptr::null_mut(),
+            )
+        };
+        if converted == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let result = unsafe {
+            SetFileSecurityW(
+                path.as_ptr(),
+                DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION,
+                security_descriptor,
+            )
+        };
+        let result = if result == 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        };
+
+        unsafe { LocalFree(security_descriptor as _) };
+        result
+    }
+
+    fn to_wide(s: &OsStr) -> Vec<u16> {
+        s.encode_wide().chain(std::iter::once(0)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn private_by_default() {
+        let permissions = StoragePermissions::default();
+        assert_eq!(permissions.file_mode(), 0o600);
+        assert_eq!(permissions.dir_mode(), 0o700);
+        assert_eq!(permissions.sddl(false), "D:P(A;;FA;;;SY)(A;;FA;;;OW)");
+    }
+
+    #[test]
+    fn group_readable_mode() {
+        let permissions = StoragePermissions::from_mode(0o640).unwrap();
+        assert_eq!(permissions.dir_mode(), 0o750);
+        assert_eq!(
+            permissions.sddl(true),
+            "D:P(A;OICI;FA;;;SY)(A;OICI;FA;;;OW)(A;OICI;FRFX;;;BA)"
+        );
+    }
+
+    #[test]
+    fn rejects_unsafe_modes() {
+        assert!(matches!(
+            StoragePermissions::from_mode(0o602),
+            Err(StoragePermissionsError::WorldWritable { .. })
+        ));
+        assert!(matches!(
+            StoragePermissions::from_mode(0o400),
+            Err(StoragePermissionsError::NotOwnerReadWrite { .. })
+        ));
+        assert!(matches!(
+            StoragePermissions::from_mode(0o4600),
+            Err(StoragePermissionsError::InvalidMode { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn applies_the_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("file");
+        std::fs::write(&path, b"").unwrap();
+
+        let permissions = StoragePermissions::from_mode(0o640).unwrap();
+        permissions.apply_to_file(&path).unwrap();
+        permissions.apply_to_dir(tempdir.path()).unwrap();
+
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&path), 0o640);
+        assert_eq!(mode(tempdir.path()), 0o750);
+    }
+}
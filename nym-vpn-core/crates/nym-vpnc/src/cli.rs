@@ -1,11 +1,11 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::net::IpAddr;
+use std::{net::IpAddr, path::PathBuf};
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use clap::{Args, Parser, Subcommand};
-use nym_gateway_directory::{EntryPoint, ExitPoint, NodeIdentity, Recipient};
+use nym_gateway_directory::{EntryPoint, ExitPoint};
 
 #[derive(Parser)]
 #[clap(author = "Nymtech", version, about)]
@@ -14,6 +14,23 @@ pub(crate) struct CliArgs {
     #[arg(long)]
     pub(crate) http: bool,
 
+    /// Endpoint of the daemon's HTTP listener, e.g. when it listens on another host.
+    #[arg(long, requires = "http")]
+    pub(crate) http_endpoint: Option<String>,
+
+    /// PEM CA certificate to verify the daemon's TLS certificate with. Requires an https
+    /// endpoint.
+    #[arg(long, requires_all = ["http_endpoint", "http_tls_cert", "http_tls_key"])]
+    pub(crate) http_tls_ca: Option<PathBuf>,
+
+    /// PEM client certificate to present to the daemon.
+    #[arg(long, requires = "http_tls_ca")]
+    pub(crate) http_tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --http-tls-cert.
+    #[arg(long, requires = "http_tls_ca")]
+    pub(crate) http_tls_key: Option<PathBuf>,
+
     #[command(subcommand)]
     pub(crate) command: Command,
 }
@@ -22,9 +39,23 @@ pub(crate) struct CliArgs {
 pub(crate) enum Command {
     Connect(ConnectArgs),
     Disconnect,
+    /// Rotate the wireguard keys of the connected tunnel without reconnecting.
+    RotateWireguardKeys,
+    /// Keep the connected tunnel up after an idle timeout warning.
+    ResetIdleTimer,
+    /// Print the config of the connected wireguard tunnel in the wg-quick format.
+    ExportWireguardConfig(ExportWireguardConfigArgs),
+    /// Check that the connected tunnel can resolve names and reach the internet.
+    RunConnectivityTest,
+    /// Print the public IP and country of the exit gateway, as seen through the connected tunnel.
+    GetExitPublicIp,
     Status,
+    /// Print the totals of the last session that ended.
+    GetLastSessionSummary,
     Info,
+    DaemonHealth,
     SetNetwork(SetNetworkArgs),
+    ListAvailableNetworks,
     GetSystemMessages,
     GetFeatureFlags,
     StoreAccount(StoreAccountArgs),
@@ -43,6 +74,9 @@ pub(crate) enum Command {
     ListEntryCountries(ListCountriesArgs),
     ListExitCountries(ListCountriesArgs),
     ListVpnCountries(ListCountriesArgs),
+    ListEntryDirectory(ListCountriesArgs),
+    ListExitDirectory(ListCountriesArgs),
+    ListVpnDirectory(ListCountriesArgs),
     ResetDeviceIdentity(ResetDeviceIdentityArgs),
     GetDeviceId,
     RegisterDevice,
@@ -52,6 +86,7 @@ pub(crate) enum Command {
     GetZkNymById(GetZkNymByIdArgs),
     ConfirmZkNymDownloaded(ConfirmZkNymDownloadedArgs),
     GetAvailableTickets,
+    RefreshTickets,
     FetchRawAccountSummary,
     FetchRawDevices,
 }
@@ -68,6 +103,11 @@ pub(crate) struct ConnectArgs {
     #[arg(long)]
     pub(crate) dns: Option<IpAddr>,
 
+    /// Use the DNS servers advertised by the exit gateway instead of the default ones, if it
+    /// advertises any.
+    #[arg(long, conflicts_with = "dns")]
+    pub(crate) gateway_dns: bool,
+
     /// Disable routing all traffic through the nym TUN device. When the flag is set, the nym TUN
     /// device will be created, but to route traffic through it you will need to do it manually,
     /// e.g. ping -Itun0.
@@ -109,6 +149,116 @@ pub(crate) struct ConnectArgs {
     /// consider a gateway for routing traffic.
     #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
     pub(crate) min_gateway_vpn_performance: Option<u8>,
+
+    /// Override the number of seconds to wait for the mixnet client to start up.
+    #[arg(long, hide = true)]
+    pub(crate) mixnet_client_startup_timeout: Option<u64>,
+
+    /// Reuse the entry and exit gateways from the last successful connection, if they are still
+    /// available.
+    #[arg(long)]
+    pub(crate) sticky_gateways: bool,
+
+    /// Fail instead of picking two distinct gateways when the entry and exit are requested in
+    /// the same country.
+    #[arg(long)]
+    pub(crate) disallow_same_country: bool,
+
+    /// Allow access to the local network (private, link-local and multicast ranges) while
+    /// connected.
+    #[arg(long, conflicts_with = "disable_routing")]
+    pub(crate) allow_lan: bool,
+
+    /// Only route the traffic to this network through the tunnel, e.g. 10.1.0.0/16, and leave
+    /// the rest outside of it. Can be given multiple times. The system DNS is kept unless the DNS
+    /// is set.
+    #[arg(
+        long = "route",
+        value_name = "NETWORK",
+        conflicts_with = "disable_routing"
+    )]
+    pub(crate) routed_destinations: Vec<String>,
+
+    /// Never select this gateway as entry or exit. Can be given multiple times.
+    #[arg(long = "exclude-gateway", value_name = "GATEWAY_ID")]
+    pub(crate) excluded_gateways: Vec<String>,
+
+    /// Store the wireguard keys in this subdirectory of the data directory, to keep the keys of
+    /// different profiles apart.
+    #[arg(long)]
+    pub(crate) key_namespace: Option<String>,
+
+    /// Periodically check for DNS queries answered by other servers than the configured ones.
+    #[arg(long)]
+    pub(crate) detect_dns_leaks: bool,
+
+    /// Check for DNS leaks every this many seconds, 60 by default.
+    #[arg(long, requires = "detect_dns_leaks", value_parser = clap::value_parser!(u64).range(1..))]
+    pub(crate) dns_leak_probe_interval: Option<u64>,
+
+    /// Don't reconnect when the default route changes, e.g. when switching networks.
+    #[arg(long)]
+    pub(crate) disable_reconnect_on_network_change: bool,
+
+    /// Rotate the wireguard keys every this many seconds while connected, at least 3600.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(3600..))]
+    pub(crate) key_rotation_interval: Option<u64>,
+
+    /// Only connect to the entry gateway over this wireguard port. Can be given multiple times.
+    /// Only helps with gateways that listen on several ports.
+    #[arg(long = "preferred-wg-port", value_name = "PORT")]
+    pub(crate) preferred_wg_ports: Vec<u16>,
+
+    /// Disconnect after having been connected for this many seconds.
+    #[arg(long)]
+    pub(crate) max_session_duration: Option<u64>,
+
+    /// Report the mixnet latency every this many seconds, 10 by default.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub(crate) latency_sample_interval: Option<u64>,
+
+    /// Disconnect once no traffic went through the tunnel for this many seconds.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub(crate) idle_timeout: Option<u64>,
+
+    /// Cache the gateway directory and reuse it for this many seconds.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub(crate) directory_cache_ttl: Option<u64>,
+
+    /// Send the directory and API requests made before the tunnel is up through this HTTP or
+    /// SOCKS5 proxy, e.g. socks5://127.0.0.1:1080.
+    #[arg(long)]
+    pub(crate) proxy: Option<String>,
+
+    /// Username to authenticate with the proxy.
+    #[arg(long, requires = "proxy")]
+    pub(crate) proxy_username: Option<String>,
+
+    /// Password to authenticate with the proxy.
+    #[arg(long, requires = "proxy_username")]
+    pub(crate) proxy_password: Option<String>,
+
+    /// Give up on a connection attempt after this many seconds, 90 by default. Set to 0 to wait
+    /// for as long as it takes.
+    #[arg(long)]
+    pub(crate) connect_deadline: Option<u64>,
+
+    /// Seed the random gateway selection, so that repeated connects pick the same gateways.
+    #[arg(long)]
+    pub(crate) selection_seed: Option<u64>,
+
+    /// Pick the random entry and exit gateways on the same continent.
+    #[arg(long, conflicts_with = "different_continent")]
+    pub(crate) same_continent: bool,
+
+    /// Pick the random entry and exit gateways on different continents.
+    #[arg(long)]
+    pub(crate) different_continent: bool,
+
+    /// Pick among the gateways with the highest performance instead of any gateway. This makes
+    /// it easier to guess which gateways are used, as there are fewer candidates.
+    #[arg(long)]
+    pub(crate) highest_performance: bool,
 }
 
 #[derive(Args)]
@@ -129,6 +279,10 @@ pub(crate) struct CliEntry {
     /// Auto-select entry gateway randomly.
     #[arg(long, alias = "entry-random")]
     pub(crate) entry_gateway_random: bool,
+
+    /// Hostname or IP address the entry gateway advertises.
+    #[arg(long, alias = "entry-hostname")]
+    pub(crate) entry_gateway_hostname: Option<String>,
 }
 
 #[derive(Args)]
@@ -149,6 +303,17 @@ pub(crate) struct CliExit {
     /// Auto-select exit gateway randomly.
     #[clap(long, alias = "exit-random")]
     pub(crate) exit_gateway_random: bool,
+
+    /// Hostname or IP address the exit gateway advertises.
+    #[clap(long, alias = "exit-hostname")]
+    pub(crate) exit_gateway_hostname: Option<String>,
+}
+
+#[derive(Args)]
+pub(crate) struct ExportWireguardConfigArgs {
+    /// Include the private keys instead of redacting them.
+    #[arg(long)]
+    pub(crate) include_private_keys: bool,
 }
 
 #[derive(Args)]
@@ -223,42 +388,35 @@ pub(crate) struct ConfirmZkNymDownloadedArgs {
 }
 
 pub(crate) fn parse_entry_point(args: &ConnectArgs) -> Result<Option<EntryPoint>> {
-    if let Some(ref entry_gateway_id) = args.entry.entry_gateway_id {
-        Ok(Some(EntryPoint::Gateway {
-            identity: NodeIdentity::from_base58_string(entry_gateway_id.clone())
-                .map_err(|_| anyhow!("Failed to parse gateway id"))?,
-        }))
+    let entry = if let Some(ref entry_gateway_id) = args.entry.entry_gateway_id {
+        format!("gateway:{entry_gateway_id}")
     } else if let Some(ref entry_gateway_country) = args.entry.entry_gateway_country {
-        Ok(Some(EntryPoint::Location {
-            location: entry_gateway_country.clone(),
-        }))
+        format!("country:{entry_gateway_country}")
     } else if args.entry.entry_gateway_low_latency {
-        Ok(Some(EntryPoint::RandomLowLatency))
+        "low-latency".to_string()
     } else if args.entry.entry_gateway_random {
-        Ok(Some(EntryPoint::Random))
+        "random".to_string()
+    } else if let Some(ref entry_gateway_hostname) = args.entry.entry_gateway_hostname {
+        format!("hostname:{entry_gateway_hostname}")
     } else {
-        Ok(None)
-    }
+        return Ok(None);
+    };
+    Ok(Some(EntryPoint::from_cli_str(&entry)?))
 }
 
 pub(crate) fn parse_exit_point(args: &ConnectArgs) -> Result<Option<ExitPoint>> {
-    if let Some(ref exit_router_address) = args.exit.exit_router_address {
-        Ok(Some(ExitPoint::Address {
-            address: Recipient::try_from_base58_string(exit_router_address.clone())
-                .map_err(|_| anyhow!("Failed to parse exit node address"))?,
-        }))
+    let exit = if let Some(ref exit_router_address) = args.exit.exit_router_address {
+        format!("address:{exit_router_address}")
     } else if let Some(ref exit_router_id) = args.exit.exit_gateway_id {
-        Ok(Some(ExitPoint::Gateway {
-            identity: NodeIdentity::from_base58_string(exit_router_id.clone())
-                .map_err(|_| anyhow!("Failed to parse gateway id"))?,
-        }))
+        format!("gateway:{exit_router_id}")
     } else if let Some(ref exit_gateway_country) = args.exit.exit_gateway_country {
-        Ok(Some(ExitPoint::Location {
-            location: exit_gateway_country.clone(),
-        }))
+        format!("country:{exit_gateway_country}")
     } else if args.exit.exit_gateway_random {
-        Ok(Some(ExitPoint::Random))
+        "random".to_string()
+    } else if let Some(ref exit_gateway_hostname) = args.exit.exit_gateway_hostname {
+        format!("hostname:{exit_gateway_hostname}")
     } else {
-        Ok(None)
-    }
+        return Ok(None);
+    };
+    Ok(Some(ExitPoint::from_cli_str(&exit)?))
 }
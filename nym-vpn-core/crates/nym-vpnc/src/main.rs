@@ -5,17 +5,22 @@ use anyhow::Result;
 use clap::Parser;
 use nym_gateway_directory::GatewayType;
 use nym_vpn_proto::{
-    ConfirmZkNymDownloadedRequest, ConnectRequest, DisconnectRequest, Empty,
-    FetchRawAccountSummaryRequest, FetchRawDevicesRequest, GetAccountIdentityRequest,
-    GetAccountLinksRequest, GetAccountStateRequest, GetAvailableTicketsRequest,
-    GetDeviceIdentityRequest, GetDeviceZkNymsRequest, GetFeatureFlagsRequest,
+    ConfirmZkNymDownloadedRequest, ConnectRequest, DaemonHealthRequest, DisconnectRequest, Empty,
+    ExportWireguardConfigRequest, FetchRawAccountSummaryRequest, FetchRawDevicesRequest,
+    GetAccountIdentityRequest, GetAccountLinksRequest, GetAccountStateRequest,
+    GetAvailableTicketsRequest, GetDeviceIdentityRequest, GetDeviceZkNymsRequest,
+    GetExitPublicIpRequest, GetFeatureFlagsRequest, GetLastSessionSummaryRequest,
     GetSystemMessagesRequest, GetZkNymByIdRequest, GetZkNymsAvailableForDownloadRequest,
     InfoRequest, InfoResponse, IsAccountStoredRequest, IsReadyToConnectRequest,
-    ListCountriesRequest, ListGatewaysRequest, RefreshAccountStateRequest, RegisterDeviceRequest,
-    RemoveAccountRequest, RequestZkNymRequest, ResetDeviceIdentityRequest, SetNetworkRequest,
-    StatusRequest, StoreAccountRequest, UserAgent,
+    ListAvailableNetworksRequest, ListCountriesRequest, ListDirectoryRequest, ListGatewaysRequest,
+    Proxy, RefreshAccountStateRequest, RefreshTicketsRequest, RegisterDeviceRequest,
+    RemoveAccountRequest, RequestZkNymRequest, ResetDeviceIdentityRequest, ResetIdleTimerRequest,
+    RotateWireguardKeysRequest, RunConnectivityTestRequest, SetNetworkRequest, StatusRequest,
+    StoreAccountRequest, UserAgent,
+};
+use protobuf_conversion::{
+    into_gateway_type, into_region_affinity, into_selection_strategy, into_threshold,
 };
-use protobuf_conversion::{into_gateway_type, into_threshold};
 use sysinfo::System;
 use vpnd_client::ClientType;
 
@@ -35,16 +40,38 @@ mod vpnd_client;
 async fn main() -> Result<()> {
     let args = cli::CliArgs::parse();
     let client_type = if args.http {
-        vpnd_client::ClientType::Http
+        let tls = match (&args.http_tls_ca, &args.http_tls_cert, &args.http_tls_key) {
+            (Some(ca_path), Some(cert_path), Some(key_path)) => Some(
+                vpnd_client::client_tls_config(ca_path, cert_path, key_path)?,
+            ),
+            _ => None,
+        };
+        vpnd_client::ClientType::Http {
+            endpoint: args
+                .http_endpoint
+                .clone()
+                .unwrap_or_else(config::default_endpoint),
+            tls,
+        }
     } else {
         vpnd_client::ClientType::Ipc
     };
     match args.command {
         Command::Connect(ref connect_args) => connect(client_type, connect_args).await?,
         Command::Disconnect => disconnect(client_type).await?,
+        Command::RotateWireguardKeys => rotate_wireguard_keys(client_type).await?,
+        Command::ResetIdleTimer => reset_idle_timer(client_type).await?,
+        Command::ExportWireguardConfig(ref args) => {
+            export_wireguard_config(client_type, args).await?
+        }
+        Command::RunConnectivityTest => run_connectivity_test(client_type).await?,
+        Command::GetExitPublicIp => get_exit_public_ip(client_type).await?,
         Command::Status => status(client_type).await?,
+        Command::GetLastSessionSummary => get_last_session_summary(client_type).await?,
         Command::Info => info(client_type).await?,
+        Command::DaemonHealth => daemon_health(client_type).await?,
         Command::SetNetwork(ref args) => set_network(client_type, args).await?,
+        Command::ListAvailableNetworks => list_available_networks(client_type).await?,
         Command::GetSystemMessages => get_system_messages(client_type).await?,
         Command::GetFeatureFlags => get_feature_flags(client_type).await?,
         Command::StoreAccount(ref store_args) => store_account(client_type, store_args).await?,
@@ -75,6 +102,15 @@ async fn main() -> Result<()> {
         Command::ListVpnCountries(ref list_args) => {
             list_countries(client_type, list_args, GatewayType::Wg).await?
         }
+        Command::ListEntryDirectory(ref list_args) => {
+            list_directory(client_type, list_args, GatewayType::MixnetEntry).await?
+        }
+        Command::ListExitDirectory(ref list_args) => {
+            list_directory(client_type, list_args, GatewayType::MixnetExit).await?
+        }
+        Command::ListVpnDirectory(ref list_args) => {
+            list_directory(client_type, list_args, GatewayType::Wg).await?
+        }
         Command::ResetDeviceIdentity(ref args) => reset_device_identity(client_type, args).await?,
         Command::GetDeviceId => get_device_id(client_type).await?,
         Command::RegisterDevice => register_device(client_type).await?,
@@ -88,6 +124,7 @@ async fn main() -> Result<()> {
             confirm_zk_nym_downloaded(client_type, args).await?
         }
         Command::GetAvailableTickets => get_available_tickets(client_type).await?,
+        Command::RefreshTickets => refresh_tickets(client_type).await?,
         Command::FetchRawAccountSummary => fetch_raw_account_summary(client_type).await?,
         Command::FetchRawDevices => fetch_raw_devices(client_type).await?,
     }
@@ -138,6 +175,39 @@ async fn connect(client_type: ClientType, connect_args: &cli::ConnectArgs) -> Re
             .min_gateway_mixnet_performance
             .map(into_threshold),
         min_gateway_vpn_performance: connect_args.min_gateway_vpn_performance.map(into_threshold),
+        mixnet_client_startup_timeout_secs: connect_args.mixnet_client_startup_timeout,
+        sticky_gateways: connect_args.sticky_gateways,
+        disallow_same_country: connect_args.disallow_same_country,
+        allow_lan: connect_args.allow_lan,
+        excluded_gateways: connect_args.excluded_gateways.clone(),
+        key_namespace: connect_args.key_namespace.clone(),
+        detect_dns_leaks: connect_args.detect_dns_leaks,
+        dns_leak_probe_interval_secs: connect_args.dns_leak_probe_interval,
+        disable_reconnect_on_network_change: connect_args.disable_reconnect_on_network_change,
+        key_rotation_interval_secs: connect_args.key_rotation_interval,
+        max_session_duration_secs: connect_args.max_session_duration,
+        selection_seed: connect_args.selection_seed,
+        region_affinity: into_region_affinity(
+            connect_args.same_continent,
+            connect_args.different_continent,
+        ) as i32,
+        selection_strategy: into_selection_strategy(connect_args.highest_performance) as i32,
+        connect_deadline_secs: connect_args.connect_deadline,
+        gateway_dns: connect_args.gateway_dns,
+        preferred_wg_ports: connect_args
+            .preferred_wg_ports
+            .iter()
+            .map(|&port| u32::from(port))
+            .collect(),
+        routed_destinations: connect_args.routed_destinations.clone(),
+        latency_sample_interval_secs: connect_args.latency_sample_interval,
+        idle_timeout_secs: connect_args.idle_timeout,
+        directory_cache_ttl_secs: connect_args.directory_cache_ttl,
+        proxy: connect_args.proxy.clone().map(|url| Proxy {
+            url,
+            username: connect_args.proxy_username.clone(),
+            password: connect_args.proxy_password.clone(),
+        }),
     });
 
     let response = client.vpn_connect(request).await?.into_inner();
@@ -153,19 +223,82 @@ async fn disconnect(client_type: ClientType) -> Result<()> {
     Ok(())
 }
 
+async fn rotate_wireguard_keys(client_type: ClientType) -> Result<()> {
+    let mut client = vpnd_client::get_client(client_type).await?;
+    let request = tonic::Request::new(RotateWireguardKeysRequest {});
+    let response = client.rotate_wireguard_keys(request).await?.into_inner();
+    println!("{:#?}", response);
+    Ok(())
+}
+
+async fn reset_idle_timer(client_type: ClientType) -> Result<()> {
+    let mut client = vpnd_client::get_client(client_type).await?;
+    let request = tonic::Request::new(ResetIdleTimerRequest {});
+    let response = client.reset_idle_timer(request).await?.into_inner();
+    println!("{:#?}", response);
+    Ok(())
+}
+
+async fn export_wireguard_config(
+    client_type: ClientType,
+    args: &cli::ExportWireguardConfigArgs,
+) -> Result<()> {
+    let mut client = vpnd_client::get_client(client_type).await?;
+    let request = tonic::Request::new(ExportWireguardConfigRequest {
+        include_private_keys: args.include_private_keys,
+    });
+    let response = client.export_wireguard_config(request).await?.into_inner();
+    print!("{}", response.config);
+    Ok(())
+}
+
+async fn run_connectivity_test(client_type: ClientType) -> Result<()> {
+    let mut client = vpnd_client::get_client(client_type).await?;
+    let request = tonic::Request::new(RunConnectivityTestRequest {});
+    let response = client.run_connectivity_test(request).await?.into_inner();
+    println!("{:#?}", response);
+    Ok(())
+}
+
+async fn get_exit_public_ip(client_type: ClientType) -> Result<()> {
+    let mut client = vpnd_client::get_client(client_type).await?;
+    let request = tonic::Request::new(GetExitPublicIpRequest {});
+    let response = client.get_exit_public_ip(request).await?.into_inner();
+    println!("{:#?}", response);
+    Ok(())
+}
+
 async fn status(client_type: ClientType) -> Result<()> {
     let mut client = vpnd_client::get_client(client_type).await?;
     let request = tonic::Request::new(StatusRequest {});
     let response = client.vpn_status(request).await?.into_inner();
     println!("{:#?}", response);
 
-    if let Some(Ok(utc_since)) = response
-        .details
-        .and_then(|details| details.since)
+    if let Some(Ok(utc_since)) = response.connected_since.map(parse_offset_datetime) {
+        println!("since (utc): {:?}", utc_since);
+    }
+    if let Some(duration_secs) = response.duration_secs {
+        println!(
+            "duration: {}",
+            time::Duration::seconds(duration_secs as i64)
+        );
+    }
+
+    Ok(())
+}
+
+async fn get_last_session_summary(client_type: ClientType) -> Result<()> {
+    let mut client = vpnd_client::get_client(client_type).await?;
+    let request = tonic::Request::new(GetLastSessionSummaryRequest {});
+    let response = client.get_last_session_summary(request).await?.into_inner();
+    println!("{:#?}", response);
+
+    if let Some(Ok(utc_started_at)) = response
+        .summary
+        .and_then(|summary| summary.started_at)
         .map(parse_offset_datetime)
     {
-        println!("since (utc): {:?}", utc_since);
-        println!("duration: {}", time::OffsetDateTime::now_utc() - utc_since);
+        println!("started at (utc): {:?}", utc_started_at);
     }
 
     Ok(())
@@ -187,6 +320,14 @@ async fn info(client_type: ClientType) -> Result<()> {
     Ok(())
 }
 
+async fn daemon_health(client_type: ClientType) -> Result<()> {
+    let mut client = vpnd_client::get_client(client_type).await?;
+    let request = tonic::Request::new(DaemonHealthRequest {});
+    let response = client.daemon_health(request).await?.into_inner();
+    println!("{:#?}", response);
+    Ok(())
+}
+
 async fn set_network(client_type: ClientType, args: &cli::SetNetworkArgs) -> Result<()> {
     let mut client = vpnd_client::get_client(client_type).await?;
     let request = tonic::Request::new(SetNetworkRequest {
@@ -197,6 +338,14 @@ async fn set_network(client_type: ClientType, args: &cli::SetNetworkArgs) -> Res
     Ok(())
 }
 
+async fn list_available_networks(client_type: ClientType) -> Result<()> {
+    let mut client = vpnd_client::get_client(client_type).await?;
+    let request = tonic::Request::new(ListAvailableNetworksRequest {});
+    let response = client.list_available_networks(request).await?.into_inner();
+    println!("{:#?}", response);
+    Ok(())
+}
+
 async fn get_system_messages(client_type: ClientType) -> Result<()> {
     let mut client = vpnd_client::get_client(client_type).await?;
     let request = tonic::Request::new(GetSystemMessagesRequest {});
@@ -391,6 +540,14 @@ async fn get_available_tickets(client_type: ClientType) -> Result<()> {
     Ok(())
 }
 
+async fn refresh_tickets(client_type: ClientType) -> Result<()> {
+    let mut client = vpnd_client::get_client(client_type).await?;
+    let request = tonic::Request::new(RefreshTicketsRequest {});
+    let response = client.refresh_tickets(request).await?.into_inner();
+    println!("{:#?}", response);
+    Ok(())
+}
+
 async fn listen_to_status(client_type: ClientType) -> Result<()> {
     let mut client = vpnd_client::get_client(client_type).await?;
     let request = tonic::Request::new(Empty {});
@@ -469,3 +626,25 @@ async fn list_countries(
     println!("{:#?}", response);
     Ok(())
 }
+
+async fn list_directory(
+    client_type: ClientType,
+    list_args: &cli::ListCountriesArgs,
+    gw_type: GatewayType,
+) -> Result<()> {
+    let mut client = vpnd_client::get_client(client_type).await?;
+
+    let info_request = tonic::Request::new(InfoRequest {});
+    let info = client.info(info_request).await?.into_inner();
+    let user_agent = construct_user_agent(info);
+
+    let request = tonic::Request::new(ListDirectoryRequest {
+        kind: into_gateway_type(gw_type) as i32,
+        user_agent: Some(user_agent),
+        min_mixnet_performance: list_args.min_mixnet_performance.map(into_threshold),
+        min_vpn_performance: list_args.min_vpn_performance.map(into_threshold),
+    });
+    let response = client.list_directory(request).await?.into_inner();
+    println!("{:#?}", response);
+    Ok(())
+}
@@ -39,12 +39,21 @@ fn new_entry_node_random() -> nym_vpn_proto::EntryNode {
     }
 }
 
+fn new_entry_node_hostname(hostname: &str) -> nym_vpn_proto::EntryNode {
+    nym_vpn_proto::EntryNode {
+        entry_node_enum: Some(nym_vpn_proto::entry_node::EntryNodeEnum::Hostname(
+            hostname.to_string(),
+        )),
+    }
+}
+
 pub(crate) fn into_entry_point(entry: EntryPoint) -> nym_vpn_proto::EntryNode {
     match entry {
         EntryPoint::Gateway { identity } => new_entry_node_gateway(&identity),
         EntryPoint::Location { location } => new_entry_node_location(&location),
         EntryPoint::RandomLowLatency => new_entry_node_random_low_latency(),
         EntryPoint::Random => new_entry_node_random(),
+        EntryPoint::Hostname { hostname } => new_entry_node_hostname(&hostname),
     }
 }
 
@@ -86,12 +95,21 @@ fn new_exit_node_random() -> nym_vpn_proto::ExitNode {
     }
 }
 
+fn new_exit_node_hostname(hostname: &str) -> nym_vpn_proto::ExitNode {
+    nym_vpn_proto::ExitNode {
+        exit_node_enum: Some(nym_vpn_proto::exit_node::ExitNodeEnum::Hostname(
+            hostname.to_string(),
+        )),
+    }
+}
+
 pub(crate) fn into_exit_point(exit: ExitPoint) -> nym_vpn_proto::ExitNode {
     match exit {
         ExitPoint::Address { address } => new_exit_node_address(&address),
         ExitPoint::Gateway { identity } => new_exit_node_gateway(&identity),
         ExitPoint::Location { location } => new_exit_node_location(&location),
         ExitPoint::Random => new_exit_node_random(),
+        ExitPoint::Hostname { hostname } => new_exit_node_hostname(&hostname),
     }
 }
 
@@ -105,6 +123,29 @@ pub(crate) fn into_threshold(performance: u8) -> nym_vpn_proto::Threshold {
     }
 }
 
+pub(crate) fn into_region_affinity(
+    same_continent: bool,
+    different_continent: bool,
+) -> nym_vpn_proto::RegionAffinity {
+    if same_continent {
+        nym_vpn_proto::RegionAffinity::SameContinent
+    } else if different_continent {
+        nym_vpn_proto::RegionAffinity::DifferentContinent
+    } else {
+        nym_vpn_proto::RegionAffinity::Unspecified
+    }
+}
+
+pub(crate) fn into_selection_strategy(
+    highest_performance: bool,
+) -> nym_vpn_proto::SelectionStrategy {
+    if highest_performance {
+        nym_vpn_proto::SelectionStrategy::HighestPerformance
+    } else {
+        nym_vpn_proto::SelectionStrategy::Random
+    }
+}
+
 pub(crate) fn into_gateway_type(gateway_type: GatewayType) -> nym_vpn_proto::GatewayType {
     match gateway_type {
         GatewayType::MixnetEntry => nym_vpn_proto::GatewayType::MixnetEntry,
@@ -1,17 +1,22 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::Context;
 use nym_vpn_proto::nym_vpnd_client::NymVpndClient;
 use parity_tokio_ipc::Endpoint as IpcEndpoint;
-use tonic::transport::{Channel as TonicChannel, Endpoint as TonicEndpoint};
+use tonic::transport::{
+    Certificate, Channel as TonicChannel, ClientTlsConfig, Endpoint as TonicEndpoint, Identity,
+};
 
 use crate::config;
 
 pub(crate) enum ClientType {
-    Http,
+    Http {
+        endpoint: String,
+        tls: Option<ClientTlsConfig>,
+    },
     Ipc,
 }
 
@@ -19,7 +24,7 @@ pub(crate) async fn get_client(
     client_type: ClientType,
 ) -> anyhow::Result<NymVpndClient<TonicChannel>> {
     match client_type {
-        ClientType::Http => get_http_client().await,
+        ClientType::Http { endpoint, tls } => get_http_client(endpoint, tls).await,
         ClientType::Ipc => get_ipc_client().await,
     }
 }
@@ -33,12 +38,32 @@ async fn get_channel(socket_path: PathBuf) -> anyhow::Result<TonicChannel> {
         .await?)
 }
 
-async fn get_http_client() -> anyhow::Result<NymVpndClient<TonicChannel>> {
-    let endpoint = config::default_endpoint();
-    let client = NymVpndClient::connect(endpoint.clone())
+pub(crate) fn client_tls_config(
+    ca_path: &Path,
+    cert_path: &Path,
+    key_path: &Path,
+) -> anyhow::Result<ClientTlsConfig> {
+    let read = |path: &Path| {
+        std::fs::read(path).with_context(|| format!("Failed to read: {}", path.display()))
+    };
+    Ok(ClientTlsConfig::new()
+        .ca_certificate(Certificate::from_pem(read(ca_path)?))
+        .identity(Identity::from_pem(read(cert_path)?, read(key_path)?)))
+}
+
+async fn get_http_client(
+    endpoint: String,
+    tls: Option<ClientTlsConfig>,
+) -> anyhow::Result<NymVpndClient<TonicChannel>> {
+    let mut tonic_endpoint = TonicEndpoint::from_shared(endpoint.clone())?;
+    if let Some(tls) = tls {
+        tonic_endpoint = tonic_endpoint.tls_config(tls)?;
+    }
+    let channel = tonic_endpoint
+        .connect()
         .await
         .with_context(|| format!("Failed to connect to: {}", endpoint))?;
-    Ok(client)
+    Ok(NymVpndClient::new(channel))
 }
 
 async fn get_ipc_client() -> anyhow::Result<NymVpndClient<TonicChannel>> {
@@ -1,7 +1,7 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::{path::PathBuf, sync::OnceLock};
+use std::{net::SocketAddr, path::PathBuf, sync::OnceLock};
 
 use clap::{Args, Parser};
 
@@ -24,6 +24,30 @@ pub(crate) struct CliArgs {
     #[arg(long)]
     pub(crate) enable_http_listener: bool,
 
+    /// Address for the http listener, overriding the one in the config file. Defaults to
+    /// [::1]:53181.
+    #[arg(long, requires = "enable_http_listener")]
+    pub(crate) http_listener_address: Option<SocketAddr>,
+
+    /// Allow the http listener to bind to a non-loopback address. Anyone who can reach it can
+    /// control the VPN.
+    #[arg(long, requires = "enable_http_listener")]
+    pub(crate) allow_remote: bool,
+
+    /// Serve the http listener over TLS with this PEM certificate, and only accept clients with a
+    /// certificate signed by --http-tls-client-ca. The socket listener doesn't use TLS, access to
+    /// it is controlled by the filesystem permissions of the socket.
+    #[arg(long, requires_all = ["enable_http_listener", "http_tls_key", "http_tls_client_ca"])]
+    pub(crate) http_tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --http-tls-cert.
+    #[arg(long, requires = "http_tls_cert")]
+    pub(crate) http_tls_key: Option<PathBuf>,
+
+    /// PEM CA certificate that client certificates must be signed by.
+    #[arg(long, requires = "http_tls_cert")]
+    pub(crate) http_tls_client_ca: Option<PathBuf>,
+
     #[arg(long)]
     pub(crate) disable_socket_listener: bool,
 
@@ -3,6 +3,10 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+
+use super::error::CommandInterfaceError;
+
 pub(super) fn default_socket_path() -> PathBuf {
     #[cfg(unix)]
     return Path::new("/var/run/nym-vpn.sock").to_path_buf();
@@ -14,3 +18,75 @@ pub(super) fn default_socket_path() -> PathBuf {
 pub(super) fn default_uri_addr() -> SocketAddr {
     "[::1]:53181".parse().unwrap()
 }
+
+// Anyone who can reach the http listener can control the VPN, so only listen beyond the local
+// host when explicitly asked to, ideally together with `HttpListenerTls`. The socket listener
+// relies on the filesystem permissions of the socket instead.
+pub(super) fn check_uri_addr(
+    addr: SocketAddr,
+    allow_remote: bool,
+) -> Result<(), CommandInterfaceError> {
+    if addr.ip().is_loopback() || allow_remote {
+        Ok(())
+    } else {
+        Err(CommandInterfaceError::RemoteListenerNotAllowed { address: addr })
+    }
+}
+
+/// Certificates for serving the http listener over TLS. Clients must present a certificate
+/// signed by the client CA, connections without one are rejected during the handshake.
+#[derive(Clone, Debug)]
+pub(crate) struct HttpListenerTls {
+    pub(crate) cert_path: PathBuf,
+    pub(crate) key_path: PathBuf,
+    pub(crate) client_ca_path: PathBuf,
+}
+
+impl HttpListenerTls {
+    pub(super) fn load(&self) -> Result<ServerTlsConfig, CommandInterfaceError> {
+        let cert = read_tls_file(&self.cert_path)?;
+        let key = read_tls_file(&self.key_path)?;
+        let client_ca = read_tls_file(&self.client_ca_path)?;
+        Ok(ServerTlsConfig::new()
+            .identity(Identity::from_pem(cert, key))
+            .client_ca_root(Certificate::from_pem(client_ca)))
+    }
+}
+
+fn read_tls_file(path: &Path) -> Result<Vec<u8>, CommandInterfaceError> {
+    std::fs::read(path).map_err(|source| CommandInterfaceError::ReadTlsFile {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loopback_addresses_are_always_allowed() {
+        for addr in ["127.0.0.1:53181", "[::1]:53181"] {
+            assert!(check_uri_addr(addr.parse().unwrap(), false).is_ok());
+        }
+        assert!(check_uri_addr(default_uri_addr(), false).is_ok());
+    }
+
+    #[test]
+    fn non_loopback_addresses_are_refused_by_default() {
+        for addr in ["0.0.0.0:53181", "192.168.1.10:53181", "[::]:53181"] {
+            let addr: SocketAddr = addr.parse().unwrap();
+            assert!(matches!(
+                check_uri_addr(addr, false),
+                Err(CommandInterfaceError::RemoteListenerNotAllowed { address }) if address == addr
+            ));
+        }
+    }
+
+    #[test]
+    fn non_loopback_addresses_are_allowed_when_remote_is_allowed() {
+        for addr in ["0.0.0.0:53181", "192.168.1.10:53181", "[::]:53181"] {
+            assert!(check_uri_addr(addr.parse().unwrap(), true).is_ok());
+        }
+    }
+}
@@ -1,20 +1,33 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use nym_vpn_account_controller::{AccountStateSummary, AvailableTicketbooks, ReadyToConnect};
-use nym_vpn_network_config::{FeatureFlags, ParsedAccountLinks, SystemMessages};
-use tokio::sync::{mpsc::UnboundedSender, oneshot};
+use nym_vpn_account_controller::{
+    AccountStateChange, AccountStateSummary, AvailableTicketbooks, ReadyToConnect,
+};
+use nym_vpn_network_config::{AvailableNetwork, FeatureFlags, ParsedAccountLinks, SystemMessages};
+use tokio::{
+    sync::{broadcast, mpsc::UnboundedSender, oneshot},
+    task::JoinError,
+};
 
 use nym_vpn_api_client::{
-    response::{NymVpnAccountSummaryResponse, NymVpnDevicesResponse},
+    response::{
+        NymVpnAccountSummaryResponse, NymVpnDevicesResponse, NymVpnZkNym, NymVpnZkNymResponse,
+    },
     types::GatewayMinPerformance,
 };
-use nym_vpn_lib::gateway_directory::{EntryPoint, ExitPoint, GatewayClient, GatewayType};
+use nym_vpn_lib::{
+    gateway_directory::{EntryPoint, ExitPoint, GatewayClient, GatewayType},
+    tunnel_state_machine::{ConnectivityTestReport, ExitPublicIp},
+};
 
 use crate::{
     service::{
-        AccountError, ConnectArgs, ConnectOptions, SetNetworkError, VpnServiceCommand,
-        VpnServiceConnectError, VpnServiceDisconnectError, VpnServiceInfo, VpnServiceStatus,
+        AccountError, ConnectArgs, ConnectOptions, SessionSummaryDetails, SetNetworkError,
+        VpnServiceCommand, VpnServiceConnectError, VpnServiceConnectivityTestError,
+        VpnServiceDisconnectError, VpnServiceExitPublicIpError,
+        VpnServiceExportWireguardConfigError, VpnServiceHealth, VpnServiceInfo,
+        VpnServiceResetIdleTimerError, VpnServiceRotateKeysError, VpnServiceStatus,
     },
     types::gateway,
 };
@@ -54,6 +67,10 @@ impl CommandInterfaceConnectionHandler {
         self.send_and_wait(VpnServiceCommand::Info, ()).await
     }
 
+    pub(crate) async fn handle_health(&self) -> Result<VpnServiceHealth, VpnCommandSendError> {
+        self.send_and_wait(VpnServiceCommand::Health, ()).await
+    }
+
     pub(crate) async fn handle_set_network(
         &self,
         network: String,
@@ -62,6 +79,16 @@ impl CommandInterfaceConnectionHandler {
             .await
     }
 
+    // The registry is fetched with a blocking client, so it's kept off the runtime threads.
+    pub(crate) async fn handle_list_available_networks(
+        &self,
+    ) -> Result<Vec<AvailableNetwork>, JoinError> {
+        tokio::task::spawn_blocking(|| {
+            nym_vpn_network_config::list_networks(&crate::service::config_dir())
+        })
+        .await
+    }
+
     pub(crate) async fn handle_get_system_messages(
         &self,
     ) -> Result<SystemMessages, VpnCommandSendError> {
@@ -90,7 +117,16 @@ impl CommandInterfaceConnectionHandler {
             options,
         };
 
-        self.send_and_wait(VpnServiceCommand::Connect, (connect_args, user_agent))
+        self.send_and_wait(VpnServiceCommand::Connect, (Some(connect_args), user_agent))
+            .await
+    }
+
+    pub(crate) async fn handle_connect_with_defaults(
+        &self,
+        user_agent: nym_vpn_lib::UserAgent,
+    ) -> Result<Result<(), VpnServiceConnectError>, VpnCommandSendError> {
+        tracing::info!("Starting VPN with the default options");
+        self.send_and_wait(VpnServiceCommand::Connect, (None, user_agent))
             .await
     }
 
@@ -100,10 +136,57 @@ impl CommandInterfaceConnectionHandler {
         self.send_and_wait(VpnServiceCommand::Disconnect, ()).await
     }
 
+    pub(crate) async fn handle_rotate_wireguard_keys(
+        &self,
+    ) -> Result<Result<(), VpnServiceRotateKeysError>, VpnCommandSendError> {
+        self.send_and_wait(VpnServiceCommand::RotateWireguardKeys, ())
+            .await
+    }
+
+    pub(crate) async fn handle_reset_idle_timer(
+        &self,
+    ) -> Result<Result<(), VpnServiceResetIdleTimerError>, VpnCommandSendError> {
+        self.send_and_wait(VpnServiceCommand::ResetIdleTimer, ())
+            .await
+    }
+
+    pub(crate) async fn handle_export_wireguard_config(
+        &self,
+        include_private_keys: bool,
+    ) -> Result<Result<String, VpnServiceExportWireguardConfigError>, VpnCommandSendError> {
+        self.send_and_wait(
+            VpnServiceCommand::ExportWireguardConfig,
+            include_private_keys,
+        )
+        .await
+    }
+
+    pub(crate) async fn handle_run_connectivity_test(
+        &self,
+    ) -> Result<Result<ConnectivityTestReport, VpnServiceConnectivityTestError>, VpnCommandSendError>
+    {
+        self.send_and_wait(VpnServiceCommand::RunConnectivityTest, ())
+            .await
+    }
+
+    pub(crate) async fn handle_get_exit_public_ip(
+        &self,
+    ) -> Result<Result<ExitPublicIp, VpnServiceExitPublicIpError>, VpnCommandSendError> {
+        self.send_and_wait(VpnServiceCommand::GetExitPublicIp, ())
+            .await
+    }
+
     pub(crate) async fn handle_status(&self) -> Result<VpnServiceStatus, VpnCommandSendError> {
         self.send_and_wait(VpnServiceCommand::Status, ()).await
     }
 
+    pub(crate) async fn handle_get_last_session_summary(
+        &self,
+    ) -> Result<Option<SessionSummaryDetails>, VpnCommandSendError> {
+        self.send_and_wait(VpnServiceCommand::GetLastSessionSummary, ())
+            .await
+    }
+
     pub(crate) async fn handle_list_gateways(
         &self,
         gw_type: GatewayType,
@@ -124,12 +207,34 @@ impl CommandInterfaceConnectionHandler {
         user_agent: nym_vpn_lib::UserAgent,
         min_gateway_performance: GatewayMinPerformance,
     ) -> Result<Vec<gateway::Country>, ListGatewayError> {
-        let gateways = directory_client(user_agent, min_gateway_performance)?
+        let countries = directory_client(user_agent, min_gateway_performance)?
             .lookup_countries(gw_type.clone())
             .await
             .map_err(|source| ListGatewayError::GetCountries { gw_type, source })?;
 
-        Ok(gateways.into_iter().map(gateway::Country::from).collect())
+        Ok(countries
+            .locations
+            .into_iter()
+            .map(gateway::Country::from)
+            .collect())
+    }
+
+    // Countries are derived from the same lookup as the gateways, so that the two can't disagree
+    // if the directory changes in between.
+    pub(crate) async fn handle_list_directory(
+        &self,
+        gw_type: GatewayType,
+        user_agent: nym_vpn_lib::UserAgent,
+        min_gateway_performance: GatewayMinPerformance,
+    ) -> Result<Vec<gateway::CountryGateways>, ListGatewayError> {
+        let gateways = directory_client(user_agent, min_gateway_performance)?
+            .lookup_gateways(gw_type.clone())
+            .await
+            .map_err(|source| ListGatewayError::GetGateways { gw_type, source })?;
+
+        Ok(gateway::group_by_country(
+            gateways.into_iter().map(gateway::Gateway::from),
+        ))
     }
 
     pub(crate) async fn handle_store_account(
@@ -190,6 +295,14 @@ impl CommandInterfaceConnectionHandler {
             .await
     }
 
+    pub(crate) async fn handle_subscribe_account_changes(
+        &self,
+    ) -> Result<(AccountStateSummary, broadcast::Receiver<AccountStateChange>), VpnCommandSendError>
+    {
+        self.send_and_wait(VpnServiceCommand::SubscribeAccountChanges, ())
+            .await
+    }
+
     pub(crate) async fn handle_reset_device_identity(
         &self,
         seed: Option<[u8; 32]>,
@@ -221,14 +334,14 @@ impl CommandInterfaceConnectionHandler {
 
     pub(crate) async fn handle_get_device_zk_nyms(
         &self,
-    ) -> Result<Result<(), AccountError>, VpnCommandSendError> {
+    ) -> Result<Result<NymVpnZkNymResponse, AccountError>, VpnCommandSendError> {
         self.send_and_wait(VpnServiceCommand::GetDeviceZkNyms, ())
             .await
     }
 
     pub(crate) async fn handle_get_zk_nyms_available_for_download(
         &self,
-    ) -> Result<Result<(), AccountError>, VpnCommandSendError> {
+    ) -> Result<Result<NymVpnZkNymResponse, AccountError>, VpnCommandSendError> {
         self.send_and_wait(VpnServiceCommand::GetZkNymsAvailableForDownload, ())
             .await
     }
@@ -236,7 +349,7 @@ impl CommandInterfaceConnectionHandler {
     pub(crate) async fn handle_get_zk_nym_by_id(
         &self,
         id: String,
-    ) -> Result<Result<(), AccountError>, VpnCommandSendError> {
+    ) -> Result<Result<NymVpnZkNym, AccountError>, VpnCommandSendError> {
         self.send_and_wait(VpnServiceCommand::GetZkNymById, id)
             .await
     }
@@ -256,6 +369,13 @@ impl CommandInterfaceConnectionHandler {
             .await
     }
 
+    pub(crate) async fn handle_refresh_tickets(
+        &self,
+    ) -> Result<Result<u32, AccountError>, VpnCommandSendError> {
+        self.send_and_wait(VpnServiceCommand::RefreshTickets, ())
+            .await
+    }
+
     pub(crate) async fn handle_fetch_raw_account_summary(
         &self,
     ) -> Result<Result<NymVpnAccountSummaryResponse, AccountError>, VpnCommandSendError> {
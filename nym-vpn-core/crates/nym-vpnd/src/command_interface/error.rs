@@ -1,3 +1,5 @@
+use std::{net::SocketAddr, path::PathBuf};
+
 #[derive(Debug, thiserror::Error)]
 pub(crate) enum CommandInterfaceError {
     #[error("failed to parse DNS IP address: {ip}")]
@@ -5,4 +7,42 @@ pub(crate) enum CommandInterfaceError {
         ip: String,
         source: std::net::AddrParseError,
     },
+
+    #[error("failed to parse gateway identity: {id}")]
+    FailedToParseGatewayId { id: String },
+
+    #[error("invalid wireguard port: {port}")]
+    InvalidWireguardPort { port: u32 },
+
+    #[error("failed to parse routed destination {network}: {source}")]
+    FailedToParseRoutedDestination {
+        network: String,
+        source: ipnetwork::IpNetworkError,
+    },
+
+    #[error("routed destinations can't be combined with disabling the routing")]
+    RoutedDestinationsWithoutRouting,
+
+    #[error("the DNS leak probe interval must be more than zero")]
+    ZeroDnsLeakProbeInterval,
+
+    #[error("failed to parse proxy url {url}: {source}")]
+    FailedToParseProxyUrl {
+        url: String,
+        source: url::ParseError,
+    },
+
+    #[error("invalid proxy: {source}")]
+    InvalidProxy {
+        source: nym_vpn_api_client::VpnApiClientError,
+    },
+
+    #[error("refusing to listen on non-loopback address {address} without --allow-remote")]
+    RemoteListenerNotAllowed { address: SocketAddr },
+
+    #[error("failed to read TLS file: {}", path.display())]
+    ReadTlsFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
 }
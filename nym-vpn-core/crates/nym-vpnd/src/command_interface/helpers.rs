@@ -3,83 +3,129 @@
 
 use nym_vpn_api_client::types::Percent;
 use nym_vpn_lib::{
-    gateway_directory::{EntryPoint, ExitPoint},
-    NodeIdentity, Recipient,
+    gateway_directory::{EntryPoint, ExitPoint, ProxyConfig},
+    tunnel_state_machine::{RegionAffinity, SelectionStrategy},
 };
 use tracing::{error, info};
 
+use super::error::CommandInterfaceError;
+
 pub(super) fn parse_entry_point(
     entry: nym_vpn_proto::entry_node::EntryNodeEnum,
 ) -> Result<EntryPoint, tonic::Status> {
-    Ok(match entry {
+    let entry = match entry {
         nym_vpn_proto::entry_node::EntryNodeEnum::Location(location) => {
             info!(
                 "Connecting to entry node in country: {:?}",
                 location.two_letter_iso_country_code
             );
-            EntryPoint::Location {
-                location: location.two_letter_iso_country_code.to_string(),
-            }
+            format!("country:{}", location.two_letter_iso_country_code)
         }
         nym_vpn_proto::entry_node::EntryNodeEnum::Gateway(gateway) => {
             info!("Connecting to entry node with gateway id: {:?}", gateway.id);
-            let identity = NodeIdentity::from_base58_string(&gateway.id).map_err(|err| {
-                error!("Failed to parse gateway id: {:?}", err);
-                tonic::Status::invalid_argument("Invalid gateway id")
-            })?;
-            EntryPoint::Gateway { identity }
+            format!("gateway:{}", gateway.id)
         }
         nym_vpn_proto::entry_node::EntryNodeEnum::RandomLowLatency(_) => {
             info!("Connecting to low latency entry node");
-            EntryPoint::RandomLowLatency
+            "low-latency".to_string()
         }
         nym_vpn_proto::entry_node::EntryNodeEnum::Random(_) => {
             info!("Connecting to random entry node");
-            EntryPoint::Random
+            "random".to_string()
+        }
+        nym_vpn_proto::entry_node::EntryNodeEnum::Hostname(hostname) => {
+            info!("Connecting to entry node with hostname: {:?}", hostname);
+            format!("hostname:{hostname}")
         }
+    };
+    EntryPoint::from_cli_str(&entry).map_err(|err| {
+        error!("Failed to parse entry node: {}", err);
+        tonic::Status::invalid_argument(err.to_string())
     })
 }
 
 pub(super) fn parse_exit_point(
     exit: nym_vpn_proto::exit_node::ExitNodeEnum,
 ) -> Result<ExitPoint, tonic::Status> {
-    Ok(match exit {
+    let exit = match exit {
         nym_vpn_proto::exit_node::ExitNodeEnum::Address(address) => {
             info!(
                 "Connecting to exit node at address: {:?}",
                 address.nym_address
             );
-            let address =
-                Recipient::try_from_base58_string(address.nym_address.clone()).map_err(|err| {
-                    error!("Failed to parse exit node address: {:?}", err);
-                    tonic::Status::invalid_argument("Invalid exit node address")
-                })?;
-            ExitPoint::Address { address }
+            format!("address:{}", address.nym_address)
         }
         nym_vpn_proto::exit_node::ExitNodeEnum::Gateway(gateway) => {
             info!("Connecting to exit node with gateway id: {:?}", gateway.id);
-            let identity = NodeIdentity::from_base58_string(&gateway.id).map_err(|err| {
-                error!("Failed to parse gateway id: {:?}", err);
-                tonic::Status::invalid_argument("Invalid gateway id")
-            })?;
-            ExitPoint::Gateway { identity }
+            format!("gateway:{}", gateway.id)
         }
         nym_vpn_proto::exit_node::ExitNodeEnum::Location(location) => {
             info!(
                 "Connecting to exit node in country: {:?}",
                 location.two_letter_iso_country_code
             );
-            ExitPoint::Location {
-                location: location.two_letter_iso_country_code.to_string(),
-            }
+            format!("country:{}", location.two_letter_iso_country_code)
         }
         nym_vpn_proto::exit_node::ExitNodeEnum::Random(_) => {
             info!("Connecting to low latency exit node");
-            ExitPoint::Random
+            "random".to_string()
+        }
+        nym_vpn_proto::exit_node::ExitNodeEnum::Hostname(hostname) => {
+            info!("Connecting to exit node with hostname: {:?}", hostname);
+            format!("hostname:{hostname}")
         }
+    };
+    ExitPoint::from_cli_str(&exit).map_err(|err| {
+        error!("Failed to parse exit node: {}", err);
+        tonic::Status::invalid_argument(err.to_string())
     })
 }
 
+// Unknown values are treated like an unspecified affinity, i.e. no constraint.
+pub(super) fn into_region_affinity(region_affinity: i32) -> Option<RegionAffinity> {
+    match nym_vpn_proto::RegionAffinity::try_from(region_affinity) {
+        Ok(nym_vpn_proto::RegionAffinity::SameContinent) => Some(RegionAffinity::SameContinent),
+        Ok(nym_vpn_proto::RegionAffinity::DifferentContinent) => {
+            Some(RegionAffinity::DifferentContinent)
+        }
+        Ok(nym_vpn_proto::RegionAffinity::Unspecified) | Err(_) => None,
+    }
+}
+
+// Unknown values fall back to the random selection.
+pub(super) fn into_selection_strategy(selection_strategy: i32) -> SelectionStrategy {
+    match nym_vpn_proto::SelectionStrategy::try_from(selection_strategy) {
+        Ok(nym_vpn_proto::SelectionStrategy::HighestPerformance) => {
+            SelectionStrategy::HighestPerformance
+        }
+        Ok(nym_vpn_proto::SelectionStrategy::Random) | Err(_) => SelectionStrategy::Random,
+    }
+}
+
 pub(super) fn threshold_into_percent(threshold: nym_vpn_proto::Threshold) -> Percent {
     Percent::from_percentage_value(threshold.min_performance.clamp(0, 100) as u64).unwrap()
 }
+
+// The proxy is checked here so that a bad url is rejected with the request rather than failing
+// the directory lookup once connecting.
+pub(super) fn parse_proxy(
+    proxy: nym_vpn_proto::Proxy,
+) -> Result<ProxyConfig, CommandInterfaceError> {
+    let url = proxy
+        .url
+        .parse()
+        .map_err(|source| CommandInterfaceError::FailedToParseProxyUrl {
+            url: proxy.url.clone(),
+            source,
+        })?;
+    let config = match proxy.username {
+        Some(username) => {
+            ProxyConfig::new(url).with_credentials(username, proxy.password.unwrap_or_default())
+        }
+        None => ProxyConfig::new(url),
+    };
+    config
+        .to_reqwest_proxy()
+        .map_err(|source| CommandInterfaceError::InvalidProxy { source })?;
+    Ok(config)
+}
@@ -2,55 +2,83 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 use std::{
-    fs,
+    fmt, fs,
     net::SocketAddr,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use futures::{stream::BoxStream, StreamExt};
 use tokio::sync::{broadcast, mpsc::UnboundedSender};
 
 use nym_vpn_api_client::types::GatewayMinPerformance;
-use nym_vpn_lib::tunnel_state_machine::MixnetEvent;
+use nym_vpn_lib::{
+    tunnel_state_machine::{MixnetEvent, DEFAULT_CONNECT_DEADLINE},
+    NodeIdentity,
+};
 use nym_vpn_proto::{
-    nym_vpnd_server::NymVpnd, AccountError, ConfirmZkNymDownloadedRequest,
+    nym_vpnd_server::NymVpnd, AccountError, AccountStateChange, ConfirmZkNymDownloadedRequest,
     ConfirmZkNymDownloadedResponse, ConnectRequest, ConnectResponse, ConnectionStateChange,
-    ConnectionStatusUpdate, DisconnectRequest, DisconnectResponse, Empty,
+    ConnectionStatusUpdate, DaemonHealthRequest, DaemonHealthResponse, DisconnectRequest,
+    DisconnectResponse, Empty, ExportWireguardConfigRequest, ExportWireguardConfigResponse,
     FetchRawAccountSummaryRequest, FetchRawAccountSummaryResponse, FetchRawDevicesRequest,
     FetchRawDevicesResponse, GetAccountIdentityRequest, GetAccountIdentityResponse,
     GetAccountLinksRequest, GetAccountLinksResponse, GetAccountStateRequest,
     GetAccountStateResponse, GetAvailableTicketsRequest, GetAvailableTicketsResponse,
     GetDeviceIdentityRequest, GetDeviceIdentityResponse, GetDeviceZkNymsRequest,
-    GetDeviceZkNymsResponse, GetFeatureFlagsRequest, GetFeatureFlagsResponse,
-    GetSystemMessagesRequest, GetSystemMessagesResponse, GetZkNymByIdRequest, GetZkNymByIdResponse,
-    GetZkNymsAvailableForDownloadRequest, GetZkNymsAvailableForDownloadResponse, InfoRequest,
-    InfoResponse, IsAccountStoredRequest, IsAccountStoredResponse, IsReadyToConnectRequest,
-    IsReadyToConnectResponse, ListCountriesRequest, ListCountriesResponse, ListGatewaysRequest,
+    GetDeviceZkNymsResponse, GetExitPublicIpRequest, GetExitPublicIpResponse,
+    GetFeatureFlagsRequest, GetFeatureFlagsResponse, GetLastSessionSummaryRequest,
+    GetLastSessionSummaryResponse, GetSystemMessagesRequest, GetSystemMessagesResponse,
+    GetZkNymByIdRequest, GetZkNymByIdResponse, GetZkNymsAvailableForDownloadRequest,
+    GetZkNymsAvailableForDownloadResponse, InfoRequest, InfoResponse, IsAccountStoredRequest,
+    IsAccountStoredResponse, IsReadyToConnectRequest, IsReadyToConnectResponse,
+    ListAvailableNetworksRequest, ListAvailableNetworksResponse, ListCountriesRequest,
+    ListCountriesResponse, ListDirectoryRequest, ListDirectoryResponse, ListGatewaysRequest,
     ListGatewaysResponse, RefreshAccountStateRequest, RefreshAccountStateResponse,
-    RegisterDeviceRequest, RegisterDeviceResponse, RemoveAccountRequest, RemoveAccountResponse,
-    RequestZkNymRequest, RequestZkNymResponse, ResetDeviceIdentityRequest,
-    ResetDeviceIdentityResponse, SetNetworkRequest, SetNetworkResponse, StatusRequest,
-    StatusResponse, StoreAccountRequest, StoreAccountResponse,
+    RefreshTicketsRequest, RefreshTicketsResponse, RegisterDeviceRequest, RegisterDeviceResponse,
+    RemoveAccountRequest, RemoveAccountResponse, RequestZkNymRequest, RequestZkNymResponse,
+    ResetDeviceIdentityRequest, ResetDeviceIdentityResponse, ResetIdleTimerRequest,
+    ResetIdleTimerResponse, RotateWireguardKeysRequest, RotateWireguardKeysResponse,
+    RunConnectivityTestRequest, RunConnectivityTestResponse, SetNetworkRequest, SetNetworkResponse,
+    StatusRequest, StatusResponse, StoreAccountRequest, StoreAccountResponse,
 };
 
 use super::{
     connection_handler::CommandInterfaceConnectionHandler,
     error::CommandInterfaceError,
-    helpers::{parse_entry_point, parse_exit_point, threshold_into_percent},
+    helpers::{
+        into_region_affinity, into_selection_strategy, parse_entry_point, parse_exit_point,
+        parse_proxy, threshold_into_percent,
+    },
     protobuf::info_response::into_account_management_links,
 };
 use crate::{
     command_interface::protobuf::{
+        account::{into_proto_zk_nym, into_proto_zk_nym_page},
         connection_state::into_is_ready_to_connect_response_type,
         gateway::into_user_agent,
-        info_response::{into_proto_feature_flags, into_proto_system_message},
+        info_response::{
+            into_proto_available_network, into_proto_feature_flags, into_proto_system_message,
+        },
+    },
+    service::{
+        ConnectOptions, VpnServiceCommand, VpnServiceConnectError, VpnServiceConnectivityTestError,
+        VpnServiceExitPublicIpError, VpnServiceExportWireguardConfigError, VpnServiceStateChange,
     },
-    service::{ConnectOptions, VpnServiceCommand, VpnServiceStateChange},
 };
 
 enum ListenerType {
     Path(PathBuf),
-    Uri(#[allow(unused)] SocketAddr),
+    Uri(SocketAddr),
+}
+
+impl fmt::Display for ListenerType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenerType::Path(socket_path) => write!(f, "{}", socket_path.display()),
+            ListenerType::Uri(addr) => write!(f, "{addr}"),
+        }
+    }
 }
 
 pub(super) struct CommandInterface {
@@ -116,6 +144,7 @@ impl CommandInterface {
 
 impl Drop for CommandInterface {
     fn drop(&mut self) {
+        tracing::debug!("Command interface on {} stopped", self.listener);
         self.remove_previous_socket_file();
     }
 }
@@ -135,6 +164,19 @@ impl NymVpnd for CommandInterface {
         Ok(tonic::Response::new(response))
     }
 
+    async fn daemon_health(
+        &self,
+        _request: tonic::Request<DaemonHealthRequest>,
+    ) -> Result<tonic::Response<DaemonHealthResponse>, tonic::Status> {
+        let health = CommandInterfaceConnectionHandler::new(self.vpn_command_tx.clone())
+            .handle_health()
+            .await?;
+
+        let response = DaemonHealthResponse::from(health);
+        tracing::debug!("Returning daemon health response: {:?}", response);
+        Ok(tonic::Response::new(response))
+    }
+
     async fn set_network(
         &self,
         request: tonic::Request<SetNetworkRequest>,
@@ -154,6 +196,31 @@ impl NymVpnd for CommandInterface {
         Ok(tonic::Response::new(response))
     }
 
+    async fn list_available_networks(
+        &self,
+        _request: tonic::Request<ListAvailableNetworksRequest>,
+    ) -> Result<tonic::Response<ListAvailableNetworksResponse>, tonic::Status> {
+        tracing::debug!("Got list available networks request");
+
+        let networks = CommandInterfaceConnectionHandler::new(self.vpn_command_tx.clone())
+            .handle_list_available_networks()
+            .await
+            .map_err(|err| {
+                let msg = format!("Failed to list available networks: {err}");
+                tracing::error!(msg);
+                tonic::Status::internal(msg)
+            })?;
+
+        let response = ListAvailableNetworksResponse {
+            networks: networks
+                .into_iter()
+                .map(into_proto_available_network)
+                .collect(),
+        };
+        tracing::debug!("Returning list available networks response: {:?}", response);
+        Ok(tonic::Response::new(response))
+    }
+
     async fn get_system_messages(
         &self,
         _request: tonic::Request<GetSystemMessagesRequest>,
@@ -197,6 +264,24 @@ impl NymVpnd for CommandInterface {
 
         let connect_request = request.into_inner();
 
+        let user_agent = connect_request
+            .user_agent
+            .clone()
+            .map(into_user_agent)
+            .unwrap_or_else(crate::util::construct_user_agent);
+
+        // A request that sets nothing but the user agent uses the daemon's default options
+        let without_options = ConnectRequest {
+            user_agent: None,
+            ..connect_request.clone()
+        } == ConnectRequest::default();
+
+        let handler = CommandInterfaceConnectionHandler::new(self.vpn_command_tx.clone());
+        if without_options {
+            let status = handler.handle_connect_with_defaults(user_agent).await?;
+            return Ok(tonic::Response::new(into_connect_response(status)));
+        }
+
         let entry = connect_request
             .entry
             .clone()
@@ -211,34 +296,16 @@ impl NymVpnd for CommandInterface {
             .map(parse_exit_point)
             .transpose()?;
 
-        let user_agent = connect_request
-            .user_agent
-            .clone()
-            .map(into_user_agent)
-            .unwrap_or_else(crate::util::construct_user_agent);
-
         let options = ConnectOptions::try_from(connect_request).map_err(|err| {
             tracing::error!("Failed to parse connect options: {:?}", err);
             tonic::Status::invalid_argument("Invalid connect options")
         })?;
 
-        let status = CommandInterfaceConnectionHandler::new(self.vpn_command_tx.clone())
+        let status = handler
             .handle_connect(entry, exit, options, user_agent)
             .await?;
 
-        let response = match status {
-            Ok(()) => ConnectResponse {
-                success: true,
-                error: None,
-            },
-            Err(err) => ConnectResponse {
-                success: false,
-                error: Some(nym_vpn_proto::ConnectRequestError::from(err)),
-            },
-        };
-
-        tracing::debug!("Returning connect response: {:?}", response);
-        Ok(tonic::Response::new(response))
+        Ok(tonic::Response::new(into_connect_response(status)))
     }
 
     async fn vpn_disconnect(
@@ -256,6 +323,112 @@ impl NymVpnd for CommandInterface {
         Ok(tonic::Response::new(response))
     }
 
+    async fn rotate_wireguard_keys(
+        &self,
+        _request: tonic::Request<RotateWireguardKeysRequest>,
+    ) -> Result<tonic::Response<RotateWireguardKeysResponse>, tonic::Status> {
+        let status = CommandInterfaceConnectionHandler::new(self.vpn_command_tx.clone())
+            .handle_rotate_wireguard_keys()
+            .await?;
+
+        let response = RotateWireguardKeysResponse {
+            success: status.is_ok(),
+        };
+        tracing::debug!("Returning rotate wireguard keys response: {:?}", response);
+        Ok(tonic::Response::new(response))
+    }
+
+    async fn reset_idle_timer(
+        &self,
+        _request: tonic::Request<ResetIdleTimerRequest>,
+    ) -> Result<tonic::Response<ResetIdleTimerResponse>, tonic::Status> {
+        let status = CommandInterfaceConnectionHandler::new(self.vpn_command_tx.clone())
+            .handle_reset_idle_timer()
+            .await?;
+
+        let response = ResetIdleTimerResponse {
+            success: status.is_ok(),
+        };
+        tracing::debug!("Returning reset idle timer response: {:?}", response);
+        Ok(tonic::Response::new(response))
+    }
+
+    async fn export_wireguard_config(
+        &self,
+        request: tonic::Request<ExportWireguardConfigRequest>,
+    ) -> Result<tonic::Response<ExportWireguardConfigResponse>, tonic::Status> {
+        let include_private_keys = request.into_inner().include_private_keys;
+        let config = CommandInterfaceConnectionHandler::new(self.vpn_command_tx.clone())
+            .handle_export_wireguard_config(include_private_keys)
+            .await?
+            .map_err(|err| {
+                tracing::error!("Failed to export wireguard config: {:?}", err);
+                match err {
+                    VpnServiceExportWireguardConfigError::NotConnected => {
+                        tonic::Status::failed_precondition(err.to_string())
+                    }
+                    VpnServiceExportWireguardConfigError::Internal(_) => {
+                        tonic::Status::internal(err.to_string())
+                    }
+                }
+            })?;
+
+        Ok(tonic::Response::new(ExportWireguardConfigResponse {
+            config,
+        }))
+    }
+
+    async fn run_connectivity_test(
+        &self,
+        _request: tonic::Request<RunConnectivityTestRequest>,
+    ) -> Result<tonic::Response<RunConnectivityTestResponse>, tonic::Status> {
+        let report = CommandInterfaceConnectionHandler::new(self.vpn_command_tx.clone())
+            .handle_run_connectivity_test()
+            .await?
+            .map_err(|err| {
+                tracing::error!("Failed to run the connectivity test: {:?}", err);
+                match err {
+                    VpnServiceConnectivityTestError::NotConnected => {
+                        tonic::Status::failed_precondition(err.to_string())
+                    }
+                    VpnServiceConnectivityTestError::Internal(_) => {
+                        tonic::Status::internal(err.to_string())
+                    }
+                }
+            })?;
+
+        let response = RunConnectivityTestResponse::from(report);
+        tracing::debug!("Returning connectivity test response: {:?}", response);
+        Ok(tonic::Response::new(response))
+    }
+
+    async fn get_exit_public_ip(
+        &self,
+        _request: tonic::Request<GetExitPublicIpRequest>,
+    ) -> Result<tonic::Response<GetExitPublicIpResponse>, tonic::Status> {
+        let exit_ip = CommandInterfaceConnectionHandler::new(self.vpn_command_tx.clone())
+            .handle_get_exit_public_ip()
+            .await?
+            .map_err(|err| {
+                tracing::error!("Failed to get the exit public IP: {:?}", err);
+                match err {
+                    VpnServiceExitPublicIpError::NotConnected => {
+                        tonic::Status::failed_precondition(err.to_string())
+                    }
+                    VpnServiceExitPublicIpError::Lookup(_) => {
+                        tonic::Status::unavailable(err.to_string())
+                    }
+                    VpnServiceExitPublicIpError::Internal(_) => {
+                        tonic::Status::internal(err.to_string())
+                    }
+                }
+            })?;
+
+        let response = GetExitPublicIpResponse::from(exit_ip);
+        tracing::debug!("Returning exit public ip response: {:?}", response);
+        Ok(tonic::Response::new(response))
+    }
+
     async fn vpn_status(
         &self,
         _request: tonic::Request<StatusRequest>,
@@ -269,6 +442,20 @@ impl NymVpnd for CommandInterface {
         Ok(tonic::Response::new(response))
     }
 
+    async fn get_last_session_summary(
+        &self,
+        _request: tonic::Request<GetLastSessionSummaryRequest>,
+    ) -> Result<tonic::Response<GetLastSessionSummaryResponse>, tonic::Status> {
+        let summary = CommandInterfaceConnectionHandler::new(self.vpn_command_tx.clone())
+            .handle_get_last_session_summary()
+            .await?;
+
+        let response = GetLastSessionSummaryResponse {
+            summary: summary.map(nym_vpn_proto::SessionSummary::from),
+        };
+        Ok(tonic::Response::new(response))
+    }
+
     type ListenToConnectionStatusStream =
         BoxStream<'static, Result<ConnectionStatusUpdate, tonic::Status>>;
 
@@ -311,6 +498,35 @@ impl NymVpnd for CommandInterface {
         ))
     }
 
+    type ListenToAccountChangesStream =
+        BoxStream<'static, Result<AccountStateChange, tonic::Status>>;
+
+    async fn listen_to_account_changes(
+        &self,
+        request: tonic::Request<Empty>,
+    ) -> Result<tonic::Response<Self::ListenToAccountChangesStream>, tonic::Status> {
+        tracing::debug!("Got account changes stream request: {request:?}");
+        let (current, rx) = CommandInterfaceConnectionHandler::new(self.vpn_command_tx.clone())
+            .handle_subscribe_account_changes()
+            .await?;
+
+        // Late subscribers get the current state first, so they don't have to query it separately
+        let current = futures::stream::once(async move {
+            Ok(super::protobuf::account::current_account_state(current))
+        });
+        let changes = tokio_stream::wrappers::BroadcastStream::new(rx).map(|change| {
+            change
+                .map(super::protobuf::account::into_account_state_change)
+                .map_err(|err| {
+                    tracing::error!("Failed to receive account state change: {:?}", err);
+                    tonic::Status::internal("Failed to receive account state change")
+                })
+        });
+        Ok(tonic::Response::new(
+            Box::pin(current.chain(changes)) as Self::ListenToAccountChangesStream
+        ))
+    }
+
     async fn list_gateways(
         &self,
         request: tonic::Request<ListGatewaysRequest>,
@@ -417,6 +633,59 @@ impl NymVpnd for CommandInterface {
         Ok(tonic::Response::new(response))
     }
 
+    async fn list_directory(
+        &self,
+        request: tonic::Request<ListDirectoryRequest>,
+    ) -> Result<tonic::Response<ListDirectoryResponse>, tonic::Status> {
+        tracing::debug!("Got list directory request: {request:?}");
+
+        let request = request.into_inner();
+
+        let gw_type = nym_vpn_proto::GatewayType::try_from(request.kind)
+            .ok()
+            .and_then(crate::command_interface::protobuf::gateway::into_gateway_type)
+            .ok_or_else(|| {
+                let msg = format!("Failed to parse list directory kind: {}", request.kind);
+                tracing::error!(msg);
+                tonic::Status::invalid_argument(msg)
+            })?;
+
+        let user_agent = request
+            .user_agent
+            .map(into_user_agent)
+            .unwrap_or_else(crate::util::construct_user_agent);
+
+        let min_mixnet_performance = request.min_mixnet_performance.map(threshold_into_percent);
+        let min_vpn_performance = request.min_vpn_performance.map(threshold_into_percent);
+
+        let min_gateway_performance = GatewayMinPerformance {
+            mixnet_min_performance: min_mixnet_performance,
+            vpn_min_performance: min_vpn_performance,
+        };
+
+        let countries = CommandInterfaceConnectionHandler::new(self.vpn_command_tx.clone())
+            .handle_list_directory(gw_type, user_agent, min_gateway_performance)
+            .await
+            .map_err(|err| {
+                let msg = format!("Failed to list directory: {:?}", err);
+                tracing::error!(msg);
+                tonic::Status::internal(msg)
+            })?;
+
+        let response = ListDirectoryResponse {
+            countries: countries
+                .into_iter()
+                .map(nym_vpn_proto::CountryGateways::from)
+                .collect(),
+        };
+
+        tracing::debug!(
+            "Returning list directory response: {} countries",
+            response.countries.len()
+        );
+        Ok(tonic::Response::new(response))
+    }
+
     async fn store_account(
         &self,
         request: tonic::Request<StoreAccountRequest>,
@@ -693,13 +962,8 @@ impl NymVpnd for CommandInterface {
             .await?;
 
         let response = match result {
-            Ok(response) => RequestZkNymResponse {
-                json: serde_json::to_string(&response)
-                    .unwrap_or_else(|_| "failed to serialize".to_owned()),
-                error: None,
-            },
+            Ok(()) => RequestZkNymResponse { error: None },
             Err(err) => RequestZkNymResponse {
-                json: err.to_string(),
                 error: Some(AccountError::from(err)),
             },
         };
@@ -718,13 +982,15 @@ impl NymVpnd for CommandInterface {
 
         let response = match result {
             Ok(response) => GetDeviceZkNymsResponse {
-                json: serde_json::to_string(&response)
+                raw_json: serde_json::to_string(&response)
                     .unwrap_or_else(|_| "failed to serialize".to_owned()),
                 error: None,
+                zk_nyms: Some(into_proto_zk_nym_page(response)),
             },
             Err(err) => GetDeviceZkNymsResponse {
-                json: err.to_string(),
+                raw_json: err.to_string(),
                 error: Some(AccountError::from(err)),
+                zk_nyms: None,
             },
         };
 
@@ -742,13 +1008,15 @@ impl NymVpnd for CommandInterface {
 
         let response = match result {
             Ok(response) => GetZkNymsAvailableForDownloadResponse {
-                json: serde_json::to_string(&response)
+                raw_json: serde_json::to_string(&response)
                     .unwrap_or_else(|_| "failed to serialize".to_owned()),
                 error: None,
+                zk_nyms: Some(into_proto_zk_nym_page(response)),
             },
             Err(err) => GetZkNymsAvailableForDownloadResponse {
-                json: err.to_string(),
+                raw_json: err.to_string(),
                 error: Some(AccountError::from(err)),
+                zk_nyms: None,
             },
         };
 
@@ -768,13 +1036,15 @@ impl NymVpnd for CommandInterface {
 
         let response = match result {
             Ok(response) => GetZkNymByIdResponse {
-                json: serde_json::to_string(&response)
+                raw_json: serde_json::to_string(&response)
                     .unwrap_or_else(|_| "failed to serialize".to_owned()),
                 error: None,
+                zk_nym: Some(into_proto_zk_nym(response)),
             },
             Err(err) => GetZkNymByIdResponse {
-                json: err.to_string(),
+                raw_json: err.to_string(),
                 error: Some(AccountError::from(err)),
+                zk_nym: None,
             },
         };
 
@@ -847,6 +1117,30 @@ impl NymVpnd for CommandInterface {
         Ok(tonic::Response::new(response))
     }
 
+    async fn refresh_tickets(
+        &self,
+        _request: tonic::Request<RefreshTicketsRequest>,
+    ) -> Result<tonic::Response<RefreshTicketsResponse>, tonic::Status> {
+        tracing::debug!("Got refresh tickets request");
+
+        let result = CommandInterfaceConnectionHandler::new(self.vpn_command_tx.clone())
+            .handle_refresh_tickets()
+            .await?;
+
+        let response = match result {
+            Ok(new_tickets) => RefreshTicketsResponse {
+                new_tickets,
+                error: None,
+            },
+            Err(err) => RefreshTicketsResponse {
+                new_tickets: 0,
+                error: Some(AccountError::from(err)),
+            },
+        };
+
+        Ok(tonic::Response::new(response))
+    }
+
     async fn fetch_raw_account_summary(
         &self,
         _request: tonic::Request<FetchRawAccountSummaryRequest>,
@@ -911,6 +1205,46 @@ impl TryFrom<ConnectRequest> for ConnectOptions {
             })
             .transpose()?;
 
+        let excluded_gateways = request
+            .excluded_gateways
+            .iter()
+            .map(|id| {
+                NodeIdentity::from_base58_string(id)
+                    .map_err(|_| CommandInterfaceError::FailedToParseGatewayId { id: id.clone() })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let preferred_wg_ports = request
+            .preferred_wg_ports
+            .iter()
+            .map(|&port| {
+                u16::try_from(port)
+                    .map_err(|_| CommandInterfaceError::InvalidWireguardPort { port })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if request.disable_routing && !request.routed_destinations.is_empty() {
+            return Err(CommandInterfaceError::RoutedDestinationsWithoutRouting);
+        }
+        let routed_destinations = request
+            .routed_destinations
+            .iter()
+            .map(|network| {
+                network.parse().map_err(|source| {
+                    CommandInterfaceError::FailedToParseRoutedDestination {
+                        network: network.clone(),
+                        source,
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if request.dns_leak_probe_interval_secs == Some(0) {
+            return Err(CommandInterfaceError::ZeroDnsLeakProbeInterval);
+        }
+
+        let proxy = request.proxy.map(parse_proxy).transpose()?;
+
         let min_mixnode_performance = request.min_mixnode_performance.map(threshold_into_percent);
         let min_gateway_mixnet_performance = request
             .min_gateway_mixnet_performance
@@ -919,24 +1253,78 @@ impl TryFrom<ConnectRequest> for ConnectOptions {
             .min_gateway_vpn_performance
             .map(threshold_into_percent);
 
-        let disable_background_cover_traffic = if request.enable_two_hop {
-            // If two-hop is enabled, we always disable background cover traffic
-            true
-        } else {
-            request.disable_background_cover_traffic
-        };
+        // If two-hop is enabled, we always disable cover traffic. The request can't enable it
+        // explicitly, so there is nothing to warn about.
+        let disable_poisson_rate = request.enable_two_hop || request.disable_poisson_rate;
+        let disable_background_cover_traffic =
+            request.enable_two_hop || request.disable_background_cover_traffic;
 
         Ok(ConnectOptions {
             dns,
             disable_routing: request.disable_routing,
             enable_two_hop: request.enable_two_hop,
             netstack: request.netstack,
-            disable_poisson_rate: request.disable_poisson_rate,
+            disable_poisson_rate,
             disable_background_cover_traffic,
             enable_credentials_mode: request.enable_credentials_mode,
             min_mixnode_performance,
             min_gateway_mixnet_performance,
             min_gateway_vpn_performance,
+            mixnet_client_startup_timeout: request
+                .mixnet_client_startup_timeout_secs
+                .map(Duration::from_secs),
+            sticky_gateways: request.sticky_gateways,
+            disallow_same_country: request.disallow_same_country,
+            allow_lan: request.allow_lan,
+            excluded_gateways,
+            key_namespace: request.key_namespace,
+            detect_dns_leaks: request.detect_dns_leaks,
+            dns_leak_probe_interval: request
+                .dns_leak_probe_interval_secs
+                .map(Duration::from_secs),
+            disable_reconnect_on_network_change: request.disable_reconnect_on_network_change,
+            key_rotation_interval: request.key_rotation_interval_secs.map(Duration::from_secs),
+            max_session_duration: request.max_session_duration_secs.map(Duration::from_secs),
+            selection_seed: request.selection_seed,
+            region_affinity: into_region_affinity(request.region_affinity),
+            selection_strategy: into_selection_strategy(request.selection_strategy),
+            connect_deadline: match request.connect_deadline_secs {
+                Some(0) => None,
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => Some(DEFAULT_CONNECT_DEADLINE),
+            },
+            gateway_dns: request.gateway_dns,
+            preferred_wg_ports,
+            routed_destinations,
+            latency_sample_interval: request
+                .latency_sample_interval_secs
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            idle_timeout: request
+                .idle_timeout_secs
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            directory_cache_ttl: request
+                .directory_cache_ttl_secs
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            proxy,
         })
     }
 }
+
+fn into_connect_response(status: Result<(), VpnServiceConnectError>) -> ConnectResponse {
+    let response = match status {
+        Ok(()) => ConnectResponse {
+            success: true,
+            error: None,
+        },
+        Err(err) => ConnectResponse {
+            success: false,
+            error: Some(nym_vpn_proto::ConnectRequestError::from(err)),
+        },
+    };
+
+    tracing::debug!("Returning connect response: {:?}", response);
+    response
+}
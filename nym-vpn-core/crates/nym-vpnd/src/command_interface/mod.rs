@@ -10,4 +10,5 @@ mod protobuf;
 mod socket_stream;
 mod start;
 
+pub(crate) use config::HttpListenerTls;
 pub(crate) use start::{start_command_interface, CommandInterfaceOptions};
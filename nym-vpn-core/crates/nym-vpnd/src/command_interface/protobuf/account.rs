@@ -2,8 +2,12 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 use maplit::hashmap;
-use nym_vpn_account_controller::AccountStateSummary;
+use nym_vpn_account_controller::{AccountStateChange, AccountStateSummary};
+use nym_vpn_api_client::response::{
+    NymVpnZkNym, NymVpnZkNymPost, NymVpnZkNymResponse, NymVpnZkNymStatus,
+};
 use nym_vpn_proto::account_error::AccountErrorType;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
 use crate::service::AccountError;
 
@@ -17,6 +21,37 @@ pub(crate) fn into_account_summary(state: AccountStateSummary) -> nym_vpn_proto:
     }
 }
 
+pub(crate) fn into_account_state_change(
+    change: AccountStateChange,
+) -> nym_vpn_proto::AccountStateChange {
+    use nym_vpn_proto::account_state_change::Change;
+    let change = match change {
+        AccountStateChange::Mnemonic(mnemonic) => Change::Mnemonic(into_mnemonic(mnemonic) as i32),
+        AccountStateChange::Account(account) => Change::Account(into_account(account) as i32),
+        AccountStateChange::Subscription(subscription) => {
+            Change::Subscription(into_subscription(subscription) as i32)
+        }
+        AccountStateChange::Device(device) => Change::Device(into_device(device) as i32),
+        AccountStateChange::PendingZkNym(pending) => Change::PendingZkNym(pending),
+        AccountStateChange::ZkNymAvailable { ticket_type } => {
+            Change::ZkNymAvailable(nym_vpn_proto::ZkNymAvailable { ticket_type })
+        }
+    };
+    nym_vpn_proto::AccountStateChange {
+        change: Some(change),
+    }
+}
+
+pub(crate) fn current_account_state(
+    state: AccountStateSummary,
+) -> nym_vpn_proto::AccountStateChange {
+    nym_vpn_proto::AccountStateChange {
+        change: Some(nym_vpn_proto::account_state_change::Change::Current(
+            into_account_summary(state),
+        )),
+    }
+}
+
 fn into_mnemonic(
     mnemonic: nym_vpn_account_controller::shared_state::MnemonicState,
 ) -> nym_vpn_proto::MnemonicState {
@@ -87,6 +122,68 @@ fn into_device(
     }
 }
 
+pub(crate) fn into_proto_zk_nym(zk_nym: NymVpnZkNym) -> nym_vpn_proto::ZkNym {
+    nym_vpn_proto::ZkNym {
+        id: zk_nym.id,
+        ticketbook_type: zk_nym.ticketbook_type,
+        status: into_zk_nym_status(zk_nym.status) as i32,
+        valid_from: into_proto_timestamp(&zk_nym.valid_from_utc),
+        valid_until: into_proto_timestamp(&zk_nym.valid_until_utc),
+        created_on: into_proto_timestamp(&zk_nym.created_on_utc),
+        last_updated: into_proto_timestamp(&zk_nym.last_updated_utc),
+        issued_bandwidth_in_gb: zk_nym.issued_bandwidth_in_gb,
+    }
+}
+
+pub(crate) fn into_proto_zk_nym_page(response: NymVpnZkNymResponse) -> nym_vpn_proto::ZkNymPage {
+    nym_vpn_proto::ZkNymPage {
+        zk_nyms: response
+            .items
+            .into_iter()
+            .map(into_proto_zk_nym_post)
+            .collect(),
+        total_items: response.total_items,
+        page: response.page,
+        page_size: response.page_size,
+    }
+}
+
+// The listed zk-nyms only differ in the shape of the blinded shares, which aren't exposed.
+fn into_proto_zk_nym_post(zk_nym: NymVpnZkNymPost) -> nym_vpn_proto::ZkNym {
+    into_proto_zk_nym(NymVpnZkNym {
+        created_on_utc: zk_nym.created_on_utc,
+        last_updated_utc: zk_nym.last_updated_utc,
+        id: zk_nym.id,
+        ticketbook_type: zk_nym.ticketbook_type,
+        valid_until_utc: zk_nym.valid_until_utc,
+        valid_from_utc: zk_nym.valid_from_utc,
+        issued_bandwidth_in_gb: zk_nym.issued_bandwidth_in_gb,
+        blinded_shares: None,
+        status: zk_nym.status,
+    })
+}
+
+fn into_zk_nym_status(status: NymVpnZkNymStatus) -> nym_vpn_proto::ZkNymStatus {
+    match status {
+        NymVpnZkNymStatus::Pending => nym_vpn_proto::ZkNymStatus::Pending,
+        NymVpnZkNymStatus::Active => nym_vpn_proto::ZkNymStatus::Active,
+        NymVpnZkNymStatus::Revoking => nym_vpn_proto::ZkNymStatus::Revoking,
+        NymVpnZkNymStatus::Revoked => nym_vpn_proto::ZkNymStatus::Revoked,
+        NymVpnZkNymStatus::Error => nym_vpn_proto::ZkNymStatus::Error,
+    }
+}
+
+// The API reports the dates as RFC 3339 strings, left unset when they can't be parsed.
+fn into_proto_timestamp(datetime: &str) -> Option<prost_types::Timestamp> {
+    OffsetDateTime::parse(datetime, &Rfc3339)
+        .inspect_err(|err| tracing::warn!("Failed to parse zk-nym date {datetime}: {err}"))
+        .ok()
+        .map(|datetime| prost_types::Timestamp {
+            seconds: datetime.unix_timestamp(),
+            nanos: datetime.nanosecond() as i32,
+        })
+}
+
 impl From<AccountError> for nym_vpn_proto::AccountError {
     fn from(err: AccountError) -> Self {
         match err {
@@ -190,3 +287,52 @@ impl From<AccountError> for nym_vpn_proto::AccountError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zk_nym_dates_are_parsed_as_rfc3339() {
+        let timestamp = into_proto_timestamp("2024-11-05T12:30:15.25Z").unwrap();
+        assert_eq!(timestamp.seconds, 1730809815);
+        assert_eq!(timestamp.nanos, 250_000_000);
+
+        // The offset is taken into account
+        let timestamp = into_proto_timestamp("2024-11-05T14:30:15+02:00").unwrap();
+        assert_eq!(timestamp.seconds, 1730809815);
+        assert_eq!(timestamp.nanos, 0);
+    }
+
+    #[test]
+    fn unparsable_zk_nym_dates_are_left_unset() {
+        assert_eq!(into_proto_timestamp(""), None);
+        assert_eq!(into_proto_timestamp("2024-11-05"), None);
+    }
+
+    #[test]
+    fn listed_zk_nyms_map_like_single_zk_nyms() {
+        let zk_nym = into_proto_zk_nym_post(NymVpnZkNymPost {
+            created_on_utc: "2024-11-05T12:30:15Z".to_owned(),
+            last_updated_utc: "not a date".to_owned(),
+            id: "zk-nym-id".to_owned(),
+            ticketbook_type: "v1-mixnet-entry".to_owned(),
+            valid_until_utc: "2024-12-05T12:30:15Z".to_owned(),
+            valid_from_utc: "2024-11-05T12:30:15Z".to_owned(),
+            issued_bandwidth_in_gb: 1.5,
+            blinded_shares: Some(Vec::new()),
+            status: NymVpnZkNymStatus::Active,
+        });
+
+        assert_eq!(zk_nym.id, "zk-nym-id");
+        assert_eq!(zk_nym.ticketbook_type, "v1-mixnet-entry");
+        assert_eq!(zk_nym.status, nym_vpn_proto::ZkNymStatus::Active as i32);
+        assert_eq!(zk_nym.valid_from, zk_nym.created_on);
+        assert_eq!(
+            zk_nym.valid_until.map(|until| until.seconds),
+            Some(1733401815)
+        );
+        assert_eq!(zk_nym.last_updated, None);
+        assert_eq!(zk_nym.issued_bandwidth_in_gb, 1.5);
+    }
+}
@@ -16,6 +16,7 @@ impl From<VpnServiceStateChange> for ConnectionStateChange {
             VpnServiceStateChange::NotConnected => ConnectionStatus::NotConnected,
             VpnServiceStateChange::Connecting => ConnectionStatus::Connecting,
             VpnServiceStateChange::Connected => ConnectionStatus::Connected,
+            VpnServiceStateChange::Paused => ConnectionStatus::Paused,
             VpnServiceStateChange::Disconnecting => ConnectionStatus::Disconnecting,
             VpnServiceStateChange::ConnectionFailed(reason) => {
                 error = Some(ProtoError::from(reason));
@@ -0,0 +1,49 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use nym_vpn_lib::tunnel_state_machine::{
+    ConnectivityTestReport, ConnectivityTestStep, ConnectivityTestStepKind, ExitPublicIp,
+};
+use nym_vpn_proto::{
+    run_connectivity_test_response::{step::StepType, Step},
+    GetExitPublicIpResponse, RunConnectivityTestResponse,
+};
+
+impl From<ConnectivityTestReport> for RunConnectivityTestResponse {
+    fn from(report: ConnectivityTestReport) -> Self {
+        Self {
+            passed: report.passed(),
+            failed_step: report
+                .failed_step()
+                .map(|kind| into_proto_step_type(kind) as i32),
+            rtt_ms: report.rtt.map(|rtt| rtt.as_millis() as u64),
+            steps: report.steps.into_iter().map(into_proto_step).collect(),
+        }
+    }
+}
+
+fn into_proto_step(step: ConnectivityTestStep) -> Step {
+    Step {
+        step_type: into_proto_step_type(step.kind) as i32,
+        passed: step.error.is_none(),
+        duration_ms: step.duration.as_millis() as u64,
+        error: step.error,
+    }
+}
+
+fn into_proto_step_type(kind: ConnectivityTestStepKind) -> StepType {
+    match kind {
+        ConnectivityTestStepKind::Dns => StepType::Dns,
+        ConnectivityTestStepKind::Http => StepType::Http,
+        ConnectivityTestStepKind::Rtt => StepType::Rtt,
+    }
+}
+
+impl From<ExitPublicIp> for GetExitPublicIpResponse {
+    fn from(exit_ip: ExitPublicIp) -> Self {
+        Self {
+            ip: exit_ip.ip.to_string(),
+            country: exit_ip.country,
+        }
+    }
+}
@@ -305,13 +305,20 @@ impl From<ConnectionFailedError> for ProtoError {
                     "reason".to_string() => reason.clone(),
                 },
             },
-            ConnectionFailedError::FailedToSelectEntryGatewayIdNotFound { ref requested_id } => {
+            ConnectionFailedError::FailedToSelectEntryGatewayIdNotFound {
+                ref requested_id,
+                ref network_name,
+            } => {
+                let mut details = hashmap! {
+                    "requested_id".to_string() => requested_id.clone(),
+                };
+                if let Some(network_name) = network_name {
+                    details.insert("network_name".to_string(), network_name.clone());
+                }
                 ProtoError {
                     kind: ErrorType::GatewayDirectoryEntryId as i32,
                     message: err.to_string(),
-                    details: hashmap! {
-                        "requested_id".to_string() => requested_id.clone(),
-                    },
+                    details,
                 }
             }
             ConnectionFailedError::FailedToSelectEntryGatewayLocation {
@@ -450,6 +457,59 @@ impl From<ConnectionFailedError> for ProtoError {
                     "reason".to_string() => reason.to_string(),
                 },
             },
+            ConnectionFailedError::SystemClockSkew { skew_seconds } => ProtoError {
+                kind: ErrorType::SystemClockSkew as i32,
+                message: err.to_string(),
+                details: hashmap! {
+                    "skew_seconds".to_string() => skew_seconds.to_string(),
+                },
+            },
+            ConnectionFailedError::AllGatewaysExcluded => ProtoError {
+                kind: ErrorType::AllGatewaysExcluded as i32,
+                message: err.to_string(),
+                details: Default::default(),
+            },
+            ConnectionFailedError::GatewayBelowPerformanceThreshold { min_performance } => {
+                ProtoError {
+                    kind: ErrorType::GatewayBelowPerformanceThreshold as i32,
+                    message: err.to_string(),
+                    details: hashmap! {
+                        "min_performance".to_string() => min_performance.to_string(),
+                    },
+                }
+            }
+            ConnectionFailedError::RegionAffinityNotSatisfied => ProtoError {
+                kind: ErrorType::RegionAffinityNotSatisfied as i32,
+                message: err.to_string(),
+                details: Default::default(),
+            },
+            ConnectionFailedError::WireguardPortNotAllowed => ProtoError {
+                kind: ErrorType::WireguardPortNotAllowed as i32,
+                message: err.to_string(),
+                details: Default::default(),
+            },
+            ConnectionFailedError::ConnectAttemptsExhausted => ProtoError {
+                kind: ErrorType::ConnectAttemptsExhausted as i32,
+                message: err.to_string(),
+                details: Default::default(),
+            },
+            ConnectionFailedError::GatewayWithoutAuthenticator => ProtoError {
+                kind: ErrorType::GatewayWithoutAuthenticator as i32,
+                message: err.to_string(),
+                details: Default::default(),
+            },
+            ConnectionFailedError::InvalidTunAddressOverride => ProtoError {
+                kind: ErrorType::InvalidTunAddressOverride as i32,
+                message: err.to_string(),
+                details: Default::default(),
+            },
+            ConnectionFailedError::ConnectDeadlineExceeded { ref pending_step } => ProtoError {
+                kind: ErrorType::ConnectDeadlineExceeded as i32,
+                message: err.to_string(),
+                details: hashmap! {
+                    "pending_step".to_string() => pending_step.to_string(),
+                },
+            },
         }
     }
 }
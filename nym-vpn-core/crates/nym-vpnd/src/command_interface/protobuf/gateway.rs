@@ -86,6 +86,19 @@ impl From<gateway::Country> for nym_vpn_proto::Location {
     }
 }
 
+impl From<gateway::CountryGateways> for nym_vpn_proto::CountryGateways {
+    fn from(country_gateways: gateway::CountryGateways) -> Self {
+        nym_vpn_proto::CountryGateways {
+            country: Some(nym_vpn_proto::Location::from(country_gateways.country)),
+            gateways: country_gateways
+                .gateways
+                .into_iter()
+                .map(nym_vpn_proto::GatewayResponse::from)
+                .collect(),
+        }
+    }
+}
+
 pub(crate) fn into_gateway_type(gateway_type: nym_vpn_proto::GatewayType) -> Option<GatewayType> {
     match gateway_type {
         nym_vpn_proto::GatewayType::Unspecified => None,
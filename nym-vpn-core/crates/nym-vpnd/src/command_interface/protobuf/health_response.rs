@@ -0,0 +1,22 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use nym_vpn_proto::{ConnectionStatus, DaemonHealthResponse};
+
+use crate::service::VpnServiceHealth;
+
+// Bump this when the meaning of an existing field in the health report changes. Adding new fields
+// doesn't require a bump, since older clients will just ignore them.
+const DAEMON_HEALTH_VERSION: u32 = 1;
+
+impl From<VpnServiceHealth> for DaemonHealthResponse {
+    fn from(health: VpnServiceHealth) -> Self {
+        Self {
+            version: DAEMON_HEALTH_VERSION,
+            uptime_secs: health.uptime.as_secs(),
+            tunnel_state: ConnectionStatus::from(&health.status) as i32,
+            network_name: health.network_name,
+            is_account_stored: health.is_account_stored,
+        }
+    }
+}
@@ -22,10 +22,21 @@ impl From<VpnServiceInfo> for nym_vpn_proto::InfoResponse {
             git_commit: info.git_commit,
             nym_network,
             nym_vpn_network,
+            capabilities: Some(into_proto_capabilities(info.capabilities)),
         }
     }
 }
 
+fn into_proto_capabilities(capabilities: nym_vpn_lib::Capabilities) -> nym_vpn_proto::Capabilities {
+    nym_vpn_proto::Capabilities {
+        wireguard_supported: capabilities.wireguard_supported,
+        mixnet_supported: capabilities.mixnet_supported,
+        kill_switch_supported: capabilities.kill_switch_supported,
+        split_tunnel_supported: capabilities.split_tunnel_supported,
+        dns64_supported: capabilities.dns64_supported,
+    }
+}
+
 fn into_proto_nym_network_details(
     nym_network: nym_vpn_network_config::NymNetwork,
 ) -> nym_vpn_proto::NymNetworkDetails {
@@ -120,6 +131,17 @@ pub(crate) fn into_proto_system_message(
     }
 }
 
+pub(crate) fn into_proto_available_network(
+    network: nym_vpn_network_config::AvailableNetwork,
+) -> nym_vpn_proto::AvailableNetwork {
+    nym_vpn_proto::AvailableNetwork {
+        name: network.name,
+        display_name: network.display_name,
+        nym_api_url: network.nym_api_url.map(into_proto_url),
+        nym_vpn_api_url: network.nym_vpn_api_url.map(into_proto_url),
+    }
+}
+
 pub(crate) fn into_account_management_links(
     account_links: nym_vpn_network_config::ParsedAccountLinks,
 ) -> nym_vpn_proto::AccountManagement {
@@ -154,3 +176,33 @@ pub(crate) fn into_proto_feature_flags(
 
     response
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_capability_is_reported_in_its_own_field() {
+        let none = nym_vpn_lib::Capabilities {
+            wireguard_supported: false,
+            mixnet_supported: false,
+            kill_switch_supported: false,
+            split_tunnel_supported: false,
+            dns64_supported: false,
+        };
+        let only_dns64 = into_proto_capabilities(nym_vpn_lib::Capabilities {
+            dns64_supported: true,
+            ..none
+        });
+        assert!(only_dns64.dns64_supported);
+        assert!(!only_dns64.wireguard_supported && !only_dns64.mixnet_supported);
+
+        let only_mixnet = into_proto_capabilities(nym_vpn_lib::Capabilities {
+            mixnet_supported: true,
+            ..none
+        });
+        assert!(only_mixnet.mixnet_supported);
+        assert!(!only_mixnet.wireguard_supported && !only_mixnet.dns64_supported);
+        assert!(!only_mixnet.kill_switch_supported && !only_mixnet.split_tunnel_supported);
+    }
+}
@@ -6,7 +6,11 @@ use nym_vpn_proto::{
     StatusResponse, WgConnectedStateDetails,
 };
 
-use crate::service::{ConnectedStateDetails, VpnServiceStatus};
+use nym_vpn_lib::tunnel_state_machine::{
+    GatewayDiagnostics, MixnetPacketDrops, PacketDropCounts, TunnelType,
+};
+
+use crate::service::{ConnectedStateDetails, SessionSummaryDetails, VpnServiceStatus};
 
 impl From<ConnectedStateDetails> for connected_state_details::ConnectedStateDetails {
     fn from(value: ConnectedStateDetails) -> Self {
@@ -33,18 +37,59 @@ impl From<ConnectedStateDetails> for connected_state_details::ConnectedStateDeta
     }
 }
 
+fn into_proto_gateway_diagnostics(
+    diagnostics: GatewayDiagnostics,
+) -> nym_vpn_proto::GatewayDiagnostics {
+    nym_vpn_proto::GatewayDiagnostics {
+        latency_ms: diagnostics
+            .latency
+            .map(|latency| latency.as_millis() as u64),
+        performance: diagnostics.performance.map(u32::from),
+    }
+}
+
+fn into_proto_packet_drop_counts(counts: PacketDropCounts) -> nym_vpn_proto::PacketDropCounts {
+    nym_vpn_proto::PacketDropCounts {
+        queue_full: counts.queue_full,
+        oversized: counts.oversized,
+        send_error: counts.send_error,
+    }
+}
+
+fn into_proto_packet_drops(drops: MixnetPacketDrops) -> nym_vpn_proto::MixnetPacketDrops {
+    nym_vpn_proto::MixnetPacketDrops {
+        ingress: Some(into_proto_packet_drop_counts(drops.ingress)),
+        egress: Some(into_proto_packet_drop_counts(drops.egress)),
+    }
+}
+
+impl From<&VpnServiceStatus> for ConnectionStatus {
+    fn from(status: &VpnServiceStatus) -> Self {
+        match status {
+            VpnServiceStatus::NotConnected => ConnectionStatus::NotConnected,
+            VpnServiceStatus::Connecting => ConnectionStatus::Connecting,
+            VpnServiceStatus::Connected(_) => ConnectionStatus::Connected,
+            VpnServiceStatus::Paused(_) => ConnectionStatus::Paused,
+            VpnServiceStatus::Disconnecting => ConnectionStatus::Disconnecting,
+            VpnServiceStatus::ConnectionFailed(_) => ConnectionStatus::ConnectionFailed,
+        }
+    }
+}
+
 impl From<VpnServiceStatus> for StatusResponse {
     fn from(status: VpnServiceStatus) -> Self {
         let mut details = None;
         let mut error = None;
-        let status = match status {
-            VpnServiceStatus::NotConnected => ConnectionStatus::NotConnected,
-            VpnServiceStatus::Connecting => ConnectionStatus::Connecting,
-            VpnServiceStatus::Connected(conn_details) => {
+        let mut connected_since = None;
+        let mut duration_secs = None;
+        let connection_status = ConnectionStatus::from(&status);
+        match status {
+            VpnServiceStatus::Connected(conn_details) | VpnServiceStatus::Paused(conn_details) => {
                 let timestamp = prost_types::Timestamp {
                     seconds: conn_details.since.unix_timestamp(),
                     nanos: conn_details.since.nanosecond() as i32,
                 };
+                duration_secs = conn_details.duration.map(|duration| duration.as_secs());
                 details = Some(nym_vpn_proto::ConnectionDetails {
                     entry_gateway: Some(nym_vpn_proto::Gateway {
                         id: conn_details.entry_gateway.to_string(),
@@ -52,6 +97,12 @@ impl From<VpnServiceStatus> for StatusResponse {
                     exit_gateway: Some(nym_vpn_proto::Gateway {
                         id: conn_details.exit_gateway.to_string(),
                     }),
+                    entry_gateway_diagnostics: Some(into_proto_gateway_diagnostics(
+                        conn_details.entry_gateway_diagnostics,
+                    )),
+                    exit_gateway_diagnostics: Some(into_proto_gateway_diagnostics(
+                        conn_details.exit_gateway_diagnostics,
+                    )),
                     protocol_details: Some(nym_vpn_proto::ConnectedStateDetails {
                         connected_state_details: Some(
                             connected_state_details::ConnectedStateDetails::from(
@@ -60,20 +111,58 @@ impl From<VpnServiceStatus> for StatusResponse {
                         ),
                     }),
                     since: Some(timestamp),
+                    tun_name: conn_details.tun_name,
+                    mtu: u32::from(conn_details.mtu),
+                    credentials_mode_active: conn_details.credentials_mode_active,
+                    poisson_rate_disabled: conn_details.mixnet_client_config.disable_poisson_rate,
+                    background_cover_traffic_disabled: conn_details
+                        .mixnet_client_config
+                        .disable_background_cover_traffic,
+                    packet_drops: conn_details.packet_drops.map(into_proto_packet_drops),
                 });
-                ConnectionStatus::Connected
+                connected_since = Some(timestamp);
             }
-            VpnServiceStatus::Disconnecting => ConnectionStatus::Disconnecting,
             VpnServiceStatus::ConnectionFailed(reason) => {
                 error = Some(ProtoError::from(reason));
-                ConnectionStatus::ConnectionFailed
             }
-        } as i32;
+            VpnServiceStatus::NotConnected
+            | VpnServiceStatus::Connecting
+            | VpnServiceStatus::Disconnecting => {}
+        }
 
         StatusResponse {
-            status,
+            status: connection_status as i32,
             details,
             error,
+            connected_since,
+            duration_secs,
+        }
+    }
+}
+
+impl From<SessionSummaryDetails> for nym_vpn_proto::SessionSummary {
+    fn from(summary: SessionSummaryDetails) -> Self {
+        nym_vpn_proto::SessionSummary {
+            entry_gateway: Some(nym_vpn_proto::Gateway {
+                id: summary.entry_gateway.to_string(),
+            }),
+            exit_gateway: Some(nym_vpn_proto::Gateway {
+                id: summary.exit_gateway.to_string(),
+            }),
+            tunnel_type: match summary.tunnel_type {
+                Some(TunnelType::Mixnet) => nym_vpn_proto::TunnelType::Mixnet,
+                Some(TunnelType::Wireguard) => nym_vpn_proto::TunnelType::Wireguard,
+                None => nym_vpn_proto::TunnelType::Unspecified,
+            } as i32,
+            started_at: Some(prost_types::Timestamp {
+                seconds: summary.started_at.unix_timestamp(),
+                nanos: summary.started_at.nanosecond() as i32,
+            }),
+            duration_secs: summary.duration.as_secs(),
+            bytes_sent: summary.bytes_sent,
+            bytes_received: summary.bytes_received,
+            average_throughput: summary.average_throughput,
+            tickets_spent: summary.tickets_spent,
         }
     }
 }
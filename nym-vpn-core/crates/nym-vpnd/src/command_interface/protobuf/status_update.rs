@@ -5,6 +5,7 @@ use nym_vpn_lib::{
     connection_monitor::ConnectionMonitorStatus,
     tunnel_state_machine::{
         BandwidthEvent, ConnectionEvent, ConnectionStatisticsEvent, MixnetEvent,
+        WireguardTrafficEvent,
     },
 };
 use nym_vpn_proto::{connection_status_update::StatusType, ConnectionStatusUpdate};
@@ -16,6 +17,7 @@ pub fn status_update_from_event(event: MixnetEvent) -> ConnectionStatusUpdate {
         MixnetEvent::ConnectionStatistics(sub_event) => {
             convert_connection_statistics_event(sub_event)
         }
+        MixnetEvent::WireguardTraffic(sub_event) => convert_wireguard_traffic_event(sub_event),
     }
 }
 
@@ -57,6 +59,90 @@ fn convert_connection_event(event: ConnectionEvent) -> ConnectionStatusUpdate {
             message: ConnectionMonitorStatus::ConnectedIpv6.to_string(),
             details: Default::default(),
         },
+        ConnectionEvent::RetryingGatewayLookup {
+            attempt,
+            max_retries,
+        } => ConnectionStatusUpdate {
+            kind: StatusType::RetryingGatewayLookup as i32,
+            message: event.to_string(),
+            details: maplit::hashmap! {
+                "attempt".to_string() => attempt.to_string(),
+                "max_retries".to_string() => max_retries.to_string(),
+            },
+        },
+        ConnectionEvent::RetryingIprConnect {
+            attempt,
+            max_retries,
+        } => ConnectionStatusUpdate {
+            kind: StatusType::RetryingIprConnect as i32,
+            message: event.to_string(),
+            details: maplit::hashmap! {
+                "attempt".to_string() => attempt.to_string(),
+                "max_retries".to_string() => max_retries.to_string(),
+            },
+        },
+        ConnectionEvent::DnsLeakDetected { server } => ConnectionStatusUpdate {
+            kind: StatusType::DnsLeakDetected as i32,
+            message: event.to_string(),
+            details: maplit::hashmap! {
+                "server".to_string() => server.to_string(),
+            },
+        },
+        ConnectionEvent::Quality { rtt_ms, loss_pct } => ConnectionStatusUpdate {
+            kind: StatusType::ConnectionQuality as i32,
+            message: event.to_string(),
+            details: maplit::hashmap! {
+                "rtt_ms".to_string() => rtt_ms.to_string(),
+                "loss_pct".to_string() => loss_pct.to_string(),
+            },
+        },
+        ConnectionEvent::Latency(rtt) => ConnectionStatusUpdate {
+            kind: StatusType::MixnetLatency as i32,
+            message: event.to_string(),
+            details: maplit::hashmap! {
+                "rtt_ms".to_string() => rtt.as_millis().to_string(),
+            },
+        },
+        ConnectionEvent::SessionExpiring { remaining_secs } => ConnectionStatusUpdate {
+            kind: StatusType::SessionExpiring as i32,
+            message: event.to_string(),
+            details: maplit::hashmap! {
+                "remaining_secs".to_string() => remaining_secs.to_string(),
+            },
+        },
+        ConnectionEvent::IdleTimeoutWarning { remaining_secs } => ConnectionStatusUpdate {
+            kind: StatusType::IdleTimeoutWarning as i32,
+            message: event.to_string(),
+            details: maplit::hashmap! {
+                "remaining_secs".to_string() => remaining_secs.to_string(),
+            },
+        },
+        ConnectionEvent::MtuConfigured { mtu } => ConnectionStatusUpdate {
+            kind: StatusType::MtuConfigured as i32,
+            message: event.to_string(),
+            details: maplit::hashmap! {
+                "mtu".to_string() => mtu.to_string(),
+            },
+        },
+        ConnectionEvent::TunFdEstablished { fd } => ConnectionStatusUpdate {
+            kind: StatusType::TunFdEstablished as i32,
+            message: event.to_string(),
+            details: maplit::hashmap! {
+                "fd".to_string() => fd.to_string(),
+            },
+        },
+        ConnectionEvent::TunFdReleased { fd } => ConnectionStatusUpdate {
+            kind: StatusType::TunFdReleased as i32,
+            message: event.to_string(),
+            details: maplit::hashmap! {
+                "fd".to_string() => fd.to_string(),
+            },
+        },
+        ConnectionEvent::CoverTrafficDisabledForWireguard => ConnectionStatusUpdate {
+            kind: StatusType::CoverTrafficDisabledForWireguard as i32,
+            message: event.to_string(),
+            details: Default::default(),
+        },
     }
 }
 
@@ -74,19 +160,62 @@ fn convert_bandwidth_event(event: BandwidthEvent) -> ConnectionStatusUpdate {
             message: "no bandwidth left".to_owned(),
             details: Default::default(),
         },
+        BandwidthEvent::ToppedUp { new_total } => ConnectionStatusUpdate {
+            kind: StatusType::BandwidthToppedUp as i32,
+            message: format!("bandwidth topped up, new total: {}", new_total),
+            details: maplit::hashmap! {
+                "new_total".to_string() => new_total.to_string(),
+            },
+        },
+        BandwidthEvent::LowBandwidth {
+            remaining,
+            threshold,
+        } => ConnectionStatusUpdate {
+            kind: StatusType::LowBandwidth as i32,
+            message: format!("bandwidth running low: {}", remaining),
+            details: maplit::hashmap! {
+                "remaining".to_string() => remaining.to_string(),
+                "threshold".to_string() => threshold.to_string(),
+            },
+        },
     }
 }
 
 fn convert_connection_statistics_event(event: ConnectionStatisticsEvent) -> ConnectionStatusUpdate {
+    let mut details = maplit::hashmap! {
+        "packet_rates".to_string() => event.rates.summary(),
+        "real_received".to_string() => event.rates.real_received(),
+        "real_sent".to_string() => event.rates.real_sent(),
+        "cover_received".to_string() => event.rates.cover_received(),
+        "cover_sent".to_string() => event.rates.cover_sent(),
+        "real_bytes".to_string() => event.real_bytes.to_string(),
+        "real_bytes_sent".to_string() => event.real_bytes_sent.to_string(),
+        "real_bytes_received".to_string() => event.real_bytes_received.to_string(),
+        "cover_bytes".to_string() => event.cover_bytes.to_string(),
+        "ingress_drops".to_string() => event.packet_drops.ingress.total().to_string(),
+        "egress_drops".to_string() => event.packet_drops.egress.total().to_string(),
+    };
+    if let Some(latency) = event.mixnet_latency {
+        details.insert(
+            "mixnet_latency_ms".to_string(),
+            latency.as_millis().to_string(),
+        );
+    }
+
     ConnectionStatusUpdate {
         kind: StatusType::MixnetBandwidthRate as i32,
         message: event.to_string(),
+        details,
+    }
+}
+
+fn convert_wireguard_traffic_event(event: WireguardTrafficEvent) -> ConnectionStatusUpdate {
+    ConnectionStatusUpdate {
+        kind: StatusType::WireguardTraffic as i32,
+        message: event.to_string(),
         details: maplit::hashmap! {
-            "packet_rates".to_string() => event.rates.summary(),
-            "real_received".to_string() => event.rates.real_received(),
-            "real_sent".to_string() => event.rates.real_sent(),
-            "cover_received".to_string() => event.rates.cover_received(),
-            "cover_sent".to_string() => event.rates.cover_sent(),
+            "bytes_sent".to_string() => event.bytes_sent.to_string(),
+            "bytes_received".to_string() => event.bytes_received.to_string(),
         },
     }
 }
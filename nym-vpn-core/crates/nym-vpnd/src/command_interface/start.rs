@@ -13,10 +13,11 @@ use tokio::{
     task::{JoinHandle, JoinSet},
 };
 use tokio_util::sync::CancellationToken;
-use tonic::transport::Server;
+use tonic::transport::{Server, ServerTlsConfig};
 
 use super::{
-    config::{default_socket_path, default_uri_addr},
+    config::{check_uri_addr, default_socket_path, default_uri_addr, HttpListenerTls},
+    error::CommandInterfaceError,
     listener::CommandInterface,
     socket_stream::setup_socket_stream,
 };
@@ -45,6 +46,7 @@ async fn run_uri_listener(
     vpn_command_tx: UnboundedSender<VpnServiceCommand>,
     status_rx: broadcast::Receiver<MixnetEvent>,
     addr: SocketAddr,
+    tls_config: Option<ServerTlsConfig>,
     shutdown_token: CancellationToken,
 ) -> Result<(), tonic::transport::Error> {
     tracing::info!("Starting HTTP listener on: {addr}");
@@ -60,7 +62,13 @@ async fn run_uri_listener(
     let command_interface =
         CommandInterface::new_with_uri(vpn_state_changes_rx, vpn_command_tx, status_rx, addr);
 
-    Server::builder()
+    let mut server = Server::builder();
+    if let Some(tls_config) = tls_config {
+        tracing::info!("Requiring client certificates on the HTTP listener");
+        server = server.tls_config(tls_config)?;
+    }
+
+    server
         .trace_fn(grpc_span)
         .add_service(health_service)
         .add_service(reflection_service)
@@ -109,6 +117,45 @@ async fn run_socket_listener(
 pub(crate) struct CommandInterfaceOptions {
     pub(crate) disable_socket_listener: bool,
     pub(crate) enable_http_listener: bool,
+    /// Address for the http listener. Leave `None` to listen on the default loopback address.
+    pub(crate) http_listener_address: Option<SocketAddr>,
+    /// Allow the http listener to bind to a non-loopback address.
+    pub(crate) allow_remote: bool,
+    /// Serve the http listener over TLS and require client certificates. Off by default.
+    pub(crate) http_tls: Option<HttpListenerTls>,
+}
+
+impl CommandInterfaceOptions {
+    fn uri_addr(&self) -> SocketAddr {
+        self.http_listener_address.unwrap_or_else(default_uri_addr)
+    }
+
+    /// Check the options up front, so that an unsafe listener address fails the startup instead
+    /// of only being logged by the listener task.
+    pub(crate) fn validate(&self) -> Result<(), CommandInterfaceError> {
+        if !self.enable_http_listener {
+            return Ok(());
+        }
+        let uri_addr = self.uri_addr();
+        check_uri_addr(uri_addr, self.allow_remote)?;
+        match self.http_tls {
+            Some(ref http_tls) => {
+                http_tls.load()?;
+            }
+            None if !uri_addr.ip().is_loopback() => {
+                tracing::warn!("HTTP listener on {uri_addr} accepts clients without certificates");
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn http_tls_config(&self) -> Result<Option<ServerTlsConfig>, CommandInterfaceError> {
+        self.http_tls
+            .as_ref()
+            .map(HttpListenerTls::load)
+            .transpose()
+    }
 }
 
 pub(crate) fn start_command_interface(
@@ -122,7 +169,7 @@ pub(crate) fn start_command_interface(
     let (vpn_command_tx, vpn_command_rx) = mpsc::unbounded_channel();
     let command_interface_options = command_interface_options.unwrap_or_default();
     let socket_path = default_socket_path();
-    let uri_addr = default_uri_addr();
+    let uri_addr = command_interface_options.uri_addr();
 
     let handle = tokio::spawn(async move {
         let mut join_set = JoinSet::new();
@@ -138,13 +185,21 @@ pub(crate) fn start_command_interface(
         }
 
         if command_interface_options.enable_http_listener {
-            join_set.spawn(run_uri_listener(
-                vpn_state_changes_rx,
-                vpn_command_tx.clone(),
-                status_rx.resubscribe(),
-                uri_addr,
-                shutdown_token.child_token(),
-            ));
+            let tls_config = check_uri_addr(uri_addr, command_interface_options.allow_remote)
+                .and_then(|_| command_interface_options.http_tls_config());
+            match tls_config {
+                Ok(tls_config) => {
+                    join_set.spawn(run_uri_listener(
+                        vpn_state_changes_rx,
+                        vpn_command_tx.clone(),
+                        status_rx.resubscribe(),
+                        uri_addr,
+                        tls_config,
+                        shutdown_token.child_token(),
+                    ));
+                }
+                Err(err) => tracing::error!("Not starting HTTP listener: {err}"),
+            }
         }
 
         let mut i = 0;
@@ -170,3 +225,47 @@ pub(crate) fn start_command_interface(
 
     (handle, vpn_command_rx)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn http_listener_on(address: &str, allow_remote: bool) -> CommandInterfaceOptions {
+        CommandInterfaceOptions {
+            enable_http_listener: true,
+            http_listener_address: Some(address.parse().unwrap()),
+            allow_remote,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn default_http_listener_is_valid() {
+        let options = CommandInterfaceOptions {
+            enable_http_listener: true,
+            ..Default::default()
+        };
+        assert!(options.validate().is_ok());
+    }
+
+    #[test]
+    fn remote_http_listener_needs_allow_remote() {
+        assert!(matches!(
+            http_listener_on("0.0.0.0:53181", false).validate(),
+            Err(CommandInterfaceError::RemoteListenerNotAllowed { .. })
+        ));
+        assert!(http_listener_on("0.0.0.0:53181", true).validate().is_ok());
+        assert!(http_listener_on("127.0.0.1:53181", false)
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn listener_address_is_ignored_without_http_listener() {
+        let options = CommandInterfaceOptions {
+            enable_http_listener: false,
+            ..http_listener_on("0.0.0.0:53181", false)
+        };
+        assert!(options.validate().is_ok());
+    }
+}
@@ -1,17 +1,32 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
+use std::net::SocketAddr;
+
 use nym_vpn_lib::nym_config::defaults::NymNetworkDetails;
+use nym_vpn_store::permissions::{StoragePermissions, StoragePermissionsError};
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub(crate) struct GlobalConfigFile {
     pub(crate) network_name: String,
+
+    /// Address for the http listener, when enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) http_listener_address: Option<SocketAddr>,
+
+    /// Mode of the account mnemonic, the device keys and the last session summary, e.g. `0o640`.
+    /// The data directory gets the matching directory mode, which also guards the caches written
+    /// next to them. Only the owner can access them when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) storage_mode: Option<u32>,
 }
 
 impl Default for GlobalConfigFile {
     fn default() -> Self {
         Self {
             network_name: NymNetworkDetails::default().network_name,
+            http_listener_address: None,
+            storage_mode: None,
         }
     }
 }
@@ -25,6 +40,16 @@ impl GlobalConfigFile {
         crate::service::read_config_file(&global_config_file_path).map_err(Into::into)
     }
 
+    /// Permissions of the data directory, rejecting modes that would let others write to it.
+    pub(crate) fn storage_permissions(
+        &self,
+    ) -> Result<StoragePermissions, StoragePermissionsError> {
+        self.storage_mode
+            .map(StoragePermissions::from_mode)
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
     pub(crate) fn write_to_file(&self) -> anyhow::Result<Self> {
         let global_config = self.clone();
         let global_config_file_path =
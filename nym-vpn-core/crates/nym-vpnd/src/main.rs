@@ -6,6 +6,7 @@ mod command_interface;
 mod config;
 mod environment;
 mod logging;
+mod reload_handler;
 mod runtime;
 mod service;
 mod shutdown_handler;
@@ -16,11 +17,16 @@ mod windows_service;
 
 use clap::Parser;
 use nym_vpn_network_config::Network;
+use nym_vpn_store::permissions::StoragePermissions;
 use service::NymVpnService;
 use tokio::sync::broadcast;
 use tokio_util::sync::CancellationToken;
 
-use crate::{cli::CliArgs, command_interface::CommandInterfaceOptions, config::GlobalConfigFile};
+use crate::{
+    cli::CliArgs,
+    command_interface::{CommandInterfaceOptions, HttpListenerTls},
+    config::GlobalConfigFile,
+};
 
 fn main() -> anyhow::Result<()> {
     run()
@@ -39,8 +45,10 @@ fn run() -> anyhow::Result<()> {
     logging::setup_logging(args.command.run_as_service);
 
     let network_env = environment::setup_environment(&global_config_file, &args)?;
+    let command_interface_options = command_interface_options(&args, &global_config_file)?;
+    let storage_permissions = global_config_file.storage_permissions()?;
 
-    run_inner(args, network_env)
+    run_inner(command_interface_options, network_env, storage_permissions)
 }
 
 #[cfg(windows)]
@@ -54,20 +62,62 @@ fn run() -> anyhow::Result<()> {
     }
 
     let network_env = environment::setup_environment(&global_config_file, &args)?;
+    let command_interface_options = command_interface_options(&args, &global_config_file)?;
+    let storage_permissions = global_config_file.storage_permissions()?;
 
     if args.command.is_any() {
         Ok(windows_service::start(args)?)
     } else {
         logging::setup_logging(false);
-        run_inner(args, network_env)
+        run_inner(command_interface_options, network_env, storage_permissions)
     }
 }
 
-fn run_inner(args: CliArgs, network_env: Network) -> anyhow::Result<()> {
-    runtime::new_runtime().block_on(run_inner_async(args, network_env))
+fn command_interface_options(
+    args: &CliArgs,
+    global_config_file: &GlobalConfigFile,
+) -> anyhow::Result<CommandInterfaceOptions> {
+    let options = CommandInterfaceOptions {
+        disable_socket_listener: args.disable_socket_listener,
+        enable_http_listener: args.enable_http_listener,
+        http_listener_address: args
+            .http_listener_address
+            .or(global_config_file.http_listener_address),
+        allow_remote: args.allow_remote,
+        http_tls: match (
+            &args.http_tls_cert,
+            &args.http_tls_key,
+            &args.http_tls_client_ca,
+        ) {
+            (Some(cert_path), Some(key_path), Some(client_ca_path)) => Some(HttpListenerTls {
+                cert_path: cert_path.clone(),
+                key_path: key_path.clone(),
+                client_ca_path: client_ca_path.clone(),
+            }),
+            _ => None,
+        },
+    };
+    options.validate()?;
+    Ok(options)
 }
 
-async fn run_inner_async(args: CliArgs, network_env: Network) -> anyhow::Result<()> {
+fn run_inner(
+    command_interface_options: CommandInterfaceOptions,
+    network_env: Network,
+    storage_permissions: StoragePermissions,
+) -> anyhow::Result<()> {
+    runtime::new_runtime().block_on(run_inner_async(
+        command_interface_options,
+        network_env,
+        storage_permissions,
+    ))
+}
+
+async fn run_inner_async(
+    command_interface_options: CommandInterfaceOptions,
+    network_env: Network,
+    storage_permissions: StoragePermissions,
+) -> anyhow::Result<()> {
     network_env.check_consistency().await?;
 
     let (state_changes_tx, state_changes_rx) = broadcast::channel(10);
@@ -77,19 +127,20 @@ async fn run_inner_async(args: CliArgs, network_env: Network) -> anyhow::Result<
     let (command_handle, vpn_command_rx) = command_interface::start_command_interface(
         state_changes_rx,
         status_rx,
-        Some(CommandInterfaceOptions {
-            disable_socket_listener: args.disable_socket_listener,
-            enable_http_listener: args.enable_http_listener,
-        }),
+        Some(command_interface_options),
         shutdown_token.child_token(),
     );
 
+    let config_reload_rx = reload_handler::install(shutdown_token.child_token());
+
     let vpn_service_handle = NymVpnService::spawn(
         state_changes_tx,
         vpn_command_rx,
+        config_reload_rx,
         status_tx,
         shutdown_token.child_token(),
         network_env,
+        storage_permissions,
     );
 
     let mut shutdown_join_set = shutdown_handler::install(shutdown_token);
@@ -0,0 +1,47 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+/// Notifies the returned receiver each time the configuration should be reloaded, which is on
+/// SIGHUP on unix. The receiver is never notified on other platforms.
+pub fn install(shutdown_token: CancellationToken) -> mpsc::UnboundedReceiver<()> {
+    let (reload_tx, reload_rx) = mpsc::unbounded_channel();
+
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        if let Err(e) = set_hangup_handler(reload_tx, shutdown_token).await {
+            tracing::error!("Failed to set the hangup handler: {}", e);
+        }
+    });
+
+    #[cfg(not(unix))]
+    let _ = (reload_tx, shutdown_token);
+
+    reload_rx
+}
+
+#[cfg(unix)]
+async fn set_hangup_handler(
+    reload_tx: mpsc::UnboundedSender<()>,
+    shutdown_token: CancellationToken,
+) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sighup = signal(SignalKind::hangup())?;
+
+    while shutdown_token
+        .run_until_cancelled(sighup.recv())
+        .await
+        .flatten()
+        .is_some()
+    {
+        tracing::info!("Received SIGHUP signal.");
+        if reload_tx.send(()).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,301 @@
+// Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{fmt, net::IpAddr, path::PathBuf, time::Duration};
+
+use nym_vpn_api_client::types::Percent;
+use nym_vpn_lib::{
+    gateway_directory::{EntryPoint, ExitPoint},
+    tunnel_state_machine::DEFAULT_CONNECT_DEADLINE,
+};
+use serde::Deserialize;
+
+use super::{
+    config::{read_config_file, ConfigSetupError},
+    ConnectArgs, ConnectOptions,
+};
+
+pub(crate) const DEFAULT_AUTO_CONNECT_FILE: &str = "auto-connect.toml";
+
+// How long to wait before retrying a failed auto-connect
+pub(crate) const AUTO_CONNECT_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum AutoConnectConfigError {
+    #[error(transparent)]
+    ConfigSetup(#[from] ConfigSetupError),
+
+    #[error("{field} must be between 0 and 100, got {value}")]
+    InvalidPerformance { field: &'static str, value: u8 },
+
+    #[error("netstack is only supported with the wireguard tunnel type")]
+    NetstackWithoutWireguard,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AutoConnectTunnelType {
+    #[default]
+    Mixnet,
+    Wireguard,
+}
+
+impl fmt::Display for AutoConnectTunnelType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mixnet => f.write_str("mixnet"),
+            Self::Wireguard => f.write_str("wireguard"),
+        }
+    }
+}
+
+/// Connect options loaded from the optional `auto-connect.toml` in the config directory. They are
+/// the defaults for connect requests that don't set any option, are used to connect as soon as the
+/// daemon starts, and are re-read when the daemon receives SIGHUP.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct AutoConnectConfig {
+    /// Connect at startup and after a reload, retrying until it succeeds or the user connects or
+    /// disconnects.
+    pub(crate) auto_connect: bool,
+
+    /// The entry and exit points of the last connection are used when not set.
+    pub(crate) entry_point: Option<EntryPoint>,
+    pub(crate) exit_point: Option<ExitPoint>,
+
+    pub(crate) tunnel_type: AutoConnectTunnelType,
+    pub(crate) netstack: bool,
+    pub(crate) dns: Option<IpAddr>,
+    pub(crate) allow_lan: bool,
+
+    /// Minimum performance in percent.
+    pub(crate) min_mixnode_performance: Option<u8>,
+    pub(crate) min_gateway_mixnet_performance: Option<u8>,
+    pub(crate) min_gateway_vpn_performance: Option<u8>,
+}
+
+impl AutoConnectConfig {
+    pub(crate) fn file_path() -> PathBuf {
+        super::config_dir().join(DEFAULT_AUTO_CONNECT_FILE)
+    }
+
+    /// Reads and validates the file. Returns `None` when there is no such file.
+    pub(crate) fn read_from_file() -> Result<Option<Self>, AutoConnectConfigError> {
+        let file_path = Self::file_path();
+        if !file_path.exists() {
+            return Ok(None);
+        }
+
+        let config: Self = read_config_file(&file_path)?;
+        config.validate()?;
+        Ok(Some(config))
+    }
+
+    fn validate(&self) -> Result<(), AutoConnectConfigError> {
+        for (field, value) in [
+            ("min_mixnode_performance", self.min_mixnode_performance),
+            (
+                "min_gateway_mixnet_performance",
+                self.min_gateway_mixnet_performance,
+            ),
+            (
+                "min_gateway_vpn_performance",
+                self.min_gateway_vpn_performance,
+            ),
+        ] {
+            if let Some(value) = value.filter(|value| *value > 100) {
+                return Err(AutoConnectConfigError::InvalidPerformance { field, value });
+            }
+        }
+
+        if self.netstack && self.tunnel_type != AutoConnectTunnelType::Wireguard {
+            return Err(AutoConnectConfigError::NetstackWithoutWireguard);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn to_connect_args(&self) -> ConnectArgs {
+        let enable_two_hop = self.tunnel_type == AutoConnectTunnelType::Wireguard;
+        let options = ConnectOptions {
+            dns: self.dns,
+            enable_two_hop,
+            netstack: self.netstack,
+            // If two-hop is enabled, we always disable cover traffic
+            disable_poisson_rate: enable_two_hop,
+            disable_background_cover_traffic: enable_two_hop,
+            min_mixnode_performance: self.min_mixnode_performance.map(into_percent),
+            min_gateway_mixnet_performance: self.min_gateway_mixnet_performance.map(into_percent),
+            min_gateway_vpn_performance: self.min_gateway_vpn_performance.map(into_percent),
+            allow_lan: self.allow_lan,
+            connect_deadline: Some(DEFAULT_CONNECT_DEADLINE),
+            ..Default::default()
+        };
+
+        ConnectArgs {
+            entry: self.entry_point.clone(),
+            exit: self.exit_point.clone(),
+            options,
+        }
+    }
+}
+
+// Validated to be at most 100.
+fn into_percent(value: u8) -> Percent {
+    Percent::from_percentage_value(u64::from(value).min(100)).unwrap()
+}
+
+fn display_optional<T: fmt::Display>(value: &Option<T>) -> String {
+    value
+        .as_ref()
+        .map(ToString::to_string)
+        .unwrap_or_else(|| "default".to_owned())
+}
+
+impl fmt::Display for AutoConnectConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "entry point: {}, exit point: {}, tunnel type: {}, netstack: {}, dns: {}, \
+             allow lan: {}, min mixnode performance: {}, min gateway mixnet performance: {}, \
+             min gateway vpn performance: {}",
+            display_optional(&self.entry_point),
+            display_optional(&self.exit_point),
+            self.tunnel_type,
+            self.netstack,
+            display_optional(&self.dns),
+            self.allow_lan,
+            display_optional(&self.min_mixnode_performance),
+            display_optional(&self.min_gateway_mixnet_performance),
+            display_optional(&self.min_gateway_vpn_performance),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> AutoConnectConfig {
+        toml::from_str(content).unwrap()
+    }
+
+    #[test]
+    fn empty_file_uses_defaults() {
+        let config = parse("");
+
+        assert!(!config.auto_connect);
+        assert_eq!(config.entry_point, None);
+        assert_eq!(config.exit_point, None);
+        assert_eq!(config.tunnel_type, AutoConnectTunnelType::Mixnet);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn parses_all_options() {
+        let config = parse(
+            r#"
+            auto_connect = true
+            entry_point = "RandomLowLatency"
+            exit_point = { Location = { location = "DE" } }
+            tunnel_type = "wireguard"
+            netstack = true
+            dns = "1.1.1.1"
+            allow_lan = true
+            min_mixnode_performance = 50
+            min_gateway_mixnet_performance = 60
+            min_gateway_vpn_performance = 70
+            "#,
+        );
+
+        assert!(config.auto_connect);
+        assert_eq!(config.entry_point, Some(EntryPoint::RandomLowLatency));
+        assert_eq!(
+            config.exit_point,
+            Some(ExitPoint::Location {
+                location: "DE".to_owned()
+            })
+        );
+        assert_eq!(config.tunnel_type, AutoConnectTunnelType::Wireguard);
+        assert!(config.netstack);
+        assert_eq!(config.dns, Some("1.1.1.1".parse().unwrap()));
+        assert!(config.allow_lan);
+        assert_eq!(config.min_mixnode_performance, Some(50));
+        assert_eq!(config.min_gateway_mixnet_performance, Some(60));
+        assert_eq!(config.min_gateway_vpn_performance, Some(70));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(toml::from_str::<AutoConnectConfig>("auto_conect = true").is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_tunnel_type() {
+        assert!(toml::from_str::<AutoConnectConfig>(r#"tunnel_type = "openvpn""#).is_err());
+    }
+
+    #[test]
+    fn rejects_performance_above_100() {
+        let config = parse("min_gateway_vpn_performance = 101");
+
+        match config.validate() {
+            Err(AutoConnectConfigError::InvalidPerformance { field, value }) => {
+                assert_eq!(field, "min_gateway_vpn_performance");
+                assert_eq!(value, 101);
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_netstack_with_mixnet() {
+        let config = parse("netstack = true");
+
+        assert!(matches!(
+            config.validate(),
+            Err(AutoConnectConfigError::NetstackWithoutWireguard)
+        ));
+    }
+
+    #[test]
+    fn wireguard_connect_args_enable_two_hop_without_cover_traffic() {
+        let config = parse(
+            r#"
+            tunnel_type = "wireguard"
+            min_gateway_vpn_performance = 70
+            "#,
+        );
+
+        let connect_args = config.to_connect_args();
+
+        assert!(connect_args.options.enable_two_hop);
+        assert!(connect_args.options.disable_poisson_rate);
+        assert!(connect_args.options.disable_background_cover_traffic);
+        assert_eq!(
+            connect_args
+                .options
+                .min_gateway_vpn_performance
+                .map(|p| p.round_to_integer()),
+            Some(70)
+        );
+        assert_eq!(
+            connect_args.options.connect_deadline,
+            Some(DEFAULT_CONNECT_DEADLINE)
+        );
+    }
+
+    #[test]
+    fn default_connect_args_match_an_empty_request() {
+        let connect_args = AutoConnectConfig::default().to_connect_args();
+
+        assert_eq!(connect_args.entry, None);
+        assert_eq!(connect_args.exit, None);
+        assert!(!connect_args.options.enable_two_hop);
+        assert!(!connect_args.options.disable_background_cover_traffic);
+        assert_eq!(
+            connect_args.options.connect_deadline,
+            Some(DEFAULT_CONNECT_DEADLINE)
+        );
+    }
+}
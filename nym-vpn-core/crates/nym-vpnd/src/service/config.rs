@@ -1,12 +1,14 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-#[cfg(unix)]
-use std::os::unix::fs::PermissionsExt;
-use std::{fmt, fs, path::PathBuf};
+use std::{fmt, fs, path::PathBuf, time::Duration};
 
 use nym_vpn_lib::gateway_directory;
-use serde::{de::DeserializeOwned, Serialize};
+use nym_vpn_store::permissions::StoragePermissions;
+use serde::{
+    de::{self, DeserializeOwned},
+    Deserialize, Deserializer, Serialize,
+};
 
 #[cfg(not(windows))]
 const DEFAULT_DATA_DIR: &str = "/var/lib/nym-vpnd";
@@ -16,6 +18,7 @@ const DEFAULT_LOG_DIR: &str = "/var/log/nym-vpnd";
 const DEFAULT_CONFIG_DIR: &str = "/etc/nym";
 pub(crate) const DEFAULT_CONFIG_FILE: &str = "nym-vpnd.toml";
 pub(crate) const DEFAULT_LOG_FILE: &str = "nym-vpnd.log";
+pub(crate) const DEFAULT_LAST_SESSION_SUMMARY_FILE: &str = "last-session-summary.json";
 
 pub(crate) const DEFAULT_GLOBAL_CONFIG_FILE: &str = "config.toml";
 
@@ -125,7 +128,6 @@ pub enum ConfigSetupError {
         error: std::io::Error,
     },
 
-    #[cfg(unix)]
     #[error("failed to set permissions for directory {dir}: {error}")]
     SetPermissions { dir: PathBuf, error: std::io::Error },
 
@@ -137,14 +139,70 @@ pub enum ConfigSetupError {
 pub(crate) struct NymVpnServiceConfig {
     pub(super) entry_point: gateway_directory::EntryPoint,
     pub(super) exit_point: gateway_directory::ExitPoint,
+
+    // Number of tickets to spend each time the wireguard bandwidth is topped up. Must be between 1
+    // and the maximum the gateway client spends at once.
+    #[serde(
+        default = "default_tickets_per_top_up",
+        deserialize_with = "deserialize_tickets_per_top_up"
+    )]
+    pub(super) tickets_per_top_up: u32,
+
+    // Number of seconds to wait for the mixnet client to shut down gracefully when disconnecting.
+    // Must be between 1 and 60, a too short timeout may cut off the final flush of cover traffic.
+    #[serde(default, deserialize_with = "deserialize_shutdown_timeout_secs")]
+    pub(super) shutdown_timeout_secs: Option<u64>,
+
+    // Maximum number of seconds the system clock can be off from the directory before refusing
+    // to connect
+    #[serde(default = "default_max_clock_skew_secs")]
+    pub(super) max_clock_skew_secs: u64,
+}
+
+fn default_tickets_per_top_up() -> u32 {
+    nym_vpn_lib::wg_gateway_client::TICKETS_TO_SPEND
+}
+
+fn deserialize_tickets_per_top_up<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let tickets = u32::deserialize(deserializer)?;
+    let max_tickets = nym_vpn_lib::wg_gateway_client::MAX_TICKETS_TO_SPEND;
+    if tickets == 0 || tickets > max_tickets {
+        return Err(de::Error::custom(format!(
+            "tickets_per_top_up must be between 1 and {max_tickets}, got {tickets}"
+        )));
+    }
+    Ok(tickets)
+}
+
+fn deserialize_shutdown_timeout_secs<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let secs = Option::<u64>::deserialize(deserializer)?;
+    let range = nym_vpn_lib::tunnel_state_machine::SHUTDOWN_TIMEOUT_RANGE;
+    if let Some(secs) = secs.filter(|secs| !range.contains(&Duration::from_secs(*secs))) {
+        return Err(de::Error::custom(format!(
+            "shutdown_timeout_secs must be between {} and {}, got {secs}",
+            range.start().as_secs(),
+            range.end().as_secs()
+        )));
+    }
+    Ok(secs)
+}
+
+fn default_max_clock_skew_secs() -> u64 {
+    nym_vpn_lib::tunnel_state_machine::DEFAULT_MAX_CLOCK_SKEW.as_secs()
 }
 
 impl fmt::Display for NymVpnServiceConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "entry point: {}, exit point: {}",
-            self.entry_point, self.exit_point
+            "entry point: {}, exit point: {}, tickets per top-up: {}",
+            self.entry_point, self.exit_point, self.tickets_per_top_up
         )
     }
 }
@@ -154,6 +212,9 @@ impl Default for NymVpnServiceConfig {
         Self {
             entry_point: gateway_directory::EntryPoint::Random,
             exit_point: gateway_directory::ExitPoint::Random,
+            tickets_per_top_up: default_tickets_per_top_up(),
+            shutdown_timeout_secs: None,
+            max_clock_skew_secs: default_max_clock_skew_secs(),
         }
     }
 }
@@ -215,26 +276,63 @@ where
     Ok(config)
 }
 
-pub(super) fn create_data_dir(data_dir: &PathBuf) -> Result<(), ConfigSetupError> {
+pub(super) fn create_data_dir(
+    data_dir: &PathBuf,
+    permissions: StoragePermissions,
+) -> Result<(), ConfigSetupError> {
     fs::create_dir_all(data_dir).map_err(|error| ConfigSetupError::CreateDirectory {
         dir: data_dir.clone(),
         error,
     })?;
     tracing::debug!("Making sure data dir exists at {:?}", data_dir);
 
-    #[cfg(unix)]
-    {
-        // Set directory permissions to 700 (rwx------)
-        let permissions = fs::Permissions::from_mode(0o700);
-        fs::set_permissions(data_dir, permissions).map_err(|error| {
-            ConfigSetupError::SetPermissions {
-                dir: data_dir.clone(),
-                error,
-            }
-        })?;
+    permissions
+        .apply_to_dir(data_dir)
+        .map_err(|error| ConfigSetupError::SetPermissions {
+            dir: data_dir.clone(),
+            error,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(settings: &str) -> Result<NymVpnServiceConfig, toml::de::Error> {
+        toml::from_str(&format!(
+            "entry_point = \"Random\"\nexit_point = \"Random\"\n{settings}"
+        ))
+    }
+
+    #[test]
+    fn tickets_per_top_up_defaults_when_missing() {
+        let config = parse("").unwrap();
+        assert_eq!(config.tickets_per_top_up, default_tickets_per_top_up());
     }
 
-    // TODO: same for windows?
+    #[test]
+    fn tickets_per_top_up_out_of_range_is_rejected() {
+        let max_tickets = nym_vpn_lib::wg_gateway_client::MAX_TICKETS_TO_SPEND;
+        assert_eq!(
+            parse(&format!("tickets_per_top_up = {max_tickets}"))
+                .unwrap()
+                .tickets_per_top_up,
+            max_tickets
+        );
+        assert!(parse("tickets_per_top_up = 0").is_err());
+        assert!(parse(&format!("tickets_per_top_up = {}", max_tickets + 1)).is_err());
+    }
 
-    Ok(())
+    #[test]
+    fn shutdown_timeout_out_of_range_is_rejected() {
+        assert_eq!(parse("").unwrap().shutdown_timeout_secs, None);
+        assert_eq!(
+            parse("shutdown_timeout_secs = 60")
+                .unwrap()
+                .shutdown_timeout_secs,
+            Some(60)
+        );
+        assert!(parse("shutdown_timeout_secs = 0").is_err());
+        assert!(parse("shutdown_timeout_secs = 61").is_err());
+    }
 }
@@ -69,6 +69,53 @@ pub enum VpnServiceDisconnectError {
     Internal(String),
 }
 
+// Failure to initiate the key rotation
+#[derive(Debug, thiserror::Error)]
+pub enum VpnServiceRotateKeysError {
+    #[error("internal error: {0}")]
+    Internal(String),
+}
+
+// Failure to reset the idle timer
+#[derive(Debug, thiserror::Error)]
+pub enum VpnServiceResetIdleTimerError {
+    #[error("internal error: {0}")]
+    Internal(String),
+}
+
+// Failure to run the connectivity test
+#[derive(Debug, thiserror::Error)]
+pub enum VpnServiceConnectivityTestError {
+    #[error("not connected")]
+    NotConnected,
+
+    #[error("internal error: {0}")]
+    Internal(String),
+}
+
+// Failure to look up the public IP of the exit gateway
+#[derive(Debug, thiserror::Error)]
+pub enum VpnServiceExitPublicIpError {
+    #[error("not connected")]
+    NotConnected,
+
+    #[error(transparent)]
+    Lookup(#[from] tunnel_state_machine::ExitIpLookupError),
+
+    #[error("internal error: {0}")]
+    Internal(String),
+}
+
+// Failure to export the wireguard config
+#[derive(Debug, thiserror::Error)]
+pub enum VpnServiceExportWireguardConfigError {
+    #[error("not connected over wireguard")]
+    NotConnected,
+
+    #[error("internal error: {0}")]
+    Internal(String),
+}
+
 #[derive(Clone, Debug, thiserror::Error)]
 pub enum ConnectionFailedError {
     #[error("failed to connect (unhandled): {0}")]
@@ -177,8 +224,14 @@ pub enum ConnectionFailedError {
     #[error("failed to select exit gateway: {reason}")]
     FailedToSelectExitGateway { reason: String },
 
-    #[error("selected gateway id not found: {requested_id}")]
-    FailedToSelectEntryGatewayIdNotFound { requested_id: String },
+    #[error(
+        "selected gateway id not found: {requested_id}{}",
+        network_name.as_ref().map(|name| format!(" in the {name} network")).unwrap_or_default()
+    )]
+    FailedToSelectEntryGatewayIdNotFound {
+        requested_id: String,
+        network_name: Option<String>,
+    },
 
     #[error("failed to select entry gateway location: {requested_location}")]
     FailedToSelectEntryGatewayLocation {
@@ -249,6 +302,73 @@ pub enum ConnectionFailedError {
 
     #[error("mixnet connection monitor error: {0}")]
     MixnetConnectionMonitorError(String),
+
+    #[error("system clock is off by {skew_seconds}s, please correct the date and time")]
+    SystemClockSkew { skew_seconds: i64 },
+
+    #[error("all gateways matching the requested location are excluded")]
+    AllGatewaysExcluded,
+
+    #[error("a requested gateway is below the minimum performance of {min_performance}%")]
+    GatewayBelowPerformanceThreshold { min_performance: u8 },
+
+    #[error("no entry and exit gateways satisfy the requested region affinity")]
+    RegionAffinityNotSatisfied,
+
+    #[error(
+        "a gateway only accepts wireguard on a port outside of the preferred ports, try another \
+         network or allow more ports"
+    )]
+    WireguardPortNotAllowed,
+
+    #[error("a requested gateway doesn't run the authenticator wireguard requires, pick another")]
+    GatewayWithoutAuthenticator,
+
+    #[error("the overridden tunnel addresses are invalid or weren't assigned by the exit gateway")]
+    InvalidTunAddressOverride,
+
+    #[error("gave up connecting, the connect deadline passed while {pending_step}")]
+    ConnectDeadlineExceeded { pending_step: String },
+
+    #[error("gave up connecting after the maximum number of attempts")]
+    ConnectAttemptsExhausted,
+}
+
+impl From<tunnel_state_machine::ErrorStateReason> for ConnectionFailedError {
+    fn from(reason: tunnel_state_machine::ErrorStateReason) -> Self {
+        match reason {
+            tunnel_state_machine::ErrorStateReason::SystemClockSkew { skew_seconds } => {
+                ConnectionFailedError::SystemClockSkew { skew_seconds }
+            }
+            tunnel_state_machine::ErrorStateReason::AllGatewaysExcluded => {
+                ConnectionFailedError::AllGatewaysExcluded
+            }
+            tunnel_state_machine::ErrorStateReason::GatewayBelowPerformanceThreshold {
+                min_performance,
+            } => ConnectionFailedError::GatewayBelowPerformanceThreshold { min_performance },
+            tunnel_state_machine::ErrorStateReason::RegionAffinityNotSatisfied => {
+                ConnectionFailedError::RegionAffinityNotSatisfied
+            }
+            tunnel_state_machine::ErrorStateReason::WireguardPortNotAllowed => {
+                ConnectionFailedError::WireguardPortNotAllowed
+            }
+            tunnel_state_machine::ErrorStateReason::GatewayWithoutAuthenticator => {
+                ConnectionFailedError::GatewayWithoutAuthenticator
+            }
+            tunnel_state_machine::ErrorStateReason::InvalidTunAddressOverride => {
+                ConnectionFailedError::InvalidTunAddressOverride
+            }
+            tunnel_state_machine::ErrorStateReason::ConnectDeadlineExceeded { pending_step } => {
+                ConnectionFailedError::ConnectDeadlineExceeded {
+                    pending_step: pending_step.to_string(),
+                }
+            }
+            tunnel_state_machine::ErrorStateReason::EstablishMixnetConnection => {
+                ConnectionFailedError::ConnectAttemptsExhausted
+            }
+            reason => ConnectionFailedError::InternalError(format!("Error state: {:?}", reason)),
+        }
+    }
 }
 
 impl From<&nym_vpn_lib::Error> for ConnectionFailedError {
@@ -291,6 +411,14 @@ impl From<&nym_vpn_lib::GatewayDirectoryError> for ConnectionFailedError {
                 source: DirError::NoMatchingGateway { requested_identity },
             } => ConnectionFailedError::FailedToSelectEntryGatewayIdNotFound {
                 requested_id: requested_identity.clone(),
+                network_name: None,
+            },
+            GatewayDirectoryError::FailedToSelectEntryGatewayIdNotFound {
+                requested_identity,
+                network_name,
+            } => ConnectionFailedError::FailedToSelectEntryGatewayIdNotFound {
+                requested_id: requested_identity.clone(),
+                network_name: Some(network_name.clone()),
             },
             GatewayDirectoryError::FailedToSelectEntryGateway { source } => {
                 ConnectionFailedError::FailedToSelectEntryGateway {
@@ -312,6 +440,11 @@ impl From<&nym_vpn_lib::GatewayDirectoryError> for ConnectionFailedError {
                     reason: source.to_string(),
                 }
             }
+            GatewayDirectoryError::FailedToSelectExitGatewayIdNotFound { .. } => {
+                ConnectionFailedError::FailedToSelectExitGateway {
+                    reason: e.to_string(),
+                }
+            }
             GatewayDirectoryError::FailedToLookupRouterAddress { source } => {
                 ConnectionFailedError::FailedToLookupRouterAddress {
                     reason: source.to_string(),
@@ -322,6 +455,21 @@ impl From<&nym_vpn_lib::GatewayDirectoryError> for ConnectionFailedError {
                     requested_location: requested_location.clone(),
                 }
             }
+            GatewayDirectoryError::AllGatewaysExcluded { .. } => {
+                ConnectionFailedError::AllGatewaysExcluded
+            }
+            GatewayDirectoryError::GatewayBelowPerformanceThreshold {
+                min_performance, ..
+            } => ConnectionFailedError::GatewayBelowPerformanceThreshold {
+                min_performance: *min_performance,
+            },
+            GatewayDirectoryError::RegionAffinityNotSatisfied { .. } => {
+                ConnectionFailedError::RegionAffinityNotSatisfied
+            }
+            GatewayDirectoryError::EntryGatewayWithoutAuthenticator { .. }
+            | GatewayDirectoryError::ExitGatewayWithoutAuthenticator { .. } => {
+                ConnectionFailedError::GatewayWithoutAuthenticator
+            }
         }
     }
 }
@@ -1,6 +1,7 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
+mod auto_connect;
 mod config;
 mod error;
 mod vpn_service;
@@ -11,9 +12,10 @@ pub(crate) use config::{
 };
 pub(crate) use error::{
     AccountError, AccountNotReady, ConnectionFailedError, SetNetworkError, VpnServiceConnectError,
-    VpnServiceDisconnectError,
+    VpnServiceConnectivityTestError, VpnServiceDisconnectError, VpnServiceExitPublicIpError,
+    VpnServiceExportWireguardConfigError, VpnServiceResetIdleTimerError, VpnServiceRotateKeysError,
 };
 pub(crate) use vpn_service::{
-    ConnectArgs, ConnectOptions, ConnectedStateDetails, NymVpnService, VpnServiceCommand,
-    VpnServiceInfo, VpnServiceStateChange, VpnServiceStatus,
+    ConnectArgs, ConnectOptions, ConnectedStateDetails, NymVpnService, SessionSummaryDetails,
+    VpnServiceCommand, VpnServiceHealth, VpnServiceInfo, VpnServiceStateChange, VpnServiceStatus,
 };
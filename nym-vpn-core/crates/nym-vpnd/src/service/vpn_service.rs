@@ -3,13 +3,15 @@
 
 use std::{
     fmt,
+    future::Future,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use bip39::Mnemonic;
+use ipnetwork::IpNetwork;
 use nym_vpn_network_config::{
     FeatureFlags, Network, NymNetwork, NymVpnNetwork, ParsedAccountLinks, SystemMessages,
 };
@@ -22,29 +24,43 @@ use tokio::{
 use tokio_util::sync::CancellationToken;
 
 use nym_vpn_account_controller::{
-    AccountCommand, AccountController, AccountStateSummary, AvailableTicketbooks, ReadyToConnect,
-    SharedAccountState,
+    AccountCommand, AccountController, AccountStateChange, AccountStateSummary,
+    AvailableTicketbooks, ReadyToConnect, SharedAccountState,
 };
 use nym_vpn_api_client::{
-    response::{NymVpnAccountSummaryResponse, NymVpnDevicesResponse},
+    response::{
+        NymVpnAccountSummaryResponse, NymVpnDevicesResponse, NymVpnZkNym, NymVpnZkNymResponse,
+    },
     types::{Percent, VpnApiAccount},
 };
 use nym_vpn_lib::{
-    gateway_directory::{self, EntryPoint, ExitPoint},
+    connection_monitor::ConnectionQualityConfig,
+    gateway_directory::{self, EntryPoint, ExitPoint, ProxyConfig},
     tunnel_state_machine::{
-        ConnectionData, DnsOptions, GatewayPerformanceOptions, MixnetEvent, MixnetTunnelOptions,
-        NymConfig, TunnelCommand, TunnelConnectionData, TunnelEvent, TunnelSettings, TunnelState,
-        TunnelStateMachine, TunnelType, WireguardMultihopMode, WireguardTunnelOptions,
+        ConnectRetryOptions, ConnectionData, ConnectivityTestReport, DisconnectReason, DnsOptions,
+        ExitIpLookupError, ExitPublicIp, GatewayDiagnostics, GatewayLookupRetryOptions,
+        GatewayPerformanceOptions, MixnetEvent, MixnetPacketDrops, MixnetTunnelOptions, NymConfig,
+        RegionAffinity, SelectionStrategy, SessionSummary, SystemTunProvider, TunnelCommand,
+        TunnelConnectionData, TunnelEvent, TunnelSettings, TunnelState, TunnelStateMachine,
+        TunnelType, WireguardMultihopMode, WireguardTunnelOptions, DEFAULT_DNS_LEAK_PROBE_INTERVAL,
+        DEFAULT_IDLE_TIMEOUT_WARNING, DEFAULT_SESSION_EXPIRY_WARNING,
     },
     MixnetClientConfig, NodeIdentity, Recipient,
 };
+use nym_vpn_store::permissions::StoragePermissions;
 
 use crate::config::GlobalConfigFile;
 
 use super::{
-    config::{ConfigSetupError, NetworkEnvironments, NymVpnServiceConfig, DEFAULT_CONFIG_FILE},
+    auto_connect::{AutoConnectConfig, AUTO_CONNECT_RETRY_INTERVAL},
+    config::{
+        ConfigSetupError, NetworkEnvironments, NymVpnServiceConfig, DEFAULT_CONFIG_FILE,
+        DEFAULT_LAST_SESSION_SUMMARY_FILE,
+    },
     error::{AccountError, AccountNotReady, ConnectionFailedError, Error, Result, SetNetworkError},
-    VpnServiceConnectError, VpnServiceDisconnectError,
+    VpnServiceConnectError, VpnServiceConnectivityTestError, VpnServiceDisconnectError,
+    VpnServiceExitPublicIpError, VpnServiceExportWireguardConfigError,
+    VpnServiceResetIdleTimerError, VpnServiceRotateKeysError,
 };
 
 #[derive(Debug, Clone)]
@@ -96,15 +112,35 @@ type Locale = String;
 #[allow(clippy::large_enum_variant)]
 pub enum VpnServiceCommand {
     Info(oneshot::Sender<VpnServiceInfo>, ()),
+    Health(oneshot::Sender<VpnServiceHealth>, ()),
     SetNetwork(oneshot::Sender<Result<(), SetNetworkError>>, String),
     GetSystemMessages(oneshot::Sender<SystemMessages>, ()),
     GetFeatureFlags(oneshot::Sender<Option<FeatureFlags>>, ()),
+    // Connects with the default options when no arguments are given, see `AutoConnectConfig`
     Connect(
         oneshot::Sender<Result<(), VpnServiceConnectError>>,
-        (ConnectArgs, nym_vpn_lib::UserAgent),
+        (Option<ConnectArgs>, nym_vpn_lib::UserAgent),
     ),
     Disconnect(oneshot::Sender<Result<(), VpnServiceDisconnectError>>, ()),
+    RotateWireguardKeys(oneshot::Sender<Result<(), VpnServiceRotateKeysError>>, ()),
+    ResetIdleTimer(
+        oneshot::Sender<Result<(), VpnServiceResetIdleTimerError>>,
+        (),
+    ),
+    ExportWireguardConfig(
+        oneshot::Sender<Result<String, VpnServiceExportWireguardConfigError>>,
+        bool,
+    ),
+    RunConnectivityTest(
+        oneshot::Sender<Result<ConnectivityTestReport, VpnServiceConnectivityTestError>>,
+        (),
+    ),
+    GetExitPublicIp(
+        oneshot::Sender<Result<ExitPublicIp, VpnServiceExitPublicIpError>>,
+        (),
+    ),
     Status(oneshot::Sender<VpnServiceStatus>, ()),
+    GetLastSessionSummary(oneshot::Sender<Option<SessionSummaryDetails>>, ()),
     StoreAccount(oneshot::Sender<Result<(), AccountError>>, String),
     IsAccountStored(oneshot::Sender<Result<bool, AccountError>>, ()),
     RemoveAccount(oneshot::Sender<Result<(), AccountError>>, ()),
@@ -119,18 +155,29 @@ pub enum VpnServiceCommand {
     ),
     RefreshAccountState(oneshot::Sender<Result<(), AccountError>>, ()),
     IsReadyToConnect(oneshot::Sender<Result<ReadyToConnect, AccountError>>, ()),
+    SubscribeAccountChanges(
+        oneshot::Sender<(AccountStateSummary, broadcast::Receiver<AccountStateChange>)>,
+        (),
+    ),
     ResetDeviceIdentity(oneshot::Sender<Result<(), AccountError>>, Option<Seed>),
     GetDeviceIdentity(oneshot::Sender<Result<String, AccountError>>, ()),
     RegisterDevice(oneshot::Sender<Result<(), AccountError>>, ()),
     RequestZkNym(oneshot::Sender<Result<(), AccountError>>, ()),
-    GetDeviceZkNyms(oneshot::Sender<Result<(), AccountError>>, ()),
-    GetZkNymsAvailableForDownload(oneshot::Sender<Result<(), AccountError>>, ()),
-    GetZkNymById(oneshot::Sender<Result<(), AccountError>>, String),
+    GetDeviceZkNyms(
+        oneshot::Sender<Result<NymVpnZkNymResponse, AccountError>>,
+        (),
+    ),
+    GetZkNymsAvailableForDownload(
+        oneshot::Sender<Result<NymVpnZkNymResponse, AccountError>>,
+        (),
+    ),
+    GetZkNymById(oneshot::Sender<Result<NymVpnZkNym, AccountError>>, String),
     ConfirmZkNymIdDownloaded(oneshot::Sender<Result<(), AccountError>>, String),
     GetAvailableTickets(
         oneshot::Sender<Result<AvailableTicketbooks, AccountError>>,
         (),
     ),
+    RefreshTickets(oneshot::Sender<Result<u32, AccountError>>, ()),
     FetchRawAccountSummary(
         oneshot::Sender<Result<NymVpnAccountSummaryResponse, AccountError>>,
         (),
@@ -145,6 +192,7 @@ impl fmt::Display for VpnServiceCommand {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             VpnServiceCommand::Info(..) => write!(f, "Info"),
+            VpnServiceCommand::Health(..) => write!(f, "Health"),
             VpnServiceCommand::SetNetwork(..) => write!(f, "SetNetwork"),
             VpnServiceCommand::GetSystemMessages(..) => write!(f, "GetSystemMessages"),
             VpnServiceCommand::GetFeatureFlags(..) => write!(f, "GetFeatureFlags"),
@@ -152,7 +200,13 @@ impl fmt::Display for VpnServiceCommand {
                 write!(f, "Connect {{ {args:?}, {user_agent:?} }}")
             }
             VpnServiceCommand::Disconnect(..) => write!(f, "Disconnect"),
+            VpnServiceCommand::RotateWireguardKeys(..) => write!(f, "RotateWireguardKeys"),
+            VpnServiceCommand::ResetIdleTimer(..) => write!(f, "ResetIdleTimer"),
+            VpnServiceCommand::ExportWireguardConfig(..) => write!(f, "ExportWireguardConfig"),
+            VpnServiceCommand::RunConnectivityTest(..) => write!(f, "RunConnectivityTest"),
+            VpnServiceCommand::GetExitPublicIp(..) => write!(f, "GetExitPublicIp"),
             VpnServiceCommand::Status(..) => write!(f, "Status"),
+            VpnServiceCommand::GetLastSessionSummary(..) => write!(f, "GetLastSessionSummary"),
             VpnServiceCommand::StoreAccount(..) => write!(f, "StoreAccount"),
             VpnServiceCommand::IsAccountStored(..) => write!(f, "IsAccountStored"),
             VpnServiceCommand::RemoveAccount(..) => write!(f, "RemoveAccount"),
@@ -161,6 +215,7 @@ impl fmt::Display for VpnServiceCommand {
             VpnServiceCommand::GetAccountState(..) => write!(f, "GetAccountState"),
             VpnServiceCommand::RefreshAccountState(..) => write!(f, "RefreshAccountState"),
             VpnServiceCommand::IsReadyToConnect(..) => write!(f, "IsReadyToConnect"),
+            VpnServiceCommand::SubscribeAccountChanges(..) => write!(f, "SubscribeAccountChanges"),
             VpnServiceCommand::ResetDeviceIdentity(..) => write!(f, "ResetDeviceIdentity"),
             VpnServiceCommand::GetDeviceIdentity(..) => write!(f, "GetDeviceIdentity"),
             VpnServiceCommand::RegisterDevice(..) => write!(f, "RegisterDevice"),
@@ -174,6 +229,7 @@ impl fmt::Display for VpnServiceCommand {
                 write!(f, "ConfirmZkNymIdDownloaded")
             }
             VpnServiceCommand::GetAvailableTickets(..) => write!(f, "GetAvailableTickets"),
+            VpnServiceCommand::RefreshTickets(..) => write!(f, "RefreshTickets"),
             VpnServiceCommand::FetchRawAccountSummary(..) => write!(f, "FetchRawAccountSummery"),
             VpnServiceCommand::FetchRawDevices(..) => write!(f, "FetchRawDevices"),
         }
@@ -199,6 +255,30 @@ pub(crate) struct ConnectOptions {
     pub(crate) min_mixnode_performance: Option<Percent>,
     pub(crate) min_gateway_mixnet_performance: Option<Percent>,
     pub(crate) min_gateway_vpn_performance: Option<Percent>,
+    pub(crate) mixnet_client_startup_timeout: Option<Duration>,
+    pub(crate) sticky_gateways: bool,
+    pub(crate) disallow_same_country: bool,
+    pub(crate) allow_lan: bool,
+    pub(crate) excluded_gateways: Vec<NodeIdentity>,
+    pub(crate) key_namespace: Option<String>,
+    pub(crate) detect_dns_leaks: bool,
+    pub(crate) dns_leak_probe_interval: Option<Duration>,
+    pub(crate) disable_reconnect_on_network_change: bool,
+    pub(crate) key_rotation_interval: Option<Duration>,
+    pub(crate) max_session_duration: Option<Duration>,
+    pub(crate) selection_seed: Option<u64>,
+    pub(crate) region_affinity: Option<RegionAffinity>,
+    pub(crate) selection_strategy: SelectionStrategy,
+    pub(crate) connect_deadline: Option<Duration>,
+    pub(crate) gateway_dns: bool,
+    pub(crate) preferred_wg_ports: Vec<u16>,
+    pub(crate) routed_destinations: Vec<IpNetwork>,
+    pub(crate) latency_sample_interval: Option<Duration>,
+    pub(crate) idle_timeout: Option<Duration>,
+    pub(crate) directory_cache_ttl: Option<Duration>,
+    // Not persisted, the credentials must not end up on disk
+    #[serde(skip)]
+    pub(crate) proxy: Option<ProxyConfig>,
     // Consider adding this here once UserAgent implements Serialize/Deserialize
     // pub(crate) user_agent: Option<nym_vpn_lib::UserAgent>,
 }
@@ -210,6 +290,8 @@ pub enum VpnServiceStatus {
     NotConnected,
     Connecting,
     Connected(Box<ConnectedResultDetails>),
+    // The tunnel is still set up but traffic bypasses it until it's resumed.
+    Paused(Box<ConnectedResultDetails>),
     Disconnecting,
     ConnectionFailed(ConnectionFailedError),
 }
@@ -219,9 +301,17 @@ impl From<ConnectionData> for ConnectedResultDetails {
         ConnectedResultDetails {
             entry_gateway: *value.entry_gateway,
             exit_gateway: *value.exit_gateway,
+            entry_gateway_diagnostics: value.entry_gateway_diagnostics,
+            exit_gateway_diagnostics: value.exit_gateway_diagnostics,
             specific_details: ConnectedStateDetails::from(value.tunnel),
             // FIXME: this cannot be mapped correctly
             since: value.connected_at.unwrap_or(OffsetDateTime::now_utc()),
+            duration: None,
+            tun_name: value.tun_name,
+            mtu: value.mtu,
+            credentials_mode_active: value.credentials_mode_active,
+            mixnet_client_config: value.mixnet_client_config,
+            packet_drops: None,
         }
     }
 }
@@ -258,22 +348,15 @@ impl From<TunnelState> for VpnServiceStatus {
     fn from(value: TunnelState) -> Self {
         match value {
             TunnelState::Connected { connection_data } => {
-                Self::Connected(Box::new(ConnectedResultDetails {
-                    entry_gateway: *connection_data.entry_gateway,
-                    exit_gateway: *connection_data.exit_gateway,
-                    specific_details: ConnectedStateDetails::from(connection_data.tunnel),
-                    // FIXME: impossible to map this correctly
-                    since: connection_data
-                        .connected_at
-                        .unwrap_or(OffsetDateTime::now_utc()),
-                }))
+                Self::Connected(Box::new(ConnectedResultDetails::from(connection_data)))
+            }
+            TunnelState::Paused { connection_data } => {
+                Self::Paused(Box::new(ConnectedResultDetails::from(connection_data)))
             }
             TunnelState::Connecting { .. } => Self::Connecting,
-            TunnelState::Disconnected => Self::NotConnected,
+            TunnelState::Disconnected { .. } => Self::NotConnected,
             TunnelState::Disconnecting { .. } => Self::Disconnecting,
-            TunnelState::Error(e) => Self::ConnectionFailed(ConnectionFailedError::InternalError(
-                format!("Error state: {:?}", e),
-            )),
+            TunnelState::Error(reason) => Self::ConnectionFailed(reason.into()),
         }
     }
 }
@@ -287,6 +370,15 @@ pub struct VpnServiceInfo {
     pub git_commit: String,
     pub nym_network: NymNetwork,
     pub nym_vpn_network: NymVpnNetwork,
+    pub capabilities: nym_vpn_lib::Capabilities,
+}
+
+#[derive(Clone, Debug)]
+pub struct VpnServiceHealth {
+    pub uptime: Duration,
+    pub status: VpnServiceStatus,
+    pub network_name: String,
+    pub is_account_stored: bool,
 }
 
 impl fmt::Display for VpnServiceStatus {
@@ -295,6 +387,7 @@ impl fmt::Display for VpnServiceStatus {
             VpnServiceStatus::NotConnected => write!(f, "NotConnected"),
             VpnServiceStatus::Connecting => write!(f, "Connecting"),
             VpnServiceStatus::Connected(details) => write!(f, "Connected({})", details),
+            VpnServiceStatus::Paused(details) => write!(f, "Paused({})", details),
             VpnServiceStatus::Disconnecting => write!(f, "Disconnecting"),
             VpnServiceStatus::ConnectionFailed(reason) => {
                 write!(f, "ConnectionFailed({})", reason)
@@ -307,8 +400,21 @@ impl fmt::Display for VpnServiceStatus {
 pub struct ConnectedResultDetails {
     pub entry_gateway: NodeIdentity,
     pub exit_gateway: NodeIdentity,
+    pub entry_gateway_diagnostics: GatewayDiagnostics,
+    pub exit_gateway_diagnostics: GatewayDiagnostics,
     pub specific_details: ConnectedStateDetails,
     pub since: time::OffsetDateTime,
+    // How long the connection has been up. Only known by the service, so it's filled in when
+    // reporting the status.
+    pub duration: Option<Duration>,
+    pub tun_name: Option<String>,
+    pub mtu: u16,
+    pub credentials_mode_active: bool,
+    // The config the mixnet client was started with, cover traffic is disabled for wireguard
+    pub mixnet_client_config: MixnetClientConfig,
+    // Packets dropped by the mixnet processor, taken from the latest connection statistics
+    // event. Filled in when reporting the status.
+    pub packet_drops: Option<MixnetPacketDrops>,
 }
 
 impl fmt::Display for ConnectedResultDetails {
@@ -321,6 +427,41 @@ impl fmt::Display for ConnectedResultDetails {
     }
 }
 
+// Totals of the last session, persisted so that they can still be queried after a restart or
+// once the next session is underway.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionSummaryDetails {
+    pub entry_gateway: NodeIdentity,
+    pub exit_gateway: NodeIdentity,
+    // Missing from the summaries persisted before it was recorded
+    #[serde(default)]
+    pub tunnel_type: Option<TunnelType>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    pub duration: Duration,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    // Bytes per second, both directions combined
+    pub average_throughput: u64,
+    pub tickets_spent: u32,
+}
+
+impl From<SessionSummary> for SessionSummaryDetails {
+    fn from(value: SessionSummary) -> Self {
+        Self {
+            entry_gateway: *value.entry_gateway,
+            exit_gateway: *value.exit_gateway,
+            tunnel_type: Some(value.tunnel_type),
+            started_at: value.started_at,
+            duration: value.duration,
+            bytes_sent: value.bytes_sent,
+            bytes_received: value.bytes_received,
+            average_throughput: value.average_throughput,
+            tickets_spent: value.tickets_spent,
+        }
+    }
+}
+
 impl VpnServiceStatus {
     pub fn error(&self) -> Option<ConnectionFailedError> {
         match self {
@@ -335,6 +476,7 @@ pub enum VpnServiceStateChange {
     NotConnected,
     Connecting,
     Connected,
+    Paused,
     Disconnecting,
     ConnectionFailed(ConnectionFailedError),
 }
@@ -344,11 +486,10 @@ impl From<TunnelState> for VpnServiceStateChange {
         match value {
             TunnelState::Connecting { .. } => Self::Connecting,
             TunnelState::Connected { .. } => Self::Connected,
-            TunnelState::Disconnected => Self::NotConnected,
+            TunnelState::Paused { .. } => Self::Paused,
+            TunnelState::Disconnected { .. } => Self::NotConnected,
             TunnelState::Disconnecting { .. } => Self::Disconnecting,
-            TunnelState::Error(reason) => Self::ConnectionFailed(
-                ConnectionFailedError::InternalError(format!("Error state: {:?}", reason)),
-            ),
+            TunnelState::Error(reason) => Self::ConnectionFailed(reason.into()),
         }
     }
 }
@@ -376,6 +517,15 @@ where
     // commands.
     vpn_command_rx: mpsc::UnboundedReceiver<VpnServiceCommand>,
 
+    // Notified when the auto-connect options should be re-read, e.g. on SIGHUP.
+    config_reload_rx: mpsc::UnboundedReceiver<()>,
+
+    // The options from the auto-connect file, used for connect commands without arguments.
+    connect_defaults: AutoConnectConfig,
+
+    // Set while the auto-connect hasn't succeeded yet, until the user connects or disconnects.
+    pending_auto_connect: bool,
+
     vpn_state_changes_tx: broadcast::Sender<VpnServiceStateChange>,
     status_tx: broadcast::Sender<MixnetEvent>,
 
@@ -390,6 +540,20 @@ where
     // Last known tunnel state.
     tunnel_state: TunnelState,
 
+    // When the current connection was established, kept both as a monotonic instant to compute
+    // its duration and as wall-clock time for display. `None` when not connected.
+    connected_since: Option<(Instant, OffsetDateTime)>,
+
+    // Packet drop counters from the latest connection statistics event of the current connection.
+    packet_drops: Option<MixnetPacketDrops>,
+
+    // Totals of the last session that ended, and the file they are persisted to.
+    last_session_summary: Option<SessionSummaryDetails>,
+    last_session_summary_file: PathBuf,
+
+    // Permissions of the files written to the data directory.
+    storage_permissions: StoragePermissions,
+
     // Tunnel state machine handle.
     state_machine_handle: JoinHandle<()>,
 
@@ -401,24 +565,31 @@ where
 
     // Service shutdown token.
     shutdown_token: CancellationToken,
+
+    // When the service was started, used to report uptime.
+    started_at: Instant,
 }
 
 impl NymVpnService<nym_vpn_lib::storage::VpnClientOnDiskStorage> {
     pub(crate) fn spawn(
         vpn_state_changes_tx: broadcast::Sender<VpnServiceStateChange>,
         vpn_command_rx: mpsc::UnboundedReceiver<VpnServiceCommand>,
+        config_reload_rx: mpsc::UnboundedReceiver<()>,
         status_tx: broadcast::Sender<MixnetEvent>,
         shutdown_token: CancellationToken,
         network_env: Network,
+        storage_permissions: StoragePermissions,
     ) -> JoinHandle<()> {
         tracing::info!("Starting VPN service");
         tokio::spawn(async {
             match NymVpnService::new(
                 vpn_state_changes_tx,
                 vpn_command_rx,
+                config_reload_rx,
                 status_tx,
                 shutdown_token,
                 network_env,
+                storage_permissions,
             )
             .await
             {
@@ -444,22 +615,28 @@ impl NymVpnService<nym_vpn_lib::storage::VpnClientOnDiskStorage> {
     pub(crate) async fn new(
         vpn_state_changes_tx: broadcast::Sender<VpnServiceStateChange>,
         vpn_command_rx: mpsc::UnboundedReceiver<VpnServiceCommand>,
+        config_reload_rx: mpsc::UnboundedReceiver<()>,
         status_tx: broadcast::Sender<MixnetEvent>,
         shutdown_token: CancellationToken,
         network_env: Network,
+        storage_permissions: StoragePermissions,
     ) -> Result<Self> {
         let network_name = network_env.nym_network_details().network_name.clone();
 
         let config_dir = super::config::config_dir().join(&network_name);
         let config_file = config_dir.join(DEFAULT_CONFIG_FILE);
         let data_dir = super::config::data_dir().join(&network_name);
+        let last_session_summary_file = data_dir.join(DEFAULT_LAST_SESSION_SUMMARY_FILE);
+        let last_session_summary = read_last_session_summary(&last_session_summary_file);
 
         let storage = Arc::new(tokio::sync::Mutex::new(
-            nym_vpn_lib::storage::VpnClientOnDiskStorage::new(data_dir.clone()),
+            nym_vpn_lib::storage::VpnClientOnDiskStorage::new(data_dir.clone())
+                .with_permissions(storage_permissions),
         ));
 
         // Make sure the data dir exists
-        super::config::create_data_dir(&data_dir).map_err(Error::ConfigSetup)?;
+        super::config::create_data_dir(&data_dir, storage_permissions)
+            .map_err(Error::ConfigSetup)?;
 
         // We need to create the user agent here and not in the controller so that we correctly
         // pick up build time constants.
@@ -488,6 +665,10 @@ impl NymVpnService<nym_vpn_lib::storage::VpnClientOnDiskStorage> {
             api_url,
             nym_vpn_api_url: Some(network_env.vpn_api_url()),
             min_gateway_performance: None,
+            proxy: None,
+            directory_cache_path: None,
+            directory_cache_ttl: None,
+            nym_node_http_port: None,
         };
         let nym_config = NymConfig {
             data_path: Some(data_dir.clone()),
@@ -499,6 +680,7 @@ impl NymVpnService<nym_vpn_lib::storage::VpnClientOnDiskStorage> {
             event_sender,
             nym_config,
             tunnel_settings,
+            Arc::new(SystemTunProvider),
             shutdown_token.child_token(),
         )
         .await
@@ -508,16 +690,27 @@ impl NymVpnService<nym_vpn_lib::storage::VpnClientOnDiskStorage> {
             network_env,
             shared_account_state,
             vpn_command_rx,
+            config_reload_rx,
+            connect_defaults: AutoConnectConfig::default(),
+            pending_auto_connect: false,
             vpn_state_changes_tx,
             status_tx,
             account_command_tx,
             config_file,
             storage,
-            tunnel_state: TunnelState::Disconnected,
+            tunnel_state: TunnelState::Disconnected {
+                reason: DisconnectReason::UserRequested,
+            },
+            connected_since: None,
+            packet_drops: None,
+            last_session_summary,
+            last_session_summary_file,
+            storage_permissions,
             state_machine_handle,
             command_sender,
             event_receiver,
             shutdown_token,
+            started_at: Instant::now(),
         })
     }
 }
@@ -527,16 +720,42 @@ where
     S: nym_vpn_store::VpnStorage,
 {
     pub(crate) async fn run(mut self) -> anyhow::Result<()> {
+        match AutoConnectConfig::read_from_file() {
+            Ok(Some(config)) => self.apply_connect_defaults(config),
+            Ok(None) => {}
+            Err(err) => tracing::error!("Ignoring the auto-connect options: {err}"),
+        }
+
+        // At boot the account sync or the network can take a while, so keep trying until the
+        // auto-connect succeeds
+        let mut auto_connect_timer = tokio::time::interval(AUTO_CONNECT_RETRY_INTERVAL);
+        auto_connect_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
         loop {
             tokio::select! {
                 Some(command) = self.vpn_command_rx.recv() => {
                     tracing::debug!("VPN: Received command: {command}");
                     self.handle_service_command(command).await;
                 }
+                Some(()) = self.config_reload_rx.recv() => {
+                    self.handle_config_reload();
+                    auto_connect_timer.reset_immediately();
+                }
+                _ = auto_connect_timer.tick(), if self.pending_auto_connect => {
+                    self.handle_auto_connect().await;
+                }
                 Some(event) = self.event_receiver.recv() => {
                     tracing::info!("Tunnel event: {}", event);
                     match event {
                         TunnelEvent::NewState(new_state) => {
+                            self.update_connected_since(&new_state);
+                            if let TunnelState::Disconnected {
+                                session_summary: Some(summary),
+                                ..
+                            } = &new_state
+                            {
+                                self.store_last_session_summary(summary.clone());
+                            }
                             self.tunnel_state = new_state.clone();
                             let vpn_state_change = VpnServiceStateChange::from(new_state);
                             if let Err(e) = self.vpn_state_changes_tx.send(vpn_state_change) {
@@ -544,6 +763,9 @@ where
                             }
                         }
                         TunnelEvent::MixnetState(event) => {
+                            if let MixnetEvent::ConnectionStatistics(statistics) = &event {
+                                self.packet_drops = Some(statistics.packet_drops);
+                            }
                             if let Err(e) = self.status_tx.send(event) {
                                 tracing::error!("Failed to send mixnet event: {}", e);
                             }
@@ -576,6 +798,10 @@ where
                 let result = self.handle_info().await;
                 let _ = tx.send(result);
             }
+            VpnServiceCommand::Health(tx, ()) => {
+                let result = self.handle_health().await;
+                let _ = tx.send(result);
+            }
             VpnServiceCommand::SetNetwork(tx, network) => {
                 let result = self.handle_set_network(network).await;
                 let _ = tx.send(result);
@@ -589,17 +815,64 @@ where
                 let _ = tx.send(result);
             }
             VpnServiceCommand::Connect(tx, (connect_args, user_agent)) => {
+                self.cancel_auto_connect();
+                let connect_args =
+                    connect_args.unwrap_or_else(|| self.connect_defaults.to_connect_args());
                 let result = self.handle_connect(connect_args, user_agent).await;
                 let _ = tx.send(result);
             }
             VpnServiceCommand::Disconnect(tx, ()) => {
+                self.cancel_auto_connect();
                 let result = self.handle_disconnect().await;
                 let _ = tx.send(result);
             }
+            VpnServiceCommand::RotateWireguardKeys(tx, ()) => {
+                let result = self.handle_rotate_wireguard_keys().await;
+                let _ = tx.send(result);
+            }
+            VpnServiceCommand::ResetIdleTimer(tx, ()) => {
+                let result = self.handle_reset_idle_timer().await;
+                let _ = tx.send(result);
+            }
+            VpnServiceCommand::ExportWireguardConfig(tx, include_private_keys) => {
+                let result = self
+                    .handle_export_wireguard_config(include_private_keys)
+                    .await;
+                let _ = tx.send(result);
+            }
+            VpnServiceCommand::RunConnectivityTest(tx, ()) => {
+                match self.handle_run_connectivity_test() {
+                    // The test takes a few seconds, don't hold up the other commands meanwhile
+                    Ok(reply_rx) => {
+                        tokio::spawn(async move {
+                            let _ = tx.send(wait_for_connectivity_test(reply_rx).await);
+                        });
+                    }
+                    Err(err) => {
+                        let _ = tx.send(Err(err));
+                    }
+                }
+            }
+            VpnServiceCommand::GetExitPublicIp(tx, ()) => {
+                match self.handle_get_exit_public_ip() {
+                    // The lookup takes a few seconds, don't hold up the other commands meanwhile
+                    Ok(reply_rx) => {
+                        tokio::spawn(async move {
+                            let _ = tx.send(wait_for_exit_public_ip(reply_rx).await);
+                        });
+                    }
+                    Err(err) => {
+                        let _ = tx.send(Err(err));
+                    }
+                }
+            }
             VpnServiceCommand::Status(tx, ()) => {
                 let result = self.handle_status().await;
                 let _ = tx.send(result);
             }
+            VpnServiceCommand::GetLastSessionSummary(tx, ()) => {
+                let _ = tx.send(self.last_session_summary.clone());
+            }
             VpnServiceCommand::StoreAccount(tx, account) => {
                 let result = self.handle_store_account(account).await;
                 let _ = tx.send(result);
@@ -632,6 +905,10 @@ where
                 let result = Ok(self.handle_is_ready_to_connect().await);
                 let _ = tx.send(result);
             }
+            VpnServiceCommand::SubscribeAccountChanges(tx, ()) => {
+                let result = self.shared_account_state.subscribe().await;
+                let _ = tx.send(result);
+            }
             VpnServiceCommand::ResetDeviceIdentity(tx, seed) => {
                 let result = self.handle_reset_device_identity(seed).await;
                 let _ = tx.send(result);
@@ -668,6 +945,14 @@ where
                 let result = self.handle_get_available_tickets().await;
                 let _ = tx.send(result);
             }
+            VpnServiceCommand::RefreshTickets(tx, ()) => {
+                // Waiting for the zk-nyms to be issued takes a while, so don't hold up the other
+                // commands in the meantime
+                let result = self.handle_refresh_tickets();
+                tokio::spawn(async move {
+                    let _ = tx.send(result.await);
+                });
+            }
             VpnServiceCommand::FetchRawAccountSummary(tx, ()) => {
                 let result = self.handle_fetch_raw_account_summary().await;
                 let _ = tx.send(result);
@@ -704,6 +989,7 @@ where
             let config = NymVpnServiceConfig {
                 entry_point: entry.unwrap_or(EntryPoint::Random),
                 exit_point: exit.unwrap_or(ExitPoint::Random),
+                ..Default::default()
             };
             super::config::create_config_file(&self.config_file, config)
                 .map_err(Error::ConfigSetup)?
@@ -731,6 +1017,57 @@ where
         }
     }
 
+    fn apply_connect_defaults(&mut self, config: AutoConnectConfig) {
+        tracing::info!("Using the default connect options: {config}");
+        if config.auto_connect {
+            self.pending_auto_connect = true;
+        } else {
+            tracing::info!("Auto-connect is disabled");
+        }
+        self.connect_defaults = config;
+    }
+
+    async fn handle_auto_connect(&mut self) {
+        tracing::info!("Auto-connecting");
+        let connect_args = self.connect_defaults.to_connect_args();
+        let user_agent = crate::util::construct_user_agent();
+        match self.handle_connect(connect_args, user_agent).await {
+            Ok(()) => self.pending_auto_connect = false,
+            Err(VpnServiceConnectError::Cancel) => self.pending_auto_connect = false,
+            Err(err) => tracing::warn!(
+                "Failed to auto-connect, retrying in {}s: {err}",
+                AUTO_CONNECT_RETRY_INTERVAL.as_secs()
+            ),
+        }
+    }
+
+    fn cancel_auto_connect(&mut self) {
+        if self.pending_auto_connect {
+            tracing::info!("Cancelling the auto-connect in favor of the user command");
+            self.pending_auto_connect = false;
+        }
+    }
+
+    // Keeps the current options when the file can't be read, so that a typo doesn't disconnect
+    // the user.
+    fn handle_config_reload(&mut self) {
+        let file_path = AutoConnectConfig::file_path();
+        tracing::info!(
+            "Reloading the auto-connect options from {}",
+            file_path.display()
+        );
+
+        match AutoConnectConfig::read_from_file() {
+            Ok(Some(config)) => self.apply_connect_defaults(config),
+            Ok(None) => {
+                tracing::info!("No auto-connect options at {}", file_path.display());
+                self.connect_defaults = AutoConnectConfig::default();
+                self.pending_auto_connect = false;
+            }
+            Err(err) => tracing::error!("Keeping the current options: {err}"),
+        }
+    }
+
     async fn handle_connect(
         &mut self,
         connect_args: ConnectArgs,
@@ -786,6 +1123,7 @@ where
             min_gateway_performance: options
                 .min_gateway_mixnet_performance
                 .map(|p| p.round_to_integer()),
+            ..Default::default()
         };
 
         let tunnel_type = if options.enable_two_hop {
@@ -794,27 +1132,67 @@ where
             TunnelType::Mixnet
         };
 
-        let dns = options
-            .dns
-            .map(|addr| DnsOptions::Custom(vec![addr]))
-            .unwrap_or(DnsOptions::default());
+        let dns = match options.dns {
+            Some(addr) => DnsOptions::Custom(vec![addr]),
+            None if options.gateway_dns => DnsOptions::GatewayProvided,
+            None => DnsOptions::default(),
+        };
 
         let tunnel_settings = TunnelSettings {
             tunnel_type,
             enable_credentials_mode: options.enable_credentials_mode,
-            mixnet_tunnel_options: MixnetTunnelOptions::default(),
+            mixnet_tunnel_options: MixnetTunnelOptions {
+                connection_quality: ConnectionQualityConfig {
+                    latency_interval: options
+                        .latency_sample_interval
+                        .unwrap_or(ConnectionQualityConfig::default().latency_interval),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
             wireguard_tunnel_options: WireguardTunnelOptions {
                 multihop_mode: if options.netstack {
                     WireguardMultihopMode::Netstack
                 } else {
                     WireguardMultihopMode::TunTun
                 },
+                tickets_per_top_up: config.tickets_per_top_up,
+                key_namespace: options.key_namespace,
+                key_rotation_interval: options.key_rotation_interval,
+                preferred_wg_ports: options.preferred_wg_ports,
+                ..Default::default()
             },
             gateway_performance_options: gateway_options,
+            gateway_lookup_retry_options: GatewayLookupRetryOptions::default(),
+            connect_retry_options: ConnectRetryOptions::default(),
+            connect_deadline: options.connect_deadline,
             mixnet_client_config: Some(mixnet_client_config),
+            mixnet_client_startup_timeout: options.mixnet_client_startup_timeout,
+            shutdown_timeout: config.shutdown_timeout_secs.map(Duration::from_secs),
+            max_clock_skew: Duration::from_secs(config.max_clock_skew_secs),
+            directory_cache_ttl: options.directory_cache_ttl,
+            proxy: options.proxy,
             entry_point: Box::new(config.entry_point),
             exit_point: Box::new(config.exit_point),
+            allow_same_country: !options.disallow_same_country,
+            excluded_gateways: options.excluded_gateways,
+            sticky_gateways: options.sticky_gateways,
+            allow_lan: options.allow_lan,
+            routed_destinations: options.routed_destinations,
+            reconnect_on_network_change: !options.disable_reconnect_on_network_change,
             dns,
+            detect_dns_leaks: options.detect_dns_leaks,
+            dns_leak_probe_interval: options
+                .dns_leak_probe_interval
+                .unwrap_or(DEFAULT_DNS_LEAK_PROBE_INTERVAL),
+            ephemeral_identity: false,
+            max_session_duration: options.max_session_duration,
+            session_expiry_warning: DEFAULT_SESSION_EXPIRY_WARNING,
+            idle_timeout: options.idle_timeout,
+            idle_timeout_warning: DEFAULT_IDLE_TIMEOUT_WARNING,
+            selection_seed: options.selection_seed,
+            region_affinity: options.region_affinity,
+            selection_strategy: options.selection_strategy,
         };
 
         match self
@@ -846,8 +1224,136 @@ where
             })
     }
 
+    async fn handle_rotate_wireguard_keys(&mut self) -> Result<(), VpnServiceRotateKeysError> {
+        self.command_sender
+            .send(TunnelCommand::RotateWireguardKeys)
+            .map_err(|e| {
+                tracing::error!("Failed to send command to rotate wireguard keys: {}", e);
+                VpnServiceRotateKeysError::Internal(
+                    "failed to send rotate wireguard keys command".to_owned(),
+                )
+            })
+    }
+
+    async fn handle_reset_idle_timer(&mut self) -> Result<(), VpnServiceResetIdleTimerError> {
+        self.command_sender
+            .send(TunnelCommand::ResetIdleTimer)
+            .map_err(|e| {
+                tracing::error!("Failed to send command to reset the idle timer: {}", e);
+                VpnServiceResetIdleTimerError::Internal(
+                    "failed to send reset idle timer command".to_owned(),
+                )
+            })
+    }
+
+    async fn handle_export_wireguard_config(
+        &mut self,
+        include_private_keys: bool,
+    ) -> Result<String, VpnServiceExportWireguardConfigError> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.command_sender
+            .send(TunnelCommand::ExportWireguardConfig {
+                include_private_keys,
+                reply_tx,
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to send command to export wireguard config: {}", e);
+                VpnServiceExportWireguardConfigError::Internal(
+                    "failed to send export wireguard config command".to_owned(),
+                )
+            })?;
+
+        reply_rx
+            .await
+            .map_err(|_| {
+                VpnServiceExportWireguardConfigError::Internal(
+                    "failed to receive the wireguard config".to_owned(),
+                )
+            })?
+            .ok_or(VpnServiceExportWireguardConfigError::NotConnected)
+    }
+
+    fn handle_run_connectivity_test(
+        &mut self,
+    ) -> Result<oneshot::Receiver<Option<ConnectivityTestReport>>, VpnServiceConnectivityTestError>
+    {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.command_sender
+            .send(TunnelCommand::RunConnectivityTest(reply_tx))
+            .map_err(|e| {
+                tracing::error!("Failed to send command to run the connectivity test: {}", e);
+                VpnServiceConnectivityTestError::Internal(
+                    "failed to send run connectivity test command".to_owned(),
+                )
+            })?;
+        Ok(reply_rx)
+    }
+
+    fn handle_get_exit_public_ip(
+        &mut self,
+    ) -> Result<ExitPublicIpReceiver, VpnServiceExitPublicIpError> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.command_sender
+            .send(TunnelCommand::GetExitPublicIp(reply_tx))
+            .map_err(|e| {
+                tracing::error!("Failed to send command to get the exit public IP: {}", e);
+                VpnServiceExitPublicIpError::Internal(
+                    "failed to send get exit public ip command".to_owned(),
+                )
+            })?;
+        Ok(reply_rx)
+    }
+
+    fn update_connected_since(&mut self, new_state: &TunnelState) {
+        match new_state {
+            TunnelState::Connected { .. } | TunnelState::Paused { .. } => {
+                if !matches!(
+                    self.tunnel_state,
+                    TunnelState::Connected { .. } | TunnelState::Paused { .. }
+                ) {
+                    self.connected_since = Some((Instant::now(), OffsetDateTime::now_utc()));
+                    self.packet_drops = None;
+                }
+            }
+            _ => {
+                self.connected_since = None;
+                self.packet_drops = None;
+            }
+        }
+    }
+
+    fn store_last_session_summary(&mut self, summary: SessionSummary) {
+        let summary = SessionSummaryDetails::from(summary);
+        match serde_json::to_string(&summary) {
+            Ok(json) => {
+                // Written in the background to not hold up the event loop.
+                let path = self.last_session_summary_file.clone();
+                let permissions = self.storage_permissions;
+                tokio::task::spawn_blocking(move || {
+                    let result =
+                        std::fs::write(&path, json).and_then(|()| permissions.apply_to_file(&path));
+                    if let Err(err) = result {
+                        tracing::warn!("Failed to persist the last session summary: {err}");
+                    }
+                });
+            }
+            Err(err) => tracing::warn!("Failed to serialize the last session summary: {err}"),
+        }
+        self.last_session_summary = Some(summary);
+    }
+
     async fn handle_status(&self) -> VpnServiceStatus {
-        VpnServiceStatus::from(self.tunnel_state.clone())
+        let mut status = VpnServiceStatus::from(self.tunnel_state.clone());
+        if let (
+            VpnServiceStatus::Connected(details) | VpnServiceStatus::Paused(details),
+            Some((instant, since)),
+        ) = (&mut status, self.connected_since)
+        {
+            details.since = since;
+            details.duration = Some(instant.elapsed());
+            details.packet_drops = self.packet_drops;
+        }
+        status
     }
 
     async fn handle_info(&self) -> VpnServiceInfo {
@@ -862,6 +1368,26 @@ where
             git_commit: bin_info.commit_sha.to_string(),
             nym_network: self.network_env.nym_network.clone(),
             nym_vpn_network: self.network_env.nym_vpn_network.clone(),
+            capabilities: nym_vpn_lib::capabilities(),
+        }
+    }
+
+    async fn handle_health(&self) -> VpnServiceHealth {
+        // The health report should always be available, so don't fail it if the storage can't be
+        // read.
+        let is_account_stored = self
+            .handle_is_account_stored()
+            .await
+            .inspect_err(|err| {
+                tracing::warn!("Failed to check if account is stored: {}", err);
+            })
+            .unwrap_or(false);
+
+        VpnServiceHealth {
+            uptime: self.started_at.elapsed(),
+            status: VpnServiceStatus::from(self.tunnel_state.clone()),
+            network_name: self.network_env.nym_network_details().network_name.clone(),
+            is_account_stored,
         }
     }
 
@@ -1058,28 +1584,45 @@ where
             })
     }
 
-    async fn handle_get_device_zk_nyms(&self) -> Result<(), AccountError> {
+    async fn handle_get_device_zk_nyms(&self) -> Result<NymVpnZkNymResponse, AccountError> {
+        let (result_tx, result_rx) = oneshot::channel();
         self.account_command_tx
-            .send(AccountCommand::GetDeviceZkNym)
+            .send(AccountCommand::GetDeviceZkNym(result_tx))
             .map_err(|err| AccountError::SendCommand {
                 source: Box::new(err),
-            })
+            })?;
+        let result = result_rx.await.map_err(|err| AccountError::RecvCommand {
+            source: Box::new(err),
+        })?;
+        result.map_err(|err| AccountError::AccountControllerError { source: err })
     }
 
-    async fn handle_get_zk_nyms_available_for_download(&self) -> Result<(), AccountError> {
+    async fn handle_get_zk_nyms_available_for_download(
+        &self,
+    ) -> Result<NymVpnZkNymResponse, AccountError> {
+        let (result_tx, result_rx) = oneshot::channel();
         self.account_command_tx
-            .send(AccountCommand::GetZkNymsAvailableForDownload)
+            .send(AccountCommand::GetZkNymsAvailableForDownload(result_tx))
             .map_err(|err| AccountError::SendCommand {
                 source: Box::new(err),
-            })
+            })?;
+        let result = result_rx.await.map_err(|err| AccountError::RecvCommand {
+            source: Box::new(err),
+        })?;
+        result.map_err(|err| AccountError::AccountControllerError { source: err })
     }
 
-    async fn handle_get_zk_nym_by_id(&self, id: String) -> Result<(), AccountError> {
+    async fn handle_get_zk_nym_by_id(&self, id: String) -> Result<NymVpnZkNym, AccountError> {
+        let (result_tx, result_rx) = oneshot::channel();
         self.account_command_tx
-            .send(AccountCommand::GetZkNymById(id))
+            .send(AccountCommand::GetZkNymById(id, result_tx))
             .map_err(|err| AccountError::SendCommand {
                 source: Box::new(err),
-            })
+            })?;
+        let result = result_rx.await.map_err(|err| AccountError::RecvCommand {
+            source: Box::new(err),
+        })?;
+        result.map_err(|err| AccountError::AccountControllerError { source: err })
     }
 
     async fn handle_confirm_zk_nym_id_downloaded(&self, id: String) -> Result<(), AccountError> {
@@ -1103,6 +1646,23 @@ where
         result.map_err(|err| AccountError::AccountControllerError { source: err })
     }
 
+    fn handle_refresh_tickets(&self) -> impl Future<Output = Result<u32, AccountError>> {
+        let (result_tx, result_rx) = oneshot::channel();
+        let sent = self
+            .account_command_tx
+            .send(AccountCommand::RefreshTickets(result_tx))
+            .map_err(|err| AccountError::SendCommand {
+                source: Box::new(err),
+            });
+        async move {
+            sent?;
+            let result = result_rx.await.map_err(|err| AccountError::RecvCommand {
+                source: Box::new(err),
+            })?;
+            result.map_err(|err| AccountError::AccountControllerError { source: err })
+        }
+    }
+
     async fn handle_fetch_raw_account_summary(
         &self,
     ) -> Result<NymVpnAccountSummaryResponse, AccountError> {
@@ -1140,3 +1700,38 @@ where
         api_client.get_devices(&account).await.map_err(Into::into)
     }
 }
+
+// A missing or unreadable summary is not worth failing the service over, there just isn't one.
+fn read_last_session_summary(path: &Path) -> Option<SessionSummaryDetails> {
+    let json = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&json)
+        .inspect_err(|err| tracing::warn!("Ignoring malformed last session summary: {err}"))
+        .ok()
+}
+
+async fn wait_for_connectivity_test(
+    reply_rx: oneshot::Receiver<Option<ConnectivityTestReport>>,
+) -> Result<ConnectivityTestReport, VpnServiceConnectivityTestError> {
+    reply_rx
+        .await
+        .map_err(|_| {
+            VpnServiceConnectivityTestError::Internal(
+                "failed to receive the connectivity test report".to_owned(),
+            )
+        })?
+        .ok_or(VpnServiceConnectivityTestError::NotConnected)
+}
+
+type ExitPublicIpReceiver = oneshot::Receiver<Option<Result<ExitPublicIp, ExitIpLookupError>>>;
+
+async fn wait_for_exit_public_ip(
+    reply_rx: ExitPublicIpReceiver,
+) -> Result<ExitPublicIp, VpnServiceExitPublicIpError> {
+    let result = reply_rx
+        .await
+        .map_err(|_| {
+            VpnServiceExitPublicIpError::Internal("failed to receive the exit public ip".to_owned())
+        })?
+        .ok_or(VpnServiceExitPublicIpError::NotConnected)?;
+    Ok(result?)
+}
@@ -1,6 +1,8 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -55,6 +57,34 @@ impl Country {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CountryGateways {
+    pub country: Country,
+    pub gateways: Vec<Gateway>,
+}
+
+/// Group the gateways by the country they are located in, ordered by country code. Gateways
+/// without a known location are left out, the same way they are missing from the country list.
+pub fn group_by_country(gateways: impl IntoIterator<Item = Gateway>) -> Vec<CountryGateways> {
+    let mut countries = BTreeMap::<String, Vec<Gateway>>::new();
+    for gateway in gateways {
+        if let Some(location) = &gateway.location {
+            countries
+                .entry(location.two_letter_iso_country_code.clone())
+                .or_default()
+                .push(gateway);
+        }
+    }
+
+    countries
+        .into_iter()
+        .map(|(iso_code, gateways)| CountryGateways {
+            country: Country { iso_code },
+            gateways,
+        })
+        .collect()
+}
+
 impl From<nym_vpn_lib::gateway_directory::Country> for Country {
     fn from(country: nym_vpn_lib::gateway_directory::Country) -> Self {
         Self {
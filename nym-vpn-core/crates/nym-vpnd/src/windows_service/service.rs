@@ -15,7 +15,10 @@ use windows_service::{
 };
 
 use super::install;
-use crate::{cli::CliArgs, command_interface, logging, runtime, service::NymVpnService};
+use crate::{
+    cli::CliArgs, command_interface, config::GlobalConfigFile, logging, runtime,
+    service::NymVpnService,
+};
 
 windows_service::define_windows_service!(ffi_service_main, service_main);
 
@@ -57,6 +60,16 @@ async fn run_service(_arguments: Vec<OsString>) -> windows_service::Result<()> {
         }
     };
 
+    let storage_permissions = match GlobalConfigFile::read_from_file()
+        .and_then(|config| config.storage_permissions().map_err(Into::into))
+    {
+        Ok(storage_permissions) => storage_permissions,
+        Err(err) => {
+            tracing::error!("Failed to read the storage permissions, using the default: {err}");
+            Default::default()
+        }
+    };
+
     let shutdown_token = CancellationToken::new();
     let cloned_shutdown_token = shutdown_token.clone();
     let event_handler = move |control_event| -> ServiceControlHandlerResult {
@@ -102,12 +115,15 @@ async fn run_service(_arguments: Vec<OsString>) -> windows_service::Result<()> {
     );
 
     // Start the VPN service that wraps the actual VPN
+    let config_reload_rx = crate::reload_handler::install(shutdown_token.child_token());
     let vpn_handle = NymVpnService::spawn(
         state_changes_tx,
         vpn_command_rx,
+        config_reload_rx,
         status_tx,
         shutdown_token.child_token(),
         network_env,
+        storage_permissions,
     );
 
     tracing::info!("Service has started");
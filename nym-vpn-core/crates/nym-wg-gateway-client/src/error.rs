@@ -24,6 +24,12 @@ pub enum Error {
     #[error("failed to parse entry gateway socket addr: {0}")]
     FailedToParseEntryGatewaySocketAddr(#[source] std::net::AddrParseError),
 
+    #[error("invalid number of tickets to spend: {tickets}, must be at least 1")]
+    InvalidTicketCount { tickets: u32 },
+
+    #[error("the gateway assigned a different address or port to the rotated keys")]
+    RotationChangedConnection,
+
     #[error("failed to get {ticketbook_type} ticket: {source}")]
     GetTicket {
         ticketbook_type: TicketType,
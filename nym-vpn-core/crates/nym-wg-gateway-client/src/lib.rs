@@ -4,8 +4,10 @@
 mod error;
 
 use std::{
+    fs,
+    io::Write,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
     time::Duration,
 };
@@ -39,7 +41,11 @@ const DEFAULT_PUBLIC_ENTRY_WIREGUARD_KEY_FILENAME: &str = "public_entry_wireguar
 const DEFAULT_PRIVATE_EXIT_WIREGUARD_KEY_FILENAME: &str = "private_exit_wireguard.pem";
 const DEFAULT_PUBLIC_EXIT_WIREGUARD_KEY_FILENAME: &str = "public_exit_wireguard.pem";
 
+/// Default number of tickets spent each time bandwidth is requested.
 pub const TICKETS_TO_SPEND: u32 = 1;
+/// Upper bound for the number of tickets spent in a single request, to avoid accidentally spending
+/// a large part of the ticketbook at once.
+pub const MAX_TICKETS_TO_SPEND: u32 = 10;
 const RETRY_PERIOD: Duration = Duration::from_secs(30);
 
 #[derive(Clone, Debug)]
@@ -48,6 +54,9 @@ pub struct GatewayData {
     pub endpoint: SocketAddr,
     pub private_ipv4: Ipv4Addr,
     pub private_ipv6: Ipv6Addr,
+    /// Whether tickets were spent to register. A peer the gateway already knows reuses its
+    /// existing registration, and nothing is spent without credentials mode.
+    pub credentials_spent: bool,
 }
 #[derive(Clone)]
 pub struct WgGatewayLightClient {
@@ -97,9 +106,6 @@ impl WgGatewayLightClient {
             );
         }
 
-        if remaining_bandwidth_data.available_bandwidth < 1024 * 1024 {
-            warn!("Remaining bandwidth is under 1 MB. The wireguard mode will get suspended after that until tomorrow, UTC time. The client might shutdown with timeout soon");
-        }
         Ok(Some(remaining_bandwidth_data.available_bandwidth))
     }
 
@@ -154,6 +160,8 @@ impl WgGatewayLightClient {
 
 pub struct WgGatewayClient {
     keypair: encryption::KeyPair,
+    // Where the keypair is persisted, if it isn't ephemeral.
+    keypair_paths: Option<KeyPairPath>,
     auth_client: AuthClient,
     auth_recipient: Recipient,
 }
@@ -169,6 +177,7 @@ impl WgGatewayClient {
 
     fn new_type(
         data_path: &Option<PathBuf>,
+        key_namespace: Option<&str>,
         auth_client: AuthClient,
         auth_recipient: Recipient,
         private_file_name: &str,
@@ -176,32 +185,42 @@ impl WgGatewayClient {
     ) -> Self {
         let mut rng = OsRng;
         if let Some(data_path) = data_path {
+            let key_dir = match key_namespace {
+                Some(key_namespace) => data_path.join(key_namespace),
+                None => data_path.clone(),
+            };
             let paths = KeyPairPath::new(
-                data_path.join(private_file_name),
-                data_path.join(public_file_name),
+                key_dir.join(private_file_name),
+                key_dir.join(public_file_name),
             );
-            let keypair = load_or_generate_keypair(&mut rng, paths);
+            let keypair = load_or_generate_keypair(&mut rng, &key_dir, &paths);
             WgGatewayClient {
                 keypair,
+                keypair_paths: Some(paths),
                 auth_client,
                 auth_recipient,
             }
         } else {
             WgGatewayClient {
                 keypair: KeyPair::new(&mut rng),
+                keypair_paths: None,
                 auth_client,
                 auth_recipient,
             }
         }
     }
 
+    /// Create the client for the entry gateway. When a data path is given, the keys are stored in
+    /// it, or in its `key_namespace` subdirectory so that several profiles can share a data path.
     pub fn new_entry(
         data_path: &Option<PathBuf>,
+        key_namespace: Option<&str>,
         auth_client: AuthClient,
         auth_recipient: Recipient,
     ) -> Self {
         Self::new_type(
             data_path,
+            key_namespace,
             auth_client,
             auth_recipient,
             DEFAULT_PRIVATE_ENTRY_WIREGUARD_KEY_FILENAME,
@@ -209,13 +228,16 @@ impl WgGatewayClient {
         )
     }
 
+    /// Create the client for the exit gateway, see [`WgGatewayClient::new_entry`].
     pub fn new_exit(
         data_path: &Option<PathBuf>,
+        key_namespace: Option<&str>,
         auth_client: AuthClient,
         auth_recipient: Recipient,
     ) -> Self {
         Self::new_type(
             data_path,
+            key_namespace,
             auth_client,
             auth_recipient,
             DEFAULT_PRIVATE_EXIT_WIREGUARD_KEY_FILENAME,
@@ -235,15 +257,17 @@ impl WgGatewayClient {
         wg_gateway_client: &mut WgGatewayLightClient,
         controller: &nym_bandwidth_controller::BandwidthController<QueryHttpRpcNyxdClient, St>,
         ticketbook_type: TicketType,
+        tickets: u32,
     ) -> Result<PreparedCredential>
     where
         <St as CredentialStorage>::StorageError: Send + Sync + 'static,
     {
+        let tickets = validate_tickets_to_spend(tickets)?;
         let credential = controller
             .prepare_ecash_ticket(
                 ticketbook_type,
                 wg_gateway_client.auth_recipient().gateway().to_bytes(),
-                TICKETS_TO_SPEND,
+                tickets,
             )
             .await
             .map_err(|source| Error::GetTicket {
@@ -271,7 +295,7 @@ impl WgGatewayClient {
             .auth_client
             .send(init_message, self.auth_recipient)
             .await?;
-        let registered_data = match response.data {
+        let (registered_data, credentials_spent) = match response.data {
             AuthenticatorResponseData::PendingRegistration(PendingRegistrationResponse {
                 reply:
                     RegistrationData {
@@ -287,17 +311,19 @@ impl WgGatewayClient {
                     .verify(self.keypair.private_key(), nonce)
                     .map_err(Error::VerificationFailed)?;
 
-                let credential = if enable_credentials_mode {
-                    let cred = Self::request_bandwidth(
-                        &mut self.light_client(),
+                let mut light_client = self.light_client();
+                let credential = registration_credential(enable_credentials_mode, || async move {
+                    Self::request_bandwidth(
+                        &mut light_client,
                         controller,
                         ticketbook_type,
+                        TICKETS_TO_SPEND,
                     )
-                    .await?;
-                    Some(cred.data)
-                } else {
-                    None
-                };
+                    .await
+                    .map(|cred| cred.data)
+                })
+                .await?;
+                let credentials_spent = credential.is_some();
 
                 let finalized_message = ClientMessage::Final(Box::new(FinalMessage {
                     gateway_client: GatewayClient::new(
@@ -314,9 +340,11 @@ impl WgGatewayClient {
                 else {
                     return Err(Error::InvalidGatewayAuthResponse);
                 };
-                reply
+                (reply, credentials_spent)
+            }
+            AuthenticatorResponseData::Registered(RegisteredResponse { reply, .. }) => {
+                (reply, false)
             }
-            AuthenticatorResponseData::Registered(RegisteredResponse { reply, .. }) => reply,
             _ => return Err(Error::InvalidGatewayAuthResponse),
         };
 
@@ -329,7 +357,71 @@ impl WgGatewayClient {
             .map_err(Error::FailedToParseEntryGatewaySocketAddr)?,
             private_ipv4: registered_data.private_ips.ipv4,
             private_ipv6: registered_data.private_ips.ipv6,
+            credentials_spent,
+        };
+
+        Ok(gateway_data)
+    }
+
+    /// Replace the keypair with a freshly generated one and register it with the gateway of the
+    /// `current` session.
+    ///
+    /// The old keypair is restored if the registration fails, or if the gateway assigned a
+    /// different endpoint or addresses to the new keys, so that the client stays usable with the
+    /// session it already has. On success the new keypair is persisted, and only then is the old
+    /// private key overwritten on disk.
+    ///
+    /// The authenticator has no message to remove a peer, so the old keys stay registered with
+    /// the gateway until it drops them for being inactive.
+    pub async fn rotate_keys<St: CredentialStorage>(
+        &mut self,
+        current: &GatewayData,
+        controller: &nym_bandwidth_controller::BandwidthController<QueryHttpRpcNyxdClient, St>,
+        enable_credentials_mode: bool,
+        ticketbook_type: TicketType,
+    ) -> Result<GatewayData>
+    where
+        <St as CredentialStorage>::StorageError: Send + Sync + 'static,
+    {
+        let old_keypair = std::mem::replace(&mut self.keypair, KeyPair::new(&mut OsRng));
+        let result = self
+            .register_wireguard(
+                current.endpoint.ip(),
+                controller,
+                enable_credentials_mode,
+                ticketbook_type,
+            )
+            .await
+            .and_then(|gateway_data| {
+                // The tunnel is set up for the current endpoint and addresses, so only the keys
+                // can be swapped in place.
+                if is_same_connection(current, &gateway_data) {
+                    Ok(gateway_data)
+                } else {
+                    Err(Error::RotationChangedConnection)
+                }
+            });
+        let gateway_data = match result {
+            Ok(gateway_data) => gateway_data,
+            Err(e) => {
+                self.keypair = old_keypair;
+                return Err(e);
+            }
         };
+        info!(
+            "Rotated wireguard keys with gateway {}",
+            self.auth_recipient.gateway()
+        );
+
+        if let Some(paths) = &self.keypair_paths {
+            if let Err(e) = replace_stored_keypair(&self.keypair, paths) {
+                error!(
+                    "could not store rotated keypair at {:?} - {:?}; new keys will be generated \
+                     on the next connect",
+                    paths, e
+                );
+            }
+        }
 
         Ok(gateway_data)
     }
@@ -338,24 +430,60 @@ impl WgGatewayClient {
         wg_gateway_client: &mut WgGatewayLightClient,
         controller: &nym_bandwidth_controller::BandwidthController<QueryHttpRpcNyxdClient, St>,
         ticketbook_type: TicketType,
+        tickets: u32,
     ) -> Result<i64>
     where
         <St as CredentialStorage>::StorageError: Send + Sync + 'static,
     {
         let credential =
-            Self::request_bandwidth(wg_gateway_client, controller, ticketbook_type).await?;
+            Self::request_bandwidth(wg_gateway_client, controller, ticketbook_type, tickets)
+                .await?;
         let remaining_bandwidth = wg_gateway_client.top_up(credential.data).await?;
 
         Ok(remaining_bandwidth)
     }
 }
 
-fn load_or_generate_keypair<R: RngCore + CryptoRng>(rng: &mut R, paths: KeyPairPath) -> KeyPair {
-    match nym_pemstore::load_keypair(&paths) {
+fn validate_tickets_to_spend(tickets: u32) -> Result<u32> {
+    if tickets == 0 {
+        return Err(Error::InvalidTicketCount { tickets });
+    }
+    if tickets > MAX_TICKETS_TO_SPEND {
+        warn!("Requested to spend {tickets} tickets at once, capping to {MAX_TICKETS_TO_SPEND}");
+        return Ok(MAX_TICKETS_TO_SPEND);
+    }
+    Ok(tickets)
+}
+
+// The credential to finalize a pending registration with. Tickets are only requested, and thus
+// spent, in credentials mode; otherwise the gateway is expected to register us without one.
+async fn registration_credential<T, F, Fut>(
+    enable_credentials_mode: bool,
+    request_credential: F,
+) -> Result<Option<T>>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<T>>,
+{
+    if !enable_credentials_mode {
+        return Ok(None);
+    }
+    request_credential().await.map(Some)
+}
+
+fn load_or_generate_keypair<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    key_dir: &Path,
+    paths: &KeyPairPath,
+) -> KeyPair {
+    match nym_pemstore::load_keypair(paths) {
         Ok(keypair) => keypair,
         Err(_) => {
             let keypair = KeyPair::new(rng);
-            if let Err(e) = nym_pemstore::store_keypair(&keypair, &paths) {
+            if let Err(e) = create_key_dir(key_dir) {
+                error!("could not create key directory {:?} - {:?}", key_dir, e);
+            }
+            if let Err(e) = nym_pemstore::store_keypair(&keypair, paths) {
                 error!(
                     "could not store generated keypair at {:?} - {:?}; will use ephemeral keys",
                     paths, e
@@ -365,3 +493,204 @@ fn load_or_generate_keypair<R: RngCore + CryptoRng>(rng: &mut R, paths: KeyPairP
         }
     }
 }
+
+// The keys are private, so make sure nobody else can list or read them.
+fn create_key_dir(key_dir: &Path) -> std::io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(key_dir)
+}
+
+fn is_same_connection(current: &GatewayData, rotated: &GatewayData) -> bool {
+    current.endpoint == rotated.endpoint
+        && current.private_ipv4 == rotated.private_ipv4
+        && current.private_ipv6 == rotated.private_ipv6
+}
+
+// Write the new keypair next to the stored one before touching the latter, so that a failure to
+// write it doesn't cost us the old private key. Only then scrub the old private key and move the
+// new files into place.
+fn replace_stored_keypair(keypair: &KeyPair, paths: &KeyPairPath) -> std::io::Result<()> {
+    let staged = KeyPairPath::new(
+        staged_path(&paths.private_key_path),
+        staged_path(&paths.public_key_path),
+    );
+    nym_pemstore::store_keypair(keypair, &staged)?;
+
+    match scrub_file(&paths.private_key_path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!(
+            "could not overwrite old private key at {:?} - {:?}",
+            paths.private_key_path, e
+        ),
+    }
+    fs::rename(&staged.private_key_path, &paths.private_key_path)?;
+    fs::rename(&staged.public_key_path, &paths.public_key_path)
+}
+
+fn staged_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".new");
+    path.with_file_name(file_name)
+}
+
+// Overwrite the file contents in place before it gets replaced, so that the old key doesn't
+// linger in the freed blocks.
+fn scrub_file(path: &Path) -> std::io::Result<()> {
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+    let len = file.metadata()?.len();
+    file.write_all(&vec![0; len as usize])?;
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gateway_data() -> GatewayData {
+        GatewayData {
+            public_key: PublicKey::from([0; 32]),
+            endpoint: SocketAddr::from((Ipv4Addr::new(192, 0, 2, 1), 51822)),
+            private_ipv4: Ipv4Addr::new(10, 1, 0, 2),
+            private_ipv6: Ipv6Addr::new(0xfc01, 0, 0, 0, 0, 0, 0, 2),
+            credentials_spent: true,
+        }
+    }
+
+    fn key_paths(dir: &Path) -> KeyPairPath {
+        KeyPairPath::new(dir.join("private.pem"), dir.join("public.pem"))
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn registration_spends_a_credential_in_credentials_mode() {
+        let mut requests = 0;
+        let credential = block_on(registration_credential(true, || {
+            requests += 1;
+            async { Ok(42) }
+        }))
+        .unwrap();
+        assert_eq!(credential, Some(42));
+        assert_eq!(requests, 1);
+    }
+
+    #[test]
+    fn registration_spends_nothing_without_credentials_mode() {
+        let mut requests = 0;
+        let credential = block_on(registration_credential(false, || {
+            requests += 1;
+            async { Ok(42) }
+        }))
+        .unwrap();
+        assert_eq!(credential, None);
+        assert_eq!(requests, 0);
+    }
+
+    #[test]
+    fn registration_fails_when_the_credential_cannot_be_prepared() {
+        let result = block_on(registration_credential::<u32, _, _>(true, || async {
+            Err(Error::InvalidTicketCount { tickets: 0 })
+        }));
+        assert!(matches!(
+            result,
+            Err(Error::InvalidTicketCount { tickets: 0 })
+        ));
+    }
+
+    #[test]
+    fn rotation_only_keeps_connection_with_same_endpoint_and_addresses() {
+        let current = gateway_data();
+        let rotated = GatewayData {
+            public_key: PublicKey::from([1; 32]),
+            credentials_spent: false,
+            ..gateway_data()
+        };
+        assert!(is_same_connection(&current, &rotated));
+
+        let new_port = GatewayData {
+            endpoint: SocketAddr::from((Ipv4Addr::new(192, 0, 2, 1), 51820)),
+            ..gateway_data()
+        };
+        assert!(!is_same_connection(&current, &new_port));
+
+        let new_ipv4 = GatewayData {
+            private_ipv4: Ipv4Addr::new(10, 1, 0, 3),
+            ..gateway_data()
+        };
+        assert!(!is_same_connection(&current, &new_ipv4));
+
+        let new_ipv6 = GatewayData {
+            private_ipv6: Ipv6Addr::new(0xfc01, 0, 0, 0, 0, 0, 0, 3),
+            ..gateway_data()
+        };
+        assert!(!is_same_connection(&current, &new_ipv6));
+    }
+
+    #[test]
+    fn replace_stored_keypair_overwrites_old_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = key_paths(dir.path());
+        let old_keypair = KeyPair::new(&mut OsRng);
+        nym_pemstore::store_keypair(&old_keypair, &paths).unwrap();
+
+        let new_keypair = KeyPair::new(&mut OsRng);
+        replace_stored_keypair(&new_keypair, &paths).unwrap();
+
+        let stored: KeyPair = nym_pemstore::load_keypair(&paths).unwrap();
+        assert_eq!(
+            stored.private_key().to_bytes(),
+            new_keypair.private_key().to_bytes()
+        );
+        assert_eq!(
+            stored.public_key().to_bytes(),
+            new_keypair.public_key().to_bytes()
+        );
+        assert!(!staged_path(&paths.private_key_path).exists());
+        assert!(!staged_path(&paths.public_key_path).exists());
+    }
+
+    #[test]
+    fn replace_stored_keypair_without_old_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = key_paths(dir.path());
+
+        let keypair = KeyPair::new(&mut OsRng);
+        replace_stored_keypair(&keypair, &paths).unwrap();
+
+        let stored: KeyPair = nym_pemstore::load_keypair(&paths).unwrap();
+        assert_eq!(
+            stored.public_key().to_bytes(),
+            keypair.public_key().to_bytes()
+        );
+    }
+
+    #[test]
+    fn failed_staging_keeps_old_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = key_paths(dir.path());
+        let old_keypair = KeyPair::new(&mut OsRng);
+        nym_pemstore::store_keypair(&old_keypair, &paths).unwrap();
+        // A directory in place of the staged file makes writing it fail
+        fs::create_dir(staged_path(&paths.private_key_path)).unwrap();
+
+        assert!(replace_stored_keypair(&KeyPair::new(&mut OsRng), &paths).is_err());
+
+        let stored: KeyPair = nym_pemstore::load_keypair(&paths).unwrap();
+        assert_eq!(
+            stored.private_key().to_bytes(),
+            old_keypair.private_key().to_bytes()
+        );
+    }
+}
@@ -36,6 +36,9 @@ pub enum Error {
     #[error("failed to set UAPI config (code: {})", _0)]
     SetUapiConfig(i64),
 
+    #[error("failed to get UAPI config")]
+    GetUapiConfig,
+
     #[error("failed to obtain tunnel socket fd")]
     ObtainSocketFd,
 }
@@ -54,6 +57,8 @@ pub struct PeerConfig {
     pub preshared_key: Option<PresharedKey>,
     pub endpoint: SocketAddr,
     pub allowed_ips: Vec<IpNetwork>,
+    /// Interval in seconds between keepalive packets sent to the peer, `None` to disable.
+    pub persistent_keepalive: Option<u16>,
 }
 
 impl PeerConfig {
@@ -65,6 +70,13 @@ impl PeerConfig {
 
         config_builder.add("endpoint", self.endpoint.to_string().as_str());
 
+        if let Some(persistent_keepalive) = self.persistent_keepalive {
+            config_builder.add(
+                "persistent_keepalive_interval",
+                persistent_keepalive.to_string().as_str(),
+            );
+        }
+
         if !self.allowed_ips.is_empty() {
             config_builder.add("replace_allowed_ips", "true");
         }
@@ -85,6 +97,7 @@ impl fmt::Debug for PeerConfig {
             )
             .field("endpoint", &self.endpoint)
             .field("allowed_ips", &self.allowed_ips)
+            .field("persistent_keepalive", &self.persistent_keepalive)
             .finish()
     }
 }
@@ -122,6 +135,10 @@ impl PrivateKey {
         self.0.to_bytes()
     }
 
+    pub fn to_base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(self.to_bytes())
+    }
+
     pub fn public_key(&self) -> PublicKey {
         PublicKey::from(&self.0)
     }
@@ -195,3 +212,34 @@ impl From<[u8; 32]> for PresharedKey {
         PresharedKey(key)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer_config(persistent_keepalive: Option<u16>) -> PeerConfig {
+        PeerConfig {
+            public_key: PublicKey::from([1u8; 32]),
+            preshared_key: None,
+            endpoint: "127.0.0.1:51820".parse().unwrap(),
+            allowed_ips: vec![],
+            persistent_keepalive,
+        }
+    }
+
+    #[test]
+    fn persistent_keepalive_is_written_to_uapi_config() {
+        let mut config_builder = UapiConfigBuilder::new();
+        peer_config(Some(25)).append_to(&mut config_builder);
+        let config = String::from_utf8(config_builder.into_bytes()).unwrap();
+        assert!(config.contains("persistent_keepalive_interval=25\n"));
+    }
+
+    #[test]
+    fn persistent_keepalive_is_off_by_default() {
+        let mut config_builder = UapiConfigBuilder::new();
+        peer_config(None).append_to(&mut config_builder);
+        let config = String::from_utf8(config_builder.into_bytes()).unwrap();
+        assert!(!config.contains("persistent_keepalive_interval"));
+    }
+}
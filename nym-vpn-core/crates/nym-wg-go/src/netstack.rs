@@ -92,6 +92,22 @@ impl Tunnel {
         }
     }
 
+    /// Replace the private key of the live interface, keeping its peers. The peers establish a
+    /// new session once the next handshake completes.
+    pub fn set_private_key(&mut self, private_key: &PrivateKey) -> Result<()> {
+        let mut config_builder = UapiConfigBuilder::new();
+        config_builder.add("private_key", private_key.to_bytes().as_ref());
+        let settings =
+            CString::new(config_builder.into_bytes()).map_err(|_| Error::ConfigContainsNulByte)?;
+        let ret_code = unsafe { wgNetSetConfig(self.handle, settings.as_ptr()) };
+
+        if ret_code == 0 {
+            Ok(())
+        } else {
+            Err(Error::SetUapiConfig(ret_code))
+        }
+    }
+
     /// Update the endpoints of peers matched by public key.
     pub fn update_peers(&mut self, peer_updates: &[PeerEndpointUpdate]) -> Result<()> {
         let mut config_builder = UapiConfigBuilder::new();
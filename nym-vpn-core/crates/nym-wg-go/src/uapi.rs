@@ -1,7 +1,10 @@
 // Copyright 2024 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    time::{Duration, SystemTime},
+};
 
 #[derive(Default)]
 pub struct UapiConfigBuilder {
@@ -53,9 +56,69 @@ impl<'a> Value<'a> {
     }
 }
 
+/// Returns the most recent handshake with any of the peers listed in the output of a UAPI get
+/// operation, `None` if no handshake completed yet.
+pub fn last_handshake_time(config: &str) -> Option<SystemTime> {
+    let mut last_handshake = None;
+    let mut secs = 0;
+
+    for line in config.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match key {
+            "last_handshake_time_sec" => secs = value.parse().unwrap_or(0),
+            // Listed right after the seconds, zero seconds meaning no handshake yet.
+            "last_handshake_time_nsec" if secs > 0 => {
+                let nanos = value.parse().unwrap_or(0);
+                let handshake = SystemTime::UNIX_EPOCH + Duration::new(secs, nanos);
+                last_handshake = last_handshake.max(Some(handshake));
+                secs = 0;
+            }
+            _ => {}
+        }
+    }
+
+    last_handshake
+}
+
+/// Bytes received and sent, including handshakes and keepalives.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TransferStats {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+impl TransferStats {
+    pub fn total(&self) -> u64 {
+        self.rx_bytes.saturating_add(self.tx_bytes)
+    }
+}
+
+/// Returns the bytes received and sent across all the peers listed in the output of a UAPI get
+/// operation.
+pub fn transfer_stats(config: &str) -> TransferStats {
+    let mut stats = TransferStats::default();
+    for (key, value) in config.lines().filter_map(|line| line.split_once('=')) {
+        let counter = match key {
+            "rx_bytes" => &mut stats.rx_bytes,
+            "tx_bytes" => &mut stats.tx_bytes,
+            _ => continue,
+        };
+        *counter = counter.saturating_add(value.parse().unwrap_or(0));
+    }
+    stats
+}
+
+/// Returns the number of bytes received and sent across all the peers listed in the output of a
+/// UAPI get operation, including handshakes and keepalives.
+pub fn transferred_bytes(config: &str) -> u64 {
+    transfer_stats(config).total()
+}
+
 #[cfg(test)]
 pub mod tests {
-    use super::UapiConfigBuilder;
+    use super::*;
 
     #[test]
     fn test_encode_string() {
@@ -70,4 +133,40 @@ pub mod tests {
         config_builder.add("key", "bytes".as_bytes());
         assert_eq!(config_builder.into_bytes(), b"key=6279746573\n\n");
     }
+
+    #[test]
+    fn test_last_handshake_time() {
+        let config = "public_key=01\nlast_handshake_time_sec=0\nlast_handshake_time_nsec=0\n\
+                      public_key=02\nlast_handshake_time_sec=1700000000\n\
+                      last_handshake_time_nsec=500\n";
+        assert_eq!(
+            last_handshake_time(config),
+            Some(SystemTime::UNIX_EPOCH + Duration::new(1_700_000_000, 500))
+        );
+    }
+
+    #[test]
+    fn test_no_handshake_yet() {
+        let config = "public_key=01\nlast_handshake_time_sec=0\nlast_handshake_time_nsec=0\n";
+        assert_eq!(last_handshake_time(config), None);
+    }
+
+    #[test]
+    fn test_transferred_bytes() {
+        let config = "public_key=01
+rx_bytes=1024
+tx_bytes=512
+                      public_key=02
+rx_bytes=148
+tx_bytes=92
+";
+        assert_eq!(transferred_bytes(config), 1776);
+        assert_eq!(
+            transfer_stats(config),
+            TransferStats {
+                rx_bytes: 1172,
+                tx_bytes: 604
+            }
+        );
+    }
 }
@@ -4,13 +4,14 @@
 #[cfg(unix)]
 use std::os::fd::{IntoRawFd, OwnedFd, RawFd};
 use std::{
-    ffi::{c_char, c_void, CString},
+    ffi::{c_char, c_void, CStr, CString},
     fmt,
+    time::SystemTime,
 };
 
 use super::{
-    uapi::UapiConfigBuilder, Error, LoggingCallback, PeerConfig, PeerEndpointUpdate, PrivateKey,
-    Result,
+    uapi::{self, UapiConfigBuilder},
+    Error, LoggingCallback, PeerConfig, PeerEndpointUpdate, PrivateKey, PublicKey, Result,
 };
 
 /// Classic WireGuard interface configuration.
@@ -123,6 +124,22 @@ impl Tunnel {
         unsafe { wgBumpSockets(self.handle) }
     }
 
+    /// Replace the private key of the live interface, keeping its peers. The peers establish a
+    /// new session once the next handshake completes.
+    pub fn set_private_key(&mut self, private_key: &PrivateKey) -> Result<()> {
+        let mut config_builder = UapiConfigBuilder::new();
+        config_builder.add("private_key", private_key.to_bytes().as_ref());
+        let settings =
+            CString::new(config_builder.into_bytes()).map_err(|_| Error::ConfigContainsNulByte)?;
+        let ret_code = unsafe { wgSetConfig(self.handle, settings.as_ptr()) };
+
+        if ret_code == 0 {
+            Ok(())
+        } else {
+            Err(Error::SetUapiConfig(i64::from(ret_code)))
+        }
+    }
+
     /// Update the endpoints of peers matched by public key.
     pub fn update_peers(&mut self, peer_updates: &[PeerEndpointUpdate]) -> Result<()> {
         let mut config_builder = UapiConfigBuilder::new();
@@ -140,6 +157,61 @@ impl Tunnel {
         }
     }
 
+    /// Set the persistent keepalive interval of the peer matching the public key, `None` to
+    /// disable it. Turning it on sends a keepalive right away, which starts a handshake if there
+    /// is no session.
+    pub fn set_persistent_keepalive(
+        &mut self,
+        public_key: &PublicKey,
+        interval: Option<u16>,
+    ) -> Result<()> {
+        let mut config_builder = UapiConfigBuilder::new();
+        config_builder.add("public_key", public_key.as_bytes().as_ref());
+        config_builder.add(
+            "persistent_keepalive_interval",
+            interval.unwrap_or(0).to_string().as_str(),
+        );
+        let settings =
+            CString::new(config_builder.into_bytes()).map_err(|_| Error::ConfigContainsNulByte)?;
+        let ret_code = unsafe { wgSetConfig(self.handle, settings.as_ptr()) };
+
+        if ret_code == 0 {
+            Ok(())
+        } else {
+            Err(Error::SetUapiConfig(i64::from(ret_code)))
+        }
+    }
+
+    /// Returns the most recent handshake with any of the peers, `None` if none completed yet.
+    pub fn last_handshake(&self) -> Result<Option<SystemTime>> {
+        Ok(uapi::last_handshake_time(&self.get_config()?))
+    }
+
+    /// Returns the number of bytes received and sent across all the peers since the tunnel was
+    /// started.
+    pub fn transferred_bytes(&self) -> Result<u64> {
+        Ok(uapi::transferred_bytes(&self.get_config()?))
+    }
+
+    /// Returns the bytes received and sent across all the peers since the tunnel was started.
+    pub fn transfer_stats(&self) -> Result<uapi::TransferStats> {
+        Ok(uapi::transfer_stats(&self.get_config()?))
+    }
+
+    fn get_config(&self) -> Result<String> {
+        let ptr = unsafe { wgGetConfig(self.handle) };
+        if ptr.is_null() {
+            return Err(Error::GetUapiConfig);
+        }
+
+        let config = unsafe { CStr::from_ptr(ptr) }
+            .to_string_lossy()
+            .into_owned();
+        unsafe { wgFreePtr(ptr.cast()) };
+
+        Ok(config)
+    }
+
     fn stop_inner(&mut self) {
         if self.handle >= 0 {
             unsafe { wgTurnOff(self.handle) };
@@ -169,14 +241,12 @@ extern "C" {
     fn wgTurnOff(handle: i32);
 
     // Returns the config of the WireGuard interface.
-    #[allow(unused)]
     fn wgGetConfig(handle: i32) -> *mut c_char;
 
     // Sets the config of the WireGuard interface.
     fn wgSetConfig(handle: i32, settings: *const c_char) -> i32;
 
     // Frees a pointer allocated by the go runtime - useful to free return value of wgGetConfig
-    #[allow(unused)]
     fn wgFreePtr(ptr: *mut c_void);
 
     // Re-attach wireguard-go to the tunnel interface.